@@ -0,0 +1,283 @@
+// Pulsiora agent: registers with a Pulsiora server, long-polls it for jobs,
+// runs them on this machine with pulsiora-runner and streams their events and
+// results back.
+
+use clap::Parser;
+use pulsiora_core::{
+    AgentJob, AgentOutcome, AgentRegistered, AgentRegistration, AgentReport, AgentReportAck, ExecutionEvent,
+    PulsioraError,
+};
+use pulsiora_runner::{ActionCache, PipelineExecutor, SandboxBackend, SandboxTool};
+use reqwest::{Client, StatusCode};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How often a running job is reported when it has no new events, well
+/// inside the server's agent timeout
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Wait before retrying after the server couldn't be reached
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Attempts at sending a job's result before giving up on it
+const RESULT_ATTEMPTS: u32 = 5;
+
+#[derive(Parser)]
+#[command(name = "pulsiora-agent")]
+#[command(about = "Run Pulsiora jobs for a server on this machine", long_about = None)]
+struct Args {
+    /// Server URL
+    #[arg(long, env = "PULSIORA_SERVER", default_value = "http://localhost:3000")]
+    server: String,
+
+    /// The server's agent token (PULSIORA_AGENT_TOKEN on the server)
+    #[arg(long, env = "PULSIORA_AGENT_TOKEN", hide_env_values = true)]
+    token: String,
+
+    /// Name shown in the server's agent list [default: the host name]
+    #[arg(long, env = "PULSIORA_AGENT_NAME")]
+    name: Option<String>,
+
+    /// Label describing this agent, e.g. `gpu`; can be repeated
+    #[arg(long = "label")]
+    labels: Vec<String>,
+
+    /// Directory for workspaces, artifacts and cached actions
+    #[arg(long, env = "PULSIORA_AGENT_DIR", default_value = "agent-data")]
+    data_dir: PathBuf,
+
+    /// Sandbox for repositories whose steps are sandboxed: `bubblewrap` or `firejail`
+    #[arg(long, env = "PULSIORA_SANDBOX_TOOL", default_value = "bubblewrap", value_parser = parse_sandbox_tool)]
+    sandbox_tool: SandboxTool,
+}
+
+fn parse_sandbox_tool(name: &str) -> Result<SandboxTool, String> {
+    SandboxTool::parse(name).ok_or_else(|| format!("expected `bubblewrap` or `firejail`, got {:?}", name))
+}
+
+/// What a poll for the next job returned
+enum Poll {
+    Job(Box<AgentJob>),
+    Idle,
+    /// The server doesn't know this agent, e.g. because it restarted
+    Unregistered,
+}
+
+struct Agent {
+    client: Client,
+    server: String,
+    token: String,
+    registration: AgentRegistration,
+    data_dir: PathBuf,
+    sandbox_tool: SandboxTool,
+}
+
+impl Agent {
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.server.trim_end_matches('/'), path)
+    }
+
+    async fn register(&self) -> anyhow::Result<AgentRegistered> {
+        let response = self
+            .client
+            .post(self.url("/api/v1/agents/register"))
+            .bearer_auth(&self.token)
+            .json(&self.registration)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    async fn next_job(&self, session: &AgentRegistered) -> anyhow::Result<Poll> {
+        let response = self
+            .client
+            .get(self.url("/api/v1/agents/jobs/next"))
+            .bearer_auth(&session.token)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(Poll::Idle),
+            StatusCode::UNAUTHORIZED => Ok(Poll::Unregistered),
+            _ => Ok(Poll::Job(Box::new(response.error_for_status()?.json().await?))),
+        }
+    }
+
+    /// Executor for a job, or why this agent can't run it
+    fn executor_for(&self, job: &AgentJob) -> pulsiora_core::Result<PipelineExecutor> {
+        let mut executor = PipelineExecutor::new()
+            .with_limits(job.limits.clone())
+            .with_env(job.env.clone())
+            .with_secrets(job.secrets.clone())
+            .with_queued_at(job.queued_at)
+            .with_workspace_root(self.data_dir.join("workspaces"))
+            .with_artifact_dir(self.data_dir.join("artifacts"))
+            .with_action_cache(ActionCache::new(self.data_dir.join("actions")));
+        if let Some(policy) = &job.sandbox {
+            let sandbox = SandboxBackend::new(self.sandbox_tool).with_policy(policy.clone());
+            // Running the steps unsandboxed instead would defeat the policy
+            if !sandbox.is_available() {
+                return Err(PulsioraError::BackendUnavailable(format!(
+                    "The repository's steps are sandboxed, but `{}` isn't on this agent's PATH",
+                    self.sandbox_tool.program()
+                )));
+            }
+            executor = executor.with_backend(sandbox);
+        }
+        Ok(executor)
+    }
+
+    async fn run(&self, session: &AgentRegistered, job: AgentJob) {
+        info!(execution_id = %job.execution_id, pipeline = %job.pipeline.name, "Running job");
+        let execution_id = job.execution_id;
+        let outcome = match self.executor_for(&job) {
+            Ok(executor) => self.execute(session, job, executor).await,
+            Err(error) => {
+                warn!(execution_id = %execution_id, "Refusing job: {}", error);
+                AgentOutcome::errored(&error)
+            }
+        };
+        let url = self.url(&format!("/api/v1/agents/jobs/{}/result", execution_id));
+        for attempt in 1..=RESULT_ATTEMPTS {
+            match self.client.post(&url).bearer_auth(&session.token).json(&outcome).send().await {
+                Ok(response) if response.status().is_success() => return,
+                Ok(response) if response.status() == StatusCode::CONFLICT => {
+                    warn!(execution_id = %execution_id, "Server no longer expects this job's result");
+                    return;
+                }
+                Ok(response) => warn!(attempt, "Sending job result failed: {}", response.status()),
+                Err(e) => warn!(attempt, "Sending job result failed: {}", e),
+            }
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+        warn!(execution_id = %execution_id, "Giving up on sending job result");
+    }
+
+    /// Run a job, reporting its events as it goes
+    async fn execute(&self, session: &AgentRegistered, job: AgentJob, executor: PipelineExecutor) -> AgentOutcome {
+        let (events, received) = mpsc::channel(100);
+        let cancel = CancellationToken::new();
+        let reporter = tokio::spawn(report_events(
+            self.client.clone(),
+            self.url(&format!("/api/v1/agents/jobs/{}/report", job.execution_id)),
+            session.token.clone(),
+            received,
+            cancel.clone(),
+        ));
+        let result = executor
+            .resume_with_events(job.execution_id, &job.pipeline, &job.git_event, job.completed_steps, events, cancel)
+            .await;
+        let _ = reporter.await;
+
+        match result {
+            Ok(execution) => {
+                info!(execution_id = %job.execution_id, status = ?execution.status, "Job finished");
+                AgentOutcome::Finished {
+                    execution: Box::new(execution),
+                }
+            }
+            Err(e) => {
+                warn!(execution_id = %job.execution_id, "Job failed: {}", e);
+                AgentOutcome::errored(&e)
+            }
+        }
+    }
+}
+
+/// Forward a job's events to the server in batches until the executor drops
+/// its sender, sending an empty report every `HEARTBEAT_INTERVAL` so the
+/// server knows the job is still running. Fires `cancel` when the server
+/// answers that the job was cancelled.
+async fn report_events(
+    client: Client,
+    url: String,
+    token: String,
+    mut received: mpsc::Receiver<ExecutionEvent>,
+    cancel: CancellationToken,
+) {
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let mut events = Vec::new();
+        tokio::select! {
+            event = received.recv() => match event {
+                Some(event) => {
+                    events.push(event);
+                    while let Ok(event) = received.try_recv() {
+                        events.push(event);
+                    }
+                }
+                None => return,
+            },
+            _ = heartbeat.tick() => {}
+        }
+        match client.post(&url).bearer_auth(&token).json(&AgentReport { events }).send().await {
+            Ok(response) if response.status() == StatusCode::CONFLICT => {
+                warn!("Server no longer expects this job; its result will be discarded")
+            }
+            Ok(response) if !response.status().is_success() => warn!("Reporting job progress failed: {}", response.status()),
+            Ok(response) => {
+                let cancelled = response.json::<AgentReportAck>().await.is_ok_and(|ack| ack.cancel);
+                if cancelled && !cancel.is_cancelled() {
+                    info!("Job was cancelled on the server, stopping it");
+                    cancel.cancel();
+                }
+            }
+            Err(e) => warn!("Reporting job progress failed: {}", e),
+        }
+    }
+}
+
+/// The machine's host name, for agents started without `--name`
+fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "pulsiora-agent".to_string())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let args = Args::parse();
+    let agent = Agent {
+        client: Client::new(),
+        server: args.server,
+        token: args.token,
+        registration: AgentRegistration::for_host(args.name.unwrap_or_else(host_name), args.labels),
+        data_dir: args.data_dir,
+        sandbox_tool: args.sandbox_tool,
+    };
+
+    loop {
+        let session = match agent.register().await {
+            Ok(session) => session,
+            Err(e) => {
+                warn!("Registering with {} failed: {}", agent.server, e);
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+        info!(agent_id = %session.agent_id, "Registered with {} as {}", agent.server, agent.registration.name);
+
+        loop {
+            match agent.next_job(&session).await {
+                Ok(Poll::Job(job)) => agent.run(&session, *job).await,
+                Ok(Poll::Idle) => {}
+                Ok(Poll::Unregistered) => {
+                    warn!("Server no longer knows this agent, registering again");
+                    break;
+                }
+                Err(e) => {
+                    warn!("Polling for jobs failed: {}", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+}
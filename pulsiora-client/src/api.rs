@@ -0,0 +1,62 @@
+// Picks the server API version to talk to. Newer servers list their versions
+// at `/api/versions`; servers without it only speak v1.
+
+use reqwest::Client;
+use serde::Deserialize;
+
+/// API versions this client understands, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "v1" => Some(ApiVersion::V1),
+            "v2" => Some(ApiVersion::V2),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiVersions {
+    versions: Vec<String>,
+}
+
+/// Newest version both sides support; versions this client doesn't know are ignored
+pub fn pick_version(advertised: &[String]) -> ApiVersion {
+    advertised
+        .iter()
+        .filter_map(|v| ApiVersion::parse(v))
+        .max()
+        .unwrap_or(ApiVersion::V1)
+}
+
+/// Ask the server which API versions it serves, falling back to v1
+pub async fn negotiate(client: &Client, server: &str) -> ApiVersion {
+    let response = match client.get(format!("{}/api/versions", server)).send().await {
+        Ok(response) if response.status().is_success() => response,
+        _ => return ApiVersion::V1,
+    };
+    match response.json::<ApiVersions>().await {
+        Ok(versions) => pick_version(&versions.versions),
+        Err(_) => ApiVersion::V1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_version() {
+        let versions = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(pick_version(&versions(&["v1", "v2"])), ApiVersion::V2);
+        assert_eq!(pick_version(&versions(&["v1"])), ApiVersion::V1);
+        assert_eq!(pick_version(&versions(&["v1", "v2", "v7"])), ApiVersion::V2);
+        assert_eq!(pick_version(&[]), ApiVersion::V1);
+    }
+}
@@ -0,0 +1,121 @@
+// Contribution-style calendar of pipeline runs for `pulse stats --calendar`.
+
+use chrono::{Datelike, Days, NaiveDate};
+use pulsiora_core::ActivityBucket;
+use std::collections::HashMap;
+
+/// Cell glyphs from no runs to the busiest day
+const LEVELS: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Row labels, Monday first; only every other day is named to keep it readable
+const WEEKDAYS: [&str; 7] = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+
+/// Glyph for a day with `count` runs, scaled against the busiest day
+fn level(count: usize, max: usize) -> char {
+    if count == 0 || max == 0 {
+        return LEVELS[0];
+    }
+    LEVELS[(count * 4).div_ceil(max).clamp(1, 4)]
+}
+
+/// Render daily buckets as a grid of `weeks` columns ending with the week of `today`
+pub fn render_calendar(buckets: &[ActivityBucket], today: NaiveDate, weeks: usize) -> String {
+    let weeks = weeks.max(1);
+    let counts: HashMap<NaiveDate, usize> = buckets.iter().map(|b| (b.date, b.total)).collect();
+    let this_monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+    let first_monday = this_monday - Days::new(7 * (weeks as u64 - 1));
+    let mondays: Vec<NaiveDate> = (0..weeks).map(|w| first_monday + Days::new(7 * w as u64)).collect();
+    let max = counts
+        .iter()
+        .filter(|(date, _)| **date >= first_monday && **date <= today)
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0);
+
+    // Month names above the first column of each month; each cell is two characters wide
+    let mut header = vec![' '; 4 + weeks * 2];
+    let mut previous_month = None;
+    for (column, monday) in mondays.iter().enumerate() {
+        if previous_month != Some(monday.month()) {
+            let name = monday.format("%b").to_string();
+            let start = 4 + column * 2;
+            if start + name.len() <= header.len() {
+                header.splice(start..start + name.len(), name.chars());
+            }
+            previous_month = Some(monday.month());
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(header.iter().collect::<String>().trim_end());
+    out.push('\n');
+    for (weekday, label) in WEEKDAYS.iter().enumerate() {
+        let mut row = format!("{:<4}", label);
+        for monday in &mondays {
+            let day = *monday + Days::new(weekday as u64);
+            if day > today {
+                break;
+            }
+            row.push(level(counts.get(&day).copied().unwrap_or(0), max));
+            row.push(' ');
+        }
+        out.push_str(row.trim_end());
+        out.push('\n');
+    }
+
+    let in_range = buckets.iter().filter(|b| b.date >= first_monday && b.date <= today);
+    let (runs, failed) = in_range.fold((0, 0), |(runs, failed), b| (runs + b.total, failed + b.failed));
+    out.push_str(&format!(
+        "\n    Less {} More    {} run(s), {} failed in the last {} week(s)\n",
+        LEVELS.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "),
+        runs,
+        failed,
+        weeks
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(date: NaiveDate, total: usize, failed: usize) -> ActivityBucket {
+        ActivityBucket {
+            date,
+            total,
+            success: total - failed,
+            failed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_level_scales_against_busiest_day() {
+        assert_eq!(level(0, 8), '·');
+        assert_eq!(level(1, 8), '░');
+        assert_eq!(level(4, 8), '▒');
+        assert_eq!(level(8, 8), '█');
+    }
+
+    #[test]
+    fn test_render_calendar() {
+        // 2024-05-16 is a Thursday
+        let today = NaiveDate::from_ymd_opt(2024, 5, 16).unwrap();
+        let buckets = vec![
+            bucket(NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(), 4, 1),
+            bucket(NaiveDate::from_ymd_opt(2024, 5, 15).unwrap(), 1, 0),
+            // Outside the two-week window
+            bucket(NaiveDate::from_ymd_opt(2024, 4, 1).unwrap(), 9, 9),
+        ];
+
+        let calendar = render_calendar(&buckets, today, 2);
+        let lines: Vec<&str> = calendar.lines().collect();
+        assert_eq!(lines[0], "    May");
+        assert_eq!(lines[1], "Mon █ ·");
+        assert_eq!(lines[3], "Wed · ░");
+        assert_eq!(lines[4], "    · ·");
+        // Friday of the current week is still in the future
+        assert_eq!(lines[5], "Fri ·");
+        assert!(calendar.contains("5 run(s), 1 failed in the last 2 week(s)"));
+    }
+}
@@ -0,0 +1,115 @@
+// Live tailing for `pulse pipeline logs --follow`: step boundaries as the
+// server's live updates report them, and the exit code the final status maps
+// to.
+
+use crate::timeline::format_ms;
+use chrono::{DateTime, Utc};
+use pulsiora_core::{PipelineStatus, StepResult, StepStatus};
+use std::collections::HashSet;
+
+/// The live updates endpoint of `server`, e.g. `ws://localhost:3000/api/v1/ws`
+pub fn websocket_url(server: &str) -> String {
+    let server = server.trim_end_matches('/');
+    let server = match server.strip_prefix("http") {
+        Some(rest) if rest.starts_with("s://") || rest.starts_with("://") => format!("ws{}", rest),
+        _ => server.to_string(),
+    };
+    format!("{}/api/v1/ws", server)
+}
+
+/// Whether an execution in `status` is done, so there's nothing left to follow.
+/// Interrupted executions only go on once resumed.
+pub fn is_final(status: PipelineStatus) -> bool {
+    !matches!(status, PipelineStatus::Pending | PipelineStatus::Running)
+}
+
+/// Exit code for an execution that ended in `status`, as `pulse run` exits
+pub fn exit_code(status: PipelineStatus) -> i32 {
+    match status {
+        PipelineStatus::Success | PipelineStatus::Skipped => 0,
+        PipelineStatus::Cancelled => 130,
+        _ => 1,
+    }
+}
+
+pub fn step_started(step_name: &str) -> String {
+    format!("▶ {}", step_name)
+}
+
+/// Steps already reported as finished, so a step shows up once whether it's
+/// seen in the execution fetched when following starts or in an update
+#[derive(Default)]
+pub struct FinishedSteps(HashSet<(String, DateTime<Utc>)>);
+
+impl FinishedSteps {
+    /// The line reporting `result`, unless it was reported already
+    pub fn finish(&mut self, result: &StepResult) -> Option<String> {
+        if !self.0.insert((result.step_name.clone(), result.started_at)) {
+            return None;
+        }
+        let detail = match result.exit_code {
+            Some(code) if result.status == StepStatus::Failed => format!(" (exit code {})", code),
+            _ => String::new(),
+        };
+        Some(match result.status {
+            StepStatus::Success => format!("✓ {} {}", result.step_name, format_ms(result.duration_ms)),
+            StepStatus::Failed => format!("✗ {} {}{}", result.step_name, format_ms(result.duration_ms), detail),
+            StepStatus::Skipped => format!("- {} skipped", result.step_name),
+            StepStatus::Cancelled => format!("■ {} cancelled", result.step_name),
+            StepStatus::Pending | StepStatus::Running => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(name: &str, status: StepStatus, exit_code: Option<i32>) -> StepResult {
+        StepResult {
+            step_name: name.to_string(),
+            status,
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code,
+            duration_ms: 120,
+            started_at: Utc::now(),
+            completed_at: Some(Utc::now()),
+            encoding: None,
+            replacement_chars: 0,
+            stdout_bytes: 0,
+            stderr_bytes: 0,
+            log_ref: None,
+            hooks: Vec::new(),
+            annotations: Vec::new(),
+            coverage: None,
+            resources: None,
+        }
+    }
+
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(websocket_url("http://localhost:3000"), "ws://localhost:3000/api/v1/ws");
+        assert_eq!(websocket_url("https://ci.example.com/"), "wss://ci.example.com/api/v1/ws");
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(exit_code(PipelineStatus::Success), 0);
+        assert_eq!(exit_code(PipelineStatus::Failed), 1);
+        assert_eq!(exit_code(PipelineStatus::Cancelled), 130);
+        assert!(!is_final(PipelineStatus::Running));
+        assert!(is_final(PipelineStatus::Interrupted));
+    }
+
+    #[test]
+    fn test_finished_steps_are_reported_once() {
+        let mut finished = FinishedSteps::default();
+        let build = result("build", StepStatus::Success, Some(0));
+        assert_eq!(finished.finish(&build).as_deref(), Some("✓ build 120ms"));
+        assert_eq!(finished.finish(&build), None);
+        let test = result("test", StepStatus::Failed, Some(2));
+        assert_eq!(finished.finish(&test).as_deref(), Some("✗ test 120ms (exit code 2)"));
+        assert_eq!(finished.finish(&result("deploy", StepStatus::Skipped, None)).as_deref(), Some("- deploy skipped"));
+    }
+}
@@ -0,0 +1,155 @@
+// Runs of `pulse run` on this machine, kept in a SQLite database in the
+// CLI's home so `pulse list --local` and `pulse pipeline logs --local` work
+// without a server.
+
+use pulsiora_core::PipelineExecution;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS executions (
+    id TEXT PRIMARY KEY,
+    pipeline_name TEXT NOT NULL,
+    repository TEXT NOT NULL,
+    status TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    execution TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS executions_started_at ON executions (started_at)";
+
+/// `history.db` in the CLI's home, if it has one
+pub fn default_path() -> Option<PathBuf> {
+    crate::prune::pulse_home().map(|home| home.join("history.db"))
+}
+
+/// Local executions, stored whole as JSON with a few columns to sort by
+pub struct RunHistory {
+    pool: SqlitePool,
+}
+
+impl RunHistory {
+    /// Open the database at `path`, creating it if needed
+    pub async fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+        let pool = SqlitePoolOptions::new().max_connections(1).connect_with(options).await?;
+        sqlx::raw_sql(SCHEMA).execute(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    /// Save an execution, replacing an earlier copy with the same ID
+    pub async fn record(&self, execution: &PipelineExecution) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO executions (id, pipeline_name, repository, status, started_at, execution)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(execution.id.to_string())
+        .bind(&execution.pipeline_name)
+        .bind(&execution.repository.full_name)
+        .bind(format!("{:?}", execution.status))
+        .bind(execution.started_at.timestamp_micros())
+        .bind(serde_json::to_string(execution)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Executions, newest first, optionally only those of pipelines with `label`
+    pub async fn list(&self, label: Option<&str>) -> anyhow::Result<Vec<PipelineExecution>> {
+        let rows = sqlx::query("SELECT execution FROM executions ORDER BY started_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut executions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let execution: PipelineExecution = serde_json::from_str(row.try_get("execution")?)?;
+            if label.is_none_or(|label| execution.pipeline_labels.iter().any(|l| l == label)) {
+                executions.push(execution);
+            }
+        }
+        Ok(executions)
+    }
+
+    pub async fn get(&self, id: Uuid) -> anyhow::Result<Option<PipelineExecution>> {
+        let row = sqlx::query("SELECT execution FROM executions WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => Ok(Some(serde_json::from_str(row.try_get("execution")?)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use pulsiora_core::{ExecutionMetrics, GitEvent, GitEventType, PipelineStatus, Repository};
+
+    fn execution(name: &str, labels: &[&str], minutes_ago: i64) -> PipelineExecution {
+        let repository = Repository {
+            owner: "local".to_string(),
+            name: "repo".to_string(),
+            full_name: "local/repo".to_string(),
+            clone_url: "local/repo".to_string(),
+            default_branch: "main".to_string(),
+        };
+        PipelineExecution {
+            id: Uuid::new_v4(),
+            pipeline_name: name.to_string(),
+            pipeline_version: "1.0".to_string(),
+            pipeline_labels: labels.iter().map(|l| l.to_string()).collect(),
+            environment: None,
+            repository: repository.clone(),
+            git_event: GitEvent {
+                event_type: GitEventType::Push,
+                repository,
+                branch: Some("main".to_string()),
+                tag: None,
+                release_name: None,
+                pull_request: None,
+                commit_sha: None,
+                commit_message: None,
+                sender: "manual".to_string(),
+                inputs: Default::default(),
+            },
+            status: PipelineStatus::Success,
+            step_results: Vec::new(),
+            phases: Vec::new(),
+            limit_exceeded: None,
+            coverage: None,
+            metrics: ExecutionMetrics::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
+            started_at: Utc::now() - Duration::minutes(minutes_ago),
+            completed_at: Some(Utc::now()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_history() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-history-{}", Uuid::new_v4()));
+        let history = RunHistory::open(&dir.join("history.db")).await.unwrap();
+        let older = execution("build", &["deploy"], 10);
+        let mut newer = execution("lint", &[], 1);
+        history.record(&older).await.unwrap();
+        history.record(&newer).await.unwrap();
+
+        newer.status = PipelineStatus::Failed;
+        history.record(&newer).await.unwrap();
+        let names: Vec<_> = history.list(None).await.unwrap().into_iter().map(|e| e.pipeline_name).collect();
+        assert_eq!(names, ["lint", "build"]);
+        let labelled = history.list(Some("deploy")).await.unwrap();
+        assert_eq!(labelled.len(), 1);
+        assert_eq!(labelled[0].id, older.id);
+
+        let found = history.get(newer.id).await.unwrap().unwrap();
+        assert_eq!(found.status, PipelineStatus::Failed);
+        assert!(history.get(Uuid::new_v4()).await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,12 +1,30 @@
 use clap::{Parser, Subcommand};
-use pulsiora_core::PipelineExecution;
-use pulsiora_parser::parse_pulsefile;
-use pulsiora_runner::PipelineExecutor;
+use pulsiora_core::{ActivityBucket, Page, PipelineExecution};
+use pulsiora_parser::{import_gitlab_ci, parse_pulsefile_with_warnings};
+use pulsiora_runner::{checkout_revision, ActionCache, Bisect, BisectState, BisectVerdict, ExecutionPlan, PipelineExecutor, ReplayBundle};
 use reqwest::Client;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::fs;
 use std::path::Path;
 use std::process;
+use tokio_util::sync::CancellationToken;
+
+mod api;
+mod calendar;
+mod follow;
+mod history;
+mod prune;
+mod settings;
+mod summary;
+mod terminal;
+mod timeline;
+
+use api::ApiVersion;
+use settings::ProjectSettings;
+
+const DEFAULT_SERVER: &str = "http://localhost:3000";
+const DEFAULT_PULSEFILE: &str = "Pulsefile";
+const DEFAULT_BRANCH: &str = "main";
 
 #[derive(Parser)]
 #[command(name = "pulse")]
@@ -15,9 +33,13 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Server URL
-    #[arg(long, default_value = "http://localhost:3000")]
-    server: String,
+    /// Server URL [default: from .pulsiora.toml, else http://localhost:3000]
+    #[arg(long)]
+    server: Option<String>,
+
+    /// API token sent with every request
+    #[arg(long, env = "PULSIORA_TOKEN", hide_env_values = true)]
+    token: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -36,28 +58,163 @@ enum Commands {
     #[command(subcommand)]
     Pipeline(PipelineCommands),
 
+    /// Convert another CI system's configuration into a Pulsefile
+    #[command(subcommand)]
+    Import(ImportCommands),
+
     /// Get pipeline execution details (deprecated: use pipeline logs)
     Status {
         /// Execution ID
         id: String,
     },
 
-    /// List all pipeline executions
-    List,
+    /// Cancel a queued or running pipeline execution
+    Cancel {
+        /// Execution ID
+        id: String,
+    },
+
+    /// Show when each phase and step of an execution ran, as a waterfall
+    Timeline {
+        /// Execution ID
+        id: String,
+
+        /// Width of the bars, in characters
+        #[arg(short, long, default_value = "50")]
+        width: usize,
+    },
+
+    /// Show the executions waiting to start, why they wait, and what each worker runs
+    Queue,
+
+    /// List all pipeline executions, newest first
+    List {
+        /// Only show executions of pipelines with this label
+        #[arg(short, long)]
+        label: Option<String>,
+
+        /// Only show executions with this status, e.g. `failed`
+        #[arg(long, conflicts_with = "local")]
+        status: Option<String>,
+
+        /// Only show executions triggered on this branch
+        #[arg(long, conflicts_with = "local")]
+        branch: Option<String>,
+
+        /// Only show executions of this repository (e.g., owner/repo or full URL)
+        #[arg(long, conflicts_with = "local")]
+        repo: Option<String>,
+
+        /// Only show executions started since this RFC 3339 time or YYYY-MM-DD date
+        #[arg(long, conflicts_with = "local")]
+        since: Option<String>,
+
+        /// Show only this page of results, from 1
+        #[arg(long, conflicts_with = "local")]
+        page: Option<usize>,
+
+        /// Executions per page [default: 50]
+        #[arg(long, conflicts_with = "local")]
+        per_page: Option<usize>,
+
+        /// List runs of `pulse run` on this machine instead of the server's
+        #[arg(long)]
+        local: bool,
+    },
     
+    /// Search step names and output across executions, e.g. for a recurring error
+    Search {
+        /// Terms a step must all hold; quote a phrase to keep it together
+        query: String,
+
+        /// Only search executions of this repository (e.g., owner/repo or full URL)
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// Show at most this many executions, newest first [default: 20]
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+
+    /// Show run activity for a repository
+    Stats {
+        /// Repository (e.g., owner/repo or full URL) [default: from .pulsiora.toml]
+        repo: Option<String>,
+
+        /// Show a day-by-day calendar heat map
+        #[arg(long)]
+        calendar: bool,
+
+        /// Show how often each step failed and how long it took, most failing first
+        #[arg(long, conflicts_with = "calendar")]
+        steps: bool,
+
+        /// Number of weeks to cover
+        #[arg(short, long, default_value = "26")]
+        weeks: usize,
+    },
+
+    /// Re-run a recorded execution locally with the same commands, env and commit
+    Replay {
+        /// Run ID (execution ID)
+        run_id: String,
+
+        /// Directory to check the recorded commit out into [default: a new temporary directory]
+        #[arg(long)]
+        workdir: Option<String>,
+
+        /// Run in the current directory instead of checking out the recorded commit
+        #[arg(long, conflicts_with = "workdir")]
+        here: bool,
+    },
+
+    /// Find the first commit where a step fails, using git bisect on the current repository
+    Bisect {
+        /// A commit where the step passes
+        #[arg(long)]
+        good: String,
+
+        /// A commit where the step fails
+        #[arg(long)]
+        bad: String,
+
+        /// Name of the step that tests each commit
+        #[arg(long)]
+        step: String,
+
+        /// Path to Pulsefile [default: from .pulsiora.toml, else Pulsefile]
+        #[arg(short, long)]
+        pulsefile: Option<String>,
+    },
+
+    /// Report and remove local workspaces, caches and replay checkouts
+    Prune {
+        /// Only remove entries not modified for this long (e.g. 12h, 7d, 2w)
+        #[arg(long)]
+        older_than: Option<String>,
+
+        /// Report what would be removed without removing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
     /// Manually execute a Pulsefile
     Run {
-        /// Path to Pulsefile
-        #[arg(short, long, default_value = "Pulsefile")]
-        pulsefile: String,
+        /// Path to Pulsefile [default: from .pulsiora.toml, else Pulsefile]
+        #[arg(short, long)]
+        pulsefile: Option<String>,
         
-        /// Repository URL (for logging purposes)
-        #[arg(short, long, default_value = "local/repo")]
-        repo_url: String,
+        /// Repository URL (for logging purposes) [default: from .pulsiora.toml, else local/repo]
+        #[arg(short, long)]
+        repo_url: Option<String>,
         
-        /// Branch name (for logging purposes)
-        #[arg(short, long, default_value = "main")]
-        branch: String,
+        /// Branch name (for logging purposes) [default: from .pulsiora.toml, else main]
+        #[arg(short, long)]
+        branch: Option<String>,
+
+        /// Print the steps that would run, with their scripts and environment, without running them
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
@@ -68,13 +225,28 @@ enum RepoCommands {
         /// Repository URL (e.g., https://github.com/owner/repo)
         repo_url: String,
         
-        /// Path to Pulsefile (defaults to ./Pulsefile)
-        #[arg(short, long, default_value = "Pulsefile")]
-        pulsefile: String,
+        /// Path to Pulsefile [default: from .pulsiora.toml, else Pulsefile]
+        #[arg(short, long)]
+        pulsefile: Option<String>,
         
         /// Repository type (github, local, or other SCM)
         #[arg(short, long, default_value = "github")]
         repo_type: String,
+
+        /// Organization whose base pipeline applies [default: the repository owner]
+        #[arg(long)]
+        org: Option<String>,
+    },
+
+    /// Replace a registered repository's Pulsefile; the old one is kept as a
+    /// previous revision
+    Update {
+        /// Repository (e.g., owner/repo or full URL) [default: from .pulsiora.toml]
+        repo: Option<String>,
+
+        /// Path to Pulsefile [default: from .pulsiora.toml, else Pulsefile]
+        #[arg(short, long)]
+        pulsefile: Option<String>,
     },
 
     /// Unregister repository
@@ -82,14 +254,64 @@ enum RepoCommands {
         /// Repository URL (e.g., https://github.com/owner/repo)
         repo_url: String,
     },
+
+    /// List the repositories you can see, with how their last run went
+    List,
+
+    /// Show or change the variables set for every step of a repository
+    Env {
+        /// Repository (e.g., owner/repo or full URL) [default: from .pulsiora.toml]
+        repo: Option<String>,
+
+        /// Set a variable, as NAME=VALUE; can be repeated
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_input)]
+        set: Vec<(String, String)>,
+
+        /// Remove a variable; can be repeated
+        #[arg(long = "unset", value_name = "NAME")]
+        unset: Vec<String>,
+    },
+
+    /// Check a repository's webhook: its secret, a signed ping through the
+    /// server's public URL, and for GitHub how GitHub has it set up
+    WebhookTest {
+        /// Repository (e.g., owner/repo or full URL) [default: from .pulsiora.toml]
+        repo: Option<String>,
+
+        /// The secret set on the webhook, to compare with the server's
+        #[arg(long)]
+        secret: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Convert a .gitlab-ci.yml
+    Gitlab {
+        /// Path to the GitLab CI file
+        #[arg(default_value = ".gitlab-ci.yml")]
+        input: String,
+
+        /// Where to write the Pulsefile
+        #[arg(short, long, default_value = "Pulsefile")]
+        output: String,
+
+        /// Pipeline name [default: name of the current directory]
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Overwrite an existing output file
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum PipelineCommands {
     /// Check recent pipeline runs for a repository
     Status {
-        /// Repository (e.g., owner/repo or full URL)
-        repo: String,
+        /// Repository (e.g., owner/repo or full URL) [default: from .pulsiora.toml]
+        repo: Option<String>,
         
         /// Number of runs to show
         #[arg(short, long, default_value = "10")]
@@ -99,13 +321,49 @@ enum PipelineCommands {
     /// Fetch logs for a specific pipeline run
     Logs {
         /// Repository (e.g., owner/repo or full URL)
-        repo: String,
+        #[arg(required_unless_present = "local")]
+        repo: Option<String>,
         
         /// Run ID (execution ID)
-        run_id: String,
+        #[arg(required_unless_present = "local")]
+        run_id: Option<String>,
+
+        /// Show a run of `pulse run` on this machine, by run ID, instead of asking the server
+        #[arg(long, value_name = "RUN_ID", conflicts_with_all = ["repo", "run_id"])]
+        local: Option<String>,
+
+        /// Stream the run's output as it happens and exit with a code matching how it ended
+        #[arg(short, long, conflicts_with = "local")]
+        follow: bool,
+    },
+
+    /// Start a repository's pipeline on the server; it needs a `manual` trigger
+    Trigger {
+        /// Repository (e.g., owner/repo or full URL) [default: from .pulsiora.toml]
+        repo: Option<String>,
+
+        /// Branch to run on [default: the repository's default branch]
+        #[arg(short, long)]
+        branch: Option<String>,
+
+        /// Commit to run instead of the branch head
+        #[arg(long)]
+        commit: Option<String>,
+
+        /// Input passed to steps as PULSE_INPUT_<NAME>, as NAME=VALUE; can be repeated
+        #[arg(long = "input", value_name = "NAME=VALUE", value_parser = parse_input)]
+        inputs: Vec<(String, String)>,
     },
 }
 
+/// Split a `--input` or `--set` argument into its name and value
+fn parse_input(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got `{}`", arg)),
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt()
@@ -113,41 +371,73 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let cli = Cli::parse();
-    let client = Client::new();
+    let client = api_client(cli.token.as_deref())?;
+    let settings = ProjectSettings::discover(&std::env::current_dir()?)?.unwrap_or_default();
+    let server = cli
+        .server
+        .or_else(|| settings.server.clone())
+        .unwrap_or_else(|| DEFAULT_SERVER.to_string());
 
     match cli.command {
         Commands::Health => {
-            let url = format!("{}/health", cli.server);
-            let response = client.get(&url).send().await?;
-            if response.status().is_success() {
-                println!("Server is healthy");
-                process::exit(0);
-            } else {
-                eprintln!("Server is unhealthy");
-                process::exit(1);
-            }
+            let healthy = check_health(&client, &server).await?;
+            process::exit(if healthy { 0 } else { 1 });
         }
         Commands::Init => {
             generate_pulsefile_template()?;
         }
         Commands::Repo(cmd) => match cmd {
-            RepoCommands::Add { repo_url, pulsefile, repo_type } => {
-                register_repo(&client, &cli.server, &repo_url, &pulsefile, &repo_type).await?;
+            RepoCommands::Add { repo_url, pulsefile, repo_type, org } => {
+                let pulsefile = resolve_pulsefile(pulsefile, &settings);
+                register_repo(&client, &server, &repo_url, &pulsefile, &repo_type, org.as_deref()).await?;
+            }
+            RepoCommands::Update { repo, pulsefile } => {
+                let repo = resolve_repo(repo, &settings)?;
+                let pulsefile = resolve_pulsefile(pulsefile, &settings);
+                update_pulsefile(&client, &server, &repo, &pulsefile).await?;
             }
             RepoCommands::Remove { repo_url } => {
-                unregister_repo(&client, &cli.server, &repo_url).await?;
+                unregister_repo(&client, &server, &repo_url).await?;
+            }
+            RepoCommands::List => {
+                list_repos(&client, &server).await?;
+            }
+            RepoCommands::Env { repo, set, unset } => {
+                let repo = resolve_repo(repo, &settings)?;
+                repo_env(&client, &server, &repo, set, unset).await?;
+            }
+            RepoCommands::WebhookTest { repo, secret } => {
+                let repo = resolve_repo(repo, &settings)?;
+                let ok = test_webhook(&client, &server, &repo, secret).await?;
+                process::exit(if ok { 0 } else { 1 });
             }
         },
         Commands::Pipeline(cmd) => match cmd {
             PipelineCommands::Status { repo, limit } => {
-                get_pipeline_status(&client, &cli.server, &repo, limit).await?;
+                let repo = resolve_repo(repo, &settings)?;
+                get_pipeline_status(&client, &server, &repo, limit).await?;
+            }
+            PipelineCommands::Logs { repo, run_id, local, follow } => match (local, repo, run_id) {
+                (Some(run_id), _, _) => show_local_logs(&run_id).await?,
+                (None, Some(repo), Some(run_id)) if follow => {
+                    follow_pipeline_logs(&client, &server, cli.token.as_deref(), &repo, &run_id).await?
+                }
+                (None, Some(repo), Some(run_id)) => get_pipeline_logs(&client, &server, &repo, &run_id).await?,
+                // clap requires both without --local
+                _ => unreachable!(),
+            },
+            PipelineCommands::Trigger { repo, branch, commit, inputs } => {
+                let repo = resolve_repo(repo, &settings)?;
+                trigger_pipeline(&client, &server, &repo, branch, commit, inputs).await?;
             }
-            PipelineCommands::Logs { repo, run_id } => {
-                get_pipeline_logs(&client, &cli.server, &repo, &run_id).await?;
+        },
+        Commands::Import(cmd) => match cmd {
+            ImportCommands::Gitlab { input, output, name, force } => {
+                import_gitlab(&input, &output, name, force)?;
             }
         },
         Commands::Status { id } => {
-            let url = format!("{}/api/v1/executions/{}", cli.server, id);
+            let url = format!("{}/api/v1/executions/{}", server, id);
             let response = client.get(&url).send().await?;
 
             if response.status().is_success() {
@@ -158,28 +448,94 @@ async fn main() -> anyhow::Result<()> {
                 process::exit(1);
             }
         }
-        Commands::Run { pulsefile, repo_url, branch } => {
-            manual_run_pulsefile(&pulsefile, &repo_url, &branch).await?;
+        Commands::Cancel { id } => {
+            cancel_execution(&client, &server, &id).await?;
         }
-        Commands::List => {
-            let url = format!("{}/api/v1/executions", cli.server);
-            let response = client.get(&url).send().await?;
-
-            if response.status().is_success() {
-                let executions: Vec<PipelineExecution> = response.json().await?;
-                println!("Found {} execution(s):\n", executions.len());
-                for exec in executions {
-                    println!(
-                        "  {} - {} [{}] - {}",
-                        exec.id,
-                        exec.pipeline_name,
-                        exec.repository.full_name,
-                        format_status(exec.status)
-                    );
-                }
+        Commands::Timeline { id, width } => {
+            show_timeline(&client, &server, &id, width).await?;
+        }
+        Commands::Queue => {
+            show_queue(&client, &server).await?;
+        }
+        Commands::Search { query, repo, limit } => {
+            let repo = repo.map(|repo| normalize_repo_identifier(&repo));
+            search_logs(&client, &server, &query, repo, limit).await?;
+        }
+        Commands::Run { pulsefile, repo_url, branch, dry_run } => {
+            let pulsefile = resolve_pulsefile(pulsefile, &settings);
+            let repo_url = repo_url
+                .or_else(|| settings.repo.clone())
+                .unwrap_or_else(|| "local/repo".to_string());
+            let branch = branch
+                .or_else(|| settings.branch.clone())
+                .unwrap_or_else(|| DEFAULT_BRANCH.to_string());
+            manual_run_pulsefile(&pulsefile, &repo_url, &branch, dry_run).await?;
+        }
+        Commands::Stats { repo, calendar, steps, weeks } => {
+            let repo = resolve_repo(repo, &settings)?;
+            if steps {
+                show_step_stats(&client, &server, &repo, weeks).await?;
             } else {
-                eprintln!("Failed to list executions: {}", response.status());
-                process::exit(1);
+                show_stats(&client, &server, &repo, calendar, weeks).await?;
+            }
+        }
+        Commands::Replay { run_id, workdir, here } => {
+            replay_execution(&client, &server, &run_id, workdir, here).await?;
+        }
+        Commands::Bisect { good, bad, step, pulsefile } => {
+            let pulsefile = resolve_pulsefile(pulsefile, &settings);
+            bisect_step(&pulsefile, &good, &bad, &step, &settings).await?;
+        }
+        Commands::Prune { older_than, dry_run } => {
+            prune_local_state(older_than.as_deref(), dry_run)?;
+        }
+        Commands::List {
+            label,
+            status,
+            branch,
+            repo,
+            since,
+            page,
+            per_page,
+            local,
+        } => {
+            let paging = (page.is_some() || per_page.is_some())
+                .then(|| (page.unwrap_or(1), per_page.unwrap_or(DEFAULT_PER_PAGE)));
+            let (executions, total) = if local {
+                let executions = open_history().await?.list(label.as_deref()).await?;
+                let total = executions.len();
+                (executions, total)
+            } else {
+                let filters = ExecutionFilters {
+                    label,
+                    status,
+                    branch,
+                    repo: repo.map(|repo| normalize_repo_identifier(&repo)),
+                    since,
+                };
+                match api::negotiate(&client, &server).await {
+                    ApiVersion::V2 => list_executions_v2(&client, &server, &filters, paging).await?,
+                    ApiVersion::V1 => list_executions_v1(&client, &server, &filters, paging).await?,
+                }
+            };
+            match paging {
+                Some((page, _)) => println!("Showing {} of {} execution(s), page {}:\n", executions.len(), total, page),
+                None => println!("Found {} execution(s):\n", executions.len()),
+            }
+            for exec in executions {
+                let labels = if exec.pipeline_labels.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", exec.pipeline_labels.join(", "))
+                };
+                println!(
+                    "  {} - {}{} [{}] - {}",
+                    exec.id,
+                    exec.pipeline_name,
+                    labels,
+                    exec.repository.full_name,
+                    format_status(exec.status)
+                );
             }
         }
     }
@@ -187,32 +543,332 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Which executions `pulse list` asks the server for
+struct ExecutionFilters {
+    label: Option<String>,
+    status: Option<String>,
+    branch: Option<String>,
+    repo: Option<String>,
+    since: Option<String>,
+}
+
+impl ExecutionFilters {
+    /// The filters that are set, as query parameters
+    fn query(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("label", &self.label),
+            ("status", &self.status),
+            ("branch", &self.branch),
+            ("repo", &self.repo),
+            ("since", &self.since),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|value| (name, value)))
+        .collect()
+    }
+}
+
+/// Page size of `pulse list --page` when `--per-page` isn't given
+const DEFAULT_PER_PAGE: usize = 50;
+
+/// Matching executions, or one `(page, per_page)` page of them, with the
+/// number across all pages
+async fn list_executions_v1(
+    client: &Client,
+    server: &str,
+    filters: &ExecutionFilters,
+    paging: Option<(usize, usize)>,
+) -> anyhow::Result<(Vec<PipelineExecution>, usize)> {
+    let url = format!("{}/api/v1/executions", server);
+    let mut request = client.get(&url).query(&filters.query());
+    if let Some((page, per_page)) = paging {
+        request = request.query(&[("page", page), ("per_page", per_page)]);
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        eprintln!("Failed to list executions: {}", response.status());
+        process::exit(1);
+    }
+    let total = response
+        .headers()
+        .get("x-total-count")
+        .and_then(|total| total.to_str().ok())
+        .and_then(|total| total.parse().ok());
+    let executions: Vec<PipelineExecution> = response.json().await?;
+    // Servers from before paging send neither the header nor pages
+    let total = total.unwrap_or(executions.len());
+    Ok((executions, total))
+}
+
+/// Page size used when walking v2 list endpoints
+const V2_PAGE_SIZE: usize = 500;
+
+async fn list_executions_v2(
+    client: &Client,
+    server: &str,
+    filters: &ExecutionFilters,
+    paging: Option<(usize, usize)>,
+) -> anyhow::Result<(Vec<PipelineExecution>, usize)> {
+    let url = format!("{}/api/v2/executions", server);
+    let (mut offset, limit) = match paging {
+        Some((page, per_page)) => (Some(page.saturating_sub(1) * per_page), per_page),
+        None => (Some(0), V2_PAGE_SIZE),
+    };
+    let mut executions = Vec::new();
+    let mut total = 0;
+    while let Some(current) = offset {
+        let response = client
+            .get(&url)
+            .query(&[("offset", current), ("limit", limit)])
+            .query(&filters.query())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            eprintln!("Failed to list executions: {}", response.text().await.unwrap_or_default());
+            process::exit(1);
+        }
+        let page: Page<PipelineExecution> = response.json().await?;
+        total = page.total;
+        offset = page.next_offset().filter(|_| paging.is_none());
+        executions.extend(page.items);
+    }
+    Ok((executions, total))
+}
+
+fn resolve_pulsefile(pulsefile: Option<String>, settings: &ProjectSettings) -> String {
+    pulsefile
+        .or_else(|| settings.pulsefile_path())
+        .unwrap_or_else(|| DEFAULT_PULSEFILE.to_string())
+}
+
+fn resolve_repo(repo: Option<String>, settings: &ProjectSettings) -> anyhow::Result<String> {
+    repo.or_else(|| settings.repo.clone()).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No repository given and no `repo` set in {}",
+            settings::SETTINGS_FILE
+        )
+    })
+}
+
 fn print_execution(exec: &PipelineExecution) {
+    let colors = terminal::stdout_colors();
     println!("Execution: {}", exec.id);
     println!("Pipeline: {} (v{})", exec.pipeline_name, exec.pipeline_version);
+    if !exec.pipeline_labels.is_empty() {
+        println!("Labels: {}", exec.pipeline_labels.join(", "));
+    }
     println!("Repository: {}", exec.repository.full_name);
     println!("Status: {}", format_status(exec.status));
+    if let Some(reason) = &exec.limit_exceeded {
+        println!("Stopped: {}", reason);
+    }
+    if let Some(coverage) = &exec.coverage {
+        println!("Coverage: {}", coverage);
+    }
     println!("Started: {}", exec.started_at);
     if let Some(completed_at) = exec.completed_at {
         println!("Completed: {}", completed_at);
         let duration = completed_at.signed_duration_since(exec.started_at);
         println!("Duration: {:?}", duration);
     }
+    if let Some(queue_ms) = exec.metrics.queue_ms {
+        println!("Queued for: {}ms", queue_ms);
+    }
+    if let Some(cpu_ms) = exec.metrics.cpu_ms {
+        println!("CPU time: {}ms", cpu_ms);
+    }
+
+    if !exec.phases.is_empty() {
+        println!("\nPhases:");
+        for phase in &exec.phases {
+            match phase.bytes_transferred {
+                Some(bytes) => println!(
+                    "  {:?} - {}ms, {}",
+                    phase.phase,
+                    phase.duration_ms,
+                    format_bytes(bytes)
+                ),
+                None => println!("  {:?} - {}ms", phase.phase, phase.duration_ms),
+            }
+        }
+    }
+
+    if !exec.attempts.is_empty() {
+        println!("\nFailed attempts:");
+        for attempt in &exec.attempts {
+            println!("  {}. {} - {}", attempt.attempt, attempt.failed_at, attempt.error);
+        }
+    }
 
     println!("\nSteps:");
     for (idx, step) in exec.step_results.iter().enumerate() {
         println!("\n  {}. {} - {}", idx + 1, step.step_name, format_step_status(step.status));
         if !step.stdout.is_empty() {
-            println!("     Stdout: {}", step.stdout.trim());
+            println!("     Stdout: {}", terminal::render_output(step.stdout.trim(), colors));
         }
         if !step.stderr.is_empty() {
-            println!("     Stderr: {}", step.stderr.trim());
+            println!("     Stderr: {}", terminal::render_output(step.stderr.trim(), colors));
         }
         if let Some(code) = step.exit_code {
             println!("     Exit code: {}", code);
         }
+        if step.replacement_chars > 0 {
+            println!(
+                "     Encoding: {} ({} invalid byte sequence(s) shown as U+FFFD)",
+                step.encoding.as_deref().unwrap_or("UTF-8"),
+                step.replacement_chars
+            );
+        }
         println!("     Duration: {}ms", step.duration_ms);
+        if let Some(resources) = &step.resources {
+            if let Some(cpu_ms) = resources.cpu_ms() {
+                println!("     CPU time: {}ms", cpu_ms);
+            }
+            if let Some(rss) = resources.peak_rss_bytes {
+                println!("     Peak memory: {}", format_bytes(rss));
+            }
+        }
+        if let Some(coverage) = &step.coverage {
+            println!("     Coverage: {}", coverage);
+        }
+        for annotation in &step.annotations {
+            println!("     {}", annotation);
+        }
+        for hook in &step.hooks {
+            println!(
+                "     Hook {} - {} ({}ms)",
+                hook.hook.as_str(),
+                format_step_status(hook.status),
+                hook.duration_ms
+            );
+            if !hook.stdout.is_empty() {
+                println!("       Stdout: {}", terminal::render_output(hook.stdout.trim(), colors));
+            }
+            if !hook.stderr.is_empty() {
+                println!("       Stderr: {}", terminal::render_output(hook.stderr.trim(), colors));
+            }
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+async fn show_stats(
+    client: &Client,
+    server: &str,
+    repo: &str,
+    calendar: bool,
+    weeks: usize,
+) -> anyhow::Result<()> {
+    let repo_identifier = normalize_repo_identifier(repo);
+    let url = format!(
+        "{}/api/v1/pipelines/{}/activity",
+        server,
+        repo_path_segment(&repo_identifier)
+    );
+    let granularity = if calendar { "day" } else { "week" };
+    let days = (weeks.max(1) * 7).to_string();
+
+    let response = client
+        .get(&url)
+        .query(&[("granularity", granularity), ("days", days.as_str())])
+        .send()
+        .await?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        eprintln!("Repository not found: {}", repo);
+        process::exit(1);
+    } else if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        eprintln!("Failed to get activity: {}", error_text);
+        process::exit(1);
+    }
+
+    let buckets: Vec<ActivityBucket> = response.json().await?;
+    println!("Pipeline activity for {}:\n", repo);
+    if calendar {
+        let today = chrono::Utc::now().date_naive();
+        print!("{}", calendar::render_calendar(&buckets, today, weeks));
+    } else if buckets.is_empty() {
+        println!("  No pipeline runs found.");
+    } else {
+        println!("  {:<12} {:>6} {:>8} {:>7}", "Week of", "Runs", "Success", "Failed");
+        for bucket in &buckets {
+            println!(
+                "  {:<12} {:>6} {:>8} {:>7}",
+                bucket.date.format("%Y-%m-%d").to_string(),
+                bucket.total,
+                bucket.success,
+                bucket.failed
+            );
+        }
     }
+
+    Ok(())
+}
+
+async fn show_step_stats(client: &Client, server: &str, repo: &str, weeks: usize) -> anyhow::Result<()> {
+    let repo_identifier = normalize_repo_identifier(repo);
+    let url = format!("{}/api/v1/repos/{}/stats", server, repo_path_segment(&repo_identifier));
+    let days = (weeks.max(1) * 7).to_string();
+    let response = client.get(&url).query(&[("days", days.as_str())]).send().await?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        eprintln!("Repository not found: {}", repo);
+        process::exit(1);
+    } else if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        eprintln!("Failed to get step stats: {}", error_text);
+        process::exit(1);
+    }
+
+    let stats: Value = response.json().await?;
+    let count = |value: &Value, key: &str| value[key].as_u64().unwrap_or_default();
+    let percent = |value: &Value, key: &str| format!("{:.0}%", value[key].as_f64().unwrap_or_default() * 100.0);
+    println!("Step stats for {} since {}:\n", repo, stats["since"].as_str().unwrap_or_default());
+    println!(
+        "  {} execution(s), {} failed ({}), {} on average",
+        count(&stats, "executions"),
+        count(&stats, "failed_executions"),
+        percent(&stats, "failure_rate"),
+        timeline::format_ms(count(&stats, "avg_duration_ms"))
+    );
+    let steps = stats["steps"].as_array().cloned().unwrap_or_default();
+    if steps.is_empty() {
+        println!("  No steps ran.");
+        return Ok(());
+    }
+    println!(
+        "\n  {:<20} {:<24} {:>5} {:>6} {:>6} {:>9} {:>9} {:>6}",
+        "Pipeline", "Step", "Runs", "Failed", "Rate", "Average", "Longest", "Flaky"
+    );
+    for step in &steps {
+        println!(
+            "  {:<20} {:<24} {:>5} {:>6} {:>6} {:>9} {:>9} {:>6}",
+            step["pipeline"].as_str().unwrap_or_default(),
+            step["name"].as_str().unwrap_or_default(),
+            count(step, "runs"),
+            count(step, "failures"),
+            percent(step, "failure_rate"),
+            timeline::format_ms(count(step, "avg_duration_ms")),
+            timeline::format_ms(count(step, "max_duration_ms")),
+            count(step, "flaky_commits")
+        );
+    }
+    Ok(())
 }
 
 fn format_status(status: pulsiora_core::PipelineStatus) -> &'static str {
@@ -223,6 +879,7 @@ fn format_status(status: pulsiora_core::PipelineStatus) -> &'static str {
         pulsiora_core::PipelineStatus::Failed => "FAILED",
         pulsiora_core::PipelineStatus::Cancelled => "CANCELLED",
         pulsiora_core::PipelineStatus::Skipped => "SKIPPED",
+        pulsiora_core::PipelineStatus::Interrupted => "INTERRUPTED",
     }
 }
 
@@ -232,10 +889,22 @@ fn format_step_status(step_status: pulsiora_core::StepStatus) -> &'static str {
         pulsiora_core::StepStatus::Running => "RUNNING",
         pulsiora_core::StepStatus::Success => "SUCCESS",
         pulsiora_core::StepStatus::Failed => "FAILED",
+        pulsiora_core::StepStatus::Cancelled => "CANCELLED",
         pulsiora_core::StepStatus::Skipped => "SKIPPED",
     }
 }
 
+/// HTTP client that authenticates with `token`, if given
+fn api_client(token: Option<&str>) -> anyhow::Result<Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = token {
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    Ok(Client::builder().default_headers(headers).build()?)
+}
+
 fn generate_pulsefile_template() -> anyhow::Result<()> {
     let template = r#"# Pulsefile - Pulsiora CI/CD Pipeline Definition
 
@@ -307,12 +976,49 @@ pipeline {
     Ok(())
 }
 
+fn import_gitlab(input: &str, output: &str, name: Option<String>, force: bool) -> anyhow::Result<()> {
+    let yaml = fs::read_to_string(input)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", input, e))?;
+    let name = name
+        .or_else(|| {
+            std::env::current_dir()
+                .ok()?
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "default".to_string());
+
+    let import = import_gitlab_ci(&yaml, &name)
+        .map_err(|e| anyhow::anyhow!("Failed to convert {}: {}", input, e))?;
+
+    if Path::new(output).exists() && !force {
+        eprintln!("Error: {} already exists (use --force to overwrite)", output);
+        process::exit(1);
+    }
+    fs::write(output, import.to_pulsefile())?;
+
+    println!(
+        "✓ Converted {} job(s) from {} into {}",
+        import.pipeline.steps.len(),
+        input,
+        output
+    );
+    if !import.warnings.is_empty() {
+        println!("\n⚠ {} item(s) need review (also noted at the top of {}):", import.warnings.len(), output);
+        for warning in &import.warnings {
+            println!("  - {}", warning);
+        }
+    }
+    Ok(())
+}
+
 async fn register_repo(
     client: &Client,
     server: &str,
     repo_url: &str,
     pulsefile_path: &str,
     repo_type: &str,
+    organization: Option<&str>,
 ) -> anyhow::Result<()> {
     // Read Pulsefile
     let pulsefile_content = fs::read_to_string(pulsefile_path)
@@ -327,6 +1033,7 @@ async fn register_repo(
         "repo_identifier": repo_identifier,
         "pulsefile": pulsefile_content,
         "repo_type": repo_type,
+        "organization": organization,
     });
 
     let response = client
@@ -336,8 +1043,12 @@ async fn register_repo(
         .await?;
 
     if response.status().is_success() {
+        let body: Value = response.json().await.unwrap_or_default();
         println!("✓ Repository registered successfully: {}", repo_url);
         println!("  Pulsefile uploaded from: {}", pulsefile_path);
+        for warning in body["warnings"].as_array().into_iter().flatten() {
+            println!("  ⚠️  {}", warning.as_str().unwrap_or_default());
+        }
     } else {
         let error_text = response.text().await.unwrap_or_default();
         eprintln!("Failed to register repository: {}", error_text);
@@ -347,13 +1058,63 @@ async fn register_repo(
     Ok(())
 }
 
+async fn update_pulsefile(client: &Client, server: &str, repo: &str, pulsefile_path: &str) -> anyhow::Result<()> {
+    let pulsefile_content = fs::read_to_string(pulsefile_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read Pulsefile at {}: {}", pulsefile_path, e))?;
+    let repo_identifier = normalize_repo_identifier(repo);
+    let url = format!("{}/api/v1/repos/{}/pulsefile", server, repo_path_segment(&repo_identifier));
+
+    let response = client
+        .put(&url)
+        .json(&json!({ "pulsefile": pulsefile_content }))
+        .send()
+        .await?;
+    match response.status() {
+        status if status.is_success() => {
+            let body: Value = response.json().await.unwrap_or_default();
+            println!(
+                "✓ Pulsefile of {} is now revision {} ({})",
+                repo_identifier,
+                body["pulsefile_revision"],
+                body["pulsefile_sha256"].as_str().unwrap_or_default().get(..12).unwrap_or_default()
+            );
+            println!("  Pulsefile uploaded from: {}", pulsefile_path);
+            for warning in body["warnings"].as_array().into_iter().flatten() {
+                println!("  ⚠️  {}", warning.as_str().unwrap_or_default());
+            }
+        }
+        reqwest::StatusCode::BAD_REQUEST => {
+            eprintln!("The server rejected {} as an invalid Pulsefile", pulsefile_path);
+            process::exit(1);
+        }
+        reqwest::StatusCode::NOT_FOUND => {
+            eprintln!("Repository not found: {}", repo);
+            process::exit(1);
+        }
+        reqwest::StatusCode::UNAUTHORIZED => {
+            eprintln!("Updating a Pulsefile needs an API token: pass --token or set PULSIORA_TOKEN");
+            process::exit(1);
+        }
+        reqwest::StatusCode::FORBIDDEN => {
+            eprintln!("Only admins of {} may update its Pulsefile", repo);
+            process::exit(1);
+        }
+        status => {
+            let error_text = response.text().await.unwrap_or_default();
+            eprintln!("Failed to update Pulsefile ({}): {}", status, error_text);
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
+
 async fn unregister_repo(
     client: &Client,
     server: &str,
     repo_url: &str,
 ) -> anyhow::Result<()> {
     let repo_identifier = normalize_repo_identifier(repo_url);
-    let url = format!("{}/api/v1/repos/{}", server, repo_identifier);
+    let url = format!("{}/api/v1/repos/{}", server, repo_path_segment(&repo_identifier));
 
     let response = client.delete(&url).send().await?;
 
@@ -362,6 +1123,12 @@ async fn unregister_repo(
     } else if response.status() == reqwest::StatusCode::NOT_FOUND {
         eprintln!("Repository not found: {}", repo_url);
         process::exit(1);
+    } else if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        eprintln!("Unregistering a repository needs an API token: pass --token or set PULSIORA_TOKEN");
+        process::exit(1);
+    } else if response.status() == reqwest::StatusCode::FORBIDDEN {
+        eprintln!("Only admins of {} may unregister it", repo_url);
+        process::exit(1);
     } else {
         let error_text = response.text().await.unwrap_or_default();
         eprintln!("Failed to unregister repository: {}", error_text);
@@ -371,19 +1138,476 @@ async fn unregister_repo(
     Ok(())
 }
 
-async fn get_pipeline_status(
-    client: &Client,
-    server: &str,
-    repo: &str,
-    limit: usize,
-) -> anyhow::Result<()> {
-    let repo_identifier = normalize_repo_identifier(repo);
-    let url = format!("{}/api/v1/pipelines/{}/status?limit={}", server, repo_identifier, limit);
-
+async fn list_repos(client: &Client, server: &str) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/repos", server);
     let response = client.get(&url).send().await?;
-
+    match response.status() {
+        status if status.is_success() => {}
+        reqwest::StatusCode::UNAUTHORIZED => {
+            eprintln!("Listing repositories needs an API token: pass --token or set PULSIORA_TOKEN");
+            process::exit(1);
+        }
+        status => {
+            let error_text = response.text().await.unwrap_or_default();
+            eprintln!("Failed to list repositories ({}): {}", status, error_text);
+            process::exit(1);
+        }
+    }
+
+    let repos: Vec<Value> = response.json().await?;
+    if repos.is_empty() {
+        println!("No repositories registered.");
+        return Ok(());
+    }
+    println!("  {:<32} {:<10} {:<12} {:<20} Pulsefile", "Repository", "Type", "Last run", "Started");
+    for repo in &repos {
+        let repo_type = match &repo["repo_type"] {
+            Value::String(repo_type) => repo_type.clone(),
+            // `{"Other": "gitea"}`
+            Value::Object(other) => other.values().next().and_then(Value::as_str).unwrap_or("other").to_string(),
+            _ => String::new(),
+        };
+        let last = &repo["last_execution"];
+        let status = serde_json::from_value(last["status"].clone()).map_or("-", format_status);
+        let started = last["started_at"]
+            .as_str()
+            .and_then(|started| chrono::DateTime::parse_from_rfc3339(started).ok())
+            .map(|started| started.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let hash = repo["pulsefile_sha256"].as_str().unwrap_or_default();
+        println!(
+            "  {:<32} {:<10} {:<12} {:<20} {}",
+            repo["repo_identifier"].as_str().unwrap_or_default(),
+            repo_type,
+            status,
+            started,
+            &hash[..hash.len().min(12)]
+        );
+    }
+    Ok(())
+}
+
+/// Print a repository's variables, after setting and removing the given ones
+async fn repo_env(
+    client: &Client,
+    server: &str,
+    repo: &str,
+    set: Vec<(String, String)>,
+    unset: Vec<String>,
+) -> anyhow::Result<()> {
+    let repo_identifier = normalize_repo_identifier(repo);
+    let url = format!("{}/api/v1/repos/{}/env", server, repo_path_segment(&repo_identifier));
+    let fail = |status: reqwest::StatusCode, error_text: String| -> ! {
+        match status {
+            reqwest::StatusCode::NOT_FOUND => eprintln!("Repository not found: {}", repo),
+            reqwest::StatusCode::UNAUTHORIZED => {
+                eprintln!("Repository variables need an API token: pass --token or set PULSIORA_TOKEN")
+            }
+            reqwest::StatusCode::FORBIDDEN => eprintln!("Only admins of {} may change its variables", repo),
+            reqwest::StatusCode::BAD_REQUEST => {
+                eprintln!("Variable names must be letters, digits and `_`, and can't start with PULSE_")
+            }
+            status => eprintln!("Failed to update variables ({}): {}", status, error_text),
+        }
+        process::exit(1);
+    };
+
+    let response = client.get(&url).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        fail(status, response.text().await.unwrap_or_default());
+    }
+    let mut env: std::collections::BTreeMap<String, String> = response.json().await?;
+
+    if !set.is_empty() || !unset.is_empty() {
+        for name in &unset {
+            env.remove(name);
+        }
+        env.extend(set);
+        let response = client.put(&url).json(&env).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            fail(status, response.text().await.unwrap_or_default());
+        }
+        env = response.json().await?;
+        println!("✓ Variables updated for {}", repo_identifier);
+    }
+
+    if env.is_empty() {
+        println!("No variables set for {}", repo_identifier);
+    }
+    for (name, value) in &env {
+        println!("{}={}", name, value);
+    }
+    Ok(())
+}
+
+async fn trigger_pipeline(
+    client: &Client,
+    server: &str,
+    repo: &str,
+    branch: Option<String>,
+    commit: Option<String>,
+    inputs: Vec<(String, String)>,
+) -> anyhow::Result<()> {
+    let repo_identifier = normalize_repo_identifier(repo);
+    let url = format!("{}/api/v1/pipelines/{}/run", server, repo_path_segment(&repo_identifier));
+    let body = serde_json::json!({
+        "branch": branch,
+        "commit": commit,
+        "inputs": inputs.into_iter().collect::<std::collections::BTreeMap<_, _>>(),
+    });
+    let response = client.post(&url).json(&body).send().await?;
+
+    match response.status() {
+        status if status.is_success() => {
+            let queued: serde_json::Value = response.json().await?;
+            println!("✓ Pipeline queued for {}", repo_identifier);
+            if let Some(id) = queued["execution_id"].as_str() {
+                println!("  Execution ID: {}", id);
+            }
+        }
+        reqwest::StatusCode::NOT_FOUND => {
+            eprintln!("Repository not found: {}", repo);
+            process::exit(1);
+        }
+        reqwest::StatusCode::UNPROCESSABLE_ENTITY => {
+            eprintln!("The pipeline can't be started manually: it needs a `manual` trigger and valid input names");
+            process::exit(1);
+        }
+        reqwest::StatusCode::UNAUTHORIZED => {
+            eprintln!("Starting a pipeline needs an API token: pass --token or set PULSIORA_TOKEN");
+            process::exit(1);
+        }
+        reqwest::StatusCode::FORBIDDEN => {
+            eprintln!("Only developers of {} may start its pipeline", repo);
+            process::exit(1);
+        }
+        status => {
+            let error_text = response.text().await.unwrap_or_default();
+            eprintln!("Failed to start pipeline ({}): {}", status, error_text);
+            process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a repository's webhook checks, printing each; returns whether all
+/// passed
+async fn test_webhook(client: &Client, server: &str, repo: &str, secret: Option<String>) -> anyhow::Result<bool> {
+    let repo_identifier = normalize_repo_identifier(repo);
+    let url = format!("{}/api/v1/repos/{}/webhook-test", server, repo_path_segment(&repo_identifier));
+    let response = client.post(&url).json(&json!({ "secret": secret })).send().await?;
+    match response.status() {
+        status if status.is_success() => {}
+        reqwest::StatusCode::NOT_FOUND => {
+            eprintln!("Repository not found: {}", repo);
+            process::exit(1);
+        }
+        reqwest::StatusCode::UNAUTHORIZED => {
+            eprintln!("Testing webhooks needs an API token: pass --token or set PULSIORA_TOKEN");
+            process::exit(1);
+        }
+        reqwest::StatusCode::FORBIDDEN => {
+            eprintln!("Only admins of {} may test its webhook", repo);
+            process::exit(1);
+        }
+        status => {
+            eprintln!("Failed to test webhook ({}): {}", status, response.text().await.unwrap_or_default());
+            process::exit(1);
+        }
+    }
+    let report: Value = response.json().await?;
+    print_checks(&report);
+    let ok = report["status"] == "ok";
+    if ok {
+        println!("✓ Webhook for {} looks good", repo_identifier);
+    } else {
+        eprintln!("✗ Webhook for {} needs attention", repo_identifier);
+    }
+    Ok(ok)
+}
+
+/// Check that the server is ready, printing each of its checks. Servers
+/// without `/health/ready` only say whether they answer.
+async fn check_health(client: &Client, server: &str) -> anyhow::Result<bool> {
+    let response = client.get(format!("{}/health/ready", server)).send().await?;
+    let healthy = if response.status() == reqwest::StatusCode::NOT_FOUND {
+        client.get(format!("{}/health", server)).send().await?.status().is_success()
+    } else {
+        let healthy = response.status().is_success();
+        let report: Value = response.json().await.unwrap_or_default();
+        print_checks(&report);
+        healthy
+    };
+    if healthy {
+        println!("Server is healthy");
+    } else {
+        eprintln!("Server is unhealthy");
+    }
+    Ok(healthy)
+}
+
+/// Print the checks of a health report, one per line
+fn print_checks(report: &Value) {
+    for check in report["checks"].as_array().into_iter().flatten() {
+        println!(
+            "  {:<10} {:<8} {}",
+            check["name"].as_str().unwrap_or("-"),
+            check["status"].as_str().unwrap_or("-"),
+            check["detail"].as_str().unwrap_or("")
+        );
+    }
+}
+
+/// Short form of a wait in milliseconds, e.g. `3m 12s`
+fn format_wait(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}h {}m", s / (60 * 60), s / 60 % 60),
+    }
+}
+
+async fn search_logs(
+    client: &Client,
+    server: &str,
+    query: &str,
+    repo: Option<String>,
+    limit: Option<usize>,
+) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/search", server);
+    let mut params = vec![("q", query.to_string())];
+    params.extend(repo.map(|repo| ("repo", repo)));
+    params.extend(limit.map(|limit| ("limit", limit.to_string())));
+    let response = client.get(&url).query(&params).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        eprintln!("Failed to search step logs ({}): {}", status, error_text);
+        if status == reqwest::StatusCode::BAD_REQUEST {
+            eprintln!("Search terms need at least 3 characters each");
+        }
+        process::exit(1);
+    }
+
+    let results: Vec<Value> = response.json().await?;
+    if results.is_empty() {
+        println!("No steps match {:?}", query);
+        return Ok(());
+    }
+    let colors = terminal::stdout_colors();
+    for result in &results {
+        println!(
+            "{}  {}  {}  {}  {}",
+            result["execution_id"].as_str().unwrap_or_default(),
+            result["repository"].as_str().unwrap_or("-"),
+            result["pipeline_name"].as_str().unwrap_or("-"),
+            result["status"].as_str().unwrap_or("-"),
+            result["started_at"].as_str().unwrap_or("-")
+        );
+        for found in result["matches"].as_array().into_iter().flatten() {
+            let place = match (found["stream"].as_str(), found["line"].as_u64()) {
+                (Some(stream), Some(line)) => format!("{}:{}", stream, line),
+                (stream, _) => stream.unwrap_or("name").to_string(),
+            };
+            let highlights: Vec<(usize, usize)> = serde_json::from_value(found["highlights"].clone()).unwrap_or_default();
+            println!(
+                "    {} ({}): {}",
+                found["step_name"].as_str().unwrap_or("-"),
+                place,
+                terminal::highlight(found["snippet"].as_str().unwrap_or_default(), &highlights, colors)
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn show_queue(client: &Client, server: &str) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/queue", server);
+    let response = client.get(&url).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        eprintln!("Failed to get the queue ({}): {}", status, error_text);
+        process::exit(1);
+    }
+
+    let queue: Value = response.json().await?;
+    let count = |key: &str| queue[key].as_u64().unwrap_or_default();
+    let pending = queue["pending"].as_array().cloned().unwrap_or_default();
+    let running = queue["running"].as_array().cloned().unwrap_or_default();
+    println!(
+        "Workers: {} of {} busy, {} waiting (at most {})",
+        count("busy_workers"),
+        count("workers"),
+        pending.len(),
+        count("max_queued_jobs")
+    );
+    let short_id = |job: &Value| job["execution_id"].as_str().unwrap_or_default().chars().take(8).collect::<String>();
+    let text = |job: &Value, key: &str| job[key].as_str().unwrap_or("-").to_string();
+    let wait = |job: &Value, key: &str| format_wait(job[key].as_u64().unwrap_or_default());
+
+    if !running.is_empty() {
+        println!("\nRunning:");
+        println!(
+            "  {:<7} {:<9} {:<28} {:<20} {:<14} {:<8} Running",
+            "Worker", "Execution", "Repository", "Pipeline", "Agent", "Waited"
+        );
+        for job in &running {
+            println!(
+                "  {:<7} {:<9} {:<28} {:<20} {:<14} {:<8} {}",
+                job["worker"].as_u64().unwrap_or_default(),
+                short_id(job),
+                text(job, "repository"),
+                text(job, "pipeline"),
+                text(job, "agent"),
+                wait(job, "waited_ms"),
+                wait(job, "running_ms")
+            );
+        }
+    }
+    if !pending.is_empty() {
+        println!("\nWaiting:");
+        println!(
+            "  {:<4} {:<9} {:<28} {:<20} {:<10} {:<8} {:<8} Reason",
+            "#", "Execution", "Repository", "Pipeline", "Trigger", "Priority", "Waiting"
+        );
+        for job in &pending {
+            let reason = match job["reason"].as_str() {
+                Some("workers_busy") => "all workers are busy",
+                Some("repository_limit") => "repository is at its limit of concurrent runs",
+                Some("organization_limit") => "organization is at its quota of concurrent runs",
+                Some("shutting_down") => "server is shutting down",
+                Some("retry_backoff") => "waiting to retry after an infrastructure error",
+                _ => "starting",
+            };
+            println!(
+                "  {:<4} {:<9} {:<28} {:<20} {:<10} {:<8} {:<8} {}",
+                job["position"].as_u64().unwrap_or_default(),
+                short_id(job),
+                text(job, "repository"),
+                text(job, "pipeline"),
+                text(job, "trigger"),
+                text(job, "priority"),
+                wait(job, "waiting_ms"),
+                reason
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn cancel_execution(client: &Client, server: &str, id: &str) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/executions/{}/cancel", server, id);
+    let response = client.post(&url).send().await?;
+
+    match response.status() {
+        status if status.is_success() => {
+            let execution: PipelineExecution = response.json().await?;
+            if execution.status == pulsiora_core::PipelineStatus::Cancelled {
+                println!("✓ Execution {} cancelled", execution.id);
+                let finished = execution.step_results.iter().filter(|r| r.status != pulsiora_core::StepStatus::Skipped).count();
+                if finished > 0 {
+                    println!("  {} step(s) ran before it stopped", finished);
+                }
+            } else {
+                println!(
+                    "Cancellation requested; execution {} is still {}",
+                    execution.id,
+                    format_status(execution.status)
+                );
+            }
+        }
+        reqwest::StatusCode::NOT_FOUND => {
+            eprintln!("Execution not found: {}", id);
+            process::exit(1);
+        }
+        reqwest::StatusCode::CONFLICT => {
+            eprintln!("Execution {} has already finished", id);
+            process::exit(1);
+        }
+        reqwest::StatusCode::UNAUTHORIZED => {
+            eprintln!("Cancelling an execution needs an API token: pass --token or set PULSIORA_TOKEN");
+            process::exit(1);
+        }
+        reqwest::StatusCode::FORBIDDEN => {
+            eprintln!("Only developers of the execution's repository may cancel it");
+            process::exit(1);
+        }
+        status => {
+            let error_text = response.text().await.unwrap_or_default();
+            eprintln!("Failed to cancel execution ({}): {}", status, error_text);
+            process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+async fn show_timeline(client: &Client, server: &str, id: &str, width: usize) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/executions/{}/timeline", server, id);
+    let response = client.get(&url).send().await?;
+
+    match response.status() {
+        status if status.is_success() => {
+            let timeline: Value = response.json().await?;
+            print!("{}", timeline::render_waterfall(&timeline, width));
+        }
+        reqwest::StatusCode::NOT_FOUND => {
+            eprintln!("Execution not found: {}", id);
+            process::exit(1);
+        }
+        reqwest::StatusCode::UNAUTHORIZED => {
+            eprintln!("Viewing this execution needs an API token: pass --token or set PULSIORA_TOKEN");
+            process::exit(1);
+        }
+        reqwest::StatusCode::FORBIDDEN => {
+            eprintln!("Only members of the execution's repository may view it");
+            process::exit(1);
+        }
+        status => {
+            let error_text = response.text().await.unwrap_or_default();
+            eprintln!("Failed to get the timeline ({}): {}", status, error_text);
+            process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_pipeline_status(
+    client: &Client,
+    server: &str,
+    repo: &str,
+    limit: usize,
+) -> anyhow::Result<()> {
+    let repo_identifier = normalize_repo_identifier(repo);
+    let version = api::negotiate(client, server).await;
+    let url = match version {
+        ApiVersion::V2 => format!(
+            "{}/api/v2/pipelines/{}/executions?limit={}",
+            server,
+            repo_path_segment(&repo_identifier),
+            limit
+        ),
+        ApiVersion::V1 => format!(
+            "{}/api/v1/pipelines/{}/status?limit={}",
+            server,
+            repo_path_segment(&repo_identifier),
+            limit
+        ),
+    };
+
+    let response = client.get(&url).send().await?;
+
     if response.status().is_success() {
-        let executions: Vec<PipelineExecution> = response.json().await?;
+        let executions: Vec<PipelineExecution> = match version {
+            ApiVersion::V2 => response.json::<Page<PipelineExecution>>().await?.items,
+            ApiVersion::V1 => response.json().await?,
+        };
         println!("Recent pipeline runs for {}:\n", repo);
         
         if executions.is_empty() {
@@ -447,6 +1671,121 @@ async fn get_pipeline_logs(
     Ok(())
 }
 
+/// Tail a run on the server: subscribe to its live updates, print the steps
+/// that already finished, then its output and step boundaries as they come,
+/// and exit once it ends. Exits 0 if it succeeded, 130 if it was cancelled
+/// and 1 otherwise, as `pulse run` does.
+async fn follow_pipeline_logs(
+    client: &Client,
+    server: &str,
+    token: Option<&str>,
+    repo: &str,
+    run_id: &str,
+) -> anyhow::Result<()> {
+    use futures::{SinkExt, StreamExt};
+    use pulsiora_core::{ExecutionEvent, OutputStream, PipelineStatus};
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let mut request = follow::websocket_url(server).into_client_request()?;
+    if let Some(token) = token {
+        request
+            .headers_mut()
+            .insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token))?);
+    }
+    let (mut socket, _) = match tokio_tungstenite::connect_async(request).await {
+        Ok(connected) => connected,
+        Err(tokio_tungstenite::tungstenite::Error::Http(response))
+            if response.status() == StatusCode::UNAUTHORIZED =>
+        {
+            eprintln!("Following a run needs an API token: pass --token or set PULSIORA_TOKEN");
+            process::exit(1);
+        }
+        Err(e) => anyhow::bail!("Failed to connect to live updates: {}", e),
+    };
+    // Subscribe before looking at the run, so nothing it does in between is missed
+    let subscribe = json!({"action": "subscribe", "executions": [run_id]});
+    socket.send(Message::Text(subscribe.to_string())).await?;
+    while let Some(message) = socket.next().await {
+        if let Message::Text(text) = message? {
+            let notice: Value = serde_json::from_str(&text)?;
+            match notice["type"].as_str() {
+                Some("subscribed") => break,
+                Some("error") => anyhow::bail!("Failed to follow run {}: {}", run_id, notice["message"]),
+                _ => {}
+            }
+        }
+    }
+
+    let url = format!("{}/api/v1/executions/{}", server, run_id);
+    let response = client.get(&url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        eprintln!("Pipeline run not found: {}", run_id);
+        process::exit(1);
+    } else if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        eprintln!("Failed to get pipeline logs: {}", error_text);
+        process::exit(1);
+    }
+    let execution: PipelineExecution = response.json().await?;
+    if normalize_repo_identifier(&execution.repository.full_name) != normalize_repo_identifier(repo) {
+        eprintln!("Error: Run {} does not belong to repository {}", run_id, repo);
+        process::exit(1);
+    }
+    if follow::is_final(execution.status) {
+        print_execution(&execution);
+        process::exit(follow::exit_code(execution.status));
+    }
+
+    println!("Following {} of {} ({:?})", execution.pipeline_name, execution.repository.full_name, execution.status);
+    let (stdout_colors, stderr_colors) = (terminal::stdout_colors(), terminal::stderr_colors());
+    let mut finished = follow::FinishedSteps::default();
+    for step in &execution.step_results {
+        if let Some(line) = finished.finish(step) {
+            println!("{}", line);
+        }
+    }
+    let status = loop {
+        let Some(message) = socket.next().await else {
+            anyhow::bail!("The server closed the connection before run {} finished", run_id);
+        };
+        let Message::Text(text) = message? else { continue };
+        let update: Value = serde_json::from_str(&text)?;
+        match update["type"].as_str() {
+            Some("step") => match serde_json::from_value::<ExecutionEvent>(update["event"].clone())? {
+                ExecutionEvent::StepStarted { step_name, .. } => println!("{}", follow::step_started(&step_name)),
+                ExecutionEvent::OutputChunk { stream: OutputStream::Stdout, data, .. } => {
+                    print!("{}", terminal::render_output(&data, stdout_colors));
+                    std::io::Write::flush(&mut std::io::stdout())?;
+                }
+                ExecutionEvent::OutputChunk { stream: OutputStream::Stderr, data, .. } => {
+                    eprint!("{}", terminal::render_output(&data, stderr_colors));
+                }
+                ExecutionEvent::StepFinished { result, .. } => {
+                    if let Some(line) = finished.finish(&result) {
+                        println!("{}", line);
+                    }
+                }
+                ExecutionEvent::PipelineFinished { status, .. } => break status,
+            },
+            // Runs cancelled while queued end without step events
+            Some("status") => {
+                if let Some(status) = update["status"].as_str().and_then(PipelineStatus::parse) {
+                    if follow::is_final(status) {
+                        break status;
+                    }
+                }
+            }
+            Some("lagged") => eprintln!("⚠️  Fell behind the server; {} updates were dropped", update["missed"]),
+            _ => {}
+        }
+    };
+
+    println!("📊 Status: {:?}", status);
+    process::exit(follow::exit_code(status));
+}
+
 fn normalize_repo_identifier(repo: &str) -> String {
     // Normalize repo URL or identifier to owner/repo format
     if repo.starts_with("http://") || repo.starts_with("https://") {
@@ -467,17 +1806,31 @@ fn normalize_repo_identifier(repo: &str) -> String {
     repo.to_string()
 }
 
-async fn manual_run_pulsefile(pulsefile_path: &str, repo_url: &str, branch: &str) -> anyhow::Result<()> {
+/// Encode an owner/repo identifier as a single URL path segment
+fn repo_path_segment(repo_identifier: &str) -> String {
+    repo_identifier.replace('/', "%2F")
+}
+
+async fn manual_run_pulsefile(pulsefile_path: &str, repo_url: &str, branch: &str, dry_run: bool) -> anyhow::Result<()> {
     // Read Pulsefile
     let pulsefile_content = fs::read_to_string(pulsefile_path)
         .map_err(|e| anyhow::anyhow!("Failed to read Pulsefile at {}: {}", pulsefile_path, e))?;
     
     // Parse Pulsefile
-    let pipeline = parse_pulsefile(&pulsefile_content)
+    let (pipeline, warnings) = parse_pulsefile_with_warnings(&pulsefile_content)
         .map_err(|e| anyhow::anyhow!("Failed to parse Pulsefile: {}", e))?;
     
     println!("✅ Pulsefile parsed successfully!");
+    for warning in &warnings {
+        println!("⚠️  {}: {}", pulsefile_path, warning);
+    }
     println!("📋 Pipeline: {} v{}", pipeline.name, pipeline.version);
+    if let Some(description) = &pipeline.description {
+        println!("📝 {}", description);
+    }
+    if !pipeline.owners.is_empty() {
+        println!("👥 Owners: {}", pipeline.owners.join(", "));
+    }
     println!("📁 Repository: {}", repo_url);
     println!("🌿 Branch: {}", branch);
     println!("🔢 Steps: {}", pipeline.steps.len());
@@ -494,29 +1847,341 @@ async fn manual_run_pulsefile(pulsefile_path: &str, repo_url: &str, branch: &str
         },
         branch: Some(branch.to_string()),
         tag: None,
+        release_name: None,
         pull_request: None,
         commit_sha: Some("manual-execution".to_string()),
+        commit_message: None,
         sender: "manual".to_string(),
+        inputs: Default::default(),
     };
     
+    let executor = local_executor();
+    if dry_run {
+        let plan = executor.plan(uuid::Uuid::new_v4(), &pipeline, &git_event);
+        print_plan(&plan);
+        return Ok(());
+    }
+
     println!("\n🚀 Starting manual pipeline execution...\n");
     
     // Execute the pipeline using the runner
-    let executor = PipelineExecutor::new();
-    let execution = executor.execute(&pipeline, &git_event).await
+    let execution = executor
+        .execute_with_cancel(uuid::Uuid::new_v4(), &pipeline, &git_event, cancel_on_ctrl_c())
+        .await
         .map_err(|e| anyhow::anyhow!("Pipeline execution failed: {}", e))?;
+    // The run itself matters more than its record
+    if let Err(e) = record_local_run(&execution).await {
+        eprintln!("⚠️  Failed to save the run to local history: {}", e);
+    }
     
     println!("\n✅ Pipeline execution completed!");
     println!("📊 Status: {:?}", execution.status);
+    println!("🆔 Run ID: {} (pulse pipeline logs --local {})", execution.id, execution.id);
     println!("⏱️  Duration: {:?}", execution.completed_at.unwrap() - execution.started_at);
     
     if execution.status == pulsiora_core::PipelineStatus::Success {
         println!("🎉 Pipeline executed successfully!");
+    } else if execution.status == pulsiora_core::PipelineStatus::Cancelled {
+        println!("🛑 Pipeline cancelled");
+        process::exit(130);
     } else {
         println!("❌ Pipeline failed!");
+        let rerun = format!(
+            "pulse run --pulsefile {} --repo-url {} --branch {}",
+            summary::shell_quote(pulsefile_path),
+            summary::shell_quote(repo_url),
+            summary::shell_quote(branch)
+        );
+        if let Some(summary) = summary::failure_summary(&execution, &rerun) {
+            eprintln!("\n{}", terminal::render_output(&summary, terminal::stderr_colors()));
+        }
         process::exit(1);
     }
     
     Ok(())
 }
 
+/// Local run history in the CLI's home
+async fn open_history() -> anyhow::Result<history::RunHistory> {
+    let path = history::default_path()
+        .ok_or_else(|| anyhow::anyhow!("No home directory for local history; set PULSE_HOME"))?;
+    history::RunHistory::open(&path).await
+}
+
+async fn record_local_run(execution: &PipelineExecution) -> anyhow::Result<()> {
+    open_history().await?.record(execution).await
+}
+
+/// Print a run from local history, like `pipeline logs` does for the server's
+async fn show_local_logs(run_id: &str) -> anyhow::Result<()> {
+    let id = uuid::Uuid::parse_str(run_id).map_err(|_| anyhow::anyhow!("Invalid run ID: {}", run_id))?;
+    match open_history().await?.get(id).await? {
+        Some(execution) => print_execution(&execution),
+        None => {
+            eprintln!("Pipeline run not found in local history: {}", run_id);
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+/// Executor for runs on this machine, keeping action bundles in the CLI's cache
+fn local_executor() -> PipelineExecutor {
+    let executor = PipelineExecutor::new();
+    match prune::pulse_home() {
+        Some(home) => executor.with_action_cache(ActionCache::new(home.join("cache").join("actions"))),
+        None => executor,
+    }
+}
+
+/// Print what `pulse run --dry-run` would do
+fn print_plan(plan: &ExecutionPlan) {
+    if !plan.triggered {
+        println!("\n⏭️  The pipeline's triggers don't match this push, so nothing would run");
+        return;
+    }
+    if let Some(reason) = &plan.limit_exceeded {
+        println!("\n❌ The execution would be rejected: {}", reason);
+        return;
+    }
+    println!("\n🧪 Dry run: {} step(s) would run\n", plan.steps.len());
+    for step in &plan.steps {
+        let mut notes = vec![step.backend.clone()];
+        notes.extend(step.runner.clone());
+        notes.extend(step.uses.as_ref().map(|uses| format!("uses {}", uses)));
+        notes.extend(step.image.clone());
+        if step.background {
+            notes.push("background".to_string());
+        }
+        if step.allow_failure {
+            notes.push("allow_failure".to_string());
+        }
+        println!("{}. {} ({})", step.index + 1, step.name, notes.join(", "));
+        for (key, value) in &step.env {
+            println!("   {}={}", key, value);
+        }
+        for line in step.script.lines() {
+            println!("   │ {}", line);
+        }
+        println!();
+    }
+}
+
+/// Token cancelled on Ctrl-C. Steps run in their own process groups, so the
+/// terminal's interrupt doesn't reach them and they're stopped through this.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\n🛑 Cancelling...");
+            token.cancel();
+        }
+    });
+    cancel
+}
+
+async fn replay_execution(
+    client: &Client,
+    server: &str,
+    run_id: &str,
+    workdir: Option<String>,
+    here: bool,
+) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/executions/{}/replay", server, run_id);
+    let response = client.get(&url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        eprintln!("Error: No replay bundle recorded for run {}", run_id);
+        process::exit(1);
+    }
+    if !response.status().is_success() {
+        eprintln!("Failed to fetch replay bundle: {}", response.status());
+        process::exit(1);
+    }
+    let bundle: ReplayBundle = response.json().await?;
+
+    println!("🔁 Replaying run {}", bundle.execution_id);
+    println!("📋 Pipeline: {} v{}", bundle.pipeline.name, bundle.pipeline.version);
+    println!("📁 Repository: {}", bundle.git_event.repository.full_name);
+
+    let work_dir = if here {
+        std::env::current_dir()?
+    } else {
+        match bundle.revision() {
+            Some(revision) => {
+                let dir = workdir.map(std::path::PathBuf::from).unwrap_or_else(|| {
+                    std::env::temp_dir().join(format!("{}{}", prune::REPLAY_DIR_PREFIX, bundle.execution_id))
+                });
+                if dir.exists() {
+                    anyhow::bail!("{} already exists; remove it or pass --workdir", dir.display());
+                }
+                println!("📥 Checking out {} into {}", revision, dir.display());
+                checkout_revision(&bundle.git_event.repository.clone_url, revision, &dir)
+                    .map_err(|e| anyhow::anyhow!("Failed to check out {}: {}", revision, e))?;
+                dir
+            }
+            None => {
+                println!("⚠️  Run has no recorded commit; replaying in the current directory");
+                std::env::current_dir()?
+            }
+        }
+    };
+
+    println!("\n🚀 Starting replay...\n");
+    let executor = local_executor().with_work_dir(&work_dir);
+    let execution = bundle
+        .replay(&executor, cancel_on_ctrl_c())
+        .await
+        .map_err(|e| anyhow::anyhow!("Replay failed: {}", e))?;
+
+    print_execution(&execution);
+    if execution.status == pulsiora_core::PipelineStatus::Cancelled {
+        process::exit(130);
+    }
+    if execution.status == pulsiora_core::PipelineStatus::Failed {
+        let rerun = format!("pulse replay {}", summary::shell_quote(run_id));
+        if let Some(summary) = summary::failure_summary(&execution, &rerun) {
+            eprintln!("\n{}", terminal::render_output(&summary, terminal::stderr_colors()));
+        }
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Run `git` in `dir` and return its trimmed stdout
+fn git_stdout(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = process::Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+async fn bisect_step(
+    pulsefile_path: &str,
+    good: &str,
+    bad: &str,
+    step: &str,
+    settings: &ProjectSettings,
+) -> anyhow::Result<()> {
+    let pulsefile_content = fs::read_to_string(pulsefile_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read Pulsefile at {}: {}", pulsefile_path, e))?;
+    let (pipeline, _) = parse_pulsefile_with_warnings(&pulsefile_content)
+        .map_err(|e| anyhow::anyhow!("Failed to parse Pulsefile: {}", e))?;
+
+    let toplevel = std::path::PathBuf::from(
+        git_stdout(Path::new("."), &["rev-parse", "--show-toplevel"])
+            .map_err(|_| anyhow::anyhow!("pulse bisect must be run inside a git repository"))?,
+    );
+    let resolve = |rev: &str| {
+        git_stdout(&toplevel, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", rev)])
+            .map_err(|_| anyhow::anyhow!("Unknown commit '{}'", rev))
+    };
+    let (good_sha, bad_sha) = (resolve(good)?, resolve(bad)?);
+
+    let name = toplevel
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "repo".to_string());
+    let repository = pulsiora_core::Repository {
+        owner: "local".to_string(),
+        full_name: settings.repo.clone().unwrap_or_else(|| format!("local/{}", name)),
+        name,
+        clone_url: toplevel.to_string_lossy().into_owned(),
+        default_branch: settings.branch.clone().unwrap_or_else(|| DEFAULT_BRANCH.to_string()),
+    };
+    let dir = std::env::temp_dir().join(format!("{}{}", prune::BISECT_DIR_PREFIX, uuid::Uuid::new_v4()));
+
+    println!("🔎 Bisecting {}..{} with step \"{}\"", &good_sha[..12], &bad_sha[..12], step);
+    let mut bisect = Bisect::start(&repository, &pipeline, step, &good_sha, &bad_sha, &dir)
+        .map_err(|e| anyhow::anyhow!("Failed to start bisect: {}", e))?;
+
+    let cancel = cancel_on_ctrl_c();
+    let state = bisect
+        .run(&local_executor(), cancel.clone(), |sha, verdict, execution| {
+            let label = match verdict {
+                BisectVerdict::Good => "✅ good",
+                BisectVerdict::Bad => "❌ bad ",
+                BisectVerdict::Skip => "⏭️  skip",
+            };
+            let duration = execution
+                .and_then(|e| e.completed_at.map(|end| end - e.started_at))
+                .map(|d| format!(" ({}s)", d.num_seconds()))
+                .unwrap_or_default();
+            println!("  {}  {}{}", label, &sha[..12], duration);
+        })
+        .await;
+    let state = match state {
+        Ok(state) => state,
+        Err(_) if cancel.is_cancelled() => process::exit(130),
+        Err(e) => anyhow::bail!("Bisect failed: {}", e),
+    };
+
+    match state {
+        BisectState::FirstBad(sha) => {
+            let summary = git_stdout(&toplevel, &["log", "-1", "--format=%h %s (%an, %ad)", "--date=short", &sha])?;
+            println!("\n🎯 First bad commit: {}", sha);
+            println!("   {}", summary);
+        }
+        BisectState::Inconclusive(candidates) => {
+            eprintln!("\n⚠️  Only skipped commits are left; the first bad commit is one of:");
+            for sha in candidates {
+                eprintln!("   {}", sha);
+            }
+            process::exit(1);
+        }
+        BisectState::Testing(_) => unreachable!("bisect stopped while commits were left to test"),
+    }
+    Ok(())
+}
+
+fn prune_local_state(older_than: Option<&str>, dry_run: bool) -> anyhow::Result<()> {
+    let min_age = older_than.map(prune::parse_age).transpose()?.unwrap_or_default();
+    let now = std::time::SystemTime::now();
+    let entries: Vec<prune::LocalEntry> = prune::scan(prune::pulse_home().as_deref(), &std::env::temp_dir())
+        .into_iter()
+        .filter(|entry| entry.age(now) >= min_age)
+        .collect();
+
+    if entries.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+
+    let mut total = 0;
+    let mut failed = false;
+    for entry in &entries {
+        let line = format!(
+            "  {:<10} {:>10} {:>6}  {}",
+            entry.kind.label(),
+            format_bytes(entry.bytes),
+            prune::format_age(entry.age(now)),
+            entry.path.display()
+        );
+        if dry_run {
+            println!("{}", line);
+            total += entry.bytes;
+            continue;
+        }
+        match prune::remove(entry) {
+            Ok(()) => {
+                println!("{}", line);
+                total += entry.bytes;
+            }
+            Err(e) => {
+                eprintln!("  Failed to remove {}: {}", entry.path.display(), e);
+                failed = true;
+            }
+        }
+    }
+
+    if dry_run {
+        println!("\nWould free {} (dry run, nothing removed)", format_bytes(total));
+    } else {
+        println!("\nFreed {}", format_bytes(total));
+    }
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
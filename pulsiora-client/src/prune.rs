@@ -0,0 +1,202 @@
+// `pulse prune`: find and remove local state left behind by runs, such as
+// replay checkouts, runner workspaces and caches.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Prefix of the temporary directories `pulse replay` checks commits out into
+pub const REPLAY_DIR_PREFIX: &str = "pulse-replay-";
+
+/// Prefix of the temporary directories `pulse bisect` works in
+pub const BISECT_DIR_PREFIX: &str = "pulse-bisect-";
+
+/// Kind of local state an entry holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LocalKind {
+    Workspace,
+    Cache,
+    Replay,
+    Bisect,
+}
+
+impl LocalKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            LocalKind::Workspace => "workspace",
+            LocalKind::Cache => "cache",
+            LocalKind::Replay => "replay",
+            LocalKind::Bisect => "bisect",
+        }
+    }
+}
+
+/// A prunable directory or file
+#[derive(Debug, Clone)]
+pub struct LocalEntry {
+    pub kind: LocalKind,
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub modified: SystemTime,
+}
+
+impl LocalEntry {
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.modified).unwrap_or_default()
+    }
+}
+
+/// Where the CLI keeps its own state: `$PULSE_HOME`, else a `pulse`
+/// directory in the user's cache directory
+pub fn pulse_home() -> Option<PathBuf> {
+    if let Some(home) = std::env::var_os("PULSE_HOME").filter(|h| !h.is_empty()) {
+        return Some(PathBuf::from(home));
+    }
+    let cache = if cfg!(target_os = "windows") {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .filter(|c| !c.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+    };
+    cache.map(|dir| dir.join("pulse"))
+}
+
+/// Every prunable entry: children of `<home>/workspaces` and `<home>/cache`,
+/// plus replay checkouts and bisect directories in `temp_dir`
+pub fn scan(home: Option<&Path>, temp_dir: &Path) -> Vec<LocalEntry> {
+    let mut entries = Vec::new();
+    if let Some(home) = home {
+        scan_children(&home.join("workspaces"), LocalKind::Workspace, |_| true, &mut entries);
+        scan_children(&home.join("cache"), LocalKind::Cache, |_| true, &mut entries);
+    }
+    scan_children(
+        temp_dir,
+        LocalKind::Replay,
+        |name| name.starts_with(REPLAY_DIR_PREFIX),
+        &mut entries,
+    );
+    scan_children(
+        temp_dir,
+        LocalKind::Bisect,
+        |name| name.starts_with(BISECT_DIR_PREFIX),
+        &mut entries,
+    );
+    entries.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.path.cmp(&b.path)));
+    entries
+}
+
+fn scan_children(dir: &Path, kind: LocalKind, keep: impl Fn(&str) -> bool, entries: &mut Vec<LocalEntry>) {
+    let Ok(children) = fs::read_dir(dir) else { return };
+    for child in children.flatten() {
+        if !keep(&child.file_name().to_string_lossy()) {
+            continue;
+        }
+        let Ok(metadata) = fs::symlink_metadata(child.path()) else { continue };
+        entries.push(LocalEntry {
+            kind,
+            path: child.path(),
+            bytes: disk_usage(&child.path()),
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    }
+}
+
+/// Total size of the files under `path`, without following symlinks
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else { return 0 };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|children| children.flatten().map(|c| disk_usage(&c.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Remove an entry from disk
+pub fn remove(entry: &LocalEntry) -> std::io::Result<()> {
+    if fs::symlink_metadata(&entry.path)?.is_dir() {
+        fs::remove_dir_all(&entry.path)
+    } else {
+        fs::remove_file(&entry.path)
+    }
+}
+
+/// Parse an age such as `30m`, `12h`, `7d` or `2w`
+pub fn parse_age(value: &str) -> anyhow::Result<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid age '{}': expected e.g. 7d", value))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!("Invalid age '{}': unit must be s, m, h, d or w", value),
+    };
+    Ok(Duration::from_secs(number * unit_secs))
+}
+
+/// Human-readable age, e.g. `3d`
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("7d").unwrap(), Duration::from_secs(7 * 86400));
+        assert_eq!(parse_age("90m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_age("2w").unwrap(), Duration::from_secs(14 * 86400));
+        assert!(parse_age("7").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3y").is_err());
+    }
+
+    #[test]
+    fn test_scan_finds_local_state() {
+        let root = std::env::temp_dir().join(format!("pulse-prune-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let home = root.join("home");
+        let temp = root.join("tmp");
+        fs::create_dir_all(home.join("workspaces/shop")).unwrap();
+        fs::write(home.join("workspaces/shop/file.txt"), "12345").unwrap();
+        fs::create_dir_all(home.join("cache/cargo")).unwrap();
+        fs::create_dir_all(temp.join("pulse-replay-abc")).unwrap();
+        fs::create_dir_all(temp.join("pulse-bisect-def")).unwrap();
+        fs::create_dir_all(temp.join("unrelated")).unwrap();
+
+        let entries = scan(Some(&home), &temp);
+        let found: Vec<(LocalKind, String)> = entries
+            .iter()
+            .map(|e| (e.kind, e.path.file_name().unwrap().to_string_lossy().into_owned()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (LocalKind::Workspace, "shop".to_string()),
+                (LocalKind::Cache, "cargo".to_string()),
+                (LocalKind::Replay, "pulse-replay-abc".to_string()),
+                (LocalKind::Bisect, "pulse-bisect-def".to_string()),
+            ]
+        );
+        assert_eq!(entries[0].bytes, 5);
+
+        remove(&entries[0]).unwrap();
+        assert!(!home.join("workspaces/shop").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
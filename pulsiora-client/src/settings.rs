@@ -0,0 +1,115 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SETTINGS_FILE: &str = ".pulsiora.toml";
+
+/// Project-local defaults read from `.pulsiora.toml`
+///
+/// ```toml
+/// server = "http://ci.example.com:3000"
+/// repo = "owner/repo"
+/// pulsefile = "ci/Pulsefile"
+/// branch = "main"
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSettings {
+    pub server: Option<String>,
+    pub repo: Option<String>,
+    pub pulsefile: Option<String>,
+    pub branch: Option<String>,
+    /// Directory containing the settings file; relative paths resolve against it
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+impl ProjectSettings {
+    /// Find the nearest `.pulsiora.toml` from `start` upwards, stopping at the repository root
+    pub fn discover(start: &Path) -> anyhow::Result<Option<Self>> {
+        for dir in start.ancestors() {
+            let candidate = dir.join(SETTINGS_FILE);
+            if candidate.is_file() {
+                return Self::load(&candidate).map(Some);
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let mut settings: ProjectSettings = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))?;
+        settings.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(settings)
+    }
+
+    /// Pulsefile path from the settings, resolved against the project root
+    pub fn pulsefile_path(&self) -> Option<String> {
+        self.pulsefile
+            .as_ref()
+            .map(|p| self.root.join(p).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pulsiora-settings-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_discover_from_subdirectory() {
+        let root = temp_dir("discover");
+        fs::write(
+            root.join(SETTINGS_FILE),
+            "server = \"http://ci:3000\"\nrepo = \"owner/repo\"\npulsefile = \"ci/Pulsefile\"\nbranch = \"develop\"\n",
+        )
+        .unwrap();
+        let nested = root.join("src/module");
+        fs::create_dir_all(&nested).unwrap();
+
+        let settings = ProjectSettings::discover(&nested).unwrap().unwrap();
+        assert_eq!(settings.server.as_deref(), Some("http://ci:3000"));
+        assert_eq!(settings.repo.as_deref(), Some("owner/repo"));
+        assert_eq!(settings.branch.as_deref(), Some("develop"));
+        assert_eq!(
+            settings.pulsefile_path(),
+            Some(root.join("ci/Pulsefile").to_string_lossy().into_owned())
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_discover_stops_at_git_root() {
+        let root = temp_dir("gitroot");
+        fs::write(root.join(SETTINGS_FILE), "repo = \"outer/repo\"\n").unwrap();
+        let checkout = root.join("checkout");
+        fs::create_dir_all(checkout.join(".git")).unwrap();
+
+        assert_eq!(ProjectSettings::discover(&checkout).unwrap(), None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let root = temp_dir("unknown");
+        let path = root.join(SETTINGS_FILE);
+        fs::write(&path, "sever = \"http://typo\"\n").unwrap();
+
+        assert!(ProjectSettings::load(&path).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+// End-of-run summary for `pulse run`, so a failure can be read without
+// scrolling back through the full step output.
+
+use pulsiora_core::{PipelineExecution, PipelineStatus, StepResult, StepStatus};
+
+/// Number of trailing output lines shown for the failed step
+pub const EXCERPT_LINES: usize = 20;
+
+/// Number of the failed step's annotations shown
+pub const SUMMARY_ANNOTATIONS: usize = 10;
+
+/// The step that failed the pipeline: the last failed step, since a failing
+/// step that isn't allowed to fail stops the run
+pub fn failed_step(execution: &PipelineExecution) -> Option<&StepResult> {
+    if execution.status != PipelineStatus::Failed {
+        return None;
+    }
+    execution
+        .step_results
+        .iter()
+        .rev()
+        .find(|s| s.status == StepStatus::Failed)
+}
+
+/// Last `max_lines` lines of `output`, ignoring trailing blank lines
+pub fn tail_lines(output: &str, max_lines: usize) -> Vec<&str> {
+    let lines: Vec<&str> = output.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(max_lines)..].to_vec()
+}
+
+/// Format the failure summary block, or None if the pipeline didn't fail on a step
+pub fn failure_summary(execution: &PipelineExecution, rerun_command: &str) -> Option<String> {
+    let Some(step) = failed_step(execution) else {
+        // Limits can stop a pipeline before any step runs
+        let reason = execution.limit_exceeded.as_ref()?;
+        return Some(format!(
+            "──────── Failure summary ────────\nStopped: {}\n\nRerun with:\n  {}\n",
+            reason, rerun_command
+        ));
+    };
+
+    // Some tools only write errors to stdout; fall back to it when stderr is empty
+    let (stream, output) = if step.stderr.trim().is_empty() {
+        ("stdout", step.stdout.as_str())
+    } else {
+        ("stderr", step.stderr.as_str())
+    };
+
+    let mut summary = String::new();
+    summary.push_str("──────── Failure summary ────────\n");
+    if let Some(reason) = &execution.limit_exceeded {
+        summary.push_str(&format!("Stopped:     {}\n", reason));
+    }
+    summary.push_str(&format!("Failed step: {}\n", step.step_name));
+    match step.exit_code {
+        Some(code) => summary.push_str(&format!("Exit code:   {}\n", code)),
+        None => summary.push_str("Exit code:   none (terminated by signal or failed to start)\n"),
+    }
+
+    if !step.annotations.is_empty() {
+        summary.push_str(&format!("\nAnnotations ({}):\n", step.annotations.len()));
+        for annotation in step.annotations.iter().take(SUMMARY_ANNOTATIONS) {
+            summary.push_str(&format!("  {}\n", annotation));
+        }
+    }
+
+    let excerpt = tail_lines(output, EXCERPT_LINES);
+    if !excerpt.is_empty() {
+        summary.push_str(&format!("\nLast {} line(s) of {}:\n", excerpt.len(), stream));
+        for line in excerpt {
+            summary.push_str(&format!("  │ {}\n", line));
+        }
+    }
+
+    summary.push_str(&format!("\nRerun with:\n  {}\n", rerun_command));
+    Some(summary)
+}
+
+/// Shell-quote an argument for the suggested rerun command
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use pulsiora_core::{Annotation, AnnotationLevel, ExecutionMetrics, GitEvent, GitEventType, Repository};
+    use uuid::Uuid;
+
+    fn step(name: &str, status: StepStatus, stdout: &str, stderr: &str, exit_code: Option<i32>) -> StepResult {
+        StepResult {
+            step_name: name.to_string(),
+            status,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            exit_code,
+            duration_ms: 1,
+            started_at: Utc::now(),
+            completed_at: Some(Utc::now()),
+            encoding: None,
+            replacement_chars: 0,
+            stdout_bytes: 0,
+            stderr_bytes: 0,
+            log_ref: None,
+            hooks: vec![],
+            annotations: vec![],
+            coverage: None,
+            resources: None,
+        }
+    }
+
+    fn execution(status: PipelineStatus, step_results: Vec<StepResult>) -> PipelineExecution {
+        let repository = Repository {
+            owner: "local".to_string(),
+            name: "repo".to_string(),
+            full_name: "local/repo".to_string(),
+            clone_url: "local/repo".to_string(),
+            default_branch: "main".to_string(),
+        };
+        PipelineExecution {
+            id: Uuid::new_v4(),
+            pipeline_name: "test".to_string(),
+            pipeline_version: "1.0".to_string(),
+            pipeline_labels: Vec::new(),
+            environment: None,
+            repository: repository.clone(),
+            git_event: GitEvent {
+                event_type: GitEventType::Push,
+                repository,
+                branch: Some("main".to_string()),
+                tag: None,
+                release_name: None,
+                pull_request: None,
+                commit_sha: None,
+                commit_message: None,
+                sender: "manual".to_string(),
+                inputs: Default::default(),
+            },
+            status,
+            step_results,
+            phases: Vec::new(),
+            limit_exceeded: None,
+            coverage: None,
+            metrics: ExecutionMetrics::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
+            started_at: Utc::now(),
+            completed_at: Some(Utc::now()),
+        }
+    }
+
+    #[test]
+    fn test_summary_reports_failing_step_and_stderr_tail() {
+        let stderr: String = (1..=30).map(|i| format!("error line {}\n", i)).collect();
+        let exec = execution(
+            PipelineStatus::Failed,
+            vec![
+                step("lint", StepStatus::Failed, "", "allowed", Some(1)),
+                step("build", StepStatus::Failed, "noise", &stderr, Some(2)),
+            ],
+        );
+
+        let summary = failure_summary(&exec, "pulse run --pulsefile Pulsefile").unwrap();
+        assert!(summary.contains("Failed step: build"));
+        assert!(summary.contains("Exit code:   2"));
+        assert!(summary.contains("Last 20 line(s) of stderr"));
+        assert!(summary.contains("error line 30"));
+        assert!(summary.contains("error line 11"));
+        assert!(!summary.contains("error line 10\n"));
+        assert!(summary.contains("pulse run --pulsefile Pulsefile"));
+    }
+
+    #[test]
+    fn test_summary_falls_back_to_stdout() {
+        let exec = execution(
+            PipelineStatus::Failed,
+            vec![step("test", StepStatus::Failed, "assertion failed\n", "", Some(101))],
+        );
+        let summary = failure_summary(&exec, "pulse run").unwrap();
+        assert!(summary.contains("of stdout"));
+        assert!(summary.contains("assertion failed"));
+    }
+
+    #[test]
+    fn test_summary_lists_annotations() {
+        let mut failed = step("build", StepStatus::Failed, "", "", Some(1));
+        failed.annotations = (1..=12)
+            .map(|line| Annotation {
+                level: AnnotationLevel::Error,
+                message: "expected `;`".to_string(),
+                file: Some("src/lib.rs".to_string()),
+                line: Some(line),
+                column: None,
+            })
+            .collect();
+        let exec = execution(PipelineStatus::Failed, vec![failed]);
+
+        let summary = failure_summary(&exec, "pulse run").unwrap();
+        assert!(summary.contains("Annotations (12):\n  error: src/lib.rs:1: expected `;`\n"));
+        assert!(summary.contains("src/lib.rs:10:"));
+        assert!(!summary.contains("src/lib.rs:11:"));
+    }
+
+    #[test]
+    fn test_no_summary_for_successful_run() {
+        let exec = execution(
+            PipelineStatus::Success,
+            vec![step("lint", StepStatus::Failed, "", "allowed", Some(1))],
+        );
+        assert!(failure_summary(&exec, "pulse run").is_none());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("ci/Pulsefile"), "ci/Pulsefile");
+        assert_eq!(shell_quote("my file"), "'my file'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,71 @@
+// Colors in step output printed by `pulse`. They are kept on a terminal and
+// removed when output goes to a file or pipe, or when `NO_COLOR` is set.
+
+use pulsiora_runner::strip_ansi;
+use std::borrow::Cow;
+use std::io::IsTerminal;
+
+/// Whether step output printed to stdout keeps its colors
+pub fn stdout_colors() -> bool {
+    colors_enabled(std::io::stdout().is_terminal())
+}
+
+/// Whether step output printed to stderr keeps its colors
+pub fn stderr_colors() -> bool {
+    colors_enabled(std::io::stderr().is_terminal())
+}
+
+fn colors_enabled(terminal: bool) -> bool {
+    terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Step output ready to print, with colors only if `colors`
+pub fn render_output(text: &str, colors: bool) -> Cow<'_, str> {
+    if colors {
+        Cow::Borrowed(text)
+    } else {
+        strip_ansi(text)
+    }
+}
+
+/// `text` with the byte `ranges` in bold, if `colors`; ranges that aren't
+/// within `text` are left out
+pub fn highlight(text: &str, ranges: &[(usize, usize)], colors: bool) -> String {
+    if !colors {
+        return text.to_string();
+    }
+    let mut highlighted = String::with_capacity(text.len());
+    let mut at = 0;
+    for &(start, end) in ranges {
+        let (Some(before), Some(term)) = (text.get(at..start), text.get(start..end)) else {
+            continue;
+        };
+        highlighted.push_str(before);
+        highlighted.push_str(&format!("\u{1b}[1m{}\u{1b}[0m", term));
+        at = end;
+    }
+    highlighted.push_str(&text[at..]);
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_output() {
+        let colored = "\u{1b}[31merror\u{1b}[0m";
+        assert_eq!(render_output(colored, true), colored);
+        assert_eq!(render_output(colored, false), "error");
+    }
+
+    #[test]
+    fn test_highlight() {
+        let text = "error: connection refused";
+        assert_eq!(highlight(text, &[(0, 5), (18, 25)], false), text);
+        assert_eq!(
+            highlight(text, &[(0, 5), (18, 25), (40, 50)], true),
+            "\u{1b}[1merror\u{1b}[0m: connection \u{1b}[1mrefused\u{1b}[0m"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+// Waterfall view of an execution's timeline for `pulse timeline`: a row per
+// phase and step, with a bar from when it started to when it ended, scaled
+// to the execution's duration.
+
+use serde_json::Value;
+
+/// Widest a name column gets before names are cut short
+const MAX_NAME_WIDTH: usize = 24;
+
+/// Bar glyphs for phases, steps, and steps still running
+const PHASE: char = '░';
+const STEP: char = '█';
+const RUNNING: char = '▒';
+
+/// `ms` as a short duration, e.g. `850ms`, `12.4s`, or `3m 5s`
+pub fn format_ms(ms: u64) -> String {
+    match ms {
+        ms if ms < 1000 => format!("{}ms", ms),
+        ms if ms < 60_000 => format!("{:.1}s", ms as f64 / 1000.0),
+        ms => format!("{}m {}s", ms / 60_000, ms / 1000 % 60),
+    }
+}
+
+/// Render a timeline from `GET /api/v1/executions/:id/timeline` with bars
+/// `width` cells wide
+pub fn render_waterfall(timeline: &Value, width: usize) -> String {
+    let width = width.max(10);
+    let ms = |entry: &Value, key: &str| entry[key].as_u64().unwrap_or_default();
+    let entries = timeline["entries"].as_array().cloned().unwrap_or_default();
+    let duration = ms(timeline, "duration_ms").max(1);
+    let name_width = entries
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap_or_default().chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(4, MAX_NAME_WIDTH);
+    let cell = |offset: u64| ((offset as u128 * width as u128) / duration as u128) as usize;
+
+    let mut out = format!(
+        "{} in {}",
+        timeline["status"].as_str().unwrap_or("Unknown"),
+        format_ms(ms(timeline, "duration_ms"))
+    );
+    if let Some(queued) = timeline["queue_ms"].as_u64() {
+        out.push_str(&format!(", after {} queued", format_ms(queued)));
+    }
+    if timeline["lanes"].as_u64().unwrap_or(1) > 1 {
+        out.push_str(&format!(", up to {} at once", timeline["lanes"]));
+    }
+    out.push('\n');
+
+    for entry in &entries {
+        let name: String = entry["name"].as_str().unwrap_or_default().chars().take(name_width).collect();
+        let (start, end) = (ms(entry, "start_ms"), ms(entry, "end_ms"));
+        let running = entry["running"].as_bool().unwrap_or_default();
+        let glyph = match entry["kind"].as_str() {
+            Some("phase") => PHASE,
+            _ if running => RUNNING,
+            _ => STEP,
+        };
+        let from = cell(start).min(width - 1);
+        let to = cell(end).clamp(from + 1, width);
+        let bar: String = (0..width)
+            .map(|i| if (from..to).contains(&i) { glyph } else { ' ' })
+            .collect();
+        let mut detail = format_ms(end - start);
+        match entry["status"].as_str() {
+            _ if running => detail.push_str(" running"),
+            Some(status) if status != "Success" => detail.push_str(&format!(" {}", status.to_lowercase())),
+            _ => {}
+        }
+        out.push_str(&format!("{:<name_width$} │{}│ {}\n", name, bar, detail));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_format_ms() {
+        assert_eq!(format_ms(850), "850ms");
+        assert_eq!(format_ms(12_400), "12.4s");
+        assert_eq!(format_ms(185_000), "3m 5s");
+    }
+
+    #[test]
+    fn test_render_waterfall() {
+        let timeline = json!({
+            "status": "Running",
+            "queue_ms": 1500,
+            "duration_ms": 10_000,
+            "lanes": 2,
+            "entries": [
+                {"kind": "phase", "name": "clone", "start_ms": 0, "end_ms": 1000, "running": false, "lane": 0},
+                {"kind": "step", "name": "db", "status": "Running", "start_ms": 1000, "end_ms": 10_000, "running": true, "lane": 0},
+                {"kind": "step", "name": "migrate", "status": "Failed", "start_ms": 2000, "end_ms": 4000, "running": false, "lane": 1},
+                {"kind": "step", "name": "lint", "status": "Success", "start_ms": 4000, "end_ms": 4001, "running": false, "lane": 1},
+            ],
+        });
+        let waterfall = render_waterfall(&timeline, 10);
+        let lines: Vec<&str> = waterfall.lines().collect();
+        assert_eq!(lines[0], "Running in 10.0s, after 1.5s queued, up to 2 at once");
+        assert_eq!(lines[1], "clone   │░         │ 1.0s");
+        assert_eq!(lines[2], "db      │ ▒▒▒▒▒▒▒▒▒│ 9.0s running");
+        assert_eq!(lines[3], "migrate │  ██      │ 2.0s failed");
+        // Shorter than a cell still gets one
+        assert_eq!(lines[4], "lint    │    █     │ 1ms");
+    }
+}
@@ -0,0 +1,107 @@
+// Messages between the server and remote agents. Agents register with the
+// server's agent token, long-poll for jobs, run them with pulsiora-runner and
+// send the execution's events and result back, all as JSON over HTTP.
+
+use crate::models::{ExecutionEvent, ExecutionLimits, GitEvent, Pipeline, PipelineExecution, SandboxPolicy, StepResult};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Sent by an agent when it starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentRegistration {
+    pub name: String,
+    /// Operating system, e.g. `linux` or `macos`
+    pub os: String,
+    pub arch: String,
+    /// Free-form labels describing the agent, e.g. `gpu`
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl AgentRegistration {
+    /// A registration for this machine's OS and architecture
+    pub fn for_host(name: impl Into<String>, labels: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            labels,
+        }
+    }
+}
+
+/// The server's answer to a registration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentRegistered {
+    pub agent_id: Uuid,
+    /// Token the agent authenticates its later calls with
+    pub token: String,
+}
+
+/// A job handed to an agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentJob {
+    pub execution_id: Uuid,
+    pub pipeline: Pipeline,
+    pub git_event: GitEvent,
+    pub limits: ExecutionLimits,
+    /// Steps already finished before an interruption; the agent continues
+    /// after them
+    #[serde(default)]
+    pub completed_steps: Vec<StepResult>,
+    pub queued_at: DateTime<Utc>,
+    /// The repository's variables, set for every step under its own `env`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The repository's secrets, set for every step and masked in its output
+    #[serde(default)]
+    pub secrets: BTreeMap<String, String>,
+    /// The repository's sandbox policy when its steps are sandboxed; the
+    /// agent refuses the job if it can't sandbox them
+    #[serde(default)]
+    pub sandbox: Option<SandboxPolicy>,
+}
+
+/// Progress of a job, sent while it runs. A report without events tells the
+/// server the agent is still working on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentReport {
+    #[serde(default)]
+    pub events: Vec<ExecutionEvent>,
+}
+
+/// The server's answer to a report
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AgentReportAck {
+    /// The execution was cancelled; the agent should stop the job and send
+    /// its result
+    #[serde(default)]
+    pub cancel: bool,
+}
+
+/// How a job ended on the agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum AgentOutcome {
+    /// The pipeline ran, whatever its status
+    Finished { execution: Box<PipelineExecution> },
+    /// The pipeline couldn't be run, e.g. the repository couldn't be cloned
+    Errored {
+        error: String,
+        /// The error came from the agent's side, so the job may be retried
+        #[serde(default)]
+        infrastructure: bool,
+    },
+}
+
+impl AgentOutcome {
+    /// The outcome of a job that hit `error` before its pipeline could run
+    pub fn errored(error: &crate::PulsioraError) -> Self {
+        Self::Errored {
+            error: error.to_string(),
+            infrastructure: error.is_infrastructure(),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+// Audit log records: one JSON object per line (NDJSON), so logs can be
+// shipped to a SIEM or read with standard line-oriented tools.
+//
+// Every line has `version`, `timestamp` (RFC 3339, UTC) and `event`; the
+// remaining fields depend on `event`. `actor` is set when an authenticated
+// user caused the event. Fields are only ever added within a version;
+// renaming or removing one bumps `AUDIT_FORMAT_VERSION`.
+
+use crate::error::{PulsioraError, Result};
+use crate::models::{
+    ExecutionLimits, GitEventType, PipelineStatus, PulsefileSource, RepoRole, SandboxPolicy, WebhookMapping,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Version written to every record
+pub const AUDIT_FORMAT_VERSION: u32 = 1;
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub version: u32,
+    pub timestamp: DateTime<Utc>,
+    /// User whose API call caused the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+/// What happened; serialized as `"event": "<snake_case name>"` plus its fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// A webhook was accepted and its job queued
+    ExecutionQueued {
+        execution_id: Uuid,
+        repository: String,
+        trigger: GitEventType,
+        #[serde(default)]
+        commit_sha: Option<String>,
+        sender: String,
+    },
+    ExecutionStarted {
+        execution_id: Uuid,
+        repository: String,
+        pipeline: String,
+    },
+    ExecutionFinished {
+        execution_id: Uuid,
+        repository: String,
+        pipeline: String,
+        status: PipelineStatus,
+        duration_ms: u64,
+        #[serde(default)]
+        limit_exceeded: Option<String>,
+    },
+    /// The job couldn't be run, e.g. because its Pulsefile is invalid
+    ExecutionErrored {
+        execution_id: Uuid,
+        repository: String,
+        error: String,
+    },
+    /// The job hit an infrastructure error, e.g. a failed clone, and was
+    /// queued to run again
+    ExecutionRetried {
+        execution_id: Uuid,
+        repository: String,
+        /// The try that failed, counting from 1
+        attempt: u32,
+        retry_at: DateTime<Utc>,
+        error: String,
+    },
+    /// The server restarted while the execution was running
+    ExecutionInterrupted {
+        execution_id: Uuid,
+        repository: String,
+        /// Steps that had finished
+        completed_steps: usize,
+    },
+    /// A queued or running execution was cancelled
+    ExecutionCancelled {
+        execution_id: Uuid,
+        repository: String,
+    },
+    /// An interrupted execution was continued from step `from_step`
+    ExecutionResumed {
+        execution_id: Uuid,
+        repository: String,
+        from_step: usize,
+    },
+    InstanceSetup {
+        organization: String,
+    },
+    RepoRegistered {
+        repository: String,
+        #[serde(default)]
+        organization: Option<String>,
+    },
+    RepoUnregistered {
+        repository: String,
+    },
+    /// A user's role on a repository was set, or removed if `role` is unset
+    RepoRoleChanged {
+        repository: String,
+        username: String,
+        #[serde(default)]
+        role: Option<RepoRole>,
+    },
+    UserCreated {
+        username: String,
+        organization: String,
+        admin: bool,
+    },
+    UserRemoved {
+        username: String,
+    },
+    OrganizationCreated {
+        organization: String,
+    },
+    /// An operator set an organization's quotas
+    OrganizationQuotasChanged {
+        organization: String,
+        max_concurrent_runs: Option<usize>,
+        max_storage_bytes: Option<u64>,
+    },
+    /// A remote agent registered to run jobs
+    AgentRegistered {
+        agent_id: Uuid,
+        name: String,
+        os: String,
+        arch: String,
+    },
+    RepoLimitsUpdated {
+        repository: String,
+        limits: ExecutionLimits,
+    },
+    RepoSandboxUpdated {
+        repository: String,
+        sandbox: SandboxPolicy,
+    },
+    RepoWebhookMappingUpdated {
+        repository: String,
+        mapping: WebhookMapping,
+    },
+    RepoEnvUpdated {
+        repository: String,
+        env: BTreeMap<String, String>,
+    },
+    /// A repository's stored Pulsefile was replaced by a new revision
+    RepoPulsefileUpdated {
+        repository: String,
+        revision: u32,
+        pulsefile_sha256: String,
+    },
+    RepoPulsefileSourceUpdated {
+        repository: String,
+        source: PulsefileSource,
+    },
+    /// A stored webhook delivery was handled again
+    WebhookReplayed {
+        delivery_id: String,
+        repository: Option<String>,
+        execution_ids: Vec<Uuid>,
+    },
+    /// A repository's settings were changed; its webhook secret isn't recorded
+    RepoSettingsUpdated {
+        repository: String,
+        webhook_secret_set: bool,
+        default_branch: Option<String>,
+        allowed_events: Vec<GitEventType>,
+        execution_timeout_secs: Option<u64>,
+    },
+    /// An environment was marked as rolled back to an earlier deployment
+    DeploymentRolledBack {
+        repository: String,
+        environment: String,
+        /// The deployment recorded for the rollback
+        deployment_id: Uuid,
+        /// The execution whose deployment the environment is back to
+        execution_id: Uuid,
+    },
+    /// A path-scoped pipeline was added to a repository or replaced
+    RepoPathPipelineSet {
+        repository: String,
+        name: String,
+        paths: Vec<String>,
+        pulsefile_sha256: String,
+    },
+    RepoPathPipelineRemoved {
+        repository: String,
+        name: String,
+    },
+    /// An outgoing webhook was added to a repository
+    RepoHookAdded {
+        repository: String,
+        hook_id: Uuid,
+        url: String,
+    },
+    RepoHookRemoved {
+        repository: String,
+        hook_id: Uuid,
+    },
+    /// A repository secret was set; its value isn't recorded
+    SecretSet {
+        repository: String,
+        name: String,
+    },
+    SecretRemoved {
+        repository: String,
+        name: String,
+    },
+    StatusContextsUpdated {
+        repository: String,
+        contexts: Vec<String>,
+    },
+    BasePulsefileUpdated {
+        organization: String,
+        /// The base Pulsefile was removed rather than replaced
+        cleared: bool,
+    },
+    /// A read-only link to an execution was created
+    ExecutionShared {
+        execution_id: Uuid,
+        share_id: Uuid,
+        expires_at: DateTime<Utc>,
+    },
+    ShareRevoked {
+        execution_id: Uuid,
+        share_id: Uuid,
+    },
+}
+
+impl AuditRecord {
+    pub fn new(actor: Option<String>, event: AuditEvent) -> Self {
+        Self {
+            version: AUDIT_FORMAT_VERSION,
+            timestamp: Utc::now(),
+            actor,
+            event,
+        }
+    }
+
+    /// The record as a single line, including the trailing newline
+    pub fn to_line(&self) -> String {
+        // Serializing plain data into a String can't fail
+        let mut line = serde_json::to_string(self).unwrap_or_default();
+        line.push('\n');
+        line
+    }
+}
+
+/// Parse one audit log line
+pub fn parse_audit_record(line: &str) -> Result<AuditRecord> {
+    let record: AuditRecord = serde_json::from_str(line)
+        .map_err(|e| PulsioraError::ParseError(format!("Invalid audit record: {}", e)))?;
+    if record.version > AUDIT_FORMAT_VERSION {
+        return Err(PulsioraError::ParseError(format!(
+            "Unsupported audit record version {}",
+            record.version
+        )));
+    }
+    Ok(record)
+}
+
+/// Parse a whole audit log, skipping blank lines
+pub fn parse_audit_log(input: &str) -> Result<Vec<AuditRecord>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            parse_audit_record(line).map_err(|e| PulsioraError::ParseError(format!("line {}: {}", index + 1, e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_record_round_trips_as_one_line() {
+        let record = AuditRecord::new(
+            Some("alice".to_string()),
+            AuditEvent::RepoRegistered {
+                repository: "acme/shop".to_string(),
+                organization: None,
+            },
+        );
+        let line = record.to_line();
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.matches('\n').count(), 1);
+        assert!(line.contains(r#""event":"repo_registered""#));
+        assert!(line.contains(r#""version":1"#));
+
+        assert_eq!(parse_audit_record(&line).unwrap(), record);
+    }
+
+    #[test]
+    fn test_parse_audit_log() {
+        let input = concat!(
+            r#"{"version":1,"timestamp":"2026-01-05T10:00:00Z","event":"execution_started","execution_id":"5b1f6a3e-8f3c-4d2a-9a57-1f2e3d4c5b6a","repository":"acme/shop","pipeline":"build"}"#,
+            "\n\n",
+            r#"{"version":1,"timestamp":"2026-01-05T10:01:00Z","actor":"alice","event":"base_pulsefile_updated","organization":"acme","cleared":true}"#,
+            "\n"
+        );
+        let records = parse_audit_log(input).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(matches!(records[0].event, AuditEvent::ExecutionStarted { .. }));
+        assert_eq!(records[1].actor.as_deref(), Some("alice"));
+
+        let err = parse_audit_log("{}\n").unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+        let future = r#"{"version":2,"timestamp":"2026-01-05T10:00:00Z","event":"repo_unregistered","repository":"a/b"}"#;
+        assert!(parse_audit_record(future).is_err());
+    }
+}
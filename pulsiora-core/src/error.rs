@@ -22,7 +22,50 @@ pub enum PulsioraError {
 
     #[error("Network error: {0}")]
     NetworkError(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
+    #[error("Clone failed: {0}")]
+    CloneError(String),
+
+    #[error("Agent lost: {0}")]
+    AgentLost(String),
+
+    #[error("Backend unavailable: {0}")]
+    BackendUnavailable(String),
+}
+
+impl PulsioraError {
+    /// Whether the error came from what runs a pipeline, i.e. a failed clone,
+    /// a network error, a lost agent or an unavailable backend, rather than
+    /// from the pipeline itself, so running it again may succeed
+    pub fn is_infrastructure(&self) -> bool {
+        matches!(
+            self,
+            Self::CloneError(_) | Self::NetworkError(_) | Self::AgentLost(_) | Self::BackendUnavailable(_)
+        )
+    }
 }
 
 pub type Result<T> = std::result::Result<T, PulsioraError>;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_infrastructure_errors_are_retried() {
+        assert!(PulsioraError::CloneError("git failed: connection reset".to_string()).is_infrastructure());
+        assert!(PulsioraError::NetworkError("Failed to fetch Pulsefile".to_string()).is_infrastructure());
+        assert!(PulsioraError::AgentLost("Agent a1 stopped reporting for 60s".to_string()).is_infrastructure());
+        assert!(PulsioraError::BackendUnavailable("firejail isn't on PATH".to_string()).is_infrastructure());
+
+        assert!(!PulsioraError::ExecutionError("Step \"test\" exited with code 1".to_string()).is_infrastructure());
+        assert!(!PulsioraError::ParseError("bad step".to_string()).is_infrastructure());
+        assert!(!PulsioraError::PipelineNotFound("Pulsefile".to_string()).is_infrastructure());
+        assert!(!PulsioraError::IoError(std::io::ErrorKind::PermissionDenied.into()).is_infrastructure());
+        assert!(!PulsioraError::StorageError("disk full".to_string()).is_infrastructure());
+    }
+}
+
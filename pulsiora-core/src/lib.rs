@@ -1,6 +1,11 @@
 pub mod models;
 pub mod error;
+pub mod triggers;
+pub mod audit;
+pub mod agent;
 
 pub use models::*;
 pub use error::*;
-
+pub use triggers::*;
+pub use audit::*;
+pub use agent::*;
@@ -1,20 +1,173 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::triggers::Triggers;
 
 /// Represents a complete pipeline definition
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Pipeline {
     pub name: String,
     pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Free-form labels used to filter executions, e.g. "deploy"
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// People or teams responsible for the pipeline
+    #[serde(default)]
+    pub owners: Vec<String>,
+    /// Environment a successful run deploys to, e.g. "production"
+    #[serde(default)]
+    pub environment: Option<String>,
     pub triggers: Triggers,
     pub steps: Vec<Step>,
+    /// Pipeline-wide variables, referenced as `${vars.NAME}`
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    #[serde(default)]
+    pub options: PipelineOptions,
 }
 
-/// Trigger configuration for a pipeline
+/// How step scripts are run, from the `options {}` block
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct Triggers {
-    pub git: GitTriggers,
+pub struct PipelineOptions {
+    /// Shell that runs step scripts; `sh` (PowerShell on Windows) when unset
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Shell used instead of `shell` on Windows hosts, so one Pulsefile can
+    /// run on both
+    #[serde(default)]
+    pub windows_shell: Option<String>,
+    /// Stop at the first failing step; otherwise the remaining steps still
+    /// run and the pipeline fails at the end
+    #[serde(default = "default_fail_fast")]
+    pub fail_fast: bool,
+    /// Abort a script at its first failing command instead of only
+    /// checking the exit code of the last one
+    #[serde(default)]
+    pub set_e: bool,
+    /// Container image steps run in when the runner uses a container backend
+    #[serde(default)]
+    pub image: Option<String>,
+    /// How long a cancelled or timed-out step's processes get to exit after
+    /// SIGTERM before they are killed
+    #[serde(default = "default_kill_grace_secs")]
+    pub kill_grace_secs: u64,
+    /// Script run before every step, in the step's directory and environment
+    #[serde(default)]
+    pub before_each: Option<String>,
+    /// Script run after every step that wasn't interrupted, with the step's
+    /// outcome in `PULSE_STEP_STATUS` and `PULSE_STEP_DURATION_MS`
+    #[serde(default)]
+    pub after_each: Option<String>,
+    /// Regexes, by name, that turn lines of step output into annotations.
+    /// Named groups `message` (required), `file`, `line`, `column` and
+    /// `level` fill in the annotation.
+    #[serde(default)]
+    pub problem_matchers: BTreeMap<String, String>,
+    /// What happens to ANSI escape sequences, such as colors, in the output
+    /// stored on step results
+    #[serde(default)]
+    pub ansi: AnsiMode,
+}
+
+/// Handling of ANSI escape sequences in stored step output
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnsiMode {
+    /// Store output as the step wrote it; clients render or strip the
+    /// sequences when showing it
+    #[default]
+    Keep,
+    /// Remove the sequences before output is stored
+    Strip,
+}
+
+impl AnsiMode {
+    /// The mode as written in a Pulsefile, e.g. `strip`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnsiMode::Keep => "keep",
+            AnsiMode::Strip => "strip",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(AnsiMode::Keep),
+            "strip" => Some(AnsiMode::Strip),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `name` can name an environment: letters, digits, `-`, `_` and `.`
+pub fn valid_environment_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Grace period between SIGTERM and SIGKILL when a step is stopped
+pub const DEFAULT_KILL_GRACE_SECS: u64 = 10;
+
+fn default_fail_fast() -> bool {
+    true
+}
+
+fn default_kill_grace_secs() -> u64 {
+    DEFAULT_KILL_GRACE_SECS
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            shell: None,
+            windows_shell: None,
+            fail_fast: true,
+            set_e: false,
+            image: None,
+            kill_grace_secs: DEFAULT_KILL_GRACE_SECS,
+            before_each: None,
+            after_each: None,
+            problem_matchers: BTreeMap::new(),
+            ansi: AnsiMode::Keep,
+        }
+    }
+}
+
+impl Pipeline {
+    /// Merge an organization-wide base pipeline into this one.
+    ///
+    /// Base steps are mandatory: they run before the pipeline's own steps and
+    /// replace any of its steps with the same name. Labels and owners are
+    /// combined, and the pipeline's own vars win over base vars. Name,
+    /// version, description, environment, triggers and options always come
+    /// from this pipeline.
+    /// Returns the names of the steps that were replaced.
+    pub fn apply_base(&mut self, base: &Pipeline) -> Vec<String> {
+        let own_steps = std::mem::replace(&mut self.steps, base.steps.clone());
+        let mut replaced = Vec::new();
+        for step in own_steps {
+            if base.steps.iter().any(|s| s.name == step.name) {
+                replaced.push(step.name);
+            } else {
+                self.steps.push(step);
+            }
+        }
+
+        for (own, inherited) in [(&mut self.labels, &base.labels), (&mut self.owners, &base.owners)] {
+            let mut merged = inherited.clone();
+            merged.extend(own.drain(..).filter(|v| !inherited.contains(v)));
+            *own = merged;
+        }
+
+        let mut vars = base.vars.clone();
+        vars.append(&mut self.vars);
+        self.vars = vars;
+
+        replaced
+    }
 }
 
 /// Git event triggers
@@ -28,6 +181,15 @@ pub struct GitTriggers {
     pub on_branch_create: bool,
     pub on_branch_delete: bool,
     pub branches: Vec<String>, // Supports patterns like "*", "main", "feature/*"
+    /// Event senders that never trigger the pipeline, e.g. "dependabot[bot]"
+    #[serde(default)]
+    pub ignore_senders: Vec<String>,
+    /// Pull request events only trigger when the PR carries one of these labels
+    #[serde(default)]
+    pub pr_labels: Vec<String>,
+    /// Skip pull request events for draft PRs
+    #[serde(default)]
+    pub ignore_drafts: bool,
 }
 
 /// A pipeline step
@@ -36,6 +198,238 @@ pub struct Step {
     pub name: String,
     pub run: String,
     pub allow_failure: bool,
+    /// User to run the step as (name or numeric uid)
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Group to run the step as (name or numeric gid)
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Octal file mode creation mask, e.g. "0022"
+    #[serde(default)]
+    pub umask: Option<String>,
+    /// Seconds the step may run before it is killed and fails
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Extra environment variables for the step's process
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Keep the process running for the following steps, e.g. a database for
+    /// integration tests; it is stopped when the pipeline finishes
+    #[serde(default)]
+    pub background: bool,
+    /// Run the step under a pseudo-terminal, for tools that only show colors
+    /// or progress on one. Its stdout and stderr are then both captured as
+    /// stdout.
+    #[serde(default)]
+    pub tty: bool,
+    /// How to tell that a background step is ready for the next step
+    #[serde(default)]
+    pub ready_when: Option<ReadinessCheck>,
+    /// Files or directories, relative to the working directory, kept after the step
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Character encoding the step writes its output in, e.g. "shift_jis";
+    /// UTF-8 when unset
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Container image for this step, overriding the pipeline's `image`
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Remote host that runs the step instead of the runner, e.g.
+    /// `ssh://deploy@prod-box`
+    #[serde(default)]
+    pub runner: Option<String>,
+    /// Reports the step writes, read after it finishes
+    #[serde(default)]
+    pub reports: StepReports,
+    /// Shared action the step runs instead of `run`, e.g.
+    /// `pulsiora/checkout@v1`
+    #[serde(default)]
+    pub uses: Option<String>,
+    /// Inputs for the action in `uses`
+    #[serde(default)]
+    pub with: BTreeMap<String, String>,
+}
+
+/// Reports a step writes, from its `reports` block
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StepReports {
+    /// Coverage report, LCOV or Cobertura XML, relative to the working directory
+    #[serde(default)]
+    pub coverage: Option<String>,
+    /// Line coverage percentage below which the step fails
+    #[serde(default)]
+    pub min_coverage: Option<f64>,
+}
+
+/// Line coverage read from a coverage report
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub lines_covered: u64,
+    pub lines_total: u64,
+}
+
+impl CoverageReport {
+    /// Percentage of lines covered; 100 when there are no lines to cover
+    pub fn percent(&self) -> f64 {
+        if self.lines_total == 0 {
+            return 100.0;
+        }
+        self.lines_covered as f64 * 100.0 / self.lines_total as f64
+    }
+
+    /// Coverage of several reports together, e.g. every step of an execution
+    pub fn combine<'a>(reports: impl IntoIterator<Item = &'a CoverageReport>) -> Option<CoverageReport> {
+        let mut reports = reports.into_iter().peekable();
+        reports.peek()?;
+        Some(reports.fold(CoverageReport { lines_covered: 0, lines_total: 0 }, |total, report| CoverageReport {
+            lines_covered: total.lines_covered + report.lines_covered,
+            lines_total: total.lines_total + report.lines_total,
+        }))
+    }
+}
+
+impl std::fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1}% ({}/{} lines)", self.percent(), self.lines_covered, self.lines_total)
+    }
+}
+
+/// Readiness condition for a background step
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReadinessCheck {
+    /// URL polled until it answers with a 2xx status
+    #[serde(default)]
+    pub http: Option<String>,
+    /// How long to wait before the step fails
+    pub timeout_secs: u64,
+}
+
+impl Default for ReadinessCheck {
+    fn default() -> Self {
+        Self {
+            http: None,
+            timeout_secs: 60,
+        }
+    }
+}
+
+/// Remote host from a step's `runner: "ssh://user@host:port"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshTarget {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl SshTarget {
+    /// Parse an `ssh://[user@]host[:port]` URL; IPv6 hosts go in brackets
+    pub fn parse(url: &str) -> crate::Result<Self> {
+        let invalid = |reason: &str| {
+            crate::PulsioraError::InvalidConfiguration(format!("Invalid runner \"{}\": {}", url, reason))
+        };
+        let rest = url
+            .strip_prefix("ssh://")
+            .ok_or_else(|| invalid("only ssh:// runners are supported"))?;
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+        let (user, address) = match rest.rsplit_once('@') {
+            Some((user, address)) => (Some(user.to_string()), address),
+            None => (None, rest),
+        };
+        let (host, port) = match address.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, after) = bracketed.split_once(']').ok_or_else(|| invalid("unclosed '['"))?;
+                (host, after.strip_prefix(':'))
+            }
+            None => match address.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (address, None),
+            },
+        };
+        let port = port
+            .map(|p| p.parse::<u16>().map_err(|_| invalid("port must be a number")))
+            .transpose()?;
+
+        // A leading '-' would be read as an ssh option
+        let valid = |part: &str| {
+            !part.is_empty() && !part.starts_with('-') && !part.contains(|c: char| c.is_whitespace() || c == '/')
+        };
+        if !valid(host) {
+            return Err(invalid("missing or invalid host"));
+        }
+        if user.as_deref().is_some_and(|u| !valid(u)) {
+            return Err(invalid("invalid user"));
+        }
+        Ok(Self {
+            user,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// Where a step's `uses` action comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionRef {
+    /// A git repository at a tag, branch or commit. `repository` is either a
+    /// URL or `owner/name` on the runner's action host.
+    Git { repository: String, rev: String },
+    /// A `.tar.gz` bundle published at a URL, pinned to the SHA-256 of the
+    /// archive as lowercase hex
+    Archive { url: String, sha256: String },
+}
+
+impl ActionRef {
+    /// Parse `owner/name@rev`, `<git url>@rev`, or the URL of a `.tar.gz`
+    /// bundle followed by `#sha256=<digest>`. Git actions must name a
+    /// version, and archives their digest.
+    pub fn parse(reference: &str) -> crate::Result<Self> {
+        let invalid = |reason: &str| {
+            crate::PulsioraError::InvalidConfiguration(format!("Invalid action \"{}\": {}", reference, reason))
+        };
+        let is_url = ["https://", "http://", "file://"].iter().any(|scheme| reference.starts_with(scheme));
+        let (location, digest) = match reference.split_once("#sha256=") {
+            Some((location, digest)) => (location, Some(digest)),
+            None => (reference, None),
+        };
+        if is_url && (location.ends_with(".tar.gz") || location.ends_with(".tgz")) {
+            let sha256 = digest
+                .filter(|digest| digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()))
+                .ok_or_else(|| invalid("pin the archive's SHA-256, e.g. \"https://.../action.tar.gz#sha256=<hex>\""))?;
+            return Ok(Self::Archive {
+                url: location.to_string(),
+                sha256: sha256.to_ascii_lowercase(),
+            });
+        }
+
+        // The version follows the last '@', which must come after the last
+        // '/' so that `https://user@host/...` isn't mistaken for one
+        let (repository, rev) = reference
+            .rsplit_once('@')
+            .filter(|(_, rev)| !rev.contains('/'))
+            .ok_or_else(|| invalid("pin a version, e.g. \"owner/name@v1\""))?;
+        // A leading '-' would be read as a git option
+        if rev.is_empty() || rev.starts_with('-') || rev.contains(|c: char| c.is_whitespace() || c == ':') {
+            return Err(invalid("invalid version"));
+        }
+        if !is_url {
+            let name_part = |part: &str| {
+                !part.is_empty()
+                    && !part.starts_with(['-', '.'])
+                    && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            };
+            let valid = repository
+                .split_once('/')
+                .is_some_and(|(owner, name)| name_part(owner) && name_part(name));
+            if !valid {
+                return Err(invalid("expected owner/name@version or a URL"));
+            }
+        }
+        Ok(Self::Git {
+            repository: repository.to_string(),
+            rev: rev.to_string(),
+        })
+    }
 }
 
 /// Git event types that can trigger pipelines
@@ -48,6 +442,40 @@ pub enum GitEventType {
     Release,
     BranchCreate,
     BranchDelete,
+    /// A run requested through the API rather than by the repository
+    Manual,
+}
+
+impl GitEventType {
+    /// The event's name as used in webhooks, e.g. `pull_request`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GitEventType::Push => "push",
+            GitEventType::PullRequest => "pull_request",
+            GitEventType::Merge => "merge",
+            GitEventType::Tag => "tag",
+            GitEventType::Release => "release",
+            GitEventType::BranchCreate => "branch_create",
+            GitEventType::BranchDelete => "branch_delete",
+            GitEventType::Manual => "manual",
+        }
+    }
+
+    /// The event named as in webhooks, e.g. `pull_request`; None for
+    /// unknown names
+    pub fn parse(name: &str) -> Option<Self> {
+        const ALL: [GitEventType; 8] = [
+            GitEventType::Push,
+            GitEventType::PullRequest,
+            GitEventType::Merge,
+            GitEventType::Tag,
+            GitEventType::Release,
+            GitEventType::BranchCreate,
+            GitEventType::BranchDelete,
+            GitEventType::Manual,
+        ];
+        ALL.into_iter().find(|event| event.as_str() == name)
+    }
 }
 
 impl From<&str> for GitEventType {
@@ -60,6 +488,7 @@ impl From<&str> for GitEventType {
             "release" => GitEventType::Release,
             "branch_create" => GitEventType::BranchCreate,
             "branch_delete" => GitEventType::BranchDelete,
+            "manual" => GitEventType::Manual,
             _ => GitEventType::Push, // Default
         }
     }
@@ -72,9 +501,49 @@ pub struct GitEvent {
     pub repository: Repository,
     pub branch: Option<String>,
     pub tag: Option<String>,
+    /// Release name for release events
+    #[serde(default)]
+    pub release_name: Option<String>,
     pub pull_request: Option<PullRequest>,
     pub commit_sha: Option<String>,
+    #[serde(default)]
+    pub commit_message: Option<String>,
     pub sender: String,
+    /// Parameters of a manual run, passed to steps as `PULSE_INPUT_<NAME>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, String>,
+}
+
+impl GitEvent {
+    /// Whether the commit message asks CI to skip this event with `[skip ci]` or `[ci skip]`
+    pub fn skip_requested(&self) -> bool {
+        self.commit_message.as_deref().is_some_and(|message| {
+            let message = message.to_lowercase();
+            message.contains("[skip ci]") || message.contains("[ci skip]")
+        })
+    }
+
+    /// Git ref holding the code the event is about: its commit, else its tag
+    /// or branch, else the head of its pull request
+    pub fn source_ref(&self) -> Option<String> {
+        self.commit_sha
+            .clone()
+            .or_else(|| self.tag.clone())
+            .or_else(|| self.branch.clone())
+            .or_else(|| self.pull_request.as_ref().map(|pr| format!("refs/pull/{}/head", pr.number)))
+    }
+
+    /// Whether the event runs code from outside the repository: a pull
+    /// request whose head is in another repository, or that doesn't say where
+    /// its head is
+    pub fn from_fork(&self) -> bool {
+        self.event_type == GitEventType::PullRequest
+            && self
+                .pull_request
+                .as_ref()
+                .and_then(|pr| pr.head_repository.as_deref())
+                .is_none_or(|head| head != self.repository.full_name)
+    }
 }
 
 /// Repository information
@@ -95,6 +564,14 @@ pub struct PullRequest {
     pub base_branch: String,
     pub head_branch: String,
     pub state: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Full name of the repository the head branch is in, which differs
+    /// from the base repository for pull requests from forks
+    #[serde(default)]
+    pub head_repository: Option<String>,
 }
 
 /// Execution status of a step
@@ -104,11 +581,13 @@ pub enum StepStatus {
     Running,
     Success,
     Failed,
+    /// Stopped because the execution was cancelled
+    Cancelled,
     Skipped,
 }
 
 /// Result of step execution
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct StepResult {
     pub step_name: String,
     pub status: StepStatus,
@@ -118,6 +597,212 @@ pub struct StepResult {
     pub duration_ms: u64,
     pub started_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
+    /// Encoding the output was decoded from; unset for steps that didn't run
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Byte sequences in the output that weren't valid in `encoding` and were
+    /// replaced with U+FFFD
+    #[serde(default)]
+    pub replacement_chars: usize,
+    /// Bytes the step wrote to stdout, which may be more than `stdout` kept
+    #[serde(default)]
+    pub stdout_bytes: u64,
+    /// Bytes the step wrote to stderr, which may be more than `stderr` kept
+    #[serde(default)]
+    pub stderr_bytes: u64,
+    /// Full log of the step on the runner, as `<execution id>/<step index>`;
+    /// `stdout` and `stderr` then only hold the end of each stream
+    #[serde(default)]
+    pub log_ref: Option<String>,
+    /// `before_each` and `after_each` hooks that ran around the step
+    #[serde(default)]
+    pub hooks: Vec<HookResult>,
+    /// Problems reported in the step's output
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Coverage from the step's `reports { coverage }`
+    #[serde(default)]
+    pub coverage: Option<CoverageReport>,
+    /// CPU time and memory of the step's processes, where the backend measures them
+    #[serde(default)]
+    pub resources: Option<ResourceUsage>,
+}
+
+/// CPU time and peak memory of a step's processes
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResourceUsage {
+    #[serde(default)]
+    pub cpu_user_ms: Option<u64>,
+    #[serde(default)]
+    pub cpu_system_ms: Option<u64>,
+    /// Largest resident memory seen, summed over the step's processes
+    #[serde(default)]
+    pub peak_rss_bytes: Option<u64>,
+}
+
+impl ResourceUsage {
+    /// User and system CPU time together
+    pub fn cpu_ms(&self) -> Option<u64> {
+        Some(self.cpu_user_ms? + self.cpu_system_ms?)
+    }
+}
+
+/// Timing of an execution, for tracking trends across executions
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExecutionMetrics {
+    /// Time the execution waited in the queue; unset when it wasn't queued
+    #[serde(default)]
+    pub queue_ms: Option<u64>,
+    /// Time from the first step starting to the execution finishing
+    pub wall_ms: u64,
+    /// CPU time of the steps that measured it
+    #[serde(default)]
+    pub cpu_ms: Option<u64>,
+    #[serde(default)]
+    pub steps: Vec<StepMetrics>,
+}
+
+impl ExecutionMetrics {
+    /// Metrics of finished steps; skipped steps are left out
+    pub fn new(queue_ms: Option<u64>, wall_ms: u64, results: &[StepResult]) -> Self {
+        let steps: Vec<StepMetrics> = results
+            .iter()
+            .filter(|result| result.status != StepStatus::Skipped)
+            .map(|result| StepMetrics {
+                step_name: result.step_name.clone(),
+                wall_ms: result.duration_ms,
+                resources: result.resources.unwrap_or_default(),
+            })
+            .collect();
+        let cpu = steps.iter().filter_map(|step| step.resources.cpu_ms()).reduce(|a, b| a + b);
+        Self {
+            queue_ms,
+            wall_ms,
+            cpu_ms: cpu,
+            steps,
+        }
+    }
+}
+
+/// Timing of one step
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StepMetrics {
+    pub step_name: String,
+    pub wall_ms: u64,
+    #[serde(flatten)]
+    pub resources: ResourceUsage,
+}
+
+/// Severity of an annotation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationLevel {
+    Error,
+    Warning,
+    Notice,
+}
+
+impl AnnotationLevel {
+    /// The level as written in output, e.g. `warning`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnotationLevel::Error => "error",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Notice => "notice",
+        }
+    }
+
+    /// Parse a level name, also accepting `warn`, `info` and `note`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(AnnotationLevel::Error),
+            "warning" | "warn" => Some(AnnotationLevel::Warning),
+            "notice" | "note" | "info" => Some(AnnotationLevel::Notice),
+            _ => None,
+        }
+    }
+}
+
+/// A problem found in a step's output, such as a compiler error or a
+/// failed test, pointing at a source location when the output names one
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Annotation {
+    pub level: AnnotationLevel,
+    pub message: String,
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub line: Option<u32>,
+    #[serde(default)]
+    pub column: Option<u32>,
+}
+
+impl std::fmt::Display for Annotation {
+    /// `error: src/main.rs:3:5: message`, leaving out what isn't known
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.level.as_str())?;
+        if let Some(file) = &self.file {
+            write!(f, "{}", file)?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+                if let Some(column) = self.column {
+                    write!(f, ":{}", column)?;
+                }
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Pipeline-level script run around every step
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepHook {
+    BeforeEach,
+    AfterEach,
+}
+
+impl StepHook {
+    /// The hook's name as written in `options {}`, e.g. `before_each`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepHook::BeforeEach => "before_each",
+            StepHook::AfterEach => "after_each",
+        }
+    }
+}
+
+/// Result of a hook run for a step
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HookResult {
+    pub hook: StepHook,
+    pub status: StepStatus,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+/// Infrastructure phases timed separately from step execution
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PhaseKind {
+    Clone,
+    /// A `uses` step's action bundle taken from the runner's cache
+    CacheRestore,
+    /// A `uses` step's action bundle downloaded into the runner's cache
+    CacheSave,
+}
+
+/// Timing and transfer size of an infrastructure phase
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PhaseResult {
+    pub phase: PhaseKind,
+    pub duration_ms: u64,
+    /// Bytes downloaded or uploaded during the phase, when known
+    pub bytes_transferred: Option<u64>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
 }
 
 /// Pipeline execution status
@@ -129,6 +814,25 @@ pub enum PipelineStatus {
     Failed,
     Cancelled,
     Skipped,
+    /// The server stopped while the execution was running; it can be resumed
+    /// from its first unfinished step
+    Interrupted,
+}
+
+impl PipelineStatus {
+    /// Parse a status name in any case, e.g. `failed` or `Failed`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "pending" => Some(Self::Pending),
+            "running" => Some(Self::Running),
+            "success" => Some(Self::Success),
+            "failed" => Some(Self::Failed),
+            "cancelled" => Some(Self::Cancelled),
+            "skipped" => Some(Self::Skipped),
+            "interrupted" => Some(Self::Interrupted),
+            _ => None,
+        }
+    }
 }
 
 /// Complete pipeline execution record
@@ -137,14 +841,311 @@ pub struct PipelineExecution {
     pub id: Uuid,
     pub pipeline_name: String,
     pub pipeline_version: String,
+    /// Labels of the pipeline at the time it ran
+    #[serde(default)]
+    pub pipeline_labels: Vec<String>,
+    /// Environment the pipeline deploys to, at the time it ran
+    #[serde(default)]
+    pub environment: Option<String>,
     pub repository: Repository,
     pub git_event: GitEvent,
     pub status: PipelineStatus,
     pub step_results: Vec<StepResult>,
+    /// Clone and cache phases, kept apart from step durations
+    #[serde(default)]
+    pub phases: Vec<PhaseResult>,
+    /// Set when the execution was stopped for exceeding an `ExecutionLimits` cap
+    #[serde(default)]
+    pub limit_exceeded: Option<String>,
+    /// Coverage of all steps that reported it
+    #[serde(default)]
+    pub coverage: Option<CoverageReport>,
+    /// Queue, wall and CPU time, and peak memory of each step
+    #[serde(default)]
+    pub metrics: ExecutionMetrics,
+    /// Revision of the repository's stored Pulsefile the execution ran; unset
+    /// when the Pulsefile came from the repository itself
+    #[serde(default)]
+    pub pulsefile_revision: Option<u32>,
+    /// Earlier tries that ended in an infrastructure error, oldest first;
+    /// an execution that ran out of retries ends with its last one
+    #[serde(default)]
+    pub attempts: Vec<ExecutionAttempt>,
     pub started_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
 }
 
+/// A try of an execution that ended in an infrastructure error, such as a
+/// failed clone or a lost agent, rather than in a failed step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionAttempt {
+    /// Counting from 1
+    pub attempt: u32,
+    pub started_at: DateTime<Utc>,
+    pub failed_at: DateTime<Utc>,
+    pub error: String,
+}
+
+/// Output stream a chunk of step output was read from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Progress of a running execution, emitted as it happens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecutionEvent {
+    StepStarted {
+        execution_id: Uuid,
+        step_name: String,
+    },
+    /// Output as it is read from the step's process; chunks don't follow line boundaries
+    OutputChunk {
+        execution_id: Uuid,
+        step_name: String,
+        stream: OutputStream,
+        data: String,
+    },
+    StepFinished {
+        execution_id: Uuid,
+        result: Box<StepResult>,
+    },
+    PipelineFinished {
+        execution_id: Uuid,
+        status: PipelineStatus,
+    },
+}
+
+impl ExecutionEvent {
+    pub fn execution_id(&self) -> Uuid {
+        match self {
+            ExecutionEvent::StepStarted { execution_id, .. }
+            | ExecutionEvent::OutputChunk { execution_id, .. }
+            | ExecutionEvent::StepFinished { execution_id, .. }
+            | ExecutionEvent::PipelineFinished { execution_id, .. } => *execution_id,
+        }
+    }
+}
+
+/// One page of a list, with the position of the page in the full result
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl<T> Page<T> {
+    /// Take the page starting at `offset` from the complete, ordered list
+    pub fn from_items(items: Vec<T>, offset: usize, limit: usize) -> Self {
+        let total = items.len();
+        Self {
+            items: items.into_iter().skip(offset).take(limit).collect(),
+            total,
+            offset,
+            limit,
+        }
+    }
+
+    /// Offset of the next page, if there is one
+    pub fn next_offset(&self) -> Option<usize> {
+        let next = self.offset + self.items.len();
+        (next < self.total && !self.items.is_empty()).then_some(next)
+    }
+}
+
+/// A line of step output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogLine {
+    pub stream: OutputStream,
+    /// 1-based line number within the stream
+    pub line: usize,
+    pub text: String,
+}
+
+/// A step's output split into lines
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepLog {
+    pub step_name: String,
+    pub status: StepStatus,
+    pub exit_code: Option<i32>,
+    pub lines: Vec<LogLine>,
+}
+
+/// Output of every step of an execution, line by line
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExecutionLogs {
+    pub execution_id: Uuid,
+    pub status: PipelineStatus,
+    pub steps: Vec<StepLog>,
+}
+
+impl ExecutionLogs {
+    /// Split each step's stdout and then stderr into lines
+    pub fn from_execution(execution: &PipelineExecution) -> Self {
+        let lines = |stream: OutputStream, output: &str| -> Vec<LogLine> {
+            output
+                .lines()
+                .enumerate()
+                .map(|(index, text)| LogLine {
+                    stream,
+                    line: index + 1,
+                    text: text.to_string(),
+                })
+                .collect()
+        };
+        Self {
+            execution_id: execution.id,
+            status: execution.status,
+            steps: execution
+                .step_results
+                .iter()
+                .map(|step| {
+                    let mut step_lines = lines(OutputStream::Stdout, &step.stdout);
+                    step_lines.extend(lines(OutputStream::Stderr, &step.stderr));
+                    StepLog {
+                        step_name: step.step_name.clone(),
+                        status: step.status,
+                        exit_code: step.exit_code,
+                        lines: step_lines,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Execution counts for one period of a repository's activity series
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ActivityBucket {
+    /// First day of the period (UTC)
+    pub date: NaiveDate,
+    pub total: usize,
+    pub success: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub skipped: usize,
+}
+
+/// Output the server keeps per step and stream unless configured otherwise
+pub const DEFAULT_MAX_OUTPUT_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Guardrails applied to executions; unset fields are unlimited
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    #[serde(default)]
+    pub max_steps: Option<usize>,
+    /// Wall-clock limit for all steps together
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    /// Total size of artifacts an execution may keep
+    #[serde(default)]
+    pub max_artifact_bytes: Option<u64>,
+    /// Output kept per step for each of stdout and stderr; the middle of
+    /// longer output is dropped
+    #[serde(default)]
+    pub max_output_bytes: Option<u64>,
+    /// Executions of one repository running at once; more wait as `Pending`
+    #[serde(default)]
+    pub max_concurrent_runs: Option<usize>,
+}
+
+impl ExecutionLimits {
+    /// Combine with more specific limits (e.g. per repository), which take precedence
+    pub fn overridden_by(&self, other: &ExecutionLimits) -> ExecutionLimits {
+        ExecutionLimits {
+            max_steps: other.max_steps.or(self.max_steps),
+            max_runtime_secs: other.max_runtime_secs.or(self.max_runtime_secs),
+            max_artifact_bytes: other.max_artifact_bytes.or(self.max_artifact_bytes),
+            max_output_bytes: other.max_output_bytes.or(self.max_output_bytes),
+            max_concurrent_runs: other.max_concurrent_runs.or(self.max_concurrent_runs),
+        }
+    }
+
+    /// Annotation for a pipeline with too many steps, if it exceeds the cap
+    pub fn check_step_count(&self, steps: usize) -> Option<String> {
+        let max = self.max_steps?;
+        (steps > max).then(|| format!("Pipeline has {} steps, exceeding the limit of {}", steps, max))
+    }
+
+    /// Annotation for artifacts over the size cap, if they exceed it
+    pub fn check_artifact_size(&self, bytes: u64) -> Option<String> {
+        let max = self.max_artifact_bytes?;
+        (bytes > max).then(|| format!("Artifacts total {} bytes, exceeding the limit of {} bytes", bytes, max))
+    }
+}
+
+/// How a repository's steps are sandboxed when the server runs them on its
+/// own host
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    /// Run steps in the sandbox; unset follows the server's default
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Let steps reach the network, e.g. to download dependencies
+    #[serde(default)]
+    pub network: bool,
+    /// Host paths steps can read besides system directories and the workspace
+    #[serde(default)]
+    pub read_only_paths: Vec<String>,
+}
+
+/// Where the generic webhook finds an event's details in the JSON it is sent,
+/// as JSONPath expressions such as `$.ref` or `$.commits[0].id`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WebhookMapping {
+    /// Event type, e.g. `push` or `pull_request`; unset treats every call as
+    /// a push
+    #[serde(default)]
+    pub event: Option<String>,
+    /// Branch name; a leading `refs/heads/` is dropped
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Tag name; a leading `refs/tags/` is dropped
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Commit SHA
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub commit_message: Option<String>,
+    /// Who caused the event, used for `ignore_senders`
+    #[serde(default)]
+    pub sender: Option<String>,
+}
+
+/// Which Pulsefile a registered repository's webhook events run
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PulsefileSource {
+    /// The Pulsefile stored when the repository was registered or updated
+    #[default]
+    Stored,
+    /// The Pulsefile at the event's commit, or the stored one if the commit
+    /// has none or it can't be fetched
+    Commit,
+    /// The Pulsefile at the event's commit; events whose commit has none
+    /// don't run
+    CommitOnly,
+}
+
+/// What a user may do with a repository; each role includes the ones before it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoRole {
+    /// See the repository's executions and settings
+    Viewer,
+    /// Run, resume and cancel pipelines
+    Developer,
+    /// Change settings, secrets and roles, and unregister the repository
+    Admin,
+}
+
 impl Default for GitTriggers {
     fn default() -> Self {
         Self {
@@ -156,6 +1157,9 @@ impl Default for GitTriggers {
             on_branch_create: false,
             on_branch_delete: false,
             branches: vec!["*".to_string()],
+            ignore_senders: Vec::new(),
+            pr_labels: Vec::new(),
+            ignore_drafts: false,
         }
     }
 }
@@ -166,13 +1170,82 @@ impl Step {
             name,
             run,
             allow_failure: false,
+            user: None,
+            group: None,
+            umask: None,
+            timeout_secs: None,
+            env: BTreeMap::new(),
+            background: false,
+            tty: false,
+            ready_when: None,
+            artifacts: Vec::new(),
+            encoding: None,
+            image: None,
+            runner: None,
+            reports: StepReports::default(),
+            uses: None,
+            with: BTreeMap::new(),
         }
     }
 
+    /// Run the step as a background service that is ready once `ready_when` holds
+    pub fn with_background(mut self, ready_when: Option<ReadinessCheck>) -> Self {
+        self.background = true;
+        self.ready_when = ready_when;
+        self
+    }
+
+    pub fn with_artifacts(mut self, artifacts: Vec<String>) -> Self {
+        self.artifacts = artifacts;
+        self
+    }
+
     pub fn with_allow_failure(mut self, allow: bool) -> Self {
         self.allow_failure = allow;
         self
     }
+
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    pub fn with_umask(mut self, umask: impl Into<String>) -> Self {
+        self.umask = Some(umask.into());
+        self
+    }
+
+    pub fn with_timeout_secs(mut self, secs: u64) -> Self {
+        self.timeout_secs = Some(secs);
+        self
+    }
+
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.encoding = Some(encoding.into());
+        self
+    }
+
+    /// Run the step in this container image
+    pub fn with_image(mut self, image: impl Into<String>) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    /// Run the step on a remote host, e.g. `ssh://deploy@prod-box`
+    pub fn with_runner(mut self, runner: impl Into<String>) -> Self {
+        self.runner = Some(runner.into());
+        self
+    }
 }
 
 impl GitTriggers {
@@ -187,23 +1260,43 @@ impl GitTriggers {
             GitEventType::Release => self.on_release,
             GitEventType::BranchCreate => self.on_branch_create,
             GitEventType::BranchDelete => self.on_branch_delete,
+            // Manual runs are matched by the `manual` trigger
+            GitEventType::Manual => false,
         };
 
         if !event_matches {
             return false;
         }
 
-        // Check branch filter
-        if let Some(ref branch) = event.branch {
-            self.matches_branch(branch)
-        } else if event.tag.is_some() {
-            // For tag events, we check if on_tag is enabled
-            self.on_tag
-        } else {
-            true
+        if self.ignore_senders.iter().any(|s| s == &event.sender) || event.skip_requested() {
+            return false;
+        }
+
+        if event.event_type == GitEventType::PullRequest && !self.matches_pull_request(event) {
+            return false;
+        }
+
+        // Branch filters only apply to events that happen on a branch
+        match event.branch {
+            Some(ref branch) => self.matches_branch(branch),
+            None => true,
         }
     }
 
+    /// Check the draft and label filters against a pull request event
+    pub fn matches_pull_request(&self, event: &GitEvent) -> bool {
+        let Some(pr) = &event.pull_request else {
+            // Without PR details only unfiltered triggers can match
+            return !self.ignore_drafts && self.pr_labels.is_empty();
+        };
+
+        if self.ignore_drafts && pr.draft {
+            return false;
+        }
+
+        self.pr_labels.is_empty() || self.pr_labels.iter().any(|l| pr.labels.contains(l))
+    }
+
     /// Check if a branch matches the configured branch patterns
     pub fn matches_branch(&self, branch: &str) -> bool {
         if self.branches.is_empty() {
@@ -231,6 +1324,97 @@ impl GitTriggers {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_page_from_items() {
+        let page = Page::from_items((1..=5).collect::<Vec<_>>(), 2, 2);
+        assert_eq!(page.items, vec![3, 4]);
+        assert_eq!(page.total, 5);
+        assert_eq!(page.next_offset(), Some(4));
+
+        let last = Page::from_items((1..=5).collect::<Vec<_>>(), 4, 2);
+        assert_eq!(last.items, vec![5]);
+        assert_eq!(last.next_offset(), None);
+        assert!(Page::from_items(vec![1], 9, 2).items.is_empty());
+    }
+
+    #[test]
+    fn test_pipeline_status_parse() {
+        assert_eq!(PipelineStatus::parse("failed"), Some(PipelineStatus::Failed));
+        assert_eq!(PipelineStatus::parse("Interrupted"), Some(PipelineStatus::Interrupted));
+        assert_eq!(PipelineStatus::parse("done"), None);
+    }
+
+    #[test]
+    fn test_coverage_report_combine() {
+        let unit = CoverageReport { lines_covered: 30, lines_total: 40 };
+        let integration = CoverageReport { lines_covered: 50, lines_total: 60 };
+        let total = CoverageReport::combine([&unit, &integration]).unwrap();
+        assert_eq!(total, CoverageReport { lines_covered: 80, lines_total: 100 });
+        assert_eq!(total.to_string(), "80.0% (80/100 lines)");
+        assert_eq!(CoverageReport { lines_covered: 0, lines_total: 0 }.percent(), 100.0);
+        assert_eq!(CoverageReport::combine([]), None);
+    }
+
+    #[test]
+    fn test_execution_limits_override() {
+        let global = ExecutionLimits {
+            max_steps: Some(50),
+            max_runtime_secs: Some(3600),
+            max_artifact_bytes: None,
+            max_output_bytes: Some(1024),
+            max_concurrent_runs: None,
+        };
+        let repo = ExecutionLimits {
+            max_runtime_secs: Some(600),
+            ..Default::default()
+        };
+
+        let limits = global.overridden_by(&repo);
+        assert_eq!(limits.max_steps, Some(50));
+        assert_eq!(limits.max_output_bytes, Some(1024));
+        assert_eq!(limits.max_runtime_secs, Some(600));
+        assert!(limits.check_step_count(50).is_none());
+        assert!(limits.check_step_count(51).unwrap().contains("limit of 50"));
+        assert!(limits.check_artifact_size(u64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_apply_base_pipeline() {
+        let pipeline = |name: &str, steps: Vec<Step>, labels: &[&str]| Pipeline {
+            name: name.to_string(),
+            version: "1.0".to_string(),
+            description: None,
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            owners: vec![],
+            environment: None,
+            triggers: Triggers::default(),
+            steps,
+            vars: BTreeMap::from([("REGION".to_string(), name.to_string())]),
+            options: PipelineOptions::default(),
+        };
+        let base = pipeline(
+            "base",
+            vec![Step::new("security-scan".to_string(), "scan .".to_string())],
+            &["audited"],
+        );
+        let mut repo = pipeline(
+            "shop",
+            vec![
+                Step::new("security-scan".to_string(), "true".to_string()),
+                Step::new("build".to_string(), "make".to_string()),
+            ],
+            &["deploy", "audited"],
+        );
+
+        let replaced = repo.apply_base(&base);
+        assert_eq!(replaced, vec!["security-scan"]);
+        assert_eq!(repo.name, "shop");
+        let steps: Vec<(&str, &str)> = repo.steps.iter().map(|s| (s.name.as_str(), s.run.as_str())).collect();
+        assert_eq!(steps, vec![("security-scan", "scan ."), ("build", "make")]);
+        assert_eq!(repo.labels, vec!["audited", "deploy"]);
+        assert_eq!(repo.vars["REGION"], "shop");
+    }
+
     fn create_test_repo() -> Repository {
         Repository {
             owner: "test".to_string(),
@@ -241,6 +1425,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_git_event_type_names_round_trip() {
+        for event_type in [
+            GitEventType::Push,
+            GitEventType::PullRequest,
+            GitEventType::Merge,
+            GitEventType::Tag,
+            GitEventType::Release,
+            GitEventType::BranchCreate,
+            GitEventType::BranchDelete,
+        ] {
+            assert_eq!(GitEventType::from(event_type.as_str()), event_type);
+            assert_eq!(GitEventType::parse(event_type.as_str()), Some(event_type));
+        }
+        assert_eq!(GitEventType::parse("manual"), Some(GitEventType::Manual));
+        assert_eq!(GitEventType::parse("Push"), None);
+    }
+
     #[test]
     fn test_git_triggers_default() {
         let triggers = GitTriggers::default();
@@ -292,9 +1494,12 @@ mod tests {
             repository: create_test_repo(),
             branch: Some("main".to_string()),
             tag: None,
+            release_name: None,
             pull_request: None,
             commit_sha: None,
+            commit_message: None,
             sender: "user".to_string(),
+            inputs: Default::default(),
         };
 
         assert!(triggers.matches(&event));
@@ -313,14 +1518,235 @@ mod tests {
             repository: create_test_repo(),
             branch: None,
             tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: None,
+            sender: "user".to_string(),
+            inputs: Default::default(),
+        };
+
+        assert!(!triggers.matches(&event));
+    }
+
+    #[test]
+    fn test_git_triggers_ignore_senders() {
+        let triggers = GitTriggers {
+            on_push: true,
+            ignore_senders: vec!["dependabot[bot]".to_string()],
+            ..Default::default()
+        };
+
+        let mut event = GitEvent {
+            event_type: GitEventType::Push,
+            repository: create_test_repo(),
+            branch: Some("main".to_string()),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: None,
+            sender: "dependabot[bot]".to_string(),
+            inputs: Default::default(),
+        };
+        assert!(!triggers.matches(&event));
+
+        event.sender = "user".to_string();
+        assert!(triggers.matches(&event));
+    }
+
+    #[test]
+    fn test_git_triggers_skip_ci_commit_message() {
+        let triggers = GitTriggers {
+            on_push: true,
+            ..Default::default()
+        };
+
+        let mut event = GitEvent {
+            event_type: GitEventType::Push,
+            repository: create_test_repo(),
+            branch: Some("main".to_string()),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: Some("Update docs [skip ci]".to_string()),
+            sender: "user".to_string(),
+            inputs: Default::default(),
+        };
+        assert!(!triggers.matches(&event));
+
+        event.commit_message = Some("[CI SKIP] bump version".to_string());
+        assert!(!triggers.matches(&event));
+
+        event.commit_message = Some("Fix build".to_string());
+        assert!(triggers.matches(&event));
+    }
+
+    #[test]
+    fn test_git_event_source_ref() {
+        let mut event = create_test_pr_event(false, &[]);
+        assert_eq!(event.source_ref().as_deref(), Some("refs/pull/1/head"));
+        event.branch = Some("main".to_string());
+        assert_eq!(event.source_ref().as_deref(), Some("main"));
+        event.tag = Some("v1.2.0".to_string());
+        assert_eq!(event.source_ref().as_deref(), Some("v1.2.0"));
+        event.commit_sha = Some("4f2a9c1".to_string());
+        assert_eq!(event.source_ref().as_deref(), Some("4f2a9c1"));
+        event.pull_request = None;
+        event.commit_sha = None;
+        event.tag = None;
+        event.branch = None;
+        assert_eq!(event.source_ref(), None);
+    }
+
+    fn create_test_pr_event(draft: bool, labels: &[&str]) -> GitEvent {
+        GitEvent {
+            event_type: GitEventType::PullRequest,
+            repository: create_test_repo(),
+            branch: None,
+            tag: None,
+            release_name: None,
+            pull_request: Some(PullRequest {
+                number: 1,
+                title: "Add feature".to_string(),
+                base_branch: "main".to_string(),
+                head_branch: "feature/x".to_string(),
+                state: "open".to_string(),
+                draft,
+                labels: labels.iter().map(|l| l.to_string()).collect(),
+                head_repository: Some("test/repo".to_string()),
+            }),
+            commit_sha: None,
+            commit_message: None,
+            sender: "user".to_string(),
+            inputs: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_git_event_from_fork() {
+        let mut event = create_test_pr_event(false, &[]);
+        assert!(!event.from_fork());
+        let pr = event.pull_request.as_mut().unwrap();
+        pr.head_repository = Some("someone/repo".to_string());
+        assert!(event.from_fork());
+        event.pull_request.as_mut().unwrap().head_repository = None;
+        assert!(event.from_fork());
+        event.event_type = GitEventType::Push;
+        assert!(!event.from_fork());
+    }
+
+    #[test]
+    fn test_git_triggers_ignore_drafts() {
+        let triggers = GitTriggers {
+            on_pull_request: true,
+            ignore_drafts: true,
+            ..Default::default()
+        };
+
+        assert!(!triggers.matches(&create_test_pr_event(true, &[])));
+        assert!(triggers.matches(&create_test_pr_event(false, &[])));
+    }
+
+    #[test]
+    fn test_git_triggers_pr_labels() {
+        let triggers = GitTriggers {
+            on_pull_request: true,
+            pr_labels: vec!["ci".to_string(), "ready".to_string()],
+            ..Default::default()
+        };
+
+        assert!(!triggers.matches(&create_test_pr_event(false, &[])));
+        assert!(!triggers.matches(&create_test_pr_event(false, &["docs"])));
+        assert!(triggers.matches(&create_test_pr_event(false, &["docs", "ready"])));
+    }
+
+    #[test]
+    fn test_git_triggers_matches_release() {
+        let event = GitEvent {
+            event_type: GitEventType::Release,
+            repository: create_test_repo(),
+            branch: None,
+            tag: Some("v1.0.0".to_string()),
+            release_name: Some("First release".to_string()),
             pull_request: None,
             commit_sha: None,
+            commit_message: None,
             sender: "user".to_string(),
+            inputs: Default::default(),
         };
 
+        let triggers = GitTriggers {
+            on_release: true,
+            branches: vec!["main".to_string()],
+            ..Default::default()
+        };
+        assert!(triggers.matches(&event));
+
+        let triggers = GitTriggers {
+            on_tag: true,
+            ..Default::default()
+        };
         assert!(!triggers.matches(&event));
     }
 
+    #[test]
+    fn test_ssh_target_parse() {
+        let target = SshTarget::parse("ssh://deploy@prod-box").unwrap();
+        assert_eq!(target.user.as_deref(), Some("deploy"));
+        assert_eq!(target.host, "prod-box");
+        assert_eq!(target.port, None);
+
+        let target = SshTarget::parse("ssh://10.0.0.5:2222/").unwrap();
+        assert_eq!((target.user, target.host.as_str(), target.port), (None, "10.0.0.5", Some(2222)));
+        let target = SshTarget::parse("ssh://root@[fd00::1]:22").unwrap();
+        assert_eq!((target.host.as_str(), target.port), ("fd00::1", Some(22)));
+
+        for invalid in ["prod-box", "https://prod-box", "ssh://", "ssh://-oProxyCommand=x", "ssh://a@b:port", "ssh://a b"] {
+            assert!(SshTarget::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_action_ref_parse() {
+        assert_eq!(
+            ActionRef::parse("pulsiora/checkout@v1").unwrap(),
+            ActionRef::Git {
+                repository: "pulsiora/checkout".to_string(),
+                rev: "v1".to_string()
+            }
+        );
+        assert_eq!(
+            ActionRef::parse("https://git@example.com/ci/lint.git@3f2c1a9").unwrap(),
+            ActionRef::Git {
+                repository: "https://git@example.com/ci/lint.git".to_string(),
+                rev: "3f2c1a9".to_string()
+            }
+        );
+        let digest = "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08";
+        assert_eq!(
+            ActionRef::parse(&format!("https://actions.example.com/lint-1.2.0.tar.gz#sha256={}", digest)).unwrap(),
+            ActionRef::Archive {
+                url: "https://actions.example.com/lint-1.2.0.tar.gz".to_string(),
+                sha256: digest.to_ascii_lowercase(),
+            }
+        );
+
+        for invalid in [
+            "pulsiora/checkout",
+            "checkout@v1",
+            "a/b/c@v1",
+            "a/b@-v1",
+            "https://example.com/a",
+            "a/b@",
+            "https://actions.example.com/lint-1.2.0.tar.gz",
+            "https://actions.example.com/lint-1.2.0.tar.gz#sha256=9f86d081",
+        ] {
+            assert!(ActionRef::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
     #[test]
     fn test_step_new() {
         let step = Step::new("test".to_string(), "echo hello".to_string());
@@ -335,4 +1761,15 @@ mod tests {
             .with_allow_failure(true);
         assert!(step.allow_failure);
     }
+
+    #[test]
+    fn test_step_with_identity() {
+        let step = Step::new("deploy".to_string(), "./deploy.sh".to_string())
+            .with_user("builder")
+            .with_group("deploy")
+            .with_umask("0022");
+        assert_eq!(step.user.as_deref(), Some("builder"));
+        assert_eq!(step.group.as_deref(), Some("deploy"));
+        assert_eq!(step.umask.as_deref(), Some("0022"));
+    }
 }
@@ -0,0 +1,295 @@
+use crate::models::{GitEvent, GitEventType, GitTriggers, PipelineStatus};
+use serde::{Deserialize, Serialize};
+
+/// Something that happened which may start a pipeline
+#[derive(Debug, Clone, Copy)]
+pub enum TriggerEvent<'a> {
+    Git(&'a GitEvent),
+    /// A schedule fired; carries the cron expression that fired
+    Schedule { cron: &'a str },
+    /// A user or API call requested a run
+    Manual,
+    /// A container image was pushed to a registry
+    Image { image: &'a str, tag: &'a str },
+    /// Another pipeline finished
+    Pipeline { pipeline: &'a str, status: PipelineStatus },
+    /// A named generic webhook was called
+    Webhook { name: &'a str },
+}
+
+impl<'a> From<&'a GitEvent> for TriggerEvent<'a> {
+    /// Manual runs carry their details in a `GitEvent` too
+    fn from(event: &'a GitEvent) -> Self {
+        match event.event_type {
+            GitEventType::Manual => TriggerEvent::Manual,
+            _ => TriggerEvent::Git(event),
+        }
+    }
+}
+
+/// A source of events that can start a pipeline
+pub trait TriggerSource {
+    /// Whether `event` should start the pipeline
+    fn matches(&self, event: &TriggerEvent) -> bool;
+}
+
+/// A configured trigger, tagged by `kind` when serialized
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Trigger {
+    Git(GitTriggers),
+    Schedule(ScheduleTrigger),
+    Manual(ManualTrigger),
+    Image(ImageTrigger),
+    Pipeline(PipelineTrigger),
+    Webhook(WebhookTrigger),
+}
+
+/// Run on a cron schedule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleTrigger {
+    pub cron: String,
+    /// Branch to run against; the repository default branch when unset
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+/// Allow runs requested explicitly by users or the API
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ManualTrigger {}
+
+/// Run when a container image is pushed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageTrigger {
+    pub image: String,
+    /// Tag patterns such as "latest" or "v*"; any tag when empty
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Run after another pipeline finishes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PipelineTrigger {
+    pub pipeline: String,
+    /// Upstream statuses that trigger; only Success when empty
+    #[serde(default)]
+    pub statuses: Vec<PipelineStatus>,
+}
+
+/// Run when a named generic webhook is called
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookTrigger {
+    pub name: String,
+}
+
+/// All trigger sources configured for a pipeline
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct Triggers {
+    pub sources: Vec<Trigger>,
+}
+
+impl Triggers {
+    pub fn new(sources: Vec<Trigger>) -> Self {
+        Self { sources }
+    }
+
+    /// The git trigger configuration, if the pipeline has one
+    pub fn git(&self) -> Option<&GitTriggers> {
+        self.sources.iter().find_map(|t| match t {
+            Trigger::Git(git) => Some(git),
+            _ => None,
+        })
+    }
+}
+
+impl Default for Triggers {
+    fn default() -> Self {
+        Self::from(GitTriggers::default())
+    }
+}
+
+impl From<GitTriggers> for Triggers {
+    fn from(git: GitTriggers) -> Self {
+        Self::new(vec![Trigger::Git(git)])
+    }
+}
+
+impl TriggerSource for Triggers {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        self.sources.iter().any(|t| t.matches(event))
+    }
+}
+
+impl TriggerSource for Trigger {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        match self {
+            Trigger::Git(t) => TriggerSource::matches(t, event),
+            Trigger::Schedule(t) => t.matches(event),
+            Trigger::Manual(t) => t.matches(event),
+            Trigger::Image(t) => t.matches(event),
+            Trigger::Pipeline(t) => t.matches(event),
+            Trigger::Webhook(t) => t.matches(event),
+        }
+    }
+}
+
+impl TriggerSource for GitTriggers {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        match event {
+            TriggerEvent::Git(git_event) => GitTriggers::matches(self, git_event),
+            _ => false,
+        }
+    }
+}
+
+impl TriggerSource for ScheduleTrigger {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        matches!(event, TriggerEvent::Schedule { cron } if *cron == self.cron)
+    }
+}
+
+impl TriggerSource for ManualTrigger {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        matches!(event, TriggerEvent::Manual)
+    }
+}
+
+impl TriggerSource for ImageTrigger {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        match event {
+            TriggerEvent::Image { image, tag } => {
+                *image == self.image
+                    && (self.tags.is_empty() || self.tags.iter().any(|p| matches_pattern(p, tag)))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TriggerSource for PipelineTrigger {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        match event {
+            TriggerEvent::Pipeline { pipeline, status } => {
+                *pipeline == self.pipeline
+                    && if self.statuses.is_empty() {
+                        *status == PipelineStatus::Success
+                    } else {
+                        self.statuses.contains(status)
+                    }
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TriggerSource for WebhookTrigger {
+    fn matches(&self, event: &TriggerEvent) -> bool {
+        matches!(event, TriggerEvent::Webhook { name } if *name == self.name)
+    }
+}
+
+/// Match "*", exact values, and trailing-wildcard patterns like "v*"
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => pattern == value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GitEventType, Repository};
+
+    fn create_test_event() -> GitEvent {
+        GitEvent {
+            event_type: GitEventType::Push,
+            repository: Repository {
+                owner: "test".to_string(),
+                name: "repo".to_string(),
+                full_name: "test/repo".to_string(),
+                clone_url: "https://github.com/test/repo.git".to_string(),
+                default_branch: "main".to_string(),
+            },
+            branch: Some("main".to_string()),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: None,
+            sender: "user".to_string(),
+            inputs: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_triggers_match_any_source() {
+        let triggers = Triggers::new(vec![
+            Trigger::Git(GitTriggers {
+                on_push: true,
+                ..Default::default()
+            }),
+            Trigger::Schedule(ScheduleTrigger {
+                cron: "0 3 * * *".to_string(),
+                branch: None,
+            }),
+        ]);
+
+        assert!(triggers.matches(&TriggerEvent::Git(&create_test_event())));
+        assert!(triggers.matches(&TriggerEvent::Schedule { cron: "0 3 * * *" }));
+        assert!(!triggers.matches(&TriggerEvent::Schedule { cron: "0 4 * * *" }));
+        assert!(!triggers.matches(&TriggerEvent::Manual));
+
+        // A manual run only matches the manual trigger, whatever its branch
+        let mut manual = create_test_event();
+        manual.event_type = GitEventType::Manual;
+        assert!(!triggers.matches(&TriggerEvent::from(&manual)));
+        let triggers = Triggers::new(vec![Trigger::Manual(ManualTrigger {})]);
+        assert!(triggers.matches(&TriggerEvent::from(&manual)));
+        assert!(!triggers.matches(&TriggerEvent::from(&create_test_event())));
+    }
+
+    #[test]
+    fn test_image_trigger_tag_patterns() {
+        let trigger = Trigger::Image(ImageTrigger {
+            image: "ghcr.io/acme/base".to_string(),
+            tags: vec!["v*".to_string()],
+        });
+
+        assert!(trigger.matches(&TriggerEvent::Image { image: "ghcr.io/acme/base", tag: "v1.2" }));
+        assert!(!trigger.matches(&TriggerEvent::Image { image: "ghcr.io/acme/base", tag: "latest" }));
+        assert!(!trigger.matches(&TriggerEvent::Image { image: "ghcr.io/acme/other", tag: "v1" }));
+    }
+
+    #[test]
+    fn test_pipeline_trigger_defaults_to_success() {
+        let trigger = Trigger::Pipeline(PipelineTrigger {
+            pipeline: "build".to_string(),
+            statuses: vec![],
+        });
+
+        assert!(trigger.matches(&TriggerEvent::Pipeline { pipeline: "build", status: PipelineStatus::Success }));
+        assert!(!trigger.matches(&TriggerEvent::Pipeline { pipeline: "build", status: PipelineStatus::Failed }));
+    }
+
+    #[test]
+    fn test_triggers_serde_tagging() {
+        let triggers = Triggers::new(vec![
+            Trigger::Manual(ManualTrigger {}),
+            Trigger::Webhook(WebhookTrigger {
+                name: "deploy".to_string(),
+            }),
+        ]);
+
+        let json = serde_json::to_value(&triggers).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{ "kind": "manual" }, { "kind": "webhook", "name": "deploy" }])
+        );
+
+        let parsed: Triggers = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, triggers);
+    }
+}
@@ -0,0 +1,608 @@
+// Converts `.gitlab-ci.yml` files into Pulsefiles for teams migrating from
+// GitLab CI. Anything without a Pulsefile equivalent is reported as a warning
+// rather than silently dropped.
+
+use crate::writer::render_pulsefile;
+use pulsiora_core::{GitTriggers, Pipeline, PipelineOptions, PulsioraError, Result, Step, Triggers};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+
+/// Top-level keys that configure the pipeline rather than define a job
+const RESERVED_KEYS: &[&str] = &[
+    "default",
+    "include",
+    "stages",
+    "variables",
+    "workflow",
+    "image",
+    "services",
+    "cache",
+    "before_script",
+    "after_script",
+];
+
+/// Job keywords that are converted; everything else is reported
+const CONVERTED_JOB_KEYS: &[&str] = &[
+    "stage",
+    "script",
+    "before_script",
+    "after_script",
+    "allow_failure",
+    "variables",
+    "only",
+    "except",
+    "rules",
+    "extends",
+    "when",
+];
+
+const DEFAULT_STAGES: &[&str] = &[".pre", "build", "test", "deploy", ".post"];
+
+/// Result of converting a GitLab CI configuration
+#[derive(Debug, Clone)]
+pub struct GitLabImport {
+    pub pipeline: Pipeline,
+    /// Constructs that could not be converted exactly
+    pub warnings: Vec<String>,
+}
+
+impl GitLabImport {
+    /// Pulsefile source, with warnings listed as comments at the top
+    pub fn to_pulsefile(&self) -> String {
+        let mut out = String::from("# Imported from .gitlab-ci.yml\n");
+        for warning in &self.warnings {
+            out.push_str(&format!("# WARNING: {}\n", warning));
+        }
+        out.push('\n');
+        out.push_str(&render_pulsefile(&self.pipeline));
+        out
+    }
+}
+
+/// Which events and branches a single job runs for
+#[derive(Debug, Default, PartialEq)]
+struct JobFilter {
+    /// None means every branch
+    branches: Option<Vec<String>>,
+    tags: bool,
+    merge_requests: bool,
+}
+
+impl JobFilter {
+    fn unrestricted() -> Self {
+        Self {
+            branches: None,
+            tags: true,
+            merge_requests: false,
+        }
+    }
+}
+
+/// Convert a `.gitlab-ci.yml` document into a pipeline named `name`
+pub fn import_gitlab_ci(yaml: &str, name: &str) -> Result<GitLabImport> {
+    let doc: Value = serde_yaml::from_str(yaml)
+        .map_err(|e| PulsioraError::ParseError(format!("Invalid GitLab CI YAML: {}", e)))?;
+    let root = doc
+        .as_mapping()
+        .ok_or_else(|| PulsioraError::ParseError("GitLab CI file must be a mapping".to_string()))?;
+
+    let mut warnings = Vec::new();
+
+    let stages: Vec<String> = match root.get("stages") {
+        Some(stages) => string_list(stages),
+        None => DEFAULT_STAGES.iter().map(|s| s.to_string()).collect(),
+    };
+
+    for key in ["include", "workflow", "image", "services", "cache"] {
+        if root.contains_key(key) {
+            warnings.push(format!("top-level `{}` is not supported and was ignored", key));
+        }
+    }
+
+    let default = root.get("default").and_then(Value::as_mapping);
+    let global_before = default
+        .and_then(|d| d.get("before_script"))
+        .or_else(|| root.get("before_script"))
+        .map(script_lines)
+        .unwrap_or_default();
+    let global_after = default
+        .and_then(|d| d.get("after_script"))
+        .or_else(|| root.get("after_script"))
+        .map(script_lines)
+        .unwrap_or_default();
+    let global_env = root.get("variables").map(variables).unwrap_or_default();
+
+    let mut jobs = Vec::new();
+    for (key, value) in root {
+        let Some(job_name) = key.as_str() else { continue };
+        if RESERVED_KEYS.contains(&job_name) || job_name.starts_with('.') {
+            continue;
+        }
+        let Some(job) = value.as_mapping() else { continue };
+        let job = resolve_extends(root, job_name, job, &mut warnings);
+        if !job.contains_key("script") && !job.contains_key("trigger") {
+            continue;
+        }
+        jobs.push((job_name.to_string(), job));
+    }
+
+    // Jobs run stage by stage, and in file order within a stage
+    let stage_index = |job: &Mapping| {
+        let stage = job.get("stage").and_then(Value::as_str).unwrap_or("test");
+        stages.iter().position(|s| s == stage).unwrap_or(stages.len())
+    };
+    jobs.sort_by_key(|(_, job)| stage_index(job));
+
+    let mut steps = Vec::new();
+    let mut filters = Vec::new();
+    for (job_name, job) in &jobs {
+        for key in job.keys().filter_map(Value::as_str) {
+            if !CONVERTED_JOB_KEYS.contains(&key) {
+                warnings.push(format!("job `{}`: `{}` is not supported and was ignored", job_name, key));
+            }
+        }
+        if let Some(stage) = job.get("stage").and_then(Value::as_str) {
+            if !stages.iter().any(|s| s == stage) {
+                warnings.push(format!("job `{}`: stage `{}` is not declared in `stages`", job_name, stage));
+            }
+        }
+        match job.get("when").and_then(Value::as_str) {
+            Some(when @ ("manual" | "delayed" | "never")) => {
+                warnings.push(format!("job `{}`: `when: {}` jobs were skipped", job_name, when));
+                continue;
+            }
+            Some(when @ ("always" | "on_failure")) => warnings.push(format!(
+                "job `{}`: `when: {}` is not supported; the step runs in order like any other",
+                job_name, when
+            )),
+            _ => {}
+        }
+        if job.contains_key("trigger") && !job.contains_key("script") {
+            warnings.push(format!("job `{}`: downstream `trigger` jobs were skipped", job_name));
+            continue;
+        }
+
+        let mut lines = job.get("before_script").map(script_lines).unwrap_or_else(|| global_before.clone());
+        lines.extend(job.get("script").map(script_lines).unwrap_or_default());
+        lines.extend(job.get("after_script").map(script_lines).unwrap_or_else(|| global_after.clone()));
+        let run = lines.join("\n");
+        if run.contains("\"\"\"") {
+            warnings.push(format!("job `{}`: script contains `\"\"\"` and was skipped", job_name));
+            continue;
+        }
+        if run.contains("$CI_") || run.contains("${CI_") {
+            warnings.push(format!(
+                "job `{}`: script references GitLab predefined CI_* variables, which are not set",
+                job_name
+            ));
+        }
+
+        let mut env = global_env.clone();
+        env.extend(job.get("variables").map(variables).unwrap_or_default());
+        env.retain(|key, value| {
+            let representable = !value.contains('"');
+            if !representable {
+                warnings.push(format!("job `{}`: variable `{}` contains a double quote and was dropped", job_name, key));
+            }
+            representable
+        });
+
+        let allow_failure = match job.get("allow_failure") {
+            Some(Value::Bool(b)) => *b,
+            // `allow_failure: { exit_codes: [...] }` allows only some codes; be lenient
+            Some(Value::Mapping(_)) => {
+                warnings.push(format!(
+                    "job `{}`: `allow_failure: exit_codes` was converted to `allow_failure: true`",
+                    job_name
+                ));
+                true
+            }
+            _ => false,
+        };
+
+        filters.push(job_filter(job_name, job, &mut warnings));
+        let mut step = Step::new(job_name.clone(), run).with_allow_failure(allow_failure);
+        step.env = env;
+        steps.push(step);
+    }
+
+    let triggers = merge_filters(&filters, &mut warnings);
+    let pipeline = Pipeline {
+        name: name.to_string(),
+        version: "1.0".to_string(),
+        description: None,
+        labels: Vec::new(),
+        owners: Vec::new(),
+        environment: None,
+        triggers: Triggers::from(triggers),
+        steps,
+        vars: BTreeMap::new(),
+        // GitLab fails a job as soon as one script line fails
+        options: PipelineOptions {
+            set_e: true,
+            ..Default::default()
+        },
+    };
+
+    Ok(GitLabImport { pipeline, warnings })
+}
+
+/// Merge `extends` templates into a job; keys set on the job win
+fn resolve_extends(root: &Mapping, job_name: &str, job: &Mapping, warnings: &mut Vec<String>) -> Mapping {
+    let parents = match job.get("extends") {
+        Some(extends) => string_list(extends),
+        None => return job.clone(),
+    };
+
+    let mut merged = Mapping::new();
+    for parent in parents {
+        match root.get(parent.as_str()).and_then(Value::as_mapping) {
+            // Templates may extend other templates
+            Some(template) => {
+                for (key, value) in resolve_extends(root, &parent, template, warnings) {
+                    merged.insert(key, value);
+                }
+            }
+            None => warnings.push(format!("job `{}`: extended template `{}` not found", job_name, parent)),
+        }
+    }
+    for (key, value) in job {
+        merged.insert(key.clone(), value.clone());
+    }
+    merged.remove("extends");
+    merged
+}
+
+fn job_filter(job_name: &str, job: &Mapping, warnings: &mut Vec<String>) -> JobFilter {
+    if job.contains_key("except") {
+        warnings.push(format!(
+            "job `{}`: `except` has no Pulsefile equivalent and was ignored",
+            job_name
+        ));
+    }
+
+    if let Some(rules) = job.get("rules").and_then(Value::as_sequence) {
+        return rules_filter(job_name, rules, warnings);
+    }
+
+    let only = match job.get("only") {
+        Some(Value::Mapping(only)) => only.get("refs").map(string_list).unwrap_or_default(),
+        Some(only) => string_list(only),
+        None => return JobFilter::unrestricted(),
+    };
+
+    let mut filter = JobFilter {
+        branches: Some(Vec::new()),
+        ..Default::default()
+    };
+    for entry in only {
+        match entry.as_str() {
+            "branches" | "pushes" => filter.branches = None,
+            "tags" => filter.tags = true,
+            "merge_requests" => filter.merge_requests = true,
+            "schedules" | "web" | "api" | "triggers" | "pipelines" => warnings.push(format!(
+                "job `{}`: `only: {}` has no Pulsefile equivalent and was ignored",
+                job_name, entry
+            )),
+            branch => add_branch(job_name, &mut filter, branch, warnings),
+        }
+    }
+    filter
+}
+
+fn rules_filter(job_name: &str, rules: &[Value], warnings: &mut Vec<String>) -> JobFilter {
+    let mut filter = JobFilter {
+        branches: Some(Vec::new()),
+        ..Default::default()
+    };
+
+    for rule in rules {
+        if rule.get("when").and_then(Value::as_str) == Some("never") {
+            warnings.push(format!("job `{}`: `when: never` rules were ignored", job_name));
+            continue;
+        }
+        let Some(condition) = rule.get("if").and_then(Value::as_str) else {
+            // A rule without `if` always matches
+            return JobFilter::unrestricted();
+        };
+        let condition = condition.trim();
+
+        if condition.contains("&&") || condition.contains("||") {
+            warnings.push(format!("job `{}`: rule `{}` is too complex to convert", job_name, condition));
+        } else if let Some(value) = comparison(condition, "CI_PIPELINE_SOURCE", "==") {
+            match value.as_str() {
+                "merge_request_event" => filter.merge_requests = true,
+                "push" => filter.branches = None,
+                other => warnings.push(format!(
+                    "job `{}`: pipeline source `{}` has no Pulsefile equivalent",
+                    job_name, other
+                )),
+            }
+        } else if condition == "$CI_COMMIT_TAG" {
+            filter.tags = true;
+        } else if condition == "$CI_COMMIT_BRANCH" {
+            filter.branches = None;
+        } else if let Some(branch) = comparison(condition, "CI_COMMIT_BRANCH", "==")
+            .or_else(|| comparison(condition, "CI_COMMIT_REF_NAME", "=="))
+        {
+            add_branch(job_name, &mut filter, &branch, warnings);
+        } else if let Some(regex) = comparison(condition, "CI_COMMIT_BRANCH", "=~")
+            .or_else(|| comparison(condition, "CI_COMMIT_REF_NAME", "=~"))
+        {
+            add_branch(job_name, &mut filter, &regex, warnings);
+        } else if condition == "$CI_COMMIT_BRANCH == $CI_DEFAULT_BRANCH"
+            || condition == "$CI_COMMIT_REF_NAME == $CI_DEFAULT_BRANCH"
+        {
+            warnings.push(format!(
+                "job `{}`: default-branch rule was converted to `main`",
+                job_name
+            ));
+            add_branch(job_name, &mut filter, "main", warnings);
+        } else {
+            warnings.push(format!("job `{}`: rule `{}` could not be converted", job_name, condition));
+        }
+    }
+    filter
+}
+
+/// Value compared against `$variable` with `op`, unquoted or with regex slashes intact
+fn comparison(condition: &str, variable: &str, op: &str) -> Option<String> {
+    let rest = condition
+        .strip_prefix('$')?
+        .strip_prefix(variable)?
+        .trim_start()
+        .strip_prefix(op)?
+        .trim();
+    let value = rest
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| rest.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(rest);
+    if value.starts_with('$') {
+        return None;
+    }
+    Some(value.to_string())
+}
+
+fn add_branch(job_name: &str, filter: &mut JobFilter, branch: &str, warnings: &mut Vec<String>) {
+    let pattern = if branch.starts_with('/') {
+        match regex_to_pattern(branch) {
+            Some(pattern) => pattern,
+            None => {
+                warnings.push(format!(
+                    "job `{}`: branch regex `{}` cannot be expressed as a branch pattern and was ignored",
+                    job_name, branch
+                ));
+                return;
+            }
+        }
+    } else {
+        branch.to_string()
+    };
+
+    if let Some(branches) = &mut filter.branches {
+        if !branches.contains(&pattern) {
+            branches.push(pattern);
+        }
+    }
+}
+
+/// Convert simple anchored regexes like `/^release\/.*$/` to branch patterns
+fn regex_to_pattern(regex: &str) -> Option<String> {
+    let body = regex.strip_prefix('/')?.strip_suffix('/')?;
+    let body = body.strip_prefix('^')?;
+    let (literal, wildcard) = match body.strip_suffix(".*$").or_else(|| body.strip_suffix(".*")) {
+        Some(prefix) => (prefix, true),
+        None => (body.strip_suffix('$')?, false),
+    };
+
+    let mut text = String::new();
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next()?),
+            '.' | '*' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' | '^' | '$' => return None,
+            c => text.push(c),
+        }
+    }
+
+    match wildcard {
+        false => Some(text),
+        // Branch patterns only support a trailing "/*"
+        true if text.ends_with('/') => Some(format!("{}*", text)),
+        true => None,
+    }
+}
+
+/// Combine per-job filters into the single filter a Pulsefile supports
+fn merge_filters(filters: &[JobFilter], warnings: &mut Vec<String>) -> GitTriggers {
+    let mut branches: Option<Vec<String>> = Some(Vec::new());
+    let mut tags = false;
+    let mut merge_requests = false;
+    for filter in filters {
+        tags |= filter.tags;
+        merge_requests |= filter.merge_requests;
+        match (&mut branches, &filter.branches) {
+            (Some(all), Some(job)) => {
+                for branch in job {
+                    if !all.contains(branch) {
+                        all.push(branch.clone());
+                    }
+                }
+            }
+            (all, None) => *all = None,
+            (None, Some(_)) => {}
+        }
+    }
+
+    if filters.windows(2).any(|pair| pair[0] != pair[1]) {
+        warnings.push(
+            "jobs have different branch/event filters; the Pulsefile runs all steps for the union of them"
+                .to_string(),
+        );
+    }
+
+    // With no jobs at all, keep the usual "every push" default
+    let branches = match branches {
+        Some(branches) if !filters.is_empty() => branches,
+        _ => vec!["*".to_string()],
+    };
+    GitTriggers {
+        on_push: !branches.is_empty(),
+        on_pull_request: merge_requests,
+        on_tag: tags,
+        branches,
+        ..Default::default()
+    }
+}
+
+fn script_lines(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.trim_end().to_string()],
+        Value::Sequence(items) => items.iter().flat_map(script_lines).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn string_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Sequence(items) => items.iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn variables(value: &Value) -> BTreeMap<String, String> {
+    let Some(mapping) = value.as_mapping() else {
+        return BTreeMap::new();
+    };
+    mapping
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                // Expanded form: `NAME: { value: "...", description: "..." }`
+                Value::Mapping(m) => m.get("value")?.clone(),
+                other => other.clone(),
+            };
+            let value = match value {
+                Value::String(s) => s,
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => return None,
+            };
+            Some((key.as_str()?.to_string(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_pulsefile;
+
+    const GITLAB_CI: &str = r#"
+stages:
+  - build
+  - test
+  - deploy
+
+variables:
+  CARGO_TERM_COLOR: always
+
+default:
+  before_script:
+    - rustc --version
+
+.rust:
+  variables:
+    RUST_BACKTRACE: "1"
+
+deploy:
+  stage: deploy
+  script: ./deploy.sh
+  only:
+    - main
+    - /^release\/.*$/
+
+lint:
+  extends: .rust
+  stage: test
+  script:
+    - cargo clippy
+  allow_failure: true
+
+build:
+  stage: build
+  image: rust:1.75
+  script:
+    - cargo build --release
+  rules:
+    - if: '$CI_COMMIT_BRANCH == "main"'
+    - if: $CI_PIPELINE_SOURCE == "merge_request_event"
+"#;
+
+    #[test]
+    fn test_import_orders_jobs_by_stage() {
+        let import = import_gitlab_ci(GITLAB_CI, "app").unwrap();
+        let names: Vec<&str> = import.pipeline.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "lint", "deploy"]);
+
+        let lint = &import.pipeline.steps[1];
+        assert!(lint.allow_failure);
+        assert_eq!(lint.run, "rustc --version\ncargo clippy");
+        assert_eq!(lint.env.get("RUST_BACKTRACE").map(String::as_str), Some("1"));
+        assert_eq!(lint.env.get("CARGO_TERM_COLOR").map(String::as_str), Some("always"));
+    }
+
+    #[test]
+    fn test_import_branch_filters() {
+        let import = import_gitlab_ci(GITLAB_CI, "app").unwrap();
+        let git = import.pipeline.triggers.git().unwrap();
+
+        // `lint` has no filter, so the union covers every branch
+        assert_eq!(git.branches, vec!["*"]);
+        assert!(git.on_push);
+        assert!(git.on_pull_request);
+        assert!(import.warnings.iter().any(|w| w.contains("`image`")));
+        assert!(import.warnings.iter().any(|w| w.contains("different branch")));
+    }
+
+    #[test]
+    fn test_import_only_refs() {
+        let yaml = r#"
+deploy:
+  script: ./deploy.sh
+  only:
+    refs:
+      - main
+      - /^release\/.*$/
+      - /^hotfix-\d+$/
+      - tags
+"#;
+        let import = import_gitlab_ci(yaml, "app").unwrap();
+        let git = import.pipeline.triggers.git().unwrap();
+        assert_eq!(git.branches, vec!["main", "release/*"]);
+        assert!(git.on_tag);
+        assert!(import.warnings.iter().any(|w| w.contains("hotfix")));
+    }
+
+    #[test]
+    fn test_imported_pulsefile_parses() {
+        let import = import_gitlab_ci(GITLAB_CI, "app").unwrap();
+        let pulsefile = import.to_pulsefile();
+        assert!(pulsefile.contains("# WARNING:"));
+
+        let pipeline = parse_pulsefile(&pulsefile).unwrap();
+        assert_eq!(pipeline.name, "app");
+        assert_eq!(pipeline.steps, import.pipeline.steps);
+        assert_eq!(pipeline.triggers, import.pipeline.triggers);
+    }
+
+    #[test]
+    fn test_regex_to_pattern() {
+        assert_eq!(regex_to_pattern(r"/^main$/").as_deref(), Some("main"));
+        assert_eq!(regex_to_pattern(r"/^feature\/.*$/").as_deref(), Some("feature/*"));
+        assert_eq!(regex_to_pattern(r"/^feature-.*$/"), None);
+        assert_eq!(regex_to_pattern(r"/release/"), None);
+    }
+}
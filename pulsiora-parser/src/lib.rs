@@ -1,6 +1,9 @@
 pub mod parser;
 pub mod grammar;
+pub mod gitlab;
+pub mod writer;
 
 pub use parser::*;
 pub use grammar::*;
-
+pub use gitlab::*;
+pub use writer::*;
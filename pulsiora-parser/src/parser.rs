@@ -1,192 +1,845 @@
 use crate::grammar::{PulsefileParser, Rule};
-use pulsiora_core::{GitTriggers, Pipeline, Step, Triggers, PulsioraError, Result};
+use pest::iterators::Pair;
+use pulsiora_core::{
+    valid_environment_name, ActionRef, AnsiMode, GitTriggers, ImageTrigger, ManualTrigger, Pipeline, PipelineOptions, PipelineStatus,
+    PipelineTrigger, PulsioraError, ReadinessCheck, Result, ScheduleTrigger, SshTarget, Step, StepReports, Trigger,
+    Triggers, WebhookTrigger,
+};
 use pest::Parser;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// A syntax slip the parser tolerated, such as `key = value` or a missing `;`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
 
 /// Parse a Pulsefile string into a Pipeline structure
 pub fn parse_pulsefile(input: &str) -> Result<Pipeline> {
+    parse_pulsefile_with_warnings(input).map(|(pipeline, _)| pipeline)
+}
+
+/// Parse a Pulsefile, also returning warnings for input that was accepted
+/// but isn't written in the canonical form
+pub fn parse_pulsefile_with_warnings(input: &str) -> Result<(Pipeline, Vec<ParseWarning>)> {
     let mut pairs = PulsefileParser::parse(Rule::file, input)
         .map_err(|e| PulsioraError::ParseError(format!("Parse error: {}", e)))?;
 
-    let pipeline_pair = pairs.next().ok_or_else(|| {
-        PulsioraError::ParseError("No pipeline found in file".to_string())
-    })?;
+    let pipeline_pair = pairs
+        .next()
+        .and_then(|file| file.into_inner().find(|p| p.as_rule() == Rule::pipeline))
+        .ok_or_else(|| PulsioraError::ParseError("No pipeline found in file".to_string()))?;
+
+    let mut warnings = Vec::new();
+    let pipeline = parse_pipeline(pipeline_pair, &mut warnings)?;
+    warnings.sort_by_key(|w| w.line);
+    Ok((pipeline, warnings))
+}
+
+fn warn(warnings: &mut Vec<ParseWarning>, pair: &Pair<Rule>, message: String) {
+    warnings.push(ParseWarning {
+        line: pair.as_span().start_pos().line_col().0,
+        message,
+    });
+}
 
-    parse_pipeline(pipeline_pair)
+/// Leading keyword of a field or block, e.g. `on_push` or `steps`
+fn keyword<'a>(pair: &Pair<'a, Rule>) -> &'a str {
+    pair.as_str()
+        .split(|c: char| c == ':' || c == '=' || c == '{' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
 }
 
-fn parse_pipeline(pair: pest::iterators::Pair<Rule>) -> Result<Pipeline> {
-    let mut name = String::new();
-    let mut version = String::new();
+/// Warn about `key = value` and a missing `;` on a single-value field
+fn check_field(pair: &Pair<Rule>, warnings: &mut Vec<ParseWarning>) {
+    let name = keyword(pair);
+    let mut terminated = false;
+    for inner in pair.clone().into_inner() {
+        match inner.as_rule() {
+            Rule::assign if inner.as_str() == "=" => warn(
+                warnings,
+                &inner,
+                format!("use `{}: ...` instead of `{} = ...`", name, name),
+            ),
+            Rule::semi => terminated = true,
+            _ => {}
+        }
+    }
+    if !terminated {
+        warn(warnings, pair, format!("missing `;` after `{}`", name));
+    }
+}
+
+/// Warn about a `;` after a closing brace
+fn check_block(pair: &Pair<Rule>, warnings: &mut Vec<ParseWarning>) {
+    if let Some(semi) = pair.clone().into_inner().find(|p| p.as_rule() == Rule::semi) {
+        warn(
+            warnings,
+            &semi,
+            format!("unnecessary `;` after `{}` block", keyword(pair)),
+        );
+    }
+}
+
+/// The value of a field: its last token other than the separators
+fn field_value<'a>(pair: &Pair<'a, Rule>) -> &'a str {
+    pair.clone()
+        .into_inner()
+        .filter(|p| !matches!(p.as_rule(), Rule::assign | Rule::semi))
+        .last()
+        .map(|p| p.as_str())
+        .unwrap_or("")
+}
+
+/// Canonical position of a top-level item: metadata, options, vars, triggers, steps
+fn section_rank(rule: Rule) -> usize {
+    match rule {
+        Rule::options => 1,
+        Rule::vars => 2,
+        Rule::triggers => 3,
+        Rule::steps => 4,
+        _ => 0,
+    }
+}
+
+fn parse_pipeline(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Result<Pipeline> {
+    let mut metadata = PipelineMetadata::default();
     let mut triggers = None;
     let mut steps = Vec::new();
+    let mut vars = BTreeMap::new();
+    let mut options = PipelineOptions::default();
+    let mut seen = HashSet::new();
+    let mut latest: Option<(usize, &str)> = None;
 
     for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::pipeline_metadata => {
-                let (parsed_name, parsed_version) = parse_pipeline_metadata(inner_pair)?;
-                if !parsed_name.is_empty() {
-                    name = parsed_name;
-                }
-                if !parsed_version.is_empty() {
-                    version = parsed_version;
-                }
+        let rule = inner_pair.as_rule();
+        let name = keyword(&inner_pair);
+        if !seen.insert(rule) {
+            warn(warnings, &inner_pair, format!("duplicate `{}`; the last one is used", name));
+        }
+        let rank = section_rank(rule);
+        match latest {
+            Some((latest_rank, latest_name)) if rank < latest_rank => warn(
+                warnings,
+                &inner_pair,
+                format!("`{}` should come before `{}`", name, latest_name),
+            ),
+            _ => latest = Some((rank, name)),
+        }
+
+        match rule {
+            Rule::options => {
+                check_block(&inner_pair, warnings);
+                options = parse_options(inner_pair, warnings)?;
+            }
+            Rule::vars => {
+                check_block(&inner_pair, warnings);
+                vars = parse_var_entries(inner_pair, warnings);
             }
             Rule::triggers => {
-                triggers = Some(parse_triggers(inner_pair)?);
+                check_block(&inner_pair, warnings);
+                triggers = Some(parse_triggers(inner_pair, warnings)?);
             }
             Rule::steps => {
-                steps = parse_steps(inner_pair)?;
+                check_block(&inner_pair, warnings);
+                steps = parse_steps(inner_pair, warnings)?;
             }
-            _ => {}
+            _ => parse_metadata_field(inner_pair, &mut metadata, warnings),
         }
     }
 
+    for step in &mut steps {
+        expand_step_vars(step, &vars)?;
+    }
+    for hook in [&mut options.before_each, &mut options.after_each].into_iter().flatten() {
+        *hook = expand_vars(hook, &vars)?;
+    }
+
+    let PipelineMetadata { name, version, description, labels, owners, environment } = metadata;
+    if let Some(environment) = environment.as_deref().filter(|e| !valid_environment_name(e)) {
+        return Err(PulsioraError::ParseError(format!(
+            "Invalid environment \"{}\": use letters, digits, `-`, `_` and `.`",
+            environment
+        )));
+    }
     Ok(Pipeline {
         name: if name.is_empty() { "default".to_string() } else { name },
         version: if version.is_empty() { "1.0".to_string() } else { version },
-        triggers: triggers.unwrap_or_else(|| Triggers {
-            git: GitTriggers::default(),
-        }),
+        description,
+        labels,
+        owners,
+        environment,
+        triggers: triggers.unwrap_or_default(),
         steps,
+        vars,
+        options,
     })
 }
 
-fn parse_pipeline_metadata(pair: pest::iterators::Pair<Rule>) -> Result<(String, String)> {
-    let mut name = String::new();
-    let mut version = String::new();
+/// Fields of the `pipeline {}` header
+#[derive(Default)]
+struct PipelineMetadata {
+    name: String,
+    version: String,
+    description: Option<String>,
+    labels: Vec<String>,
+    owners: Vec<String>,
+    environment: Option<String>,
+}
 
-    let text = pair.as_str();
-    
-    // Extract name field
-    if let Some(start) = text.find("name:") {
-        if let Some(end) = text[start..].find(";") {
-            let value_str = &text[start + 5..start + end];
-            if let Some(quote_start) = value_str.find('"') {
-                if let Some(quote_end) = value_str[quote_start + 1..].find('"') {
-                    name = unquote_string(&value_str[quote_start..quote_start + quote_end + 2]);
-                }
+fn parse_metadata_field(
+    field: Pair<Rule>,
+    metadata: &mut PipelineMetadata,
+    warnings: &mut Vec<ParseWarning>,
+) {
+    check_field(&field, warnings);
+    let value = field_value(&field);
+    match field.as_rule() {
+        Rule::metadata_name => metadata.name = unquote_string(value),
+        Rule::metadata_version => metadata.version = unquote_string(value),
+        Rule::metadata_description => metadata.description = Some(unquote_any_string(value)),
+        Rule::metadata_labels => metadata.labels = parse_field_string_list(field, warnings),
+        Rule::metadata_owners => metadata.owners = parse_field_string_list(field, warnings),
+        Rule::metadata_environment => metadata.environment = Some(unquote_string(value)),
+        _ => {}
+    }
+}
+
+fn parse_options(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Result<PipelineOptions> {
+    let mut options = PipelineOptions::default();
+    for field in pair.into_inner() {
+        if field.as_rule() == Rule::semi {
+            continue;
+        }
+        if field.as_rule() == Rule::option_problem_matchers {
+            check_block(&field, warnings);
+            for (name, pattern) in parse_var_entries(field, warnings) {
+                compile_problem_matcher(&pattern)
+                    .map_err(|e| PulsioraError::ParseError(format!("Problem matcher \"{}\": {}", name, e)))?;
+                options.problem_matchers.insert(name, pattern);
             }
+            continue;
         }
-    }
-    
-    // Extract version field
-    if let Some(start) = text.find("version:") {
-        if let Some(end) = text[start..].find(";") {
-            let value_str = &text[start + 8..start + end];
-            if let Some(quote_start) = value_str.find('"') {
-                if let Some(quote_end) = value_str[quote_start + 1..].find('"') {
-                    version = unquote_string(&value_str[quote_start..quote_start + quote_end + 2]);
-                }
+        check_field(&field, warnings);
+        let value = field_value(&field);
+        match field.as_rule() {
+            Rule::option_shell => options.shell = Some(unquote_string(value)),
+            Rule::option_windows_shell => options.windows_shell = Some(unquote_string(value)),
+            Rule::option_fail_fast => options.fail_fast = value == "true",
+            Rule::option_set_e => options.set_e = value == "true",
+            Rule::option_image => options.image = Some(unquote_string(value)),
+            Rule::option_before_each => options.before_each = Some(unquote_any_string(value)),
+            Rule::option_after_each => options.after_each = Some(unquote_any_string(value)),
+            Rule::option_ansi => {
+                let value = unquote_string(value);
+                options.ansi = AnsiMode::parse(&value).ok_or_else(|| {
+                    PulsioraError::ParseError(format!("Invalid ansi \"{}\": expected \"keep\" or \"strip\"", value))
+                })?;
+            }
+            Rule::option_kill_grace => {
+                let value = unquote_string(value);
+                options.kill_grace_secs = parse_duration_secs(&value).ok_or_else(|| {
+                    PulsioraError::ParseError(format!(
+                        "Invalid kill_grace \"{}\": expected a duration such as \"10s\" or \"1m\"",
+                        value
+                    ))
+                })?;
             }
+            _ => {}
         }
     }
-
-    Ok((name, version))
+    Ok(options)
 }
 
-fn parse_triggers(pair: pest::iterators::Pair<Rule>) -> Result<Triggers> {
-    let mut git_triggers = GitTriggers::default();
+fn parse_triggers(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Result<Triggers> {
+    let mut sources = Vec::new();
 
     for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::git {
-            git_triggers = parse_git_triggers(inner_pair)?;
+        if inner_pair.as_rule() != Rule::semi {
+            check_block(&inner_pair, warnings);
         }
+        let trigger = match inner_pair.as_rule() {
+            Rule::git => Trigger::Git(parse_git_triggers(inner_pair, warnings)?),
+            Rule::manual_trigger => Trigger::Manual(ManualTrigger {}),
+            Rule::schedule_trigger => {
+                let fields = TriggerFields::collect(inner_pair, warnings)?;
+                Trigger::Schedule(ScheduleTrigger {
+                    cron: required_field(fields.cron, "schedule", "cron")?,
+                    branch: fields.branch,
+                })
+            }
+            Rule::image_trigger => {
+                let fields = TriggerFields::collect(inner_pair, warnings)?;
+                Trigger::Image(ImageTrigger {
+                    image: required_field(fields.name, "image", "name")?,
+                    tags: fields.tags,
+                })
+            }
+            Rule::pipeline_trigger => {
+                let fields = TriggerFields::collect(inner_pair, warnings)?;
+                Trigger::Pipeline(PipelineTrigger {
+                    pipeline: required_field(fields.name, "pipeline", "name")?,
+                    statuses: fields.statuses,
+                })
+            }
+            Rule::webhook_trigger => {
+                let fields = TriggerFields::collect(inner_pair, warnings)?;
+                Trigger::Webhook(WebhookTrigger {
+                    name: required_field(fields.name, "webhook", "name")?,
+                })
+            }
+            _ => continue,
+        };
+        sources.push(trigger);
     }
 
-    Ok(Triggers { git: git_triggers })
+    Ok(Triggers::new(sources))
 }
 
-fn parse_git_triggers(pair: pest::iterators::Pair<Rule>) -> Result<GitTriggers> {
-    let mut triggers = GitTriggers::default();
-    let text = pair.as_str();
-
-    // Parse each trigger field by searching the text
-    triggers.on_push = parse_boolean_field(text, "on_push");
-    triggers.on_pull_request = parse_boolean_field(text, "on_pull_request");
-    triggers.on_merge = parse_boolean_field(text, "on_merge");
-    triggers.on_tag = parse_boolean_field(text, "on_tag");
-    triggers.on_release = parse_boolean_field(text, "on_release");
-    triggers.on_branch_create = parse_boolean_field(text, "on_branch_create");
-    triggers.on_branch_delete = parse_boolean_field(text, "on_branch_delete");
-
-    // Parse branches
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::branch_list {
-            triggers.branches = parse_branch_list(inner_pair)?;
+/// Fields shared by the non-git trigger blocks
+#[derive(Default)]
+struct TriggerFields {
+    cron: Option<String>,
+    branch: Option<String>,
+    name: Option<String>,
+    tags: Vec<String>,
+    statuses: Vec<PipelineStatus>,
+}
+
+impl TriggerFields {
+    fn collect(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Result<Self> {
+        let mut fields = Self::default();
+        for field in pair.into_inner() {
+            if field.as_rule() == Rule::semi {
+                continue;
+            }
+            check_field(&field, warnings);
+            match field.as_rule() {
+                Rule::trigger_cron => fields.cron = Some(unquote_string(field_value(&field))),
+                Rule::trigger_branch => fields.branch = Some(unquote_string(field_value(&field))),
+                Rule::trigger_name => fields.name = Some(unquote_string(field_value(&field))),
+                Rule::trigger_tags => fields.tags = parse_field_string_list(field, warnings),
+                Rule::trigger_statuses => {
+                    fields.statuses = parse_field_string_list(field, warnings)
+                        .iter()
+                        .map(|s| parse_pipeline_status(s))
+                        .collect::<Result<_>>()?;
+                }
+                _ => {}
+            }
         }
+        Ok(fields)
     }
+}
 
-    Ok(triggers)
+fn required_field(value: Option<String>, block: &str, field: &str) -> Result<String> {
+    value.ok_or_else(|| {
+        PulsioraError::ParseError(format!("{} trigger requires a `{}` field", block, field))
+    })
 }
 
-fn parse_boolean_field(text: &str, field_name: &str) -> bool {
-    if let Some(start) = text.find(&format!("{}:", field_name)) {
-        if let Some(end) = text[start..].find(";") {
-            let value_str = text[start + field_name.len() + 1..start + end].trim();
-            return value_str == "true";
-        }
+fn parse_pipeline_status(s: &str) -> Result<PipelineStatus> {
+    match s.to_ascii_lowercase().as_str() {
+        "success" => Ok(PipelineStatus::Success),
+        "failed" => Ok(PipelineStatus::Failed),
+        "cancelled" => Ok(PipelineStatus::Cancelled),
+        "skipped" => Ok(PipelineStatus::Skipped),
+        _ => Err(PulsioraError::ParseError(format!(
+            "Unknown pipeline status \"{}\": expected success, failed, cancelled or skipped",
+            s
+        ))),
     }
-    false
 }
 
-fn parse_branch_list(pair: pest::iterators::Pair<Rule>) -> Result<Vec<String>> {
-    let mut branches = Vec::new();
+fn parse_git_triggers(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Result<GitTriggers> {
+    let mut triggers = GitTriggers::default();
 
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::string_literal {
-            branches.push(unquote_string(inner_pair.as_str()));
+    for field in pair.into_inner() {
+        if field.as_rule() == Rule::semi {
+            continue;
+        }
+        check_field(&field, warnings);
+        match field.as_rule() {
+            Rule::git_flag => {
+                let enabled = field_value(&field) == "true";
+                match keyword(&field) {
+                    "on_push" => triggers.on_push = enabled,
+                    "on_pull_request" => triggers.on_pull_request = enabled,
+                    "on_merge" => triggers.on_merge = enabled,
+                    "on_tag" => triggers.on_tag = enabled,
+                    "on_release" => triggers.on_release = enabled,
+                    "on_branch_create" => triggers.on_branch_create = enabled,
+                    "on_branch_delete" => triggers.on_branch_delete = enabled,
+                    _ => {}
+                }
+            }
+            Rule::git_branches => triggers.branches = parse_field_string_list(field, warnings),
+            Rule::git_ignore_senders => {
+                triggers.ignore_senders = parse_field_string_list(field, warnings);
+            }
+            Rule::git_pr_labels => triggers.pr_labels = parse_field_string_list(field, warnings),
+            Rule::git_ignore_drafts => triggers.ignore_drafts = field_value(&field) == "true",
+            _ => {}
         }
     }
 
-    Ok(branches)
+    Ok(triggers)
 }
 
-fn parse_steps(pair: pest::iterators::Pair<Rule>) -> Result<Vec<Step>> {
-    let mut steps = Vec::new();
+/// Collect the strings of a `key: [..]` field wrapping a `string_list`
+fn parse_field_string_list(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Vec<String> {
+    let mut values = Vec::new();
+    for item in pair
+        .into_inner()
+        .filter(|p| p.as_rule() == Rule::string_list)
+        .flat_map(|list| list.into_inner())
+    {
+        if item.as_rule() == Rule::trailing_comma {
+            warn(warnings, &item, "trailing `,` in list".to_string());
+        } else {
+            values.push(unquote_string(item.as_str()));
+        }
+    }
+    values
+}
+
+/// A step as written, before `extends` is resolved; unset fields are inherited
+#[derive(Default)]
+struct StepDecl {
+    name: String,
+    extends: Option<String>,
+    run: Option<String>,
+    allow_failure: Option<bool>,
+    user: Option<String>,
+    group: Option<String>,
+    umask: Option<String>,
+    timeout_secs: Option<u64>,
+    env: BTreeMap<String, String>,
+    background: Option<bool>,
+    tty: Option<bool>,
+    ready_when: Option<ReadinessCheck>,
+    artifacts: Option<Vec<String>>,
+    encoding: Option<String>,
+    image: Option<String>,
+    runner: Option<String>,
+    reports: Option<StepReports>,
+    uses: Option<String>,
+    with: BTreeMap<String, String>,
+}
+
+fn parse_steps(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Result<Vec<Step>> {
+    let mut decls = Vec::new();
 
     for inner_pair in pair.into_inner() {
         if inner_pair.as_rule() == Rule::step {
-            steps.push(parse_step(inner_pair)?);
+            check_block(&inner_pair, warnings);
+            decls.push(parse_step(inner_pair, warnings)?);
         }
     }
 
+    let steps = decls
+        .iter()
+        .map(|decl| resolve_step(decl, &decls, &mut Vec::new()))
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(step) = steps.iter().find(|s| s.ready_when.is_some() && !s.background) {
+        return Err(PulsioraError::ParseError(format!(
+            "Step \"{}\" has `ready_when` but is not a background step",
+            step.name
+        )));
+    }
+    if let Some(step) = steps.iter().find(|s| s.tty && s.background) {
+        return Err(PulsioraError::ParseError(format!(
+            "Step \"{}\" has `tty` but is a background step",
+            step.name
+        )));
+    }
+    if let Some(step) = steps.iter().find(|s| s.timeout_secs.is_some() && s.background) {
+        return Err(PulsioraError::ParseError(format!(
+            "Step \"{}\" has `timeout` but is a background step",
+            step.name
+        )));
+    }
+    if let Some(step) = steps.iter().find(|s| s.uses.is_some() && !s.run.is_empty()) {
+        return Err(PulsioraError::ParseError(format!(
+            "Step \"{}\" has both `uses` and `run`",
+            step.name
+        )));
+    }
+    if let Some(step) = steps.iter().find(|s| s.uses.is_some() && s.runner.is_some()) {
+        return Err(PulsioraError::ParseError(format!(
+            "Step \"{}\" has `uses` but runs on a remote `runner`",
+            step.name
+        )));
+    }
+    if let Some(step) = steps.iter().find(|s| s.uses.is_none() && !s.with.is_empty()) {
+        return Err(PulsioraError::ParseError(format!(
+            "Step \"{}\" has `with` but no `uses`",
+            step.name
+        )));
+    }
     Ok(steps)
 }
 
-fn parse_step(pair: pest::iterators::Pair<Rule>) -> Result<Step> {
-    let mut name = String::new();
-    let mut run = String::new();
-    let mut allow_failure = false;
+fn parse_step(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> Result<StepDecl> {
+    let mut inner = pair.into_inner();
+    let name = inner
+        .next()
+        .map(|p| unquote_string(p.as_str()))
+        .unwrap_or_default();
+    let mut step = StepDecl {
+        name,
+        ..Default::default()
+    };
 
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::string_literal => {
-                // First string_literal is the step name
-                if name.is_empty() {
-                    name = unquote_string(inner_pair.as_str());
+    for field in inner {
+        let rule = field.as_rule();
+        match rule {
+            Rule::semi => continue,
+            Rule::step_env | Rule::step_with | Rule::step_ready_when | Rule::step_reports => {
+                check_block(&field, warnings)
+            }
+            _ => check_field(&field, warnings),
+        }
+        let value = field_value(&field);
+        match rule {
+            Rule::step_run => step.run = Some(unquote_any_string(value)),
+            Rule::step_allow_failure => step.allow_failure = Some(value == "true"),
+            Rule::step_user => step.user = Some(unquote_string(value)),
+            Rule::step_group => step.group = Some(unquote_string(value)),
+            Rule::step_env => step.env.extend(parse_var_entries(field, warnings)),
+            Rule::step_with => step.with.extend(parse_var_entries(field, warnings)),
+            Rule::step_uses => {
+                let uses = unquote_string(value);
+                if let Err(PulsioraError::InvalidConfiguration(reason)) = ActionRef::parse(&uses) {
+                    return Err(PulsioraError::ParseError(format!("Step \"{}\": {}", step.name, reason)));
+                }
+                step.uses = Some(uses);
+            }
+            Rule::step_extends => step.extends = Some(unquote_string(value)),
+            Rule::step_image => step.image = Some(unquote_string(value)),
+            Rule::step_runner => {
+                let runner = unquote_string(value);
+                if let Err(PulsioraError::InvalidConfiguration(reason)) = SshTarget::parse(&runner) {
+                    return Err(PulsioraError::ParseError(format!("Step \"{}\": {}", step.name, reason)));
+                }
+                step.runner = Some(runner);
+            }
+            Rule::step_background => step.background = Some(value == "true"),
+            Rule::step_tty => step.tty = Some(value == "true"),
+            Rule::step_artifacts => {
+                let artifacts = parse_field_string_list(field, warnings);
+                if let Some(path) = artifacts.iter().find(|p| !is_relative_artifact_path(p)) {
+                    return Err(invalid_artifact_path(path, &step.name));
+                }
+                step.artifacts = Some(artifacts);
+            }
+            Rule::step_ready_when => {
+                step.ready_when = Some(parse_ready_when(field, &step.name, warnings)?);
+            }
+            Rule::step_reports => step.reports = Some(parse_reports(field, &step.name, warnings)?),
+            Rule::step_encoding => {
+                let encoding = unquote_string(value);
+                if encoding_rs::Encoding::for_label(encoding.as_bytes()).is_none() {
+                    return Err(PulsioraError::ParseError(format!(
+                        "Unknown encoding \"{}\" in step \"{}\": expected a label such as \"utf-8\" or \"shift_jis\"",
+                        encoding, step.name
+                    )));
                 }
+                step.encoding = Some(encoding);
             }
-            Rule::multiline_string => {
-                run = unquote_multiline_string(inner_pair.as_str());
+            Rule::step_umask => {
+                let umask = unquote_string(value);
+                if !is_valid_umask(&umask) {
+                    return Err(PulsioraError::ParseError(format!(
+                        "Invalid umask \"{}\" in step \"{}\": expected an octal mask such as \"0022\"",
+                        umask, step.name
+                    )));
+                }
+                step.umask = Some(umask);
             }
-            Rule::boolean => {
-                allow_failure = inner_pair.as_str() == "true";
+            Rule::step_timeout => {
+                let timeout = unquote_string(value);
+                step.timeout_secs = Some(parse_duration_secs(&timeout).ok_or_else(|| {
+                    PulsioraError::ParseError(format!(
+                        "Invalid timeout \"{}\" in step \"{}\": expected a duration such as \"10m\" or \"1h\"",
+                        timeout, step.name
+                    ))
+                })?);
             }
             _ => {}
         }
     }
 
-    Ok(Step {
-        name,
-        run: run.trim().to_string(),
-        allow_failure,
-    })
+    Ok(step)
+}
+
+fn parse_ready_when(
+    pair: Pair<Rule>,
+    step_name: &str,
+    warnings: &mut Vec<ParseWarning>,
+) -> Result<ReadinessCheck> {
+    let mut check = ReadinessCheck::default();
+    for field in pair.into_inner() {
+        if matches!(field.as_rule(), Rule::assign | Rule::semi) {
+            continue;
+        }
+        check_field(&field, warnings);
+        let value = unquote_string(field_value(&field));
+        match field.as_rule() {
+            Rule::ready_http => check.http = Some(value),
+            Rule::ready_timeout => {
+                check.timeout_secs = parse_duration_secs(&value).ok_or_else(|| {
+                    PulsioraError::ParseError(format!(
+                        "Invalid timeout \"{}\" in step \"{}\": expected a duration such as \"60s\" or \"2m\"",
+                        value, step_name
+                    ))
+                })?;
+            }
+            _ => {}
+        }
+    }
+    Ok(check)
+}
+
+fn parse_reports(pair: Pair<Rule>, step_name: &str, warnings: &mut Vec<ParseWarning>) -> Result<StepReports> {
+    let mut reports = StepReports::default();
+    for field in pair.into_inner() {
+        if matches!(field.as_rule(), Rule::assign | Rule::semi) {
+            continue;
+        }
+        check_field(&field, warnings);
+        let value = field_value(&field);
+        match field.as_rule() {
+            Rule::report_coverage => {
+                let path = unquote_string(value);
+                if !is_relative_artifact_path(&path) {
+                    return Err(PulsioraError::ParseError(format!(
+                        "Invalid coverage report path \"{}\" in step \"{}\": paths must be relative and stay inside the working directory",
+                        path, step_name
+                    )));
+                }
+                reports.coverage = Some(path);
+            }
+            Rule::report_min_coverage => {
+                let min = value.parse::<f64>().ok().filter(|min| *min <= 100.0).ok_or_else(|| {
+                    PulsioraError::ParseError(format!(
+                        "Invalid min_coverage {} in step \"{}\": expected a percentage from 0 to 100",
+                        value, step_name
+                    ))
+                })?;
+                reports.min_coverage = Some(min);
+            }
+            _ => {}
+        }
+    }
+    if reports.min_coverage.is_some() && reports.coverage.is_none() {
+        return Err(PulsioraError::ParseError(format!(
+            "Step \"{}\" has `min_coverage` but no `coverage` report",
+            step_name
+        )));
+    }
+    Ok(reports)
+}
+
+/// Parse a duration like "90", "90s", "5m" or "1h" into seconds
+pub fn parse_duration_secs(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Build a step by merging it over the step it extends. Fields set on the
+/// step win; env entries are merged key by key. `chain` detects cycles.
+fn resolve_step(decl: &StepDecl, decls: &[StepDecl], chain: &mut Vec<String>) -> Result<Step> {
+    if chain.contains(&decl.name) {
+        chain.push(decl.name.clone());
+        return Err(PulsioraError::ParseError(format!(
+            "Circular step extends: {}",
+            chain.join(" -> ")
+        )));
+    }
+
+    let mut step = match &decl.extends {
+        Some(parent) => {
+            let parent_decl = decls.iter().find(|d| &d.name == parent).ok_or_else(|| {
+                PulsioraError::ParseError(format!(
+                    "Step \"{}\" extends unknown step \"{}\"",
+                    decl.name, parent
+                ))
+            })?;
+            chain.push(decl.name.clone());
+            let mut base = resolve_step(parent_decl, decls, chain)?;
+            chain.pop();
+            base.name = decl.name.clone();
+            base
+        }
+        None => Step::new(decl.name.clone(), String::new()),
+    };
+
+    if let Some(run) = &decl.run {
+        step.run = run.clone();
+    }
+    if let Some(allow_failure) = decl.allow_failure {
+        step.allow_failure = allow_failure;
+    }
+    if decl.user.is_some() {
+        step.user = decl.user.clone();
+    }
+    if decl.group.is_some() {
+        step.group = decl.group.clone();
+    }
+    if decl.umask.is_some() {
+        step.umask = decl.umask.clone();
+    }
+    if decl.timeout_secs.is_some() {
+        step.timeout_secs = decl.timeout_secs;
+    }
+    step.env.extend(decl.env.clone());
+    if let Some(background) = decl.background {
+        step.background = background;
+    }
+    if let Some(tty) = decl.tty {
+        step.tty = tty;
+    }
+    if decl.ready_when.is_some() {
+        step.ready_when = decl.ready_when.clone();
+    }
+    if let Some(artifacts) = &decl.artifacts {
+        step.artifacts = artifacts.clone();
+    }
+    if decl.encoding.is_some() {
+        step.encoding = decl.encoding.clone();
+    }
+    if decl.image.is_some() {
+        step.image = decl.image.clone();
+    }
+    if decl.runner.is_some() {
+        step.runner = decl.runner.clone();
+    }
+    if let Some(reports) = &decl.reports {
+        step.reports = reports.clone();
+    }
+    if decl.uses.is_some() {
+        step.uses = decl.uses.clone();
+    }
+    step.with.extend(decl.with.clone());
+
+    Ok(step)
+}
+
+/// Collect `KEY: "value";` entries from a vars or env block
+fn parse_var_entries(pair: Pair<Rule>, warnings: &mut Vec<ParseWarning>) -> BTreeMap<String, String> {
+    pair.into_inner()
+        .filter(|p| p.as_rule() == Rule::var_entry)
+        .map(|entry| {
+            check_field(&entry, warnings);
+            (keyword(&entry).to_string(), unquote_string(field_value(&entry)))
+        })
+        .collect()
+}
+
+/// Substitute `${vars.NAME}` references in a step's run command and env values
+fn expand_step_vars(step: &mut Step, vars: &BTreeMap<String, String>) -> Result<()> {
+    step.run = expand_vars(&step.run, vars)?;
+    for value in step.env.values_mut() {
+        *value = expand_vars(value, vars)?;
+    }
+    for path in &mut step.artifacts {
+        *path = expand_vars(path, vars)?;
+        // A variable can hold `..` or an absolute path
+        if !is_relative_artifact_path(path) {
+            return Err(invalid_artifact_path(path, &step.name));
+        }
+    }
+    Ok(())
+}
+
+fn invalid_artifact_path(path: &str, step_name: &str) -> PulsioraError {
+    PulsioraError::ParseError(format!(
+        "Invalid artifact path \"{}\" in step \"{}\": paths must be relative and stay inside the working directory",
+        path, step_name
+    ))
+}
+
+/// Replace every `${vars.NAME}` in `text`; unknown names are an error
+pub fn expand_vars(text: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    const PREFIX: &str = "${vars.";
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(PREFIX) {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + PREFIX.len()..];
+        let end = after.find('}').ok_or_else(|| {
+            PulsioraError::ParseError(format!("Unterminated variable reference in: {}", text))
+        })?;
+        let name = &after[..end];
+        let value = vars.get(name).ok_or_else(|| {
+            PulsioraError::ParseError(format!("Undefined variable: vars.{}", name))
+        })?;
+        result.push_str(value);
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Compile a problem matcher's regex, which must have a `message` group;
+/// the error says what's wrong with it
+pub fn compile_problem_matcher(pattern: &str) -> std::result::Result<regex::Regex, String> {
+    let regex = regex::Regex::new(pattern).map_err(|e| format!("invalid regex: {}", e))?;
+    if !regex.capture_names().any(|name| name == Some("message")) {
+        return Err("regex needs a `(?P<message>...)` group".to_string());
+    }
+    Ok(regex)
+}
+
+/// Whether an artifact path is relative and has no `..` components
+pub fn is_relative_artifact_path(path: &str) -> bool {
+    let path = std::path::Path::new(path);
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+}
+
+fn is_valid_umask(umask: &str) -> bool {
+    (3..=4).contains(&umask.len()) && umask.chars().all(|c| ('0'..='7').contains(&c))
 }
 
 fn unquote_string(s: &str) -> String {
     s.trim_matches('"').to_string()
 }
 
+/// Unquote a value that may be written as `"..."` or `"""..."""`
+fn unquote_any_string(s: &str) -> String {
+    if s.starts_with("\"\"\"") {
+        unquote_multiline_string(s)
+    } else {
+        unquote_string(s)
+    }
+}
+
 fn unquote_multiline_string(s: &str) -> String {
     s.trim()
         .strip_prefix("\"\"\"")
@@ -225,8 +878,8 @@ pipeline {
         let pipeline = parse_pulsefile(input).unwrap();
         assert_eq!(pipeline.name, "test-pipeline");
         assert_eq!(pipeline.version, "1.0");
-        assert!(pipeline.triggers.git.on_push);
-        assert_eq!(pipeline.triggers.git.branches, vec!["main"]);
+        assert!(pipeline.triggers.git().unwrap().on_push);
+        assert_eq!(pipeline.triggers.git().unwrap().branches, vec!["main"]);
         assert_eq!(pipeline.steps.len(), 1);
         assert_eq!(pipeline.steps[0].name, "test");
     }
@@ -286,10 +939,10 @@ pipeline {
         let pipeline = parse_pulsefile(input).unwrap();
         assert_eq!(pipeline.name, "build-and-deploy");
         assert_eq!(pipeline.version, "1.0");
-        assert!(pipeline.triggers.git.on_push);
-        assert!(pipeline.triggers.git.on_pull_request);
-        assert!(pipeline.triggers.git.on_tag);
-        assert_eq!(pipeline.triggers.git.branches, vec!["*"]);
+        assert!(pipeline.triggers.git().unwrap().on_push);
+        assert!(pipeline.triggers.git().unwrap().on_pull_request);
+        assert!(pipeline.triggers.git().unwrap().on_tag);
+        assert_eq!(pipeline.triggers.git().unwrap().branches, vec!["*"]);
         assert_eq!(pipeline.steps.len(), 5);
         assert_eq!(pipeline.steps[0].name, "install");
         assert_eq!(pipeline.steps[1].name, "lint");
@@ -387,4 +1040,694 @@ pipeline {
         assert!(pipeline.steps[1].allow_failure);
         assert!(!pipeline.steps[2].allow_failure);
     }
+
+    #[test]
+    fn test_parse_sender_and_pull_request_filters() {
+        let input = r#"
+pipeline {
+  triggers {
+    git {
+      on_push: true;
+      branches: ["main"];
+      ignore_senders: ["dependabot[bot]", "renovate[bot]"];
+      pr_labels: ["ci"];
+      ignore_drafts: true;
+    }
+  }
+  steps {
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        assert_eq!(
+            pipeline.triggers.git().unwrap().ignore_senders,
+            vec!["dependabot[bot]", "renovate[bot]"]
+        );
+        assert_eq!(pipeline.triggers.git().unwrap().pr_labels, vec!["ci"]);
+        assert!(pipeline.triggers.git().unwrap().ignore_drafts);
+    }
+
+    #[test]
+    fn test_parse_trigger_sources() {
+        let input = r#"
+pipeline {
+  triggers {
+    schedule {
+      cron: "0 3 * * *";
+      branch: "develop";
+    }
+    manual { }
+    image {
+      name: "ghcr.io/acme/base";
+      tags: ["v*"];
+    }
+    pipeline {
+      name: "build";
+      statuses: ["success", "failed"];
+    }
+    webhook {
+      name: "deploy-hook";
+    }
+  }
+  steps {
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        assert!(pipeline.triggers.git().is_none());
+        assert_eq!(
+            pipeline.triggers.sources,
+            vec![
+                Trigger::Schedule(ScheduleTrigger {
+                    cron: "0 3 * * *".to_string(),
+                    branch: Some("develop".to_string()),
+                }),
+                Trigger::Manual(ManualTrigger {}),
+                Trigger::Image(ImageTrigger {
+                    image: "ghcr.io/acme/base".to_string(),
+                    tags: vec!["v*".to_string()],
+                }),
+                Trigger::Pipeline(PipelineTrigger {
+                    pipeline: "build".to_string(),
+                    statuses: vec![PipelineStatus::Success, PipelineStatus::Failed],
+                }),
+                Trigger::Webhook(WebhookTrigger {
+                    name: "deploy-hook".to_string(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_trigger_missing_required_field() {
+        let input = r#"
+pipeline {
+  triggers {
+    schedule { }
+  }
+  steps {
+  }
+}
+"#;
+        assert!(parse_pulsefile(input).is_err());
+    }
+
+    #[test]
+    fn test_parse_vars_and_env() {
+        let input = r#"
+pipeline {
+  name: "shop";
+  vars {
+    APP_NAME: "shop";
+    REGISTRY: "ghcr.io/acme";
+  }
+  triggers {
+    git {
+      on_push: true;
+    }
+  }
+  steps {
+    step "build" {
+      env {
+        IMAGE: "${vars.REGISTRY}/${vars.APP_NAME}";
+      }
+      run: """docker build -t ${vars.REGISTRY}/${vars.APP_NAME} .""";
+    }
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        assert_eq!(pipeline.vars.get("APP_NAME").map(String::as_str), Some("shop"));
+        let step = &pipeline.steps[0];
+        assert_eq!(step.run, "docker build -t ghcr.io/acme/shop .");
+        assert_eq!(step.env.get("IMAGE").map(String::as_str), Some("ghcr.io/acme/shop"));
+    }
+
+    #[test]
+    fn test_parse_undefined_var() {
+        let input = r#"
+pipeline {
+  triggers {
+  }
+  steps {
+    step "build" {
+      run: """echo ${vars.MISSING}""";
+    }
+  }
+}
+"#;
+        assert!(parse_pulsefile(input).is_err());
+    }
+
+    #[test]
+    fn test_expand_vars_leaves_other_references() {
+        let vars = BTreeMap::from([("A".to_string(), "1".to_string())]);
+        assert_eq!(expand_vars("${vars.A}-${HOME}-$vars", &vars).unwrap(), "1-${HOME}-$vars");
+    }
+
+    #[test]
+    fn test_parse_step_identity() {
+        let input = r#"
+pipeline {
+  triggers {
+    git {
+      on_push: true;
+    }
+  }
+  steps {
+    step "deploy" {
+      user: "builder";
+      group: "deploy";
+      umask: "0022";
+      run: """./deploy.sh""";
+    }
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        let step = &pipeline.steps[0];
+        assert_eq!(step.run, "./deploy.sh");
+        assert_eq!(step.user.as_deref(), Some("builder"));
+        assert_eq!(step.group.as_deref(), Some("deploy"));
+        assert_eq!(step.umask.as_deref(), Some("0022"));
+    }
+
+    #[test]
+    fn test_parse_step_encoding() {
+        let pulsefile = |encoding: &str| {
+            format!(
+                r#"
+pipeline {{
+  triggers {{
+    git {{
+      on_push: true;
+    }}
+  }}
+  steps {{
+    step "build" {{
+      encoding: "{}";
+      run: """msbuild""";
+    }}
+  }}
+}}
+"#,
+                encoding
+            )
+        };
+        let pipeline = parse_pulsefile(&pulsefile("shift_jis")).unwrap();
+        assert_eq!(pipeline.steps[0].encoding.as_deref(), Some("shift_jis"));
+
+        let err = parse_pulsefile(&pulsefile("klingon")).unwrap_err();
+        assert!(err.to_string().contains("Unknown encoding \"klingon\""));
+    }
+
+    #[test]
+    fn test_parse_step_invalid_umask() {
+        let input = r#"
+pipeline {
+  triggers {
+    git {
+    }
+  }
+  steps {
+    step "deploy" {
+      run: """./deploy.sh""";
+      umask: "0999";
+    }
+  }
+}
+"#;
+        assert!(parse_pulsefile(input).is_err());
+    }
+
+    #[test]
+    fn test_parse_step_timeout_errors() {
+        let invalid = r#"pipeline { steps { step "a" { timeout: "soon"; run: "true"; } } }"#;
+        let err = parse_pulsefile(invalid).unwrap_err().to_string();
+        assert!(err.contains("Invalid timeout \"soon\" in step \"a\""), "{}", err);
+
+        let background = r#"pipeline { steps { step "db" { background: true; timeout: "1m"; run: "true"; } } }"#;
+        let err = parse_pulsefile(background).unwrap_err().to_string();
+        assert!(err.contains("has `timeout` but is a background step"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_step_extends() {
+        let input = r#"
+pipeline {
+  triggers {
+    git {
+    }
+  }
+  steps {
+    step "base-test" {
+      user: "builder";
+      timeout: "10m";
+      env {
+        LANG: "rust";
+        CI: "true";
+      }
+      run: """make test LANG=$LANG""";
+    }
+    step "test-py" {
+      extends: "base-test";
+      env {
+        LANG: "py";
+      }
+    }
+    step "test-py-flaky" {
+      extends: "test-py";
+      allow_failure: true;
+      timeout: "90s";
+      run: """make flaky""";
+    }
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        let test_py = &pipeline.steps[1];
+        assert_eq!(test_py.name, "test-py");
+        assert_eq!(test_py.run, "make test LANG=$LANG");
+        assert_eq!(test_py.user.as_deref(), Some("builder"));
+        assert_eq!(test_py.timeout_secs, Some(600));
+        assert_eq!(test_py.env.get("LANG").map(String::as_str), Some("py"));
+        assert_eq!(test_py.env.get("CI").map(String::as_str), Some("true"));
+        assert!(!test_py.allow_failure);
+
+        let flaky = &pipeline.steps[2];
+        assert_eq!(flaky.run, "make flaky");
+        assert!(flaky.allow_failure);
+        assert_eq!(flaky.timeout_secs, Some(90));
+        assert_eq!(flaky.env.get("LANG").map(String::as_str), Some("py"));
+    }
+
+    #[test]
+    fn test_parse_step_extends_errors() {
+        let unknown = r#"
+pipeline {
+  triggers { git { } }
+  steps {
+    step "a" { extends: "missing"; }
+  }
+}
+"#;
+        let err = parse_pulsefile(unknown).unwrap_err().to_string();
+        assert!(err.contains("unknown step \"missing\""));
+
+        let circular = r#"
+pipeline {
+  triggers { git { } }
+  steps {
+    step "a" { extends: "b"; }
+    step "b" { extends: "a"; }
+  }
+}
+"#;
+        let err = parse_pulsefile(circular).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"));
+    }
+
+    #[test]
+    fn test_parse_pipeline_description_labels_owners() {
+        let input = r#"
+pipeline {
+  name: "deploy";
+  version: "2.0";
+  description: "Deploys the name: service";
+  labels: ["deploy", "prod"];
+  owners: ["@acme/platform", "alice"];
+  environment: "production";
+  triggers {
+    git {
+    }
+  }
+  steps {
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        assert_eq!(pipeline.name, "deploy");
+        assert_eq!(pipeline.version, "2.0");
+        assert_eq!(pipeline.description.as_deref(), Some("Deploys the name: service"));
+        assert_eq!(pipeline.labels, vec!["deploy", "prod"]);
+        assert_eq!(pipeline.owners, vec!["@acme/platform", "alice"]);
+        assert_eq!(pipeline.environment.as_deref(), Some("production"));
+
+        let invalid = input.replace(r#""production""#, r#""prod/eu""#);
+        let err = parse_pulsefile(&invalid).unwrap_err().to_string();
+        assert!(err.contains("Invalid environment \"prod/eu\""));
+    }
+
+    #[test]
+    fn test_parse_tolerates_syntax_slips() {
+        let input = r#"
+pipeline {
+  name = "tolerant"
+  steps {
+    step "build" {
+      run: "cargo build"
+      allow_failure = true;
+    };
+  }
+  triggers {
+    git {
+      branches: ["main", "release/*",];
+      on_push: true;
+    }
+  }
+}
+"#;
+        let (pipeline, warnings) = parse_pulsefile_with_warnings(input).unwrap();
+        assert_eq!(pipeline.name, "tolerant");
+        assert_eq!(pipeline.steps[0].run, "cargo build");
+        assert!(pipeline.steps[0].allow_failure);
+        assert_eq!(pipeline.triggers.git().unwrap().branches, vec!["main", "release/*"]);
+        assert!(pipeline.triggers.git().unwrap().on_push);
+
+        let messages: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "line 3: use `name: ...` instead of `name = ...`",
+                "line 3: missing `;` after `name`",
+                "line 6: missing `;` after `run`",
+                "line 7: use `allow_failure: ...` instead of `allow_failure = ...`",
+                "line 8: unnecessary `;` after `step` block",
+                "line 10: `triggers` should come before `steps`",
+                "line 12: trailing `,` in list",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_canonical_file_has_no_warnings() {
+        let input = r#"
+pipeline {
+  name: "clean";
+  vars {
+    TARGET: "release";
+  }
+  triggers {
+    git {
+      on_push: true;
+      branches: ["main"];
+    }
+  }
+  steps {
+    step "build" {
+      run: """cargo build --${vars.TARGET}""";
+    }
+  }
+}
+"#;
+        let (_, warnings) = parse_pulsefile_with_warnings(input).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_parse_pipeline_options() {
+        let input = r#"
+pipeline {
+  name: "strict";
+  options {
+    shell: "bash";
+    windows_shell: "pwsh";
+    fail_fast: false;
+    set_e: true;
+    image: "rust:1.80";
+    kill_grace: "1m";
+    before_each: """
+      . ./ci/env.sh
+      echo "starting $PULSE_STEP_NAME"
+    """;
+    after_each: "./ci/report-timing.sh";
+    problem_matchers {
+      gcc: "^(?P<file>[^:]+):(?P<line>\d+):(?P<column>\d+): (?P<level>error|warning): (?P<message>.+)$";
+    }
+    ansi: "strip";
+  }
+  triggers {
+    git {
+    }
+  }
+  steps {
+    step "build" {
+      run: "cargo build";
+    }
+    step "web" {
+      extends: "build";
+      image: "node:20";
+      run: "npm test";
+    }
+    step "web-lint" {
+      extends: "web";
+      run: "npm run lint";
+    }
+  }
+}
+"#;
+        let (pipeline, warnings) = parse_pulsefile_with_warnings(input).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(pipeline.options.shell.as_deref(), Some("bash"));
+        assert_eq!(pipeline.options.windows_shell.as_deref(), Some("pwsh"));
+        assert!(!pipeline.options.fail_fast);
+        assert!(pipeline.options.set_e);
+        assert_eq!(pipeline.options.image.as_deref(), Some("rust:1.80"));
+        assert_eq!(pipeline.options.kill_grace_secs, 60);
+        let before_each = pipeline.options.before_each.as_deref().unwrap();
+        assert!(before_each.contains(". ./ci/env.sh\n"));
+        assert_eq!(pipeline.options.after_each.as_deref(), Some("./ci/report-timing.sh"));
+        assert!(pipeline.options.problem_matchers["gcc"].starts_with("^(?P<file>"));
+        assert_eq!(pipeline.options.ansi, AnsiMode::Strip);
+        let images: Vec<_> = pipeline.steps.iter().map(|s| s.image.as_deref()).collect();
+        assert_eq!(images, vec![None, Some("node:20"), Some("node:20")]);
+
+        let defaults = parse_pulsefile("pipeline { steps { } }").unwrap().options;
+        assert_eq!(defaults, PipelineOptions::default());
+        assert!(defaults.fail_fast);
+        assert!(parse_pulsefile(r#"pipeline { options { kill_grace: "soon"; } steps { } }"#).is_err());
+        let no_message = r#"pipeline { options { problem_matchers { lint: "^(?P<file>.+)$"; } } steps { } }"#;
+        assert!(parse_pulsefile(no_message).unwrap_err().to_string().contains("Problem matcher \"lint\""));
+        assert!(parse_pulsefile(r#"pipeline { options { problem_matchers { bad: "(?P<message>"; } } steps { } }"#).is_err());
+        let ansi = parse_pulsefile(r#"pipeline { options { ansi: "colour"; } steps { } }"#).unwrap_err();
+        assert!(ansi.to_string().contains("Invalid ansi \"colour\""));
+    }
+
+    #[test]
+    fn test_parse_step_runner() {
+        let input = r#"
+pipeline {
+  steps {
+    step "deploy" {
+      runner: "ssh://deploy@prod-box";
+      run: "systemctl restart app";
+    }
+    step "smoke" {
+      extends: "deploy";
+      run: "curl -f localhost/health";
+    }
+    step "report" {
+      run: "echo done";
+    }
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        let runners: Vec<_> = pipeline.steps.iter().map(|s| s.runner.as_deref()).collect();
+        assert_eq!(runners, vec![Some("ssh://deploy@prod-box"), Some("ssh://deploy@prod-box"), None]);
+
+        let invalid = r#"pipeline { steps { step "deploy" { runner: "prod-box"; run: "true"; } } }"#;
+        let err = parse_pulsefile(invalid).unwrap_err().to_string();
+        assert!(err.contains("Step \"deploy\"") && err.contains("ssh://"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_uses_step() {
+        let input = r#"
+pipeline {
+  steps {
+    step "checkout" {
+      uses: "pulsiora/checkout@v1";
+      with {
+        depth: "1";
+        path: "src";
+      }
+    }
+  }
+}
+"#;
+        let (pipeline, warnings) = parse_pulsefile_with_warnings(input).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let step = &pipeline.steps[0];
+        assert_eq!(step.uses.as_deref(), Some("pulsiora/checkout@v1"));
+        assert_eq!(step.with.get("path").map(String::as_str), Some("src"));
+        assert!(step.run.is_empty());
+
+        for (step, expected) in [
+            (r#"uses: "pulsiora/checkout";"#, "pin a version"),
+            (r#"uses: "pulsiora/checkout@v1"; run: "true";"#, "both `uses` and `run`"),
+            (r#"with { depth: "1"; } run: "true";"#, "has `with` but no `uses`"),
+        ] {
+            let input = format!(r#"pipeline {{ steps {{ step "checkout" {{ {} }} }} }}"#, step);
+            let err = parse_pulsefile(&input).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_parse_background_step() {
+        let input = r#"
+pipeline {
+  steps {
+    step "api" {
+      background: true;
+      ready_when: {
+        http: "http://localhost:8080/health";
+        timeout: "2m";
+      }
+      run: """./server""";
+    }
+    step "db" {
+      background: true;
+      run: """postgres""";
+    }
+  }
+}
+"#;
+        let (pipeline, warnings) = parse_pulsefile_with_warnings(input).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let api = &pipeline.steps[0];
+        assert!(api.background);
+        assert_eq!(
+            api.ready_when,
+            Some(ReadinessCheck {
+                http: Some("http://localhost:8080/health".to_string()),
+                timeout_secs: 120,
+            })
+        );
+        assert!(pipeline.steps[1].background);
+        assert_eq!(pipeline.steps[1].ready_when, None);
+
+        let not_background = r#"
+pipeline {
+  steps {
+    step "api" {
+      ready_when { http: "http://localhost:8080"; }
+      run: """./server""";
+    }
+  }
+}
+"#;
+        let err = parse_pulsefile(not_background).unwrap_err().to_string();
+        assert!(err.contains("not a background step"));
+
+        let background_tty = r#"
+pipeline {
+  steps {
+    step "api" {
+      background: true;
+      tty: true;
+      run: """./server""";
+    }
+  }
+}
+"#;
+        let err = parse_pulsefile(background_tty).unwrap_err().to_string();
+        assert!(err.contains("has `tty` but is a background step"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_step_reports() {
+        let input = r#"
+pipeline {
+  steps {
+    step "test" {
+      reports { coverage: "coverage/lcov.info"; min_coverage: 80; }
+      run: """cargo llvm-cov --lcov --output-path coverage/lcov.info""";
+    }
+    step "test-ci" {
+      extends: "test";
+    }
+  }
+}
+"#;
+        let (pipeline, warnings) = parse_pulsefile_with_warnings(input).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let expected = StepReports {
+            coverage: Some("coverage/lcov.info".to_string()),
+            min_coverage: Some(80.0),
+        };
+        assert_eq!(pipeline.steps[0].reports, expected);
+        assert_eq!(pipeline.steps[1].reports, expected);
+
+        let invalid = [
+            (r#"reports { coverage: "../lcov.info"; }"#, "Invalid coverage report path"),
+            (r#"reports { coverage: "lcov.info"; min_coverage: 120; }"#, "Invalid min_coverage 120"),
+            ("reports { min_coverage: 80; }", "no `coverage` report"),
+        ];
+        for (reports, message) in invalid {
+            let input = format!(r#"pipeline {{ steps {{ step "test" {{ {} run: "make"; }} }} }}"#, reports);
+            let err = parse_pulsefile(&input).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_parse_duration_secs() {
+        assert_eq!(parse_duration_secs("90"), Some(90));
+        assert_eq!(parse_duration_secs("60s"), Some(60));
+        assert_eq!(parse_duration_secs("2m"), Some(120));
+        assert_eq!(parse_duration_secs("1h"), Some(3600));
+        assert_eq!(parse_duration_secs("1d"), None);
+        assert_eq!(parse_duration_secs("s"), None);
+    }
+
+    #[test]
+    fn test_parse_step_artifacts() {
+        let input = r#"
+pipeline {
+  steps {
+    step "test" {
+      artifacts: ["coverage/", "./reports/junit.xml"];
+      run: """cargo test""";
+    }
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        assert_eq!(pipeline.steps[0].artifacts, vec!["coverage/", "./reports/junit.xml"]);
+
+        for path in ["/etc/passwd", "../secrets", "out/../../x", ""] {
+            let input = format!(
+                "pipeline {{ steps {{ step \"a\" {{ artifacts: [\"{}\"]; run: \"true\"; }} }} }}",
+                path
+            );
+            let err = parse_pulsefile(&input).unwrap_err().to_string();
+            assert!(err.contains("Invalid artifact path"), "{}: {}", path, err);
+        }
+    }
+
+    #[test]
+    fn test_parse_artifact_vars() {
+        let input = r#"
+pipeline {
+  vars {
+    OUT: "target/reports";
+    UP: "..";
+  }
+  steps {
+    step "test" {
+      artifacts: ["${vars.OUT}/junit.xml"];
+      run: """cargo test""";
+    }
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        assert_eq!(pipeline.steps[0].artifacts, vec!["target/reports/junit.xml"]);
+
+        let input = input.replace("${vars.OUT}", "${vars.UP}");
+        let err = parse_pulsefile(&input).unwrap_err().to_string();
+        assert!(err.contains("Invalid artifact path \"../junit.xml\""), "{}", err);
+    }
 }
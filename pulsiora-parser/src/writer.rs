@@ -0,0 +1,345 @@
+use pulsiora_core::{AnsiMode, GitTriggers, Pipeline, PipelineOptions, Step, Trigger, DEFAULT_KILL_GRACE_SECS};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Render a pipeline back to Pulsefile syntax.
+///
+/// The grammar has no escape sequences, so string values must not contain
+/// double quotes and scripts must not contain `"""`.
+pub fn render_pulsefile(pipeline: &Pipeline) -> String {
+    let mut out = String::new();
+    out.push_str("pipeline {\n");
+    let _ = writeln!(out, "  name: {};", quote(&pipeline.name));
+    let _ = writeln!(out, "  version: {};", quote(&pipeline.version));
+    if let Some(description) = &pipeline.description {
+        if description.contains('"') || description.contains('\n') {
+            let _ = writeln!(out, "  description: \"\"\"{}\"\"\";", description);
+        } else {
+            let _ = writeln!(out, "  description: {};", quote(description));
+        }
+    }
+    if !pipeline.labels.is_empty() {
+        let _ = writeln!(out, "  labels: {};", list(&pipeline.labels));
+    }
+    if !pipeline.owners.is_empty() {
+        let _ = writeln!(out, "  owners: {};", list(&pipeline.owners));
+    }
+    if let Some(environment) = &pipeline.environment {
+        let _ = writeln!(out, "  environment: {};", quote(environment));
+    }
+
+    if pipeline.options != PipelineOptions::default() {
+        out.push_str("\n  options {\n");
+        if let Some(shell) = &pipeline.options.shell {
+            let _ = writeln!(out, "    shell: {};", quote(shell));
+        }
+        if let Some(shell) = &pipeline.options.windows_shell {
+            let _ = writeln!(out, "    windows_shell: {};", quote(shell));
+        }
+        let _ = writeln!(out, "    fail_fast: {};", pipeline.options.fail_fast);
+        let _ = writeln!(out, "    set_e: {};", pipeline.options.set_e);
+        if let Some(image) = &pipeline.options.image {
+            let _ = writeln!(out, "    image: {};", quote(image));
+        }
+        if pipeline.options.kill_grace_secs != DEFAULT_KILL_GRACE_SECS {
+            let _ = writeln!(out, "    kill_grace: \"{}s\";", pipeline.options.kill_grace_secs);
+        }
+        if let Some(script) = &pipeline.options.before_each {
+            render_script(&mut out, "before_each", script, 4);
+        }
+        if let Some(script) = &pipeline.options.after_each {
+            render_script(&mut out, "after_each", script, 4);
+        }
+        if !pipeline.options.problem_matchers.is_empty() {
+            render_entries(&mut out, "problem_matchers", &pipeline.options.problem_matchers, 4);
+        }
+        if pipeline.options.ansi != AnsiMode::default() {
+            let _ = writeln!(out, "    ansi: \"{}\";", pipeline.options.ansi.as_str());
+        }
+        out.push_str("  }\n");
+    }
+
+    if !pipeline.vars.is_empty() {
+        out.push('\n');
+        render_entries(&mut out, "vars", &pipeline.vars, 2);
+    }
+
+    out.push_str("\n  triggers {\n");
+    for trigger in &pipeline.triggers.sources {
+        render_trigger(&mut out, trigger);
+    }
+    out.push_str("  }\n");
+
+    out.push_str("\n  steps {\n");
+    for (idx, step) in pipeline.steps.iter().enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        render_step(&mut out, step);
+    }
+    out.push_str("  }\n");
+    out.push_str("}\n");
+    out
+}
+
+fn render_trigger(out: &mut String, trigger: &Trigger) {
+    match trigger {
+        Trigger::Git(git) => render_git(out, git),
+        Trigger::Schedule(schedule) => {
+            out.push_str("    schedule {\n");
+            let _ = writeln!(out, "      cron: {};", quote(&schedule.cron));
+            if let Some(branch) = &schedule.branch {
+                let _ = writeln!(out, "      branch: {};", quote(branch));
+            }
+            out.push_str("    }\n");
+        }
+        Trigger::Manual(_) => out.push_str("    manual { }\n"),
+        Trigger::Image(image) => {
+            out.push_str("    image {\n");
+            let _ = writeln!(out, "      name: {};", quote(&image.image));
+            if !image.tags.is_empty() {
+                let _ = writeln!(out, "      tags: {};", list(&image.tags));
+            }
+            out.push_str("    }\n");
+        }
+        Trigger::Pipeline(upstream) => {
+            out.push_str("    pipeline {\n");
+            let _ = writeln!(out, "      name: {};", quote(&upstream.pipeline));
+            if !upstream.statuses.is_empty() {
+                let statuses: Vec<String> = upstream.statuses.iter().map(|s| format!("{:?}", s)).collect();
+                let _ = writeln!(out, "      statuses: {};", list(&statuses));
+            }
+            out.push_str("    }\n");
+        }
+        Trigger::Webhook(webhook) => {
+            out.push_str("    webhook {\n");
+            let _ = writeln!(out, "      name: {};", quote(&webhook.name));
+            out.push_str("    }\n");
+        }
+        _ => {}
+    }
+}
+
+fn render_git(out: &mut String, git: &GitTriggers) {
+    out.push_str("    git {\n");
+    for (field, value) in [
+        ("on_push", git.on_push),
+        ("on_pull_request", git.on_pull_request),
+        ("on_merge", git.on_merge),
+        ("on_tag", git.on_tag),
+        ("on_release", git.on_release),
+        ("on_branch_create", git.on_branch_create),
+        ("on_branch_delete", git.on_branch_delete),
+    ] {
+        let _ = writeln!(out, "      {}: {};", field, value);
+    }
+    let _ = writeln!(out, "      branches: {};", list(&git.branches));
+    if !git.ignore_senders.is_empty() {
+        let _ = writeln!(out, "      ignore_senders: {};", list(&git.ignore_senders));
+    }
+    if !git.pr_labels.is_empty() {
+        let _ = writeln!(out, "      pr_labels: {};", list(&git.pr_labels));
+    }
+    if git.ignore_drafts {
+        out.push_str("      ignore_drafts: true;\n");
+    }
+    out.push_str("    }\n");
+}
+
+fn render_step(out: &mut String, step: &Step) {
+    let _ = writeln!(out, "    step {} {{", quote(&step.name));
+    if step.allow_failure {
+        out.push_str("      allow_failure: true;\n");
+    }
+    if let Some(user) = &step.user {
+        let _ = writeln!(out, "      user: {};", quote(user));
+    }
+    if let Some(group) = &step.group {
+        let _ = writeln!(out, "      group: {};", quote(group));
+    }
+    if let Some(umask) = &step.umask {
+        let _ = writeln!(out, "      umask: {};", quote(umask));
+    }
+    if let Some(timeout_secs) = step.timeout_secs {
+        let _ = writeln!(out, "      timeout: \"{}s\";", timeout_secs);
+    }
+    if let Some(encoding) = &step.encoding {
+        let _ = writeln!(out, "      encoding: {};", quote(encoding));
+    }
+    if let Some(image) = &step.image {
+        let _ = writeln!(out, "      image: {};", quote(image));
+    }
+    if let Some(runner) = &step.runner {
+        let _ = writeln!(out, "      runner: {};", quote(runner));
+    }
+    if let Some(uses) = &step.uses {
+        let _ = writeln!(out, "      uses: {};", quote(uses));
+    }
+    if !step.with.is_empty() {
+        render_entries(out, "with", &step.with, 6);
+    }
+    if !step.env.is_empty() {
+        render_entries(out, "env", &step.env, 6);
+    }
+    if step.background {
+        out.push_str("      background: true;\n");
+    }
+    if step.tty {
+        out.push_str("      tty: true;\n");
+    }
+    if !step.artifacts.is_empty() {
+        let _ = writeln!(out, "      artifacts: {};", list(&step.artifacts));
+    }
+    if let Some(ready_when) = &step.ready_when {
+        out.push_str("      ready_when {\n");
+        if let Some(http) = &ready_when.http {
+            let _ = writeln!(out, "        http: {};", quote(http));
+        }
+        let _ = writeln!(out, "        timeout: \"{}s\";", ready_when.timeout_secs);
+        out.push_str("      }\n");
+    }
+    if let Some(coverage) = &step.reports.coverage {
+        out.push_str("      reports {\n");
+        let _ = writeln!(out, "        coverage: {};", quote(coverage));
+        if let Some(min) = step.reports.min_coverage {
+            let _ = writeln!(out, "        min_coverage: {};", min);
+        }
+        out.push_str("      }\n");
+    }
+
+    // Action steps have no script of their own
+    if step.uses.is_none() {
+        render_script(out, "run", &step.run, 6);
+    }
+    out.push_str("    }\n");
+}
+
+/// Write a script field. Scripts are written verbatim; indenting them would
+/// change the script.
+fn render_script(out: &mut String, key: &str, script: &str, indent: usize) {
+    let pad = " ".repeat(indent);
+    if script.contains('\n') {
+        let _ = writeln!(out, "{}{}: \"\"\"\n{}\n{}\"\"\";", pad, key, script, pad);
+    } else {
+        let _ = writeln!(out, "{}{}: \"\"\"{}\"\"\";", pad, key, script);
+    }
+}
+
+fn render_entries(out: &mut String, block: &str, entries: &BTreeMap<String, String>, indent: usize) {
+    let pad = " ".repeat(indent);
+    let _ = writeln!(out, "{}{} {{", pad, block);
+    for (key, value) in entries {
+        let _ = writeln!(out, "{}  {}: {};", pad, key, quote(value));
+    }
+    let _ = writeln!(out, "{}}}", pad);
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value)
+}
+
+fn list(values: &[String]) -> String {
+    let quoted: Vec<String> = values.iter().map(|v| quote(v)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_pulsefile;
+
+    #[test]
+    fn test_render_round_trips() {
+        let input = r#"
+pipeline {
+  name: "shop";
+  version: "2.1";
+  description: "Builds the shop image";
+  labels: ["deploy", "docker"];
+  owners: ["@acme/platform"];
+  environment: "staging";
+  options {
+    shell: "bash";
+    windows_shell: "powershell";
+    set_e: true;
+    image: "rust:1.80";
+    kill_grace: "30s";
+    before_each: """. ./ci/env.sh""";
+    problem_matchers {
+      eslint: "^(?P<file>.+): line (?P<line>\d+), (?P<message>.+)$";
+    }
+    ansi: "strip";
+  }
+  vars {
+    REGISTRY: "ghcr.io/acme";
+  }
+  triggers {
+    git {
+      on_push: true;
+      on_tag: true;
+      branches: ["main", "release/*"];
+      ignore_senders: ["dependabot[bot]"];
+    }
+    schedule {
+      cron: "0 3 * * *";
+      branch: "main";
+    }
+    manual { }
+    pipeline {
+      name: "build";
+      statuses: ["success", "failed"];
+    }
+  }
+  steps {
+    step "build" {
+      env {
+        IMAGE: "${vars.REGISTRY}/shop";
+      }
+      run: """
+        echo building
+        docker build -t $IMAGE .
+      """;
+    }
+    step "lint" {
+      allow_failure: true;
+      user: "builder";
+      umask: "0022";
+      timeout: "15m";
+      encoding: "shift_jis";
+      tty: true;
+      image: "rust:1.80-slim";
+      runner: "ssh://ci@build-box:2222";
+      artifacts: ["target/clippy.json", "reports/"];
+      reports { coverage: "coverage/lcov.info"; min_coverage: 82.5; }
+      run: """cargo clippy""";
+    }
+    step "api" {
+      background: true;
+      ready_when: { http: "http://localhost:8080/health"; timeout: "30s"; }
+      run: """./server""";
+    }
+    step "checkout" {
+      uses: "pulsiora/checkout@v1";
+      with {
+        depth: "1";
+      }
+    }
+  }
+}
+"#;
+        let pipeline = parse_pulsefile(input).unwrap();
+        let rendered = render_pulsefile(&pipeline);
+        let reparsed = parse_pulsefile(&rendered).unwrap();
+
+        assert_eq!(reparsed.name, pipeline.name);
+        assert_eq!(reparsed.version, pipeline.version);
+        assert_eq!(reparsed.description, pipeline.description);
+        assert_eq!(reparsed.labels, pipeline.labels);
+        assert_eq!(reparsed.owners, pipeline.owners);
+        assert_eq!(reparsed.environment.as_deref(), Some("staging"));
+        assert_eq!(reparsed.vars, pipeline.vars);
+        assert_eq!(reparsed.options, pipeline.options);
+        assert_eq!(reparsed.triggers, pipeline.triggers);
+        assert_eq!(reparsed.steps, pipeline.steps);
+    }
+}
@@ -0,0 +1,462 @@
+// Shared actions: steps with `uses: "owner/name@v1"` run a bundle published
+// in a git repository or as a `.tar.gz` pinned to its SHA-256. Bundles are
+// downloaded once into a cache on the runner and copied into the workspace,
+// where the entrypoint named by their `pulse-action.json` runs like any
+// other step script.
+
+use crate::artifacts::copy_tree;
+use crate::workspace::run_git;
+use pulsiora_core::{ActionRef, PulsioraError, Result, Step};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Manifest at the root of every action bundle
+pub const ACTION_MANIFEST: &str = "pulse-action.json";
+
+/// Where bundles are copied to, relative to the step's working directory
+pub const ACTION_DIR: &str = ".pulse/actions";
+
+/// A bundle fetched through the cache
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedAction {
+    /// Directory holding the bundle
+    pub dir: PathBuf,
+    /// Taken from the cache without downloading anything
+    pub cached: bool,
+}
+
+/// What an action runs and the inputs it takes, from `pulse-action.json`
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ActionManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Executable in the bundle the step runs, e.g. `run.sh`
+    pub entrypoint: String,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, ActionInput>,
+}
+
+/// An input of an action, set from the step's `with` block
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ActionInput {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Value when the step doesn't set one; inputs without a default are
+    /// required
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+impl ActionManifest {
+    /// Read the manifest of the bundle in `dir`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(ACTION_MANIFEST);
+        let content = fs::read_to_string(&path)
+            .map_err(|e| PulsioraError::ExecutionError(format!("Failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&content)
+            .map_err(|e| PulsioraError::ExecutionError(format!("Invalid {}: {}", ACTION_MANIFEST, e)))
+    }
+
+    /// `INPUT_<NAME>` variables for the step's `with` block, with defaults
+    /// for inputs it leaves out
+    pub fn input_env(&self, with: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>> {
+        if let Some(unknown) = with.keys().find(|name| !self.inputs.contains_key(*name)) {
+            return Err(PulsioraError::ExecutionError(format!(
+                "Action \"{}\" has no input \"{}\"",
+                self.name, unknown
+            )));
+        }
+        let mut env = BTreeMap::new();
+        for (name, input) in &self.inputs {
+            let value = with.get(name).or(input.default.as_ref()).ok_or_else(|| {
+                PulsioraError::ExecutionError(format!("Action \"{}\" requires input \"{}\"", self.name, name))
+            })?;
+            env.insert(format!("INPUT_{}", name.to_uppercase().replace('-', "_")), value.clone());
+        }
+        Ok(env)
+    }
+}
+
+/// Downloaded action bundles, kept between executions. Clones share a lock
+/// so that two executions don't fetch into the same directory at once.
+#[derive(Debug, Clone)]
+pub struct ActionCache {
+    dir: PathBuf,
+    host: String,
+    lock: Arc<Mutex<()>>,
+}
+
+impl ActionCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            host: "https://github.com".to_string(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Resolve `owner/name` actions against this git host instead of GitHub
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Clone URL of a git action's repository
+    pub fn git_url(&self, repository: &str) -> String {
+        if repository.contains("://") {
+            repository.to_string()
+        } else {
+            format!("{}/{}.git", self.host, repository)
+        }
+    }
+
+    /// The bundle, downloading it unless it's cached. Tags and branches are
+    /// fetched again each time since they can move; if that fails, the copy
+    /// from the last fetch is used.
+    pub async fn fetch(&self, action: &ActionRef) -> Result<FetchedAction> {
+        let _guard = self.lock.lock().await;
+        match action {
+            ActionRef::Git { repository, rev } => {
+                let url = self.git_url(repository);
+                let dir = self.dir.join(cache_name(&format!("{}@{}", url, rev)));
+                let rev = rev.clone();
+                tokio::task::spawn_blocking(move || fetch_git(&url, &rev, &dir))
+                    .await
+                    .map_err(|e| PulsioraError::ExecutionError(format!("Action fetch task failed: {}", e)))?
+            }
+            ActionRef::Archive { url, sha256 } => self.fetch_archive(url, sha256).await,
+        }
+    }
+
+    /// Published bundles are downloaded once per URL and digest. The archive
+    /// must match the digest before it's unpacked, and may only hold files
+    /// and directories.
+    async fn fetch_archive(&self, url: &str, sha256: &str) -> Result<FetchedAction> {
+        let dir = self.dir.join(cache_name(&format!("{}#sha256={}", url, sha256)));
+        if dir.is_dir() {
+            return Ok(FetchedAction {
+                dir: bundle_root(&dir)?,
+                cached: true,
+            });
+        }
+        let failed = |e: &dyn std::fmt::Display| {
+            PulsioraError::ExecutionError(format!("Failed to download action {}: {}", url, e))
+        };
+        let bytes = match url.strip_prefix("file://") {
+            Some(path) => tokio::fs::read(path).await.map_err(|e| failed(&e))?,
+            None => reqwest::get(url)
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| failed(&e))?
+                .bytes()
+                .await
+                .map_err(|e| failed(&e))?
+                .to_vec(),
+        };
+        let digest = hex::encode(Sha256::digest(&bytes));
+        if digest != sha256 {
+            return Err(PulsioraError::ExecutionError(format!(
+                "Action {} doesn't match its digest: expected sha256 {}, got {}",
+                url, sha256, digest
+            )));
+        }
+
+        tokio::task::spawn_blocking(move || {
+            // Unpacked next to the cache entry and moved into place, so an
+            // interrupted download never looks cached
+            let staging = dir.with_file_name(format!(".{}", Uuid::new_v4()));
+            fs::create_dir_all(&staging)?;
+            let archive = staging.join("bundle.tar.gz");
+            fs::write(&archive, bytes)?;
+            let unpacked = Command::new("tar")
+                .arg("--no-same-owner")
+                .arg("-xzf")
+                .arg(&archive)
+                .arg("-C")
+                .arg(&staging)
+                .output()?;
+            fs::remove_file(&archive)?;
+            if !unpacked.status.success() {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(PulsioraError::ExecutionError(format!(
+                    "Failed to unpack action: {}",
+                    String::from_utf8_lossy(&unpacked.stderr).trim()
+                )));
+            }
+            if let Err(e) = check_bundle_tree(&staging, &staging) {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+            fs::rename(&staging, &dir)?;
+            Ok(FetchedAction {
+                dir: bundle_root(&dir)?,
+                cached: false,
+            })
+        })
+        .await
+        .map_err(|e| PulsioraError::ExecutionError(format!("Action fetch task failed: {}", e)))?
+    }
+}
+
+/// Fetch `rev` of the repository at `url` into `dir`. A commit that is
+/// already there can't have changed and isn't fetched again.
+fn fetch_git(url: &str, rev: &str, dir: &Path) -> Result<FetchedAction> {
+    let cached = dir.join(ACTION_MANIFEST).is_file();
+    let fetched = |cached| FetchedAction {
+        dir: dir.to_path_buf(),
+        cached,
+    };
+    if cached && rev.len() == 40 && rev.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(fetched(true));
+    }
+    let git = |args: &[&str]| {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(dir).args(args).env("GIT_TERMINAL_PROMPT", "0");
+        run_git(&mut command)
+    };
+    let updated = (|| {
+        if !dir.join(".git").is_dir() {
+            fs::create_dir_all(dir)?;
+            git(&["init", "--quiet"])?;
+        }
+        git(&["fetch", "--quiet", "--depth=1", url, rev])?;
+        git(&["checkout", "--quiet", "--force", "FETCH_HEAD"])
+    })();
+    match updated {
+        Ok(()) => Ok(fetched(false)),
+        Err(e) if cached => {
+            warn!(url = %url, rev = %rev, error = %e, "Failed to update action, using cached copy");
+            Ok(fetched(true))
+        }
+        Err(e) => {
+            let _ = fs::remove_dir_all(dir);
+            Err(PulsioraError::ExecutionError(format!("Failed to fetch action {}@{}: {}", url, rev, e)))
+        }
+    }
+}
+
+/// Fail if the unpacked bundle under `root` holds anything but files and
+/// directories, such as a symlink that could point outside the cache
+fn check_bundle_tree(root: &Path, path: &Path) -> Result<()> {
+    let file_type = fs::symlink_metadata(path)?.file_type();
+    if file_type.is_dir() {
+        for entry in fs::read_dir(path)? {
+            check_bundle_tree(root, &entry?.path())?;
+        }
+        return Ok(());
+    }
+    if !file_type.is_file() {
+        return Err(PulsioraError::ExecutionError(format!(
+            "Action bundle may only hold files and directories, but has {}",
+            path.strip_prefix(root).unwrap_or(path).display()
+        )));
+    }
+    Ok(())
+}
+
+/// Directory of an unpacked bundle with the manifest: its root, or the one
+/// directory the archive holds, as in tarballs of a git tag
+fn bundle_root(dir: &Path) -> Result<PathBuf> {
+    if dir.join(ACTION_MANIFEST).is_file() {
+        return Ok(dir.to_path_buf());
+    }
+    let entries: Vec<_> = fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+    match entries.as_slice() {
+        [entry] if entry.path().join(ACTION_MANIFEST).is_file() => Ok(entry.path()),
+        _ => Err(PulsioraError::ExecutionError(format!(
+            "Action bundle has no {}",
+            ACTION_MANIFEST
+        ))),
+    }
+}
+
+/// File name for a cache entry: the URL with anything but letters, digits,
+/// `.`, `-` and `_` replaced
+fn cache_name(key: &str) -> String {
+    let trimmed = key.split_once("://").map_or(key, |(_, rest)| rest);
+    trimmed
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect()
+}
+
+/// Copy the bundle in `bundle` into `<work_dir>/.pulse/actions/` and turn
+/// the step into one that runs its entrypoint with the step's inputs.
+/// `PULSE_ACTION_PATH` is the bundle's directory, relative to `work_dir`.
+pub fn prepare_action(step: &Step, bundle: &Path, work_dir: &Path) -> Result<Step> {
+    let manifest = ActionManifest::load(bundle)?;
+    let entrypoint = Path::new(&manifest.entrypoint);
+    // Run as a plain relative path, so it must be one the shell won't reinterpret
+    let plain = !manifest.entrypoint.is_empty()
+        && entrypoint.components().all(|c| matches!(c, Component::Normal(_)))
+        && manifest
+            .entrypoint
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'));
+    if !plain {
+        return Err(PulsioraError::ExecutionError(format!(
+            "Action \"{}\" has an invalid entrypoint \"{}\": expected a relative path inside the bundle",
+            manifest.name, manifest.entrypoint
+        )));
+    }
+    let inputs = manifest.input_env(&step.with)?;
+
+    let name = bundle
+        .file_name()
+        .map(|name| cache_name(&name.to_string_lossy()))
+        .unwrap_or_else(|| cache_name(&manifest.name));
+    let action_path = format!("{}/{}", ACTION_DIR, name);
+    let dest = work_dir.join(&action_path);
+    if dest.exists() {
+        fs::remove_dir_all(&dest)?;
+    }
+    fs::create_dir_all(&dest)?;
+    for entry in fs::read_dir(bundle)? {
+        let entry = entry?;
+        if entry.file_name() != ".git" {
+            copy_tree(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    }
+
+    let mut action_step = step.clone();
+    action_step.run = format!("./{}/{}", action_path, manifest.entrypoint);
+    action_step.env.extend(inputs);
+    action_step.env.insert("PULSE_ACTION_PATH".to_string(), action_path);
+    Ok(action_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let mut command = Command::new("git");
+        command
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", "test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com");
+        run_git(&mut command).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_prepare_action() {
+        let root = std::env::temp_dir().join(format!("pulsiora-actions-{}", Uuid::new_v4()));
+        let repo = root.join("host/acme/greet.git");
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(
+            repo.join(ACTION_MANIFEST),
+            r#"{"name": "greet", "entrypoint": "bin/greet.sh",
+                "inputs": {"who": {}, "greeting": {"default": "hello"}}}"#,
+        )
+        .unwrap();
+        fs::create_dir_all(repo.join("bin")).unwrap();
+        fs::write(repo.join("bin/greet.sh"), "echo \"$INPUT_GREETING $INPUT_WHO\"\n").unwrap();
+        git(&repo, &["init", "--quiet"]);
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "--quiet", "-m", "greet"]);
+        git(&repo, &["tag", "v1"]);
+
+        let cache = ActionCache::new(root.join("cache")).with_host(format!("file://{}/", root.join("host").display()));
+        let action = ActionRef::parse("acme/greet@v1").unwrap();
+        let fetched = cache.fetch(&action).await.unwrap();
+        assert!(!fetched.cached);
+        let bundle = fetched.dir;
+        assert!(bundle.starts_with(cache.dir()));
+        assert!(bundle.join("bin/greet.sh").is_file());
+
+        // The repository is gone, so this is the cached copy
+        fs::remove_dir_all(root.join("host")).unwrap();
+        let fetched = cache.fetch(&action).await.unwrap();
+        assert!(fetched.cached);
+        assert_eq!(fetched.dir, bundle);
+
+        let mut step = Step::new("greet".to_string(), String::new());
+        step.uses = Some("acme/greet@v1".to_string());
+        step.with.insert("who".to_string(), "world".to_string());
+        let work_dir = root.join("work");
+        let prepared = prepare_action(&step, &bundle, &work_dir).unwrap();
+        assert!(prepared.run.starts_with("./.pulse/actions/") && prepared.run.ends_with("/bin/greet.sh"));
+        assert!(work_dir.join(&prepared.run).is_file());
+        assert!(!work_dir.join(&prepared.env["PULSE_ACTION_PATH"]).join(".git").exists());
+        assert_eq!(prepared.env["INPUT_WHO"], "world");
+        assert_eq!(prepared.env["INPUT_GREETING"], "hello");
+
+        step.with.clear();
+        let err = prepare_action(&step, &bundle, &work_dir).unwrap_err().to_string();
+        assert!(err.contains("requires input \"who\""), "{}", err);
+        step.with.insert("whom".to_string(), "world".to_string());
+        let err = prepare_action(&step, &bundle, &work_dir).unwrap_err().to_string();
+        assert!(err.contains("has no input \"whom\""), "{}", err);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fetch_archive_checks_digest_and_tree() {
+        let root = std::env::temp_dir().join(format!("pulsiora-actions-{}", Uuid::new_v4()));
+        let bundle = root.join("lint");
+        fs::create_dir_all(&bundle).unwrap();
+        fs::write(bundle.join(ACTION_MANIFEST), r#"{"name": "lint", "entrypoint": "run.sh"}"#).unwrap();
+        fs::write(bundle.join("run.sh"), "echo lint\n").unwrap();
+        let pack = |name: &str| {
+            let archive = root.join(name);
+            let status = Command::new("tar")
+                .arg("-czf")
+                .arg(&archive)
+                .arg("-C")
+                .arg(&root)
+                .arg("lint")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let digest = hex::encode(Sha256::digest(fs::read(&archive).unwrap()));
+            (format!("file://{}", archive.display()), digest)
+        };
+        let cache = ActionCache::new(root.join("cache"));
+        let cached_entries = || fs::read_dir(cache.dir()).map_or(0, |entries| entries.count());
+
+        let (url, digest) = pack("lint.tar.gz");
+        let wrong = ActionRef::Archive {
+            url: url.clone(),
+            sha256: "0".repeat(64),
+        };
+        let err = cache.fetch(&wrong).await.unwrap_err().to_string();
+        assert!(err.contains("doesn't match its digest"), "{}", err);
+        assert_eq!(cached_entries(), 0);
+
+        let action = ActionRef::Archive { url, sha256: digest };
+        let fetched = cache.fetch(&action).await.unwrap();
+        assert!(!fetched.cached);
+        assert!(fetched.dir.join("run.sh").is_file());
+        assert!(cache.fetch(&action).await.unwrap().cached);
+
+        std::os::unix::fs::symlink("/etc/passwd", bundle.join("leak")).unwrap();
+        let (url, digest) = pack("leaky.tar.gz");
+        let err = cache
+            .fetch(&ActionRef::Archive { url, sha256: digest })
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("only hold files and directories"), "{}", err);
+        assert_eq!(cached_entries(), 1);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
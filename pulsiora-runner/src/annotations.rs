@@ -0,0 +1,196 @@
+// Annotations: problems a step reports in its output. Steps can print
+// workflow commands such as `::error file=src/app.rs,line=3::message`, and a
+// Pulsefile's `problem_matchers` turn tool output, like compiler errors, into
+// annotations with one regex per line.
+
+use crate::ansi::strip_ansi;
+use encoding_rs::Encoding;
+use pulsiora_core::{Annotation, AnnotationLevel, PipelineOptions};
+use pulsiora_parser::compile_problem_matcher;
+use regex::Regex;
+use std::io;
+use std::path::Path;
+use tokio::io::AsyncBufReadExt;
+use tracing::warn;
+
+/// Annotations kept per step, so a flood of warnings doesn't bloat the result
+pub const MAX_ANNOTATIONS: usize = 100;
+
+/// A pipeline's compiled `problem_matchers`
+#[derive(Debug, Clone, Default)]
+pub struct ProblemMatchers {
+    matchers: Vec<Regex>,
+}
+
+impl ProblemMatchers {
+    /// Compile a pipeline's matchers. The parser rejects invalid ones, but
+    /// pipelines can also be built in code; those are skipped.
+    pub fn new(options: &PipelineOptions) -> Self {
+        let matchers = options
+            .problem_matchers
+            .iter()
+            .filter_map(|(name, pattern)| match compile_problem_matcher(pattern) {
+                Ok(regex) => Some(regex),
+                Err(reason) => {
+                    warn!(matcher = %name, reason = %reason, "Skipping invalid problem matcher");
+                    None
+                }
+            })
+            .collect();
+        Self { matchers }
+    }
+
+    /// Add the annotations found in each line of `output`, up to `MAX_ANNOTATIONS`
+    pub fn annotate(&self, output: &str, annotations: &mut Vec<Annotation>) {
+        for line in output.lines() {
+            if annotations.len() >= MAX_ANNOTATIONS {
+                return;
+            }
+            // Colored output would keep matchers from seeing the text
+            let line = strip_ansi(line.trim_end_matches('\r'));
+            if let Some(annotation) = parse_workflow_command(&line).or_else(|| self.match_line(&line)) {
+                annotations.push(annotation);
+            }
+        }
+    }
+
+    /// Like `annotate`, reading a log file line by line. Logs in encodings
+    /// that aren't ASCII-compatible, such as UTF-16, can't be split on
+    /// newlines and are skipped.
+    pub async fn annotate_file(
+        &self,
+        path: &Path,
+        encoding: &'static Encoding,
+        annotations: &mut Vec<Annotation>,
+    ) -> io::Result<()> {
+        if !encoding.is_ascii_compatible() {
+            return Ok(());
+        }
+        let mut reader = tokio::io::BufReader::new(tokio::fs::File::open(path).await?);
+        let mut line = Vec::new();
+        while annotations.len() < MAX_ANNOTATIONS && reader.read_until(b'\n', &mut line).await? > 0 {
+            let (text, _, _) = encoding.decode(&line);
+            self.annotate(&text, annotations);
+            line.clear();
+        }
+        Ok(())
+    }
+
+    fn match_line(&self, line: &str) -> Option<Annotation> {
+        self.matchers.iter().find_map(|regex| {
+            let captures = regex.captures(line)?;
+            let group = |name: &str| {
+                captures
+                    .name(name)
+                    .map(|m| m.as_str().trim())
+                    .filter(|text| !text.is_empty())
+            };
+            Some(Annotation {
+                level: group("level")
+                    .and_then(AnnotationLevel::parse)
+                    .unwrap_or(AnnotationLevel::Error),
+                message: group("message")?.to_string(),
+                file: group("file").map(String::from),
+                line: group("line").and_then(|line| line.parse().ok()),
+                column: group("column").and_then(|column| column.parse().ok()),
+            })
+        })
+    }
+}
+
+/// Parse a workflow command: `::error file=src/app.rs,line=3,col=5::message`,
+/// or `warning` or `notice` in place of `error`. The properties are optional.
+pub fn parse_workflow_command(line: &str) -> Option<Annotation> {
+    let rest = line.trim_start().strip_prefix("::")?;
+    let (command, message) = rest.split_once("::")?;
+    let (level, properties) = command.split_once(' ').unwrap_or((command, ""));
+    let level = match level {
+        "error" => AnnotationLevel::Error,
+        "warning" => AnnotationLevel::Warning,
+        "notice" => AnnotationLevel::Notice,
+        _ => return None,
+    };
+
+    let mut annotation = Annotation {
+        level,
+        message: unescape(message),
+        file: None,
+        line: None,
+        column: None,
+    };
+    for property in properties.split(',') {
+        match property.trim().split_once('=') {
+            Some(("file", file)) => annotation.file = Some(unescape(file)),
+            Some(("line", line)) => annotation.line = line.parse().ok(),
+            Some(("col", column)) => annotation.column = column.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(annotation)
+}
+
+/// Undo the percent-escapes workflow commands use for newlines, `:`, `,` and `%`
+fn unescape(text: &str) -> String {
+    text.replace("%0D", "\r")
+        .replace("%0A", "\n")
+        .replace("%3A", ":")
+        .replace("%2C", ",")
+        .replace("%25", "%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_workflow_command() {
+        let annotation = parse_workflow_command("::error file=src/app.rs,line=3,col=5::expected `;`%0Afound `}`").unwrap();
+        assert_eq!(
+            annotation,
+            Annotation {
+                level: AnnotationLevel::Error,
+                message: "expected `;`\nfound `}`".to_string(),
+                file: Some("src/app.rs".to_string()),
+                line: Some(3),
+                column: Some(5),
+            }
+        );
+        assert_eq!(annotation.to_string(), "error: src/app.rs:3:5: expected `;`\nfound `}`");
+
+        let notice = parse_workflow_command("  ::notice::Cache hit").unwrap();
+        assert_eq!((notice.level, notice.file.as_deref()), (AnnotationLevel::Notice, None));
+        assert_eq!(notice.to_string(), "notice: Cache hit");
+        assert!(parse_workflow_command("::group::Build").is_none());
+        assert!(parse_workflow_command("error: not a command").is_none());
+    }
+
+    #[test]
+    fn test_problem_matchers_annotate_output() {
+        let mut options = PipelineOptions::default();
+        options.problem_matchers.insert(
+            "gcc".to_string(),
+            r"^(?P<file>[^:\s]+):(?P<line>\d+):(?P<column>\d+): (?P<level>error|warning): (?P<message>.+)$"
+                .to_string(),
+        );
+        options.problem_matchers.insert("broken".to_string(), "(".to_string());
+        let matchers = ProblemMatchers::new(&options);
+
+        let output = "gcc -c main.c\r\nmain.c:12:7: warning: unused variable 'x'\r\nmain.c:20:1: error: expected ';'\n::warning::deprecated flag\nok\n";
+        let mut annotations = Vec::new();
+        matchers.annotate(output, &mut annotations);
+        let shown: Vec<String> = annotations.iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            shown,
+            [
+                "warning: main.c:12:7: unused variable 'x'",
+                "error: main.c:20:1: expected ';'",
+                "warning: deprecated flag",
+            ]
+        );
+
+        let flood = "::error::again\n".repeat(MAX_ANNOTATIONS + 5);
+        let mut annotations = Vec::new();
+        matchers.annotate(&flood, &mut annotations);
+        assert_eq!(annotations.len(), MAX_ANNOTATIONS);
+    }
+}
@@ -0,0 +1,92 @@
+// ANSI escape sequences in step output. Steps are captured raw, colors
+// included; pipelines with `ansi: "strip"` have them removed before results
+// are stored, and problem matchers always look at the text without them.
+
+use pulsiora_core::{AnsiMode, StepResult};
+use std::borrow::Cow;
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{7}';
+/// Single-character form of `ESC [`
+const CSI: char = '\u{9b}';
+
+/// Remove escape sequences: CSI sequences such as colors and cursor
+/// movement, OSC sequences such as hyperlinks and window titles, and other
+/// two-character escapes. Text without any is returned as is.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains([ESC, CSI]) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            CSI => {}
+            ESC => match chars.next() {
+                Some('[') => {}
+                Some(']') => {
+                    // OSC runs to BEL or ST (`ESC \`)
+                    while let Some(c) = chars.next() {
+                        if c == BEL || (c == ESC && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                // Other escapes are one more character, e.g. `ESC c`
+                _ => continue,
+            },
+            _ => {
+                out.push(c);
+                continue;
+            }
+        }
+        // A CSI sequence: parameters and intermediates, then one final
+        // character from `@` to `~`
+        for c in chars.by_ref() {
+            if ('@'..='~').contains(&c) {
+                break;
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Apply a pipeline's `ansi` mode to a finished step's output and the
+/// output of its hooks
+pub fn apply_ansi_mode(result: &mut StepResult, mode: AnsiMode) {
+    if mode != AnsiMode::Strip {
+        return;
+    }
+    let strip = |text: &mut String| {
+        if let Cow::Owned(stripped) = strip_ansi(text) {
+            *text = stripped;
+        }
+    };
+    strip(&mut result.stdout);
+    strip(&mut result.stderr);
+    for hook in &mut result.hooks {
+        strip(&mut hook.stdout);
+        strip(&mut hook.stderr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert!(matches!(strip_ansi("plain text\n"), Cow::Borrowed(_)));
+        assert_eq!(strip_ansi("\u{1b}[1;31merror\u{1b}[0m: failed"), "error: failed");
+        assert_eq!(strip_ansi("50%\u{1b}[2K\r\u{1b}[1G100%"), "50%\r100%");
+        assert_eq!(
+            strip_ansi("see \u{1b}]8;;https://example.com\u{7}docs\u{1b}]8;;\u{1b}\\ here"),
+            "see docs here"
+        );
+        assert_eq!(strip_ansi("\u{1b}creset \u{9b}32mgreen"), "reset green");
+        // A sequence cut off at the end of the output is dropped
+        assert_eq!(strip_ansi("done\u{1b}[3"), "done");
+    }
+}
@@ -0,0 +1,112 @@
+// Artifact collection: files a step lists under `artifacts` are copied out of
+// the working directory so they outlive the checkout.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Result of copying one step's artifacts
+#[derive(Debug, Default)]
+pub struct CollectedArtifacts {
+    /// Total size of the copied files
+    pub bytes: u64,
+    /// Listed paths that didn't exist
+    pub missing: Vec<String>,
+    /// Listed paths that a symlink led outside the working directory; they
+    /// aren't copied
+    pub outside: Vec<String>,
+}
+
+/// Copy each of `paths` (files or directories, relative to `source`) into
+/// `dest`, keeping their relative layout. A step can make symlinks in its
+/// working directory, so each path is resolved first and only copied if it
+/// is still inside it, and symlinks within copied directories are skipped.
+pub fn collect_artifacts(source: &Path, paths: &[String], dest: &Path) -> io::Result<CollectedArtifacts> {
+    let mut collected = CollectedArtifacts::default();
+    let source = source.canonicalize()?;
+    for path in paths {
+        match source.join(path).canonicalize() {
+            Ok(from) if from.starts_with(&source) => collected.bytes += copy_tree(&from, &dest.join(path))?,
+            Ok(_) => collected.outside.push(path.clone()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => collected.missing.push(path.clone()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(collected)
+}
+
+/// Copy a file or directory tree, skipping symlinks; returns the bytes copied
+pub(crate) fn copy_tree(from: &Path, to: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_dir() {
+        fs::create_dir_all(to)?;
+        let mut bytes = 0;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            bytes += copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(bytes)
+    } else if metadata.is_file() {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(from, to)
+    } else {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_artifacts() {
+        let root = std::env::temp_dir().join(format!("pulsiora-artifacts-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let source = root.join("work");
+        let dest = root.join("out");
+        fs::create_dir_all(source.join("coverage/html")).unwrap();
+        fs::write(source.join("coverage/html/index.html"), "<h1>84%</h1>").unwrap();
+        fs::write(source.join("report.txt"), "ok").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc/hostname", source.join("coverage/leak")).unwrap();
+
+        let paths = vec!["coverage".to_string(), "report.txt".to_string(), "missing.log".to_string()];
+        let collected = collect_artifacts(&source, &paths, &dest).unwrap();
+
+        assert_eq!(collected.bytes, 14);
+        assert_eq!(collected.missing, vec!["missing.log"]);
+        assert_eq!(fs::read_to_string(dest.join("coverage/html/index.html")).unwrap(), "<h1>84%</h1>");
+        assert!(dest.join("report.txt").exists());
+        assert!(!dest.join("coverage/leak").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_artifacts_through_symlinked_dir() {
+        let root = std::env::temp_dir().join(format!("pulsiora-artifacts-link-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let (source, dest, host) = (root.join("work"), root.join("out"), root.join("host"));
+        fs::create_dir_all(source.join("build")).unwrap();
+        fs::create_dir_all(&host).unwrap();
+        fs::write(host.join("shadow"), "root:*").unwrap();
+        fs::write(source.join("build/app"), "bin").unwrap();
+        std::os::unix::fs::symlink(&host, source.join("etc")).unwrap();
+        // Links that stay inside the working directory are fine
+        std::os::unix::fs::symlink(source.join("build"), source.join("latest")).unwrap();
+
+        let paths = vec!["etc/shadow".to_string(), "etc".to_string(), "latest/app".to_string()];
+        let collected = collect_artifacts(&source, &paths, &dest).unwrap();
+
+        assert_eq!(collected.outside, vec!["etc/shadow", "etc"]);
+        assert!(collected.missing.is_empty());
+        assert!(!dest.join("etc").exists());
+        assert_eq!(fs::read_to_string(dest.join("latest/app")).unwrap(), "bin");
+        assert_eq!(collected.bytes, 3);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
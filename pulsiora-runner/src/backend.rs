@@ -0,0 +1,359 @@
+// Where step commands run. The executor runs every step through an
+// `ExecutionBackend`: the host backend runs the shell directly, the Docker
+// backend runs it in a container with the workspace bind-mounted, and the
+// Kubernetes backend schedules it as a Job.
+
+use crate::process::{
+    apply_step_identity, output_with_deadline_streaming, shell_command, Interrupt, OutputCapture,
+    OutputSink,
+};
+use async_trait::async_trait;
+use pulsiora_core::{PipelineOptions, ResourceUsage, Step};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Path the workspace is mounted at inside step containers
+pub const CONTAINER_WORKSPACE: &str = "/workspace";
+
+/// Everything a backend needs to start one step
+pub struct StepContext<'a> {
+    pub execution_id: Uuid,
+    /// Position of the step in the execution's results
+    pub step_index: usize,
+    pub step: &'a Step,
+    pub options: &'a PipelineOptions,
+    /// Script passed to the shell, with strict mode and umask applied
+    pub script: &'a str,
+    pub work_dir: &'a Path,
+    /// Built-in variables followed by the step's own `env`
+    pub env: &'a BTreeMap<String, String>,
+    /// How much output is kept, and where it is logged in full
+    pub output: OutputCapture,
+}
+
+/// What a finished or interrupted step wrote and how it exited
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StepOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Bytes written to each stream, which may be more than was kept
+    pub stdout_bytes: u64,
+    pub stderr_bytes: u64,
+    /// Missing when the step was killed or never started
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    /// CPU time and memory, when the backend measured them
+    pub usage: Option<ResourceUsage>,
+}
+
+impl From<Output> for StepOutput {
+    fn from(output: Output) -> Self {
+        Self {
+            success: output.status.success(),
+            exit_code: output.status.code(),
+            stdout_bytes: output.stdout.len() as u64,
+            stderr_bytes: output.stderr.len() as u64,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            usage: None,
+        }
+    }
+}
+
+/// Runs step commands somewhere: on the host, in a container, ...
+#[async_trait]
+pub trait ExecutionBackend: fmt::Debug + Send + Sync {
+    /// Short name shown in logs, e.g. "docker"
+    fn name(&self) -> &'static str;
+
+    /// Build the process that runs the step. It is spawned in its own process
+    /// group and killed with it when the step is interrupted. Background
+    /// steps always run this way.
+    fn command(&self, ctx: &StepContext) -> io::Result<Command>;
+
+    /// Run a step to completion, streaming its output to `sink` and stopping
+    /// it at `deadline` or when `cancel` fires. By default this runs
+    /// `command` as a local process.
+    async fn run(
+        &self,
+        ctx: &StepContext<'_>,
+        deadline: Option<Instant>,
+        sink: Option<OutputSink>,
+        cancel: &CancellationToken,
+    ) -> io::Result<(StepOutput, Option<Interrupt>)> {
+        let command = self.command(ctx)?;
+        let grace = Duration::from_secs(ctx.options.kill_grace_secs);
+        output_with_deadline_streaming(command, deadline, grace, &ctx.output, sink, cancel).await
+    }
+
+    /// Whether the processes `run` starts are the step's own, so their CPU
+    /// time and memory describe the step; not so for a container or remote
+    /// host client
+    fn measures_usage(&self) -> bool {
+        false
+    }
+
+    /// Release anything a step left behind after its process was killed or
+    /// a background step was stopped. Blocking.
+    fn cleanup(&self, _execution_id: Uuid, _step_index: usize) {}
+}
+
+/// Runs steps with the pipeline's shell on the runner host
+#[derive(Debug, Clone, Default)]
+pub struct HostBackend;
+
+#[async_trait]
+impl ExecutionBackend for HostBackend {
+    fn name(&self) -> &'static str {
+        "host"
+    }
+
+    fn measures_usage(&self) -> bool {
+        true
+    }
+
+    fn command(&self, ctx: &StepContext) -> io::Result<Command> {
+        let mut command = shell_command(ctx.options, ctx.script);
+        command.current_dir(ctx.work_dir).envs(ctx.env);
+        apply_step_identity(&mut command, ctx.step)?;
+        Ok(command)
+    }
+
+    async fn run(
+        &self,
+        ctx: &StepContext<'_>,
+        deadline: Option<Instant>,
+        sink: Option<OutputSink>,
+        cancel: &CancellationToken,
+    ) -> io::Result<(StepOutput, Option<Interrupt>)> {
+        run_on_host(self.command(ctx)?, ctx, deadline, sink, cancel).await
+    }
+}
+
+/// Run a step's command as a local process, under a pseudo-terminal for
+/// `tty` steps
+pub(crate) async fn run_on_host(
+    command: Command,
+    ctx: &StepContext<'_>,
+    deadline: Option<Instant>,
+    sink: Option<OutputSink>,
+    cancel: &CancellationToken,
+) -> io::Result<(StepOutput, Option<Interrupt>)> {
+    let grace = Duration::from_secs(ctx.options.kill_grace_secs);
+    if ctx.step.tty {
+        #[cfg(unix)]
+        {
+            if ctx.step.user.is_some() || ctx.step.group.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "`tty` steps can't set `user` or `group` on the host",
+                ));
+            }
+            return crate::pty::output_with_pty(command, deadline, grace, &ctx.output, sink, cancel).await;
+        }
+        #[cfg(not(unix))]
+        tracing::warn!(step_name = %ctx.step.name, "tty is only supported on Unix hosts, ignoring");
+    }
+    output_with_deadline_streaming(command, deadline, grace, &ctx.output, sink, cancel).await
+}
+
+/// Runs each step in a fresh container of the step's image, with the work
+/// directory mounted at `/workspace`. The image is the step's `image`, else
+/// the pipeline's, else the backend's default.
+#[derive(Debug, Clone)]
+pub struct DockerBackend {
+    /// Docker CLI to invoke
+    program: String,
+    default_image: Option<String>,
+}
+
+impl DockerBackend {
+    pub fn new() -> Self {
+        Self {
+            program: "docker".to_string(),
+            default_image: None,
+        }
+    }
+
+    /// Image for steps whose Pulsefile doesn't name one
+    pub fn with_default_image(mut self, image: impl Into<String>) -> Self {
+        self.default_image = Some(image.into());
+        self
+    }
+
+    /// Use another Docker-compatible CLI, e.g. `podman`
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Image a step runs in
+    pub fn image_for<'a>(&'a self, step: &'a Step, options: &'a PipelineOptions) -> Option<&'a str> {
+        step_image(step, options, self.default_image.as_deref())
+    }
+
+    /// Name of the container running a step, so it can be removed if the
+    /// CLI is killed before the container exits
+    pub fn container_name(execution_id: Uuid, step_index: usize) -> String {
+        format!("pulse-{}-{}", execution_id, step_index)
+    }
+}
+
+impl Default for DockerBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionBackend for DockerBackend {
+    fn name(&self) -> &'static str {
+        "docker"
+    }
+
+    fn command(&self, ctx: &StepContext) -> io::Result<Command> {
+        let image = self.image_for(ctx.step, ctx.options).ok_or_else(missing_image)?;
+        let work_dir = std::path::absolute(ctx.work_dir)?;
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(["run", "--rm", "--name", &Self::container_name(ctx.execution_id, ctx.step_index)])
+            .arg("--volume")
+            .arg(format!("{}:{}", work_dir.display(), CONTAINER_WORKSPACE))
+            .args(["--workdir", CONTAINER_WORKSPACE]);
+        if ctx.step.tty {
+            command.arg("--tty");
+        }
+        for (key, value) in ctx.env {
+            // The host path means nothing inside the container
+            let value = if key == "PULSE_WORKSPACE" { CONTAINER_WORKSPACE } else { value };
+            command.arg("--env").arg(format!("{}={}", key, value));
+        }
+        // Names are resolved against the image's users and groups
+        match (&ctx.step.user, &ctx.step.group) {
+            (Some(user), Some(group)) => {
+                command.arg("--user").arg(format!("{}:{}", user, group));
+            }
+            (Some(user), None) => {
+                command.arg("--user").arg(user);
+            }
+            (None, Some(group)) => {
+                command.arg("--group-add").arg(group);
+            }
+            (None, None) => {}
+        }
+        command.arg(image).arg(container_shell(ctx.options)).arg("-c").arg(ctx.script);
+        Ok(command)
+    }
+
+    fn cleanup(&self, execution_id: Uuid, step_index: usize) {
+        let name = Self::container_name(execution_id, step_index);
+        let removed = Command::new(&self.program)
+            .args(["rm", "--force", &name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if let Err(e) = removed {
+            tracing::warn!(container = %name, error = %e, "Failed to remove step container");
+        }
+    }
+}
+
+/// Image a container step runs in: the step's, else the pipeline's, else `default`
+pub fn step_image<'a>(step: &'a Step, options: &'a PipelineOptions, default: Option<&'a str>) -> Option<&'a str> {
+    step.image.as_deref().or(options.image.as_deref()).or(default)
+}
+
+pub(crate) fn missing_image() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "no container image: set `image` in the Pulsefile or a default image on the server",
+    )
+}
+
+/// Shell that runs scripts in containers; Windows hosts still run Linux
+/// containers, so `windows_shell` doesn't apply
+pub(crate) fn container_shell(options: &PipelineOptions) -> String {
+    options.shell.clone().unwrap_or_else(|| "sh".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command.get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn test_docker_image_precedence() {
+        let backend = DockerBackend::new();
+        let step = Step::new("build".to_string(), "make".to_string());
+        let mut options = PipelineOptions::default();
+        assert_eq!(backend.image_for(&step, &options), None);
+
+        let backend = backend.with_default_image("debian:bookworm");
+        assert_eq!(backend.image_for(&step, &options), Some("debian:bookworm"));
+        options.image = Some("rust:1.80".to_string());
+        assert_eq!(backend.image_for(&step, &options), Some("rust:1.80"));
+        let step = step.with_image("node:20");
+        assert_eq!(backend.image_for(&step, &options), Some("node:20"));
+    }
+
+    #[test]
+    fn test_docker_command() {
+        let backend = DockerBackend::new();
+        let mut step = Step::new("test".to_string(), "npm test".to_string()).with_image("node:20");
+        step.user = Some("node".to_string());
+        let options = PipelineOptions::default();
+        let env = BTreeMap::from([
+            ("CI".to_string(), "true".to_string()),
+            ("PULSE_WORKSPACE".to_string(), "/srv/ws/1".to_string()),
+        ]);
+        let execution_id = Uuid::new_v4();
+        let ctx = StepContext {
+            execution_id,
+            step_index: 2,
+            step: &step,
+            options: &options,
+            script: "npm test",
+            work_dir: Path::new("/srv/ws/1"),
+            env: &env,
+            output: OutputCapture::default(),
+        };
+
+        let command = backend.command(&ctx).unwrap();
+        assert_eq!(command.get_program(), "docker");
+        let expected = [
+            "run",
+            "--rm",
+            "--name",
+            &format!("pulse-{}-2", execution_id),
+            "--volume",
+            "/srv/ws/1:/workspace",
+            "--workdir",
+            "/workspace",
+            "--env",
+            "CI=true",
+            "--env",
+            "PULSE_WORKSPACE=/workspace",
+            "--user",
+            "node",
+            "node:20",
+            "sh",
+            "-c",
+            "npm test",
+        ];
+        assert_eq!(args(&command), expected);
+
+        let no_image = Step::new("test".to_string(), "npm test".to_string());
+        let ctx = StepContext { step: &no_image, ..ctx };
+        let err = backend.command(&ctx).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
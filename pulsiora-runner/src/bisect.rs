@@ -0,0 +1,365 @@
+// Automated regression hunting: drives `git bisect` with one pipeline step as
+// the test. Bisect state lives in a scratch clone, so the caller's checkout is
+// never touched, and every candidate commit is tested in its own workspace.
+
+use crate::executor::PipelineExecutor;
+use pulsiora_core::{
+    GitEvent, GitEventType, GitTriggers, Pipeline, PipelineExecution, PipelineStatus, PulsioraError, Repository,
+    Result, Triggers,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Exit code a test step uses to say a commit can't be tested, as with `git bisect run`
+pub const BISECT_SKIP_EXIT_CODE: i32 = 125;
+
+/// What a test run says about a commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BisectVerdict {
+    Good,
+    Bad,
+    /// The commit couldn't be tested, e.g. it doesn't build
+    Skip,
+}
+
+impl BisectVerdict {
+    /// Verdict for a test execution; `None` if it was cancelled
+    pub fn from_execution(execution: &PipelineExecution) -> Option<Self> {
+        match execution.status {
+            PipelineStatus::Success => Some(BisectVerdict::Good),
+            PipelineStatus::Cancelled => None,
+            PipelineStatus::Failed => {
+                let skip = execution
+                    .step_results
+                    .iter()
+                    .any(|r| r.exit_code == Some(BISECT_SKIP_EXIT_CODE));
+                Some(if skip { BisectVerdict::Skip } else { BisectVerdict::Bad })
+            }
+            _ => Some(BisectVerdict::Skip),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            BisectVerdict::Good => "good",
+            BisectVerdict::Bad => "bad",
+            BisectVerdict::Skip => "skip",
+        }
+    }
+}
+
+/// Where a bisection stands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BisectState {
+    /// This commit is tested next
+    Testing(String),
+    FirstBad(String),
+    /// Only skipped commits are left; the first bad commit is one of these
+    Inconclusive(Vec<String>),
+}
+
+/// A running bisection. Its scratch directory is removed on drop.
+pub struct Bisect {
+    dir: PathBuf,
+    pipeline: Pipeline,
+    repository: Repository,
+    state: BisectState,
+}
+
+impl Bisect {
+    /// Clone `repository` into `dir` and start bisecting from `good` to
+    /// `bad`, testing each commit with the step `step_name` of `pipeline`
+    pub fn start(
+        repository: &Repository,
+        pipeline: &Pipeline,
+        step_name: &str,
+        good: &str,
+        bad: &str,
+        dir: &Path,
+    ) -> Result<Self> {
+        let pipeline = test_pipeline(pipeline, step_name)?;
+        let clone = dir.join("repo");
+        if let Some(parent) = clone.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut bisect = Self {
+            dir: dir.to_path_buf(),
+            pipeline,
+            repository: repository.clone(),
+            state: BisectState::Inconclusive(Vec::new()),
+        };
+        git_output(Command::new("git").args(["clone", "--quiet", "--no-checkout", &repository.clone_url]).arg(&clone))?;
+        // Workspaces clone from the scratch copy rather than the original
+        bisect.repository.clone_url = clone.to_string_lossy().into_owned();
+
+        let output = bisect.git(&["bisect", "start", "--no-checkout", bad, good])?;
+        bisect.state = bisect.parse_state(&output)?;
+        Ok(bisect)
+    }
+
+    pub fn state(&self) -> &BisectState {
+        &self.state
+    }
+
+    /// Run the test step against `sha` in a fresh workspace
+    pub async fn test(&self, executor: &PipelineExecutor, sha: &str, cancel: CancellationToken) -> Result<PipelineExecution> {
+        let executor = executor.clone().with_workspace_root(self.dir.join("workspaces"));
+        let event = GitEvent {
+            event_type: GitEventType::Push,
+            repository: self.repository.clone(),
+            branch: None,
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: Some(sha.to_string()),
+            commit_message: None,
+            sender: "bisect".to_string(),
+            inputs: Default::default(),
+        };
+        executor.execute_with_cancel(Uuid::new_v4(), &self.pipeline, &event, cancel).await
+    }
+
+    /// Record the verdict for the commit under test and move on
+    pub fn mark(&mut self, verdict: BisectVerdict) -> Result<&BisectState> {
+        let output = self.git(&["bisect", verdict.as_str()])?;
+        self.state = self.parse_state(&output)?;
+        Ok(&self.state)
+    }
+
+    /// Test commits until the first bad one is found, calling `on_tested`
+    /// after each run. A commit whose workspace can't be prepared is skipped.
+    pub async fn run(
+        &mut self,
+        executor: &PipelineExecutor,
+        cancel: CancellationToken,
+        mut on_tested: impl FnMut(&str, BisectVerdict, Option<&PipelineExecution>),
+    ) -> Result<BisectState> {
+        while let BisectState::Testing(sha) = self.state.clone() {
+            let (verdict, execution) = match self.test(executor, &sha, cancel.clone()).await {
+                Ok(execution) => match BisectVerdict::from_execution(&execution) {
+                    Some(verdict) => (verdict, Some(execution)),
+                    None => return Err(PulsioraError::ExecutionError("Bisect cancelled".to_string())),
+                },
+                Err(e) => {
+                    warn!(commit = %sha, error = %e, "Could not test commit, skipping it");
+                    (BisectVerdict::Skip, None)
+                }
+            };
+            on_tested(&sha, verdict, execution.as_ref());
+            self.mark(verdict)?;
+        }
+        Ok(self.state.clone())
+    }
+
+    /// Read `git bisect` output. Git exits non-zero when only skipped
+    /// commits are left, so that case is recognized before failing.
+    fn parse_state(&self, output: &std::process::Output) -> Result<BisectState> {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Some(sha) = stdout
+            .lines()
+            .find_map(|line| line.strip_suffix(" is the first bad commit"))
+        {
+            return Ok(BisectState::FirstBad(sha.trim().to_string()));
+        }
+        if let Some((_, rest)) = stdout.split_once("The first bad commit could be any of:") {
+            let candidates = rest
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .take_while(|line| !line.starts_with("We cannot bisect more"))
+                .map(String::from)
+                .collect();
+            return Ok(BisectState::Inconclusive(candidates));
+        }
+        if !output.status.success() {
+            return Err(PulsioraError::ExecutionError(format!(
+                "git bisect failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let head = git_output(Command::new("git").arg("-C").arg(self.dir.join("repo")).args(["rev-parse", "BISECT_HEAD"]))?;
+        Ok(BisectState::Testing(String::from_utf8_lossy(&head.stdout).trim().to_string()))
+    }
+
+    fn git(&self, args: &[&str]) -> Result<std::process::Output> {
+        Command::new("git")
+            .arg("-C")
+            .arg(self.dir.join("repo"))
+            .args(args)
+            .output()
+            .map_err(|e| PulsioraError::ExecutionError(format!("Failed to run git: {}", e)))
+    }
+}
+
+impl Drop for Bisect {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(path = %self.dir.display(), error = %e, "Failed to remove bisect directory");
+            }
+        }
+    }
+}
+
+/// The pipeline reduced to the test step, run for any commit
+fn test_pipeline(pipeline: &Pipeline, step_name: &str) -> Result<Pipeline> {
+    let step = pipeline.steps.iter().find(|s| s.name == step_name).ok_or_else(|| {
+        let names: Vec<&str> = pipeline.steps.iter().map(|s| s.name.as_str()).collect();
+        PulsioraError::ExecutionError(format!(
+            "Pipeline has no step \"{}\" (steps: {})",
+            step_name,
+            names.join(", ")
+        ))
+    })?;
+    let mut pipeline = pipeline.clone();
+    pipeline.steps = vec![step.clone()];
+    pipeline.triggers = Triggers::from(GitTriggers {
+        on_push: true,
+        ..Default::default()
+    });
+    Ok(pipeline)
+}
+
+fn git_output(command: &mut Command) -> Result<std::process::Output> {
+    let output = command
+        .output()
+        .map_err(|e| PulsioraError::ExecutionError(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(PulsioraError::ExecutionError(format!(
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::Step;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pulsiora-bisect-{}-{}", name, Uuid::new_v4()))
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// A repository whose `value` file reads "ok" until the `broken_at`th commit
+    fn create_repo(dir: &Path, commits: usize, broken_at: usize) -> Vec<String> {
+        fs::create_dir_all(dir).unwrap();
+        git(dir, &["init", "--quiet", "--initial-branch=main"]);
+        (1..=commits)
+            .map(|i| {
+                let value = if i >= broken_at { "broken" } else { "ok" };
+                fs::write(dir.join("value"), format!("{}\n{}\n", value, i)).unwrap();
+                git(dir, &["add", "value"]);
+                git(dir, &["commit", "--quiet", "-m", &format!("commit {}", i)]);
+                git(dir, &["rev-parse", "HEAD"])
+            })
+            .collect()
+    }
+
+    fn create_pipeline() -> Pipeline {
+        Pipeline {
+            name: "test".to_string(),
+            version: "1.0".to_string(),
+            description: None,
+            labels: vec![],
+            owners: vec![],
+            environment: None,
+            triggers: Triggers::default(),
+            steps: vec![
+                Step::new("build".to_string(), "false".to_string()),
+                Step::new("check".to_string(), "grep -qx ok value".to_string()),
+            ],
+            vars: Default::default(),
+            options: Default::default(),
+        }
+    }
+
+    fn repository(origin: &Path) -> Repository {
+        Repository {
+            owner: "test".to_string(),
+            name: "repo".to_string(),
+            full_name: "test/repo".to_string(),
+            clone_url: origin.to_string_lossy().into_owned(),
+            default_branch: "main".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bisect_finds_first_bad_commit() {
+        let root = temp_dir("find");
+        let origin = root.join("origin");
+        let commits = create_repo(&origin, 6, 4);
+        let scratch = root.join("scratch");
+
+        let mut bisect =
+            Bisect::start(&repository(&origin), &create_pipeline(), "check", &commits[0], &commits[5], &scratch).unwrap();
+        assert!(matches!(bisect.state(), BisectState::Testing(_)));
+
+        let mut tested = Vec::new();
+        let state = bisect
+            .run(&PipelineExecutor::new(), CancellationToken::new(), |sha, verdict, _| {
+                tested.push((sha.to_string(), verdict))
+            })
+            .await
+            .unwrap();
+        assert_eq!(state, BisectState::FirstBad(commits[3].clone()));
+        for (sha, verdict) in &tested {
+            let index = commits.iter().position(|c| c == sha).unwrap();
+            let expected = if index >= 3 { BisectVerdict::Bad } else { BisectVerdict::Good };
+            assert_eq!(*verdict, expected);
+        }
+
+        // The original checkout is untouched and the scratch clone goes away
+        assert_eq!(git(&origin, &["rev-parse", "HEAD"]), commits[5]);
+        drop(bisect);
+        assert!(!scratch.exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bisect_skips_untestable_commits() {
+        let root = temp_dir("skip");
+        let origin = root.join("origin");
+        let commits = create_repo(&origin, 3, 3);
+        let mut pipeline = create_pipeline();
+        pipeline.steps[1].run = format!("grep -q 2 value && exit {}; grep -qx ok value", BISECT_SKIP_EXIT_CODE);
+
+        let mut bisect =
+            Bisect::start(&repository(&origin), &pipeline, "check", &commits[0], &commits[2], &root.join("scratch"))
+                .unwrap();
+        let state = bisect
+            .run(&PipelineExecutor::new(), CancellationToken::new(), |_, _, _| {})
+            .await
+            .unwrap();
+        let BisectState::Inconclusive(mut candidates) = state else { panic!("expected inconclusive") };
+        candidates.sort();
+        let mut expected = vec![commits[1].clone(), commits[2].clone()];
+        expected.sort();
+        assert_eq!(candidates, expected);
+        drop(bisect);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_bisect_rejects_unknown_step() {
+        let err = test_pipeline(&create_pipeline(), "deploy").unwrap_err();
+        assert!(err.to_string().contains("no step \"deploy\" (steps: build, check)"));
+    }
+}
@@ -0,0 +1,111 @@
+// Coverage reports named by a step's `reports { coverage }`. LCOV
+// (`lcov.info`) and Cobertura XML (`coverage.xml`) are read; both are reduced
+// to covered and total lines.
+
+use pulsiora_core::CoverageReport;
+use regex::Regex;
+use std::io;
+use std::path::Path;
+
+/// Read a coverage report, telling the format from its content
+pub async fn read_coverage(path: &Path) -> io::Result<CoverageReport> {
+    let text = tokio::fs::read_to_string(path).await?;
+    parse_coverage(&text).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "not an LCOV or Cobertura coverage report")
+    })
+}
+
+/// Parse an LCOV or Cobertura XML report
+pub fn parse_coverage(text: &str) -> Option<CoverageReport> {
+    if text.trim_start().starts_with('<') {
+        parse_cobertura(text)
+    } else {
+        parse_lcov(text)
+    }
+}
+
+/// Sum the records of an LCOV report. Each record's `LF`/`LH` totals are
+/// used, or its `DA` lines when a tool leaves the totals out.
+pub fn parse_lcov(text: &str) -> Option<CoverageReport> {
+    #[derive(Default)]
+    struct Record {
+        found: Option<u64>,
+        hit: Option<u64>,
+        da_total: u64,
+        da_covered: u64,
+    }
+    impl Record {
+        fn lines(&self) -> (u64, u64) {
+            match (self.found, self.hit) {
+                (Some(found), Some(hit)) => (hit, found),
+                _ => (self.da_covered, self.da_total),
+            }
+        }
+    }
+
+    let mut report = CoverageReport { lines_covered: 0, lines_total: 0 };
+    let mut record = Record::default();
+    let mut seen = false;
+    for line in text.lines().map(str::trim) {
+        if let Some(found) = line.strip_prefix("LF:") {
+            record.found = found.parse().ok();
+        } else if let Some(hit) = line.strip_prefix("LH:") {
+            record.hit = hit.parse().ok();
+        } else if let Some(data) = line.strip_prefix("DA:") {
+            record.da_total += 1;
+            let hits = data.split(',').nth(1).and_then(|hits| hits.parse::<u64>().ok());
+            if hits.is_some_and(|hits| hits > 0) {
+                record.da_covered += 1;
+            }
+        } else if line == "end_of_record" {
+            let (covered, total) = std::mem::take(&mut record).lines();
+            report.lines_covered += covered;
+            report.lines_total += total;
+            seen = true;
+        } else {
+            seen |= line.starts_with("SF:");
+        }
+    }
+    seen.then_some(report)
+}
+
+/// Read the `lines-covered` and `lines-valid` totals of a Cobertura report
+pub fn parse_cobertura(text: &str) -> Option<CoverageReport> {
+    let root = Regex::new(r"<coverage\b[^>]*>").ok()?.find(text)?.as_str();
+    let attribute = |name: &str| -> Option<u64> {
+        let pattern = format!(r#"\s{}\s*=\s*"(\d+)""#, regex::escape(name));
+        Regex::new(&pattern).ok()?.captures(root)?[1].parse().ok()
+    };
+    Some(CoverageReport {
+        lines_covered: attribute("lines-covered")?,
+        lines_total: attribute("lines-valid")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lcov() {
+        let lcov = "TN:\nSF:src/lib.rs\nDA:1,4\nDA:2,0\nLF:2\nLH:1\nend_of_record\nSF:src/main.rs\nDA:1,1\nDA:2,3\nDA:3,0\nend_of_record\n";
+        assert_eq!(
+            parse_coverage(lcov),
+            Some(CoverageReport { lines_covered: 3, lines_total: 5 })
+        );
+        assert_eq!(parse_coverage("not a report"), None);
+    }
+
+    #[test]
+    fn test_parse_cobertura() {
+        let xml = r#"<?xml version="1.0" ?>
+<coverage version="7.4" timestamp="1700000000" lines-valid="200" lines-covered="150" line-rate="0.75">
+  <packages/>
+</coverage>"#;
+        assert_eq!(
+            parse_coverage(xml),
+            Some(CoverageReport { lines_covered: 150, lines_total: 200 })
+        );
+        assert_eq!(parse_coverage("<coverage line-rate=\"0.5\"/>"), None);
+    }
+}
@@ -0,0 +1,160 @@
+// Decoding step output. Steps write UTF-8 unless they set `encoding`; bytes
+// that aren't valid in the step's encoding are replaced with U+FFFD and
+// counted, so a corrupted log is visible in the step result.
+
+use crate::process::Capture;
+use encoding_rs::{Decoder, DecoderResult, Encoding, UTF_8};
+use pulsiora_core::{Step, StepResult};
+use tracing::warn;
+
+/// The encoding a step's output is decoded from
+pub fn output_encoding(step: &Step) -> &'static Encoding {
+    let Some(label) = &step.encoding else { return UTF_8 };
+    Encoding::for_label(label.as_bytes()).unwrap_or_else(|| {
+        // The parser rejects unknown labels, but steps can also be built in code
+        warn!(step_name = %step.name, encoding = %label, "Unknown output encoding, using UTF-8");
+        UTF_8
+    })
+}
+
+/// A step's stdout and stderr as text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub encoding: &'static Encoding,
+    /// Invalid byte sequences replaced with U+FFFD, across both streams
+    pub replacement_chars: usize,
+    /// Bytes written to each stream, which may be more than was kept
+    pub stdout_bytes: u64,
+    pub stderr_bytes: u64,
+}
+
+impl DecodedOutput {
+    pub fn decode(stdout: &[u8], stderr: &[u8], encoding: &'static Encoding) -> Self {
+        let (stdout_bytes, stderr_bytes) = (stdout.len() as u64, stderr.len() as u64);
+        let (stdout, stdout_replaced) = decode_counting(stdout, encoding);
+        let (stderr, stderr_replaced) = decode_counting(stderr, encoding);
+        Self {
+            stdout,
+            stderr,
+            encoding,
+            replacement_chars: stdout_replaced + stderr_replaced,
+            stdout_bytes,
+            stderr_bytes,
+        }
+    }
+
+    /// Decode output captured under a size limit, keeping its byte counts
+    pub(crate) fn decode_captured(stdout: &Capture, stderr: &Capture, encoding: &'static Encoding) -> Self {
+        Self {
+            stdout_bytes: stdout.total_bytes(),
+            stderr_bytes: stderr.total_bytes(),
+            ..Self::decode(&stdout.to_bytes(), &stderr.to_bytes(), encoding)
+        }
+    }
+
+    /// Store the output, with how it was decoded, in `result`
+    pub fn apply_to(self, result: &mut StepResult) {
+        result.stdout = self.stdout;
+        result.stderr = self.stderr;
+        result.encoding = Some(self.encoding.name().to_string());
+        result.replacement_chars = self.replacement_chars;
+        result.stdout_bytes = self.stdout_bytes;
+        result.stderr_bytes = self.stderr_bytes;
+    }
+}
+
+/// Decode `bytes`, returning the text and the number of replaced sequences
+pub fn decode_counting(bytes: &[u8], encoding: &'static Encoding) -> (String, usize) {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::new();
+    let mut replaced = 0;
+    let mut input = bytes;
+    loop {
+        let needed = decoder
+            .max_utf8_buffer_length_without_replacement(input.len())
+            .unwrap_or(input.len());
+        text.reserve(needed);
+        let (result, read) = decoder.decode_to_string_without_replacement(input, &mut text, true);
+        input = &input[read..];
+        match result {
+            DecoderResult::InputEmpty => return (text, replaced),
+            DecoderResult::OutputFull => {}
+            DecoderResult::Malformed(_, _) => {
+                text.push('\u{FFFD}');
+                replaced += 1;
+            }
+        }
+    }
+}
+
+/// Decodes output that arrives in chunks, keeping a character split across
+/// chunks for the next one
+pub(crate) struct ChunkDecoder {
+    decoder: Decoder,
+}
+
+impl ChunkDecoder {
+    pub(crate) fn new(encoding: &'static Encoding) -> Self {
+        Self {
+            decoder: encoding.new_decoder_without_bom_handling(),
+        }
+    }
+
+    /// Decode `bytes`; `last` flushes anything held back
+    pub(crate) fn decode(&mut self, bytes: &[u8], last: bool) -> String {
+        let mut text = String::new();
+        let mut input = bytes;
+        loop {
+            let needed = self.decoder.max_utf8_buffer_length(input.len()).unwrap_or(input.len());
+            text.reserve(needed);
+            let (result, read, _) = self.decoder.decode_to_string(input, &mut text, last);
+            input = &input[read..];
+            if result == encoding_rs::CoderResult::InputEmpty {
+                return text;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::SHIFT_JIS;
+
+    #[test]
+    fn test_decode_counting_replaces_invalid_bytes() {
+        assert_eq!(decode_counting("héllo".as_bytes(), UTF_8), ("héllo".to_string(), 0));
+        assert_eq!(
+            decode_counting(b"ok \xff\xfe done", UTF_8),
+            ("ok \u{FFFD}\u{FFFD} done".to_string(), 2)
+        );
+
+        // "ビルド" (build) as Windows tools write it
+        let shift_jis = [0x83, 0x72, 0x83, 0x8b, 0x83, 0x68];
+        assert_eq!(decode_counting(&shift_jis, SHIFT_JIS), ("ビルド".to_string(), 0));
+        let (text, replaced) = decode_counting(&shift_jis, UTF_8);
+        assert!(replaced > 0);
+        assert!(text.contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn test_output_encoding() {
+        let step = Step::new("build".to_string(), "msbuild".to_string());
+        assert_eq!(output_encoding(&step), UTF_8);
+        assert_eq!(output_encoding(&step.clone().with_encoding("shift_jis")), SHIFT_JIS);
+        assert_eq!(output_encoding(&step.with_encoding("nonsense")), UTF_8);
+    }
+
+    #[test]
+    fn test_chunk_decoder_keeps_split_character() {
+        let mut decoder = ChunkDecoder::new(UTF_8);
+        assert_eq!(decoder.decode(&[b'h', 0xc3], false), "h");
+        assert_eq!(decoder.decode(&[0xa9, b'l', b'l', b'o'], false), "éllo");
+
+        let mut decoder = ChunkDecoder::new(SHIFT_JIS);
+        assert_eq!(decoder.decode(&[0x83], false), "");
+        assert_eq!(decoder.decode(&[0x72], true), "ビ");
+    }
+}
@@ -1,23 +1,95 @@
 use pulsiora_core::{
-    Pipeline, Step, StepResult, StepStatus, PipelineExecution, PipelineStatus,
-    GitEvent,
+    ActionRef, ExecutionEvent, ExecutionLimits, Pipeline, PipelineOptions, Step, StepResult, StepStatus, PipelineExecution,
+    OutputStream, PipelineStatus, GitEvent, HookResult, Annotation, CoverageReport, ExecutionMetrics, PhaseKind, PhaseResult, PulsioraError, StepHook, TriggerEvent,
+    TriggerSource,
 };
 use pulsiora_parser::parse_pulsefile;
-use std::path::Path;
+use crate::actions::{prepare_action, ActionCache};
+use crate::backend::{ExecutionBackend, HostBackend, StepContext};
+use crate::ssh::SshBackend;
+use crate::process::{step_log_path, step_script, Interrupt, OutputCapture, OutputSink};
+use crate::service::BackgroundService;
+use crate::annotations::ProblemMatchers;
+use crate::ansi::apply_ansi_mode;
+use crate::artifacts::collect_artifacts;
+use crate::coverage::read_coverage;
+use crate::encoding::{output_encoding, DecodedOutput};
+use crate::limiter::ConcurrencyLimit;
+use crate::phases::{dir_size, PhaseTimer};
+use crate::plan::{ExecutionPlan, PlannedStep};
+use crate::workspace::{CloneOptions, Workspace};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use chrono::Utc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
-use tracing::{info, warn, error};
+use tracing::{info, info_span, warn, error, Instrument};
 
 /// Executes a pipeline from a Pulsefile
 #[derive(Clone)]
 pub struct PipelineExecutor {
     work_dir: Option<std::path::PathBuf>,
+    limits: ExecutionLimits,
+    /// Step artifacts are kept under `<artifact_dir>/<execution id>/`
+    artifact_dir: Option<PathBuf>,
+    /// Each execution clones its repository into `<workspace_root>/<execution id>/`
+    workspace_root: Option<PathBuf>,
+    /// Step output is logged in full under `<log_dir>/<execution id>/`
+    log_dir: Option<PathBuf>,
+    clone_options: CloneOptions,
+    /// Where step commands run
+    backend: Arc<dyn ExecutionBackend>,
+    /// Runs steps that name an `ssh://` runner
+    ssh: Arc<SshBackend>,
+    /// ID of the running execution, set with `builtin_env`
+    execution_id: Uuid,
+    /// Built-in `PULSE_*` variables of the running execution, set for every step
+    builtin_env: BTreeMap<String, String>,
+    /// Variables set for every step, under its own `env`
+    env: BTreeMap<String, String>,
+    /// Secrets set for every step and masked in its output
+    secrets: BTreeMap<String, String>,
+    /// `problem_matchers` of the running execution's pipeline
+    matchers: ProblemMatchers,
+    /// When the next execution was queued, for its queue time
+    queued_at: Option<DateTime<Utc>>,
+    /// Executions running at once, shared by clones of the executor
+    execution_limit: Option<ConcurrencyLimit>,
+    /// Foreground steps running at once, across all executions
+    step_limit: Option<ConcurrencyLimit>,
+    /// Bundles of `uses` steps
+    actions: ActionCache,
+    /// Action cache phases of the running execution, kept as its steps fetch
+    /// their bundles
+    cache_phases: Arc<std::sync::Mutex<Vec<PhaseResult>>>,
 }
 
 impl PipelineExecutor {
     pub fn new() -> Self {
-        Self { work_dir: None }
+        Self {
+            work_dir: None,
+            limits: ExecutionLimits::default(),
+            artifact_dir: None,
+            workspace_root: None,
+            log_dir: None,
+            clone_options: CloneOptions::default(),
+            backend: Arc::new(HostBackend),
+            ssh: Arc::new(SshBackend::default()),
+            execution_id: Uuid::nil(),
+            builtin_env: BTreeMap::new(),
+            env: BTreeMap::new(),
+            secrets: BTreeMap::new(),
+            matchers: ProblemMatchers::default(),
+            queued_at: None,
+            execution_limit: None,
+            step_limit: None,
+            actions: ActionCache::new(std::env::temp_dir().join("pulsiora-actions")),
+            cache_phases: Default::default(),
+        }
     }
 
     pub fn with_work_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
@@ -25,6 +97,125 @@ impl PipelineExecutor {
         self
     }
 
+    /// Keep step artifacts under this directory; without it they aren't collected
+    pub fn with_artifact_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.artifact_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Clone the event's repository for every execution and run its steps in
+    /// the checkout, which is removed afterwards. Without this, steps run in
+    /// the work directory.
+    pub fn with_workspace_root<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.workspace_root = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// How workspaces are cloned, e.g. shallow or with submodules
+    pub fn with_clone_options(mut self, options: CloneOptions) -> Self {
+        self.clone_options = options;
+        self
+    }
+
+    /// Run steps through this backend instead of directly on the host
+    pub fn with_backend(mut self, backend: impl ExecutionBackend + 'static) -> Self {
+        self.backend = Arc::new(backend);
+        self
+    }
+
+    /// Run steps that name an `ssh://` runner through this backend, e.g. one
+    /// with the server's identity file
+    pub fn with_ssh_backend(mut self, backend: SshBackend) -> Self {
+        self.ssh = Arc::new(backend);
+        self
+    }
+
+    /// Run at most `max` executions at once, counting every clone of this
+    /// executor; others wait for one to finish before they start
+    pub fn with_max_concurrent_executions(mut self, max: usize) -> Self {
+        self.execution_limit = Some(ConcurrencyLimit::new(max));
+        self
+    }
+
+    /// Run at most `max` steps at once across all executions of this executor
+    /// and its clones. Background steps don't take a slot, so a service
+    /// can't keep the steps that use it from running.
+    pub fn with_max_concurrent_steps(mut self, max: usize) -> Self {
+        self.step_limit = Some(ConcurrencyLimit::new(max));
+        self
+    }
+
+    /// Where bundles of `uses` steps are downloaded and kept
+    pub fn with_action_cache(mut self, cache: ActionCache) -> Self {
+        self.actions = cache;
+        self
+    }
+
+    /// Set these variables for every step, under its own `env` and any secrets
+    pub fn with_env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Set these variables for every step, under its own `env`, and replace
+    /// their values with `SECRET_MASK` in the output that is kept
+    pub fn with_secrets(mut self, secrets: BTreeMap<String, String>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    /// Record the time executions waited since being queued at `queued_at`
+    pub fn with_queued_at(mut self, queued_at: DateTime<Utc>) -> Self {
+        self.queued_at = Some(queued_at);
+        self
+    }
+
+    /// Directory holding an execution's artifacts, if artifacts are collected
+    pub fn artifacts_path(&self, execution_id: Uuid) -> Option<PathBuf> {
+        self.artifact_dir.as_ref().map(|dir| dir.join(execution_id.to_string()))
+    }
+
+    /// Write every step's output in full to log files under this directory
+    /// and keep only the end of it in the step result
+    pub fn with_log_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.log_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Log file of one stream of a step, from the step result's `log_ref`
+    pub fn step_log_path(&self, log_ref: &str, stream: OutputStream) -> Option<PathBuf> {
+        let dir = self.log_dir.as_ref()?;
+        // References are `<uuid>/<index>`; anything else can't name a log
+        let (execution_id, index) = log_ref.split_once('/')?;
+        let execution_id = Uuid::parse_str(execution_id).ok()?;
+        let index: usize = index.parse().ok()?;
+        Some(step_log_path(&dir.join(execution_id.to_string()).join(index.to_string()), stream))
+    }
+
+    /// `log_ref` of a step's full log, if output is logged
+    fn step_log_ref(&self, step_index: usize) -> Option<String> {
+        self.log_dir.as_ref().map(|_| format!("{}/{}", self.execution_id, step_index))
+    }
+
+    /// How a step's output is kept
+    fn output_capture(&self, step_index: usize) -> OutputCapture {
+        OutputCapture {
+            max_bytes: self.limits.max_output_bytes,
+            log_prefix: self
+                .log_dir
+                .as_ref()
+                .map(|dir| dir.join(self.execution_id.to_string()).join(step_index.to_string())),
+            measure_usage: false,
+            secrets: self.secrets.values().cloned().collect(),
+        }
+    }
+
+    /// Stop executions that exceed these limits
+    pub fn with_limits(mut self, limits: ExecutionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Execute a pipeline from a Pulsefile string
     pub async fn execute_from_pulsefile(
         &self,
@@ -35,52 +226,387 @@ impl PipelineExecutor {
         self.execute(&pipeline, git_event).await
     }
 
+    /// Execute a Pulsefile string under a caller-chosen execution ID
+    pub async fn execute_from_pulsefile_with_id(
+        &self,
+        execution_id: Uuid,
+        pulsefile_content: &str,
+        git_event: &GitEvent,
+    ) -> Result<PipelineExecution, pulsiora_core::PulsioraError> {
+        let pipeline = parse_pulsefile(pulsefile_content)?;
+        self.execute_with_id(execution_id, &pipeline, git_event).await
+    }
+
     /// Execute a parsed pipeline
     pub async fn execute(
         &self,
         pipeline: &Pipeline,
         git_event: &GitEvent,
     ) -> Result<PipelineExecution, pulsiora_core::PulsioraError> {
-        let execution_id = Uuid::new_v4();
-        let started_at = Utc::now();
+        self.execute_with_id(Uuid::new_v4(), pipeline, git_event).await
+    }
+
+    /// Execute a parsed pipeline under a caller-chosen execution ID
+    pub async fn execute_with_id(
+        &self,
+        execution_id: Uuid,
+        pipeline: &Pipeline,
+        git_event: &GitEvent,
+    ) -> Result<PipelineExecution, pulsiora_core::PulsioraError> {
+        self.run(execution_id, pipeline, git_event, None, &CancellationToken::new(), Vec::new())
+            .await
+    }
+
+    /// Execute a parsed pipeline that stops when `cancel` fires: the running
+    /// step's process group is killed, the remaining steps are recorded as
+    /// skipped and the execution ends `Cancelled`
+    pub async fn execute_with_cancel(
+        &self,
+        execution_id: Uuid,
+        pipeline: &Pipeline,
+        git_event: &GitEvent,
+        cancel: CancellationToken,
+    ) -> Result<PipelineExecution, pulsiora_core::PulsioraError> {
+        self.run(execution_id, pipeline, git_event, None, &cancel, Vec::new()).await
+    }
+
+    /// Execute a parsed pipeline, sending `ExecutionEvent`s to `events` while
+    /// it runs. A full channel holds the pipeline back until events are read;
+    /// a dropped receiver doesn't stop it.
+    pub async fn execute_with_events(
+        &self,
+        execution_id: Uuid,
+        pipeline: &Pipeline,
+        git_event: &GitEvent,
+        events: mpsc::Sender<ExecutionEvent>,
+    ) -> Result<PipelineExecution, pulsiora_core::PulsioraError> {
+        self.run(execution_id, pipeline, git_event, Some(&events), &CancellationToken::new(), Vec::new())
+            .await
+    }
+
+    /// Continue an execution that was interrupted, e.g. by a server restart,
+    /// from its first unfinished step. `completed` holds the results recorded
+    /// before the interruption; those up to the `resume_point` are kept and
+    /// their steps aren't run again. The execution reuses the workspace the
+    /// interrupted run left behind, if there is one, and sends `events` for
+    /// the steps it runs. It stops like `execute_with_cancel` when `cancel`
+    /// fires.
+    pub async fn resume_with_events(
+        &self,
+        execution_id: Uuid,
+        pipeline: &Pipeline,
+        git_event: &GitEvent,
+        completed: Vec<StepResult>,
+        events: mpsc::Sender<ExecutionEvent>,
+        cancel: CancellationToken,
+    ) -> Result<PipelineExecution, pulsiora_core::PulsioraError> {
+        self.run(execution_id, pipeline, git_event, Some(&events), &cancel, completed)
+            .await
+    }
+
+    /// Variables every step of an execution gets apart from secrets: the
+    /// built-in `PULSE_*` variables and the executor's env. `PULSE_WORKSPACE`
+    /// is left out, since it depends on where the execution runs.
+    pub fn resolved_env(&self, execution_id: Uuid, pipeline: &Pipeline, git_event: &GitEvent) -> BTreeMap<String, String> {
+        let mut env = builtin_env(execution_id, pipeline, git_event, self.step_dir());
+        env.remove("PULSE_WORKSPACE");
+        env.extend(self.env.clone());
+        env
+    }
+
+    /// Work out which steps an execution of `pipeline` for `git_event` would
+    /// run, and with which script and environment, without running anything
+    /// or preparing a workspace
+    pub fn plan(&self, execution_id: Uuid, pipeline: &Pipeline, git_event: &GitEvent) -> ExecutionPlan {
+        let triggered = pipeline.triggers.matches(&TriggerEvent::from(git_event));
+        let limit_exceeded = self.limits.check_step_count(pipeline.steps.len());
+
+        let mut executor = self.clone();
+        if let Some(root) = &self.workspace_root {
+            executor.work_dir = Some(root.join(execution_id.to_string()));
+        }
+        executor.execution_id = execution_id;
+        executor.builtin_env = builtin_env(execution_id, pipeline, git_event, executor.step_dir());
+
+        let steps = pipeline
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                let (script, env) = executor.step_inputs(step, &pipeline.options);
+                PlannedStep {
+                    index,
+                    name: step.name.clone(),
+                    backend: executor.backend_for(step).name().to_string(),
+                    image: step.image.clone().or_else(|| pipeline.options.image.clone()),
+                    runner: step.runner.clone(),
+                    uses: step.uses.clone(),
+                    script,
+                    env,
+                    background: step.background,
+                    allow_failure: step.allow_failure,
+                }
+            })
+            .collect();
+
+        ExecutionPlan {
+            execution_id,
+            pipeline_name: pipeline.name.clone(),
+            triggered,
+            limit_exceeded,
+            steps,
+        }
+    }
+
+    async fn run(
+        &self,
+        execution_id: Uuid,
+        pipeline: &Pipeline,
+        git_event: &GitEvent,
+        events: Option<&mpsc::Sender<ExecutionEvent>>,
+        cancel: &CancellationToken,
+        mut completed: Vec<StepResult>,
+    ) -> Result<PipelineExecution, pulsiora_core::PulsioraError> {
+        // Waiting for a slot counts as queue time. A cancelled execution
+        // goes on without one and stops before its first step.
+        let _execution_slot = match &self.execution_limit {
+            Some(limit) => {
+                if limit.in_use() >= limit.max() {
+                    info!(execution_id = %execution_id, max = limit.max(), "Waiting for an execution slot");
+                }
+                limit.acquire(None, cancel).await.ok()
+            }
+            None => None,
+        };
+        let started_at = completed.first().map_or_else(Utc::now, |result| result.started_at);
+        completed.truncate(resume_point(pipeline, &completed));
+        let emit = |event: ExecutionEvent| async move {
+            if let Some(events) = events {
+                let _ = events.send(event).await;
+            }
+        };
 
         info!(
             execution_id = %execution_id,
             pipeline_name = %pipeline.name,
+            backend = self.backend.name(),
             "Starting pipeline execution"
         );
 
         // Check if pipeline should be triggered
-        if !pipeline.triggers.git.matches(git_event) {
+        if !pipeline.triggers.matches(&TriggerEvent::from(git_event)) {
+            emit(ExecutionEvent::PipelineFinished {
+                execution_id,
+                status: PipelineStatus::Skipped,
+            })
+            .await;
             return Ok(PipelineExecution {
                 id: execution_id,
                 pipeline_name: pipeline.name.clone(),
                 pipeline_version: pipeline.version.clone(),
+                pipeline_labels: pipeline.labels.clone(),
+                environment: pipeline.environment.clone(),
                 repository: git_event.repository.clone(),
                 git_event: git_event.clone(),
                 status: PipelineStatus::Skipped,
                 step_results: vec![],
+                phases: vec![],
+                limit_exceeded: None,
+                coverage: None,
+                metrics: ExecutionMetrics::default(),
+                pulsefile_revision: None,
+                attempts: Vec::new(),
                 started_at,
                 completed_at: Some(Utc::now()),
             });
         }
 
-        let mut step_results = Vec::new();
+        let resuming = !completed.is_empty();
+        // A step failed but fail_fast is off, so the remaining steps still run
+        let mut failed = completed
+            .iter()
+            .zip(&pipeline.steps)
+            .any(|(result, step)| result.status == StepStatus::Failed && !step.allow_failure);
+        let mut step_results = completed;
         let mut pipeline_status = PipelineStatus::Running;
+        // Background steps that are still running, stopped after the last step
+        let mut services: Vec<BackgroundService> = Vec::new();
+        // Artifacts of steps finished before a resume count towards the limit
+        let mut artifact_bytes = match self.artifacts_path(execution_id) {
+            Some(dir) if resuming => dir_size(&dir).unwrap_or_default(),
+            _ => 0,
+        };
+        let mut limit_exceeded = self.limits.check_step_count(pipeline.steps.len());
+        // A pipeline over the step limit is rejected as a whole, so it has no step results
+        let rejected = limit_exceeded.is_some();
+        if rejected {
+            pipeline_status = PipelineStatus::Failed;
+            step_results.clear();
+        }
+
+        let mut phases = Vec::new();
+        // A resumed execution continues in what its finished steps left behind
+        let reopened = match &self.workspace_root {
+            Some(root) if resuming && !rejected => Workspace::reopen(root, execution_id),
+            _ => None,
+        };
+        let workspace = match &self.workspace_root {
+            Some(_) if reopened.is_some() => {
+                info!(execution_id = %execution_id, "Resuming in the interrupted execution's workspace");
+                reopened
+            }
+            Some(root) if !rejected && !cancel.is_cancelled() => {
+                let timer = PhaseTimer::start(PhaseKind::Clone);
+                let root = root.clone();
+                let event = git_event.clone();
+                let options = self.clone_options.clone();
+                let workspace =
+                    tokio::task::spawn_blocking(move || Workspace::prepare(&root, execution_id, &event, &options))
+                        .instrument(info_span!("clone"))
+                        .await
+                        .map_err(|e| PulsioraError::ExecutionError(format!("Workspace task failed: {}", e)))??;
+                phases.push(timer.finish(dir_size(workspace.path()).ok()));
+                info!(execution_id = %execution_id, path = %workspace.path().display(), "Prepared workspace");
+                Some(workspace)
+            }
+            _ => None,
+        };
+        // Steps and artifact collection run in the workspace when there is one
+        let mut executor = self.clone();
+        if let Some(workspace) = &workspace {
+            executor.work_dir = Some(workspace.path().to_path_buf());
+        }
+        executor.execution_id = execution_id;
+        executor.builtin_env = builtin_env(execution_id, pipeline, git_event, executor.step_dir());
+        executor.matchers = ProblemMatchers::new(&pipeline.options);
+        executor.cache_phases = Default::default();
+
+        let deadline = self
+            .limits
+            .max_runtime_secs
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        let runtime_exceeded = || {
+            format!(
+                "Execution exceeded the maximum runtime of {}s",
+                self.limits.max_runtime_secs.unwrap_or_default()
+            )
+        };
+
+        // Execute each step in order, after those finished before a resume
+        for step in &pipeline.steps[step_results.len()..] {
+            if pipeline_status != PipelineStatus::Running {
+                break;
+            }
+            if cancel.is_cancelled() {
+                pipeline_status = PipelineStatus::Cancelled;
+                break;
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                limit_exceeded = Some(runtime_exceeded());
+                pipeline_status = PipelineStatus::Failed;
+                break;
+            }
+
+            let step_slot = match &self.step_limit {
+                Some(limit) if !step.background => match limit.acquire(deadline, cancel).await {
+                    Ok(slot) => Some(slot),
+                    Err(Interrupt::Deadline) => {
+                        limit_exceeded = Some(runtime_exceeded());
+                        pipeline_status = PipelineStatus::Failed;
+                        break;
+                    }
+                    Err(Interrupt::Cancelled) => {
+                        pipeline_status = PipelineStatus::Cancelled;
+                        break;
+                    }
+                },
+                _ => None,
+            };
 
-        // Execute each step in order
-        for step in &pipeline.steps {
             info!(
                 execution_id = %execution_id,
                 step_name = %step.name,
                 "Executing step"
             );
+            emit(ExecutionEvent::StepStarted {
+                execution_id,
+                step_name: step.name.clone(),
+            })
+            .await;
 
-            let step_result = self.execute_step(step).await;
+            let sink = events.map(|events| OutputSink {
+                events: events.clone(),
+                execution_id,
+                step_name: step.name.clone(),
+                encoding: output_encoding(step),
+            });
+            // One span per step, so traces show where an execution's time went
+            let step_span = info_span!(
+                "step",
+                step_name = %step.name,
+                index = step_results.len(),
+                status = tracing::field::Empty
+            );
+            let (mut step_result, interrupt, service) = executor
+                .run_step(step, step_results.len(), &pipeline.options, deadline, cancel, sink)
+                .instrument(step_span.clone())
+                .await;
+            step_span.record("status", tracing::field::debug(step_result.status));
+            drop(step_slot);
+            apply_ansi_mode(&mut step_result, pipeline.options.ansi);
+            services.extend(service);
 
-            if step_result.status == StepStatus::Failed && !step.allow_failure {
+            if interrupt.is_none() {
+                artifact_bytes += executor.collect_step_artifacts(execution_id, step, &mut step_result).await;
+                if !step.background {
+                    executor.check_step_coverage(step, &mut step_result).await;
+                }
+            }
+            emit(ExecutionEvent::StepFinished {
+                execution_id,
+                result: Box::new(step_result.clone()),
+            })
+            .await;
+
+            match interrupt {
+                Some(Interrupt::Deadline) => {
+                    // Runtime limits apply even to steps that are allowed to fail
+                    limit_exceeded = Some(runtime_exceeded());
+                    pipeline_status = PipelineStatus::Failed;
+                    step_results.push(step_result);
+                    break;
+                }
+                Some(Interrupt::Cancelled) => {
+                    pipeline_status = PipelineStatus::Cancelled;
+                    step_results.push(step_result);
+                    break;
+                }
+                None => {}
+            }
+
+            if let Some(reason) = self.limits.check_artifact_size(artifact_bytes) {
+                // Don't keep a partial set of artifacts over the cap
+                if let Some(dir) = self.artifacts_path(execution_id) {
+                    let _ = tokio::fs::remove_dir_all(dir).await;
+                }
+                limit_exceeded = Some(reason);
                 pipeline_status = PipelineStatus::Failed;
                 step_results.push(step_result);
+                break;
+            }
+
+            if step_result.status == StepStatus::Failed && !step.allow_failure {
+                step_results.push(step_result);
+                if !pipeline.options.fail_fast {
+                    failed = true;
+                    warn!(
+                        execution_id = %execution_id,
+                        step_name = %step.name,
+                        "Step failed, continuing because fail_fast is false"
+                    );
+                    continue;
+                }
+                pipeline_status = PipelineStatus::Failed;
                 warn!(
                     execution_id = %execution_id,
                     step_name = %step.name,
@@ -92,10 +618,44 @@ impl PipelineExecutor {
             }
         }
 
-        // Determine final status
+        if pipeline_status == PipelineStatus::Cancelled {
+            info!(execution_id = %execution_id, "Pipeline cancelled");
+        }
+        // Steps after the one that stopped the pipeline are still listed
+        if !rejected {
+            for step in &pipeline.steps[step_results.len()..] {
+                let result = skipped_step(step);
+                emit(ExecutionEvent::StepFinished {
+                    execution_id,
+                    result: Box::new(result.clone()),
+                })
+                .await;
+                step_results.push(result);
+            }
+        }
+
+        // Tear down services, newest first, and keep their full output
+        for service in services.into_iter().rev() {
+            info!(execution_id = %execution_id, step_name = %service.step_name, "Stopping background step");
+            let index = service.result_index;
+            let service_name = service.step_name.clone();
+            let output = service.stop(Duration::from_secs(pipeline.options.kill_grace_secs)).await;
+            if let Some(step) = pipeline.steps.iter().find(|s| s.name == service_name) {
+                executor.cleanup_step(step, index).await;
+            }
+            if let Some(result) = step_results.get_mut(index) {
+                output.apply_to(result);
+                apply_ansi_mode(result, pipeline.options.ansi);
+            }
+        }
+        // Removed only once services, which may run from it, have stopped
+        if let Some(workspace) = workspace {
+            let _ = tokio::task::spawn_blocking(move || drop(workspace)).await;
+        }
+
+        // Determine final status; failures of allow_failure steps don't fail the pipeline
         if pipeline_status == PipelineStatus::Running {
-            let has_failures = step_results.iter().any(|r| r.status == StepStatus::Failed);
-            pipeline_status = if has_failures {
+            pipeline_status = if failed {
                 PipelineStatus::Failed
             } else {
                 PipelineStatus::Success
@@ -103,6 +663,11 @@ impl PipelineExecutor {
         }
 
         let completed_at = Utc::now();
+        phases.append(&mut executor.cache_phases.lock().unwrap());
+
+        if let Some(reason) = &limit_exceeded {
+            warn!(execution_id = %execution_id, reason = %reason, "Execution stopped by limits");
+        }
 
         info!(
             execution_id = %execution_id,
@@ -110,151 +675,728 @@ impl PipelineExecutor {
             status = ?pipeline_status,
             "Pipeline execution completed"
         );
+        emit(ExecutionEvent::PipelineFinished {
+            execution_id,
+            status: pipeline_status,
+        })
+        .await;
 
         Ok(PipelineExecution {
             id: execution_id,
             pipeline_name: pipeline.name.clone(),
             pipeline_version: pipeline.version.clone(),
+            pipeline_labels: pipeline.labels.clone(),
+            environment: pipeline.environment.clone(),
             repository: git_event.repository.clone(),
             git_event: git_event.clone(),
             status: pipeline_status,
+            coverage: CoverageReport::combine(step_results.iter().filter_map(|r| r.coverage.as_ref())),
+            metrics: ExecutionMetrics::new(
+                self.queued_at.map(|queued_at| elapsed_ms(queued_at, started_at)),
+                elapsed_ms(started_at, completed_at),
+                &step_results,
+            ),
             step_results,
+            phases,
+            limit_exceeded,
+            pulsefile_revision: None,
+            attempts: Vec::new(),
             started_at,
             completed_at: Some(completed_at),
         })
     }
 
-    async fn execute_step(&self, step: &Step) -> StepResult {
-        let started_at = Utc::now();
-        let start_instant = std::time::Instant::now();
-
-        info!(step_name = %step.name, "Executing step command");
-
-        // Execute the step's run command
-        // For simplicity, we'll execute commands in a shell
-        // In production, you'd want to handle different shells and environments
-        
-        let output = if cfg!(target_os = "windows") {
-            Command::new("cmd")
-                .arg("/C")
-                .arg(&step.run)
-                .current_dir(self.work_dir.as_ref().map(|p| p.as_path()).unwrap_or_else(|| std::path::Path::new(".")))
-                .output()
-        } else {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&step.run)
-                .current_dir(self.work_dir.as_ref().map(|p| p.as_path()).unwrap_or_else(|| std::path::Path::new(".")))
-                .output()
+    /// Copy a step's artifacts out of the working directory, noting missing
+    /// paths on the step's stderr. Returns the number of bytes kept.
+    async fn collect_step_artifacts(&self, execution_id: Uuid, step: &Step, result: &mut StepResult) -> u64 {
+        let Some(dest) = self.artifacts_path(execution_id).filter(|_| !step.artifacts.is_empty()) else {
+            return 0;
         };
+        let source = self.step_dir().to_path_buf();
+        let paths = step.artifacts.clone();
+        let collected = tokio::task::spawn_blocking(move || collect_artifacts(&source, &paths, &dest)).await;
 
-        let duration_ms = start_instant.elapsed().as_millis() as u64;
-        let completed_at = Utc::now();
-
-        match output {
-            Ok(output) => {
-                let status = if output.status.success() {
-                    StepStatus::Success
-                } else {
-                    StepStatus::Failed
-                };
-
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = output.status.code();
-
-                info!(
-                    step_name = %step.name,
-                    status = ?status,
-                    exit_code = ?exit_code,
-                    "Step execution completed"
-                );
-
-                StepResult {
-                    step_name: step.name.clone(),
-                    status,
-                    stdout,
-                    stderr,
-                    exit_code,
-                    duration_ms,
-                    started_at,
-                    completed_at: Some(completed_at),
+        match collected {
+            Ok(Ok(collected)) => {
+                for path in &collected.missing {
+                    result.stderr.push_str(&format!("\nArtifact not found: {}\n", path));
+                }
+                for path in &collected.outside {
+                    result.stderr.push_str(&format!("\nArtifact outside the working directory: {}\n", path));
                 }
+                collected.bytes
+            }
+            Ok(Err(e)) => {
+                warn!(step_name = %step.name, error = %e, "Failed to collect artifacts");
+                result.stderr.push_str(&format!("\nFailed to collect artifacts: {}\n", e));
+                0
             }
             Err(e) => {
-                error!(
-                    step_name = %step.name,
-                    error = %e,
-                    "Step execution failed"
-                );
+                warn!(step_name = %step.name, error = %e, "Artifact collection task failed");
+                0
+            }
+        }
+    }
 
-                StepResult {
-                    step_name: step.name.clone(),
-                    status: StepStatus::Failed,
-                    stdout: String::new(),
-                    stderr: format!("Failed to execute command: {}", e),
-                    exit_code: None,
-                    duration_ms,
-                    started_at,
-                    completed_at: Some(completed_at),
+    /// Read a step's coverage report into its result, failing the step when
+    /// coverage is below `min_coverage` or can't be read to check it
+    async fn check_step_coverage(&self, step: &Step, result: &mut StepResult) {
+        let Some(path) = &step.reports.coverage else {
+            return;
+        };
+        let coverage = match read_coverage(&self.step_dir().join(path)).await {
+            Ok(coverage) => coverage,
+            Err(e) => {
+                warn!(step_name = %step.name, path = %path, error = %e, "Failed to read coverage report");
+                result.stderr.push_str(&format!("\nFailed to read coverage report {}: {}\n", path, e));
+                if step.reports.min_coverage.is_some() {
+                    result.status = StepStatus::Failed;
                 }
+                return;
             }
+        };
+        info!(step_name = %step.name, coverage = %coverage, "Read coverage report");
+        result.coverage = Some(coverage);
+
+        if let Some(min) = step.reports.min_coverage.filter(|min| coverage.percent() < *min) {
+            result.stderr.push_str(&format!(
+                "\nCoverage {:.1}% is below the minimum of {}%\n",
+                coverage.percent(),
+                min
+            ));
+            result.status = StepStatus::Failed;
         }
     }
-}
 
-impl Default for PipelineExecutor {
-    fn default() -> Self {
-        Self::new()
+    /// Directory steps run in
+    fn step_dir(&self) -> &Path {
+        self.work_dir.as_deref().unwrap_or_else(|| Path::new("."))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use pulsiora_core::{GitEventType, Repository};
+    /// Script and environment a step runs with. The step's own `env` can
+    /// override built-in variables.
+    fn step_inputs(&self, step: &Step, options: &PipelineOptions) -> (String, BTreeMap<String, String>) {
+        let mut env = self.builtin_env.clone();
+        env.extend(self.env.clone());
+        env.extend(self.secrets.clone());
+        env.extend(step.env.clone());
+        (step_script(step, options), env)
+    }
 
-    fn create_test_repo() -> Repository {
-        Repository {
-            owner: "test".to_string(),
-            name: "repo".to_string(),
-            full_name: "test/repo".to_string(),
-            clone_url: "https://github.com/test/repo.git".to_string(),
-            default_branch: "main".to_string(),
+    fn step_context<'a>(
+        &'a self,
+        step: &'a Step,
+        step_index: usize,
+        options: &'a PipelineOptions,
+        (script, env): &'a (String, BTreeMap<String, String>),
+    ) -> StepContext<'a> {
+        StepContext {
+            execution_id: self.execution_id,
+            step_index,
+            step,
+            options,
+            script,
+            work_dir: self.step_dir(),
+            env,
+            output: OutputCapture {
+                measure_usage: self.backend_for(step).measures_usage(),
+                ..self.output_capture(step_index)
+            },
         }
     }
 
-    fn create_test_event() -> GitEvent {
-        GitEvent {
-            event_type: GitEventType::Push,
-            repository: create_test_repo(),
-            branch: Some("main".to_string()),
-            tag: None,
-            pull_request: None,
-            commit_sha: None,
-            sender: "test".to_string(),
+    /// Backend that runs a step: SSH when it names a remote runner
+    fn backend_for(&self, step: &Step) -> Arc<dyn ExecutionBackend> {
+        match step.runner {
+            Some(_) => self.ssh.clone(),
+            None => self.backend.clone(),
         }
     }
 
-    #[tokio::test]
-    async fn test_executor_skips_non_matching_trigger() {
-        let executor = PipelineExecutor::new();
-        
-        let pulsefile = r#"
-pipeline {
-  name: "test";
-  triggers {
-    git {
-      on_push: false;
+    /// Build the process that runs a background step
+    fn step_command(&self, step: &Step, step_index: usize, options: &PipelineOptions) -> std::io::Result<Command> {
+        let inputs = self.step_inputs(step, options);
+        self.backend_for(step).command(&self.step_context(step, step_index, options, &inputs))
     }
-  }
-  steps {
-    step "test" {
-      run: """echo "test"""";
+
+    /// Let the backend release what a killed or stopped step left behind
+    async fn cleanup_step(&self, step: &Step, step_index: usize) {
+        let backend = self.backend_for(step);
+        let execution_id = self.execution_id;
+        let _ = tokio::task::spawn_blocking(move || backend.cleanup(execution_id, step_index)).await;
     }
-  }
-}
-"#;
+
+    /// Run a step, or start it if it's a background step, with the pipeline's
+    /// hooks around it. A failing `before_each` fails the step without
+    /// running it and a failing `after_each` fails a step that succeeded.
+    /// Nothing more runs once the step is interrupted.
+    async fn run_step(
+        &self,
+        step: &Step,
+        step_index: usize,
+        options: &PipelineOptions,
+        deadline: Option<Instant>,
+        cancel: &CancellationToken,
+        sink: Option<OutputSink>,
+    ) -> (StepResult, Option<Interrupt>, Option<BackgroundService>) {
+        let action_step;
+        let step = match self.resolve_action(step).await {
+            Ok(Some(resolved)) => {
+                action_step = resolved;
+                &action_step
+            }
+            Ok(None) => step,
+            Err(e) => {
+                warn!(step_name = %step.name, error = %e, "Failed to prepare action");
+                let mut result = skipped_step(step);
+                result.status = StepStatus::Failed;
+                result.stderr = format!("Step not run: {}\n", e);
+                result.completed_at = Some(Utc::now());
+                return (result, None, None);
+            }
+        };
+        let mut hooks = Vec::new();
+        if options.before_each.is_some() {
+            let started_at = Utc::now();
+            let (hook, interrupt) = self.run_hook(step, step_index, options, None, deadline, cancel).await;
+            if hook.status != StepStatus::Success {
+                warn!(step_name = %step.name, "before_each hook failed, not running step");
+                let mut result = skipped_step(step);
+                result.status = if hook.status == StepStatus::Cancelled {
+                    StepStatus::Cancelled
+                } else {
+                    StepStatus::Failed
+                };
+                result.stderr = "Step not run: before_each hook failed\n".to_string();
+                result.duration_ms = hook.duration_ms;
+                result.started_at = started_at;
+                result.completed_at = Some(Utc::now());
+                result.hooks.push(hook);
+                return (result, interrupt, None);
+            }
+            hooks.push(hook);
+        }
+
+        let (mut result, mut interrupt, service) = if step.background {
+            self.start_service(step, options, deadline, cancel, step_index, sink).await
+        } else {
+            let (result, interrupt) = self
+                .execute_step(step, step_index, options, deadline, cancel, sink)
+                .await;
+            (result, interrupt, None)
+        };
+
+        if options.after_each.is_some() && interrupt.is_none() {
+            let (hook, hook_interrupt) = self
+                .run_hook(step, step_index, options, Some(&result), deadline, cancel)
+                .await;
+            if hook.status != StepStatus::Success && result.status == StepStatus::Success {
+                warn!(step_name = %step.name, "after_each hook failed, failing step");
+                result.status = hook.status;
+                result.stderr.push_str("\nStep failed: after_each hook failed\n");
+            }
+            interrupt = hook_interrupt;
+            hooks.push(hook);
+        }
+        result.hooks = hooks;
+        (result, interrupt, service)
+    }
+
+    /// For a `uses` step, fetch its action and copy it into the step's
+    /// directory; the step returned runs the action's entrypoint
+    async fn resolve_action(&self, step: &Step) -> Result<Option<Step>, PulsioraError> {
+        let Some(uses) = &step.uses else { return Ok(None) };
+        let timer = PhaseTimer::start(PhaseKind::CacheRestore);
+        let fetched = self.actions.fetch(&ActionRef::parse(uses)?).await?;
+        let phase = if fetched.cached { PhaseKind::CacheRestore } else { PhaseKind::CacheSave };
+        let bytes = dir_size(&fetched.dir).ok();
+        self.cache_phases.lock().unwrap().push(timer.finish_as(phase, bytes));
+        let bundle = fetched.dir;
+        let step = step.clone();
+        let work_dir = self.step_dir().to_path_buf();
+        tokio::task::spawn_blocking(move || prepare_action(&step, &bundle, &work_dir).map(Some))
+            .await
+            .map_err(|e| PulsioraError::ExecutionError(format!("Action task failed: {}", e)))?
+    }
+
+    /// Run the `before_each` hook for a step, or its `after_each` hook once
+    /// it has `finished`. Hooks run through the step's backend, in its
+    /// directory, with its environment plus `PULSE_STEP_NAME`.
+    async fn run_hook(
+        &self,
+        step: &Step,
+        step_index: usize,
+        options: &PipelineOptions,
+        finished: Option<&StepResult>,
+        deadline: Option<Instant>,
+        cancel: &CancellationToken,
+    ) -> (HookResult, Option<Interrupt>) {
+        let start_instant = Instant::now();
+        let (hook, script) = match finished {
+            None => (StepHook::BeforeEach, &options.before_each),
+            Some(_) => (StepHook::AfterEach, &options.after_each),
+        };
+        let hook_step = Step {
+            run: script.clone().unwrap_or_default(),
+            ..step.clone()
+        };
+        let (script, mut env) = self.step_inputs(&hook_step, options);
+        env.insert("PULSE_STEP_NAME".to_string(), step.name.clone());
+        if let Some(result) = finished {
+            env.insert("PULSE_STEP_STATUS".to_string(), step_status_name(result.status).to_string());
+            env.insert("PULSE_STEP_DURATION_MS".to_string(), result.duration_ms.to_string());
+        }
+        let inputs = (script, env);
+        let mut ctx = self.step_context(&hook_step, step_index, options, &inputs);
+        // Hooks keep their own output rather than writing to the step's log
+        ctx.output.log_prefix = None;
+
+        info!(step_name = %step.name, hook = hook.as_str(), "Running hook");
+        let output = self.backend_for(step).run(&ctx, deadline, None, cancel).await;
+        if matches!(output, Ok((_, Some(_)))) {
+            self.cleanup_step(step, step_index).await;
+        }
+
+        let (status, stdout, stderr, exit_code, interrupt) = match output {
+            Ok((output, interrupt)) => {
+                let status = match interrupt {
+                    None if output.success => StepStatus::Success,
+                    Some(Interrupt::Cancelled) => StepStatus::Cancelled,
+                    _ => StepStatus::Failed,
+                };
+                let decoded = DecodedOutput::decode(&output.stdout, &output.stderr, output_encoding(step));
+                (status, decoded.stdout, decoded.stderr, output.exit_code, interrupt)
+            }
+            Err(e) => {
+                error!(step_name = %step.name, hook = hook.as_str(), error = %e, "Hook failed to run");
+                let stderr = format!("Failed to execute command: {}", e);
+                (StepStatus::Failed, String::new(), stderr, None, None)
+            }
+        };
+        let result = HookResult {
+            hook,
+            status,
+            stdout,
+            stderr,
+            exit_code,
+            duration_ms: start_instant.elapsed().as_millis() as u64,
+        };
+        (result, interrupt)
+    }
+
+    /// Start a background step and wait until it is ready. The step succeeds
+    /// once ready and the service is returned to be stopped at teardown; also
+    /// returns what interrupted the wait, if anything.
+    async fn start_service(
+        &self,
+        step: &Step,
+        options: &PipelineOptions,
+        deadline: Option<Instant>,
+        cancel: &CancellationToken,
+        result_index: usize,
+        sink: Option<OutputSink>,
+    ) -> (StepResult, Option<Interrupt>, Option<BackgroundService>) {
+        let started_at = Utc::now();
+        let start_instant = Instant::now();
+
+        info!(step_name = %step.name, "Starting background step");
+
+        let spawned = self.step_command(step, result_index, options).and_then(|command| {
+            BackgroundService::spawn(
+                &step.name,
+                command,
+                result_index,
+                output_encoding(step),
+                &self.output_capture(result_index),
+                sink,
+            )
+        });
+        let mut service = match spawned {
+            Ok(service) => service,
+            Err(e) => {
+                error!(step_name = %step.name, error = %e, "Background step failed to start");
+                let result = StepResult {
+                    step_name: step.name.clone(),
+                    status: StepStatus::Failed,
+                    stdout: String::new(),
+                    stderr: format!("Failed to execute command: {}", e),
+                    exit_code: None,
+                    duration_ms: start_instant.elapsed().as_millis() as u64,
+                    started_at,
+                    completed_at: Some(Utc::now()),
+                    encoding: None,
+                    replacement_chars: 0,
+                    stdout_bytes: 0,
+                    stderr_bytes: 0,
+                    log_ref: None,
+                    hooks: vec![],
+                    annotations: vec![],
+                    coverage: None,
+                    resources: None,
+                };
+                return (result, None, None);
+            }
+        };
+
+        let ready = tokio::select! {
+            ready = service.wait_ready(step.ready_when.as_ref(), deadline) => ready,
+            _ = cancel.cancelled() => Err("Step cancelled".to_string()),
+        };
+        let interrupt = match &ready {
+            Ok(()) => None,
+            Err(_) if cancel.is_cancelled() => Some(Interrupt::Cancelled),
+            Err(_) if deadline.is_some_and(|d| Instant::now() >= d) => Some(Interrupt::Deadline),
+            Err(_) => None,
+        };
+        let (status, service, mut output) = match ready {
+            Ok(()) => {
+                info!(step_name = %step.name, "Background step is ready");
+                let output = service.output();
+                (StepStatus::Success, Some(service), output)
+            }
+            Err(reason) => {
+                warn!(step_name = %step.name, reason = %reason, "Background step did not become ready");
+                let mut output = service.stop(Duration::from_secs(options.kill_grace_secs)).await;
+                self.cleanup_step(step, result_index).await;
+                output.stderr.push_str(&format!("\n{}\n", reason));
+                let status = if interrupt == Some(Interrupt::Cancelled) {
+                    StepStatus::Cancelled
+                } else {
+                    StepStatus::Failed
+                };
+                (status, None, output)
+            }
+        };
+
+        let result = StepResult {
+            step_name: step.name.clone(),
+            status,
+            stdout: std::mem::take(&mut output.stdout),
+            stderr: std::mem::take(&mut output.stderr),
+            exit_code: None,
+            duration_ms: start_instant.elapsed().as_millis() as u64,
+            started_at,
+            completed_at: Some(Utc::now()),
+            encoding: Some(output.encoding.name().to_string()),
+            replacement_chars: output.replacement_chars,
+            stdout_bytes: output.stdout_bytes,
+            stderr_bytes: output.stderr_bytes,
+            log_ref: self.step_log_ref(result_index),
+            hooks: vec![],
+            annotations: vec![],
+            coverage: None,
+            resources: None,
+        };
+        (result, interrupt, service)
+    }
+
+    /// Annotations in a step's output. The full logs are read when output is
+    /// logged, since the kept output may be only its end; streams without a
+    /// readable log use the kept output.
+    async fn step_annotations(&self, step: &Step, step_index: usize, stdout: &str, stderr: &str) -> Vec<Annotation> {
+        let mut annotations = Vec::new();
+        let encoding = output_encoding(step);
+        let log_prefix = self
+            .output_capture(step_index)
+            .log_prefix
+            .filter(|_| encoding.is_ascii_compatible());
+        for (stream, kept) in [(OutputStream::Stdout, stdout), (OutputStream::Stderr, stderr)] {
+            let Some(prefix) = &log_prefix else {
+                self.matchers.annotate(kept, &mut annotations);
+                continue;
+            };
+            let path = step_log_path(prefix, stream);
+            let before = annotations.len();
+            if let Err(e) = self.matchers.annotate_file(&path, encoding, &mut annotations).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!(step_name = %step.name, path = %path.display(), error = %e, "Failed to read step log for annotations");
+                }
+                annotations.truncate(before);
+                self.matchers.annotate(kept, &mut annotations);
+            }
+        }
+        annotations
+    }
+
+    /// Run a step, killing it at `deadline`, once its own timeout is up or
+    /// when `cancel` fires; also returns what interrupted the execution, if
+    /// anything. A step that times out just fails.
+    async fn execute_step(
+        &self,
+        step: &Step,
+        step_index: usize,
+        options: &PipelineOptions,
+        deadline: Option<Instant>,
+        cancel: &CancellationToken,
+        sink: Option<OutputSink>,
+    ) -> (StepResult, Option<Interrupt>) {
+        let started_at = Utc::now();
+        let start_instant = std::time::Instant::now();
+
+        info!(step_name = %step.name, "Executing step command");
+
+        let inputs = self.step_inputs(step, options);
+        let ctx = self.step_context(step, step_index, options, &inputs);
+        let step_deadline = step
+            .timeout_secs
+            .map(|secs| Instant::now() + Duration::from_secs(secs))
+            .filter(|timeout| deadline.is_none_or(|d| *timeout < d));
+        let output = self
+            .backend_for(step)
+            .run(&ctx, step_deadline.or(deadline), sink, cancel)
+            .await;
+        if matches!(output, Ok((_, Some(_)))) {
+            self.cleanup_step(step, step_index).await;
+        }
+
+        let duration_ms = start_instant.elapsed().as_millis() as u64;
+        let completed_at = Utc::now();
+
+        match output {
+            Ok((output, mut interrupt)) => {
+                let timed_out = step_deadline.is_some() && interrupt == Some(Interrupt::Deadline);
+                if timed_out {
+                    interrupt = None;
+                }
+                let status = match interrupt {
+                    None if output.success => StepStatus::Success,
+                    Some(Interrupt::Cancelled) => StepStatus::Cancelled,
+                    _ => StepStatus::Failed,
+                };
+
+                let encoding = output_encoding(step);
+                let DecodedOutput {
+                    stdout,
+                    mut stderr,
+                    replacement_chars,
+                    ..
+                } = DecodedOutput::decode(&output.stdout, &output.stderr, encoding);
+                match interrupt {
+                    Some(Interrupt::Deadline) => {
+                        stderr.push_str("\nStep terminated: execution exceeded its maximum runtime\n")
+                    }
+                    Some(Interrupt::Cancelled) => stderr.push_str("\nStep terminated: execution was cancelled\n"),
+                    None if timed_out => stderr.push_str(&format!(
+                        "\nStep terminated: exceeded its timeout of {}s\n",
+                        step.timeout_secs.unwrap_or_default()
+                    )),
+                    None => {}
+                }
+                let exit_code = output.exit_code;
+                let annotations = self.step_annotations(step, step_index, &stdout, &stderr).await;
+
+                info!(
+                    step_name = %step.name,
+                    status = ?status,
+                    exit_code = ?exit_code,
+                    "Step execution completed"
+                );
+
+                let result = StepResult {
+                    step_name: step.name.clone(),
+                    status,
+                    stdout,
+                    stderr,
+                    exit_code,
+                    duration_ms,
+                    started_at,
+                    completed_at: Some(completed_at),
+                    encoding: Some(encoding.name().to_string()),
+                    replacement_chars,
+                    stdout_bytes: output.stdout_bytes,
+                    stderr_bytes: output.stderr_bytes,
+                    log_ref: self.step_log_ref(step_index),
+                    hooks: vec![],
+                    annotations,
+                    coverage: None,
+                    resources: output.usage,
+                };
+                if replacement_chars > 0 {
+                    warn!(
+                        step_name = %step.name,
+                        encoding = encoding.name(),
+                        replacement_chars,
+                        "Step output had bytes that aren't valid in its encoding"
+                    );
+                }
+                (result, interrupt)
+            }
+            Err(e) => {
+                error!(
+                    step_name = %step.name,
+                    error = %e,
+                    "Step execution failed"
+                );
+
+                let result = StepResult {
+                    step_name: step.name.clone(),
+                    status: StepStatus::Failed,
+                    stdout: String::new(),
+                    stderr: format!("Failed to execute command: {}", e),
+                    exit_code: None,
+                    duration_ms,
+                    started_at,
+                    completed_at: Some(completed_at),
+                    encoding: None,
+                    replacement_chars: 0,
+                    stdout_bytes: 0,
+                    stderr_bytes: 0,
+                    log_ref: None,
+                    hooks: vec![],
+                    annotations: vec![],
+                    coverage: None,
+                    resources: None,
+                };
+                (result, None)
+            }
+        }
+    }
+}
+
+/// Milliseconds from `from` to `to`, zero if `to` is earlier
+fn elapsed_ms(from: DateTime<Utc>, to: DateTime<Utc>) -> u64 {
+    (to - from).num_milliseconds().max(0) as u64
+}
+
+/// Number of leading `completed` results a resumed execution of `pipeline`
+/// keeps: results of steps that finished without stopping the pipeline, up to
+/// the first background step, whose service has to be started again for the
+/// steps after it. Results stop counting where step names no longer match.
+pub fn resume_point(pipeline: &Pipeline, completed: &[StepResult]) -> usize {
+    completed
+        .iter()
+        .zip(&pipeline.steps)
+        .take_while(|(result, step)| {
+            let stopped = result.status == StepStatus::Failed && !step.allow_failure && pipeline.options.fail_fast;
+            result.step_name == step.name
+                && !step.background
+                && matches!(result.status, StepStatus::Success | StepStatus::Failed)
+                && !stopped
+        })
+        .count()
+}
+
+/// Variables describing the execution, set for every step
+fn builtin_env(
+    execution_id: Uuid,
+    pipeline: &Pipeline,
+    git_event: &GitEvent,
+    work_dir: &Path,
+) -> BTreeMap<String, String> {
+    let workspace = std::path::absolute(work_dir).unwrap_or_else(|_| work_dir.to_path_buf());
+    // Pull request events name the PR's branch; merges the branch they landed on
+    let branch = git_event
+        .branch
+        .clone()
+        .or_else(|| git_event.pull_request.as_ref().map(|pr| pr.head_branch.clone()));
+    let inputs = git_event
+        .inputs
+        .iter()
+        .map(|(name, value)| (format!("PULSE_INPUT_{}", name.to_uppercase()), value.clone()));
+    BTreeMap::from([
+        ("PULSE_EXECUTION_ID".to_string(), execution_id.to_string()),
+        ("PULSE_PIPELINE_NAME".to_string(), pipeline.name.clone()),
+        ("PULSE_BRANCH".to_string(), branch.unwrap_or_default()),
+        ("PULSE_COMMIT_SHA".to_string(), git_event.commit_sha.clone().unwrap_or_default()),
+        ("PULSE_EVENT".to_string(), git_event.event_type.as_str().to_string()),
+        ("PULSE_REPO".to_string(), git_event.repository.full_name.clone()),
+        ("PULSE_WORKSPACE".to_string(), workspace.to_string_lossy().into_owned()),
+    ])
+    .into_iter()
+    .chain(inputs)
+    .collect()
+}
+
+/// Lower-case name of a step status, as passed to `after_each` hooks
+fn step_status_name(status: StepStatus) -> &'static str {
+    match status {
+        StepStatus::Pending => "pending",
+        StepStatus::Running => "running",
+        StepStatus::Success => "success",
+        StepStatus::Failed => "failed",
+        StepStatus::Cancelled => "cancelled",
+        StepStatus::Skipped => "skipped",
+    }
+}
+
+/// Result recorded for a step that never ran
+fn skipped_step(step: &Step) -> StepResult {
+    StepResult {
+        step_name: step.name.clone(),
+        status: StepStatus::Skipped,
+        stdout: String::new(),
+        stderr: String::new(),
+        exit_code: None,
+        duration_ms: 0,
+        started_at: Utc::now(),
+        completed_at: None,
+        encoding: None,
+        replacement_chars: 0,
+        stdout_bytes: 0,
+        stderr_bytes: 0,
+        log_ref: None,
+        hooks: vec![],
+        annotations: vec![],
+        coverage: None,
+        resources: None,
+    }
+}
+
+impl Default for PipelineExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::{AnsiMode, GitEventType, Repository, StepReports};
+
+    fn create_test_repo() -> Repository {
+        Repository {
+            owner: "test".to_string(),
+            name: "repo".to_string(),
+            full_name: "test/repo".to_string(),
+            clone_url: "https://github.com/test/repo.git".to_string(),
+            default_branch: "main".to_string(),
+        }
+    }
+
+    fn create_test_event() -> GitEvent {
+        GitEvent {
+            event_type: GitEventType::Push,
+            repository: create_test_repo(),
+            branch: Some("main".to_string()),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: None,
+            sender: "test".to_string(),
+            inputs: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_executor_skips_non_matching_trigger() {
+        let executor = PipelineExecutor::new();
+        
+        let pulsefile = r#"
+pipeline {
+  name: "test";
+  triggers {
+    git {
+      on_push: false;
+    }
+  }
+  steps {
+    step "test" {
+      run: """echo "test"""";
+    }
+  }
+}
+"#;
         
         let execution = executor
             .execute_from_pulsefile(pulsefile, &create_test_event())
@@ -326,7 +1468,10 @@ pipeline {
             .unwrap();
         
         assert_eq!(execution.status, PipelineStatus::Failed);
-        assert_eq!(execution.step_results.len(), 1);
+        assert_eq!(execution.step_results.len(), 2);
+        assert_eq!(execution.step_results[1].step_name, "should_not_run");
+        assert_eq!(execution.step_results[1].status, StepStatus::Skipped);
+        assert_eq!(execution.step_results[1].completed_at, None);
     }
 
     #[tokio::test]
@@ -401,4 +1546,1020 @@ pipeline {
         assert_eq!(execution.step_results[1].step_name, "step2");
         assert_eq!(execution.step_results[2].step_name, "step3");
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_applies_umask() {
+        let executor = PipelineExecutor::new();
+
+        let pulsefile = r#"
+pipeline {
+  name: "test";
+  triggers {
+    git {
+      on_push: true;
+    }
+  }
+  steps {
+    step "umask" {
+      umask: "0077";
+      run: """umask""";
+    }
+  }
+}
+"#;
+
+        let execution = executor
+            .execute_from_pulsefile(pulsefile, &create_test_event())
+            .await
+            .unwrap();
+
+        assert_eq!(execution.status, PipelineStatus::Success);
+        assert_eq!(execution.step_results[0].stdout.trim(), "0077");
+    }
+
+    #[tokio::test]
+    async fn test_executor_sets_step_env() {
+        let executor = PipelineExecutor::new();
+
+        let pulsefile = r#"
+pipeline {
+  name: "test";
+  vars {
+    GREETING: "hello";
+  }
+  triggers {
+    git {
+      on_push: true;
+    }
+  }
+  steps {
+    step "env" {
+      env {
+        MESSAGE: "${vars.GREETING} world";
+      }
+      run: """echo $MESSAGE""";
+    }
+  }
+}
+"#;
+
+        let execution = executor
+            .execute_from_pulsefile(pulsefile, &create_test_event())
+            .await
+            .unwrap();
+
+        assert_eq!(execution.status, PipelineStatus::Success);
+        assert_eq!(execution.step_results[0].stdout.trim(), "hello world");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_fails_step_with_unknown_user() {
+        let executor = PipelineExecutor::new();
+        let pipeline = Pipeline {
+            name: "test".to_string(),
+            version: "1.0".to_string(),
+            description: None,
+            labels: vec![],
+            owners: vec![],
+            environment: None,
+            triggers: pulsiora_core::GitTriggers {
+                on_push: true,
+                ..Default::default()
+            }
+            .into(),
+            steps: vec![Step::new("deploy".to_string(), "true".to_string())
+                .with_user("pulsiora-no-such-user")],
+            vars: Default::default(),
+            options: Default::default(),
+        };
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert!(execution.step_results[0].stderr.contains("unknown user"));
+    }
+
+    fn push_pipeline(steps: Vec<Step>) -> Pipeline {
+        Pipeline {
+            name: "test".to_string(),
+            version: "1.0".to_string(),
+            description: None,
+            labels: vec![],
+            owners: vec![],
+            environment: None,
+            triggers: pulsiora_core::GitTriggers {
+                on_push: true,
+                ..Default::default()
+            }
+            .into(),
+            steps,
+            vars: Default::default(),
+            options: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_executor_streams_events() {
+        use pulsiora_core::{ExecutionEvent, OutputStream};
+
+        let executor = PipelineExecutor::new();
+        let pipeline = push_pipeline(vec![
+            Step::new("greet".to_string(), "echo hello; echo oops >&2".to_string()),
+            Step::new("fail".to_string(), "exit 3".to_string()),
+        ]);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let id = Uuid::new_v4();
+
+        let execution = executor
+            .execute_with_events(id, &pipeline, &create_test_event(), tx)
+            .await
+            .unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+
+        assert!(events.iter().all(|e| e.execution_id() == id));
+        assert!(matches!(&events[0], ExecutionEvent::StepStarted { step_name, .. } if step_name == "greet"));
+        let stdout: String = events
+            .iter()
+            .filter_map(|e| match e {
+                ExecutionEvent::OutputChunk { step_name, stream: OutputStream::Stdout, data, .. }
+                    if step_name == "greet" => Some(data.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stdout, "hello\n");
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ExecutionEvent::OutputChunk { stream: OutputStream::Stderr, data, .. } if data == "oops\n"
+        )));
+        let finished: Vec<&StepResult> = events
+            .iter()
+            .filter_map(|e| match e {
+                ExecutionEvent::StepFinished { result, .. } => Some(result.as_ref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(finished.len(), 2);
+        assert_eq!(finished[1].exit_code, Some(3));
+        assert!(matches!(
+            events.last(),
+            Some(ExecutionEvent::PipelineFinished { status: PipelineStatus::Failed, .. })
+        ));
+        assert_eq!(execution.status, PipelineStatus::Failed);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_cancel_kills_process_group() {
+        let root = std::env::temp_dir().join(format!("pulsiora-cancel-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let executor = PipelineExecutor::new().with_work_dir(&root);
+        let pipeline = push_pipeline(vec![
+            Step::new("setup".to_string(), "true".to_string()),
+            Step::new("hang".to_string(), "sleep 30 & echo $! > child.pid; wait".to_string()),
+            Step::new("deploy".to_string(), "true".to_string()),
+        ]);
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        let pid_file = root.join("child.pid");
+        tokio::spawn(async move {
+            while !pid_file.exists() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            trigger.cancel();
+        });
+
+        let started = Instant::now();
+        let execution = executor
+            .execute_with_cancel(Uuid::new_v4(), &pipeline, &create_test_event(), cancel)
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(execution.status, PipelineStatus::Cancelled);
+        let statuses: Vec<StepStatus> = execution.step_results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![StepStatus::Success, StepStatus::Cancelled, StepStatus::Skipped]
+        );
+
+        // The step's background `sleep` went down with its process group
+        let pid: i32 = std::fs::read_to_string(root.join("child.pid")).unwrap().trim().parse().unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Killed orphans can linger as zombies when nothing reaps them
+        let alive = match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) if Path::new("/proc/self").exists() => false,
+            Err(_) => unsafe { libc::kill(pid, 0) == 0 },
+        };
+        assert!(!alive, "process {} outlived the cancelled step", pid);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_executor_rejects_too_many_steps() {
+        let executor = PipelineExecutor::new().with_limits(ExecutionLimits {
+            max_steps: Some(1),
+            ..Default::default()
+        });
+        let pipeline = push_pipeline(vec![
+            Step::new("one".to_string(), "true".to_string()),
+            Step::new("two".to_string(), "true".to_string()),
+        ]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert!(execution.step_results.is_empty());
+        assert!(execution.limit_exceeded.unwrap().contains("limit of 1"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_kills_steps_past_max_runtime() {
+        let executor = PipelineExecutor::new().with_limits(ExecutionLimits {
+            max_runtime_secs: Some(1),
+            ..Default::default()
+        });
+        let pipeline = push_pipeline(vec![
+            Step::new("slow".to_string(), "echo started; sleep 10".to_string()).with_allow_failure(true),
+            Step::new("after".to_string(), "true".to_string()),
+        ]);
+
+        let started = Instant::now();
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert_eq!(execution.step_results.len(), 2);
+        assert_eq!(execution.step_results[0].status, StepStatus::Failed);
+        assert_eq!(execution.step_results[0].stdout.trim(), "started");
+        assert_eq!(execution.step_results[1].status, StepStatus::Skipped);
+        assert!(execution.limit_exceeded.unwrap().contains("maximum runtime of 1s"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_kills_steps_past_their_timeout() {
+        let executor = PipelineExecutor::new();
+        let pipeline = push_pipeline(vec![
+            Step::new("slow".to_string(), "echo started; sleep 10".to_string())
+                .with_timeout_secs(1)
+                .with_allow_failure(true),
+            Step::new("after".to_string(), "true".to_string()).with_timeout_secs(60),
+        ]);
+
+        let started = Instant::now();
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // Unlike the runtime limit, a step timeout only fails the step
+        assert_eq!(execution.status, PipelineStatus::Success);
+        assert!(execution.limit_exceeded.is_none());
+        let slow = &execution.step_results[0];
+        assert_eq!(slow.status, StepStatus::Failed);
+        assert_eq!(slow.stdout.trim(), "started");
+        assert!(slow.stderr.contains("exceeded its timeout of 1s"));
+        assert_eq!(execution.step_results[1].status, StepStatus::Success);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_truncates_step_output() {
+        let executor = PipelineExecutor::new().with_limits(ExecutionLimits {
+            max_output_bytes: Some(1000),
+            ..Default::default()
+        });
+        let pipeline = push_pipeline(vec![Step::new(
+            "chatty".to_string(),
+            "echo first; seq 1 100000; echo last; echo small >&2".to_string(),
+        )]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let result = &execution.step_results[0];
+        assert_eq!(result.status, StepStatus::Success);
+        assert!(result.stdout.starts_with("first\n1\n2\n"));
+        assert!(result.stdout.ends_with("100000\nlast\n"));
+        assert!(result.stdout.contains("bytes of output omitted"));
+        assert!(result.stdout.len() < 1100);
+        assert_eq!(result.stdout_bytes, 6 + 588895 + 5);
+        assert_eq!(result.stderr, "small\n");
+        assert_eq!(result.stderr_bytes, 6);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_spills_step_output_to_log_files() {
+        let log_dir = std::env::temp_dir().join(format!("pulsiora-logs-{}", Uuid::new_v4()));
+        let executor = PipelineExecutor::new().with_log_dir(&log_dir);
+        let pipeline = push_pipeline(vec![Step::new(
+            "chatty".to_string(),
+            "seq 1 100000; echo oops >&2".to_string(),
+        )]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let result = &execution.step_results[0];
+        let log_ref = result.log_ref.as_deref().unwrap();
+        assert_eq!(log_ref, format!("{}/0", execution.id));
+        assert!(result.stdout.len() <= crate::LOG_TAIL_BYTES as usize + 100);
+        assert!(result.stdout.ends_with("99999\n100000\n"));
+        assert_eq!(result.stdout_bytes, 588895);
+
+        let stdout = executor.step_log_path(log_ref, OutputStream::Stdout).unwrap();
+        assert_eq!(stdout, log_dir.join(execution.id.to_string()).join("0.stdout.log"));
+        let full = std::fs::read_to_string(&stdout).unwrap();
+        assert_eq!(full.len(), 588895);
+        assert!(full.starts_with("1\n2\n"));
+        let stderr = executor.step_log_path(log_ref, OutputStream::Stderr).unwrap();
+        assert_eq!(std::fs::read_to_string(stderr).unwrap(), "oops\n");
+        assert_eq!(executor.step_log_path("../../etc/0", OutputStream::Stdout), None);
+        let _ = std::fs::remove_dir_all(&log_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_set_e_stops_at_first_failing_command() {
+        let executor = PipelineExecutor::new();
+        let mut pipeline = push_pipeline(vec![Step::new(
+            "build".to_string(),
+            "false\necho after".to_string(),
+        )]);
+
+        // Without strict mode only the last command's exit code counts
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Success);
+
+        pipeline.options.set_e = true;
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert!(!execution.step_results[0].stdout.contains("after"));
+    }
+
+    #[tokio::test]
+    async fn test_executor_runs_remaining_steps_without_fail_fast() {
+        let executor = PipelineExecutor::new();
+        let mut pipeline = push_pipeline(vec![
+            Step::new("test".to_string(), "exit 1".to_string()),
+            Step::new("report".to_string(), "echo reported".to_string()),
+        ]);
+        pipeline.options.fail_fast = false;
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert_eq!(execution.step_results.len(), 2);
+        assert_eq!(execution.step_results[1].status, StepStatus::Success);
+        assert_eq!(execution.step_results[1].stdout.trim(), "reported");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_does_not_block_runtime() {
+        let executor = PipelineExecutor::new();
+        let pipeline = push_pipeline(vec![Step::new("slow".to_string(), "sleep 1".to_string())]);
+
+        // The test runtime has a single thread, so a blocking step would hold
+        // back the timer until the step finished
+        let event = create_test_event();
+        let started = Instant::now();
+        let (execution, ticked_after) = tokio::join!(
+            executor.execute(&pipeline, &event),
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                started.elapsed()
+            }
+        );
+
+        assert_eq!(execution.unwrap().status, PipelineStatus::Success);
+        assert!(ticked_after < Duration::from_millis(500), "{:?}", ticked_after);
+    }
+
+    /// Serve `200 OK` to every request on a local port
+    async fn spawn_health_server() -> String {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let _ = socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    .await;
+            }
+        });
+        url
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_keeps_background_step_running() {
+        let executor = PipelineExecutor::new();
+        let url = spawn_health_server().await;
+        let pipeline = push_pipeline(vec![
+            Step::new("service".to_string(), "echo up; sleep 30".to_string()).with_background(Some(
+                pulsiora_core::ReadinessCheck {
+                    http: Some(url),
+                    timeout_secs: 5,
+                },
+            )),
+            Step::new("test".to_string(), "echo testing".to_string()),
+        ]);
+
+        let started = Instant::now();
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+
+        // The service is stopped at teardown instead of running for 30s
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(execution.status, PipelineStatus::Success);
+        assert_eq!(execution.step_results[0].status, StepStatus::Success);
+        assert_eq!(execution.step_results[0].stdout.trim(), "up");
+        assert_eq!(execution.step_results[1].stdout.trim(), "testing");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_fails_background_step_that_never_gets_ready() {
+        let executor = PipelineExecutor::new();
+        // Bind and release a port so nothing is listening on it
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let check = pulsiora_core::ReadinessCheck {
+            http: Some(format!("http://{}/", addr)),
+            timeout_secs: 1,
+        };
+        let pipeline = push_pipeline(vec![
+            Step::new("service".to_string(), "sleep 30".to_string()).with_background(Some(check.clone())),
+            Step::new("test".to_string(), "true".to_string()),
+        ]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert_eq!(execution.step_results.len(), 2);
+        assert!(execution.step_results[0].stderr.contains("did not become ready"));
+        assert_eq!(execution.step_results[1].status, StepStatus::Skipped);
+
+        let pipeline = push_pipeline(vec![
+            Step::new("service".to_string(), "exit 3".to_string()).with_background(Some(check)),
+        ]);
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert!(execution.step_results[0].stderr.contains("exited with exit status: 3"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_collects_artifacts_within_limit() {
+        let root = std::env::temp_dir().join(format!("pulsiora-executor-artifacts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("work")).unwrap();
+        let executor = PipelineExecutor::new()
+            .with_work_dir(root.join("work"))
+            .with_artifact_dir(root.join("artifacts"));
+        let pipeline = push_pipeline(vec![Step::new(
+            "report".to_string(),
+            "mkdir -p out && printf 12345 > out/report.txt".to_string(),
+        )
+        .with_artifacts(vec!["out".to_string(), "absent.xml".to_string()])]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Success);
+        assert!(execution.step_results[0].stderr.contains("Artifact not found: absent.xml"));
+        let kept = executor.artifacts_path(execution.id).unwrap().join("out/report.txt");
+        assert_eq!(std::fs::read_to_string(kept).unwrap(), "12345");
+
+        let executor = executor.with_limits(ExecutionLimits {
+            max_artifact_bytes: Some(4),
+            ..Default::default()
+        });
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert!(execution.limit_exceeded.unwrap().contains("exceeding the limit of 4 bytes"));
+        assert!(!executor.artifacts_path(execution.id).unwrap().exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_decodes_step_output_encoding() {
+        let executor = PipelineExecutor::new();
+        let pipeline = push_pipeline(vec![
+            // "ビルド" in Shift_JIS
+            Step::new("sjis".to_string(), r"printf '\203r\203\213\203h'".to_string()).with_encoding("shift_jis"),
+            Step::new("broken".to_string(), r"printf 'ok \377\n'".to_string()),
+        ]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let sjis = &execution.step_results[0];
+        assert_eq!(sjis.stdout, "ビルド");
+        assert_eq!(sjis.encoding.as_deref(), Some("Shift_JIS"));
+        assert_eq!(sjis.replacement_chars, 0);
+        let broken = &execution.step_results[1];
+        assert_eq!(broken.stdout, "ok \u{FFFD}\n");
+        assert_eq!(broken.encoding.as_deref(), Some("UTF-8"));
+        assert_eq!(broken.replacement_chars, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_action_fetches_are_cache_phases() {
+        let root = std::env::temp_dir().join(format!("pulsiora-executor-actions-{}", Uuid::new_v4()));
+        let action = root.join("greet.git");
+        std::fs::create_dir_all(&action).unwrap();
+        std::fs::write(action.join("pulse-action.json"), r#"{"name": "greet", "entrypoint": "run.sh"}"#).unwrap();
+        std::fs::write(action.join("run.sh"), "echo hello\n").unwrap();
+        std::fs::set_permissions(action.join("run.sh"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        for args in [
+            &["init", "--quiet"][..],
+            &["add", "."],
+            &["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "--quiet", "-m", "greet"],
+        ] {
+            let status = Command::new("git").arg("-C").arg(&action).args(args).status().unwrap();
+            assert!(status.success());
+        }
+        let sha = Command::new("git").arg("-C").arg(&action).args(["rev-parse", "HEAD"]).output().unwrap();
+        let sha = String::from_utf8(sha.stdout).unwrap();
+
+        let executor = PipelineExecutor::new()
+            .with_work_dir(root.join("work"))
+            .with_action_cache(ActionCache::new(root.join("cache")));
+        std::fs::create_dir_all(root.join("work")).unwrap();
+        let mut step = Step::new("greet".to_string(), String::new());
+        step.uses = Some(format!("file://{}@{}", action.display(), sha.trim()));
+        let pipeline = push_pipeline(vec![step]);
+
+        let first = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(first.status, PipelineStatus::Success, "{}", first.step_results[0].stderr);
+        let phases: Vec<PhaseKind> = first.phases.iter().map(|phase| phase.phase).collect();
+        assert_eq!(phases, [PhaseKind::CacheSave]);
+        assert!(first.phases[0].bytes_transferred.is_some_and(|bytes| bytes > 0));
+
+        let second = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let phases: Vec<PhaseKind> = second.phases.iter().map(|phase| phase.phase).collect();
+        assert_eq!(phases, [PhaseKind::CacheRestore]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_runs_steps_in_cloned_workspace() {
+        let root = std::env::temp_dir().join(format!("pulsiora-executor-workspace-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let origin = root.join("origin");
+        std::fs::create_dir_all(&origin).unwrap();
+        std::fs::write(origin.join("README"), "from the repo").unwrap();
+        for args in [
+            &["init", "--quiet", "--initial-branch=main"][..],
+            &["add", "."],
+            &["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "--quiet", "-m", "init"],
+        ] {
+            let status = Command::new("git").arg("-C").arg(&origin).args(args).status().unwrap();
+            assert!(status.success());
+        }
+
+        let executor = PipelineExecutor::new().with_workspace_root(root.join("workspaces"));
+        let mut event = create_test_event();
+        event.repository.clone_url = origin.to_string_lossy().into_owned();
+        let pipeline = push_pipeline(vec![Step::new("read".to_string(), "cat README && pwd".to_string())]);
+
+        let execution = executor.execute(&pipeline, &event).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Success);
+        let stdout = &execution.step_results[0].stdout;
+        assert!(stdout.starts_with("from the repo"));
+        assert!(stdout.trim_end().ends_with(&execution.id.to_string()));
+        assert_eq!(execution.phases.len(), 1);
+        assert_eq!(execution.phases[0].phase, PhaseKind::Clone);
+        // The checkout is gone once the execution ends
+        assert_eq!(std::fs::read_dir(root.join("workspaces")).unwrap().count(), 0);
+
+        event.repository.clone_url = root.join("missing").to_string_lossy().into_owned();
+        assert!(executor.execute(&pipeline, &event).await.is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_sets_builtin_env() {
+        let executor = PipelineExecutor::new();
+        let mut event = create_test_event();
+        event.commit_sha = Some("0123abcd".to_string());
+        let pipeline = push_pipeline(vec![
+            Step::new(
+                "context".to_string(),
+                "echo $PULSE_PIPELINE_NAME $PULSE_BRANCH $PULSE_COMMIT_SHA $PULSE_EVENT $PULSE_REPO".to_string(),
+            ),
+            Step::new("ids".to_string(), "echo $PULSE_EXECUTION_ID; echo $PULSE_WORKSPACE".to_string()),
+            Step::new("override".to_string(), "echo $PULSE_BRANCH".to_string()).with_env("PULSE_BRANCH", "mine"),
+        ]);
+
+        let execution = executor.execute(&pipeline, &event).await.unwrap();
+        let stdout = |index: usize| execution.step_results[index].stdout.clone();
+        assert_eq!(stdout(0).trim(), "test main 0123abcd push test/repo");
+        let ids: Vec<String> = stdout(1).lines().map(String::from).collect();
+        assert_eq!(ids[0], execution.id.to_string());
+        assert_eq!(
+            std::path::Path::new(&ids[1]),
+            std::env::current_dir().unwrap().join(".")
+        );
+        assert_eq!(stdout(2).trim(), "mine");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_sets_env_and_masks_secrets() {
+        let log_dir = std::env::temp_dir().join(format!("pulsiora-secrets-{}", Uuid::new_v4()));
+        let secrets = BTreeMap::from([("DEPLOY_TOKEN".to_string(), "s3cr3t-value".to_string())]);
+        let env = BTreeMap::from([
+            ("REGISTRY".to_string(), "registry.example.com".to_string()),
+            ("TARGET".to_string(), "staging".to_string()),
+        ]);
+        let executor = PipelineExecutor::new().with_log_dir(&log_dir).with_env(env).with_secrets(secrets);
+        let pipeline = push_pipeline(vec![
+            Step::new("leak".to_string(), "echo token=$DEPLOY_TOKEN; echo $DEPLOY_TOKEN >&2".to_string()),
+            Step::new("length".to_string(), "echo ${#DEPLOY_TOKEN}".to_string()),
+            // The step's own env wins over the repository's
+            Step::new("env".to_string(), "echo $REGISTRY $TARGET".to_string()).with_env("TARGET", "production"),
+        ]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let result = &execution.step_results[0];
+        assert_eq!(result.stdout.trim(), "token=***");
+        assert_eq!(result.stderr.trim(), "***");
+        // The step saw the real value
+        assert_eq!(execution.step_results[1].stdout.trim(), "12");
+        assert_eq!(execution.step_results[2].stdout.trim(), "registry.example.com production");
+        let log = executor
+            .step_log_path(result.log_ref.as_deref().unwrap(), OutputStream::Stdout)
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(log).unwrap().trim(), "token=***");
+
+        std::fs::remove_dir_all(&log_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_executor_runs_hooks_around_steps() {
+        let executor = PipelineExecutor::new();
+        let mut pipeline = push_pipeline(vec![
+            Step::new("ok".to_string(), "echo hi".to_string()),
+            Step::new("bad".to_string(), "exit 3".to_string()).with_allow_failure(true),
+        ]);
+        pipeline.options.before_each = Some("echo before $PULSE_STEP_NAME".to_string());
+        pipeline.options.after_each = Some("echo after $PULSE_STEP_STATUS".to_string());
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Success);
+        let hooks = |index: usize| -> Vec<(StepHook, String)> {
+            execution.step_results[index]
+                .hooks
+                .iter()
+                .map(|h| (h.hook, h.stdout.trim().to_string()))
+                .collect()
+        };
+        assert_eq!(
+            hooks(0),
+            vec![
+                (StepHook::BeforeEach, "before ok".to_string()),
+                (StepHook::AfterEach, "after success".to_string()),
+            ]
+        );
+        assert_eq!(hooks(1)[1], (StepHook::AfterEach, "after failed".to_string()));
+        assert_eq!(execution.step_results[0].stdout.trim(), "hi");
+
+        let work_dir = std::env::temp_dir().join(format!("pulsiora-hooks-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let executor = PipelineExecutor::new().with_work_dir(&work_dir);
+        let mut pipeline = push_pipeline(vec![Step::new("guarded".to_string(), "touch ran".to_string())]);
+        pipeline.options.before_each = Some("exit 1".to_string());
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        let result = &execution.step_results[0];
+        assert_eq!(result.status, StepStatus::Failed);
+        assert_eq!(result.hooks.len(), 1);
+        assert_eq!(result.hooks[0].exit_code, Some(1));
+        assert!(!work_dir.join("ran").exists());
+        let _ = std::fs::remove_dir_all(&work_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_annotates_step_output() {
+        let log_dir = std::env::temp_dir().join(format!("pulsiora-annotations-{}", Uuid::new_v4()));
+        let executor = PipelineExecutor::new().with_log_dir(&log_dir).with_limits(ExecutionLimits {
+            max_output_bytes: Some(1000),
+            ..Default::default()
+        });
+        let mut pipeline = push_pipeline(vec![Step::new(
+            "build".to_string(),
+            "echo '::warning file=Cargo.toml::unused key'; seq 1 10000; echo 'src/main.rs:4: error: boom' >&2; exit 1"
+                .to_string(),
+        )]);
+        pipeline.options.problem_matchers.insert(
+            "compiler".to_string(),
+            r"^(?P<file>[^:]+):(?P<line>\d+): (?P<level>\w+): (?P<message>.+)$".to_string(),
+        );
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let result = &execution.step_results[0];
+        // The warning was cut from the kept output but is still found in the log
+        assert!(!result.stdout.contains("::warning"));
+        let shown: Vec<String> = result.annotations.iter().map(|a| a.to_string()).collect();
+        assert_eq!(shown, ["warning: Cargo.toml: unused key", "error: src/main.rs:4: boom"]);
+        let _ = std::fs::remove_dir_all(&log_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_checks_coverage() {
+        let work_dir = std::env::temp_dir().join(format!("pulsiora-coverage-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let executor = PipelineExecutor::new().with_work_dir(&work_dir);
+        let mut unit = Step::new(
+            "unit".to_string(),
+            "printf 'SF:a.rs\\nLF:10\\nLH:9\\nend_of_record\\n' > unit.info".to_string(),
+        );
+        unit.reports.coverage = Some("unit.info".to_string());
+        let mut integration = Step::new(
+            "integration".to_string(),
+            "printf 'SF:b.rs\\nLF:10\\nLH:5\\nend_of_record\\n' > it.info".to_string(),
+        );
+        integration.reports = StepReports {
+            coverage: Some("it.info".to_string()),
+            min_coverage: Some(80.0),
+        };
+        let mut missing = Step::new("missing".to_string(), "true".to_string());
+        missing.reports.coverage = Some("none.info".to_string());
+        let mut pipeline = push_pipeline(vec![unit, integration, missing]);
+        pipeline.options.fail_fast = false;
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let results = &execution.step_results;
+        assert_eq!(results[0].status, StepStatus::Success);
+        assert_eq!(results[0].coverage.unwrap().percent(), 90.0);
+        assert_eq!(results[1].status, StepStatus::Failed);
+        assert!(results[1].stderr.contains("Coverage 50.0% is below the minimum of 80%"));
+        // Without a threshold a missing report is only noted
+        assert_eq!(results[2].status, StepStatus::Success);
+        assert!(results[2].stderr.contains("Failed to read coverage report none.info"));
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert_eq!(execution.coverage, Some(CoverageReport { lines_covered: 14, lines_total: 20 }));
+        let _ = std::fs::remove_dir_all(&work_dir);
+    }
+
+    #[tokio::test]
+    async fn test_executor_records_metrics() {
+        let queued_at = Utc::now() - chrono::Duration::seconds(2);
+        let executor = PipelineExecutor::new().with_queued_at(queued_at);
+        let pipeline = push_pipeline(vec![
+            // Long enough for its memory to be sampled
+            Step::new("build".to_string(), "echo built; sleep 0.3".to_string()),
+            Step::new("fail".to_string(), "exit 1".to_string()),
+            Step::new("after".to_string(), "echo never".to_string()),
+        ]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let metrics = &execution.metrics;
+        assert!(metrics.queue_ms.unwrap() >= 2000);
+        let steps: Vec<&str> = metrics.steps.iter().map(|s| s.step_name.as_str()).collect();
+        assert_eq!(steps, ["build", "fail"]);
+        assert_eq!(metrics.steps[0].wall_ms, execution.step_results[0].duration_ms);
+        let total = execution.completed_at.unwrap() - execution.started_at;
+        assert_eq!(metrics.wall_ms, total.num_milliseconds() as u64);
+        if cfg!(target_os = "linux") {
+            let usage = execution.step_results[0].resources.unwrap();
+            assert!(usage.cpu_ms().is_some());
+            assert!(usage.peak_rss_bytes.is_some());
+            assert!(metrics.cpu_ms.is_some());
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_limits_concurrency() {
+        let work_dir = std::env::temp_dir().join(format!("pulsiora-limit-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        // `mkdir` fails if another step holds the lock
+        let locked = |name: &str| Step::new(name.to_string(), "mkdir lock && sleep 0.2 && rmdir lock".to_string());
+        let pipeline = push_pipeline(vec![locked("one"), locked("two")]);
+        let event = create_test_event();
+
+        for executor in [
+            PipelineExecutor::new().with_work_dir(&work_dir).with_max_concurrent_executions(1),
+            PipelineExecutor::new().with_work_dir(&work_dir).with_max_concurrent_steps(1),
+        ] {
+            let runs = (0..3).map(|_| {
+                let executor = executor.clone();
+                let (pipeline, event) = (pipeline.clone(), event.clone());
+                tokio::spawn(async move { executor.execute(&pipeline, &event).await.unwrap() })
+            });
+            for run in runs.collect::<Vec<_>>() {
+                assert_eq!(run.await.unwrap().status, PipelineStatus::Success);
+            }
+        }
+
+        let executor = PipelineExecutor::new().with_work_dir(&work_dir).with_max_concurrent_steps(1);
+        let cancel = CancellationToken::new();
+        let slot = executor.step_limit.as_ref().unwrap().acquire(None, &cancel).await.unwrap();
+        let waiting = {
+            let (executor, pipeline, event) = (executor.clone(), pipeline.clone(), event.clone());
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                executor
+                    .run(Uuid::new_v4(), &pipeline, &event, None, &cancel, Vec::new())
+                    .await
+                    .unwrap()
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+        let execution = waiting.await.unwrap();
+        drop(slot);
+        assert_eq!(execution.status, PipelineStatus::Cancelled);
+        assert!(execution.step_results.iter().all(|r| r.status == StepStatus::Skipped));
+        let _ = std::fs::remove_dir_all(&work_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_strips_ansi() {
+        let executor = PipelineExecutor::new();
+        let mut pipeline = push_pipeline(vec![Step::new(
+            "color".to_string(),
+            r"printf '\033[32mok\033[0m\n'; printf '\033[31mwarn\033[0m\n' >&2".to_string(),
+        )]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.step_results[0].stdout, "\u{1b}[32mok\u{1b}[0m\n");
+
+        pipeline.options.ansi = AnsiMode::Strip;
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.step_results[0].stdout, "ok\n");
+        assert_eq!(execution.step_results[0].stderr, "warn\n");
+    }
+
+    #[tokio::test]
+    async fn test_executor_resumes_from_first_unfinished_step() {
+        let work_dir = std::env::temp_dir().join(format!("pulsiora-resume-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let executor = PipelineExecutor::new().with_work_dir(&work_dir);
+        let event = create_test_event();
+        let pipeline = push_pipeline(vec![
+            Step::new("one".to_string(), "echo one >> ran".to_string()),
+            Step::new("two".to_string(), "echo two >> ran".to_string()),
+            Step::new("three".to_string(), "echo three >> ran".to_string()),
+        ]);
+        let first = executor.execute(&pipeline, &event).await.unwrap();
+        std::fs::remove_file(work_dir.join("ran")).unwrap();
+
+        // Interrupted while "two" was running
+        let mut completed = first.step_results[..2].to_vec();
+        completed[1].status = StepStatus::Running;
+        assert_eq!(resume_point(&pipeline, &completed), 1);
+        let (events, mut receiver) = mpsc::channel(16);
+        let execution = executor
+            .resume_with_events(first.id, &pipeline, &event, completed, events, CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(execution.status, PipelineStatus::Success);
+        assert_eq!(execution.step_results.len(), 3);
+        assert_eq!(execution.step_results[0].started_at, first.step_results[0].started_at);
+        assert_eq!(std::fs::read_to_string(work_dir.join("ran")).unwrap(), "two\nthree\n");
+        let mut started = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let ExecutionEvent::StepStarted { step_name, .. } = event {
+                started.push(step_name);
+            }
+        }
+        assert_eq!(started, ["two", "three"]);
+
+        // Services don't survive the interruption, so they start again
+        let mut with_service = pipeline.clone();
+        with_service.steps[0].background = true;
+        assert_eq!(resume_point(&with_service, &first.step_results), 0);
+        let mut renamed = pipeline.clone();
+        renamed.steps[1].name = "build".to_string();
+        assert_eq!(resume_point(&renamed, &first.step_results), 1);
+        let _ = std::fs::remove_dir_all(&work_dir);
+    }
+
+    #[test]
+    fn test_executor_plans_without_running() {
+        let work_dir = std::env::temp_dir().join(format!("pulsiora-plan-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let executor = PipelineExecutor::new().with_work_dir(&work_dir);
+        let pipeline = push_pipeline(vec![
+            Step::new("build".to_string(), "touch built".to_string()).with_env("PULSE_BRANCH", "mine"),
+            Step::new("deploy".to_string(), "./deploy.sh".to_string()).with_runner("ssh://deploy@prod-box"),
+        ]);
+        let execution_id = Uuid::new_v4();
+
+        let plan = executor.plan(execution_id, &pipeline, &create_test_event());
+        assert!(plan.runs());
+        assert_eq!(plan.steps.len(), 2);
+        let build = &plan.steps[0];
+        assert_eq!(build.backend, "host");
+        assert!(build.script.contains("touch built"));
+        assert_eq!(build.env["PULSE_BRANCH"], "mine");
+        assert_eq!(build.env["PULSE_EXECUTION_ID"], execution_id.to_string());
+        assert_eq!(plan.steps[1].backend, "ssh");
+        assert_eq!(plan.steps[1].runner.as_deref(), Some("ssh://deploy@prod-box"));
+        assert!(!work_dir.join("built").exists());
+
+        let mut with_inputs = create_test_event();
+        with_inputs.inputs.insert("target".to_string(), "staging".to_string());
+        let plan = executor.plan(execution_id, &pipeline, &with_inputs);
+        assert_eq!(plan.steps[0].env["PULSE_INPUT_TARGET"], "staging");
+
+        let mut tag_event = create_test_event();
+        tag_event.event_type = GitEventType::Tag;
+        assert!(!executor.plan(execution_id, &pipeline, &tag_event).runs());
+
+        let limited = executor.with_limits(ExecutionLimits {
+            max_steps: Some(1),
+            ..Default::default()
+        });
+        let plan = limited.plan(execution_id, &pipeline, &create_test_event());
+        assert!(plan.limit_exceeded.is_some());
+        assert!(!plan.runs());
+        let _ = std::fs::remove_dir_all(&work_dir);
+    }
+
+    /// Runs steps on the host with a marker variable, and records cleanups
+    #[derive(Debug, Default)]
+    struct MarkingBackend {
+        cleaned_up: Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    impl ExecutionBackend for MarkingBackend {
+        fn name(&self) -> &'static str {
+            "marking"
+        }
+
+        fn command(&self, ctx: &StepContext) -> std::io::Result<Command> {
+            let mut command = HostBackend.command(ctx)?;
+            command.env("BACKEND_STEP", ctx.step_index.to_string());
+            Ok(command)
+        }
+
+        fn cleanup(&self, _execution_id: Uuid, step_index: usize) {
+            self.cleaned_up.lock().unwrap().push(step_index);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_executor_runs_steps_through_backend() {
+        let backend = MarkingBackend::default();
+        let cleaned_up = backend.cleaned_up.clone();
+        let executor = PipelineExecutor::new()
+            .with_backend(backend)
+            .with_limits(ExecutionLimits {
+                max_runtime_secs: Some(1),
+                ..Default::default()
+            });
+        let pipeline = push_pipeline(vec![
+            Step::new("first".to_string(), "echo $BACKEND_STEP".to_string()),
+            Step::new("second".to_string(), "echo $BACKEND_STEP; sleep 10".to_string()),
+        ]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        assert_eq!(execution.step_results[0].stdout.trim(), "0");
+        assert_eq!(execution.step_results[1].stdout.trim(), "1");
+        // Only the step that was killed is cleaned up
+        assert_eq!(*cleaned_up.lock().unwrap(), vec![1]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executor_runs_remote_steps_over_ssh() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stands in for ssh by running the remote command locally
+        let dir = std::env::temp_dir().join(format!("pulsiora-fake-ssh-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("ssh");
+        std::fs::write(&program, "#!/bin/sh\nfor last; do :; done\nexec sh -c \"$last\"\n").unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let ssh = SshBackend::new(crate::ssh::SshOptions {
+            control_dir: dir.join("control"),
+            ..Default::default()
+        })
+        .with_program(program.to_string_lossy());
+        let executor = PipelineExecutor::new().with_ssh_backend(ssh);
+        let remote = Step::new("remote".to_string(), "echo \"$GREETING $PULSE_EVENT ${PULSE_WORKSPACE:-none}\"".to_string())
+            .with_env("GREETING", "it's")
+            .with_runner("ssh://deploy@prod-box");
+        let pipeline = push_pipeline(vec![remote, Step::new("local".to_string(), "echo local".to_string())]);
+
+        let execution = executor.execute(&pipeline, &create_test_event()).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(execution.step_results[0].stdout.trim(), "it's push none");
+        assert_eq!(execution.step_results[1].stdout.trim(), "local");
+    }
 }
@@ -0,0 +1,467 @@
+// Kubernetes backend: every step runs as a Job in the cluster, and the pod's
+// log is streamed back into the step result. Workspaces reach the pods through
+// a PersistentVolumeClaim that the server also mounts at its workspace root.
+
+use crate::backend::{container_shell, missing_image, step_image, ExecutionBackend, StepContext, StepOutput};
+use crate::process::{collect_reader, sleep_until, spawn_reader, Interrupt, OutputCapture, OutputSink};
+use async_trait::async_trait;
+use k8s_openapi::api::batch::v1::{Job, JobSpec};
+use k8s_openapi::api::core::v1::{
+    Container, EmptyDirVolumeSource, EnvVar, PersistentVolumeClaimVolumeSource, Pod, PodSecurityContext, PodSpec,
+    PodTemplateSpec, Volume, VolumeMount,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::api::{Api, DeleteParams, ListParams, LogParams, PostParams};
+use kube::Client;
+use pulsiora_core::OutputStream;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Name of the container that runs the step in each pod
+const STEP_CONTAINER: &str = "step";
+
+/// Where the workspace volume is mounted in step pods
+const POD_WORKSPACE: &str = "/workspace";
+
+/// How often pod status is polled while waiting for it to start or finish
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Finished Jobs are deleted right away; this only cleans up after a server
+/// that went away mid-step
+const JOB_TTL_SECS: i32 = 600;
+
+/// Container states that won't resolve on their own
+const FATAL_WAITING_REASONS: &[&str] = &[
+    "ErrImagePull",
+    "ImagePullBackOff",
+    "InvalidImageName",
+    "CreateContainerConfigError",
+    "CreateContainerError",
+];
+
+/// How step Jobs are built
+#[derive(Debug, Clone, Default)]
+pub struct KubernetesOptions {
+    /// Namespace Jobs are created in [default: the client's namespace]
+    pub namespace: Option<String>,
+    /// Image for steps whose Pulsefile doesn't name one
+    pub default_image: Option<String>,
+    /// PersistentVolumeClaim holding the workspaces. The server must have it
+    /// mounted at `workspace_root`, so each execution's checkout is found at
+    /// the same relative path in the pods. Without it steps start in an
+    /// empty directory.
+    pub workspace_claim: Option<String>,
+    pub workspace_root: Option<PathBuf>,
+    pub service_account: Option<String>,
+}
+
+/// Runs each step as a Kubernetes Job
+#[derive(Clone)]
+pub struct KubernetesBackend {
+    client: Client,
+    namespace: String,
+    options: KubernetesOptions,
+}
+
+impl fmt::Debug for KubernetesBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KubernetesBackend")
+            .field("namespace", &self.namespace)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl KubernetesBackend {
+    pub fn new(client: Client, options: KubernetesOptions) -> Self {
+        let namespace = options
+            .namespace
+            .clone()
+            .unwrap_or_else(|| client.default_namespace().to_string());
+        Self {
+            client,
+            namespace,
+            options,
+        }
+    }
+
+    /// Connect with the in-cluster service account, or the local kubeconfig
+    pub async fn connect(options: KubernetesOptions) -> kube::Result<Self> {
+        Ok(Self::new(Client::try_default().await?, options))
+    }
+
+    /// Wait for the Job's pod to start running, or to finish before it was seen
+    async fn wait_for_pod(&self, pods: &Api<Pod>, job_name: &str) -> io::Result<String> {
+        let selector = ListParams::default().labels(&format!("job-name={}", job_name));
+        loop {
+            let list = pods.list(&selector).await.map_err(kube_error)?;
+            for pod in &list.items {
+                let name = pod.metadata.name.clone().unwrap_or_default();
+                let status = pod.status.as_ref();
+                if let Some(waiting) = status
+                    .and_then(|s| s.container_statuses.as_ref())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|c| c.state.as_ref()?.waiting.as_ref())
+                    .find(|w| w.reason.as_deref().is_some_and(|r| FATAL_WAITING_REASONS.contains(&r)))
+                {
+                    return Err(io::Error::other(format!(
+                        "pod {} cannot start: {}: {}",
+                        name,
+                        waiting.reason.as_deref().unwrap_or_default(),
+                        waiting.message.as_deref().unwrap_or_default()
+                    )));
+                }
+                if matches!(
+                    status.and_then(|s| s.phase.as_deref()),
+                    Some("Running" | "Succeeded" | "Failed")
+                ) {
+                    return Ok(name);
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Wait for the step container to terminate and return its exit code
+    async fn wait_for_exit(&self, pods: &Api<Pod>, pod_name: &str) -> io::Result<i32> {
+        loop {
+            let pod = pods.get(pod_name).await.map_err(kube_error)?;
+            let status = pod.status.unwrap_or_default();
+            let terminated = status
+                .container_statuses
+                .unwrap_or_default()
+                .into_iter()
+                .find(|c| c.name == STEP_CONTAINER)
+                .and_then(|c| c.state?.terminated);
+            if let Some(terminated) = terminated {
+                return Ok(terminated.exit_code);
+            }
+            if status.phase.as_deref() == Some("Failed") {
+                return Err(io::Error::other(format!(
+                    "pod {} failed: {}",
+                    pod_name,
+                    status.message.unwrap_or_default()
+                )));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Follow a created Job until its step finishes or is interrupted
+    async fn follow(
+        &self,
+        job_name: &str,
+        deadline: Option<Instant>,
+        capture: &OutputCapture,
+        sink: Option<OutputSink>,
+        cancel: &CancellationToken,
+    ) -> io::Result<(StepOutput, Option<Interrupt>)> {
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
+        let pod_name = match until_interrupted(self.wait_for_pod(&pods, job_name), deadline, cancel).await {
+            Ok(pod_name) => pod_name?,
+            Err(interrupt) => return Ok((StepOutput::default(), Some(interrupt))),
+        };
+
+        let params = LogParams {
+            container: Some(STEP_CONTAINER.to_string()),
+            follow: true,
+            ..Default::default()
+        };
+        let logs = pods.log_stream(&pod_name, &params).await.map_err(kube_error)?;
+        // The pod log interleaves stdout and stderr
+        let reader = spawn_reader(Some(logs.compat()), OutputStream::Stdout, capture, sink);
+
+        let (exit_code, interrupt) =
+            match until_interrupted(self.wait_for_exit(&pods, &pod_name), deadline, cancel).await {
+                Ok(exit_code) => (Some(exit_code?), None),
+                Err(interrupt) => (None, Some(interrupt)),
+            };
+        let log = collect_reader(reader, interrupt.is_some()).await;
+        let output = StepOutput {
+            stdout: log.to_bytes(),
+            stdout_bytes: log.total_bytes(),
+            exit_code,
+            success: exit_code == Some(0),
+            ..Default::default()
+        };
+        Ok((output, interrupt))
+    }
+}
+
+#[async_trait]
+impl ExecutionBackend for KubernetesBackend {
+    fn name(&self) -> &'static str {
+        "kubernetes"
+    }
+
+    fn command(&self, _ctx: &StepContext) -> io::Result<Command> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "background steps are not supported by the Kubernetes backend",
+        ))
+    }
+
+    async fn run(
+        &self,
+        ctx: &StepContext<'_>,
+        deadline: Option<Instant>,
+        sink: Option<OutputSink>,
+        cancel: &CancellationToken,
+    ) -> io::Result<(StepOutput, Option<Interrupt>)> {
+        let active_deadline = deadline.map(|d| d.saturating_duration_since(Instant::now()).as_secs().max(1) as i64);
+        let job = step_job(ctx, &self.options, active_deadline)?;
+        let job_name = job.metadata.name.clone().unwrap_or_default();
+
+        let jobs: Api<Job> = Api::namespaced(self.client.clone(), &self.namespace);
+        jobs.create(&PostParams::default(), &job).await.map_err(kube_error)?;
+        info!(step_name = %ctx.step.name, job = %job_name, namespace = %self.namespace, "Created step job");
+
+        let outcome = self.follow(&job_name, deadline, &ctx.output, sink, cancel).await;
+
+        // Deleting the Job in the background removes its pod too, stopping an interrupted step
+        if let Err(e) = jobs.delete(&job_name, &DeleteParams::background()).await {
+            warn!(job = %job_name, error = %e, "Failed to delete step job");
+        }
+        outcome
+    }
+}
+
+/// Name of the Job running a step
+pub fn job_name(execution_id: Uuid, step_index: usize) -> String {
+    format!("pulse-{}-{}", execution_id, step_index)
+}
+
+/// The Job that runs a step. `active_deadline_secs` lets the cluster stop a
+/// step that outlives the execution's runtime limit.
+pub fn step_job(ctx: &StepContext, options: &KubernetesOptions, active_deadline_secs: Option<i64>) -> io::Result<Job> {
+    let image = step_image(ctx.step, ctx.options, options.default_image.as_deref()).ok_or_else(missing_image)?;
+
+    // Pods can't look names up in the image's /etc/passwd
+    let numeric = |field: &str, value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|v| {
+                v.parse::<i64>().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("the Kubernetes backend needs a numeric {}, got \"{}\"", field, v),
+                    )
+                })
+            })
+            .transpose()
+    };
+    let run_as_user = numeric("user", &ctx.step.user)?;
+    let run_as_group = numeric("group", &ctx.step.group)?;
+
+    let (volume, sub_path) = match (&options.workspace_claim, &options.workspace_root) {
+        (Some(claim), Some(root)) => {
+            let work_dir = std::path::absolute(ctx.work_dir)?;
+            let root = std::path::absolute(root)?;
+            let relative = work_dir.strip_prefix(&root).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("workspace {} is not under {}", work_dir.display(), root.display()),
+                )
+            })?;
+            let volume = Volume {
+                name: "workspace".to_string(),
+                persistent_volume_claim: Some(PersistentVolumeClaimVolumeSource {
+                    claim_name: claim.clone(),
+                    read_only: None,
+                }),
+                ..Default::default()
+            };
+            let sub_path = Some(relative.to_string_lossy().into_owned()).filter(|p| !p.is_empty());
+            (volume, sub_path)
+        }
+        _ => {
+            let volume = Volume {
+                name: "workspace".to_string(),
+                empty_dir: Some(EmptyDirVolumeSource::default()),
+                ..Default::default()
+            };
+            (volume, None)
+        }
+    };
+
+    let env = ctx
+        .env
+        .iter()
+        .map(|(name, value)| EnvVar {
+            name: name.clone(),
+            value: Some(if name == "PULSE_WORKSPACE" { POD_WORKSPACE.to_string() } else { value.clone() }),
+            value_from: None,
+        })
+        .collect();
+    let labels = BTreeMap::from([
+        ("app.kubernetes.io/managed-by".to_string(), "pulsiora".to_string()),
+        ("pulsiora.dev/execution-id".to_string(), ctx.execution_id.to_string()),
+    ]);
+
+    Ok(Job {
+        metadata: ObjectMeta {
+            name: Some(job_name(ctx.execution_id, ctx.step_index)),
+            labels: Some(labels.clone()),
+            ..Default::default()
+        },
+        spec: Some(JobSpec {
+            backoff_limit: Some(0),
+            active_deadline_seconds: active_deadline_secs,
+            ttl_seconds_after_finished: Some(JOB_TTL_SECS),
+            template: PodTemplateSpec {
+                metadata: Some(ObjectMeta {
+                    labels: Some(labels),
+                    ..Default::default()
+                }),
+                spec: Some(PodSpec {
+                    restart_policy: Some("Never".to_string()),
+                    // Deleting the Job sends SIGTERM, then SIGKILL after this
+                    termination_grace_period_seconds: Some(ctx.options.kill_grace_secs as i64),
+                    service_account_name: options.service_account.clone(),
+                    security_context: (run_as_user.is_some() || run_as_group.is_some()).then(|| PodSecurityContext {
+                        run_as_user,
+                        run_as_group,
+                        ..Default::default()
+                    }),
+                    containers: vec![Container {
+                        name: STEP_CONTAINER.to_string(),
+                        image: Some(image.to_string()),
+                        command: Some(vec![container_shell(ctx.options), "-c".to_string(), ctx.script.to_string()]),
+                        env: Some(env),
+                        working_dir: Some(POD_WORKSPACE.to_string()),
+                        tty: ctx.step.tty.then_some(true),
+                        volume_mounts: Some(vec![VolumeMount {
+                            name: "workspace".to_string(),
+                            mount_path: POD_WORKSPACE.to_string(),
+                            sub_path,
+                            ..Default::default()
+                        }]),
+                        ..Default::default()
+                    }],
+                    volumes: Some(vec![volume]),
+                    ..Default::default()
+                }),
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Await `future` unless `deadline` passes or `cancel` fires first
+async fn until_interrupted<T>(
+    future: impl Future<Output = T>,
+    deadline: Option<Instant>,
+    cancel: &CancellationToken,
+) -> Result<T, Interrupt> {
+    tokio::select! {
+        value = future => Ok(value),
+        _ = sleep_until(deadline) => Err(Interrupt::Deadline),
+        _ = cancel.cancelled() => Err(Interrupt::Cancelled),
+    }
+}
+
+fn kube_error(error: kube::Error) -> io::Error {
+    io::Error::other(format!("Kubernetes API error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::{PipelineOptions, Step};
+    use std::path::Path;
+
+    fn context<'a>(
+        step: &'a Step,
+        options: &'a PipelineOptions,
+        env: &'a BTreeMap<String, String>,
+        execution_id: Uuid,
+    ) -> StepContext<'a> {
+        StepContext {
+            execution_id,
+            step_index: 1,
+            step,
+            options,
+            script: "cargo test",
+            work_dir: Path::new("/data/workspaces/abc"),
+            env,
+            output: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_step_job_spec() {
+        let mut step = Step::new("test".to_string(), "cargo test".to_string());
+        step.user = Some("1000".to_string());
+        let pipeline_options = PipelineOptions {
+            image: Some("rust:1.80".to_string()),
+            ..Default::default()
+        };
+        let env = BTreeMap::from([
+            ("CI".to_string(), "true".to_string()),
+            ("PULSE_WORKSPACE".to_string(), "/data/workspaces/abc".to_string()),
+        ]);
+        let options = KubernetesOptions {
+            workspace_claim: Some("pulsiora-workspaces".to_string()),
+            workspace_root: Some(PathBuf::from("/data/workspaces")),
+            ..Default::default()
+        };
+        let execution_id = Uuid::new_v4();
+
+        let job = step_job(&context(&step, &pipeline_options, &env, execution_id), &options, Some(30)).unwrap();
+        assert_eq!(job.metadata.name, Some(format!("pulse-{}-1", execution_id)));
+        let spec = job.spec.unwrap();
+        assert_eq!(spec.backoff_limit, Some(0));
+        assert_eq!(spec.active_deadline_seconds, Some(30));
+        let pod = spec.template.spec.unwrap();
+        assert_eq!(pod.restart_policy.as_deref(), Some("Never"));
+        assert_eq!(pod.termination_grace_period_seconds, Some(10));
+        assert_eq!(pod.security_context.unwrap().run_as_user, Some(1000));
+        let volume = &pod.volumes.unwrap()[0];
+        assert_eq!(volume.persistent_volume_claim.as_ref().unwrap().claim_name, "pulsiora-workspaces");
+
+        let container = &pod.containers[0];
+        assert_eq!(container.image.as_deref(), Some("rust:1.80"));
+        assert_eq!(
+            container.command,
+            Some(vec!["sh".to_string(), "-c".to_string(), "cargo test".to_string()])
+        );
+        let mount = &container.volume_mounts.as_ref().unwrap()[0];
+        assert_eq!(mount.mount_path, "/workspace");
+        assert_eq!(mount.sub_path.as_deref(), Some("abc"));
+        let workspace = container.env.as_ref().unwrap().iter().find(|e| e.name == "PULSE_WORKSPACE").unwrap();
+        assert_eq!(workspace.value.as_deref(), Some("/workspace"));
+    }
+
+    #[test]
+    fn test_step_job_rejects_unusable_steps() {
+        let env = BTreeMap::new();
+        let pipeline_options = PipelineOptions::default();
+        let options = KubernetesOptions::default();
+
+        let step = Step::new("test".to_string(), "make".to_string());
+        let err = step_job(&context(&step, &pipeline_options, &env, Uuid::new_v4()), &options, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let mut step = step.with_image("debian");
+        step.user = Some("builder".to_string());
+        let err = step_job(&context(&step, &pipeline_options, &env, Uuid::new_v4()), &options, None).unwrap_err();
+        assert!(err.to_string().contains("numeric user"));
+
+        // Without a workspace claim the step starts in an empty directory
+        step.user = None;
+        let job = step_job(&context(&step, &pipeline_options, &env, Uuid::new_v4()), &options, None).unwrap();
+        let volumes = job.spec.unwrap().template.spec.unwrap().volumes.unwrap();
+        assert!(volumes[0].empty_dir.is_some());
+    }
+}
@@ -1,6 +1,45 @@
+pub mod actions;
+pub mod annotations;
+pub mod ansi;
+pub mod artifacts;
+pub mod backend;
+pub mod bisect;
+pub mod coverage;
+pub mod encoding;
 pub mod executor;
+pub mod kubernetes;
+pub mod limiter;
+pub mod mask;
+pub mod phases;
+pub mod plan;
 pub mod process;
+pub mod replay;
+pub mod sandbox;
+pub mod service;
+pub mod ssh;
+pub mod workspace;
+#[cfg(unix)]
+mod pty;
+#[cfg(target_os = "linux")]
+mod usage;
 
+pub use actions::*;
+pub use annotations::*;
+pub use ansi::*;
+pub use artifacts::*;
+pub use backend::*;
+pub use bisect::*;
+pub use coverage::*;
+pub use encoding::*;
 pub use executor::*;
+pub use kubernetes::*;
+pub use limiter::*;
+pub use mask::*;
+pub use phases::*;
+pub use plan::*;
 pub use process::*;
-
+pub use replay::*;
+pub use sandbox::*;
+pub use service::*;
+pub use ssh::*;
+pub use workspace::*;
@@ -0,0 +1,82 @@
+// Caps on how much an executor runs at once. A burst of webhooks would
+// otherwise start every execution, and fork every shell, at the same time;
+// with a limit the rest wait for a slot.
+
+use crate::process::{sleep_until, Interrupt};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+/// A number of slots shared by every clone of the limit
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    max: usize,
+    slots: Arc<Semaphore>,
+}
+
+impl ConcurrencyLimit {
+    /// Allow `max` at once; a limit of 0 is treated as 1
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            slots: Arc::new(Semaphore::new(max)),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Slots currently taken
+    pub fn in_use(&self) -> usize {
+        self.max - self.slots.available_permits()
+    }
+
+    /// Wait for a free slot, which is held until the permit is dropped.
+    /// Gives up at `deadline` or when `cancel` fires.
+    pub async fn acquire(
+        &self,
+        deadline: Option<Instant>,
+        cancel: &CancellationToken,
+    ) -> Result<OwnedSemaphorePermit, Interrupt> {
+        tokio::select! {
+            // The semaphore is never closed
+            permit = self.slots.clone().acquire_owned() => permit.map_err(|_| Interrupt::Cancelled),
+            _ = sleep_until(deadline) => Err(Interrupt::Deadline),
+            _ = cancel.cancelled() => Err(Interrupt::Cancelled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrency_limit_waits_for_slot() {
+        let limit = ConcurrencyLimit::new(1);
+        let cancel = CancellationToken::new();
+        let held = limit.acquire(None, &cancel).await.unwrap();
+        assert_eq!(limit.in_use(), 1);
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert_eq!(limit.acquire(Some(deadline), &cancel).await.unwrap_err(), Interrupt::Deadline);
+        let waiting = {
+            let limit = limit.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move { limit.acquire(None, &cancel).await.is_ok() })
+        };
+        drop(held);
+        assert!(waiting.await.unwrap());
+        assert_eq!(limit.in_use(), 0);
+
+        let held = limit.acquire(None, &cancel).await.unwrap();
+        cancel.cancel();
+        assert_eq!(limit.acquire(None, &cancel).await.unwrap_err(), Interrupt::Cancelled);
+        drop(held);
+        assert_eq!(ConcurrencyLimit::new(0).max(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+// Hiding secret values in step output. Output is read in chunks that can
+// split a value in two, so the end of a chunk that could be the start of one
+// is held back until the next chunk shows whether it is.
+
+use std::borrow::Cow;
+
+/// What secret values in output are replaced with
+pub const SECRET_MASK: &str = "***";
+
+/// Replaces secret values in a stream of output with `SECRET_MASK`
+#[derive(Debug, Clone, Default)]
+pub struct SecretMasker {
+    /// Longest first, so a value containing another is masked whole
+    secrets: Vec<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl SecretMasker {
+    pub fn new(secrets: &[String]) -> Self {
+        let mut secrets: Vec<Vec<u8>> = secrets
+            .iter()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| secret.as_bytes().to_vec())
+            .collect();
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        secrets.dedup();
+        Self {
+            secrets,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The next chunk of output, masked, without any end that could still
+    /// turn out to be part of a secret
+    pub fn push<'a>(&mut self, chunk: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.secrets.is_empty() {
+            return Cow::Borrowed(chunk);
+        }
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(chunk);
+        Cow::Owned(self.mask(&data, false))
+    }
+
+    /// Whatever was held back, once the output has ended
+    pub fn finish(&mut self) -> Vec<u8> {
+        let data = std::mem::take(&mut self.pending);
+        self.mask(&data, true)
+    }
+
+    fn mask(&mut self, data: &[u8], last: bool) -> Vec<u8> {
+        let mut masked = Vec::with_capacity(data.len());
+        let mut i = 0;
+        'scan: while i < data.len() {
+            let rest = &data[i..];
+            for secret in &self.secrets {
+                if rest.starts_with(secret) {
+                    masked.extend_from_slice(SECRET_MASK.as_bytes());
+                    i += secret.len();
+                    continue 'scan;
+                }
+            }
+            if !last && self.secrets.iter().any(|secret| secret.starts_with(rest)) {
+                self.pending = rest.to_vec();
+                break;
+            }
+            masked.push(data[i]);
+            i += 1;
+        }
+        masked
+    }
+}
+
+/// `text` with every secret value replaced by `SECRET_MASK`
+pub fn mask_secrets(text: &str, secrets: &[String]) -> String {
+    let mut masker = SecretMasker::new(secrets);
+    let mut masked = masker.push(text.as_bytes()).into_owned();
+    masked.extend(masker.finish());
+    String::from_utf8(masked).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_masker() {
+        let secrets = vec!["hunter2".to_string(), "hunter2-admin".to_string(), String::new()];
+        assert_eq!(
+            mask_secrets("login hunter2 then hunter2-admin", &secrets),
+            "login *** then ***"
+        );
+
+        // A value split across chunks is held back until it is complete
+        let mut masker = SecretMasker::new(&secrets);
+        assert_eq!(&*masker.push(b"token=hun"), b"token=");
+        assert_eq!(&*masker.push(b"ter2\nnext hunt"), b"***\nnext ");
+        assert_eq!(&*masker.push(b"ing"), b"hunting");
+        assert_eq!(masker.push(b"done: hunter").as_ref(), b"done: ");
+        assert_eq!(masker.finish(), b"hunter");
+
+        let mut none = SecretMasker::new(&[]);
+        assert!(matches!(none.push(b"plain"), Cow::Borrowed(b"plain")));
+        assert!(none.finish().is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+// Timing for infrastructure phases (clone, action cache restore/save) so they
+// are reported separately from the steps they precede.
+
+use chrono::Utc;
+use pulsiora_core::{PhaseKind, PhaseResult};
+use std::path::Path;
+use std::time::Instant;
+
+/// Measures a single phase from `start` to `finish`
+pub struct PhaseTimer {
+    phase: PhaseKind,
+    started_at: chrono::DateTime<Utc>,
+    start_instant: Instant,
+}
+
+impl PhaseTimer {
+    pub fn start(phase: PhaseKind) -> Self {
+        Self {
+            phase,
+            started_at: Utc::now(),
+            start_instant: Instant::now(),
+        }
+    }
+
+    pub fn finish(self, bytes_transferred: Option<u64>) -> PhaseResult {
+        let phase = self.phase;
+        self.finish_as(phase, bytes_transferred)
+    }
+
+    /// Finish as `phase`, for phases only known once they're done, such as
+    /// whether a bundle came from the cache
+    pub fn finish_as(self, phase: PhaseKind, bytes_transferred: Option<u64>) -> PhaseResult {
+        PhaseResult {
+            phase,
+            duration_ms: self.start_instant.elapsed().as_millis() as u64,
+            bytes_transferred,
+            started_at: self.started_at,
+            completed_at: Utc::now(),
+        }
+    }
+}
+
+/// Total size of the files under `path`, used to estimate bytes transferred
+/// for clones and action bundles. Symlinks are not followed.
+pub fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        total += dir_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timer_records_phase() {
+        let timer = PhaseTimer::start(PhaseKind::Clone);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let result = timer.finish(Some(42));
+
+        assert_eq!(result.phase, PhaseKind::Clone);
+        assert_eq!(result.bytes_transferred, Some(42));
+        assert!(result.duration_ms >= 5);
+        assert!(result.completed_at >= result.started_at);
+
+        let result = PhaseTimer::start(PhaseKind::CacheRestore).finish_as(PhaseKind::CacheSave, None);
+        assert_eq!(result.phase, PhaseKind::CacheSave);
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-dir-size-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a.txt"), [0u8; 10]).unwrap();
+        std::fs::write(dir.join("nested/b.txt"), [0u8; 32]).unwrap();
+
+        assert_eq!(dir_size(&dir).unwrap(), 42);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+// What an execution would do, worked out without running anything. The plan
+// goes through the same trigger check, limits and environment as a real run,
+// so `pulse run --dry-run` shows exactly the steps a push would start.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Steps an execution would run for an event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionPlan {
+    pub execution_id: Uuid,
+    pub pipeline_name: String,
+    /// False when the pipeline's triggers don't match the event, in which
+    /// case nothing would run
+    pub triggered: bool,
+    /// Why the execution would be rejected before running any step
+    pub limit_exceeded: Option<String>,
+    pub steps: Vec<PlannedStep>,
+}
+
+impl ExecutionPlan {
+    /// Whether any step would run
+    pub fn runs(&self) -> bool {
+        self.triggered && self.limit_exceeded.is_none()
+    }
+}
+
+/// One step as it would be started
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedStep {
+    pub index: usize,
+    pub name: String,
+    /// Backend that would run the step, e.g. "host" or "ssh"
+    pub backend: String,
+    /// Image named by the step or pipeline; container backends may fall back
+    /// to their default image
+    pub image: Option<String>,
+    /// Remote runner the step targets, if any
+    pub runner: Option<String>,
+    /// Action the step runs; it is only fetched when the step runs, so
+    /// `script` is empty
+    pub uses: Option<String>,
+    /// Script passed to the shell, with strict mode and umask applied
+    pub script: String,
+    /// Built-in variables followed by the step's own `env`
+    pub env: BTreeMap<String, String>,
+    pub background: bool,
+    pub allow_failure: bool,
+}
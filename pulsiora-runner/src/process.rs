@@ -1,19 +1,717 @@
 // Process execution utilities
 // Future extension point for more sophisticated process management
 
+use crate::backend::StepOutput;
+use crate::encoding::ChunkDecoder;
+use crate::mask::SecretMasker;
+use encoding_rs::Encoding;
+use pulsiora_core::{ExecutionEvent, OutputStream, PipelineOptions, Step, DEFAULT_KILL_GRACE_SECS};
+use std::borrow::Cow;
+use std::io;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+#[derive(Default)]
 pub struct ProcessConfig {
     pub timeout: Option<std::time::Duration>,
     pub env: Vec<(String, String)>,
     pub working_directory: Option<std::path::PathBuf>,
 }
 
-impl Default for ProcessConfig {
-    fn default() -> Self {
+/// Build the script passed to the shell, applying strict mode and the step's umask first
+pub fn step_script(step: &Step, options: &PipelineOptions) -> String {
+    let mut script = String::new();
+    if options.set_e {
+        if let Some(strict) = strict_mode(&shell_program(options)) {
+            script.push_str(strict);
+            script.push('\n');
+        }
+    }
+    if let Some(umask) = step.umask.as_ref().filter(|_| cfg!(unix)) {
+        script.push_str(&format!("umask {}\n", umask));
+    }
+    script.push_str(&step.run);
+    script
+}
+
+/// Shell that runs step scripts on this host
+pub fn shell_program(options: &PipelineOptions) -> String {
+    if cfg!(target_os = "windows") {
+        match options.windows_shell.as_ref().or(options.shell.as_ref()) {
+            Some(shell) => shell.clone(),
+            None => default_windows_shell(),
+        }
+    } else {
+        options.shell.clone().unwrap_or_else(|| "sh".to_string())
+    }
+}
+
+/// PowerShell 7 when it is installed, else the Windows PowerShell that
+/// ships with Windows. `cmd` can't run multi-line scripts.
+fn default_windows_shell() -> String {
+    let shell = if which::which("pwsh").is_ok() { "pwsh" } else { "powershell" };
+    shell.to_string()
+}
+
+/// Build the command that runs `script` with the pipeline's shell
+pub fn shell_command(options: &PipelineOptions, script: &str) -> Command {
+    let shell = shell_program(options);
+    let mut command = Command::new(&shell);
+    match shell_name(&shell) {
+        "cmd" => {
+            command.arg("/C");
+        }
+        "powershell" | "pwsh" => {
+            command.args(["-NoProfile", "-NonInteractive", "-Command"]);
+        }
+        _ => {
+            command.arg("-c");
+        }
+    }
+    command.arg(script);
+    command
+}
+
+/// Command prefix that stops a script at its first failure; `pipefail`
+/// isn't POSIX, so plain `sh` only gets `-eu`. PowerShell 7.3+ also stops
+/// when a native program fails; older versions ignore the variable.
+fn strict_mode(shell: &str) -> Option<&'static str> {
+    match shell_name(shell) {
+        "bash" | "zsh" | "ksh" => Some("set -euo pipefail"),
+        "powershell" | "pwsh" => Some("$ErrorActionPreference = 'Stop'\n$PSNativeCommandUseErrorActionPreference = $true"),
+        "cmd" => None,
+        _ => Some("set -eu"),
+    }
+}
+
+/// File name of a shell program without directory or `.exe`
+fn shell_name(shell: &str) -> &str {
+    let name = shell.rsplit(['/', '\\']).next().unwrap_or(shell);
+    name.strip_suffix(".exe").unwrap_or(name)
+}
+
+/// Run the command as the step's configured user and group.
+/// A user name also selects that user's primary group unless `group` is set.
+#[cfg(unix)]
+pub fn apply_step_identity(command: &mut Command, step: &Step) -> io::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let mut gid = None;
+    if let Some(user) = &step.user {
+        let (uid, primary_gid) = lookup_user(user).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("unknown user: {}", user))
+        })?;
+        command.uid(uid);
+        gid = primary_gid;
+    }
+    if let Some(group) = &step.group {
+        gid = Some(lookup_group(group).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("unknown group: {}", group))
+        })?);
+    }
+    if let Some(gid) = gid {
+        command.gid(gid);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn apply_step_identity(_command: &mut Command, step: &Step) -> io::Result<()> {
+    if step.user.is_some() || step.group.is_some() || step.umask.is_some() {
+        tracing::warn!(
+            step_name = %step.name,
+            "user, group and umask are only supported on Unix hosts, ignoring"
+        );
+    }
+    Ok(())
+}
+
+/// Forwards a step's output as `OutputChunk` events while it is read
+#[derive(Clone)]
+pub struct OutputSink {
+    pub events: mpsc::Sender<ExecutionEvent>,
+    pub execution_id: Uuid,
+    pub step_name: String,
+    /// Encoding the step writes its output in
+    pub encoding: &'static Encoding,
+}
+
+impl OutputSink {
+    async fn send(&self, stream: OutputStream, data: String) {
+        // The receiver going away doesn't stop the step
+        let _ = self
+            .events
+            .send(ExecutionEvent::OutputChunk {
+                execution_id: self.execution_id,
+                step_name: self.step_name.clone(),
+                stream,
+                data,
+            })
+            .await;
+    }
+}
+
+/// Why a command was killed before it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    /// The execution's runtime limit passed
+    Deadline,
+    /// The execution was cancelled
+    Cancelled,
+}
+
+/// Run a command to completion like `Command::output` without blocking the
+/// async runtime, stopping it once `deadline` passes. Returns the output
+/// collected so far and whether the deadline was hit.
+pub async fn output_with_deadline(command: Command, deadline: Option<Instant>) -> io::Result<(StepOutput, bool)> {
+    let grace = Duration::from_secs(DEFAULT_KILL_GRACE_SECS);
+    let (output, interrupt) =
+        output_with_deadline_streaming(command, deadline, grace, &OutputCapture::default(), None, &CancellationToken::new())
+            .await?;
+    Ok((output, interrupt.is_some()))
+}
+
+/// Like `output_with_deadline`, also streaming the output to `sink` as it is
+/// read and stopping the command's process group when `cancel` fires. A
+/// stopped command gets `grace` to exit before it is killed. Each stream
+/// is kept as `capture` says.
+pub async fn output_with_deadline_streaming(
+    command: Command,
+    deadline: Option<Instant>,
+    grace: Duration,
+    capture: &OutputCapture,
+    sink: Option<OutputSink>,
+    cancel: &CancellationToken,
+) -> io::Result<(StepOutput, Option<Interrupt>)> {
+    let mut command = tokio::process::Command::from(command);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    own_process_group(&mut command);
+    let mut child = command.spawn()?;
+    let stdout = spawn_reader(child.stdout.take(), OutputStream::Stdout, capture, sink.clone());
+    let stderr = spawn_reader(child.stderr.take(), OutputStream::Stderr, capture, sink);
+    #[cfg(target_os = "linux")]
+    let monitor = child
+        .id()
+        .filter(|_| capture.measure_usage)
+        .map(crate::usage::UsageMonitor::start);
+
+    let interrupt = tokio::select! {
+        status = async {
+            // Usage is read between the process exiting and being reaped
+            #[cfg(target_os = "linux")]
+            if let Some(monitor) = &monitor {
+                monitor.exited().await;
+                return Ok(());
+            }
+            child.wait().await.map(drop)
+        } => {
+            status?;
+            None
+        }
+        _ = sleep_until(deadline) => Some(Interrupt::Deadline),
+        _ = cancel.cancelled() => Some(Interrupt::Cancelled),
+    };
+    if interrupt.is_some() {
+        terminate_process_group(&mut child, grace).await;
+    }
+    #[cfg(target_os = "linux")]
+    let usage = monitor.map(crate::usage::UsageMonitor::finish);
+    #[cfg(not(target_os = "linux"))]
+    let usage = None;
+    let status = child.wait().await?;
+
+    let killed = interrupt.is_some();
+    let stdout = collect_reader(stdout, killed).await;
+    let stderr = collect_reader(stderr, killed).await;
+    Ok((
+        StepOutput {
+            stdout: stdout.to_bytes(),
+            stderr: stderr.to_bytes(),
+            stdout_bytes: stdout.total_bytes(),
+            stderr_bytes: stderr.total_bytes(),
+            exit_code: status.code(),
+            success: status.success(),
+            usage,
+        },
+        interrupt,
+    ))
+}
+
+/// Sleep until `deadline`, or forever without one
+pub(crate) async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Start the command as the leader of a new process group, so processes it
+/// spawns can be killed with it
+pub(crate) fn own_process_group(command: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// How often a terminating process group is checked for having exited
+const TERMINATE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Stop a child started with `own_process_group` together with everything
+/// it spawned: ask them to exit (SIGTERM, or `taskkill` without `/F` on
+/// Windows) and kill whatever is left once `grace` has passed
+pub(crate) async fn terminate_process_group(child: &mut tokio::process::Child, grace: Duration) {
+    let Some(pid) = child.id() else { return };
+    if !stop_process_group(pid, grace, || matches!(child.try_wait(), Ok(Some(_)))).await {
+        kill_process_group(child, pid);
+    }
+}
+
+/// Ask the process group led by `pid` to exit and wait up to `grace` for
+/// it and its leader, as told by `leader_exited`, to be gone. Returns
+/// whether they were.
+pub(crate) async fn stop_process_group(pid: u32, grace: Duration, mut leader_exited: impl FnMut() -> bool) -> bool {
+    if grace.is_zero() {
+        return false;
+    }
+    #[cfg(unix)]
+    signal_process_group(pid, libc::SIGTERM);
+    #[cfg(windows)]
+    taskkill(pid, false);
+
+    let give_up_at = Instant::now() + grace;
+    while Instant::now() < give_up_at {
+        // The group outlives its leader while any process in it runs
+        if leader_exited() && !process_group_alive(pid) {
+            return true;
+        }
+        tokio::time::sleep(TERMINATE_POLL_INTERVAL).await;
+    }
+    false
+}
+
+/// Kill a child started with `own_process_group` together with its group
+fn kill_process_group(child: &mut tokio::process::Child, pid: u32) {
+    #[cfg(unix)]
+    signal_process_group(pid, libc::SIGKILL);
+    #[cfg(windows)]
+    taskkill(pid, true);
+    let _ = child.start_kill();
+}
+
+#[cfg(unix)]
+pub(crate) fn signal_process_group(pgid: u32, signal: libc::c_int) {
+    // SAFETY: killpg only sends a signal; a stale group id fails with ESRCH
+    unsafe {
+        libc::killpg(pgid as libc::pid_t, signal);
+    }
+}
+
+#[cfg(unix)]
+fn process_group_alive(pgid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the group exists
+    unsafe { libc::killpg(pgid as libc::pid_t, 0) == 0 }
+}
+
+/// Windows has no process groups to signal; `taskkill /T` walks the tree
+/// of processes the child started instead
+#[cfg(not(unix))]
+fn process_group_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(windows)]
+fn taskkill(pid: u32, force: bool) {
+    let mut command = Command::new("taskkill");
+    command.args(["/T", "/PID", &pid.to_string()]);
+    if force {
+        command.arg("/F");
+    }
+    let _ = command.stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
+
+/// Output kept in memory when the full log is written to disk
+pub const LOG_TAIL_BYTES: u64 = 64 * 1024;
+
+/// How a step's output is kept
+#[derive(Debug, Clone, Default)]
+pub struct OutputCapture {
+    /// Bytes kept in memory for each of stdout and stderr, see [`Capture`]
+    pub max_bytes: Option<u64>,
+    /// Write each stream in full to a log file next to this path, see
+    /// [`step_log_path`], and keep only its last `LOG_TAIL_BYTES` in memory
+    pub log_prefix: Option<PathBuf>,
+    /// Also measure the CPU time and memory of the process group, on Linux
+    pub measure_usage: bool,
+    /// Values replaced with `SECRET_MASK` wherever the output goes
+    pub secrets: Vec<String>,
+}
+
+impl OutputCapture {
+    fn buffer(&self) -> Capture {
+        match self.log_prefix {
+            Some(_) => Capture::tail(self.max_bytes.map_or(LOG_TAIL_BYTES, |max| max.min(LOG_TAIL_BYTES))),
+            None => Capture::new(self.max_bytes),
+        }
+    }
+}
+
+/// Log file holding one stream of a step's output, e.g. `<prefix>.stdout.log`
+pub fn step_log_path(prefix: &Path, stream: OutputStream) -> PathBuf {
+    let suffix = match stream {
+        OutputStream::Stdout => "stdout.log",
+        OutputStream::Stderr => "stderr.log",
+    };
+    let mut path = prefix.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Output read from a pipe, capped at a size limit. Output over the limit
+/// keeps its first and last halves, which hold what ran and how it ended,
+/// and drops the middle.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    head_limit: usize,
+    tail_limit: usize,
+    total_bytes: u64,
+}
+
+impl Capture {
+    pub fn new(max_bytes: Option<u64>) -> Self {
+        let limit = max_bytes.map_or(usize::MAX, |max| usize::try_from(max).unwrap_or(usize::MAX));
+        Self {
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            head_limit: limit - limit / 2,
+            tail_limit: limit / 2,
+            total_bytes: 0,
+        }
+    }
+
+    /// Keep only the last `max_bytes` of the output
+    pub fn tail(max_bytes: u64) -> Self {
         Self {
-            timeout: None,
-            env: Vec::new(),
-            working_directory: None,
+            head_limit: 0,
+            tail_limit: usize::try_from(max_bytes).unwrap_or(usize::MAX),
+            ..Self::new(None)
         }
     }
+
+    pub fn push(&mut self, mut bytes: &[u8]) {
+        self.total_bytes += bytes.len() as u64;
+        if self.head.len() < self.head_limit {
+            let taken = bytes.len().min(self.head_limit - self.head.len());
+            self.head.extend_from_slice(&bytes[..taken]);
+            bytes = &bytes[taken..];
+        }
+        self.tail.extend(bytes);
+        let excess = self.tail.len().saturating_sub(self.tail_limit);
+        self.tail.drain(..excess);
+    }
+
+    /// Bytes written to the pipe, including any that were dropped
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// The output kept, with a marker where the middle was dropped
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tail = self.tail.iter().copied();
+        let kept = (self.head.len() + self.tail.len()) as u64;
+        if kept == self.total_bytes {
+            return self.head.iter().copied().chain(tail).collect();
+        }
+        // Cut at character boundaries so the marker doesn't leave broken
+        // UTF-8 around it
+        let head = &self.head[..utf8_prefix_len(&self.head)];
+        let tail: Vec<u8> = tail.skip_while(|b| b & 0xC0 == 0x80).collect();
+        let omitted = self.total_bytes - (head.len() + tail.len()) as u64;
+        let mut bytes = head.to_vec();
+        bytes.extend_from_slice(format!("\n[... {} bytes of output omitted ...]\n", omitted).as_bytes());
+        bytes.extend(tail);
+        bytes
+    }
+}
+
+/// Length of `bytes` without a UTF-8 sequence cut off at its end
+fn utf8_prefix_len(bytes: &[u8]) -> usize {
+    for (back, &byte) in bytes.iter().rev().take(4).enumerate() {
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let start = bytes.len() - back - 1;
+        let width = match byte {
+            0xF0..=0xF7 => 4,
+            0xE0..=0xEF => 3,
+            0xC0..=0xDF => 2,
+            _ => 1,
+        };
+        return if back + 1 >= width { bytes.len() } else { start };
+    }
+    bytes.len()
+}
+
+/// Output buffer filled by a background task reading a child's pipe
+pub(crate) type Reader = (Arc<Mutex<Capture>>, tokio::task::JoinHandle<()>);
+
+/// How long a killed command's pipes are still read before giving up
+const READER_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub(crate) fn spawn_reader<R: AsyncRead + Unpin + Send + 'static>(
+    source: Option<R>,
+    stream: OutputStream,
+    capture: &OutputCapture,
+    sink: Option<OutputSink>,
+) -> Reader {
+    let buffer = Arc::new(Mutex::new(capture.buffer()));
+    let collected = buffer.clone();
+    let log_path = capture.log_prefix.as_deref().map(|prefix| step_log_path(prefix, stream));
+    let mut masker = SecretMasker::new(&capture.secrets);
+    let handle = tokio::spawn(async move {
+        let Some(mut source) = source else { return };
+        let mut log = match log_path {
+            Some(path) => open_log(&path).await,
+            None => None,
+        };
+        let mut chunk = [0u8; 8192];
+        let mut decoder = sink.as_ref().map(|sink| ChunkDecoder::new(sink.encoding));
+        loop {
+            let n = source.read(&mut chunk).await.unwrap_or(0);
+            // Secrets are masked before the output is kept anywhere; the
+            // end of the output flushes what the masker held back
+            let data = if n == 0 { Cow::Owned(masker.finish()) } else { masker.push(&chunk[..n]) };
+            if !data.is_empty() {
+                if let Ok(mut collected) = collected.lock() {
+                    collected.push(&data);
+                }
+                if let Some((path, file)) = &mut log {
+                    if let Err(e) = file.write_all(&data).await {
+                        tracing::warn!(path = %path.display(), error = %e, "Failed to write step log");
+                        log = None;
+                    }
+                }
+                if let (Some(sink), Some(decoder)) = (&sink, &mut decoder) {
+                    let text = decoder.decode(&data, false);
+                    if !text.is_empty() {
+                        sink.send(stream, text).await;
+                    }
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+        if let Some((_, file)) = &mut log {
+            let _ = file.flush().await;
+        }
+        if let (Some(sink), Some(decoder)) = (&sink, &mut decoder) {
+            let text = decoder.decode(&[], true);
+            if !text.is_empty() {
+                sink.send(stream, text).await;
+            }
+        }
+    });
+    (buffer, handle)
 }
 
+/// Create a step log file, logging rather than failing the step if it can't be
+async fn open_log(path: &Path) -> Option<(PathBuf, tokio::fs::File)> {
+    let created = async {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::File::create(path).await
+    };
+    match created.await {
+        Ok(file) => Some((path.to_path_buf(), file)),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to create step log");
+            None
+        }
+    }
+}
+
+/// Processes the shell started may keep the pipes open after a kill, so a
+/// killed command's reader only gets a moment to drain what was written
+pub(crate) async fn collect_reader((buffer, mut handle): Reader, killed: bool) -> Capture {
+    if !killed {
+        let _ = (&mut handle).await;
+    } else if tokio::time::timeout(READER_DRAIN_TIMEOUT, &mut handle).await.is_err() {
+        handle.abort();
+    }
+    snapshot_reader(&(buffer, handle))
+}
+
+/// Output a reader has collected so far
+pub(crate) fn snapshot_reader((buffer, _): &Reader) -> Capture {
+    buffer.lock().map(|capture| capture.clone()).unwrap_or_else(|poisoned| poisoned.into_inner().clone())
+}
+
+/// Resolve a user name or numeric uid to (uid, primary gid)
+#[cfg(unix)]
+fn lookup_user(user: &str) -> Option<(u32, Option<u32>)> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Some((uid, None));
+    }
+
+    let name = std::ffi::CString::new(user).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: all pointers refer to live, correctly sized buffers for the duration of the call
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwnam_r(name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    Some((pwd.pw_uid, Some(pwd.pw_gid)))
+}
+
+/// Resolve a group name or numeric gid
+#[cfg(unix)]
+fn lookup_group(group: &str) -> Option<u32> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Some(gid);
+    }
+
+    let name = std::ffi::CString::new(group).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: all pointers refer to live, correctly sized buffers for the duration of the call
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getgrnam_r(name.as_ptr(), &mut grp, buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    Some(grp.gr_gid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_script_strict_mode_per_shell() {
+        let step = Step::new("build".to_string(), "make".to_string());
+        let mut options = PipelineOptions {
+            set_e: true,
+            ..Default::default()
+        };
+
+        options.shell = Some("/usr/bin/bash".to_string());
+        assert_eq!(step_script(&step, &options), "set -euo pipefail\nmake");
+        options.shell = Some("sh".to_string());
+        assert_eq!(step_script(&step, &options), "set -eu\nmake");
+        options.shell = Some("cmd.exe".to_string());
+        assert_eq!(step_script(&step, &options), "make");
+        options.shell = Some("pwsh".to_string());
+        assert_eq!(
+            step_script(&step, &options),
+            "$ErrorActionPreference = 'Stop'\n$PSNativeCommandUseErrorActionPreference = $true\nmake"
+        );
+
+        options.set_e = false;
+        assert_eq!(step_script(&step, &options), "make");
+    }
+
+    #[test]
+    fn test_shell_command_arguments_per_shell() {
+        let args = |shell: &str| {
+            let options = PipelineOptions {
+                shell: Some(shell.to_string()),
+                ..Default::default()
+            };
+            let command = shell_command(&options, "make");
+            command.get_args().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>()
+        };
+        assert_eq!(args("bash"), ["-c", "make"]);
+        assert_eq!(args("cmd"), ["/C", "make"]);
+        assert_eq!(args("C:\\Program Files\\PowerShell\\7\\pwsh.exe"), ["-NoProfile", "-NonInteractive", "-Command", "make"]);
+        assert_eq!(args("powershell"), ["-NoProfile", "-NonInteractive", "-Command", "make"]);
+    }
+
+    #[test]
+    fn test_shell_program_prefers_windows_shell_on_windows() {
+        let options = PipelineOptions {
+            shell: Some("bash".to_string()),
+            windows_shell: Some("cmd".to_string()),
+            ..Default::default()
+        };
+        let expected = if cfg!(target_os = "windows") { "cmd" } else { "bash" };
+        assert_eq!(shell_program(&options), expected);
+        if !cfg!(target_os = "windows") {
+            assert_eq!(shell_program(&PipelineOptions::default()), "sh");
+        }
+    }
+
+    #[test]
+    fn test_capture_keeps_head_and_tail() {
+        let mut capture = Capture::new(Some(8));
+        capture.push(b"abc");
+        assert_eq!(capture.to_bytes(), b"abc");
+        capture.push(b"defgh");
+        assert_eq!(capture.to_bytes(), b"abcdefgh");
+
+        capture.push(b"ijklmnop");
+        assert_eq!(capture.total_bytes(), 16);
+        assert_eq!(String::from_utf8(capture.to_bytes()).unwrap(), "abcd\n[... 8 bytes of output omitted ...]\nmnop");
+
+        // Multi-byte characters aren't split at the cut
+        let mut capture = Capture::new(Some(8));
+        capture.push("aé€bcdefé€".as_bytes());
+        let text = String::from_utf8(capture.to_bytes()).unwrap();
+        assert_eq!(text, "aé\n[... 10 bytes of output omitted ...]\n€");
+
+        let mut unlimited = Capture::new(None);
+        unlimited.push(&[b'x'; 10_000]);
+        assert_eq!(unlimited.to_bytes().len(), 10_000);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stopped_command_gets_grace_period() {
+        let options = PipelineOptions::default();
+        let deadline = Some(Instant::now() + Duration::from_millis(300));
+
+        // A step that cleans up on SIGTERM finishes its cleanup
+        let command = shell_command(&options, "trap 'echo cleaned up; exit 3' TERM; sleep 10 & wait");
+        let (output, interrupt) =
+            output_with_deadline_streaming(command, deadline, Duration::from_secs(5), &OutputCapture::default(), None, &CancellationToken::new())
+                .await
+                .unwrap();
+        assert_eq!(interrupt, Some(Interrupt::Deadline));
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "cleaned up");
+        assert_eq!(output.exit_code, Some(3));
+
+        // One that ignores it is killed once the grace period is over
+        let started = Instant::now();
+        let command = shell_command(&options, "trap '' TERM; sleep 10");
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let (output, interrupt) =
+            output_with_deadline_streaming(command, None, Duration::from_millis(300), &OutputCapture::default(), None, &cancel)
+                .await
+                .unwrap();
+        assert_eq!(interrupt, Some(Interrupt::Cancelled));
+        assert_eq!(output.exit_code, None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
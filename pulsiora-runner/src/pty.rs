@@ -0,0 +1,201 @@
+// Steps with `tty: true` on the host run under a pseudo-terminal, so tools
+// that look for a terminal print colors and progress the way they would for
+// a person. A terminal has a single output: stderr is captured as stdout.
+
+use crate::backend::StepOutput;
+use crate::process::{
+    collect_reader, signal_process_group, sleep_until, spawn_reader, stop_process_group, Interrupt,
+    OutputCapture, OutputSink,
+};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use pulsiora_core::OutputStream;
+use std::io::{self, Read};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use tokio_util::sync::CancellationToken;
+
+/// Size of the terminal steps see
+const PTY_SIZE: PtySize = PtySize {
+    rows: 24,
+    cols: 120,
+    pixel_width: 0,
+    pixel_height: 0,
+};
+
+/// Terminal type for steps whose environment doesn't set `TERM`
+const DEFAULT_TERM: &str = "xterm-256color";
+
+/// How often the step's shell is checked for having exited
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Like `output_with_deadline_streaming`, with the command's stdin, stdout
+/// and stderr connected to a new pseudo-terminal. Nothing is typed into it,
+/// so a step waiting for input waits until it is stopped.
+pub(crate) async fn output_with_pty(
+    command: Command,
+    deadline: Option<Instant>,
+    grace: Duration,
+    capture: &OutputCapture,
+    sink: Option<OutputSink>,
+    cancel: &CancellationToken,
+) -> io::Result<(StepOutput, Option<Interrupt>)> {
+    let pty = native_pty_system().openpty(PTY_SIZE).map_err(io::Error::other)?;
+    keep_newlines(pty.master.as_ref());
+    // The child is the leader of a new session, and so of its process group
+    let mut child = pty.slave.spawn_command(command_builder(&command)).map_err(io::Error::other)?;
+    // The terminal only reports the end of output once every process holding
+    // it has closed it, this one included
+    drop(pty.slave);
+    let reader = pty.master.try_clone_reader().map_err(io::Error::other)?;
+    let output = spawn_reader(Some(pipe_reader(reader)), OutputStream::Stdout, capture, sink);
+    let pid = child.process_id();
+    #[cfg(target_os = "linux")]
+    let monitor = pid.filter(|_| capture.measure_usage).map(crate::usage::UsageMonitor::start);
+
+    let interrupt = tokio::select! {
+        status = async {
+            // Usage is read between the process exiting and being reaped
+            #[cfg(target_os = "linux")]
+            if let Some(monitor) = &monitor {
+                monitor.exited().await;
+                return Ok(());
+            }
+            wait_exited(child.as_mut()).await
+        } => {
+            status?;
+            None
+        }
+        _ = sleep_until(deadline) => Some(Interrupt::Deadline),
+        _ = cancel.cancelled() => Some(Interrupt::Cancelled),
+    };
+    if let (Some(_), Some(pid)) = (interrupt, pid) {
+        if !stop_process_group(pid, grace, || matches!(child.try_wait(), Ok(Some(_)))).await {
+            signal_process_group(pid, libc::SIGKILL);
+            let _ = child.kill();
+        }
+    }
+    #[cfg(target_os = "linux")]
+    let usage = monitor.map(crate::usage::UsageMonitor::finish);
+    #[cfg(not(target_os = "linux"))]
+    let usage = None;
+    let status = tokio::task::spawn_blocking(move || child.wait()).await.map_err(io::Error::other)??;
+
+    let output = collect_reader(output, interrupt.is_some()).await;
+    // A killed process is reported as exit code 1 along with the signal
+    let exit_code = match status.signal() {
+        Some(_) => None,
+        None => i32::try_from(status.exit_code()).ok(),
+    };
+    Ok((
+        StepOutput {
+            stdout: output.to_bytes(),
+            stdout_bytes: output.total_bytes(),
+            exit_code,
+            success: status.success(),
+            usage,
+            ..Default::default()
+        },
+        interrupt,
+    ))
+}
+
+/// Poll for the child to exit; portable-pty only offers a blocking wait
+async fn wait_exited(child: &mut (dyn Child + Send + Sync)) -> io::Result<()> {
+    while child.try_wait()?.is_none() {
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// The same program, arguments, directory and environment for portable-pty,
+/// which starts from the runner's own environment like `Command` does
+fn command_builder(command: &Command) -> CommandBuilder {
+    let mut builder = CommandBuilder::new(command.get_program());
+    builder.args(command.get_args());
+    if let Some(dir) = command.get_current_dir() {
+        builder.cwd(dir);
+    }
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => builder.env(key, value),
+            None => builder.env_remove(key),
+        }
+    }
+    if builder.get_env("TERM").is_none() {
+        builder.env("TERM", DEFAULT_TERM);
+    }
+    builder
+}
+
+/// Terminals turn `\n` into `\r\n` by default; keep output as the step wrote it
+fn keep_newlines(master: &dyn MasterPty) {
+    let Some(fd) = master.as_raw_fd() else { return };
+    // SAFETY: the termios struct is only read and written through a live descriptor
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) == 0 {
+            termios.c_oflag &= !libc::ONLCR;
+            libc::tcsetattr(fd, libc::TCSANOW, &termios);
+        }
+    }
+}
+
+/// Reading the terminal blocks, so a thread reads it into an async pipe.
+/// On Linux the read fails with EIO once the terminal is closed.
+fn pipe_reader(mut reader: Box<dyn Read + Send>) -> DuplexStream {
+    let (mut writer, pipe) = tokio::io::duplex(8192);
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = reader.read(&mut chunk) {
+            if n == 0 || runtime.block_on(writer.write_all(&chunk[..n])).is_err() {
+                break;
+            }
+        }
+    });
+    pipe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::shell_command;
+    use pulsiora_core::PipelineOptions;
+
+    #[tokio::test]
+    async fn test_output_with_pty() {
+        let options = PipelineOptions::default();
+        let mut command = shell_command(&options, "[ -t 1 ] && echo \"on a terminal: $TERM\"; echo oops >&2; exit 4");
+        command.env_remove("TERM");
+        let (output, interrupt) = output_with_pty(
+            command,
+            None,
+            Duration::from_secs(5),
+            &OutputCapture::default(),
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(interrupt, None);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "on a terminal: xterm-256color\noops\n");
+        assert!(output.stderr.is_empty());
+        assert_eq!(output.exit_code, Some(4));
+        assert!(!output.success);
+
+        let deadline = Some(Instant::now() + Duration::from_millis(200));
+        let (output, interrupt) = output_with_pty(
+            shell_command(&options, "read line"),
+            deadline,
+            Duration::from_secs(5),
+            &OutputCapture::default(),
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(interrupt, Some(Interrupt::Deadline));
+        assert_eq!(output.exit_code, None);
+    }
+}
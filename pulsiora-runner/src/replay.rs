@@ -0,0 +1,171 @@
+// Replay bundles capture everything needed to re-run an execution locally:
+// the fully resolved pipeline (commands and env after `vars` and `extends`),
+// the env its steps got apart from secrets, the triggering event and the
+// revision it ran against.
+
+use crate::executor::PipelineExecutor;
+use crate::workspace::is_commit_sha;
+use chrono::{DateTime, Utc};
+use pulsiora_core::{GitEvent, Pipeline, PipelineExecution, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayBundle {
+    /// Execution the bundle was recorded from
+    pub execution_id: Uuid,
+    pub pipeline: Pipeline,
+    pub git_event: GitEvent,
+    /// Non-secret variables the steps ran with: the repository's env and the
+    /// built-in `PULSE_*` variables of the original execution
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl ReplayBundle {
+    pub fn new(execution_id: Uuid, pipeline: Pipeline, git_event: GitEvent) -> Self {
+        Self {
+            execution_id,
+            pipeline,
+            git_event,
+            env: BTreeMap::new(),
+            recorded_at: Utc::now(),
+        }
+    }
+
+    /// Record the env the steps ran with, e.g. from `PipelineExecutor::resolved_env`
+    pub fn with_env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Revision the original execution ran against, if it was a real commit
+    pub fn revision(&self) -> Option<&str> {
+        self.git_event
+            .commit_sha
+            .as_deref()
+            .filter(|sha| is_commit_sha(sha))
+    }
+
+    /// Re-run the recorded pipeline with the same commands, env and event,
+    /// stopping when `cancel` fires
+    pub async fn replay(&self, executor: &PipelineExecutor, cancel: CancellationToken) -> Result<PipelineExecution> {
+        executor
+            .clone()
+            .with_env(self.env.clone())
+            .execute_with_cancel(Uuid::new_v4(), &self.pipeline, &self.git_event, cancel)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::{GitEventType, GitTriggers, PipelineStatus, Repository, Step};
+
+    fn create_test_event(commit_sha: Option<&str>, clone_url: &str) -> GitEvent {
+        GitEvent {
+            event_type: GitEventType::Push,
+            repository: Repository {
+                owner: "test".to_string(),
+                name: "repo".to_string(),
+                full_name: "test/repo".to_string(),
+                clone_url: clone_url.to_string(),
+                default_branch: "main".to_string(),
+            },
+            branch: Some("main".to_string()),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: commit_sha.map(String::from),
+            commit_message: None,
+            sender: "test".to_string(),
+            inputs: Default::default(),
+        }
+    }
+
+    fn create_test_pipeline(steps: Vec<Step>) -> Pipeline {
+        Pipeline {
+            name: "test".to_string(),
+            version: "1.0".to_string(),
+            description: None,
+            labels: vec![],
+            owners: vec![],
+            environment: None,
+            triggers: GitTriggers {
+                on_push: true,
+                ..Default::default()
+            }
+            .into(),
+            steps,
+            vars: Default::default(),
+            options: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_revision_ignores_placeholders() {
+        let pipeline = create_test_pipeline(vec![]);
+        let bundle = ReplayBundle::new(
+            Uuid::new_v4(),
+            pipeline.clone(),
+            create_test_event(Some("manual-execution"), ""),
+        );
+        assert_eq!(bundle.revision(), None);
+
+        let bundle = ReplayBundle::new(Uuid::new_v4(), pipeline, create_test_event(Some("0123abcd"), ""));
+        assert_eq!(bundle.revision(), Some("0123abcd"));
+    }
+
+    #[tokio::test]
+    async fn test_replay_reruns_recorded_steps() {
+        let step = Step::new("greet".to_string(), "echo $GREETING".to_string()).with_env("GREETING", "hello");
+        let bundle = ReplayBundle::new(
+            Uuid::new_v4(),
+            create_test_pipeline(vec![step]),
+            create_test_event(None, ""),
+        );
+
+        // Bundles travel through the API as JSON
+        let json = serde_json::to_string(&bundle).unwrap();
+        let bundle: ReplayBundle = serde_json::from_str(&json).unwrap();
+
+        let execution = bundle
+            .replay(&PipelineExecutor::new(), CancellationToken::new())
+            .await
+            .unwrap();
+        assert_ne!(execution.id, bundle.execution_id);
+        assert_eq!(execution.status, PipelineStatus::Success);
+        assert_eq!(execution.step_results[0].stdout.trim(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_replay_uses_recorded_env() {
+        let step = Step::new(
+            "show".to_string(),
+            "echo \"$REGION $PULSE_EXECUTION_ID $PULSE_BRANCH\"".to_string(),
+        );
+        let pipeline = create_test_pipeline(vec![step]);
+        let event = create_test_event(None, "");
+        let execution_id = Uuid::new_v4();
+        let original = PipelineExecutor::new().with_env(BTreeMap::from([("REGION".to_string(), "eu-west-1".to_string())]));
+        let env = original.resolved_env(execution_id, &pipeline, &event);
+        assert!(!env.contains_key("PULSE_WORKSPACE"));
+        let bundle = ReplayBundle::new(execution_id, pipeline, event).with_env(env);
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        let bundle: ReplayBundle = serde_json::from_str(&json).unwrap();
+
+        let execution = bundle
+            .replay(&PipelineExecutor::new(), CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            execution.step_results[0].stdout.trim(),
+            format!("eu-west-1 {} main", execution_id)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+// Steps on the runner host, confined with bubblewrap or firejail. Steps of
+// untrusted Pulsefiles otherwise get a shell with everything the runner can
+// read, reach and write. In the sandbox they can only write to their
+// workspace and `/tmp`, reach the network if the repository's policy allows
+// it, and see none of the runner's own environment variables.
+
+use crate::backend::{run_on_host, ExecutionBackend, StepContext, StepOutput};
+use crate::process::{apply_step_identity, shell_command, Interrupt, OutputSink};
+use async_trait::async_trait;
+use pulsiora_core::SandboxPolicy;
+use std::io;
+use std::process::Command;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// System directories bubblewrap shows to steps, read-only, where they exist
+const SYSTEM_PATHS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt"];
+
+/// `PATH` for steps when the runner has none
+const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/local/sbin:/usr/sbin:/sbin";
+
+/// Program that builds the sandbox
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxTool {
+    /// `bwrap`: steps see only system directories, their workspace and
+    /// `read_only_paths`
+    Bubblewrap,
+    /// `firejail`: the host's filesystem stays visible but is read-only
+    /// outside the workspace
+    Firejail,
+}
+
+impl SandboxTool {
+    /// Parse a tool name: `bubblewrap` (or `bwrap`) or `firejail`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "bubblewrap" | "bwrap" => Some(Self::Bubblewrap),
+            "firejail" => Some(Self::Firejail),
+            _ => None,
+        }
+    }
+
+    /// Default program for the tool
+    pub fn program(&self) -> &'static str {
+        match self {
+            Self::Bubblewrap => "bwrap",
+            Self::Firejail => "firejail",
+        }
+    }
+}
+
+/// Runs steps with the pipeline's shell on the runner host, inside a sandbox
+#[derive(Debug, Clone)]
+pub struct SandboxBackend {
+    tool: SandboxTool,
+    program: String,
+    policy: SandboxPolicy,
+}
+
+impl SandboxBackend {
+    pub fn new(tool: SandboxTool) -> Self {
+        Self {
+            tool,
+            program: tool.program().to_string(),
+            policy: SandboxPolicy::default(),
+        }
+    }
+
+    /// Network access and extra paths for the steps, usually the repository's
+    pub fn with_policy(mut self, policy: SandboxPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Use another path or wrapper for the tool
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    pub fn tool(&self) -> SandboxTool {
+        self.tool
+    }
+
+    /// Whether the tool's program can be found on `PATH`
+    pub fn is_available(&self) -> bool {
+        which::which(&self.program).is_ok()
+    }
+
+    /// Arguments placing a command in the sandbox, before the command itself
+    fn sandbox_args(&self, work_dir: &str) -> Vec<String> {
+        let mut args: Vec<String> = Vec::new();
+        let mut push = |parts: &[&str]| args.extend(parts.iter().map(|part| part.to_string()));
+        match self.tool {
+            SandboxTool::Bubblewrap => {
+                push(&["--die-with-parent", "--unshare-all"]);
+                if self.policy.network {
+                    push(&["--share-net"]);
+                }
+                for path in SYSTEM_PATHS.iter().copied().chain(self.policy.read_only_paths.iter().map(String::as_str)) {
+                    push(&["--ro-bind-try", path, path]);
+                }
+                push(&["--bind", work_dir, work_dir, "--chdir", work_dir]);
+                push(&["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"]);
+            }
+            SandboxTool::Firejail => {
+                push(&["--quiet", "--noprofile", "--nonewprivs", "--caps.drop=all", "--seccomp"]);
+                push(&["--private-tmp", "--private-dev", "--read-only=/"]);
+                args.push(format!("--read-write={}", work_dir));
+                if !self.policy.network {
+                    args.push("--net=none".to_string());
+                }
+            }
+        }
+        args.push("--".to_string());
+        args
+    }
+}
+
+#[async_trait]
+impl ExecutionBackend for SandboxBackend {
+    fn name(&self) -> &'static str {
+        "sandbox"
+    }
+
+    fn measures_usage(&self) -> bool {
+        true
+    }
+
+    fn command(&self, ctx: &StepContext) -> io::Result<Command> {
+        let work_dir = std::path::absolute(ctx.work_dir)?;
+        let work_dir = work_dir
+            .to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "workspace path is not valid UTF-8"))?;
+        let shell = shell_command(ctx.options, ctx.script);
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.sandbox_args(work_dir))
+            .arg(shell.get_program())
+            .args(shell.get_args())
+            .current_dir(work_dir)
+            // The runner's environment may hold its own credentials
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_else(|| DEFAULT_PATH.into()))
+            .env("HOME", "/tmp")
+            .envs(ctx.env);
+        apply_step_identity(&mut command, ctx.step)?;
+        Ok(command)
+    }
+
+    async fn run(
+        &self,
+        ctx: &StepContext<'_>,
+        deadline: Option<Instant>,
+        sink: Option<OutputSink>,
+        cancel: &CancellationToken,
+    ) -> io::Result<(StepOutput, Option<Interrupt>)> {
+        run_on_host(self.command(ctx)?, ctx, deadline, sink, cancel).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::OutputCapture;
+    use pulsiora_core::{PipelineOptions, Step};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    fn args(command: &Command) -> Vec<String> {
+        command.get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn test_sandbox_command() {
+        let step = Step::new("test".to_string(), "make test".to_string());
+        let options = PipelineOptions::default();
+        let env = BTreeMap::from([("CI".to_string(), "true".to_string())]);
+        let ctx = StepContext {
+            execution_id: Uuid::new_v4(),
+            step_index: 0,
+            step: &step,
+            options: &options,
+            script: "make test",
+            work_dir: Path::new("/srv/ws/1"),
+            env: &env,
+            output: OutputCapture::default(),
+        };
+
+        let backend = SandboxBackend::new(SandboxTool::Bubblewrap).with_policy(SandboxPolicy {
+            read_only_paths: vec!["/opt/toolchain".to_string()],
+            ..Default::default()
+        });
+        let command = backend.command(&ctx).unwrap();
+        assert_eq!(command.get_program(), "bwrap");
+        let bwrap_args = args(&command);
+        assert_eq!(bwrap_args[..2], ["--die-with-parent", "--unshare-all"]);
+        assert!(!bwrap_args.contains(&"--share-net".to_string()));
+        assert!(bwrap_args.windows(3).any(|a| a == ["--ro-bind-try", "/opt/toolchain", "/opt/toolchain"]));
+        assert!(bwrap_args.windows(3).any(|a| a == ["--bind", "/srv/ws/1", "/srv/ws/1"]));
+        assert_eq!(bwrap_args[bwrap_args.len() - 4..], ["--", "sh", "-c", "make test"]);
+        let envs: Vec<_> = command.get_envs().map(|(key, _)| key.to_string_lossy().into_owned()).collect();
+        assert_eq!(envs, ["CI", "HOME", "PATH"]);
+
+        let backend = SandboxBackend::new(SandboxTool::Firejail).with_policy(SandboxPolicy {
+            network: true,
+            ..Default::default()
+        });
+        let firejail_args = args(&backend.command(&ctx).unwrap());
+        assert!(firejail_args.contains(&"--read-write=/srv/ws/1".to_string()));
+        assert!(!firejail_args.contains(&"--net=none".to_string()));
+        assert_eq!(SandboxTool::parse("bwrap"), Some(SandboxTool::Bubblewrap));
+        assert_eq!(SandboxTool::parse("nsjail"), None);
+    }
+}
@@ -0,0 +1,125 @@
+// Background service steps: started by the executor, kept running while the
+// following steps use them, and stopped when the pipeline finishes.
+
+use crate::process::{
+    collect_reader, own_process_group, snapshot_reader, spawn_reader, terminate_process_group, OutputCapture, OutputSink,
+    Reader,
+};
+use crate::encoding::DecodedOutput;
+use encoding_rs::Encoding;
+use pulsiora_core::{OutputStream, ReadinessCheck};
+use std::io;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Delay between readiness probes
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time allowed for a single readiness probe request
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A background step's running process and its captured output
+pub struct BackgroundService {
+    pub step_name: String,
+    /// Position of the step's result in the execution, updated with the
+    /// service's full output when it is stopped
+    pub result_index: usize,
+    child: tokio::process::Child,
+    stdout: Reader,
+    stderr: Reader,
+    encoding: &'static Encoding,
+}
+
+impl BackgroundService {
+    /// Start the process; its output, written in `encoding`, is also
+    /// streamed to `sink` while it runs
+    pub fn spawn(
+        step_name: &str,
+        command: Command,
+        result_index: usize,
+        encoding: &'static Encoding,
+        capture: &OutputCapture,
+        sink: Option<OutputSink>,
+    ) -> io::Result<Self> {
+        let mut command = tokio::process::Command::from(command);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        own_process_group(&mut command);
+        let mut child = command.spawn()?;
+        let stdout = spawn_reader(child.stdout.take(), OutputStream::Stdout, capture, sink.clone());
+        let stderr = spawn_reader(child.stderr.take(), OutputStream::Stderr, capture, sink);
+        Ok(Self {
+            step_name: step_name.to_string(),
+            result_index,
+            child,
+            stdout,
+            stderr,
+            encoding,
+        })
+    }
+
+    /// Output written so far
+    pub fn output(&self) -> DecodedOutput {
+        DecodedOutput::decode_captured(&snapshot_reader(&self.stdout), &snapshot_reader(&self.stderr), self.encoding)
+    }
+
+    /// Wait until the readiness check passes. Without an `http` probe the
+    /// service is ready once started. Gives up at the check's timeout or at
+    /// `deadline`, whichever comes first.
+    pub async fn wait_ready(
+        &mut self,
+        check: Option<&ReadinessCheck>,
+        deadline: Option<Instant>,
+    ) -> Result<(), String> {
+        let check = check.cloned().unwrap_or_default();
+        let mut give_up_at = Instant::now() + Duration::from_secs(check.timeout_secs);
+        if let Some(deadline) = deadline {
+            give_up_at = give_up_at.min(deadline);
+        }
+
+        let Some(url) = check.http else {
+            return self.ensure_running();
+        };
+        let client = reqwest::Client::builder()
+            .timeout(PROBE_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create readiness probe client: {}", e))?;
+
+        loop {
+            self.ensure_running()?;
+            if let Ok(response) = client.get(&url).send().await {
+                if response.status().is_success() {
+                    return Ok(());
+                }
+            }
+            if Instant::now() >= give_up_at {
+                return Err(format!(
+                    "Service did not become ready at {} within {}s",
+                    url, check.timeout_secs
+                ));
+            }
+            tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+        }
+    }
+
+    fn ensure_running(&mut self) -> Result<(), String> {
+        match self.child.try_wait() {
+            Ok(Some(status)) => Err(format!("Service exited with {} before becoming ready", status)),
+            Ok(None) => Ok(()),
+            Err(e) => Err(format!("Failed to check service process: {}", e)),
+        }
+    }
+
+    /// Stop the service, giving it `grace` to shut down, and return
+    /// everything it wrote
+    pub async fn stop(mut self, grace: Duration) -> DecodedOutput {
+        terminate_process_group(&mut self.child, grace).await;
+        let _ = self.child.wait().await;
+        let stdout = collect_reader(self.stdout, true).await;
+        let stderr = collect_reader(self.stderr, true).await;
+        DecodedOutput::decode_captured(&stdout, &stderr, self.encoding)
+    }
+}
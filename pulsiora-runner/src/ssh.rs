@@ -0,0 +1,224 @@
+// Steps with `runner: "ssh://user@host"` run on that host over SSH instead of
+// on the runner. Connections are pooled with OpenSSH's ControlMaster, so the
+// steps of an execution share one authenticated connection per host.
+
+use crate::backend::{container_shell, ExecutionBackend, StepContext};
+use pulsiora_core::SshTarget;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// How the runner connects to remote hosts
+#[derive(Debug, Clone)]
+pub struct SshOptions {
+    /// Private key to authenticate with; otherwise ssh's defaults and agent
+    pub identity_file: Option<PathBuf>,
+    /// Known hosts to verify host keys against; otherwise ssh's defaults
+    pub known_hosts_file: Option<PathBuf>,
+    /// Directory holding the pooled connections' control sockets
+    pub control_dir: PathBuf,
+    /// How long an idle pooled connection stays open
+    pub control_persist_secs: u64,
+}
+
+impl Default for SshOptions {
+    fn default() -> Self {
+        Self {
+            identity_file: None,
+            known_hosts_file: None,
+            control_dir: std::env::temp_dir().join("pulse-ssh"),
+            control_persist_secs: 60,
+        }
+    }
+}
+
+/// Runs steps on the host named by their `runner` with the `ssh` client. The
+/// step's script runs with the remote user's shell in their login directory,
+/// with the step's environment passed along.
+#[derive(Debug, Clone)]
+pub struct SshBackend {
+    /// SSH client to invoke
+    program: String,
+    options: SshOptions,
+}
+
+impl SshBackend {
+    pub fn new(options: SshOptions) -> Self {
+        Self {
+            program: "ssh".to_string(),
+            options,
+        }
+    }
+
+    /// Use another OpenSSH-compatible client
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Create the control socket directory, readable only by the runner
+    fn control_dir(&self) -> io::Result<&PathBuf> {
+        let dir = &self.options.control_dir;
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(dir)?;
+        Ok(dir)
+    }
+}
+
+impl Default for SshBackend {
+    fn default() -> Self {
+        Self::new(SshOptions::default())
+    }
+}
+
+impl ExecutionBackend for SshBackend {
+    fn name(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn command(&self, ctx: &StepContext) -> io::Result<Command> {
+        let runner = ctx.step.runner.as_deref().unwrap_or_default();
+        let target = SshTarget::parse(runner)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        if ctx.step.user.is_some() || ctx.step.group.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "`user` and `group` aren't supported on ssh runners; set the user in the runner URL",
+            ));
+        }
+        let control_dir = self.control_dir()?;
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(["-o", "BatchMode=yes", "-o", "ControlMaster=auto"])
+            .arg("-o")
+            .arg(format!("ControlPath={}", control_dir.join("%C").display()))
+            .arg("-o")
+            .arg(format!(
+                "ControlPersist={}",
+                self.options.control_persist_secs
+            ));
+        if let Some(identity) = &self.options.identity_file {
+            command.arg("-i").arg(identity);
+        }
+        if let Some(known_hosts) = &self.options.known_hosts_file {
+            command
+                .arg("-o")
+                .arg(format!("UserKnownHostsFile={}", known_hosts.display()));
+        }
+        if let Some(port) = target.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if let Some(user) = &target.user {
+            command.arg("-l").arg(user);
+        }
+        if ctx.step.tty {
+            // Forced, as ssh's own stdin isn't a terminal
+            command.arg("-tt");
+        }
+        command.arg(&target.host).arg(remote_command(ctx));
+        Ok(command)
+    }
+}
+
+/// The command line the remote shell runs: the step's script under `env`
+fn remote_command(ctx: &StepContext) -> String {
+    let mut line = String::from("env");
+    for (key, value) in ctx.env {
+        // The runner's workspace doesn't exist on the remote host
+        if key == "PULSE_WORKSPACE" {
+            continue;
+        }
+        line.push(' ');
+        line.push_str(&shell_quote(&format!("{}={}", key, value)));
+    }
+    line.push(' ');
+    line.push_str(&shell_quote(&container_shell(ctx.options)));
+    line.push_str(" -c ");
+    line.push_str(&shell_quote(ctx.script));
+    line
+}
+
+/// Quote a word for a POSIX shell
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::{PipelineOptions, Step};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_ssh_command() {
+        let control_dir = std::env::temp_dir().join(format!("pulsiora-ssh-{}", Uuid::new_v4()));
+        let backend = SshBackend::new(SshOptions {
+            identity_file: Some(PathBuf::from("/etc/pulse/id_ed25519")),
+            control_dir: control_dir.clone(),
+            ..Default::default()
+        });
+        let step = Step::new("deploy".to_string(), "echo 'hi'".to_string())
+            .with_runner("ssh://deploy@prod-box:2222");
+        let options = PipelineOptions::default();
+        let env = BTreeMap::from([
+            ("CI".to_string(), "true".to_string()),
+            ("PULSE_WORKSPACE".to_string(), "/srv/ws/1".to_string()),
+        ]);
+        let ctx = StepContext {
+            execution_id: Uuid::new_v4(),
+            step_index: 0,
+            step: &step,
+            options: &options,
+            script: "echo 'hi'",
+            work_dir: Path::new("/srv/ws/1"),
+            env: &env,
+            output: Default::default(),
+        };
+
+        let command = backend.command(&ctx).unwrap();
+        assert_eq!(command.get_program(), "ssh");
+        let args: Vec<_> = command
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        let control_path = format!("ControlPath={}", control_dir.join("%C").display());
+        let expected = [
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            "ControlMaster=auto",
+            "-o",
+            &control_path,
+            "-o",
+            "ControlPersist=60",
+            "-i",
+            "/etc/pulse/id_ed25519",
+            "-p",
+            "2222",
+            "-l",
+            "deploy",
+            "prod-box",
+            r"env 'CI=true' 'sh' -c 'echo '\''hi'\'''",
+        ];
+        assert_eq!(args, expected);
+        assert!(control_dir.is_dir());
+        let _ = std::fs::remove_dir_all(&control_dir);
+
+        let mut as_root = step.clone();
+        as_root.user = Some("root".to_string());
+        let ctx = StepContext {
+            step: &as_root,
+            ..ctx
+        };
+        assert_eq!(
+            backend.command(&ctx).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
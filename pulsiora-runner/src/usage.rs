@@ -0,0 +1,161 @@
+// CPU time and memory of step processes on Linux. CPU time is read from the
+// step's shell once it has exited but before it is reaped, and includes the
+// commands the shell waited for. Memory is the peak resident set of the
+// step's process group, sampled while it runs.
+
+use pulsiora_core::ResourceUsage;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the process group's memory is sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches one step process, started with its own process group
+pub(crate) struct UsageMonitor {
+    pid: u32,
+    peak_rss: Arc<AtomicU64>,
+    sampler: tokio::task::JoinHandle<()>,
+}
+
+impl UsageMonitor {
+    pub(crate) fn start(pid: u32) -> Self {
+        // Sampled right away too, as short steps may exit before the sampler runs
+        let peak_rss = Arc::new(AtomicU64::new(group_rss_bytes(pid)));
+        let peak = peak_rss.clone();
+        let sampler = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(SAMPLE_INTERVAL).await;
+                let rss = tokio::task::spawn_blocking(move || group_rss_bytes(pid)).await.unwrap_or(0);
+                peak.fetch_max(rss, Ordering::Relaxed);
+            }
+        });
+        Self { pid, peak_rss, sampler }
+    }
+
+    /// Wait for the process to exit without reaping it, so `finish` can
+    /// still read its CPU time
+    pub(crate) async fn exited(&self) {
+        let pid = self.pid;
+        let _ = tokio::task::spawn_blocking(move || wait_exited(pid)).await;
+    }
+
+    /// Stop sampling and read the process's usage. CPU time is only known
+    /// when the process hasn't been reaped yet.
+    pub(crate) fn finish(self) -> ResourceUsage {
+        self.sampler.abort();
+        let peak = self.peak_rss.load(Ordering::Relaxed);
+        let cpu = read_stat(self.pid).map(|stat| stat.cpu_ticks());
+        let ms = |ticks: u64| ticks * 1000 / clock_ticks_per_sec();
+        ResourceUsage {
+            cpu_user_ms: cpu.map(|(user, _)| ms(user)),
+            cpu_system_ms: cpu.map(|(_, system)| ms(system)),
+            peak_rss_bytes: (peak > 0).then_some(peak),
+        }
+    }
+}
+
+/// Fields of `/proc/<pid>/stat` used here
+struct ProcStat {
+    pgrp: u32,
+    utime: u64,
+    stime: u64,
+    cutime: u64,
+    cstime: u64,
+    rss_pages: u64,
+}
+
+impl ProcStat {
+    /// User and system ticks of the process and the children it waited for
+    fn cpu_ticks(&self) -> (u64, u64) {
+        (self.utime + self.cutime, self.stime + self.cstime)
+    }
+}
+
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    parse_stat(&stat)
+}
+
+/// Parse a stat line. The command name is in parentheses and may contain
+/// spaces, so fields are counted from the last `)`.
+fn parse_stat(stat: &str) -> Option<ProcStat> {
+    let fields: Vec<&str> = stat.get(stat.rfind(')')? + 1..)?.split_whitespace().collect();
+    // `fields[0]` is field 3 of proc(5), the state
+    let field = |n: usize| -> Option<u64> { fields.get(n - 3)?.parse().ok() };
+    Some(ProcStat {
+        pgrp: field(5)? as u32,
+        utime: field(14)?,
+        stime: field(15)?,
+        cutime: field(16)?,
+        cstime: field(17)?,
+        rss_pages: field(24)?,
+    })
+}
+
+/// Resident memory of every process in the group led by `pgid`
+fn group_rss_bytes(pgid: u32) -> u64 {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return 0;
+    };
+    let pages: u64 = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(read_stat)
+        .filter(|stat| stat.pgrp == pgid)
+        .map(|stat| stat.rss_pages)
+        .sum();
+    pages * page_size()
+}
+
+/// Block until `pid` has exited, leaving it to be reaped by its `Child`
+fn wait_exited(pid: u32) {
+    loop {
+        // SAFETY: waitid only writes to the siginfo it is given
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let rc = unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED | libc::WNOWAIT) };
+        if rc == 0 || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return;
+        }
+    }
+}
+
+fn clock_ticks_per_sec() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 { ticks as u64 } else { 100 }
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 { size as u64 } else { 4096 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "4242 (my (odd) cmd) S 1 4242 4242 0 -1 4194560 120 0 0 0 25 7 3 2 20 0 1 0 900 10485760 512 18446744073709551615";
+        let stat = parse_stat(stat).unwrap();
+        assert_eq!(stat.pgrp, 4242);
+        assert_eq!(stat.cpu_ticks(), (28, 9));
+        assert_eq!(stat.rss_pages, 512);
+        assert!(parse_stat("4242 (truncated) S 1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_usage_monitor_measures_cpu() {
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "i=0; while [ $i -lt 200000 ]; do i=$((i+1)); done"]).process_group(0);
+        let mut child = command.spawn().unwrap();
+        let monitor = UsageMonitor::start(child.id().unwrap());
+        monitor.exited().await;
+        let usage = monitor.finish();
+        assert!(child.wait().await.unwrap().success());
+        assert!(usage.cpu_user_ms.unwrap() + usage.cpu_system_ms.unwrap() > 0);
+        assert!(usage.peak_rss_bytes.unwrap() > 0);
+    }
+}
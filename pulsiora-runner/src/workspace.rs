@@ -0,0 +1,393 @@
+// Per-execution workspaces: a fresh clone of the event's repository, checked
+// out at the revision the event points to and removed when the execution ends.
+
+use pulsiora_core::{GitEvent, PulsioraError, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+use uuid::Uuid;
+
+/// How workspaces are cloned. The default is a full clone without
+/// submodules or LFS objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// Fetch only this many commits of history
+    pub depth: Option<u32>,
+    /// Partial clone filter, e.g. "blob:none" to fetch file contents on demand
+    pub filter: Option<String>,
+    /// Check out submodules, recursively
+    pub submodules: bool,
+    /// Download Git LFS objects; otherwise LFS files are left as pointers
+    pub lfs: bool,
+    /// Local repository to borrow objects from, such as a mirror kept on the
+    /// host; ignored if it doesn't exist
+    pub reference: Option<PathBuf>,
+}
+
+/// A checkout that steps run in. The directory is deleted on drop.
+#[derive(Debug)]
+pub struct Workspace {
+    path: PathBuf,
+}
+
+impl Workspace {
+    /// Clone the event's repository into `<root>/<execution id>` and check out
+    /// the event's revision
+    pub fn prepare(root: &Path, execution_id: Uuid, git_event: &GitEvent, options: &CloneOptions) -> Result<Self> {
+        let path = root.join(execution_id.to_string());
+        if path.exists() {
+            // Left behind by a run that didn't finish
+            fs::remove_dir_all(&path)?;
+        }
+        fs::create_dir_all(root)?;
+
+        // Created before cloning so a failed checkout is still cleaned up
+        let workspace = Self { path };
+        let dir = &workspace.path;
+        // A shallow clone only has the history of the ref it was asked for
+        let shallow_ref = options
+            .depth
+            .and(git_event.branch.as_deref().or(git_event.tag.as_deref()));
+        clone_repository(&git_event.repository.clone_url, dir, options, shallow_ref)?;
+
+        let mut pr_head_fetched = false;
+        if let Some(pr) = &git_event.pull_request {
+            // Commits from forks are only reachable through the PR ref. Hosts
+            // other than GitHub don't have it, so a failure isn't fatal.
+            match fetch(dir, &format!("pull/{}/head", pr.number), options) {
+                Ok(()) => pr_head_fetched = true,
+                Err(e) => warn!(pull_request = pr.number, error = %e, "Failed to fetch pull request head"),
+            }
+        }
+        // Pull request events carry neither a branch nor a commit, so they
+        // build the head that was just fetched
+        let revision = event_revision(git_event).or_else(|| pr_head_fetched.then(|| "FETCH_HEAD".to_string()));
+        if let Some(sha) = revision.as_deref().filter(|r| is_commit_sha(r)) {
+            if options.depth.is_some() && !has_commit(dir, sha) {
+                fetch(dir, sha, options)?;
+            }
+        }
+        if let Some(revision) = &revision {
+            checkout_detached(dir, revision, options)?;
+        }
+
+        if options.submodules {
+            let mut update = git(Some(dir), options);
+            update.args(["submodule", "update", "--quiet", "--init", "--recursive"]);
+            if let Some(depth) = options.depth {
+                update.arg(format!("--depth={}", depth));
+            }
+            run_git(&mut update)?;
+        }
+        if options.lfs {
+            run_git(git(Some(dir), options).args(["lfs", "pull"]))?;
+        }
+        Ok(workspace)
+    }
+
+    /// Take over the workspace an interrupted execution left in
+    /// `<root>/<execution id>`, with whatever its finished steps produced
+    pub fn reopen(root: &Path, execution_id: Uuid) -> Option<Self> {
+        let path = root.join(execution_id.to_string());
+        path.is_dir().then_some(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(path = %self.path.display(), error = %e, "Failed to remove workspace");
+            }
+        }
+    }
+}
+
+/// Revision to check out for an event: the commit when it's a real SHA,
+/// otherwise the branch or tag. `None` keeps the default branch.
+pub fn event_revision(git_event: &GitEvent) -> Option<String> {
+    if let Some(sha) = git_event.commit_sha.as_deref().filter(|sha| is_commit_sha(sha)) {
+        return Some(sha.to_string());
+    }
+    if let Some(branch) = &git_event.branch {
+        return Some(format!("origin/{}", branch));
+    }
+    git_event.tag.as_ref().map(|tag| format!("tags/{}", tag))
+}
+
+/// Whether `value` looks like a (possibly abbreviated) commit SHA rather than
+/// a placeholder such as `manual-execution`
+pub(crate) fn is_commit_sha(value: &str) -> bool {
+    value.len() >= 7 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Clone `clone_url` into `dir` and check out `revision` detached
+pub fn checkout_revision(clone_url: &str, revision: &str, dir: &Path) -> Result<()> {
+    let options = CloneOptions::default();
+    clone_repository(clone_url, dir, &options, None)?;
+    checkout_detached(dir, revision, &options)
+}
+
+/// A git command, run in `dir` if given. LFS files are only downloaded when
+/// asked for.
+fn git(dir: Option<&Path>, options: &CloneOptions) -> Command {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    if !options.lfs {
+        command.env("GIT_LFS_SKIP_SMUDGE", "1");
+    }
+    command
+}
+
+fn clone_repository(clone_url: &str, dir: &Path, options: &CloneOptions, branch: Option<&str>) -> Result<()> {
+    let mut clone = git(None, options);
+    clone.args(["clone", "--quiet"]);
+    if let Some(depth) = options.depth {
+        clone.arg(format!("--depth={}", depth));
+    }
+    if let Some(branch) = branch {
+        clone.arg("--branch").arg(branch);
+    }
+    if let Some(filter) = &options.filter {
+        clone.arg(format!("--filter={}", filter));
+    }
+    if let Some(reference) = &options.reference {
+        clone.arg("--reference-if-able").arg(reference);
+    }
+    run_git(clone.arg(clone_url).arg(dir))
+}
+
+fn fetch(dir: &Path, refspec: &str, options: &CloneOptions) -> Result<()> {
+    let mut fetch = git(Some(dir), options);
+    fetch.args(["fetch", "--quiet"]);
+    if let Some(depth) = options.depth {
+        fetch.arg(format!("--depth={}", depth));
+    }
+    run_git(fetch.args(["origin", refspec]))
+}
+
+fn has_commit(dir: &Path, sha: &str) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["cat-file", "-e"])
+        .arg(format!("{}^{{commit}}", sha))
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn checkout_detached(dir: &Path, revision: &str, options: &CloneOptions) -> Result<()> {
+    run_git(git(Some(dir), options).args(["checkout", "--quiet", "--detach", revision]))
+}
+
+pub(crate) fn run_git(command: &mut Command) -> Result<()> {
+    let output = command
+        .output()
+        .map_err(|e| PulsioraError::ExecutionError(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(PulsioraError::CloneError(format!(
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::{GitEventType, Repository};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pulsiora-workspace-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn create_test_event(clone_url: &Path, branch: Option<&str>, commit_sha: Option<&str>) -> GitEvent {
+        GitEvent {
+            event_type: GitEventType::Push,
+            repository: Repository {
+                owner: "test".to_string(),
+                name: "repo".to_string(),
+                full_name: "test/repo".to_string(),
+                clone_url: clone_url.to_string_lossy().into_owned(),
+                default_branch: "main".to_string(),
+            },
+            branch: branch.map(String::from),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: commit_sha.map(String::from),
+            commit_message: None,
+            sender: "test".to_string(),
+            inputs: Default::default(),
+        }
+    }
+
+    /// An origin repository with two commits on `main` and a `feature` branch
+    /// off the first one; returns the first commit's SHA
+    fn create_origin(origin: &Path) -> String {
+        fs::create_dir_all(origin).unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(origin)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", output);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&["init", "--quiet", "--initial-branch=main"]);
+        fs::write(origin.join("file.txt"), "first").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "first"]);
+        let first = git(&["rev-parse", "HEAD"]);
+        git(&["branch", "feature"]);
+        fs::write(origin.join("file.txt"), "second").unwrap();
+        git(&["commit", "--quiet", "-am", "second"]);
+        git(&["checkout", "--quiet", "feature"]);
+        fs::write(origin.join("file.txt"), "feature").unwrap();
+        git(&["commit", "--quiet", "-am", "feature"]);
+        git(&["checkout", "--quiet", "main"]);
+        first
+    }
+
+    #[test]
+    fn test_event_revision() {
+        let origin = Path::new("/tmp/origin");
+        let event = create_test_event(origin, Some("main"), Some("0123abcd"));
+        assert_eq!(event_revision(&event).as_deref(), Some("0123abcd"));
+        let event = create_test_event(origin, Some("main"), Some("manual-execution"));
+        assert_eq!(event_revision(&event).as_deref(), Some("origin/main"));
+        let mut event = create_test_event(origin, None, None);
+        assert_eq!(event_revision(&event), None);
+        event.tag = Some("v1.0".to_string());
+        assert_eq!(event_revision(&event).as_deref(), Some("tags/v1.0"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_checkout_revision() {
+        let origin = temp_dir("origin-rev");
+        let first = create_origin(&origin);
+
+        let checkout = temp_dir("checkout");
+        checkout_revision(origin.to_str().unwrap(), &first, &checkout).unwrap();
+        assert_eq!(fs::read_to_string(checkout.join("file.txt")).unwrap(), "first");
+
+        fs::remove_dir_all(&origin).unwrap();
+        fs::remove_dir_all(&checkout).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_workspace_checks_out_event_and_cleans_up() {
+        let origin = temp_dir("origin-ws");
+        let first = create_origin(&origin);
+        let root = temp_dir("root");
+
+        let event = create_test_event(&origin, Some("feature"), None);
+        let workspace = Workspace::prepare(&root, Uuid::new_v4(), &event, &CloneOptions::default()).unwrap();
+        assert!(workspace.path().starts_with(&root));
+        assert_eq!(fs::read_to_string(workspace.path().join("file.txt")).unwrap(), "feature");
+        let path = workspace.path().to_path_buf();
+        drop(workspace);
+        assert!(!path.exists());
+
+        let event = create_test_event(&origin, Some("main"), Some(&first));
+        let workspace = Workspace::prepare(&root, Uuid::new_v4(), &event, &CloneOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(workspace.path().join("file.txt")).unwrap(), "first");
+        drop(workspace);
+
+        let event = create_test_event(&origin, Some("missing"), None);
+        assert!(Workspace::prepare(&root, Uuid::new_v4(), &event, &CloneOptions::default()).is_err());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+
+        fs::remove_dir_all(&origin).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_workspace_shallow_clone() {
+        let origin = temp_dir("origin-shallow");
+        let first = create_origin(&origin);
+        let root = temp_dir("root-shallow");
+        // Local paths ignore --depth; a file:// URL goes through the transport
+        let url = PathBuf::from(format!("file://{}", origin.display()));
+        let commits = |dir: &Path| {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(["rev-list", "--count", "HEAD"])
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let options = CloneOptions {
+            depth: Some(1),
+            reference: Some(origin.clone()),
+            ..Default::default()
+        };
+
+        let event = create_test_event(&url, Some("feature"), None);
+        let workspace = Workspace::prepare(&root, Uuid::new_v4(), &event, &options).unwrap();
+        assert_eq!(fs::read_to_string(workspace.path().join("file.txt")).unwrap(), "feature");
+        assert_eq!(commits(workspace.path()), "1");
+        assert!(workspace.path().join(".git/objects/info/alternates").exists());
+        drop(workspace);
+
+        // A commit that isn't the branch tip is fetched on its own
+        let event = create_test_event(&url, Some("main"), Some(&first));
+        let workspace = Workspace::prepare(&root, Uuid::new_v4(), &event, &options).unwrap();
+        assert_eq!(fs::read_to_string(workspace.path().join("file.txt")).unwrap(), "first");
+        drop(workspace);
+
+        fs::remove_dir_all(&origin).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_workspace_checks_out_pull_request_head() {
+        let origin = temp_dir("origin-pr");
+        create_origin(&origin);
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&origin)
+            .args(["update-ref", "refs/pull/7/head", "feature"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let root = temp_dir("root-pr");
+
+        let mut event = create_test_event(&origin, None, None);
+        event.event_type = GitEventType::PullRequest;
+        event.pull_request = Some(pulsiora_core::PullRequest {
+            number: 7,
+            title: "Add feature".to_string(),
+            base_branch: "main".to_string(),
+            head_branch: "feature".to_string(),
+            state: "open".to_string(),
+            draft: false,
+            labels: vec![],
+            head_repository: None,
+        });
+        let workspace = Workspace::prepare(&root, Uuid::new_v4(), &event, &CloneOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(workspace.path().join("file.txt")).unwrap(), "feature");
+        drop(workspace);
+
+        fs::remove_dir_all(&origin).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use pulsiora_core::ExecutionLimits;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// A user allowed to call the API with a personal token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    pub organization: String,
+    pub admin: bool,
+    /// SHA-256 of the user's API token; the token itself is never stored
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Organization {
+    pub name: String,
+    /// Pulsefile merged into every pipeline of the organization's repos
+    #[serde(default)]
+    pub base_pulsefile: Option<String>,
+    /// Caps on what the organization's repositories may use together
+    #[serde(default)]
+    pub quotas: OrganizationQuotas,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Caps an organization's repositories share, set by the instance's
+/// operators; caps left unset don't apply
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrganizationQuotas {
+    /// Jobs of the organization's repositories running at once
+    #[serde(default)]
+    pub max_concurrent_runs: Option<usize>,
+    /// Bytes of step logs and artifacts its executions may keep on the
+    /// server's disk; new runs are refused beyond it
+    #[serde(default)]
+    pub max_storage_bytes: Option<u64>,
+}
+
+impl OrganizationQuotas {
+    /// Whether `used` bytes reach the storage quota
+    pub fn storage_exceeded(&self, used: u64) -> bool {
+        self.max_storage_bytes.is_some_and(|max| used >= max)
+    }
+}
+
+/// Instance-wide settings that can be changed at runtime
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstanceSettings {
+    /// Token used for GitHub API calls (commit statuses, branch protection)
+    #[serde(skip_serializing)]
+    pub github_token: Option<String>,
+    /// Report executions as check runs, with a summary of their steps and
+    /// annotations, instead of commit statuses
+    #[serde(default)]
+    pub github_checks: bool,
+    /// Comment on pull requests with a summary of each pipeline's execution,
+    /// updating the comment on later runs
+    #[serde(default)]
+    pub github_pr_comments: bool,
+    /// Address the server is reached at, for links back to it
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// Default guardrails for every execution; repos may override them
+    #[serde(default)]
+    pub limits: ExecutionLimits,
+}
+
+/// Lifetime of a share link when none is requested
+pub const DEFAULT_SHARE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Longest lifetime a share link may have
+pub const MAX_SHARE_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Read-only access to one execution's details and logs for people without
+/// an API token, e.g. a vendor looking into a failure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub execution_id: Uuid,
+    /// SHA-256 of the link's token; the token itself is never stored
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    /// User who created the link
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ShareLink {
+    /// Whether the link still grants access
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && now < self.expires_at
+    }
+}
+
+/// Generate a random API token
+pub fn generate_token() -> String {
+    format!("pls_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Extract the token from an `Authorization: Bearer <token>` header
+pub fn bearer_token(headers: &axum::http::HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_tokens_are_unique() {
+        let a = generate_token();
+        let b = generate_token();
+        assert!(a.starts_with("pls_"));
+        assert_eq!(a.len(), 68);
+        assert_ne!(a, b);
+        assert_ne!(hash_token(&a), hash_token(&b));
+        assert_eq!(hash_token(&a), hash_token(&a));
+    }
+
+    #[test]
+    fn test_share_link_is_active() {
+        let now = Utc::now();
+        let mut link = ShareLink {
+            id: Uuid::new_v4(),
+            execution_id: Uuid::new_v4(),
+            token_hash: hash_token("pls_share"),
+            created_by: "alice".to_string(),
+            created_at: now,
+            expires_at: now + chrono::Duration::hours(1),
+            revoked_at: None,
+        };
+        assert!(link.is_active(now));
+        assert!(!link.is_active(now + chrono::Duration::hours(2)));
+        link.revoked_at = Some(now);
+        assert!(!link.is_active(now));
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+
+        headers.insert("Authorization", "Bearer pls_abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), Some("pls_abc"));
+
+        headers.insert("Authorization", "Basic abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), None);
+    }
+}
@@ -0,0 +1,165 @@
+// Aggregates executions into per-period counts for heat maps and calendars.
+
+use chrono::{Datelike, Days, NaiveDate};
+use pulsiora_core::{ActivityBucket, PipelineExecution, PipelineStatus, PulsioraError, Result};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Length of the periods an activity series is grouped into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityGranularity {
+    Day,
+    /// Weeks start on Monday
+    Week,
+    Month,
+}
+
+impl ActivityGranularity {
+    /// First day of the period containing `date`
+    pub fn period_start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            Self::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+impl FromStr for ActivityGranularity {
+    type Err = PulsioraError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(PulsioraError::InvalidConfiguration(format!(
+                "Unknown granularity \"{}\": expected day, week or month",
+                s
+            ))),
+        }
+    }
+}
+
+/// Count executions started on or after `since` per period, oldest first.
+/// Periods without executions are omitted.
+pub fn activity_series(
+    executions: &[PipelineExecution],
+    granularity: ActivityGranularity,
+    since: NaiveDate,
+) -> Vec<ActivityBucket> {
+    let mut buckets: BTreeMap<NaiveDate, ActivityBucket> = BTreeMap::new();
+
+    for execution in executions {
+        let day = execution.started_at.date_naive();
+        if day < since {
+            continue;
+        }
+        let date = granularity.period_start(day);
+        let bucket = buckets.entry(date).or_insert_with(|| ActivityBucket {
+            date,
+            ..Default::default()
+        });
+        bucket.total += 1;
+        match execution.status {
+            PipelineStatus::Success => bucket.success += 1,
+            PipelineStatus::Failed => bucket.failed += 1,
+            PipelineStatus::Cancelled => bucket.cancelled += 1,
+            PipelineStatus::Skipped => bucket.skipped += 1,
+            PipelineStatus::Pending | PipelineStatus::Running | PipelineStatus::Interrupted => {}
+        }
+    }
+
+    buckets.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use pulsiora_core::{ExecutionMetrics, GitEvent, GitEventType, Repository};
+    use uuid::Uuid;
+
+    fn execution(day: u32, status: PipelineStatus) -> PipelineExecution {
+        let repository = Repository {
+            owner: "test".to_string(),
+            name: "repo".to_string(),
+            full_name: "test/repo".to_string(),
+            clone_url: "https://github.com/test/repo.git".to_string(),
+            default_branch: "main".to_string(),
+        };
+        PipelineExecution {
+            id: Uuid::new_v4(),
+            pipeline_name: "test".to_string(),
+            pipeline_version: "1.0".to_string(),
+            pipeline_labels: vec![],
+            environment: None,
+            repository: repository.clone(),
+            git_event: GitEvent {
+                event_type: GitEventType::Push,
+                repository,
+                branch: Some("main".to_string()),
+                tag: None,
+                release_name: None,
+                pull_request: None,
+                commit_sha: None,
+                commit_message: None,
+                sender: "test".to_string(),
+                inputs: Default::default(),
+            },
+            status,
+            step_results: vec![],
+            phases: vec![],
+            limit_exceeded: None,
+            coverage: None,
+            metrics: ExecutionMetrics::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
+            started_at: Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+            completed_at: None,
+        }
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_granularity_period_start() {
+        // 2024-05-16 is a Thursday
+        assert_eq!(ActivityGranularity::Day.period_start(date(5, 16)), date(5, 16));
+        assert_eq!(ActivityGranularity::Week.period_start(date(5, 16)), date(5, 13));
+        assert_eq!(ActivityGranularity::Month.period_start(date(5, 16)), date(5, 1));
+        assert!("hour".parse::<ActivityGranularity>().is_err());
+    }
+
+    #[test]
+    fn test_activity_series_counts_statuses_per_period() {
+        let executions = vec![
+            execution(13, PipelineStatus::Success),
+            execution(16, PipelineStatus::Failed),
+            execution(16, PipelineStatus::Success),
+            execution(20, PipelineStatus::Cancelled),
+            execution(2, PipelineStatus::Success),
+        ];
+
+        let daily = activity_series(&executions, ActivityGranularity::Day, date(5, 10));
+        let days: Vec<(NaiveDate, usize)> = daily.iter().map(|b| (b.date, b.total)).collect();
+        assert_eq!(days, vec![(date(5, 13), 1), (date(5, 16), 2), (date(5, 20), 1)]);
+
+        let weekly = activity_series(&executions, ActivityGranularity::Week, date(5, 1));
+        assert_eq!(weekly.len(), 3);
+        assert_eq!(
+            weekly[1],
+            ActivityBucket {
+                date: date(5, 13),
+                total: 3,
+                success: 2,
+                failed: 1,
+                cancelled: 0,
+                skipped: 0,
+            }
+        );
+        assert_eq!(weekly[2].cancelled, 1);
+    }
+}
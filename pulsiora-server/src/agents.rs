@@ -0,0 +1,459 @@
+// Remote agents: machines that run jobs for the server, so builds can be
+// spread over several hosts and operating systems. Agents register with the
+// agent token, long-poll for jobs and report the job's events as it runs. A
+// job whose agent stops reporting fails, like a job on a host that crashed.
+// A cancelled job is withdrawn if no agent has taken it yet; otherwise its
+// agent is told to stop in the answer to its next report.
+
+use crate::accounts::{generate_token, hash_token};
+use chrono::{DateTime, Utc};
+use pulsiora_core::{
+    AgentJob, AgentOutcome, AgentRegistered, AgentRegistration, AgentReportAck, ExecutionEvent, PipelineExecution,
+    PulsioraError, Result,
+};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// How long a running job's agent may go without reporting before the job
+/// fails; agents report at least every 15 seconds while they run one
+pub const DEFAULT_AGENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A registered agent
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentInfo {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub registration: AgentRegistration,
+    pub registered_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Execution the agent is running
+    pub execution_id: Option<Uuid>,
+    #[serde(skip)]
+    token_hash: String,
+}
+
+/// A job waiting for, or running on, an agent
+struct Assignment {
+    agent_id: Option<Uuid>,
+    last_report: Instant,
+    /// The job was cancelled after an agent took it
+    cancelled: bool,
+    events: mpsc::Sender<ExecutionEvent>,
+    outcome: Option<oneshot::Sender<AgentOutcome>>,
+}
+
+/// Registered agents and the jobs offered to them
+pub struct AgentPool {
+    /// SHA-256 of the token agents register with
+    registration_token_hash: String,
+    timeout: Duration,
+    agents: Mutex<HashMap<Uuid, AgentInfo>>,
+    offers: Mutex<VecDeque<AgentJob>>,
+    offered: Notify,
+    assignments: Mutex<HashMap<Uuid, Assignment>>,
+}
+
+impl AgentPool {
+    pub fn new(registration_token: &str) -> Self {
+        Self {
+            registration_token_hash: hash_token(registration_token),
+            timeout: DEFAULT_AGENT_TIMEOUT,
+            agents: Mutex::new(HashMap::new()),
+            offers: Mutex::new(VecDeque::new()),
+            offered: Notify::new(),
+            assignments: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fail jobs whose agent hasn't reported for this long
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Register an agent presenting the registration token
+    pub fn register(&self, token: &str, registration: AgentRegistration) -> Option<AgentRegistered> {
+        if hash_token(token) != self.registration_token_hash {
+            return None;
+        }
+        let agent_token = generate_token();
+        let now = Utc::now();
+        let agent = AgentInfo {
+            id: Uuid::new_v4(),
+            registration,
+            registered_at: now,
+            last_seen: now,
+            execution_id: None,
+            token_hash: hash_token(&agent_token),
+        };
+        let agent_id = agent.id;
+        self.agents.lock().unwrap().insert(agent_id, agent);
+        Some(AgentRegistered {
+            agent_id,
+            token: agent_token,
+        })
+    }
+
+    /// The agent a token belongs to, noting that it was seen
+    pub fn authenticate(&self, token: &str) -> Option<Uuid> {
+        let token_hash = hash_token(token);
+        let mut agents = self.agents.lock().unwrap();
+        let agent = agents.values_mut().find(|agent| agent.token_hash == token_hash)?;
+        agent.last_seen = Utc::now();
+        Some(agent.id)
+    }
+
+    /// Registered agents, by name
+    pub fn agents(&self) -> Vec<AgentInfo> {
+        let mut agents: Vec<_> = self.agents.lock().unwrap().values().cloned().collect();
+        agents.sort_by(|a, b| a.registration.name.cmp(&b.registration.name));
+        agents
+    }
+
+    /// Offer a job to the agents and wait for one to run it. Events the agent
+    /// reports are passed on to `events`. When `cancel` fires, the job is
+    /// withdrawn or its agent is asked to stop it.
+    pub async fn run(
+        &self,
+        job: AgentJob,
+        events: mpsc::Sender<ExecutionEvent>,
+        cancel: CancellationToken,
+    ) -> Result<PipelineExecution> {
+        let execution_id = job.execution_id;
+        let (outcome, mut finished) = oneshot::channel();
+        self.assignments.lock().unwrap().insert(
+            execution_id,
+            Assignment {
+                agent_id: None,
+                last_report: Instant::now(),
+                cancelled: false,
+                events,
+                outcome: Some(outcome),
+            },
+        );
+        self.offers.lock().unwrap().push_back(job);
+        self.offered.notify_one();
+
+        let mut check = tokio::time::interval(self.timeout / 4);
+        let mut cancelling = false;
+        loop {
+            tokio::select! {
+                outcome = &mut finished => {
+                    return match outcome {
+                        Ok(AgentOutcome::Finished { execution }) => Ok(*execution),
+                        Ok(AgentOutcome::Errored { error, infrastructure: true }) => {
+                            Err(PulsioraError::BackendUnavailable(error))
+                        }
+                        Ok(AgentOutcome::Errored { error, .. }) => Err(PulsioraError::ExecutionError(error)),
+                        Err(_) => Err(PulsioraError::AgentLost("Agent job was dropped".to_string())),
+                    };
+                }
+                _ = cancel.cancelled(), if !cancelling => {
+                    cancelling = true;
+                    if self.withdraw(execution_id) {
+                        return Err(PulsioraError::ExecutionError(
+                            "Cancelled before an agent took the job".to_string(),
+                        ));
+                    }
+                }
+                _ = check.tick() => {
+                    if let Some(agent_id) = self.expired(execution_id) {
+                        return Err(PulsioraError::AgentLost(format!(
+                            "Agent {} stopped reporting for {}s",
+                            agent_id,
+                            self.timeout.as_secs()
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Take back a job no agent has taken yet; true if it was withdrawn,
+    /// otherwise its agent is told to stop it
+    fn withdraw(&self, execution_id: Uuid) -> bool {
+        let mut offers = self.offers.lock().unwrap();
+        let mut assignments = self.assignments.lock().unwrap();
+        if let Some(index) = offers.iter().position(|job| job.execution_id == execution_id) {
+            offers.remove(index);
+            assignments.remove(&execution_id);
+            return true;
+        }
+        if let Some(assignment) = assignments.get_mut(&execution_id) {
+            assignment.cancelled = true;
+        }
+        false
+    }
+
+    /// Drop the assignment of a job whose agent stopped reporting, returning
+    /// that agent
+    fn expired(&self, execution_id: Uuid) -> Option<Uuid> {
+        let mut assignments = self.assignments.lock().unwrap();
+        let assignment = assignments.get(&execution_id)?;
+        let agent_id = assignment.agent_id?;
+        if assignment.last_report.elapsed() < self.timeout {
+            return None;
+        }
+        assignments.remove(&execution_id);
+        drop(assignments);
+        self.release_agent(agent_id);
+        Some(agent_id)
+    }
+
+    fn release_agent(&self, agent_id: Uuid) {
+        if let Some(agent) = self.agents.lock().unwrap().get_mut(&agent_id) {
+            agent.execution_id = None;
+        }
+    }
+
+    /// Wait up to `wait` for a job for the agent
+    pub async fn poll(&self, agent_id: Uuid, wait: Duration) -> Option<AgentJob> {
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            // Registered before looking, so an offer made in between wakes it
+            let offered = self.offered.notified();
+            if let Some(job) = self.offers.lock().unwrap().pop_front() {
+                self.assign(agent_id, &job);
+                return Some(job);
+            }
+            if tokio::time::timeout_at(deadline, offered).await.is_err() {
+                return None;
+            }
+        }
+    }
+
+    fn assign(&self, agent_id: Uuid, job: &AgentJob) {
+        if let Some(assignment) = self.assignments.lock().unwrap().get_mut(&job.execution_id) {
+            assignment.agent_id = Some(agent_id);
+            assignment.last_report = Instant::now();
+        }
+        if let Some(agent) = self.agents.lock().unwrap().get_mut(&agent_id) {
+            agent.execution_id = Some(job.execution_id);
+        }
+    }
+
+    /// Pass on events from the agent running a job; None if the job isn't
+    /// assigned to it (any more)
+    pub async fn report(
+        &self,
+        agent_id: Uuid,
+        execution_id: Uuid,
+        events: Vec<ExecutionEvent>,
+    ) -> Option<AgentReportAck> {
+        let (sender, ack) = {
+            let mut assignments = self.assignments.lock().unwrap();
+            match assignments.get_mut(&execution_id) {
+                Some(assignment) if assignment.agent_id == Some(agent_id) => {
+                    assignment.last_report = Instant::now();
+                    let ack = AgentReportAck {
+                        cancel: assignment.cancelled,
+                    };
+                    (assignment.events.clone(), ack)
+                }
+                _ => return None,
+            }
+        };
+        for event in events {
+            // The server side of the job has stopped listening; nothing to do
+            if sender.send(event).await.is_err() {
+                break;
+            }
+        }
+        Some(ack)
+    }
+
+    /// Record how a job ended; false if the job isn't assigned to the agent
+    pub fn finish(&self, agent_id: Uuid, execution_id: Uuid, outcome: AgentOutcome) -> bool {
+        let assignment = {
+            let mut assignments = self.assignments.lock().unwrap();
+            match assignments.get(&execution_id) {
+                Some(assignment) if assignment.agent_id == Some(agent_id) => assignments.remove(&execution_id),
+                _ => None,
+            }
+        };
+        let Some(mut assignment) = assignment else { return false };
+        self.release_agent(agent_id);
+        if let Some(sender) = assignment.outcome.take() {
+            let _ = sender.send(outcome);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use pulsiora_core::{ExecutionMetrics, GitEvent, GitEventType, Pipeline, PipelineStatus, Repository};
+    use std::sync::Arc;
+
+    fn job() -> AgentJob {
+        let repository = Repository {
+            owner: "test".to_string(),
+            name: "repo".to_string(),
+            full_name: "test/repo".to_string(),
+            clone_url: "https://github.com/test/repo.git".to_string(),
+            default_branch: "main".to_string(),
+        };
+        AgentJob {
+            execution_id: Uuid::new_v4(),
+            pipeline: pulsiora_parser::parse_pulsefile(r#"pipeline { name: "ci"; version: "1.0"; triggers { git { } } steps { } }"#).unwrap(),
+            git_event: GitEvent {
+                event_type: GitEventType::Push,
+                repository,
+                branch: Some("main".to_string()),
+                tag: None,
+                release_name: None,
+                pull_request: None,
+                commit_sha: None,
+                commit_message: None,
+                sender: "test".to_string(),
+                inputs: Default::default(),
+            },
+            limits: Default::default(),
+            completed_steps: vec![],
+            queued_at: Utc::now(),
+            env: Default::default(),
+            secrets: Default::default(),
+            sandbox: None,
+        }
+    }
+
+    fn execution(job: &AgentJob, pipeline: &Pipeline) -> PipelineExecution {
+        PipelineExecution {
+            id: job.execution_id,
+            pipeline_name: pipeline.name.clone(),
+            pipeline_version: pipeline.version.clone(),
+            pipeline_labels: vec![],
+            environment: None,
+            repository: job.git_event.repository.clone(),
+            git_event: job.git_event.clone(),
+            status: PipelineStatus::Success,
+            step_results: vec![],
+            phases: vec![],
+            limit_exceeded: None,
+            coverage: None,
+            metrics: ExecutionMetrics::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
+            started_at: Utc::now(),
+            completed_at: Some(Utc::now()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_runs_job() {
+        let pool = Arc::new(AgentPool::new("secret"));
+        let registration = AgentRegistration::for_host("builder", vec!["gpu".to_string()]);
+        assert!(pool.register("wrong", registration.clone()).is_none());
+        let registered = pool.register("secret", registration).unwrap();
+        let agent_id = pool.authenticate(&registered.token).unwrap();
+        assert_eq!(agent_id, registered.agent_id);
+        assert!(pool.authenticate("secret").is_none());
+        assert!(pool.poll(agent_id, Duration::from_millis(10)).await.is_none());
+
+        let offered = job();
+        let (events, mut received) = mpsc::channel(4);
+        let running = tokio::spawn({
+            let pool = pool.clone();
+            let offered = offered.clone();
+            async move { pool.run(offered, events, CancellationToken::new()).await }
+        });
+        let polled = pool.poll(agent_id, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(polled, offered);
+        assert_eq!(pool.agents()[0].execution_id, Some(offered.execution_id));
+
+        let started = ExecutionEvent::StepStarted {
+            execution_id: offered.execution_id,
+            step_name: "build".to_string(),
+        };
+        assert!(pool.report(Uuid::new_v4(), offered.execution_id, vec![started.clone()]).await.is_none());
+        let ack = pool.report(agent_id, offered.execution_id, vec![started]).await;
+        assert_eq!(ack, Some(AgentReportAck { cancel: false }));
+        assert!(matches!(received.recv().await, Some(ExecutionEvent::StepStarted { step_name, .. }) if step_name == "build"));
+
+        let execution = execution(&offered, &offered.pipeline);
+        let outcome = AgentOutcome::Finished {
+            execution: Box::new(execution),
+        };
+        assert!(pool.finish(agent_id, offered.execution_id, outcome.clone()));
+        assert!(!pool.finish(agent_id, offered.execution_id, outcome));
+        let finished = running.await.unwrap().unwrap();
+        assert_eq!(finished.id, offered.execution_id);
+        assert_eq!(pool.agents()[0].execution_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_silent_agent_loses_job() {
+        let pool = Arc::new(AgentPool::new("secret").with_timeout(Duration::from_millis(100)));
+        let agent_id = pool
+            .register("secret", AgentRegistration::for_host("builder", vec![]))
+            .unwrap()
+            .agent_id;
+        let offered = job();
+        let (events, _received) = mpsc::channel(4);
+        let running = tokio::spawn({
+            let pool = pool.clone();
+            let offered = offered.clone();
+            async move { pool.run(offered, events, CancellationToken::new()).await }
+        });
+        pool.poll(agent_id, Duration::from_secs(5)).await.unwrap();
+
+        let error = running.await.unwrap().unwrap_err();
+        assert!(error.to_string().contains("stopped reporting"));
+        assert!(pool.report(agent_id, offered.execution_id, vec![]).await.is_none());
+        assert_eq!(pool.agents()[0].execution_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_jobs() {
+        let pool = Arc::new(AgentPool::new("secret"));
+        let agent_id = pool
+            .register("secret", AgentRegistration::for_host("builder", vec![]))
+            .unwrap()
+            .agent_id;
+        let spawn = |offered: &AgentJob, cancel: &CancellationToken| {
+            let (events, _received) = mpsc::channel(4);
+            let (pool, offered, cancel) = (pool.clone(), offered.clone(), cancel.clone());
+            tokio::spawn(async move { pool.run(offered, events, cancel).await })
+        };
+
+        // Not taken by an agent yet: withdrawn
+        let waiting = job();
+        let cancel = CancellationToken::new();
+        let running = spawn(&waiting, &cancel);
+        tokio::task::yield_now().await;
+        cancel.cancel();
+        let error = running.await.unwrap().unwrap_err();
+        assert!(error.to_string().contains("before an agent took"));
+        assert!(pool.poll(agent_id, Duration::from_millis(10)).await.is_none());
+
+        // Taken: the agent is told to stop and still sends the result
+        let taken = job();
+        let cancel = CancellationToken::new();
+        let running = spawn(&taken, &cancel);
+        pool.poll(agent_id, Duration::from_secs(5)).await.unwrap();
+        cancel.cancel();
+        let mut ack = None;
+        for _ in 0..50 {
+            ack = pool.report(agent_id, taken.execution_id, vec![]).await;
+            if ack.is_some_and(|ack| ack.cancel) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(ack, Some(AgentReportAck { cancel: true }));
+        let mut execution = execution(&taken, &taken.pipeline);
+        execution.status = PipelineStatus::Cancelled;
+        let outcome = AgentOutcome::Finished {
+            execution: Box::new(execution),
+        };
+        assert!(pool.finish(agent_id, taken.execution_id, outcome));
+        assert_eq!(running.await.unwrap().unwrap().status, PipelineStatus::Cancelled);
+    }
+}
@@ -0,0 +1,108 @@
+use ipnet::IpNet;
+use pulsiora_core::{PulsioraError, Result};
+use std::net::IpAddr;
+
+/// Source address filter for webhook routes.
+///
+/// Combines statically configured ranges with GitHub's published hook ranges,
+/// which are refreshed separately. An enabled list that matches nothing denies
+/// the request, so a failed refresh never opens the endpoint up.
+#[derive(Debug, Clone, Default)]
+pub struct IpAllowList {
+    configured: Vec<IpNet>,
+    github: Vec<IpNet>,
+    include_github: bool,
+}
+
+impl IpAllowList {
+    pub fn new(configured: Vec<IpNet>, include_github: bool) -> Self {
+        Self {
+            configured,
+            github: Vec::new(),
+            include_github,
+        }
+    }
+
+    /// Whether any filtering applies; a disabled list allows every address
+    pub fn is_enabled(&self) -> bool {
+        self.include_github || !self.configured.is_empty()
+    }
+
+    pub fn includes_github(&self) -> bool {
+        self.include_github
+    }
+
+    pub fn set_github_ranges(&mut self, ranges: Vec<IpNet>) {
+        self.github = ranges;
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        // IPv4 clients on a dual-stack listener show up as ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        self.configured
+            .iter()
+            .chain(&self.github)
+            .any(|net| net.contains(&ip))
+    }
+}
+
+/// Parse CIDR ranges such as "192.30.252.0/22"; bare addresses are treated as single hosts
+pub fn parse_cidrs<I, S>(entries: I) -> Result<Vec<IpNet>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    entries
+        .into_iter()
+        .map(|entry| entry.as_ref().trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| PulsioraError::InvalidConfiguration(format!("Invalid CIDR range: {}", entry)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_allow_list_allows_everything() {
+        let list = IpAllowList::default();
+        assert!(!list.is_enabled());
+        assert!(list.allows("203.0.113.9".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_allow_list_matches_configured_and_github_ranges() {
+        let mut list = IpAllowList::new(parse_cidrs(["10.0.0.0/8", "2001:db8::1"]).unwrap(), true);
+        assert!(list.allows("10.1.2.3".parse().unwrap()));
+        assert!(list.allows("2001:db8::1".parse().unwrap()));
+        assert!(!list.allows("192.30.252.10".parse().unwrap()));
+
+        list.set_github_ranges(parse_cidrs(["192.30.252.0/22"]).unwrap());
+        assert!(list.allows("192.30.252.10".parse().unwrap()));
+        assert!(list.allows("::ffff:192.30.252.10".parse().unwrap()));
+        assert!(!list.allows("203.0.113.9".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_github_only_list_denies_until_refreshed() {
+        let list = IpAllowList::new(Vec::new(), true);
+        assert!(list.is_enabled());
+        assert!(!list.allows("192.30.252.10".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_cidrs_rejects_garbage() {
+        assert_eq!(parse_cidrs(" 10.0.0.0/8 , ".split(',')).unwrap().len(), 1);
+        assert!(parse_cidrs(["10.0.0.0/33"]).is_err());
+        assert!(parse_cidrs(["not-an-ip"]).is_err());
+    }
+}
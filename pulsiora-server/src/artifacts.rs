@@ -0,0 +1,202 @@
+// Browsing of stored execution artifacts: listings, safe path resolution and
+// content types, so HTML reports can be viewed inline.
+
+use serde::Serialize;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// A file or directory among an execution's artifacts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArtifactEntry {
+    /// Path relative to the execution's artifact root, using `/`
+    pub path: String,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Resolve `relative` inside `root`, rejecting anything that could escape it
+pub fn resolve_artifact_path(root: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative.trim_start_matches('/'));
+    if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return None;
+    }
+    let root = root.canonicalize().ok()?;
+    let path = root.join(relative).canonicalize().ok()?;
+    path.starts_with(&root).then_some(path)
+}
+
+/// Entries directly inside `dir`, directories first, then by name
+pub fn list_artifact_dir(root: &Path, dir: &Path) -> io::Result<Vec<ArtifactEntry>> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        entries.push(ArtifactEntry {
+            path: relative_path(root, &entry.path()),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            is_dir: metadata.is_dir(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.cmp(&b.path)));
+    Ok(entries)
+}
+
+/// Every file under `root`, sorted by path
+pub fn list_artifact_files(root: &Path) -> io::Result<Vec<ArtifactEntry>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in list_artifact_dir(root, &dir)? {
+            if entry.is_dir {
+                pending.push(root.join(&entry.path));
+            } else {
+                files.push(entry);
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Content type from the file extension; extensionless files are shown as
+/// text when they look like UTF-8
+pub fn content_type_for(path: &Path, head: &[u8]) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "txt" | "log" | "md" | "csv" | "lcov" | "info" => "text/plain; charset=utf-8",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        _ if std::str::from_utf8(head).is_ok() => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Whether a content type can be rendered by the browser instead of downloaded
+pub fn is_inline_type(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type.starts_with("image/")
+        || matches!(content_type, "application/json" | "application/xml" | "application/pdf")
+}
+
+/// HTML listing of a directory of artifacts
+pub fn render_artifact_listing(execution_id: &str, dir: &str, entries: &[ArtifactEntry]) -> String {
+    let title = format!("Artifacts of {} /{}", execution_id, dir);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head>\n<body>\n<h1>{0}</h1>\n<ul>\n",
+        escape_html(&title)
+    );
+    if !dir.is_empty() {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for entry in entries {
+        let name = entry.path.rsplit('/').next().unwrap_or(&entry.path);
+        let (href, label) = if entry.is_dir {
+            (format!("{}/", name), format!("{}/", name))
+        } else {
+            (name.to_string(), format!("{} ({} bytes)", name, entry.size))
+        };
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            escape_html(&href),
+            escape_html(&label)
+        ));
+    }
+    if entries.is_empty() {
+        html.push_str("<li>No artifacts</li>\n");
+    }
+    html.push_str("</ul>\n</body></html>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("pulsiora-server-artifacts-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("coverage")).unwrap();
+        std::fs::write(root.join("coverage/index.html"), "<h1>ok</h1>").unwrap();
+        std::fs::write(root.join("junit.xml"), "<testsuite/>").unwrap();
+        root
+    }
+
+    #[test]
+    fn test_resolve_artifact_path_stays_inside_root() {
+        let root = artifact_root("resolve");
+        assert!(resolve_artifact_path(&root, "coverage/index.html").is_some());
+        assert!(resolve_artifact_path(&root, "/junit.xml").is_some());
+        assert!(resolve_artifact_path(&root, "../").is_none());
+        assert!(resolve_artifact_path(&root, "coverage/../../etc/passwd").is_none());
+        assert!(resolve_artifact_path(&root, "missing.txt").is_none());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_list_artifacts() {
+        let root = artifact_root("list");
+        let top = list_artifact_dir(&root, &root).unwrap();
+        let names: Vec<(&str, bool)> = top.iter().map(|e| (e.path.as_str(), e.is_dir)).collect();
+        assert_eq!(names, vec![("coverage", true), ("junit.xml", false)]);
+
+        let files = list_artifact_files(&root).unwrap();
+        let paths: Vec<&str> = files.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["coverage/index.html", "junit.xml"]);
+        assert_eq!(files[0].size, 11);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_content_type_for() {
+        assert_eq!(content_type_for(Path::new("index.HTML"), b""), "text/html; charset=utf-8");
+        assert_eq!(content_type_for(Path::new("logo.png"), b"\x89PNG"), "image/png");
+        assert_eq!(content_type_for(Path::new("LICENSE"), b"MIT"), "text/plain; charset=utf-8");
+        assert_eq!(content_type_for(Path::new("blob"), &[0xff, 0xfe, 0x00]), "application/octet-stream");
+        assert!(is_inline_type("text/html; charset=utf-8"));
+        assert!(!is_inline_type("application/zip"));
+    }
+
+    #[test]
+    fn test_render_listing_escapes_names() {
+        let entries = vec![ArtifactEntry {
+            path: "dir/<script>.txt".to_string(),
+            size: 3,
+            is_dir: false,
+        }];
+        let html = render_artifact_listing("abc", "dir", &entries);
+        assert!(html.contains("&lt;script&gt;.txt (3 bytes)"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("href=\"../\""));
+    }
+}
@@ -0,0 +1,81 @@
+// Append-only audit log in the NDJSON format defined by `pulsiora_core::AuditRecord`.
+
+use pulsiora_core::{AuditEvent, AuditRecord, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Writes audit records, one line each, to a file that is only ever appended to
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a record. A failed write is logged rather than failing the
+    /// request that caused it.
+    pub fn record(&self, actor: Option<&str>, event: AuditEvent) {
+        let line = AuditRecord::new(actor.map(String::from), event).to_line();
+        let written = match self.file.lock() {
+            Ok(mut file) => file.write_all(line.as_bytes()).and_then(|_| file.flush()),
+            Err(_) => Err(std::io::Error::other("audit log lock poisoned")),
+        };
+        if let Err(e) = written {
+            tracing::warn!(path = %self.path.display(), error = %e, "Failed to write audit record");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::parse_audit_log;
+
+    #[test]
+    fn test_audit_log_appends_records() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-audit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("audit.log");
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(
+            None,
+            AuditEvent::RepoUnregistered {
+                repository: "acme/shop".to_string(),
+            },
+        );
+        drop(log);
+
+        // Reopening keeps existing records
+        let log = AuditLog::open(&path).unwrap();
+        log.record(
+            Some("alice"),
+            AuditEvent::InstanceSetup {
+                organization: "acme".to_string(),
+            },
+        );
+
+        let records = parse_audit_log(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].actor, None);
+        assert_eq!(records[1].actor.as_deref(), Some("alice"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
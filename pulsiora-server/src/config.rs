@@ -0,0 +1,417 @@
+// Server settings, layered: a TOML file, then environment variables, then
+// command-line flags, each overriding the one before. Settings not covered
+// here are read from the environment where they are used.
+
+use crate::github_app::GitHubApp;
+use crate::objects::S3Options;
+use crate::queue::RetryPolicy;
+use crate::retention::RetentionPolicy;
+use crate::secrets::SecretsKey;
+use crate::tls::TlsSource;
+use pulsiora_core::{PulsioraError, Result};
+use serde::Deserialize;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File read when neither `--config` nor `PULSIORA_CONFIG` names one
+pub const DEFAULT_CONFIG_FILE: &str = "pulsiora.toml";
+pub const DEFAULT_BIND: &str = "0.0.0.0:3000";
+pub const DEFAULT_DATA_DIR: &str = "data";
+
+/// Settings the server starts with
+///
+/// ```toml
+/// bind = "127.0.0.1:8080"
+/// data_dir = "/var/lib/pulsiora"
+/// storage = "sqlite"
+/// workers = 8
+/// webhook_secret = "..."
+/// github_token = "ghp_..."
+/// github_app_id = 123456
+/// github_app_private_key = "/etc/pulsiora/github-app.pem"
+/// github_checks = true
+/// log_retention_days = 30
+/// tls_cert = "/etc/pulsiora/cert.pem"
+/// tls_key = "/etc/pulsiora/key.pem"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address and port to listen on
+    pub bind: Option<String>,
+    /// Where the database, journal, logs, artifacts and workspaces live
+    pub data_dir: Option<PathBuf>,
+    /// `sqlite` or `memory`
+    pub storage: Option<String>,
+    /// SQLite database file [default: `<data_dir>/pulsiora.db`]
+    pub database: Option<PathBuf>,
+    /// Jobs run at once
+    pub workers: Option<usize>,
+    /// Executions running at once, across workers and resumed jobs
+    pub max_concurrent_executions: Option<usize>,
+    /// Steps running at once across all executions
+    pub max_concurrent_steps: Option<usize>,
+    /// Secret GitHub and generic webhook payloads must be signed with
+    pub webhook_secret: Option<String>,
+    /// 64 hex characters; repository secrets are encrypted with it and
+    /// can't be used without it
+    pub secrets_key: Option<String>,
+    /// Token for the GitHub API, e.g. for commit statuses
+    pub github_token: Option<String>,
+    /// ID of the GitHub App the server runs as, along with `github_app_private_key`
+    pub github_app_id: Option<u64>,
+    /// PEM file holding the GitHub App's private key
+    pub github_app_private_key: Option<PathBuf>,
+    /// Report executions as check runs instead of commit statuses; needs a
+    /// GitHub App
+    pub github_checks: Option<bool>,
+    /// Comment on pull requests with a summary of each execution
+    pub github_pr_comments: Option<bool>,
+    /// Address the server is reached at, e.g. `https://ci.example.com`, for
+    /// links in pull request comments
+    pub public_url: Option<String>,
+    /// Step logs older than this many days are deleted
+    pub log_retention_days: Option<u64>,
+    /// Step logs on disk are deleted, oldest first, beyond this many bytes
+    pub max_log_bytes: Option<u64>,
+    /// Finished executions older than this many days are removed, along with
+    /// their logs and artifacts
+    pub execution_retention_days: Option<u64>,
+    /// Finished executions kept per repository; older ones are removed
+    pub max_executions_per_repo: Option<usize>,
+    /// PEM certificate chain to serve HTTPS with, along with `tls_key`
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Domains to get a certificate for from Let's Encrypt, instead of
+    /// `tls_cert` and `tls_key`
+    pub acme_domains: Option<Vec<String>>,
+    /// Email address Let's Encrypt sends expiry notices to
+    pub acme_email: Option<String>,
+    /// Use Let's Encrypt's staging directory, whose certificates aren't trusted
+    pub acme_staging: Option<bool>,
+    /// API requests each user, or each address without a token, may make a
+    /// minute; 0 turns the limit off
+    pub rate_limit_per_minute: Option<u32>,
+    /// Jobs waiting for a worker before new ones are turned away
+    pub max_queued_jobs: Option<usize>,
+    /// Largest webhook payload accepted, in bytes
+    pub max_webhook_body_bytes: Option<u64>,
+    /// Times an execution that hit an infrastructure error is retried
+    pub infra_retries: Option<u32>,
+    /// Seconds before the first retry, doubled for each one after it
+    pub infra_retry_backoff_secs: Option<u64>,
+    /// Bucket step logs and artifacts are moved to once an execution ends
+    pub s3_bucket: Option<String>,
+    /// Prepended to the keys of objects in `s3_bucket`
+    pub s3_prefix: Option<String>,
+    /// Endpoint of an S3-compatible service, instead of AWS
+    pub s3_endpoint: Option<String>,
+    pub s3_region: Option<String>,
+    /// Address the bucket by path, as MinIO and most S3-compatible services need
+    pub s3_path_style: Option<bool>,
+    /// OTLP/gRPC endpoint spans are exported to, e.g. `http://localhost:4317`
+    pub otlp_endpoint: Option<String>,
+    /// Service name spans are exported under
+    pub otel_service_name: Option<String>,
+}
+
+impl ServerConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| PulsioraError::InvalidConfiguration(format!("Failed to read {}: {}", path.display(), e)))?;
+        toml::from_str(&content)
+            .map_err(|e| PulsioraError::InvalidConfiguration(format!("Invalid {}: {}", path.display(), e)))
+    }
+
+    /// Settings from the environment, e.g. `PULSIORA_WORKERS`; `lookup`
+    /// returns a variable's value if it is set and not blank
+    pub fn from_env(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let number = |name: &str| -> Result<Option<u64>> {
+            lookup(name)
+                .map(|value| {
+                    value.trim().parse().map_err(|_| {
+                        PulsioraError::InvalidConfiguration(format!(
+                            "{} must be a non-negative integer, got {:?}",
+                            name, value
+                        ))
+                    })
+                })
+                .transpose()
+        };
+        let count = |name: &str| number(name).map(|n| n.map(|n| n as usize));
+        Ok(Self {
+            bind: lookup("PULSIORA_BIND"),
+            data_dir: lookup("PULSIORA_DATA_DIR").map(PathBuf::from),
+            storage: lookup("PULSIORA_STORAGE"),
+            database: lookup("PULSIORA_DATABASE").map(PathBuf::from),
+            workers: count("PULSIORA_WORKERS")?,
+            max_concurrent_executions: count("PULSIORA_MAX_CONCURRENT_EXECUTIONS")?,
+            max_concurrent_steps: count("PULSIORA_MAX_CONCURRENT_STEPS")?,
+            webhook_secret: lookup("PULSIORA_WEBHOOK_SECRET"),
+            secrets_key: lookup("PULSIORA_SECRETS_KEY"),
+            github_token: lookup("GITHUB_TOKEN"),
+            github_app_id: number("PULSIORA_GITHUB_APP_ID")?,
+            github_app_private_key: lookup("PULSIORA_GITHUB_APP_PRIVATE_KEY").map(PathBuf::from),
+            github_checks: lookup("PULSIORA_GITHUB_CHECKS").map(|value| matches!(value.trim(), "1" | "true")),
+            github_pr_comments: lookup("PULSIORA_GITHUB_PR_COMMENTS").map(|value| matches!(value.trim(), "1" | "true")),
+            public_url: lookup("PULSIORA_PUBLIC_URL"),
+            log_retention_days: number("PULSIORA_LOG_RETENTION_DAYS")?,
+            max_log_bytes: number("PULSIORA_MAX_LOG_BYTES")?,
+            execution_retention_days: number("PULSIORA_EXECUTION_RETENTION_DAYS")?,
+            max_executions_per_repo: count("PULSIORA_MAX_EXECUTIONS_PER_REPO")?,
+            tls_cert: lookup("PULSIORA_TLS_CERT").map(PathBuf::from),
+            tls_key: lookup("PULSIORA_TLS_KEY").map(PathBuf::from),
+            acme_domains: lookup("PULSIORA_ACME_DOMAINS").map(|domains| {
+                domains
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|domain| !domain.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+            acme_email: lookup("PULSIORA_ACME_EMAIL"),
+            acme_staging: lookup("PULSIORA_ACME_STAGING").map(|value| matches!(value.trim(), "1" | "true")),
+            rate_limit_per_minute: number("PULSIORA_RATE_LIMIT_PER_MINUTE")?
+                .map(|n| u32::try_from(n).unwrap_or(u32::MAX)),
+            max_queued_jobs: count("PULSIORA_MAX_QUEUED_JOBS")?,
+            max_webhook_body_bytes: number("PULSIORA_MAX_WEBHOOK_BODY_BYTES")?,
+            infra_retries: number("PULSIORA_INFRA_RETRIES")?.map(|n| u32::try_from(n).unwrap_or(u32::MAX)),
+            infra_retry_backoff_secs: number("PULSIORA_INFRA_RETRY_BACKOFF_SECS")?,
+            s3_bucket: lookup("PULSIORA_S3_BUCKET"),
+            s3_prefix: lookup("PULSIORA_S3_PREFIX"),
+            s3_endpoint: lookup("PULSIORA_S3_ENDPOINT"),
+            s3_region: lookup("PULSIORA_S3_REGION"),
+            s3_path_style: lookup("PULSIORA_S3_PATH_STYLE").map(|value| matches!(value.trim(), "1" | "true")),
+            otlp_endpoint: lookup("PULSIORA_OTLP_ENDPOINT"),
+            otel_service_name: lookup("PULSIORA_OTEL_SERVICE_NAME"),
+        })
+    }
+
+    /// These settings, with every one `other` sets taking its place
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            bind: other.bind.or(self.bind),
+            data_dir: other.data_dir.or(self.data_dir),
+            storage: other.storage.or(self.storage),
+            database: other.database.or(self.database),
+            workers: other.workers.or(self.workers),
+            max_concurrent_executions: other.max_concurrent_executions.or(self.max_concurrent_executions),
+            max_concurrent_steps: other.max_concurrent_steps.or(self.max_concurrent_steps),
+            webhook_secret: other.webhook_secret.or(self.webhook_secret),
+            secrets_key: other.secrets_key.or(self.secrets_key),
+            github_token: other.github_token.or(self.github_token),
+            github_app_id: other.github_app_id.or(self.github_app_id),
+            github_app_private_key: other.github_app_private_key.or(self.github_app_private_key),
+            github_checks: other.github_checks.or(self.github_checks),
+            github_pr_comments: other.github_pr_comments.or(self.github_pr_comments),
+            public_url: other.public_url.or(self.public_url),
+            log_retention_days: other.log_retention_days.or(self.log_retention_days),
+            max_log_bytes: other.max_log_bytes.or(self.max_log_bytes),
+            execution_retention_days: other.execution_retention_days.or(self.execution_retention_days),
+            max_executions_per_repo: other.max_executions_per_repo.or(self.max_executions_per_repo),
+            tls_cert: other.tls_cert.or(self.tls_cert),
+            tls_key: other.tls_key.or(self.tls_key),
+            acme_domains: other.acme_domains.or(self.acme_domains),
+            acme_email: other.acme_email.or(self.acme_email),
+            acme_staging: other.acme_staging.or(self.acme_staging),
+            rate_limit_per_minute: other.rate_limit_per_minute.or(self.rate_limit_per_minute),
+            max_queued_jobs: other.max_queued_jobs.or(self.max_queued_jobs),
+            max_webhook_body_bytes: other.max_webhook_body_bytes.or(self.max_webhook_body_bytes),
+            infra_retries: other.infra_retries.or(self.infra_retries),
+            infra_retry_backoff_secs: other.infra_retry_backoff_secs.or(self.infra_retry_backoff_secs),
+            s3_bucket: other.s3_bucket.or(self.s3_bucket),
+            s3_prefix: other.s3_prefix.or(self.s3_prefix),
+            s3_endpoint: other.s3_endpoint.or(self.s3_endpoint),
+            s3_region: other.s3_region.or(self.s3_region),
+            s3_path_style: other.s3_path_style.or(self.s3_path_style),
+            otlp_endpoint: other.otlp_endpoint.or(self.otlp_endpoint),
+            otel_service_name: other.otel_service_name.or(self.otel_service_name),
+        }
+    }
+
+    pub fn bind_addr(&self) -> Result<SocketAddr> {
+        let bind = self.bind.as_deref().unwrap_or(DEFAULT_BIND);
+        bind.parse().map_err(|_| {
+            PulsioraError::InvalidConfiguration(format!("bind must be an address and port like {}, got {:?}", DEFAULT_BIND, bind))
+        })
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR))
+    }
+
+    pub fn retention(&self) -> RetentionPolicy {
+        let days = |days: u64| Duration::from_secs(days * 24 * 60 * 60);
+        RetentionPolicy {
+            max_executions_per_repo: self.max_executions_per_repo,
+            max_execution_age: self.execution_retention_days.map(days),
+            max_log_age: self.log_retention_days.map(days),
+            max_log_bytes: self.max_log_bytes,
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: self.infra_retries.unwrap_or(default.max_retries),
+            backoff: self
+                .infra_retry_backoff_secs
+                // Retries never wait more than an hour anyway
+                .map_or(default.backoff, |secs| chrono::Duration::seconds(secs.min(60 * 60) as i64)),
+        }
+    }
+
+    /// Key repository secrets are encrypted with, or None if secrets can't be used
+    pub fn secrets_key(&self) -> Result<Option<SecretsKey>> {
+        self.secrets_key.as_deref().map(SecretsKey::from_hex).transpose()
+    }
+
+    /// The GitHub App the server runs as, or None to use `github_token`
+    pub fn github_app(&self) -> Result<Option<GitHubApp>> {
+        match (self.github_app_id, &self.github_app_private_key) {
+            (None, None) => Ok(None),
+            (Some(app_id), Some(path)) => {
+                let key = fs::read(path).map_err(|e| {
+                    PulsioraError::InvalidConfiguration(format!("Failed to read {}: {}", path.display(), e))
+                })?;
+                GitHubApp::new(app_id, &key).map(Some)
+            }
+            _ => Err(PulsioraError::InvalidConfiguration(
+                "github_app_id and github_app_private_key must be set together".to_string(),
+            )),
+        }
+    }
+
+    /// Bucket to move logs and artifacts to, or None to keep them in the data
+    /// directory
+    pub fn object_store(&self) -> Option<S3Options> {
+        Some(S3Options {
+            bucket: self.s3_bucket.clone()?,
+            prefix: self.s3_prefix.clone().unwrap_or_default(),
+            endpoint: self.s3_endpoint.clone(),
+            region: self.s3_region.clone(),
+            path_style: self.s3_path_style.unwrap_or(false),
+        })
+    }
+
+    /// Where the HTTPS certificate comes from, or None to serve plain HTTP.
+    /// ACME keeps its account and certificates in `<data_dir>/acme`.
+    pub fn tls(&self) -> Result<Option<TlsSource>> {
+        let invalid = |message: &str| Err(PulsioraError::InvalidConfiguration(message.to_string()));
+        let domains = self.acme_domains.clone().filter(|domains| !domains.is_empty());
+        match (&self.tls_cert, &self.tls_key, domains) {
+            (None, None, None) => Ok(None),
+            (Some(cert), Some(key), None) => Ok(Some(TlsSource::Files {
+                cert: cert.clone(),
+                key: key.clone(),
+            })),
+            (None, None, Some(domains)) => Ok(Some(TlsSource::Acme {
+                domains,
+                contact: self.acme_email.clone(),
+                cache_dir: self.data_dir().join("acme"),
+                staging: self.acme_staging.unwrap_or(false),
+            })),
+            (_, _, Some(_)) => invalid("acme_domains can't be combined with tls_cert and tls_key"),
+            _ => invalid("tls_cert and tls_key must be set together"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_layered_config() {
+        let file: ServerConfig = toml::from_str(
+            r#"
+            bind = "127.0.0.1:8080"
+            data_dir = "/var/lib/pulsiora"
+            workers = 8
+            github_token = "from-file"
+            "#,
+        )
+        .unwrap();
+        let env: HashMap<&str, &str> = [
+            ("PULSIORA_WORKERS", "2"),
+            ("PULSIORA_LOG_RETENTION_DAYS", "30"),
+            ("PULSIORA_MAX_WEBHOOK_BODY_BYTES", "1048576"),
+        ]
+        .into();
+        let env = ServerConfig::from_env(|name| env.get(name).map(|v| v.to_string())).unwrap();
+        let flags = ServerConfig {
+            bind: Some("0.0.0.0:9000".to_string()),
+            ..Default::default()
+        };
+
+        let config = file.merge(env).merge(flags);
+        assert_eq!(config.bind_addr().unwrap(), "0.0.0.0:9000".parse().unwrap());
+        assert_eq!(config.data_dir(), PathBuf::from("/var/lib/pulsiora"));
+        assert_eq!(config.workers, Some(2));
+        assert_eq!(config.log_retention_days, Some(30));
+        assert_eq!(config.max_webhook_body_bytes, Some(1024 * 1024));
+        assert_eq!(config.retention().max_log_age, Some(Duration::from_secs(30 * 24 * 60 * 60)));
+        assert!(!config.retention().prunes_executions());
+        assert_eq!(config.retry_policy(), RetryPolicy::default());
+        let env: HashMap<&str, &str> = [("PULSIORA_INFRA_RETRIES", "0"), ("PULSIORA_INFRA_RETRY_BACKOFF_SECS", "5")].into();
+        let retries = ServerConfig::from_env(|name| env.get(name).map(|v| v.to_string())).unwrap().retry_policy();
+        assert_eq!(retries.max_retries, 0);
+        assert_eq!(retries.backoff, chrono::Duration::seconds(5));
+        assert_eq!(config.github_token.as_deref(), Some("from-file"));
+        assert_eq!(ServerConfig::default().bind_addr().unwrap(), DEFAULT_BIND.parse().unwrap());
+
+        assert!(toml::from_str::<ServerConfig>("port = 80").is_err());
+        assert!(ServerConfig::from_env(|_| Some("many".to_string())).is_err());
+        let bad_bind = ServerConfig {
+            bind: Some("localhost".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_bind.bind_addr().is_err());
+    }
+
+    #[test]
+    fn test_tls_source() {
+        assert_eq!(ServerConfig::default().tls().unwrap(), None);
+        let env: HashMap<&str, &str> = [("PULSIORA_ACME_DOMAINS", "ci.example.com, hooks.example.com,")].into();
+        let acme = ServerConfig::from_env(|name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(
+            acme.tls().unwrap(),
+            Some(TlsSource::Acme {
+                domains: vec!["ci.example.com".to_string(), "hooks.example.com".to_string()],
+                contact: None,
+                cache_dir: PathBuf::from("data/acme"),
+                staging: false,
+            })
+        );
+
+        let files = ServerConfig {
+            tls_cert: Some(PathBuf::from("cert.pem")),
+            tls_key: Some(PathBuf::from("key.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(files.tls().unwrap(), Some(TlsSource::Files { .. })));
+        assert!(acme.clone().merge(files).tls().is_err());
+        let cert_only = ServerConfig {
+            tls_cert: Some(PathBuf::from("cert.pem")),
+            ..Default::default()
+        };
+        assert!(cert_only.tls().is_err());
+    }
+
+    #[test]
+    fn test_github_app_config() {
+        assert!(ServerConfig::default().github_app().unwrap().is_none());
+        let env: HashMap<&str, &str> = [("PULSIORA_GITHUB_APP_ID", "12345")].into();
+        let id_only = ServerConfig::from_env(|name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(id_only.github_app_id, Some(12345));
+        assert!(id_only.github_app().is_err());
+        let missing_key = ServerConfig {
+            github_app_private_key: Some(PathBuf::from("/nonexistent/github-app.pem")),
+            ..id_only
+        };
+        assert!(missing_key.github_app().is_err());
+    }
+}
@@ -0,0 +1,570 @@
+// SQLite storage: executions, with their step results, registered
+// repositories and their secrets, users and organizations in one database
+// file, so they survive restarts. Step output is also kept in a full-text
+// index for log search. The schema is created and upgraded by numbered
+// migrations when the database is opened.
+
+use crate::accounts::{Organization, User};
+use crate::search::LogSearch;
+use crate::storage::{ExecutionQuery, RegisteredRepo, Storage};
+use async_trait::async_trait;
+use pulsiora_core::{PipelineExecution, PulsioraError, Result};
+use pulsiora_runner::strip_ansi;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::{QueryBuilder, Row, Sqlite};
+use std::path::Path;
+use tracing::info;
+use uuid::Uuid;
+
+/// Schema changes, applied in order. The database's `user_version` is the
+/// number of migrations it has; add new ones at the end, never edit old ones.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE executions (
+        id TEXT PRIMARY KEY,
+        repository TEXT NOT NULL,
+        pipeline_name TEXT NOT NULL,
+        status TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        execution TEXT NOT NULL
+    );
+    CREATE INDEX executions_repository ON executions (repository, started_at);
+    CREATE TABLE step_results (
+        execution_id TEXT NOT NULL REFERENCES executions (id) ON DELETE CASCADE,
+        step_index INTEGER NOT NULL,
+        step_name TEXT NOT NULL,
+        status TEXT NOT NULL,
+        result TEXT NOT NULL,
+        PRIMARY KEY (execution_id, step_index)
+    );
+    CREATE TABLE repos (
+        repo_identifier TEXT PRIMARY KEY,
+        repo TEXT NOT NULL
+    );",
+    "CREATE TABLE secrets (
+        repo_identifier TEXT NOT NULL,
+        name TEXT NOT NULL,
+        value BLOB NOT NULL,
+        PRIMARY KEY (repo_identifier, name)
+    );",
+    "CREATE TABLE users (
+        username TEXT PRIMARY KEY,
+        organization TEXT NOT NULL,
+        admin INTEGER NOT NULL,
+        token_hash TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE organizations (
+        name TEXT PRIMARY KEY,
+        organization TEXT NOT NULL
+    );",
+    // Trigram index of step names and output for log search; steps stored
+    // before it are indexed with their colors left in
+    "CREATE VIRTUAL TABLE step_logs USING fts5(
+        execution_id UNINDEXED,
+        step_index UNINDEXED,
+        step_name,
+        stdout,
+        stderr,
+        tokenize = 'trigram'
+    );
+    INSERT INTO step_logs (execution_id, step_index, step_name, stdout, stderr)
+        SELECT execution_id, step_index, step_name, json_extract(result, '$.stdout'), json_extract(result, '$.stderr')
+        FROM step_results;",
+];
+
+fn storage_error(e: impl std::fmt::Display) -> PulsioraError {
+    PulsioraError::StorageError(e.to_string())
+}
+
+/// Storage in a SQLite database
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    /// Open or create the database at `path` and bring its schema up to date
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let options = SqliteConnectOptions::new()
+            .filename(path.as_ref())
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(storage_error)?;
+        let storage = Self { pool };
+        storage.migrate().await?;
+        Ok(storage)
+    }
+
+    /// Number of migrations applied to the database
+    pub async fn schema_version(&self) -> Result<usize> {
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(version as usize)
+    }
+
+    async fn migrate(&self) -> Result<()> {
+        let current = self.schema_version().await?;
+        if current > MIGRATIONS.len() {
+            return Err(PulsioraError::StorageError(format!(
+                "Database schema version {} is newer than this server knows ({})",
+                current,
+                MIGRATIONS.len()
+            )));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+            let mut tx = self.pool.begin().await.map_err(storage_error)?;
+            sqlx::raw_sql(migration).execute(&mut *tx).await.map_err(storage_error)?;
+            // PRAGMA doesn't take bound parameters
+            sqlx::raw_sql(&format!("PRAGMA user_version = {}", index + 1))
+                .execute(&mut *tx)
+                .await
+                .map_err(storage_error)?;
+            tx.commit().await.map_err(storage_error)?;
+            info!(version = index + 1, "Applied database migration");
+        }
+        Ok(())
+    }
+
+    /// An execution's step results, in order
+    async fn step_results(&self, id: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar("SELECT result FROM step_results WHERE execution_id = ? ORDER BY step_index")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(storage_error)
+    }
+}
+
+/// An execution from its row, with its steps from `step_results`
+fn execution_from_json(execution: &str, steps: Vec<String>) -> Result<PipelineExecution> {
+    let mut execution: PipelineExecution = serde_json::from_str(execution).map_err(storage_error)?;
+    execution.step_results = steps
+        .iter()
+        .map(|step| serde_json::from_str(step))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(storage_error)?;
+    Ok(execution)
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn store_execution(&self, execution: &PipelineExecution) -> Result<()> {
+        let id = execution.id.to_string();
+        // Steps are kept in their own table; the execution row holds the rest
+        let row = PipelineExecution {
+            step_results: Vec::new(),
+            ..execution.clone()
+        };
+        let mut tx = self.pool.begin().await.map_err(storage_error)?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO executions (id, repository, pipeline_name, status, started_at, execution)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(&execution.repository.full_name)
+        .bind(&execution.pipeline_name)
+        .bind(format!("{:?}", execution.status))
+        .bind(execution.started_at.timestamp_micros())
+        .bind(serde_json::to_string(&row).map_err(storage_error)?)
+        .execute(&mut *tx)
+        .await
+        .map_err(storage_error)?;
+        for table in ["step_results", "step_logs"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE execution_id = ?", table))
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(storage_error)?;
+        }
+        for (index, step) in execution.step_results.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO step_results (execution_id, step_index, step_name, status, result) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&id)
+            .bind(index as i64)
+            .bind(&step.step_name)
+            .bind(format!("{:?}", step.status))
+            .bind(serde_json::to_string(step).map_err(storage_error)?)
+            .execute(&mut *tx)
+            .await
+            .map_err(storage_error)?;
+            sqlx::query(
+                "INSERT INTO step_logs (execution_id, step_index, step_name, stdout, stderr) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&id)
+            .bind(index as i64)
+            .bind(&step.step_name)
+            .bind(strip_ansi(&step.stdout))
+            .bind(strip_ansi(&step.stderr))
+            .execute(&mut *tx)
+            .await
+            .map_err(storage_error)?;
+        }
+        tx.commit().await.map_err(storage_error)
+    }
+
+    async fn get_execution(&self, id: Uuid) -> Result<Option<PipelineExecution>> {
+        let id = id.to_string();
+        let row: Option<String> = sqlx::query_scalar("SELECT execution FROM executions WHERE id = ?")
+            .bind(&id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        let Some(row) = row else { return Ok(None) };
+        execution_from_json(&row, self.step_results(&id).await?).map(Some)
+    }
+
+    async fn list_executions(&self) -> Result<Vec<PipelineExecution>> {
+        let rows = sqlx::query("SELECT id, execution FROM executions ORDER BY started_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        let step_rows = sqlx::query("SELECT execution_id, result FROM step_results ORDER BY execution_id, step_index")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        let mut steps: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+        for row in step_rows {
+            steps
+                .entry(row.try_get("execution_id").map_err(storage_error)?)
+                .or_default()
+                .push(row.try_get("result").map_err(storage_error)?);
+        }
+        rows.iter()
+            .map(|row| {
+                let id: String = row.try_get("id").map_err(storage_error)?;
+                let execution: String = row.try_get("execution").map_err(storage_error)?;
+                execution_from_json(&execution, steps.remove(&id).unwrap_or_default())
+            })
+            .collect()
+    }
+
+    async fn query_executions(&self, query: &ExecutionQuery) -> Result<Vec<PipelineExecution>> {
+        let mut sql = QueryBuilder::<Sqlite>::new("SELECT id, execution FROM executions WHERE 1 = 1");
+        if let Some(repository) = &query.repository {
+            sql.push(" AND repository = ").push_bind(repository);
+        }
+        if let Some(status) = query.status {
+            sql.push(" AND status = ").push_bind(format!("{:?}", status));
+        }
+        if let Some(label) = &query.label {
+            sql.push(" AND EXISTS (SELECT 1 FROM json_each(execution, '$.pipeline_labels') WHERE value = ")
+                .push_bind(label)
+                .push(")");
+        }
+        if let Some(branch) = &query.branch {
+            sql.push(" AND json_extract(execution, '$.git_event.branch') = ").push_bind(branch);
+        }
+        if let Some(since) = query.since {
+            sql.push(" AND started_at >= ").push_bind(since.timestamp_micros());
+        }
+        sql.push(" ORDER BY started_at DESC");
+        if let Some(limit) = query.limit {
+            sql.push(" LIMIT ").push_bind(i64::try_from(limit).unwrap_or(i64::MAX));
+        }
+        let rows = sql.build().fetch_all(&self.pool).await.map_err(storage_error)?;
+        let mut executions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: String = row.try_get("id").map_err(storage_error)?;
+            let execution: String = row.try_get("execution").map_err(storage_error)?;
+            executions.push(execution_from_json(&execution, self.step_results(&id).await?)?);
+        }
+        Ok(executions)
+    }
+
+    async fn search_executions(&self, search: &LogSearch) -> Result<Vec<PipelineExecution>> {
+        let mut sql = QueryBuilder::<Sqlite>::new(
+            "SELECT id, execution FROM executions
+             WHERE id IN (SELECT execution_id FROM step_logs WHERE step_logs MATCH ",
+        );
+        sql.push_bind(search.fts_query()).push(")");
+        if let Some(repositories) = &search.repositories {
+            if repositories.is_empty() {
+                return Ok(Vec::new());
+            }
+            let mut list = sql.push(" AND repository IN (").separated(", ");
+            for repository in repositories {
+                list.push_bind(repository);
+            }
+            sql.push(")");
+        }
+        sql.push(" ORDER BY started_at DESC LIMIT ")
+            .push_bind(i64::try_from(search.limit).unwrap_or(i64::MAX));
+        let rows = sql.build().fetch_all(&self.pool).await.map_err(storage_error)?;
+        let mut executions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: String = row.try_get("id").map_err(storage_error)?;
+            let execution: String = row.try_get("execution").map_err(storage_error)?;
+            executions.push(execution_from_json(&execution, self.step_results(&id).await?)?);
+        }
+        Ok(executions)
+    }
+
+    async fn remove_execution(&self, id: Uuid) -> Result<bool> {
+        // Step results go with it; the search index has no foreign keys
+        let id = id.to_string();
+        let mut tx = self.pool.begin().await.map_err(storage_error)?;
+        sqlx::query("DELETE FROM step_logs WHERE execution_id = ?")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(storage_error)?;
+        let result = sqlx::query("DELETE FROM executions WHERE id = ?")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(storage_error)?;
+        tx.commit().await.map_err(storage_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn store_repo(&self, repo: &RegisteredRepo) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO repos (repo_identifier, repo) VALUES (?, ?)")
+            .bind(&repo.repo_identifier)
+            .bind(serde_json::to_string(repo).map_err(storage_error)?)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn get_repo(&self, repo_identifier: &str) -> Result<Option<RegisteredRepo>> {
+        let repo: Option<String> = sqlx::query_scalar("SELECT repo FROM repos WHERE repo_identifier = ?")
+            .bind(repo_identifier)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        repo.map(|repo| serde_json::from_str(&repo).map_err(storage_error)).transpose()
+    }
+
+    async fn remove_repo(&self, repo_identifier: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await.map_err(storage_error)?;
+        sqlx::query("DELETE FROM secrets WHERE repo_identifier = ?")
+            .bind(repo_identifier)
+            .execute(&mut *tx)
+            .await
+            .map_err(storage_error)?;
+        let result = sqlx::query("DELETE FROM repos WHERE repo_identifier = ?")
+            .bind(repo_identifier)
+            .execute(&mut *tx)
+            .await
+            .map_err(storage_error)?;
+        tx.commit().await.map_err(storage_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_repos(&self) -> Result<Vec<RegisteredRepo>> {
+        let repos: Vec<String> = sqlx::query_scalar("SELECT repo FROM repos ORDER BY repo_identifier")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        repos
+            .iter()
+            .map(|repo| serde_json::from_str(repo).map_err(storage_error))
+            .collect()
+    }
+
+    async fn store_secret(&self, repo_identifier: &str, name: &str, value: &[u8]) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO secrets (repo_identifier, name, value) VALUES (?, ?, ?)")
+            .bind(repo_identifier)
+            .bind(name)
+            .bind(value)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn get_secret(&self, repo_identifier: &str, name: &str) -> Result<Option<Vec<u8>>> {
+        sqlx::query_scalar("SELECT value FROM secrets WHERE repo_identifier = ? AND name = ?")
+            .bind(repo_identifier)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn list_secrets(&self, repo_identifier: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar("SELECT name FROM secrets WHERE repo_identifier = ? ORDER BY name")
+            .bind(repo_identifier)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn remove_secret(&self, repo_identifier: &str, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM secrets WHERE repo_identifier = ? AND name = ?")
+            .bind(repo_identifier)
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn store_user(&self, user: &User) -> Result<()> {
+        // The token hash isn't serialized, so users are kept in columns
+        sqlx::query(
+            "INSERT OR REPLACE INTO users (username, organization, admin, token_hash, created_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&user.username)
+        .bind(&user.organization)
+        .bind(user.admin)
+        .bind(&user.token_hash)
+        .bind(user.created_at.timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>> {
+        let rows = sqlx::query("SELECT username, organization, admin, token_hash, created_at FROM users ORDER BY username")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        rows.iter()
+            .map(|row| {
+                let created_at: i64 = row.try_get("created_at").map_err(storage_error)?;
+                Ok(User {
+                    username: row.try_get("username").map_err(storage_error)?,
+                    organization: row.try_get("organization").map_err(storage_error)?,
+                    admin: row.try_get("admin").map_err(storage_error)?,
+                    token_hash: row.try_get("token_hash").map_err(storage_error)?,
+                    created_at: chrono::DateTime::from_timestamp_micros(created_at)
+                        .ok_or_else(|| storage_error(format!("Invalid created_at {}", created_at)))?,
+                })
+            })
+            .collect()
+    }
+
+    async fn remove_user(&self, username: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM users WHERE username = ?")
+            .bind(username)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn store_organization(&self, organization: &Organization) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO organizations (name, organization) VALUES (?, ?)")
+            .bind(&organization.name)
+            .bind(serde_json::to_string(organization).map_err(storage_error)?)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn list_organizations(&self) -> Result<Vec<Organization>> {
+        let organizations: Vec<String> = sqlx::query_scalar("SELECT organization FROM organizations ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        organizations
+            .iter()
+            .map(|organization| serde_json::from_str(organization).map_err(storage_error))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance::{self, execution, repo};
+    use crate::storage::{InMemoryStorage, StorageChange};
+    use pulsiora_core::PipelineStatus;
+
+    #[tokio::test]
+    async fn test_sqlite_storage_conformance() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-db-{}", Uuid::new_v4()));
+        let storage = SqliteStorage::open(dir.join("pulsiora.db")).await.unwrap();
+        conformance::check_storage(&storage).await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_storage_survives_reopen() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-db-{}", Uuid::new_v4()));
+        let path = dir.join("pulsiora.db");
+        let storage = SqliteStorage::open(&path).await.unwrap();
+        assert_eq!(storage.schema_version().await.unwrap(), MIGRATIONS.len());
+
+        let mut first = execution(&["build", "test"]);
+        storage.store_execution(&first).await.unwrap();
+        // Storing again replaces the steps rather than adding to them
+        first.step_results.truncate(1);
+        first.status = PipelineStatus::Failed;
+        storage.store_execution(&first).await.unwrap();
+        storage.store_execution(&execution(&[])).await.unwrap();
+        storage.store_repo(&repo()).await.unwrap();
+        drop(storage);
+
+        let storage = SqliteStorage::open(&path).await.unwrap();
+        let stored = storage.get_execution(first.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, PipelineStatus::Failed);
+        assert_eq!(stored.step_results.len(), 1);
+        assert_eq!(stored.step_results[0].stdout, "build ok\n");
+        assert_eq!(storage.list_executions().await.unwrap().len(), 2);
+        assert_eq!(storage.list_repos().await.unwrap(), vec![repo()]);
+        assert!(storage.remove_repo("test/repo").await.unwrap());
+        assert!(!storage.remove_repo("test/repo").await.unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_storage_writes_through() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-db-{}", Uuid::new_v4()));
+        let storage = SqliteStorage::open(dir.join("pulsiora.db")).await.unwrap();
+        let stored = execution(&["build"]);
+        storage.store_execution(&stored).await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut memory = InMemoryStorage::new();
+        memory.restore(&storage, tx).await.unwrap();
+        assert!(memory.get_execution(&stored.id.to_string()).is_some());
+        assert!(memory.persisting());
+        storage.ping().await.unwrap();
+
+        memory.register_repo(repo());
+        memory.set_execution_status(stored.id, PipelineStatus::Interrupted);
+        memory.unregister_repo("test/repo");
+        let mut changes = Vec::new();
+        while let Ok(change) = rx.try_recv() {
+            changes.push(change);
+        }
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(&changes[1].change, StorageChange::Execution(e) if e.status == PipelineStatus::Interrupted));
+        for write in changes {
+            storage.apply(write.change).await.unwrap();
+        }
+        let reloaded = storage.get_execution(stored.id).await.unwrap().unwrap();
+        assert_eq!(reloaded.status, PipelineStatus::Interrupted);
+        assert!(storage.list_repos().await.unwrap().is_empty());
+
+        // At shutdown the writer sees the end of the changes
+        memory.register_repo(repo());
+        memory.stop_persisting();
+        assert!(!memory.persisting());
+        assert!(matches!(rx.recv().await.map(|write| write.change), Some(StorageChange::Repo(_))));
+        assert!(rx.recv().await.is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
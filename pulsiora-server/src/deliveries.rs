@@ -0,0 +1,303 @@
+// Webhook deliveries as they were received, so they can be replayed. Senders
+// identify each delivery with an ID, e.g. GitHub's `X-GitHub-Delivery` GUID,
+// and send it again under the same ID when they redeliver it. A delivery
+// that was handled already is skipped; one that failed, e.g. because the
+// queue was full, is handled again. One JSON file is kept per delivery, and
+// only the most recent ones are kept.
+
+use chrono::{DateTime, Utc};
+use pulsiora_core::{PulsioraError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Headers senders put a delivery's ID in, in the order they're looked at
+pub const DELIVERY_ID_HEADERS: [&str; 2] = ["X-GitHub-Delivery", "X-Gitlab-Event-UUID"];
+
+/// How many deliveries are kept; older ones are dropped
+pub const MAX_STORED_DELIVERIES: usize = 1000;
+
+/// Longest delivery ID accepted
+const MAX_DELIVERY_ID_LEN: usize = 128;
+
+/// Endpoint a delivery was sent to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeliverySource {
+    /// The GitHub webhook, with the event named in `X-GitHub-Event`
+    #[serde(rename = "github")]
+    GitHub { event: String },
+    /// A repository's generic webhook
+    Generic { repository: String },
+}
+
+/// A webhook delivery and how it was handled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub source: DeliverySource,
+    /// Repository the delivery is about, if it names one
+    pub repository: Option<String>,
+    pub received_at: DateTime<Utc>,
+    /// The payload, as it was received
+    pub body: String,
+    /// HTTP status it was last answered with
+    pub status: u16,
+    /// Executions it last queued
+    #[serde(default)]
+    pub execution_ids: Vec<Uuid>,
+    /// How many times it was replayed, and when last
+    #[serde(default)]
+    pub replays: u32,
+    #[serde(default)]
+    pub replayed_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookDelivery {
+    pub fn new(id: String, source: DeliverySource, body: String) -> Self {
+        let repository = match &source {
+            DeliverySource::GitHub { .. } => payload_repository(body.as_bytes()),
+            DeliverySource::Generic { repository } => Some(repository.clone()),
+        };
+        Self {
+            id,
+            source,
+            repository,
+            received_at: Utc::now(),
+            body,
+            status: 0,
+            execution_ids: Vec::new(),
+            replays: 0,
+            replayed_at: None,
+        }
+    }
+
+    /// Whether it was handled, so a redelivery has nothing left to do. Server
+    /// errors and a full queue leave it to be handled again.
+    pub fn handled(&self) -> bool {
+        self.status != 0 && self.status < 500 && self.status != 429
+    }
+}
+
+/// Repository a payload names in `repository.full_name`, read without
+/// keeping the rest of the payload in memory
+pub fn payload_repository(body: &[u8]) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Payload {
+        repository: Option<Repository>,
+    }
+    #[derive(Deserialize)]
+    struct Repository {
+        full_name: String,
+    }
+    serde_json::from_slice::<Payload>(body).ok()?.repository.map(|repository| repository.full_name)
+}
+
+/// Whether `id` can identify a delivery: letters, digits, `-` and `_`
+pub fn valid_delivery_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_DELIVERY_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+/// Received deliveries, one JSON file each
+pub struct DeliveryLog {
+    dir: PathBuf,
+    max_deliveries: usize,
+    /// Deliveries being handled right now
+    in_flight: Mutex<HashSet<String>>,
+}
+
+impl DeliveryLog {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_deliveries: MAX_STORED_DELIVERIES,
+            in_flight: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Keep at most this many deliveries
+    pub fn with_max_deliveries(mut self, max_deliveries: usize) -> Self {
+        self.max_deliveries = max_deliveries.max(1);
+        self
+    }
+
+    fn delivery_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Mark a new delivery as being handled. False if it is a duplicate: it's
+    /// being handled right now, or it was handled already.
+    pub fn begin(&self, id: &str) -> Result<bool> {
+        if self.get(id)?.is_some_and(|delivery| delivery.handled()) {
+            return Ok(false);
+        }
+        Ok(self.claim(id))
+    }
+
+    /// Mark a delivery as being handled, even if it was handled before, as
+    /// when it's replayed. False if it's being handled right now.
+    pub fn claim(&self, id: &str) -> bool {
+        self.in_flight.lock().unwrap().insert(id.to_string())
+    }
+
+    /// Store a delivery once it was handled, dropping the oldest ones beyond
+    /// the limit; the temp file + rename keeps a crash from leaving half a one
+    pub fn record(&self, delivery: &WebhookDelivery) -> Result<()> {
+        let result = self.write(delivery);
+        self.in_flight.lock().unwrap().remove(&delivery.id);
+        result?;
+        self.prune()
+    }
+
+    fn write(&self, delivery: &WebhookDelivery) -> Result<()> {
+        if !valid_delivery_id(&delivery.id) {
+            return Err(PulsioraError::StorageError(format!("invalid delivery ID {:?}", delivery.id)));
+        }
+        let json = serde_json::to_vec(delivery)
+            .map_err(|e| PulsioraError::StorageError(format!("Failed to serialize delivery: {}", e)))?;
+        let tmp = self.dir.join(format!("{}.json.tmp", delivery.id));
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, self.delivery_path(&delivery.id))?;
+        Ok(())
+    }
+
+    /// A stored delivery
+    pub fn get(&self, id: &str) -> Result<Option<WebhookDelivery>> {
+        if !valid_delivery_id(id) {
+            return Ok(None);
+        }
+        match fs::read(self.delivery_path(id)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| PulsioraError::StorageError(format!("Failed to read delivery {}: {}", id, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Stored deliveries, newest first. Unreadable entries are skipped.
+    pub fn list(&self) -> Result<Vec<WebhookDelivery>> {
+        let mut deliveries = Vec::new();
+        for path in self.delivery_files()? {
+            match fs::read(&path).map(|bytes| serde_json::from_slice::<WebhookDelivery>(&bytes)) {
+                Ok(Ok(delivery)) => deliveries.push(delivery),
+                _ => tracing::warn!(path = %path.display(), "Skipping unreadable webhook delivery"),
+            }
+        }
+        deliveries.sort_by_key(|delivery| std::cmp::Reverse(delivery.received_at));
+        Ok(deliveries)
+    }
+
+    fn delivery_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("json") {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Drop the least recently written deliveries beyond the limit
+    fn prune(&self) -> Result<()> {
+        let files = self.delivery_files()?;
+        if files.len() <= self.max_deliveries {
+            return Ok(());
+        }
+        let mut files: Vec<_> = files
+            .into_iter()
+            .map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (modified, path)
+            })
+            .collect();
+        files.sort();
+        let excess = files.len() - self.max_deliveries;
+        for (_, path) in files.into_iter().take(excess) {
+            if let Err(e) = fs::remove_file(&path) {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to remove old webhook delivery");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str) -> (PathBuf, DeliveryLog) {
+        let dir = std::env::temp_dir().join(format!("pulsiora-deliveries-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let log = DeliveryLog::open(&dir).unwrap();
+        (dir, log)
+    }
+
+    fn delivery(id: &str, status: u16) -> WebhookDelivery {
+        let mut delivery = WebhookDelivery::new(
+            id.to_string(),
+            DeliverySource::GitHub {
+                event: "push".to_string(),
+            },
+            r#"{"repository": {"full_name": "test/repo"}}"#.to_string(),
+        );
+        delivery.status = status;
+        delivery
+    }
+
+    #[test]
+    fn test_delivery_dedup() {
+        let (dir, log) = temp_log("dedup");
+        assert!(log.begin("d-1").unwrap());
+        // Being handled right now
+        assert!(!log.begin("d-1").unwrap());
+        log.record(&delivery("d-1", 202)).unwrap();
+        // Handled already
+        assert!(!log.begin("d-1").unwrap());
+        // Replays go ahead anyway, unless one is running
+        assert!(log.claim("d-1"));
+        assert!(!log.claim("d-1"));
+
+        // A delivery turned away with a full queue is handled again
+        assert!(log.begin("d-2").unwrap());
+        log.record(&delivery("d-2", 429)).unwrap();
+        assert!(log.begin("d-2").unwrap());
+
+        let stored = log.get("d-1").unwrap().unwrap();
+        assert_eq!(stored.repository.as_deref(), Some("test/repo"));
+        assert_eq!(stored.status, 202);
+        assert_eq!(serde_json::to_value(&stored.source).unwrap()["type"], "github");
+        assert!(log.get("missing").unwrap().is_none());
+        assert!(log.get("../queue/x").unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_delivery_log_keeps_latest() {
+        let (dir, log) = temp_log("prune");
+        let log = log.with_max_deliveries(2);
+        for (n, id) in ["a", "b", "c"].into_iter().enumerate() {
+            let mut delivery = delivery(id, 200);
+            delivery.received_at += chrono::Duration::seconds(n as i64);
+            log.record(&delivery).unwrap();
+            // Distinct modification times
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        let ids: Vec<_> = log.list().unwrap().into_iter().map(|delivery| delivery.id).collect();
+        assert_eq!(ids, ["c", "b"]);
+
+        assert!(log.record(&delivery("not/valid", 200)).is_err());
+        assert!(valid_delivery_id("72d3162e-cc78-11e3-81ab-4c9367dc0958"));
+        assert!(!valid_delivery_id(""));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+// Deployments: executions of a pipeline with an `environment` that
+// succeeded, kept per repository so each environment's current and previous
+// deployment can be looked up. A rollback is recorded as a new deployment of
+// an earlier one's execution, marking the deployment it undid, so the
+// history reads in the order things happened.
+
+use chrono::{DateTime, Utc};
+use pulsiora_core::{PipelineExecution, PipelineStatus};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// How many deployments are kept per environment of a repository
+pub const MAX_DEPLOYMENTS_PER_ENVIRONMENT: usize = 100;
+
+/// An execution that deployed to an environment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub id: Uuid,
+    pub environment: String,
+    /// The execution that deployed, or for a rollback the one whose
+    /// deployment the environment went back to
+    pub execution_id: Uuid,
+    pub pipeline: String,
+    pub commit_sha: Option<String>,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    /// Who triggered the execution, or marked the rollback
+    pub deployed_by: String,
+    pub deployed_at: DateTime<Utc>,
+    /// For a rollback, the deployment whose state was restored
+    #[serde(default)]
+    pub rollback_of: Option<Uuid>,
+    /// When a rollback undid this deployment
+    #[serde(default)]
+    pub rolled_back_at: Option<DateTime<Utc>>,
+}
+
+impl Deployment {
+    /// The deployment an execution made: one that succeeded, of a pipeline
+    /// with an environment
+    pub fn from_execution(execution: &PipelineExecution) -> Option<Self> {
+        let environment = execution.environment.clone()?;
+        if execution.status != PipelineStatus::Success {
+            return None;
+        }
+        let git_event = &execution.git_event;
+        Some(Self {
+            id: Uuid::new_v4(),
+            environment,
+            execution_id: execution.id,
+            pipeline: execution.pipeline_name.clone(),
+            commit_sha: git_event.commit_sha.clone(),
+            branch: git_event.branch.clone(),
+            tag: git_event.tag.clone(),
+            deployed_by: git_event.sender.clone(),
+            deployed_at: execution.completed_at.unwrap_or_else(Utc::now),
+            rollback_of: None,
+            rolled_back_at: None,
+        })
+    }
+
+    /// A deployment putting `environment` back to `target`, marked by `by`
+    fn rollback_to(target: &Deployment, by: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            deployed_by: by.to_string(),
+            deployed_at: Utc::now(),
+            rollback_of: Some(target.id),
+            rolled_back_at: None,
+            ..target.clone()
+        }
+    }
+}
+
+/// An environment's latest deployments
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnvironmentSummary {
+    pub name: String,
+    pub current: Deployment,
+    /// What a rollback goes back to by default
+    pub previous: Option<Deployment>,
+    pub deployments: usize,
+}
+
+/// Why a rollback can't be marked
+#[derive(Debug, Clone, PartialEq)]
+pub enum RollbackError {
+    /// The environment has no deployments
+    UnknownEnvironment,
+    /// The target isn't one of the environment's deployments
+    UnknownDeployment,
+    /// The environment has nothing before its current deployment, or the
+    /// target is the current deployment
+    NothingToRollBack,
+}
+
+/// Add a deployment to a repository's, dropping the oldest of its
+/// environment past `MAX_DEPLOYMENTS_PER_ENVIRONMENT`
+pub fn record_deployment(deployments: &mut Vec<Deployment>, deployment: Deployment) {
+    let environment = deployment.environment.clone();
+    deployments.push(deployment);
+    let count = deployments.iter().filter(|d| d.environment == environment).count();
+    let mut excess = count.saturating_sub(MAX_DEPLOYMENTS_PER_ENVIRONMENT);
+    deployments.retain(|d| {
+        if excess > 0 && d.environment == environment {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+/// An environment's deployments, newest first
+pub fn environment_deployments<'a>(deployments: &'a [Deployment], environment: &str) -> Vec<&'a Deployment> {
+    deployments.iter().rev().filter(|d| d.environment == environment).collect()
+}
+
+/// The deployment a rollback of an environment goes back to by default: the
+/// newest one before the current that wasn't rolled back and deployed a
+/// different execution
+fn previous_deployment<'a>(history: &[&'a Deployment]) -> Option<&'a Deployment> {
+    let current = history.first()?;
+    history[1..]
+        .iter()
+        .find(|d| d.rolled_back_at.is_none() && d.execution_id != current.execution_id)
+        .copied()
+}
+
+/// Every environment deployed to, by name, with its current and previous
+/// deployment
+pub fn environments(deployments: &[Deployment]) -> Vec<EnvironmentSummary> {
+    let mut names: Vec<&str> = deployments.iter().map(|d| d.environment.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let history = environment_deployments(deployments, name);
+            Some(EnvironmentSummary {
+                name: name.to_string(),
+                current: (*history.first()?).clone(),
+                previous: previous_deployment(&history).cloned(),
+                deployments: history.len(),
+            })
+        })
+        .collect()
+}
+
+/// Mark `environment` as rolled back by `by` to the deployment `to`, or else
+/// to its previous one. The current deployment is marked as undone and the
+/// rollback recorded as the new current one, which is returned.
+pub fn mark_rollback(
+    deployments: &mut Vec<Deployment>,
+    environment: &str,
+    to: Option<Uuid>,
+    by: &str,
+) -> Result<Deployment, RollbackError> {
+    let history = environment_deployments(deployments, environment);
+    let current_id = history.first().ok_or(RollbackError::UnknownEnvironment)?.id;
+    let target = match to {
+        Some(id) => *history.iter().find(|d| d.id == id).ok_or(RollbackError::UnknownDeployment)?,
+        None => previous_deployment(&history).ok_or(RollbackError::NothingToRollBack)?,
+    };
+    if target.id == current_id {
+        return Err(RollbackError::NothingToRollBack);
+    }
+    let rollback = Deployment::rollback_to(target, by);
+    if let Some(current) = deployments.iter_mut().find(|d| d.id == current_id) {
+        current.rolled_back_at = Some(rollback.deployed_at);
+    }
+    record_deployment(deployments, rollback.clone());
+    Ok(rollback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance::execution;
+
+    fn deployed(environment: &str, commit: &str) -> Deployment {
+        let mut execution = execution(&["deploy"]);
+        execution.environment = Some(environment.to_string());
+        execution.git_event.commit_sha = Some(commit.to_string());
+        Deployment::from_execution(&execution).unwrap()
+    }
+
+    #[test]
+    fn test_deployment_from_execution() {
+        let mut execution = execution(&["deploy"]);
+        assert!(Deployment::from_execution(&execution).is_none());
+        execution.environment = Some("production".to_string());
+        let deployment = Deployment::from_execution(&execution).unwrap();
+        assert_eq!(deployment.environment, "production");
+        assert_eq!(deployment.execution_id, execution.id);
+        execution.status = PipelineStatus::Failed;
+        assert!(Deployment::from_execution(&execution).is_none());
+    }
+
+    #[test]
+    fn test_environments_and_rollbacks() {
+        let mut deployments = Vec::new();
+        for (environment, commit) in [("production", "a1"), ("staging", "b1"), ("production", "a2"), ("production", "a3")] {
+            record_deployment(&mut deployments, deployed(environment, commit));
+        }
+        let summaries = environments(&deployments);
+        assert_eq!(summaries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["production", "staging"]);
+        let production = &summaries[0];
+        assert_eq!(production.current.commit_sha.as_deref(), Some("a3"));
+        assert_eq!(production.previous.as_ref().unwrap().commit_sha.as_deref(), Some("a2"));
+        assert_eq!(production.deployments, 3);
+        assert!(summaries[1].previous.is_none());
+
+        // Back to a2, then on back to a1 as a2 was the previous one
+        let rollback = mark_rollback(&mut deployments, "production", None, "alice").unwrap();
+        assert_eq!(rollback.commit_sha.as_deref(), Some("a2"));
+        assert_eq!(rollback.deployed_by, "alice");
+        let history = environment_deployments(&deployments, "production");
+        assert_eq!(history[0].id, rollback.id);
+        assert!(history[1].rolled_back_at.is_some());
+        assert_eq!(
+            environments(&deployments)[0].previous.as_ref().unwrap().commit_sha.as_deref(),
+            Some("a1")
+        );
+        let rollback = mark_rollback(&mut deployments, "production", None, "alice").unwrap();
+        assert_eq!(rollback.commit_sha.as_deref(), Some("a1"));
+
+        let current = environment_deployments(&deployments, "production")[0].id;
+        assert_eq!(
+            mark_rollback(&mut deployments, "production", Some(current), "alice"),
+            Err(RollbackError::NothingToRollBack)
+        );
+        assert_eq!(
+            mark_rollback(&mut deployments, "staging", None, "alice"),
+            Err(RollbackError::NothingToRollBack)
+        );
+        assert_eq!(
+            mark_rollback(&mut deployments, "qa", None, "alice"),
+            Err(RollbackError::UnknownEnvironment)
+        );
+        assert_eq!(
+            mark_rollback(&mut deployments, "production", Some(Uuid::new_v4()), "alice"),
+            Err(RollbackError::UnknownDeployment)
+        );
+    }
+
+    #[test]
+    fn test_deployment_history_is_capped() {
+        let mut deployments = vec![deployed("staging", "s")];
+        for i in 0..MAX_DEPLOYMENTS_PER_ENVIRONMENT + 5 {
+            record_deployment(&mut deployments, deployed("production", &i.to_string()));
+        }
+        let production = environment_deployments(&deployments, "production");
+        assert_eq!(production.len(), MAX_DEPLOYMENTS_PER_ENVIRONMENT);
+        assert_eq!(production.last().unwrap().commit_sha.as_deref(), Some("5"));
+        assert_eq!(environment_deployments(&deployments, "staging").len(), 1);
+    }
+}
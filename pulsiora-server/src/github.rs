@@ -1,30 +1,77 @@
-use pulsiora_core::{Repository, PulsioraError, Result};
+use pulsiora_core::{
+    Annotation, AnnotationLevel, PipelineExecution, PipelineStatus, PulsioraError, Repository, Result, StepResult,
+};
 use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
 
-pub async fn fetch_pulsefile(repository: &Repository) -> Result<String> {
+pub(crate) const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Annotations GitHub accepts in one check run update; more are sent in
+/// further updates, which add to the earlier ones
+const MAX_ANNOTATIONS_PER_REQUEST: usize = 50;
+
+/// Longest check run summary GitHub accepts
+const MAX_SUMMARY_CHARS: usize = 65_535;
+
+/// Request for a repository's Pulsefile at `git_ref`, or on its default
+/// branch without one. With a token,
+/// e.g. `GITHUB_TOKEN` or an app installation token, it goes through the
+/// contents API, which serves private repositories too; without one it's
+/// fetched from raw.githubusercontent.com, which only serves public ones.
+fn pulsefile_request(
+    client: &Client,
+    repository: &Repository,
+    git_ref: Option<&str>,
+    token: Option<&str>,
+) -> reqwest::RequestBuilder {
+    let git_ref = git_ref.unwrap_or(&repository.default_branch);
+    match token {
+        // The raw media type answers with the file itself rather than JSON
+        Some(token) => client
+            .get(format!("{}/repos/{}/contents/Pulsefile", GITHUB_API_URL, repository.full_name))
+            .query(&[("ref", git_ref)])
+            .bearer_auth(token)
+            .header("Accept", "application/vnd.github.raw+json")
+            .header("User-Agent", "pulsiora"),
+        None => client.get(format!(
+            "https://raw.githubusercontent.com/{}/{}/Pulsefile",
+            repository.full_name, git_ref
+        )),
+    }
+}
+
+/// Fetch a repository's Pulsefile at `git_ref`, or on its default branch
+/// without one; see `pulsefile_request`
+pub async fn fetch_pulsefile(repository: &Repository, git_ref: Option<&str>, token: Option<&str>) -> Result<String> {
     let client = Client::new();
-    
-    // Construct GitHub raw content URL
-    // For now, we'll fetch from the default branch
-    let url = format!(
-        "https://raw.githubusercontent.com/{}/{}/Pulsefile",
-        repository.full_name,
-        repository.default_branch
+    let request = pulsefile_request(&client, repository, git_ref, token);
+    info!(
+        repository = %repository.full_name,
+        git_ref,
+        authenticated = token.is_some(),
+        "Fetching Pulsefile"
     );
 
-    info!("Fetching Pulsefile from: {}", url);
-
-    let response = client
-        .get(&url)
+    let response = request
         .send()
         .await
         .map_err(|e| PulsioraError::NetworkError(format!("Failed to fetch Pulsefile: {}", e)))?;
 
-    if !response.status().is_success() {
-        return Err(PulsioraError::PipelineNotFound(format!(
-            "Pulsefile not found in repository {}",
-            repository.full_name
-        )));
+    match response.status() {
+        status if status.is_success() => {}
+        reqwest::StatusCode::NOT_FOUND => {
+            return Err(PulsioraError::PipelineNotFound(format!(
+                "Pulsefile not found in repository {}",
+                repository.full_name
+            )));
+        }
+        status => {
+            return Err(PulsioraError::GitHubError(format!(
+                "Fetching the Pulsefile of {} failed with {}",
+                repository.full_name, status
+            )));
+        }
     }
 
     let content = response
@@ -35,5 +82,775 @@ pub async fn fetch_pulsefile(repository: &Repository) -> Result<String> {
     Ok(content)
 }
 
+/// Commit status state reported to GitHub for a pipeline status
+pub fn commit_status_state(status: PipelineStatus) -> Option<&'static str> {
+    match status {
+        // Interrupted executions can still be resumed
+        PipelineStatus::Pending | PipelineStatus::Running | PipelineStatus::Interrupted => Some("pending"),
+        PipelineStatus::Success => Some("success"),
+        PipelineStatus::Failed => Some("failure"),
+        PipelineStatus::Cancelled => Some("error"),
+        PipelineStatus::Skipped => None,
+    }
+}
+
+fn github_request(client: &Client, method: reqwest::Method, token: &str, path: &str) -> reqwest::RequestBuilder {
+    client
+        .request(method, format!("{}{}", GITHUB_API_URL, path))
+        .bearer_auth(token)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "pulsiora")
+}
+
+/// Create or update a commit status, which also makes the context known to GitHub
+pub async fn report_commit_status(
+    token: &str,
+    repository: &Repository,
+    sha: &str,
+    context: &str,
+    state: &str,
+    description: &str,
+) -> Result<()> {
+    let client = Client::new();
+    let path = format!("/repos/{}/statuses/{}", repository.full_name, sha);
+
+    let response = github_request(&client, reqwest::Method::POST, token, &path)
+        .json(&json!({
+            "state": state,
+            "context": context,
+            "description": description,
+        }))
+        .send()
+        .await
+        .map_err(|e| PulsioraError::NetworkError(format!("Failed to report commit status: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(PulsioraError::GitHubError(format!(
+            "Reporting status {} on {} failed with {}",
+            context,
+            repository.full_name,
+            response.status()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check run conclusion for a pipeline status, or None while the execution
+/// can still go on
+pub fn check_run_conclusion(status: PipelineStatus) -> Option<&'static str> {
+    match status {
+        PipelineStatus::Pending | PipelineStatus::Running | PipelineStatus::Interrupted => None,
+        PipelineStatus::Success => Some("success"),
+        PipelineStatus::Failed => Some("failure"),
+        PipelineStatus::Cancelled => Some("cancelled"),
+        PipelineStatus::Skipped => Some("skipped"),
+    }
+}
+
+/// A check run annotation for a problem found in a step's output. GitHub
+/// only takes annotations on a file, so problems without one are left to
+/// the summary.
+pub fn check_run_annotation(annotation: &Annotation) -> Option<serde_json::Value> {
+    let path = annotation.file.as_ref()?;
+    let line = annotation.line.unwrap_or(1);
+    let mut value = json!({
+        "path": path,
+        "start_line": line,
+        "end_line": line,
+        "annotation_level": match annotation.level {
+            AnnotationLevel::Error => "failure",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Notice => "notice",
+        },
+        "message": annotation.message,
+    });
+    if let (Some(_), Some(column)) = (annotation.line, annotation.column) {
+        value["start_column"] = json!(column);
+        value["end_column"] = json!(column);
+    }
+    Some(value)
+}
+
+/// Markdown summary of the steps that ran so far: a table of their status
+/// and duration, followed by problems found that name no file
+pub fn check_run_summary(steps: &[StepResult]) -> String {
+    let mut summary = String::from("| Step | Status | Duration |\n|---|---|---|\n");
+    for step in steps {
+        summary.push_str(&format!(
+            "| {} | {:?} | {:.1}s |\n",
+            step.step_name.replace('|', "\\|"),
+            step.status,
+            step.duration_ms as f64 / 1000.0
+        ));
+    }
+    let unplaced: Vec<String> = steps
+        .iter()
+        .flat_map(|step| &step.annotations)
+        .filter(|annotation| annotation.file.is_none())
+        .map(|annotation| format!("- {}", annotation))
+        .collect();
+    if !unplaced.is_empty() {
+        summary.push('\n');
+        summary.push_str(&unplaced.join("\n"));
+        summary.push('\n');
+    }
+    if summary.len() > MAX_SUMMARY_CHARS {
+        let mut end = MAX_SUMMARY_CHARS - 4;
+        while !summary.is_char_boundary(end) {
+            end -= 1;
+        }
+        summary.truncate(end);
+        summary.push_str("\n...");
+    }
+    summary
+}
+
+/// What a check run shows after an update
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckRunUpdate {
+    pub title: String,
+    pub summary: String,
+    /// Problems found since the last update
+    pub annotations: Vec<Annotation>,
+    /// Completes the check run when set
+    pub conclusion: Option<&'static str>,
+}
+
+/// A check run on a commit, updated as the execution it reports on goes on
+pub struct CheckRun {
+    client: Client,
+    token: String,
+    path: String,
+}
+
+impl CheckRun {
+    /// Start an in-progress check run named `name` on `sha`. Creating check
+    /// runs takes a GitHub App installation token.
+    pub async fn create(token: &str, repository: &Repository, sha: &str, name: &str, external_id: &str) -> Result<Self> {
+        let client = Client::new();
+        let path = format!("/repos/{}/check-runs", repository.full_name);
+
+        let response = github_request(&client, reqwest::Method::POST, token, &path)
+            .json(&json!({
+                "name": name,
+                "head_sha": sha,
+                "status": "in_progress",
+                "external_id": external_id,
+                "started_at": chrono::Utc::now(),
+            }))
+            .send()
+            .await
+            .map_err(|e| PulsioraError::NetworkError(format!("Failed to create check run: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(PulsioraError::GitHubError(format!(
+                "Creating check run {} on {} failed with {}",
+                name,
+                repository.full_name,
+                response.status()
+            )));
+        }
+
+        let created: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| PulsioraError::NetworkError(format!("Failed to read check run: {}", e)))?;
+        let id = created["id"]
+            .as_u64()
+            .ok_or_else(|| PulsioraError::GitHubError("Created check run has no id".to_string()))?;
+
+        Ok(Self {
+            client,
+            token: token.to_string(),
+            path: format!("{}/{}", path, id),
+        })
+    }
+
+    pub async fn update(&self, update: &CheckRunUpdate) -> Result<()> {
+        let annotations: Vec<serde_json::Value> = update.annotations.iter().filter_map(check_run_annotation).collect();
+        let mut chunks = annotations.chunks(MAX_ANNOTATIONS_PER_REQUEST);
+        let mut body = json!({
+            "status": if update.conclusion.is_some() { "completed" } else { "in_progress" },
+            "output": {
+                "title": update.title,
+                "summary": update.summary,
+                "annotations": chunks.next().unwrap_or_default(),
+            },
+        });
+        if let Some(conclusion) = update.conclusion {
+            body["conclusion"] = json!(conclusion);
+            body["completed_at"] = json!(chrono::Utc::now());
+        }
+        self.patch(&body).await?;
+
+        for chunk in chunks {
+            self.patch(&json!({
+                "output": {
+                    "title": update.title,
+                    "summary": update.summary,
+                    "annotations": chunk,
+                },
+            }))
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn patch(&self, body: &serde_json::Value) -> Result<()> {
+        let response = github_request(&self.client, reqwest::Method::PATCH, &self.token, &self.path)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| PulsioraError::NetworkError(format!("Failed to update check run: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(PulsioraError::GitHubError(format!(
+                "Updating check run {} failed with {}",
+                self.path,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Hidden first line of the comment a status context keeps on a pull request
+fn pr_comment_marker(context: &str) -> String {
+    format!("<!-- pulsiora: {} -->", context)
+}
+
+/// Markdown comment summarizing an execution on a pull request: each step's
+/// status and duration, coverage, problems found, and with `execution_url`
+/// links to each step's log
+pub fn pr_comment_body(context: &str, execution: &PipelineExecution, execution_url: Option<&str>) -> String {
+    let duration = execution
+        .completed_at
+        .map(|end| format!(" in {:.1}s", (end - execution.started_at).num_milliseconds().max(0) as f64 / 1000.0))
+        .unwrap_or_default();
+    let mut body = format!(
+        "{}\n### {}: {:?}{}\n\n",
+        pr_comment_marker(context),
+        context,
+        execution.status,
+        duration
+    );
+    if let Some(sha) = &execution.git_event.commit_sha {
+        body.push_str(&format!("Pipeline `{}` on {}\n\n", execution.pipeline_name, sha));
+    }
+
+    body.push_str("| Step | Status | Duration |");
+    body.push_str(if execution_url.is_some() { " Log |\n|---|---|---|---|\n" } else { "\n|---|---|---|\n" });
+    for (index, step) in execution.step_results.iter().enumerate() {
+        body.push_str(&format!(
+            "| {} | {:?} | {:.1}s |",
+            step.step_name.replace('|', "\\|"),
+            step.status,
+            step.duration_ms as f64 / 1000.0
+        ));
+        if let Some(url) = execution_url {
+            body.push_str(&format!(" [log]({}/steps/{}/log) |", url, index));
+        }
+        body.push('\n');
+    }
+
+    let mut notes = Vec::new();
+    if let Some(coverage) = &execution.coverage {
+        notes.push(format!("Coverage: {}", coverage));
+    }
+    let problems: Vec<&Annotation> = execution.step_results.iter().flat_map(|step| &step.annotations).collect();
+    if !problems.is_empty() {
+        let errors = problems.iter().filter(|a| a.level == AnnotationLevel::Error).count();
+        notes.push(format!("Problems: {} errors, {} other", errors, problems.len() - errors));
+    }
+    if let Some(limit) = &execution.limit_exceeded {
+        notes.push(format!("Stopped: {}", limit));
+    }
+    if !notes.is_empty() {
+        body.push('\n');
+        body.push_str(&notes.join("  \n"));
+        body.push('\n');
+    }
+    body
+}
+
+/// Post `body` on a pull request, or update the comment posted there earlier
+/// for the same status context
+pub async fn upsert_pr_comment(
+    token: &str,
+    repository: &Repository,
+    number: u64,
+    context: &str,
+    body: &str,
+) -> Result<()> {
+    let client = Client::new();
+    let marker = pr_comment_marker(context);
+    let comments_path = format!("/repos/{}/issues/{}/comments", repository.full_name, number);
+
+    let mut existing = None;
+    for page in 1.. {
+        let response = github_request(&client, reqwest::Method::GET, token, &comments_path)
+            .query(&[("per_page", "100"), ("page", &page.to_string())])
+            .send()
+            .await
+            .map_err(|e| PulsioraError::NetworkError(format!("Failed to list pull request comments: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(PulsioraError::GitHubError(format!(
+                "Listing comments on {}#{} failed with {}",
+                repository.full_name,
+                number,
+                response.status()
+            )));
+        }
+        let comments: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| PulsioraError::NetworkError(format!("Failed to read pull request comments: {}", e)))?;
+        existing = comments
+            .iter()
+            .find(|comment| comment["body"].as_str().is_some_and(|text| text.starts_with(&marker)))
+            .and_then(|comment| comment["id"].as_u64());
+        if existing.is_some() || comments.len() < 100 {
+            break;
+        }
+    }
+
+    let request = match existing {
+        Some(id) => github_request(
+            &client,
+            reqwest::Method::PATCH,
+            token,
+            &format!("/repos/{}/issues/comments/{}", repository.full_name, id),
+        ),
+        None => github_request(&client, reqwest::Method::POST, token, &comments_path),
+    };
+    let response = request
+        .json(&json!({ "body": body }))
+        .send()
+        .await
+        .map_err(|e| PulsioraError::NetworkError(format!("Failed to comment on pull request: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(PulsioraError::GitHubError(format!(
+            "Commenting on {}#{} failed with {}",
+            repository.full_name,
+            number,
+            response.status()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Pages of files GitHub lists for a pull request, at most; it stops at 3000 files
+const MAX_PR_FILE_PAGES: u32 = 30;
+
+/// Paths a pull request changes, relative to the repository root. Renamed
+/// files are listed under their old path as well as their new one.
+pub async fn pull_request_files(token: &str, repository: &Repository, number: u64) -> Result<Vec<String>> {
+    let client = Client::new();
+    let path = format!("/repos/{}/pulls/{}/files", repository.full_name, number);
+    let mut files = Vec::new();
+    for page in 1..=MAX_PR_FILE_PAGES {
+        let response = github_request(&client, reqwest::Method::GET, token, &path)
+            .query(&[("per_page", "100"), ("page", &page.to_string())])
+            .send()
+            .await
+            .map_err(|e| PulsioraError::NetworkError(format!("Failed to list pull request files: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(PulsioraError::GitHubError(format!(
+                "Listing files of {}#{} failed with {}",
+                repository.full_name,
+                number,
+                response.status()
+            )));
+        }
+        let listed: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| PulsioraError::NetworkError(format!("Failed to read pull request files: {}", e)))?;
+        for file in &listed {
+            for key in ["filename", "previous_filename"] {
+                if let Some(name) = file[key].as_str() {
+                    files.push(name.to_string());
+                }
+            }
+        }
+        if listed.len() < 100 {
+            break;
+        }
+    }
+    Ok(files)
+}
+
+/// Encode `value` as a single URL path segment
+fn path_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn required_status_checks_path(full_name: &str, branch: &str) -> String {
+    format!(
+        "/repos/{}/branches/{}/protection/required_status_checks",
+        full_name,
+        path_segment(branch)
+    )
+}
+
+/// The contexts a branch should require: those of `current` that Pulsiora
+/// doesn't manage, which other tools set up, plus `required`
+fn merge_required_contexts(current: &[String], managed: &[String], required: &[String]) -> Vec<String> {
+    let mut contexts: Vec<String> = current
+        .iter()
+        .filter(|c| !managed.contains(c) && !required.contains(c))
+        .cloned()
+        .collect();
+    contexts.extend(required.iter().cloned());
+    contexts
+}
+
+#[derive(Deserialize)]
+struct RequiredStatusChecks {
+    #[serde(default)]
+    contexts: Vec<String>,
+}
+
+/// Make `required` the contexts Pulsiora requires in a branch's protection
+/// rules. Contexts of `managed` that aren't required any more are removed;
+/// contexts other tools require are kept.
+pub async fn sync_required_status_checks(
+    token: &str,
+    full_name: &str,
+    branch: &str,
+    managed: &[String],
+    required: &[String],
+) -> Result<()> {
+    let client = Client::new();
+    let path = required_status_checks_path(full_name, branch);
+    let not_protected = || {
+        PulsioraError::GitHubError(format!(
+            "Branch protection with status checks is not enabled for {} on {}",
+            branch, full_name
+        ))
+    };
+
+    let response = github_request(&client, reqwest::Method::GET, token, &path)
+        .send()
+        .await
+        .map_err(|e| PulsioraError::NetworkError(format!("Failed to read branch protection: {}", e)))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(not_protected());
+    }
+    if !response.status().is_success() {
+        return Err(PulsioraError::GitHubError(format!(
+            "Reading required status checks for {} failed with {}",
+            full_name,
+            response.status()
+        )));
+    }
+    let current: RequiredStatusChecks = response
+        .json()
+        .await
+        .map_err(|e| PulsioraError::GitHubError(format!("Invalid required status checks: {}", e)))?;
+    let contexts = merge_required_contexts(&current.contexts, managed, required);
+
+    info!(repo = full_name, branch, ?contexts, "Syncing required status checks");
+
+    let response = github_request(&client, reqwest::Method::PATCH, token, &path)
+        .json(&json!({ "contexts": contexts }))
+        .send()
+        .await
+        .map_err(|e| PulsioraError::NetworkError(format!("Failed to update branch protection: {}", e)))?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(not_protected());
+    }
+    if !response.status().is_success() {
+        return Err(PulsioraError::GitHubError(format!(
+            "Updating required status checks for {} failed with {}",
+            full_name,
+            response.status()
+        )));
+    }
+
+    Ok(())
+}
+
+/// A repository webhook as GitHub lists it
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct GitHubHook {
+    pub id: u64,
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub config: GitHubHookConfig,
+    /// How GitHub's last delivery was answered; GitHub leaves `code` unset
+    /// until the hook has delivered something
+    #[serde(default)]
+    pub last_response: GitHubHookResponse,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct GitHubHookConfig {
+    pub url: Option<String>,
+    /// `json` or `form`
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct GitHubHookResponse {
+    pub code: Option<u16>,
+    pub status: Option<String>,
+    pub message: Option<String>,
+}
+
+/// The webhooks of a repository; needs a token allowed to manage them
+pub async fn fetch_repo_webhooks(token: &str, full_name: &str) -> Result<Vec<GitHubHook>> {
+    let client = Client::new();
+    let path = format!("/repos/{}/hooks", full_name);
+    let response = github_request(&client, reqwest::Method::GET, token, &path)
+        .query(&[("per_page", "100")])
+        .send()
+        .await
+        .map_err(|e| PulsioraError::NetworkError(format!("Failed to list webhooks: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(PulsioraError::GitHubError(format!(
+            "Listing the webhooks of {} failed with {}",
+            full_name,
+            response.status()
+        )));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| PulsioraError::GitHubError(format!("Failed to read the webhooks of {}: {}", full_name, e)))
+}
+
+/// CIDR ranges GitHub sends webhooks from, as published by the meta API
+pub async fn fetch_hook_ranges() -> Result<Vec<String>> {
+    let client = Client::new();
+    let response = client
+        .get(format!("{}/meta", GITHUB_API_URL))
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "pulsiora")
+        .send()
+        .await
+        .map_err(|e| PulsioraError::NetworkError(format!("Failed to fetch GitHub meta: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(PulsioraError::GitHubError(format!(
+            "Fetching GitHub meta failed with {}",
+            response.status()
+        )));
+    }
+
+    let meta: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| PulsioraError::NetworkError(format!("Failed to read GitHub meta: {}", e)))?;
+
+    Ok(meta
+        .get("hooks")
+        .and_then(|h| h.as_array())
+        .map(|ranges| {
+            ranges
+                .iter()
+                .filter_map(|r| r.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
 use tracing::info;
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(name: &str, annotations: Vec<Annotation>) -> StepResult {
+        StepResult {
+            step_name: name.to_string(),
+            status: pulsiora_core::StepStatus::Failed,
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: Some(1),
+            duration_ms: 1250,
+            started_at: chrono::Utc::now(),
+            completed_at: None,
+            encoding: None,
+            replacement_chars: 0,
+            stdout_bytes: 0,
+            stderr_bytes: 0,
+            log_ref: None,
+            hooks: vec![],
+            annotations,
+            coverage: None,
+            resources: None,
+        }
+    }
+
+    #[test]
+    fn test_check_run_output() {
+        let placed = Annotation {
+            level: AnnotationLevel::Error,
+            message: "mismatched types".to_string(),
+            file: Some("src/main.rs".to_string()),
+            line: Some(3),
+            column: Some(5),
+        };
+        let unplaced = Annotation {
+            level: AnnotationLevel::Warning,
+            message: "2 tests ignored".to_string(),
+            file: None,
+            line: None,
+            column: None,
+        };
+
+        let annotation = check_run_annotation(&placed).unwrap();
+        assert_eq!(annotation["path"], "src/main.rs");
+        assert_eq!(annotation["start_line"], 3);
+        assert_eq!(annotation["start_column"], 5);
+        assert_eq!(annotation["annotation_level"], "failure");
+        assert!(check_run_annotation(&unplaced).is_none());
+
+        let summary = check_run_summary(&[step("build|test", vec![placed, unplaced])]);
+        assert!(summary.contains("| build\\|test | Failed | 1.2s |"));
+        assert!(summary.ends_with("- warning: 2 tests ignored\n"));
+        assert!(!summary.contains("mismatched types"));
+
+        assert_eq!(check_run_conclusion(PipelineStatus::Failed), Some("failure"));
+        assert_eq!(check_run_conclusion(PipelineStatus::Interrupted), None);
+    }
+
+    #[test]
+    fn test_pr_comment_body() {
+        let repository = Repository {
+            owner: "acme".to_string(),
+            name: "shop".to_string(),
+            full_name: "acme/shop".to_string(),
+            clone_url: "https://github.com/acme/shop.git".to_string(),
+            default_branch: "main".to_string(),
+        };
+        let started_at = chrono::Utc::now();
+        let problem = Annotation {
+            level: AnnotationLevel::Error,
+            message: "mismatched types".to_string(),
+            file: None,
+            line: None,
+            column: None,
+        };
+        let execution = PipelineExecution {
+            id: uuid::Uuid::new_v4(),
+            pipeline_name: "build".to_string(),
+            pipeline_version: "1.0".to_string(),
+            pipeline_labels: vec![],
+            environment: None,
+            repository: repository.clone(),
+            git_event: pulsiora_core::GitEvent {
+                event_type: pulsiora_core::GitEventType::PullRequest,
+                repository,
+                branch: Some("feature".to_string()),
+                tag: None,
+                release_name: None,
+                pull_request: None,
+                commit_sha: Some("abc123".to_string()),
+                commit_message: None,
+                sender: "dana".to_string(),
+                inputs: Default::default(),
+            },
+            status: PipelineStatus::Failed,
+            step_results: vec![step("compile", vec![problem])],
+            phases: vec![],
+            limit_exceeded: None,
+            coverage: Some(pulsiora_core::CoverageReport {
+                lines_covered: 80,
+                lines_total: 100,
+            }),
+            metrics: Default::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
+            started_at,
+            completed_at: Some(started_at + chrono::Duration::milliseconds(2500)),
+        };
+
+        let body = pr_comment_body("ci/build", &execution, Some("https://ci.example.com/api/v1/executions/1"));
+        assert!(body.starts_with("<!-- pulsiora: ci/build -->\n### ci/build: Failed in 2.5s\n"));
+        assert!(body.contains("Pipeline `build` on abc123"));
+        assert!(body.contains(
+            "| compile | Failed | 1.2s | [log](https://ci.example.com/api/v1/executions/1/steps/0/log) |"
+        ));
+        assert!(body.contains("Coverage: 80.0% (80/100 lines)"));
+        assert!(body.contains("Problems: 1 errors, 0 other"));
+
+        let body = pr_comment_body("ci/build", &execution, None);
+        assert!(body.contains("| Step | Status | Duration |\n|---|---|---|\n| compile | Failed | 1.2s |\n"));
+    }
+
+    #[test]
+    fn test_pulsefile_request() {
+        let client = Client::new();
+        let repository = Repository {
+            owner: "acme".to_string(),
+            name: "shop".to_string(),
+            full_name: "acme/shop".to_string(),
+            clone_url: String::new(),
+            default_branch: "release/2.x".to_string(),
+        };
+
+        let request = pulsefile_request(&client, &repository, None, None).build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://raw.githubusercontent.com/acme/shop/release/2.x/Pulsefile"
+        );
+        assert!(request.headers().get("Authorization").is_none());
+
+        // Private repositories need the contents API and a token
+        let request = pulsefile_request(&client, &repository, None, Some("ghs_token")).build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.github.com/repos/acme/shop/contents/Pulsefile?ref=release%2F2.x"
+        );
+        assert_eq!(request.headers()["Authorization"], "Bearer ghs_token");
+        assert_eq!(request.headers()["Accept"], "application/vnd.github.raw+json");
+
+        // At the commit that triggered the execution
+        let request = pulsefile_request(&client, &repository, Some("4f2a9c1"), None).build().unwrap();
+        assert_eq!(request.url().as_str(), "https://raw.githubusercontent.com/acme/shop/4f2a9c1/Pulsefile");
+        let request = pulsefile_request(&client, &repository, Some("refs/pull/7/head"), Some("ghs_token"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.github.com/repos/acme/shop/contents/Pulsefile?ref=refs%2Fpull%2F7%2Fhead"
+        );
+    }
+
+    #[test]
+    fn test_required_status_checks_path() {
+        assert_eq!(
+            required_status_checks_path("acme/shop", "release/1.0"),
+            "/repos/acme/shop/branches/release%2F1.0/protection/required_status_checks"
+        );
+    }
+
+    #[test]
+    fn test_merge_required_contexts() {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        // `lint` comes from another tool; `pulsiora/e2e` is no longer required
+        let current = strings(&["lint", "pulsiora/build", "pulsiora/e2e"]);
+        let managed = strings(&["pulsiora/build", "pulsiora/e2e", "pulsiora/test"]);
+        let required = strings(&["pulsiora/build", "pulsiora/test"]);
+        assert_eq!(
+            merge_required_contexts(&current, &managed, &required),
+            strings(&["lint", "pulsiora/build", "pulsiora/test"])
+        );
+    }
+}
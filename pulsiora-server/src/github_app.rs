@@ -0,0 +1,281 @@
+// Running as a GitHub App: the server signs a short JWT with the app's
+// private key and exchanges it for installation tokens, which act on the
+// repositories the app is installed on. Tokens last an hour and are cached
+// until shortly before they expire. The app can also point each installed
+// repository's webhook at this server.
+
+use crate::github::GITHUB_API_URL;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use pulsiora_core::{PulsioraError, Result};
+use reqwest::{Client, Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// GitHub events repository webhooks are created for: the ones pipelines run on
+pub const GITHUB_WEBHOOK_EVENTS: [&str; 5] = ["push", "pull_request", "create", "delete", "release"];
+
+/// Installation tokens are renewed this long before they expire
+const TOKEN_RENEW_MARGIN: Duration = Duration::minutes(5);
+
+/// Items GitHub lists per page, at most
+const PER_PAGE: usize = 100;
+
+/// Claims of the JWT the app authenticates with
+#[derive(Debug, PartialEq, Serialize)]
+struct AppClaims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+impl AppClaims {
+    fn new(app_id: u64, now: DateTime<Utc>) -> Self {
+        // GitHub takes JWTs valid for up to 10 minutes; issuing them a minute
+        // early allows for clock drift
+        Self {
+            iat: (now - Duration::seconds(60)).timestamp(),
+            exp: (now + Duration::minutes(9)).timestamp(),
+            iss: app_id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl InstallationToken {
+    fn fresh(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at - now > TOKEN_RENEW_MARGIN
+    }
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct InstallationRepos {
+    repositories: Vec<InstalledRepo>,
+}
+
+#[derive(Deserialize)]
+struct InstalledRepo {
+    full_name: String,
+}
+
+#[derive(Deserialize)]
+struct RepoHook {
+    #[serde(default)]
+    config: HookConfig,
+}
+
+#[derive(Default, Deserialize)]
+struct HookConfig {
+    url: Option<String>,
+}
+
+/// A GitHub App the server acts as
+pub struct GitHubApp {
+    app_id: u64,
+    key: EncodingKey,
+    client: Client,
+    /// Installation each repository belongs to, by `owner/repo`
+    installations: Mutex<HashMap<String, u64>>,
+    /// Installation tokens, by installation ID
+    tokens: Mutex<HashMap<u64, InstallationToken>>,
+}
+
+impl GitHubApp {
+    /// The app with ID `app_id`, signing with its PEM private key as
+    /// downloaded from the app's settings
+    pub fn new(app_id: u64, private_key_pem: &[u8]) -> Result<Self> {
+        let key = EncodingKey::from_rsa_pem(private_key_pem)
+            .map_err(|e| PulsioraError::InvalidConfiguration(format!("invalid GitHub App private key: {}", e)))?;
+        Ok(Self {
+            app_id,
+            key,
+            client: Client::new(),
+            installations: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn app_id(&self) -> u64 {
+        self.app_id
+    }
+
+    fn jwt(&self) -> Result<String> {
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &AppClaims::new(self.app_id, Utc::now()), &self.key)
+            .map_err(|e| PulsioraError::GitHubError(format!("failed to sign GitHub App JWT: {}", e)))
+    }
+
+    fn request(&self, method: Method, token: &str, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", GITHUB_API_URL, path))
+            .bearer_auth(token)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "pulsiora")
+    }
+
+    /// Installation token for a repository the app is installed on
+    pub async fn token_for_repo(&self, repo: &str) -> Result<String> {
+        let installation = self.installation_for(repo).await?;
+        let token = self.installation_token(installation).await;
+        if token.is_err() {
+            // E.g. the app was reinstalled; look the installation up again next time
+            self.installations.lock().unwrap().remove(repo);
+        }
+        token
+    }
+
+    async fn installation_for(&self, repo: &str) -> Result<u64> {
+        if let Some(id) = self.installations.lock().unwrap().get(repo) {
+            return Ok(*id);
+        }
+        let path = format!("/repos/{}/installation", repo);
+        let response = self.request(Method::GET, &self.jwt()?, &path).send().await;
+        let installation: Installation = parse(response, &format!("finding the installation for {}", repo)).await?;
+        self.installations.lock().unwrap().insert(repo.to_string(), installation.id);
+        Ok(installation.id)
+    }
+
+    /// Token acting on an installation's repositories, cached until shortly
+    /// before it expires
+    pub async fn installation_token(&self, installation: u64) -> Result<String> {
+        if let Some(token) = self.tokens.lock().unwrap().get(&installation) {
+            if token.fresh(Utc::now()) {
+                return Ok(token.token.clone());
+            }
+        }
+        let path = format!("/app/installations/{}/access_tokens", installation);
+        let response = self.request(Method::POST, &self.jwt()?, &path).send().await;
+        let token: InstallationToken = parse(response, "creating an installation token").await?;
+        self.tokens.lock().unwrap().insert(installation, token.clone());
+        Ok(token.token)
+    }
+
+    /// IDs of the app's installations
+    pub async fn installations(&self) -> Result<Vec<u64>> {
+        let jwt = self.jwt()?;
+        let mut ids = Vec::new();
+        for page in 1.. {
+            let path = format!("/app/installations?per_page={}&page={}", PER_PAGE, page);
+            let response = self.request(Method::GET, &jwt, &path).send().await;
+            let installations: Vec<Installation> = parse(response, "listing installations").await?;
+            let last = installations.len() < PER_PAGE;
+            ids.extend(installations.into_iter().map(|installation| installation.id));
+            if last {
+                break;
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Repositories an installation covers, as `owner/repo`
+    pub async fn installation_repos(&self, installation: u64) -> Result<Vec<String>> {
+        let token = self.installation_token(installation).await?;
+        let mut repos = Vec::new();
+        for page in 1.. {
+            let path = format!("/installation/repositories?per_page={}&page={}", PER_PAGE, page);
+            let response = self.request(Method::GET, &token, &path).send().await;
+            let listed: InstallationRepos = parse(response, "listing installation repositories").await?;
+            let last = listed.repositories.len() < PER_PAGE;
+            repos.extend(listed.repositories.into_iter().map(|repo| repo.full_name));
+            if last {
+                break;
+            }
+        }
+        let mut installations = self.installations.lock().unwrap();
+        for repo in &repos {
+            installations.insert(repo.clone(), installation);
+        }
+        Ok(repos)
+    }
+
+    /// Point the webhook of every repository an installation covers at
+    /// `url`, signed with `secret`. Repositories that already have a webhook
+    /// for `url` are left alone. Returns how many webhooks were created.
+    pub async fn configure_webhooks(&self, installation: u64, url: &str, secret: Option<&str>) -> Result<usize> {
+        let token = self.installation_token(installation).await?;
+        let mut created = 0;
+        for repo in self.installation_repos(installation).await? {
+            let path = format!("/repos/{}/hooks", repo);
+            let response = self.request(Method::GET, &token, &path).send().await;
+            let hooks: Vec<RepoHook> = parse(response, &format!("listing the webhooks of {}", repo)).await?;
+            if hooks.iter().any(|hook| hook.config.url.as_deref() == Some(url)) {
+                continue;
+            }
+            let response = self
+                .request(Method::POST, &token, &path)
+                .json(&webhook_body(url, secret))
+                .send()
+                .await;
+            let _: serde_json::Value = parse(response, &format!("creating a webhook for {}", repo)).await?;
+            created += 1;
+        }
+        Ok(created)
+    }
+}
+
+/// Body creating a repository webhook that delivers `GITHUB_WEBHOOK_EVENTS`
+/// to `url` as JSON
+fn webhook_body(url: &str, secret: Option<&str>) -> serde_json::Value {
+    let mut config = json!({ "url": url, "content_type": "json" });
+    if let Some(secret) = secret {
+        config["secret"] = json!(secret);
+    }
+    json!({
+        "name": "web",
+        "active": true,
+        "events": GITHUB_WEBHOOK_EVENTS,
+        "config": config,
+    })
+}
+
+async fn parse<T: DeserializeOwned>(response: reqwest::Result<Response>, what: &str) -> Result<T> {
+    let response = response.map_err(|e| PulsioraError::NetworkError(format!("{} failed: {}", what, e)))?;
+    if !response.status().is_success() {
+        return Err(PulsioraError::GitHubError(format!("{} failed with {}", what, response.status())));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| PulsioraError::GitHubError(format!("{} returned an unexpected answer: {}", what, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_app() {
+        let now = Utc::now();
+        let claims = AppClaims::new(12345, now);
+        assert_eq!(claims.iss, "12345");
+        assert!(claims.iat < now.timestamp());
+        assert!(claims.exp - claims.iat <= 10 * 60);
+
+        let token = |minutes| InstallationToken {
+            token: "ghs_x".to_string(),
+            expires_at: now + Duration::minutes(minutes),
+        };
+        assert!(token(60).fresh(now));
+        assert!(!token(2).fresh(now));
+
+        let body = webhook_body("https://ci.example.com/api/v1/webhook/github", Some("shh"));
+        assert_eq!(body["config"]["url"], "https://ci.example.com/api/v1/webhook/github");
+        assert_eq!(body["config"]["secret"], "shh");
+        assert_eq!(body["events"][0], "push");
+        assert!(webhook_body("https://ci.example.com", None)["config"].get("secret").is_none());
+
+        assert!(GitHubApp::new(1, b"not a key").is_err());
+    }
+}
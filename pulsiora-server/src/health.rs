@@ -0,0 +1,144 @@
+// Health reports for load balancers and orchestrators. Liveness only says the
+// process answers; readiness runs a check per dependency a job needs and
+// fails if any of them does.
+
+use crate::queue::JobScheduler;
+use serde::Serialize;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Failing,
+}
+
+/// The outcome of checking one dependency
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub status: HealthStatus,
+    /// What was found, e.g. how busy the workers are or why a check failed
+    pub detail: String,
+}
+
+impl HealthCheck {
+    pub fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: HealthStatus::Ok,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn failing(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: HealthStatus::Failing,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// Failing if any check is
+    pub status: HealthStatus,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    pub fn new(checks: Vec<HealthCheck>) -> Self {
+        let failing = checks.iter().any(|check| check.status == HealthStatus::Failing);
+        Self {
+            status: if failing { HealthStatus::Failing } else { HealthStatus::Ok },
+            checks,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == HealthStatus::Ok
+    }
+}
+
+/// Check that the job scheduler can still be locked, and report how busy it is.
+/// A worker that panicked while holding the lock leaves it poisoned, and no job
+/// can be queued or started after that.
+pub fn scheduler_check(scheduler: &Mutex<JobScheduler>) -> HealthCheck {
+    match scheduler.lock() {
+        Ok(scheduler) => HealthCheck::ok(
+            "scheduler",
+            format!(
+                "{} of {} workers busy, {} queued",
+                scheduler.running_count(),
+                scheduler.workers(),
+                scheduler.pending().count()
+            ),
+        ),
+        Err(_) => HealthCheck::failing("scheduler", "A worker panicked while holding the scheduler"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{QueuedJob, RunLimits};
+    use pulsiora_core::{GitEvent, GitEventType, Repository};
+    use std::sync::Arc;
+
+    fn job() -> QueuedJob {
+        let event = GitEvent {
+            event_type: GitEventType::Push,
+            repository: Repository {
+                owner: "acme".to_string(),
+                name: "shop".to_string(),
+                full_name: "acme/shop".to_string(),
+                clone_url: "https://example.com/acme/shop.git".to_string(),
+                default_branch: "main".to_string(),
+            },
+            branch: Some("main".to_string()),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: None,
+            sender: "dana".to_string(),
+            inputs: Default::default(),
+        };
+        QueuedJob::new(event, "pipeline {}".to_string())
+    }
+
+    #[test]
+    fn test_health_report() {
+        let scheduler = Arc::new(Mutex::new(JobScheduler::new(2)));
+        {
+            let mut scheduler = scheduler.lock().unwrap();
+            scheduler.push(job());
+            scheduler.push(job());
+            scheduler
+                .next(|_| RunLimits {
+                    repository: Some(1),
+                    organization: None,
+                })
+                .unwrap();
+        }
+        let check = scheduler_check(&scheduler);
+        assert_eq!(check, HealthCheck::ok("scheduler", "1 of 2 workers busy, 1 queued"));
+
+        let report = HealthReport::new(vec![HealthCheck::ok("storage", "sqlite"), check]);
+        assert!(report.is_ok());
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "ok");
+        assert_eq!(json["checks"][1]["name"], "scheduler");
+
+        let poisoner = scheduler.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("worker panicked");
+        })
+        .join();
+        let check = scheduler_check(&scheduler);
+        assert_eq!(check.status, HealthStatus::Failing);
+        assert!(!HealthReport::new(vec![HealthCheck::ok("storage", "sqlite"), check]).is_ok());
+    }
+}
@@ -0,0 +1,260 @@
+// Outgoing webhooks: URLs registered on a repository that are sent a JSON
+// payload when one of its executions is queued, starts or finishes, or
+// something is deployed, e.g. for chat bots or deployment dashboards. Payloads are signed like GitHub signs
+// its webhooks, so receivers can check they came from this server.
+
+use crate::deployments::Deployment;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use pulsiora_core::{PipelineExecution, PulsioraError, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Header holding the HMAC-SHA256 of the payload, `sha256=<hex>`
+pub const HOOK_SIGNATURE_HEADER: &str = "X-Pulsiora-Signature-256";
+/// Header naming the event, e.g. `execution_finished`
+pub const HOOK_EVENT_HEADER: &str = "X-Pulsiora-Event";
+/// Header with an ID for the delivery, the same across its retries
+pub const HOOK_DELIVERY_HEADER: &str = "X-Pulsiora-Delivery";
+
+/// How long to wait before each retry of a failed delivery
+const RETRY_DELAYS: [Duration; 2] = [Duration::from_secs(5), Duration::from_secs(30)];
+/// How long a receiver has to answer
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Point in an execution's life a webhook can be sent for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    ExecutionQueued,
+    ExecutionStarted,
+    ExecutionFinished,
+    /// An execution deployed to an environment, or a rollback was marked
+    DeploymentCreated,
+}
+
+impl WebhookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::ExecutionQueued => "execution_queued",
+            WebhookEvent::ExecutionStarted => "execution_started",
+            WebhookEvent::ExecutionFinished => "execution_finished",
+            WebhookEvent::DeploymentCreated => "deployment_created",
+        }
+    }
+}
+
+/// A URL a repository's execution events are sent to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutgoingWebhook {
+    pub id: Uuid,
+    pub url: String,
+    /// Key payloads are signed with
+    pub secret: String,
+    /// Events sent to the URL; empty sends all of them
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OutgoingWebhook {
+    /// A webhook for `url`, signed with `secret` or else a random one
+    pub fn new(url: String, secret: Option<String>, events: Vec<WebhookEvent>) -> Result<Self> {
+        let valid_url = reqwest::Url::parse(&url).is_ok_and(|parsed| matches!(parsed.scheme(), "http" | "https"));
+        if !valid_url {
+            return Err(PulsioraError::InvalidConfiguration(format!("webhook URL must be http(s): {}", url)));
+        }
+        Ok(Self {
+            id: Uuid::new_v4(),
+            url,
+            secret: secret
+                .filter(|secret| !secret.is_empty())
+                .unwrap_or_else(|| format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())),
+            events,
+            created_at: Utc::now(),
+        })
+    }
+
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// What a webhook is sent for an execution event
+pub fn webhook_payload(event: WebhookEvent, execution: &PipelineExecution, execution_url: Option<&str>) -> Value {
+    let git_event = &execution.git_event;
+    json!({
+        "event": event.as_str(),
+        "execution_id": execution.id,
+        "repository": execution.repository.full_name,
+        "pipeline": execution.pipeline_name,
+        "status": execution.status,
+        "trigger": git_event.event_type,
+        "branch": git_event.branch,
+        "tag": git_event.tag,
+        "commit_sha": git_event.commit_sha,
+        "pull_request": git_event.pull_request.as_ref().map(|pr| pr.number),
+        "sender": git_event.sender,
+        "started_at": execution.started_at,
+        "completed_at": execution.completed_at,
+        "duration_ms": execution
+            .completed_at
+            .map(|end| (end - execution.started_at).num_milliseconds().max(0)),
+        "environment": execution.environment,
+        "url": execution_url,
+    })
+}
+
+/// What a webhook is sent for a deployment of `repository`
+pub fn deployment_payload(repository: &str, deployment: &Deployment, execution_url: Option<&str>) -> Value {
+    json!({
+        "event": WebhookEvent::DeploymentCreated.as_str(),
+        "deployment_id": deployment.id,
+        "repository": repository,
+        "environment": deployment.environment,
+        "execution_id": deployment.execution_id,
+        "pipeline": deployment.pipeline,
+        "commit_sha": deployment.commit_sha,
+        "branch": deployment.branch,
+        "tag": deployment.tag,
+        "deployed_by": deployment.deployed_by,
+        "deployed_at": deployment.deployed_at,
+        "rollback_of": deployment.rollback_of,
+        "url": execution_url,
+    })
+}
+
+/// `sha256=<hex>` HMAC-SHA256 of `body` keyed with `secret`, as checked by
+/// `verify_signature`
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Send `body` to a webhook, retrying on network errors, server errors and
+/// `429` a couple of times before giving up
+pub async fn deliver_webhook(client: &Client, hook: &OutgoingWebhook, event: WebhookEvent, body: Vec<u8>) -> Result<()> {
+    let delivery = Uuid::new_v4().to_string();
+    let signature = sign_payload(&hook.secret, &body);
+    let mut delays = RETRY_DELAYS.iter();
+    loop {
+        let sent = client
+            .post(&hook.url)
+            .timeout(DELIVERY_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(HOOK_EVENT_HEADER, event.as_str())
+            .header(HOOK_DELIVERY_HEADER, &delivery)
+            .header(HOOK_SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let error = match sent {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                if !status.is_server_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    return Err(PulsioraError::NetworkError(format!("{} answered {}", hook.url, status)));
+                }
+                format!("{} answered {}", hook.url, status)
+            }
+            Err(e) => format!("failed to reach {}: {}", hook.url, e),
+        };
+        match delays.next() {
+            Some(delay) => tokio::time::sleep(*delay).await,
+            None => return Err(PulsioraError::NetworkError(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::verify_signature;
+    use pulsiora_core::{ExecutionMetrics, GitEvent, GitEventType, PipelineStatus, Repository};
+
+    #[test]
+    fn test_outgoing_webhook() {
+        let hook = OutgoingWebhook::new("https://chat.example.com/hook".to_string(), None, vec![]).unwrap();
+        assert_eq!(hook.secret.len(), 64);
+        assert!(hook.wants(WebhookEvent::ExecutionQueued));
+        let hook = OutgoingWebhook::new(
+            "http://dash.internal:8080/pulsiora".to_string(),
+            Some("shh".to_string()),
+            vec![WebhookEvent::ExecutionFinished],
+        )
+        .unwrap();
+        assert_eq!(hook.secret, "shh");
+        assert!(hook.wants(WebhookEvent::ExecutionFinished));
+        assert!(!hook.wants(WebhookEvent::ExecutionStarted));
+
+        assert!(OutgoingWebhook::new("ftp://example.com".to_string(), None, vec![]).is_err());
+        assert!(OutgoingWebhook::new("not a url".to_string(), None, vec![]).is_err());
+
+        let signature = sign_payload("shh", b"{}");
+        assert!(verify_signature("shh", b"{}", &signature));
+        assert!(!verify_signature("other", b"{}", &signature));
+    }
+
+    #[test]
+    fn test_webhook_payload() {
+        let started_at = Utc::now();
+        let execution = PipelineExecution {
+            id: Uuid::new_v4(),
+            pipeline_name: "ci".to_string(),
+            pipeline_version: "1.0".to_string(),
+            pipeline_labels: vec![],
+            environment: None,
+            repository: Repository {
+                owner: "acme".to_string(),
+                name: "shop".to_string(),
+                full_name: "acme/shop".to_string(),
+                clone_url: String::new(),
+                default_branch: "main".to_string(),
+            },
+            git_event: GitEvent {
+                event_type: GitEventType::Push,
+                repository: Repository {
+                    owner: "acme".to_string(),
+                    name: "shop".to_string(),
+                    full_name: "acme/shop".to_string(),
+                    clone_url: String::new(),
+                    default_branch: "main".to_string(),
+                },
+                branch: Some("main".to_string()),
+                tag: None,
+                release_name: None,
+                pull_request: None,
+                commit_sha: Some("4f2a9c1".to_string()),
+                commit_message: None,
+                sender: "alice".to_string(),
+                inputs: Default::default(),
+            },
+            status: PipelineStatus::Failed,
+            step_results: vec![],
+            phases: vec![],
+            limit_exceeded: None,
+            coverage: None,
+            metrics: ExecutionMetrics::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
+            started_at,
+            completed_at: Some(started_at + chrono::Duration::milliseconds(1500)),
+        };
+
+        let payload = webhook_payload(WebhookEvent::ExecutionFinished, &execution, Some("https://ci.example.com/x"));
+        assert_eq!(payload["event"], "execution_finished");
+        assert_eq!(payload["execution_id"], execution.id.to_string());
+        assert_eq!(payload["repository"], "acme/shop");
+        assert_eq!(payload["status"], "Failed");
+        assert_eq!(payload["trigger"], "Push");
+        assert_eq!(payload["commit_sha"], "4f2a9c1");
+        assert_eq!(payload["duration_ms"], 1500);
+        assert_eq!(payload["url"], "https://ci.example.com/x");
+        assert!(payload["pull_request"].is_null());
+    }
+}
@@ -1,6 +1,61 @@
+pub mod accounts;
+pub mod activity;
+pub mod agents;
+pub mod artifacts;
+pub mod allowlist;
+pub mod audit;
+pub mod config;
+pub mod database;
+pub mod deliveries;
+pub mod deployments;
 pub mod github;
+pub mod github_app;
+pub mod health;
+pub mod hooks;
+pub mod monorepo;
+pub mod objects;
+pub mod queue;
+pub mod ratelimit;
+pub mod repo_settings;
+pub mod retention;
+pub mod search;
+pub mod secrets;
 pub mod storage;
+pub mod stats;
+pub mod telemetry;
+pub mod timeline;
+pub mod tls;
+pub mod updates;
+pub mod webhook;
+pub mod webhook_check;
 
+pub use accounts::*;
+pub use activity::*;
+pub use agents::*;
+pub use artifacts::*;
+pub use allowlist::*;
+pub use audit::*;
+pub use config::*;
+pub use database::*;
+pub use deliveries::*;
+pub use deployments::*;
 pub use github::*;
+pub use github_app::*;
+pub use health::*;
+pub use hooks::*;
+pub use monorepo::*;
+pub use objects::*;
+pub use queue::*;
+pub use ratelimit::*;
+pub use repo_settings::*;
+pub use retention::*;
+pub use search::*;
+pub use secrets::*;
 pub use storage::*;
-
+pub use stats::*;
+pub use telemetry::*;
+pub use timeline::*;
+pub use tls::*;
+pub use updates::*;
+pub use webhook::*;
+pub use webhook_check::*;
@@ -1,393 +1,5829 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    body::Body,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, DefaultBodyLimit, MatchedPath, Path, Query, Request, State,
+    },
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
     routing::{delete, get, post},
     Json, Router,
 };
-use std::collections::HashMap;
-use pulsiora_core::{GitEvent, GitEventType, Repository, PipelineExecution};
-use pulsiora_runner::PipelineExecutor;
+use clap::Parser;
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use pulsiora_core::{
+    ActivityBucket, AgentJob, AgentOutcome, AgentRegistered, AgentRegistration, AgentReport, AgentReportAck, AuditEvent, ExecutionEvent, ExecutionLimits, ExecutionMetrics, ExecutionLogs, GitEvent, GitEventType, OutputStream,
+    Page, Pipeline, PipelineStatus, PulsefileSource, RepoRole, Repository, PipelineExecution, SandboxPolicy, TriggerEvent, TriggerSource, WebhookMapping,
+    DEFAULT_MAX_OUTPUT_BYTES,
+};
+use pulsiora_runner::{
+    resume_point, ActionCache, CloneOptions, DockerBackend, KubernetesBackend, KubernetesOptions, PipelineExecutor, ReplayBundle,
+    SandboxBackend, SandboxTool, SshBackend, SshOptions,
+};
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::info;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, Mutex, Notify, RwLock};
+use tokio_util::sync::CancellationToken;
+use tower_http::trace::{DefaultOnFailure, TraceLayer};
+use tracing::{info, warn, Instrument};
+use uuid::Uuid;
 
-mod github;
-mod storage;
+use pulsiora_server::*;
 
-use github::*;
-use storage::*;
+mod v2;
 
 #[derive(Clone)]
 struct AppState {
     executor: PipelineExecutor,
     storage: Arc<RwLock<InMemoryStorage>>,
+    /// Hash of the one-time setup token; cleared once setup completes
+    bootstrap_token: Arc<Mutex<Option<String>>>,
+    /// Accepted jobs that haven't completed, replayed after a restart
+    journal: Arc<JobJournal>,
+    /// Received webhook deliveries, for skipping duplicates and replaying them
+    deliveries: Arc<DeliveryLog>,
+    /// Source addresses allowed to call webhook routes
+    webhook_allowlist: Arc<RwLock<IpAllowList>>,
+    audit: Arc<AuditLog>,
+    /// Sandbox for steps run on the server host; `None` when steps run in
+    /// containers
+    host_sandbox: Option<HostSandbox>,
+    /// Journaled jobs waiting for a worker
+    scheduler: Arc<std::sync::Mutex<JobScheduler>>,
+    /// Woken when a job is queued or finishes
+    jobs_changed: Arc<Notify>,
+    /// How jobs that hit an infrastructure error are retried
+    retry_policy: RetryPolicy,
+    /// Cancellation tokens of the jobs running now
+    running: Arc<std::sync::Mutex<HashMap<Uuid, CancellationToken>>>,
+    /// Live execution updates for WebSocket clients
+    updates: UpdateHub,
+    /// Remote agents running jobs instead of this server, when enabled
+    agents: Option<Arc<AgentPool>>,
+    /// The database storage changes are written to, checked for readiness
+    database: Option<Arc<dyn Storage>>,
+    /// Task starting queued jobs; it only ends if it panicked
+    dispatcher: Arc<std::sync::OnceLock<tokio::task::JoinHandle<()>>>,
+    /// Fired on SIGTERM or SIGINT: no new jobs are queued or started while
+    /// the running ones finish
+    shutdown: CancellationToken,
+    /// Secret webhook payloads must be signed with, if any
+    webhook_secret: Option<Arc<str>>,
+    /// Limits API requests per user or address, unless turned off
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Jobs waiting for a worker before webhooks and runs get `429`
+    max_queued_jobs: usize,
+    /// Largest webhook payload accepted, in bytes
+    webhook_body_limit: usize,
+    /// Bucket the logs and artifacts of finished executions are moved to
+    objects: Option<Arc<dyn ObjectStore>>,
+    /// Holds logs and artifacts until they are moved
+    data_dir: std::path::PathBuf,
+    /// Master key repository secrets are encrypted with; without it they
+    /// can't be set or used
+    secrets_key: Option<SecretsKey>,
+    /// GitHub App whose installation tokens are used instead of `GITHUB_TOKEN`
+    github_app: Option<Arc<GitHubApp>>,
+}
+
+/// How steps on the server host are sandboxed, see `SandboxPolicy`
+#[derive(Clone, Copy)]
+struct HostSandbox {
+    tool: SandboxTool,
+    /// Sandbox repositories whose policy doesn't say
+    enabled_by_default: bool,
+}
+
+impl HostSandbox {
+    /// Backend for a repository's executions, if they are sandboxed
+    fn backend_for(&self, policy: &SandboxPolicy) -> Option<SandboxBackend> {
+        policy
+            .enabled
+            .unwrap_or(self.enabled_by_default)
+            .then(|| SandboxBackend::new(self.tool).with_policy(policy.clone()))
+    }
+}
+
+/// The policy agents sandbox a repository's steps with, if they are
+/// sandboxed; agents have no backend of their own that isolates steps
+fn agent_sandbox(host_sandbox: Option<&HostSandbox>, policy: &SandboxPolicy) -> Option<SandboxPolicy> {
+    let enabled_by_default = host_sandbox.is_some_and(|sandbox| sandbox.enabled_by_default);
+    policy.enabled.unwrap_or(enabled_by_default).then(|| policy.clone())
+}
+
+/// Jobs running at once when `PULSIORA_WORKERS` isn't set
+const DEFAULT_WORKERS: usize = 4;
+
+/// Longest an agent's poll waits for a job before answering `204 No Content`
+const AGENT_POLL_WAIT: Duration = Duration::from_secs(30);
+
+/// Largest event report or result an agent may send; a result holds the
+/// output of every step
+const AGENT_BODY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// How long running jobs may take to finish once the server is asked to stop
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+/// How long a readiness check waits on storage before failing
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often GitHub's published hook ranges are re-fetched
+const GITHUB_META_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// API requests a minute allowed per user or address when not configured
+const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 600;
+
+/// Jobs waiting for a worker before new ones are turned away, when not configured
+const DEFAULT_MAX_QUEUED_JOBS: usize = 1000;
+
+/// When clients turned away by a full queue are asked to try again
+const QUEUE_FULL_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How long the webhook self-test waits for its ping to be answered
+const WEBHOOK_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often executions and step logs past their retention are deleted
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Largest webhook payload accepted when `max_webhook_body_bytes` isn't
+/// set; GitHub caps payloads at 25 MB
+const DEFAULT_WEBHOOK_BODY_LIMIT: usize = 25 * 1024 * 1024;
+
+/// Webhook payloads larger than this are parsed on a blocking thread, so
+/// giant pushes don't hold up other requests
+const WEBHOOK_INLINE_PARSE_LIMIT: usize = 256 * 1024;
+
+/// Settings are read from the config file, then the environment, then these
+/// flags, each overriding the one before
+#[derive(Parser)]
+#[command(name = "pulsiora-server")]
+#[command(about = "Run the Pulsiora server", long_about = None)]
+struct Args {
+    /// TOML settings file [default: pulsiora.toml, if there is one]
+    #[arg(long, env = "PULSIORA_CONFIG")]
+    config: Option<std::path::PathBuf>,
+
+    /// Address and port to listen on [default: 0.0.0.0:3000]
+    #[arg(long)]
+    bind: Option<String>,
+
+    /// Directory for the database, journal, logs, artifacts and workspaces [default: data]
+    #[arg(long)]
+    data_dir: Option<std::path::PathBuf>,
+
+    /// Where executions and repositories are kept: `sqlite` or `memory`
+    #[arg(long)]
+    storage: Option<String>,
+
+    /// SQLite database file [default: <data-dir>/pulsiora.db]
+    #[arg(long)]
+    database: Option<std::path::PathBuf>,
+
+    /// Jobs run at once [default: 4]
+    #[arg(long)]
+    workers: Option<usize>,
+
+    /// PEM certificate chain to serve HTTPS with
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<std::path::PathBuf>,
+
+    /// PEM private key of the certificate
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<std::path::PathBuf>,
+}
+
+impl Args {
+    /// The settings file, overridden by the environment and then the flags
+    fn config(self) -> anyhow::Result<ServerConfig> {
+        let file = match &self.config {
+            Some(path) => ServerConfig::load(path)?,
+            None if std::path::Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                ServerConfig::load(std::path::Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => ServerConfig::default(),
+        };
+        let flags = ServerConfig {
+            bind: self.bind,
+            data_dir: self.data_dir,
+            storage: self.storage,
+            database: self.database,
+            workers: self.workers,
+            tls_cert: self.tls_cert,
+            tls_key: self.tls_key,
+            ..Default::default()
+        };
+        Ok(file.merge(ServerConfig::from_env(env_string)?).merge(flags))
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    let config = Args::parse().config()?;
+    let telemetry = init_tracing(
+        config.otlp_endpoint.as_deref(),
+        config.otel_service_name.as_deref().unwrap_or(DEFAULT_OTEL_SERVICE_NAME),
+    )?;
+    if let Some(endpoint) = config.otlp_endpoint.as_deref().filter(|_| telemetry.exporting()) {
+        info!(endpoint, "Exporting traces over OTLP");
+    }
+    let bind = config.bind_addr()?;
+    let data_dir = config.data_dir();
+    let tls = match config.tls()? {
+        Some(source) => {
+            if let TlsSource::Acme { domains, staging, .. } = &source {
+                info!(?domains, staging, "Getting a certificate from Let's Encrypt");
+            }
+            Some(TlsAcceptor::new(&source)?)
+        }
+        None => None,
+    };
+    let journal = JobJournal::open(std::path::Path::new(&data_dir).join("queue"))?;
+    let audit_path = env_string("PULSIORA_AUDIT_LOG")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::Path::new(&data_dir).join("audit.log"));
+    let audit = AuditLog::open(&audit_path)?;
+    info!("Writing audit log to {}", audit.path().display());
+
+    let updates = UpdateHub::default();
+    let mut storage = InMemoryStorage::new();
+    storage.announce_updates(updates.clone());
+    let (database, writer) = match config.storage.as_deref() {
+        None | Some("sqlite") => {
+            let path = config
+                .database
+                .clone()
+                .unwrap_or_else(|| std::path::Path::new(&data_dir).join("pulsiora.db"));
+            let database: Arc<dyn Storage> = Arc::new(SqliteStorage::open(&path).await?);
+            let (changes, written) = tokio::sync::mpsc::unbounded_channel();
+            storage.restore(database.as_ref(), changes).await?;
+            let writer = tokio::spawn(write_through(database.clone(), written));
+            info!("Storing executions and repositories in {}", path.display());
+            (Some(database), Some(writer))
+        }
+        Some("memory") => {
+            warn!("Storage is in memory only: executions and repositories are lost on restart");
+            (None, None)
+        }
+        Some(other) => anyhow::bail!("storage must be `sqlite` or `memory`, got {:?}", other),
+    };
+    storage.settings_mut().github_token = config.github_token.clone();
+    storage.settings_mut().github_checks = config.github_checks.unwrap_or(false);
+    storage.settings_mut().github_pr_comments = config.github_pr_comments.unwrap_or(false);
+    storage.settings_mut().public_url = config.public_url.as_ref().map(|url| url.trim_end_matches('/').to_string());
+    storage.settings_mut().limits = ExecutionLimits {
+        max_steps: env_limit("PULSIORA_MAX_STEPS")?,
+        max_runtime_secs: env_limit("PULSIORA_MAX_RUNTIME_SECS")?,
+        max_artifact_bytes: env_limit("PULSIORA_MAX_ARTIFACT_BYTES")?,
+        max_output_bytes: Some(env_limit("PULSIORA_MAX_OUTPUT_BYTES")?.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES)),
+        max_concurrent_runs: env_limit("PULSIORA_MAX_RUNS_PER_REPO")?,
+    };
+
+    // A fresh instance gets a one-time token that can only be used for setup
+    let bootstrap_token = if storage.is_initialized() {
+        None
+    } else {
+        let token = generate_token();
+        warn!("Instance is not set up yet. Bootstrap token (valid once): {}", token);
+        warn!("Complete setup with POST /api/v1/setup using 'Authorization: Bearer <token>'");
+        Some(hash_token(&token))
+    };
+
+    let allowed_ips = parse_cidrs(
+        std::env::var("PULSIORA_WEBHOOK_ALLOWED_IPS")
+            .unwrap_or_default()
+            .split(','),
+    )?;
+    let allow_github = env_flag("PULSIORA_WEBHOOK_ALLOW_GITHUB");
+    let webhook_allowlist = Arc::new(RwLock::new(IpAllowList::new(allowed_ips, allow_github)));
+    if allow_github {
+        tokio::spawn(refresh_github_hook_ranges(webhook_allowlist.clone()));
+    }
+
+    let sandbox_tool = match env_string("PULSIORA_SANDBOX_TOOL") {
+        Some(name) => SandboxTool::parse(&name)
+            .ok_or_else(|| anyhow::anyhow!("PULSIORA_SANDBOX_TOOL must be `bubblewrap` or `firejail`, got {:?}", name))?,
+        None => SandboxTool::Bubblewrap,
+    };
+    let mut host_sandbox = Some(HostSandbox {
+        tool: sandbox_tool,
+        enabled_by_default: false,
+    });
+    let executor = match env_string("PULSIORA_BACKEND").as_deref() {
+        None | Some("host") => PipelineExecutor::new(),
+        Some("sandbox") => {
+            let sandbox = SandboxBackend::new(sandbox_tool);
+            if !sandbox.is_available() {
+                anyhow::bail!("PULSIORA_BACKEND=sandbox needs `{}` on PATH", sandbox_tool.program());
+            }
+            host_sandbox = Some(HostSandbox {
+                tool: sandbox_tool,
+                enabled_by_default: true,
+            });
+            PipelineExecutor::new().with_backend(sandbox)
+        }
+        Some("docker") => {
+            host_sandbox = None;
+            let mut docker = DockerBackend::new();
+            if let Some(image) = env_string("PULSIORA_DOCKER_IMAGE") {
+                docker = docker.with_default_image(image);
+            }
+            PipelineExecutor::new().with_backend(docker)
+        }
+        Some("kubernetes") => {
+            host_sandbox = None;
+            let backend = KubernetesBackend::connect(KubernetesOptions {
+                namespace: env_string("PULSIORA_K8S_NAMESPACE"),
+                default_image: env_string("PULSIORA_K8S_IMAGE"),
+                workspace_claim: env_string("PULSIORA_K8S_WORKSPACE_CLAIM"),
+                workspace_root: Some(std::path::Path::new(&data_dir).join("workspaces")),
+                service_account: env_string("PULSIORA_K8S_SERVICE_ACCOUNT"),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to connect to Kubernetes: {}", e))?;
+            PipelineExecutor::new().with_backend(backend)
+        }
+        Some(other) => anyhow::bail!(
+            "PULSIORA_BACKEND must be `host`, `sandbox`, `docker` or `kubernetes`, got {:?}",
+            other
+        ),
+    };
+
+    let ssh = SshBackend::new(SshOptions {
+        identity_file: env_string("PULSIORA_SSH_IDENTITY").map(std::path::PathBuf::from),
+        known_hosts_file: env_string("PULSIORA_SSH_KNOWN_HOSTS").map(std::path::PathBuf::from),
+        control_dir: std::path::Path::new(&data_dir).join("ssh"),
+        ..Default::default()
+    });
+
+    let mut actions = ActionCache::new(std::path::Path::new(&data_dir).join("actions"));
+    if let Some(host) = env_string("PULSIORA_ACTION_HOST") {
+        actions = actions.with_host(host);
+    }
+    let mut executor = executor.with_ssh_backend(ssh).with_action_cache(actions);
+    if let Some(max) = config.max_concurrent_executions {
+        executor = executor.with_max_concurrent_executions(max);
+    }
+    if let Some(max) = config.max_concurrent_steps {
+        executor = executor.with_max_concurrent_steps(max);
+    }
 
+    let workers = config.workers.unwrap_or(DEFAULT_WORKERS);
+    let shutdown_grace = env_limit("PULSIORA_SHUTDOWN_GRACE_SECS")?
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SHUTDOWN_GRACE);
+    let objects: Option<Arc<dyn ObjectStore>> = match config.object_store() {
+        Some(options) => {
+            info!(bucket = %options.bucket, "Moving logs and artifacts of finished executions to object storage");
+            Some(Arc::new(S3ObjectStore::new(options).await))
+        }
+        None => None,
+    };
     let state = AppState {
-        executor: PipelineExecutor::new(),
-        storage: Arc::new(RwLock::new(InMemoryStorage::new())),
+        executor: executor
+            .with_artifact_dir(std::path::Path::new(&data_dir).join("artifacts"))
+            .with_log_dir(std::path::Path::new(&data_dir).join("logs"))
+            .with_workspace_root(std::path::Path::new(&data_dir).join("workspaces"))
+            .with_clone_options(CloneOptions {
+                depth: env_limit("PULSIORA_CLONE_DEPTH")?,
+                filter: env_string("PULSIORA_CLONE_FILTER"),
+                submodules: env_flag("PULSIORA_CLONE_SUBMODULES"),
+                lfs: env_flag("PULSIORA_CLONE_LFS"),
+                reference: env_string("PULSIORA_CLONE_REFERENCE").map(std::path::PathBuf::from),
+            }),
+        storage: Arc::new(RwLock::new(storage)),
+        bootstrap_token: Arc::new(Mutex::new(bootstrap_token)),
+        journal: Arc::new(journal),
+        deliveries: Arc::new(DeliveryLog::open(std::path::Path::new(&data_dir).join("deliveries"))?),
+        webhook_allowlist,
+        audit: Arc::new(audit),
+        host_sandbox,
+        scheduler: Arc::new(std::sync::Mutex::new(JobScheduler::new(workers))),
+        jobs_changed: Arc::new(Notify::new()),
+        retry_policy: config.retry_policy(),
+        running: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        updates,
+        agents: env_string("PULSIORA_AGENT_TOKEN").map(|token| Arc::new(AgentPool::new(&token))),
+        database,
+        dispatcher: Arc::new(std::sync::OnceLock::new()),
+        shutdown: CancellationToken::new(),
+        webhook_secret: config.webhook_secret.as_deref().map(Arc::from),
+        rate_limiter: match config.rate_limit_per_minute.unwrap_or(DEFAULT_RATE_LIMIT_PER_MINUTE) {
+            0 => None,
+            per_minute => Some(Arc::new(RateLimiter::new(per_minute))),
+        },
+        max_queued_jobs: config.max_queued_jobs.unwrap_or(DEFAULT_MAX_QUEUED_JOBS),
+        webhook_body_limit: config
+            .max_webhook_body_bytes
+            .map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX))
+            .unwrap_or(DEFAULT_WEBHOOK_BODY_LIMIT),
+        objects,
+        data_dir: data_dir.clone(),
+        secrets_key: config.secrets_key()?,
+        github_app: config.github_app()?.map(Arc::new),
     };
+    if state.agents.is_some() {
+        info!("Jobs run on remote agents; register them with PULSIORA_AGENT_TOKEN");
+    }
+
+    recover_queued_jobs(&state).await?;
+    info!(workers, "Starting job workers");
+    let _ = state.dispatcher.set(tokio::spawn(dispatch_jobs(state.clone())));
+    if state.webhook_secret.is_none() {
+        warn!("No webhook secret is set; webhook payloads aren't checked for a signature");
+    }
+    if let Some(app) = &state.github_app {
+        info!(app_id = app.app_id(), "Calling GitHub as a GitHub App");
+        tokio::spawn(configure_app_webhooks(state.clone(), None));
+    }
+    let retention = config.retention();
+    if retention.prunes_executions() || retention.prunes_logs() {
+        tokio::spawn(enforce_retention(state.clone(), retention));
+    }
 
     let app = Router::new()
-        .route("/health", get(health_check))
-        .route("/api/v1/webhook/github", post(handle_github_webhook))
+        .route("/health", get(health_live))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .route("/api/versions", get(api_versions))
+        .nest("/api/v2", v2::router())
+        .route("/api/v1/setup", post(setup_instance))
+        .route("/api/v1/users", get(list_users).post(create_user))
+        .route("/api/v1/users/:username", delete(remove_user))
+        .route("/api/v1/orgs", get(list_organizations).post(create_organization))
+        .route(
+            "/api/v1/orgs/:org/quotas",
+            get(get_organization_quotas).put(update_organization_quotas),
+        )
+        .route(
+            GITHUB_WEBHOOK_PATH,
+            post(handle_github_webhook)
+                .layer(DefaultBodyLimit::max(state.webhook_body_limit))
+                .route_layer(middleware::from_fn_with_state(state.clone(), verify_webhook_signature))
+                .route_layer(middleware::from_fn_with_state(state.clone(), enforce_webhook_allowlist)),
+        )
+        .route(
+            "/api/v1/webhook/generic/:repo",
+            post(handle_generic_webhook)
+                .layer(DefaultBodyLimit::max(state.webhook_body_limit))
+                .route_layer(middleware::from_fn_with_state(state.clone(), verify_webhook_signature))
+                .route_layer(middleware::from_fn_with_state(state.clone(), enforce_webhook_allowlist)),
+        )
+        .route("/api/v1/webhooks", get(list_deliveries))
+        .route("/api/v1/webhooks/:delivery_id", get(get_delivery))
+        .route("/api/v1/webhooks/:delivery_id/replay", post(replay_delivery))
+        .route("/api/v1/agents", get(list_agents))
+        .route("/api/v1/agents/register", post(register_agent))
+        .route("/api/v1/agents/jobs/next", get(poll_agent_job))
+        .route(
+            "/api/v1/agents/jobs/:id/report",
+            post(report_agent_job).layer(DefaultBodyLimit::max(AGENT_BODY_LIMIT)),
+        )
+        .route(
+            "/api/v1/agents/jobs/:id/result",
+            post(finish_agent_job).layer(DefaultBodyLimit::max(AGENT_BODY_LIMIT)),
+        )
         .route("/api/v1/executions/:id", get(get_execution))
+        .route("/api/v1/executions/:id/timeline", get(get_execution_timeline))
+        .route("/api/v1/executions/:id/replay", get(get_replay_bundle))
+        .route("/api/v1/executions/:id/resume", post(resume_execution))
+        .route("/api/v1/executions/:id/cancel", post(cancel_execution))
+        .route("/api/v1/executions/:id/steps/:index/log", get(get_step_log))
+        .route(
+            "/api/v1/executions/:id/share",
+            post(create_share_link).get(list_share_links),
+        )
+        .route("/api/v1/executions/:id/share/:share_id", delete(revoke_share_link))
+        .route("/shared/:token", get(get_shared_execution))
+        .route("/shared/:token/logs", get(get_shared_execution_logs))
+        .route("/api/v1/executions/:id/artifacts", get(list_execution_artifacts))
+        .route("/ui/executions/:id/artifacts", get(redirect_artifact_root))
+        .route("/ui/executions/:id/artifacts/", get(browse_artifact_root))
+        .route("/ui/executions/:id/artifacts/*path", get(browse_artifact))
         .route("/api/v1/executions", get(list_executions))
-        .route("/api/v1/repos", post(register_repo))
-        .route("/api/v1/repos/:repo", delete(unregister_repo))
+        .route("/api/v1/search", get(search_logs))
+        .route("/api/v1/queue", get(get_queue))
+        .route("/api/v1/ws", get(execution_updates))
+        .route("/api/v1/repos", get(list_repos).post(register_repo))
+        .route("/api/v1/repos/:repo", get(get_repo).delete(unregister_repo))
+        .route(
+            "/api/v1/repos/:repo/status-contexts",
+            get(get_status_contexts).put(update_status_contexts),
+        )
+        .route(
+            "/api/v1/repos/:repo/limits",
+            get(get_repo_limits).put(update_repo_limits),
+        )
+        .route(
+            "/api/v1/repos/:repo/sandbox",
+            get(get_repo_sandbox).put(update_repo_sandbox),
+        )
+        .route(
+            "/api/v1/repos/:repo/webhook-mapping",
+            get(get_repo_webhook_mapping).put(update_repo_webhook_mapping),
+        )
+        .route("/api/v1/repos/:repo/pulsefile", axum::routing::put(update_repo_pulsefile))
+        .route("/api/v1/repos/:repo/pulsefile/revisions", get(list_pulsefile_revisions))
+        .route(
+            "/api/v1/repos/:repo/pulsefile-source",
+            get(get_pulsefile_source).put(update_pulsefile_source),
+        )
+        .route("/api/v1/repos/:repo/env", get(get_repo_env).put(update_repo_env))
+        .route("/api/v1/repos/:repo/hooks", get(list_repo_hooks).post(add_repo_hook))
+        .route("/api/v1/repos/:repo/pipelines", get(list_path_pipelines))
+        .route(
+            "/api/v1/repos/:repo/pipelines/:name",
+            axum::routing::put(set_path_pipeline).delete(remove_path_pipeline),
+        )
+        .route("/api/v1/repos/:repo/hooks/:id", delete(remove_repo_hook))
+        .route("/api/v1/repos/:repo/webhook-test", post(test_repo_webhook))
+        .route(
+            "/api/v1/repos/:repo/settings",
+            get(get_repo_settings).patch(update_repo_settings),
+        )
+        .route("/api/v1/repos/:repo/stats", get(get_repo_stats))
+        .route("/api/v1/repos/:repo/environments", get(list_environments))
+        .route(
+            "/api/v1/repos/:repo/environments/:environment/deployments",
+            get(list_environment_deployments),
+        )
+        .route(
+            "/api/v1/repos/:repo/environments/:environment/rollback",
+            post(rollback_environment),
+        )
+        .route(
+            "/api/v1/repos/:repo/secrets",
+            get(list_repo_secrets).post(set_repo_secret),
+        )
+        .route("/api/v1/repos/:repo/secrets/:name", delete(remove_repo_secret))
+        .route("/api/v1/repos/:repo/roles", get(get_repo_roles))
+        .route(
+            "/api/v1/repos/:repo/roles/:username",
+            axum::routing::put(grant_repo_role).delete(revoke_repo_role),
+        )
         .route("/api/v1/pipelines/:repo/status", get(get_pipeline_status))
-        .with_state(state);
+        .route("/api/v1/pipelines/:repo/activity", get(get_pipeline_activity))
+        .route("/api/v1/pipelines/:repo/run", post(run_pipeline))
+        .route(
+            "/api/v1/orgs/:org/base-pulsefile",
+            get(get_base_pulsefile).put(update_base_pulsefile),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), enforce_rate_limit))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_failure(DefaultOnFailure::new().level(tracing::Level::DEBUG)),
+        )
+        .with_state(state.clone());
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    info!("Server listening on http://0.0.0.0:3000");
-    axum::serve(listener, app).await?;
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    match tls {
+        Some(acceptor) => {
+            info!("Server listening on https://{}", bind);
+            serve_tls(listener, acceptor, app, drain_jobs(state.clone(), shutdown_grace)).await?;
+        }
+        None => {
+            info!("Server listening on http://{}", bind);
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(drain_jobs(state.clone(), shutdown_grace))
+                .await?;
+        }
+    }
 
+    // Let the writer finish what storage already sent it
+    state.storage.write().await.stop_persisting();
+    if let Some(writer) = writer {
+        let _ = writer.await;
+    }
+    info!("Server stopped");
+    telemetry.shutdown();
     Ok(())
 }
 
-async fn health_check() -> &'static str {
-    "OK"
+/// Span of an HTTP request, continuing the caller's trace if it sent a
+/// `traceparent`. Only the route is recorded, such as `/shared/:token`:
+/// paths and query strings can hold tokens.
+fn request_span(request: &axum::http::Request<Body>) -> tracing::Span {
+    let route = request.extensions().get::<MatchedPath>().map(MatchedPath::as_str);
+    let span = tracing::info_span!("request", method = %request.method(), route);
+    let trace_parent = request.headers().get(TRACE_PARENT_HEADER).and_then(|v| v.to_str().ok());
+    continue_trace(&span, trace_parent);
+    span
 }
 
-#[derive(Deserialize)]
-struct GitHubWebhookPayload {
-    #[serde(rename = "ref")]
-    ref_field: Option<String>,
-    repository: Option<GitHubRepository>,
-    pull_request: Option<serde_json::Value>,
-    action: Option<String>,
-    created: Option<bool>,
-    deleted: Option<bool>,
-    sender: Option<GitHubUser>,
-    head_commit: Option<serde_json::Value>,
+/// Wait for SIGTERM or SIGINT
+async fn shutdown_signal() {
+    let interrupt = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
 }
 
-#[derive(Deserialize)]
-struct GitHubRepository {
-    name: String,
-    #[serde(rename = "full_name")]
-    full_name: String,
-    owner: GitHubUser,
-    #[serde(rename = "clone_url")]
-    clone_url: String,
-    #[serde(rename = "default_branch")]
-    default_branch: String,
+/// On shutdown, stop queueing and starting jobs and give the running ones up
+/// to `grace` to finish, then return so the server stops. Jobs still running
+/// after that stay in the journal with the steps they finished and are
+/// marked interrupted on the next start, where they can be resumed; queued
+/// jobs that never started run after the restart.
+async fn drain_jobs(state: AppState, grace: Duration) {
+    shutdown_signal().await;
+    state.shutdown.cancel();
+    state.jobs_changed.notify_one();
+
+    let running = || state.running.lock().unwrap().len();
+    if running() > 0 {
+        info!(running = running(), grace_secs = grace.as_secs(), "Shutting down, waiting for running jobs");
+    }
+    let drained = tokio::time::timeout(grace, async {
+        while running() > 0 {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    })
+    .await;
+    if drained.is_err() {
+        let ids: Vec<Uuid> = state.running.lock().unwrap().keys().copied().collect();
+        warn!(?ids, "Jobs still running after the grace period; they can be resumed after the restart");
+    }
+    info!("Shutting down");
 }
 
-#[derive(Deserialize)]
-struct GitHubUser {
-    login: String,
+/// Read an optional numeric limit from the environment
+fn env_limit<T: std::str::FromStr>(name: &str) -> anyhow::Result<Option<T>> {
+    match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("{} must be a non-negative integer, got {:?}", name, value)),
+        _ => Ok(None),
+    }
 }
 
-async fn handle_github_webhook(
-    State(state): State<AppState>,
-    headers: axum::http::HeaderMap,
-    Json(payload): Json<GitHubWebhookPayload>,
-) -> Result<StatusCode, StatusCode> {
-    info!("Received GitHub webhook");
+/// A non-empty environment variable
+fn env_string(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
 
-    // Determine event type from X-GitHub-Event header
-    let event_type = headers
-        .get("X-GitHub-Event")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("unknown");
+/// Whether an environment variable is set to `1` or `true`
+fn env_flag(name: &str) -> bool {
+    matches!(std::env::var(name).as_deref(), Ok("1") | Ok("true"))
+}
 
-    let repository = match &payload.repository {
-        Some(repo) => Repository {
-            owner: repo.owner.login.clone(),
-            name: repo.name.clone(),
-            full_name: repo.full_name.clone(),
-            clone_url: repo.clone_url.clone(),
-            default_branch: repo.default_branch.clone(),
+/// The process is up and answering requests
+async fn health_live() -> Json<HealthReport> {
+    Json(HealthReport::new(vec![]))
+}
+
+/// Whether the server can take and run jobs: storage answers and its changes
+/// are still being written, the dispatcher is running and the scheduler isn't
+/// poisoned. 503 when any check fails.
+async fn health_ready(State(state): State<AppState>) -> (StatusCode, Json<HealthReport>) {
+    let mut checks = vec![storage_check(&state).await];
+    checks.push(match state.dispatcher.get() {
+        _ if state.shutdown.is_cancelled() => HealthCheck::failing("workers", "Shutting down"),
+        Some(dispatcher) if dispatcher.is_finished() => HealthCheck::failing("workers", "The job dispatcher stopped"),
+        Some(_) => match &state.agents {
+            Some(agents) => HealthCheck::ok("workers", format!("{} agents registered", agents.agents().len())),
+            None => HealthCheck::ok("workers", "Running jobs on this server"),
         },
-        None => {
-            return Err(StatusCode::BAD_REQUEST);
-        }
-    };
+        None => HealthCheck::failing("workers", "The job dispatcher hasn't started"),
+    });
+    checks.push(scheduler_check(&state.scheduler));
 
-    let git_event = match event_type {
-        "push" => create_push_event(repository, &payload),
-        "pull_request" => create_pull_request_event(repository, &payload),
-        "create" => create_create_event(repository, &payload),
-        "delete" => create_delete_event(repository, &payload),
-        _ => {
-            info!(event_type, "Unhandled event type, skipping");
-            return Ok(StatusCode::OK);
-        }
+    let report = HealthReport::new(checks);
+    let status = if report.is_ok() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
     };
+    (status, Json(report))
+}
 
-    // Try to get Pulsefile from registered repos first, otherwise fetch from GitHub
-    let repo_identifier = git_event.repository.full_name.clone();
-    let pulsefile_content = {
-        let storage = state.storage.read().await;
-        if let Some(pulsefile) = storage.get_repo_pulsefile(&repo_identifier) {
-            info!("Using stored Pulsefile for {}", repo_identifier);
-            drop(storage);
-            pulsefile
-        } else {
-            drop(storage);
-            // Fall back to fetching from GitHub
-            match fetch_pulsefile(&git_event.repository).await {
-                Ok(content) => content,
-                Err(e) => {
-                    info!(error = %e, "Failed to fetch Pulsefile");
-                    return Ok(StatusCode::OK); // Not an error, just no pipeline to run
-                }
-            }
-        }
+async fn storage_check(state: &AppState) -> HealthCheck {
+    let persisting = match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, state.storage.read()).await {
+        Ok(storage) => storage.persisting(),
+        Err(_) => return HealthCheck::failing("storage", "Timed out waiting for the storage lock"),
     };
-
-    // Execute pipeline
-    let execution = match state
-        .executor
-        .execute_from_pulsefile(&pulsefile_content, &git_event)
-        .await
-    {
-        Ok(exec) => exec,
-        Err(e) => {
-            info!(error = %e, "Pipeline execution failed");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+    let Some(database) = &state.database else {
+        return HealthCheck::ok("storage", "In memory only");
     };
-
-    // Store execution
-    {
-        let mut storage = state.storage.write().await;
-        storage.store_execution(execution.clone());
+    if !persisting {
+        return HealthCheck::failing("storage", "Changes are no longer written to the database");
+    }
+    match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, database.ping()).await {
+        Ok(Ok(())) => HealthCheck::ok("storage", "Database reachable"),
+        Ok(Err(e)) => HealthCheck::failing("storage", e.to_string()),
+        Err(_) => HealthCheck::failing("storage", "Timed out waiting for the database"),
     }
+}
 
-    info!(
-        execution_id = %execution.id,
-        status = ?execution.status,
-        "Pipeline execution completed"
-    );
+#[derive(Serialize)]
+struct ApiVersions {
+    versions: Vec<&'static str>,
+}
 
-    Ok(StatusCode::OK)
+/// API versions this server serves, so clients can pick the newest they know
+async fn api_versions() -> Json<ApiVersions> {
+    Json(ApiVersions {
+        versions: vec!["v1", "v2"],
+    })
 }
 
-async fn get_execution(
-    State(state): State<AppState>,
-    Path(id): Path<String>,
-) -> Result<Json<PipelineExecution>, StatusCode> {
-    let storage = state.storage.read().await;
-    let execution = storage
-        .get_execution(&id)
-        .ok_or(StatusCode::NOT_FOUND)?
-        .clone();
-    Ok(Json(execution))
+#[derive(Deserialize)]
+struct SetupRequest {
+    admin_username: String,
+    organization: String,
+    github_token: Option<String>,
 }
 
-async fn list_executions(
-    State(state): State<AppState>,
-) -> Json<Vec<PipelineExecution>> {
-    let storage = state.storage.read().await;
-    Json(storage.list_executions())
+#[derive(Serialize)]
+struct SetupResponse {
+    admin: User,
+    organization: Organization,
+    /// API token for the admin; shown only once
+    token: String,
 }
 
-fn create_push_event(repo: Repository, payload: &GitHubWebhookPayload) -> GitEvent {
-    let branch = payload
-        .ref_field
-        .as_ref()
-        .and_then(|r| r.strip_prefix("refs/heads/").map(String::from));
+/// Create the initial admin, organization and settings using the bootstrap token
+async fn setup_instance(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<SetupRequest>,
+) -> Result<(StatusCode, Json<SetupResponse>), StatusCode> {
+    let mut bootstrap = state.bootstrap_token.lock().await;
+    let mut storage = state.storage.write().await;
+    if storage.is_initialized() {
+        return Err(StatusCode::CONFLICT);
+    }
 
-    GitEvent {
-        event_type: GitEventType::Push,
-        repository: repo,
-        branch,
-        tag: None,
-        pull_request: None,
-        commit_sha: payload
-            .head_commit
-            .as_ref()
-            .and_then(|h| h.get("id"))
-            .and_then(|v| v.as_str())
-            .map(String::from),
-        sender: payload
-            .sender
-            .as_ref()
-            .map(|s| s.login.clone())
-            .unwrap_or_default(),
+    let presented = bearer_token(&headers).map(hash_token);
+    if bootstrap.is_none() || presented != *bootstrap {
+        return Err(StatusCode::UNAUTHORIZED);
     }
-}
 
-fn create_pull_request_event(repo: Repository, payload: &GitHubWebhookPayload) -> GitEvent {
-    let pr = payload.pull_request.as_ref().and_then(|pr| {
-        let number = pr.get("number")?.as_u64()?;
-        let title = pr.get("title")?.as_str()?.to_string();
-        let base = pr.get("base")?;
-        let head = pr.get("head")?;
-        let base_branch = base.get("ref")?.as_str()?.to_string();
-        let head_branch = head.get("ref")?.as_str()?.to_string();
-        let state = pr.get("state")?.as_str()?.to_string();
+    if req.admin_username.trim().is_empty() || req.organization.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
-        Some(pulsiora_core::PullRequest {
-            number,
-            title,
-            base_branch,
-            head_branch,
-            state,
-        })
-    });
+    let now = chrono::Utc::now();
+    let organization = Organization {
+        name: req.organization.trim().to_string(),
+        base_pulsefile: None,
+        quotas: OrganizationQuotas::default(),
+        created_at: now,
+    };
+    let (admin, token) = new_user(req.admin_username.trim(), &organization.name, true);
 
-    GitEvent {
-        event_type: GitEventType::PullRequest,
-        repository: repo,
-        branch: None,
-        tag: None,
-        pull_request: pr,
-        commit_sha: None,
-        sender: payload
-            .sender
-            .as_ref()
-            .map(|s| s.login.clone())
-            .unwrap_or_default(),
+    storage.create_organization(organization.clone());
+    storage.create_user(admin.clone());
+    if let Some(github_token) = req.github_token.filter(|t| !t.is_empty()) {
+        storage.settings_mut().github_token = Some(github_token);
     }
+    *bootstrap = None;
+
+    info!(admin = %admin.username, organization = %organization.name, "Instance setup completed");
+    state.audit.record(
+        Some(&admin.username),
+        AuditEvent::InstanceSetup {
+            organization: organization.name.clone(),
+        },
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SetupResponse {
+            admin,
+            organization,
+            token,
+        }),
+    ))
 }
 
-fn create_create_event(repo: Repository, payload: &GitHubWebhookPayload) -> GitEvent {
-    let ref_str = payload.ref_field.as_deref().unwrap_or("");
-    let is_tag = ref_str.starts_with("refs/tags/");
-    let branch = if !is_tag {
-        ref_str.strip_prefix("refs/heads/").map(String::from)
+/// Username behind the request's bearer token, for the audit log
+fn request_actor(storage: &InMemoryStorage, headers: &axum::http::HeaderMap) -> Option<String> {
+    bearer_token(headers)
+        .and_then(|token| storage.find_user_by_token(token))
+        .map(|user| user.username.clone())
+}
+
+/// The user behind the request's bearer token
+fn request_user<'a>(storage: &'a InMemoryStorage, headers: &axum::http::HeaderMap) -> Result<&'a User, StatusCode> {
+    bearer_token(headers)
+        .and_then(|token| storage.find_user_by_token(token))
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// Check the caller may see the executions of `repo`. A server hosting one
+/// organization shows them to anyone; one hosting several only to users who
+/// can view the repository, and to operators. Hidden ones are `404 Not Found`.
+fn authorize_execution_read(
+    storage: &InMemoryStorage,
+    headers: &axum::http::HeaderMap,
+    repo: &str,
+) -> Result<(), StatusCode> {
+    if !storage.is_multi_tenant() {
+        return Ok(());
+    }
+    let user = request_user(storage, headers)?;
+    if storage.is_operator(user) || storage.repo_role(user, repo) >= Some(RepoRole::Viewer) {
+        Ok(())
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Repositories whose executions the caller may see, see
+/// `authorize_execution_read`; `None` if they may see all of them
+fn visible_repos(
+    storage: &InMemoryStorage,
+    headers: &axum::http::HeaderMap,
+) -> Result<Option<HashSet<String>>, StatusCode> {
+    if !storage.is_multi_tenant() {
+        return Ok(None);
+    }
+    let user = request_user(storage, headers)?;
+    if storage.is_operator(user) {
+        return Ok(None);
+    }
+    Ok(Some(
+        storage
+            .registered_repos()
+            .into_iter()
+            .map(|repo| repo.repo_identifier.clone())
+            .filter(|repo| storage.repo_role(user, repo) >= Some(RepoRole::Viewer))
+            .collect(),
+    ))
+}
+
+/// The calling user, if they are an admin
+fn request_admin<'a>(storage: &'a InMemoryStorage, headers: &axum::http::HeaderMap) -> Result<&'a User, StatusCode> {
+    let user = request_user(storage, headers)?;
+    if !user.admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(user)
+}
+
+/// Username of the calling user if they have at least `required` on `repo`
+fn authorize_repo(
+    storage: &InMemoryStorage,
+    headers: &axum::http::HeaderMap,
+    repo: &str,
+    required: RepoRole,
+) -> Result<String, StatusCode> {
+    let user = request_user(storage, headers)?;
+    if !storage.is_repo_registered(repo) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    match storage.repo_role(user, repo) {
+        Some(role) if role >= required => Ok(user.username.clone()),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+#[derive(Deserialize)]
+struct CreateUserRequest {
+    username: String,
+    #[serde(default)]
+    admin: bool,
+}
+
+#[derive(Serialize)]
+struct CreatedUser {
+    user: User,
+    /// API token for the user; shown only once
+    token: String,
+}
+
+/// A new user with a fresh API token
+fn new_user(username: &str, organization: &str, admin: bool) -> (User, String) {
+    let token = generate_token();
+    let user = User {
+        username: username.to_string(),
+        organization: organization.to_string(),
+        admin,
+        token_hash: hash_token(&token),
+        created_at: chrono::Utc::now(),
+    };
+    (user, token)
+}
+
+/// Create a user in the calling admin's organization
+async fn create_user(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<CreateUserRequest>,
+) -> Result<(StatusCode, Json<CreatedUser>), StatusCode> {
+    let mut storage = state.storage.write().await;
+    let admin = request_admin(&storage, &headers)?;
+    let (actor, organization) = (admin.username.clone(), admin.organization.clone());
+    let username = req.username.trim();
+    if username.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if storage.get_user(username).is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+    let (user, token) = new_user(username, &organization, req.admin);
+    storage.create_user(user.clone());
+    drop(storage);
+
+    info!(username = %user.username, organization = %organization, "Created user");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::UserCreated {
+            username: user.username.clone(),
+            organization,
+            admin: user.admin,
+        },
+    );
+    Ok((StatusCode::CREATED, Json(CreatedUser { user, token })))
+}
+
+/// Users of the calling admin's organization
+async fn list_users(State(state): State<AppState>, headers: axum::http::HeaderMap) -> Result<Json<Vec<User>>, StatusCode> {
+    let storage = state.storage.read().await;
+    let admin = request_admin(&storage, &headers)?;
+    Ok(Json(storage.list_users(&admin.organization)))
+}
+
+/// Remove a user of the calling admin's organization, with their roles
+async fn remove_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let admin = request_admin(&storage, &headers)?;
+    let actor = admin.username.clone();
+    if storage.get_user(&username).is_none_or(|user| user.organization != admin.organization) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    // Admins can't lock themselves out
+    if username == actor {
+        return Err(StatusCode::CONFLICT);
+    }
+    storage.remove_user(&username);
+    drop(storage);
+
+    info!(username = %username, "Removed user");
+    state.audit.record(Some(&actor), AuditEvent::UserRemoved { username });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct CreateOrganizationRequest {
+    name: String,
+    admin_username: String,
+    #[serde(default)]
+    quotas: OrganizationQuotas,
+}
+
+/// The calling user, if they are one of the instance's operators
+fn request_operator<'a>(storage: &'a InMemoryStorage, headers: &axum::http::HeaderMap) -> Result<&'a User, StatusCode> {
+    let user = request_user(storage, headers)?;
+    if !storage.is_operator(user) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(user)
+}
+
+/// Create another organization along with its first admin; only operators
+/// may
+async fn create_organization(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<CreateOrganizationRequest>,
+) -> Result<(StatusCode, Json<SetupResponse>), StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = request_operator(&storage, &headers)?.username.clone();
+    let (name, admin_username) = (req.name.trim(), req.admin_username.trim());
+    if name.is_empty() || admin_username.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if storage.get_organization(name).is_some() || storage.get_user(admin_username).is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+    let organization = Organization {
+        name: name.to_string(),
+        base_pulsefile: None,
+        quotas: req.quotas,
+        created_at: chrono::Utc::now(),
+    };
+    let (admin, token) = new_user(admin_username, name, true);
+    storage.create_organization(organization.clone());
+    storage.create_user(admin.clone());
+    drop(storage);
+
+    info!(organization = %organization.name, admin = %admin.username, "Created organization");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::OrganizationCreated {
+            organization: organization.name.clone(),
+        },
+    );
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::UserCreated {
+            username: admin.username.clone(),
+            organization: organization.name.clone(),
+            admin: true,
+        },
+    );
+    if organization.quotas != OrganizationQuotas::default() {
+        record_quotas_changed(&state, &actor, &organization);
+    }
+    Ok((
+        StatusCode::CREATED,
+        Json(SetupResponse {
+            admin,
+            organization,
+            token,
+        }),
+    ))
+}
+
+fn record_quotas_changed(state: &AppState, actor: &str, organization: &Organization) {
+    state.audit.record(
+        Some(actor),
+        AuditEvent::OrganizationQuotasChanged {
+            organization: organization.name.clone(),
+            max_concurrent_runs: organization.quotas.max_concurrent_runs,
+            max_storage_bytes: organization.quotas.max_storage_bytes,
+        },
+    );
+}
+
+/// An organization as listed by the API, with what it uses of its quotas
+#[derive(Serialize)]
+struct OrganizationResponse {
+    name: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    quotas: OrganizationQuotas,
+    usage: OrganizationUsage,
+}
+
+#[derive(Serialize)]
+struct OrganizationUsage {
+    repositories: usize,
+    running_jobs: usize,
+    /// Bytes of step logs and artifacts on the server's disk
+    storage_bytes: u64,
+}
+
+async fn organization_response(state: &AppState, organization: Organization) -> OrganizationResponse {
+    let repos: HashSet<String> = state
+        .storage
+        .read()
+        .await
+        .organization_repos(&organization.name)
+        .into_iter()
+        .collect();
+    let running_jobs = state
+        .scheduler
+        .lock()
+        .unwrap()
+        .running()
+        .iter()
+        .filter(|job| repos.contains(&job.repository))
+        .count();
+    OrganizationResponse {
+        usage: OrganizationUsage {
+            repositories: repos.len(),
+            running_jobs,
+            storage_bytes: organization_storage_bytes(state, &organization.name).await,
+        },
+        name: organization.name,
+        created_at: organization.created_at,
+        quotas: organization.quotas,
+    }
+}
+
+/// Bytes the executions of an organization's repositories keep on the
+/// server's disk in step logs and artifacts
+async fn organization_storage_bytes(state: &AppState, organization: &str) -> u64 {
+    let executions: Vec<Uuid> = {
+        let storage = state.storage.read().await;
+        storage
+            .organization_repos(organization)
+            .iter()
+            .flat_map(|repo| storage.execution_ids_by_repo(repo))
+            .collect()
+    };
+    let data_dir = state.data_dir.clone();
+    match tokio::task::spawn_blocking(move || execution_disk_usage(&data_dir, &executions)).await {
+        Ok(Ok(bytes)) => bytes,
+        Ok(Err(e)) => {
+            warn!(organization, error = %e, "Failed to measure organization storage");
+            0
+        }
+        Err(e) => {
+            warn!(organization, error = %e, "Storage measuring task failed");
+            0
+        }
+    }
+}
+
+/// Organizations with their quotas and usage: all of them for operators,
+/// otherwise the caller's own
+async fn list_organizations(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<OrganizationResponse>>, StatusCode> {
+    let organizations: Vec<Organization> = {
+        let storage = state.storage.read().await;
+        let user = request_user(&storage, &headers)?;
+        let operator = storage.is_operator(user);
+        storage
+            .list_organizations()
+            .into_iter()
+            .filter(|org| operator || org.name == user.organization)
+            .collect()
+    };
+    let mut listed = Vec::with_capacity(organizations.len());
+    for organization in organizations {
+        listed.push(organization_response(&state, organization).await);
+    }
+    Ok(Json(listed))
+}
+
+async fn get_organization_quotas(
+    State(state): State<AppState>,
+    Path(org): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<OrganizationResponse>, StatusCode> {
+    let organization = {
+        let storage = state.storage.read().await;
+        let user = request_user(&storage, &headers)?;
+        if !storage.is_operator(user) && user.organization != org {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        storage.get_organization(&org).cloned().ok_or(StatusCode::NOT_FOUND)?
+    };
+    Ok(Json(organization_response(&state, organization).await))
+}
+
+/// Set an organization's quotas; only operators may, so a team can't raise
+/// its own
+async fn update_organization_quotas(
+    State(state): State<AppState>,
+    Path(org): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(quotas): Json<OrganizationQuotas>,
+) -> Result<Json<OrganizationResponse>, StatusCode> {
+    if quotas.max_concurrent_runs == Some(0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (actor, organization) = {
+        let mut storage = state.storage.write().await;
+        let actor = request_operator(&storage, &headers)?.username.clone();
+        if !storage.set_organization_quotas(&org, quotas) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        (actor, storage.get_organization(&org).cloned().ok_or(StatusCode::NOT_FOUND)?)
+    };
+    info!(organization = %org, user = %actor, "Updated organization quotas");
+    record_quotas_changed(&state, &actor, &organization);
+    // A raised cap may let waiting jobs start
+    state.jobs_changed.notify_one();
+    Ok(Json(organization_response(&state, organization).await))
+}
+
+/// Reject webhook calls from addresses outside the configured allow-list
+async fn enforce_webhook_allowlist(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if !state.webhook_allowlist.read().await.allows(addr.ip()) {
+        warn!(remote = %addr.ip(), path = %request.uri().path(), "Rejected webhook from address outside allow-list");
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(next.run(request).await)
+}
+
+/// Answer `429 Too Many Requests` to clients over their rate limit. Users are
+/// limited by their token and everyone else by address. Health checks and
+/// agents, which poll by design, aren't limited.
+async fn enforce_rate_limit(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    let Some(limiter) = state.rate_limiter.clone().filter(|_| {
+        !path.starts_with("/health") && !path.starts_with("/api/v1/agents/")
+    }) else {
+        return next.run(request).await;
+    };
+    let user = request_actor(&*state.storage.read().await, request.headers());
+    let client = match user {
+        Some(username) => format!("user:{}", username),
+        None => format!("ip:{}", addr.ip()),
+    };
+    match limiter.check(&client, std::time::Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            warn!(client, path = %request.uri().path(), "Rate limit exceeded");
+            too_many_requests(wait)
+        }
+    }
+}
+
+/// `429 Too Many Requests`, asking the client to retry after `wait`
+fn too_many_requests(wait: Duration) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_secs(wait).to_string())],
+    )
+        .into_response()
+}
+
+/// Parse a webhook payload with `parse`, on a blocking thread when it's
+/// larger than `WEBHOOK_INLINE_PARSE_LIMIT`. A payload that makes the
+/// parser fail outright gets `400 Bad Request`.
+async fn parse_webhook_body<B, T, F>(body: B, parse: F) -> Result<T, StatusCode>
+where
+    B: AsRef<[u8]> + Send + 'static,
+    T: Send + 'static,
+    F: FnOnce(B) -> T + Send + 'static,
+{
+    if body.as_ref().len() <= WEBHOOK_INLINE_PARSE_LIMIT {
+        return Ok(parse(body));
+    }
+    tokio::task::spawn_blocking(move || parse(body)).await.map_err(|e| {
+        warn!(error = %e, "Failed to parse a webhook payload");
+        StatusCode::BAD_REQUEST
+    })
+}
+
+/// Read a webhook payload as it arrives, stopping with `413 Payload Too
+/// Large` as soon as it passes `limit` bytes
+async fn read_webhook_body(body: Body, limit: usize) -> Result<axum::body::Bytes, StatusCode> {
+    let mut chunks = body.into_data_stream();
+    let mut body = Vec::new();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if body.len() + chunk.len() > limit {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.into())
+}
+
+/// Reject webhook payloads without a valid `X-Hub-Signature-256` when a
+/// webhook secret is set. Payloads for a repository with its own secret are
+/// checked against that one instead of the server's; the repository is the
+/// generic webhook's, or the one a GitHub payload names.
+async fn verify_webhook_signature(
+    State(state): State<AppState>,
+    path: Option<Path<HashMap<String, String>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let (parts, body) = request.into_parts();
+    // Payloads that say they're too large aren't read at all
+    let declared = parts
+        .headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let too_large = || {
+        warn!(path = %parts.uri.path(), limit = state.webhook_body_limit, "Rejected a webhook payload over the size limit");
+        StatusCode::PAYLOAD_TOO_LARGE
+    };
+    if declared.is_some_and(|length| length > state.webhook_body_limit as u64) {
+        return Err(too_large());
+    }
+    let body = match read_webhook_body(body, state.webhook_body_limit).await {
+        Err(StatusCode::PAYLOAD_TOO_LARGE) => return Err(too_large()),
+        body => body?,
+    };
+    let repo = match path.and_then(|Path(mut params)| params.remove("repo")) {
+        Some(repo) => Some(repo),
+        None => parse_webhook_body(body.clone(), |body| payload_repository(&body)).await?,
+    };
+    let repo_secret = match &repo {
+        Some(repo) => state.storage.read().await.get_registered_repo(repo).and_then(|r| r.settings.webhook_secret),
+        None => None,
+    };
+    let Some(secret) = repo_secret.map(Arc::from).or_else(|| state.webhook_secret.clone()) else {
+        return Ok(next.run(Request::from_parts(parts, Body::from(body))).await);
+    };
+    let signature = parts.headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+    if !signature.is_some_and(|signature| verify_signature(&secret, &body, signature)) {
+        warn!(path = %parts.uri.path(), "Rejected webhook with a missing or invalid signature");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
+
+/// Remove executions and delete step logs past their retention every
+/// `RETENTION_INTERVAL`
+async fn enforce_retention(state: AppState, policy: RetentionPolicy) {
+    let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+    loop {
+        interval.tick().await;
+        if policy.prunes_executions() {
+            let expired = {
+                let storage = state.storage.read().await;
+                expired_executions(&storage.list_executions(), &policy, chrono::Utc::now())
+            };
+            for &execution_id in &expired {
+                remove_execution_files(&state, execution_id).await;
+                state.storage.write().await.remove_execution(execution_id);
+            }
+            if !expired.is_empty() {
+                info!(removed = expired.len(), "Removed executions past their retention");
+            }
+        }
+
+        if policy.prunes_logs() {
+            // Running and interrupted executions still write to their logs
+            let active: HashSet<String> = {
+                let storage = state.storage.read().await;
+                storage
+                    .list_executions()
+                    .iter()
+                    .filter(|execution| {
+                        matches!(
+                            execution.status,
+                            PipelineStatus::Pending | PipelineStatus::Running | PipelineStatus::Interrupted
+                        )
+                    })
+                    .map(|execution| execution.id.to_string())
+                    .collect()
+            };
+            let logs = state.data_dir.join("logs");
+            let pruned = tokio::task::spawn_blocking(move || -> pulsiora_core::Result<usize> {
+                let by_age = match policy.max_log_age {
+                    Some(max_age) => prune_logs(&logs, max_age)?,
+                    None => 0,
+                };
+                let by_size = match policy.max_log_bytes {
+                    Some(max_bytes) => prune_log_bytes(&logs, max_bytes, &active)?,
+                    None => 0,
+                };
+                Ok(by_age + by_size)
+            })
+            .await;
+            match pruned {
+                Ok(Ok(0)) => {}
+                Ok(Ok(pruned)) => info!(pruned, "Deleted step logs past their retention"),
+                Ok(Err(e)) => warn!(error = %e, "Failed to delete old step logs"),
+                Err(e) => warn!(error = %e, "Log pruning task failed"),
+            }
+        }
+    }
+}
+
+/// Delete an execution's logs and artifacts, on disk and in object storage
+async fn remove_execution_files(state: &AppState, execution_id: Uuid) {
+    for dir in ["logs", "artifacts"] {
+        let path = state.data_dir.join(dir).join(execution_id.to_string());
+        match tokio::fs::remove_dir_all(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                warn!(%execution_id, error = %e, "Failed to delete execution files");
+            }
+            _ => {}
+        }
+    }
+    if let Some(objects) = &state.objects {
+        if let Err(e) = delete_execution_objects(objects.as_ref(), execution_id).await {
+            warn!(%execution_id, error = %e, "Failed to delete execution files from object storage");
+        }
+    }
+}
+
+/// Keep GitHub's hook ranges current; the previous ranges are kept if a refresh fails
+async fn refresh_github_hook_ranges(allowlist: Arc<RwLock<IpAllowList>>) {
+    let mut interval = tokio::time::interval(GITHUB_META_REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        match fetch_hook_ranges().await.and_then(parse_cidrs) {
+            Ok(ranges) => {
+                info!(count = ranges.len(), "Refreshed GitHub webhook ranges");
+                allowlist.write().await.set_github_ranges(ranges);
+            }
+            Err(e) => warn!(error = %e, "Failed to refresh GitHub webhook ranges"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GitHubWebhookPayload {
+    #[serde(rename = "ref")]
+    ref_field: Option<String>,
+    repository: Option<GitHubRepository>,
+    pull_request: Option<serde_json::Value>,
+    release: Option<serde_json::Value>,
+    action: Option<String>,
+    sender: Option<GitHubUser>,
+    head_commit: Option<serde_json::Value>,
+    /// Commits of a push, with the files each one changed
+    #[serde(default, deserialize_with = "capped_commits")]
+    commits: Option<Vec<GitHubCommit>>,
+    /// Sent with events for GitHub Apps
+    installation: Option<GitHubInstallation>,
+    /// The webhook a `ping` is about
+    hook_id: Option<u64>,
+    hook: Option<GitHubPingHook>,
+}
+
+#[derive(Deserialize)]
+struct GitHubPingHook {
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+/// Answer to GitHub's `ping`, sent when a webhook is created or redelivered,
+/// which GitHub shows with the delivery
+#[derive(Serialize)]
+struct PingResponse {
+    message: &'static str,
+    hook_id: Option<u64>,
+    repository: Option<String>,
+    /// Whether the repository is registered, so its events run pipelines
+    registered: bool,
+    /// What keeps the webhook from running pipelines
+    warnings: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GitHubCommit {
+    #[serde(default)]
+    added: Vec<String>,
+    #[serde(default)]
+    removed: Vec<String>,
+    #[serde(default)]
+    modified: Vec<String>,
+}
+
+/// Push payloads list at most this many commits, so a push with this many
+/// may have changed files they don't show
+const MAX_PUSH_PAYLOAD_COMMITS: usize = 20;
+
+/// The first `MAX_PUSH_PAYLOAD_COMMITS` commits of a push; the rest of a
+/// long list, which can't tell what the push changed anyway, is skipped
+/// without being kept
+fn capped_commits<'de, D>(deserializer: D) -> Result<Option<Vec<GitHubCommit>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct CappedCommits(Vec<GitHubCommit>);
+
+    impl<'de> Deserialize<'de> for CappedCommits {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(CappedCommits(Vec::new()))
+        }
+    }
+
+    impl<'de> serde::de::Visitor<'de> for CappedCommits {
+        type Value = CappedCommits;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a list of commits")
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
+            while self.0.len() < MAX_PUSH_PAYLOAD_COMMITS {
+                match seq.next_element()? {
+                    Some(commit) => self.0.push(commit),
+                    None => return Ok(self),
+                }
+            }
+            while seq.next_element::<serde::de::IgnoredAny>()?.is_some() {}
+            Ok(self)
+        }
+    }
+
+    Ok(Option::<CappedCommits>::deserialize(deserializer)?.map(|commits| commits.0))
+}
+
+#[derive(Deserialize)]
+struct GitHubInstallation {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct GitHubRepository {
+    name: String,
+    #[serde(rename = "full_name")]
+    full_name: String,
+    owner: GitHubUser,
+    #[serde(rename = "clone_url")]
+    clone_url: String,
+    #[serde(rename = "default_branch")]
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct GitHubUser {
+    login: String,
+}
+
+async fn handle_github_webhook(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Response, StatusCode> {
+    info!("Received GitHub webhook");
+
+    // Determine event type from X-GitHub-Event header
+    let event = headers
+        .get("X-GitHub-Event")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+    receive_delivery(&state, &headers, DeliverySource::GitHub { event }, body).await
+}
+
+/// Handle a webhook delivery and store it for replays. Deliveries whose ID
+/// was handled already are answered with `200 OK` and skipped.
+#[tracing::instrument(name = "webhook.delivery", skip_all, fields(delivery_id = tracing::field::Empty))]
+async fn receive_delivery(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    source: DeliverySource,
+    body: axum::body::Bytes,
+) -> Result<Response, StatusCode> {
+    let sent_id = DELIVERY_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name).and_then(|v| v.to_str().ok()))
+        .filter(|id| valid_delivery_id(id));
+    let id = match sent_id {
+        Some(id) => match state.deliveries.begin(id) {
+            Ok(true) => id.to_string(),
+            Ok(false) => {
+                info!(delivery_id = id, "Skipping duplicate webhook delivery");
+                return Ok(StatusCode::OK.into_response());
+            }
+            Err(e) => {
+                warn!(delivery_id = id, error = %e, "Failed to look up webhook delivery");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+        // Deliveries without an ID can't be recognised when sent again, but
+        // can still be replayed
+        None => {
+            let id = Uuid::new_v4().to_string();
+            state.deliveries.claim(&id);
+            id
+        }
+    };
+    tracing::Span::current().record("delivery_id", id.as_str());
+    let body = String::from_utf8(body.to_vec()).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut delivery = parse_webhook_body(body, move |body| WebhookDelivery::new(id, source, body)).await?;
+    let result = handle_delivery(state, &delivery).await;
+    record_delivery(state, &mut delivery, &result);
+    result
+}
+
+/// Note how a delivery was answered and store it
+fn record_delivery(state: &AppState, delivery: &mut WebhookDelivery, result: &Result<Response, StatusCode>) {
+    match result {
+        Ok(response) => {
+            delivery.status = response.status().as_u16();
+            delivery.execution_ids = response
+                .extensions()
+                .get::<QueuedExecutions>()
+                .map(|queued| queued.0.clone())
+                .unwrap_or_default();
+        }
+        Err(status) => {
+            delivery.status = status.as_u16();
+            delivery.execution_ids.clear();
+        }
+    }
+    if let Err(e) = state.deliveries.record(delivery) {
+        warn!(delivery_id = %delivery.id, error = %e, "Failed to store webhook delivery");
+    }
+}
+
+/// Run what a delivery asks for, as when it was first received
+async fn handle_delivery(state: &AppState, delivery: &WebhookDelivery) -> Result<Response, StatusCode> {
+    match &delivery.source {
+        DeliverySource::GitHub { event } => {
+            let payload = parse_webhook_body(delivery.body.clone(), |body| {
+                serde_json::from_str::<GitHubWebhookPayload>(&body)
+            })
+            .await?
+            .map_err(|_| {
+                if delivery.body.starts_with("payload=") {
+                    warn!(delivery_id = %delivery.id, "GitHub webhook sends form-encoded payloads; set its content type to application/json");
+                }
+                StatusCode::BAD_REQUEST
+            })?;
+            handle_github_event(state, event, payload).await
+        }
+        DeliverySource::Generic { repository } => {
+            let payload = parse_webhook_body(delivery.body.clone(), |body| {
+                serde_json::from_str::<serde_json::Value>(&body)
+            })
+            .await?
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+            handle_generic_event(state, repository, payload).await
+        }
+    }
+}
+
+/// Answer a `ping` with what would keep the webhook's events from running
+/// pipelines
+async fn ping_response(state: &AppState, payload: &GitHubWebhookPayload) -> PingResponse {
+    let repository = payload.repository.as_ref().map(|repo| repo.full_name.clone());
+    let registered = match &repository {
+        Some(repo) => state.storage.read().await.is_repo_registered(repo),
+        None => false,
+    };
+    let mut warnings = Vec::new();
+    if let (Some(repo), false) = (&repository, registered) {
+        warnings.push(format!("{} isn't registered, so its events don't run pipelines", repo));
+    }
+    if let Some(hook) = &payload.hook {
+        warnings.extend(missing_event_warnings(&hook.events));
+    }
+    info!(hook_id = ?payload.hook_id, repository = ?repository, ?warnings, "Answered GitHub webhook ping");
+    PingResponse {
+        message: "pong",
+        hook_id: payload.hook_id,
+        repository,
+        registered,
+        warnings,
+    }
+}
+
+async fn handle_github_event(
+    state: &AppState,
+    event_type: &str,
+    payload: GitHubWebhookPayload,
+) -> Result<Response, StatusCode> {
+    // The app was installed, or repositories were added to an installation
+    if matches!(event_type, "installation" | "installation_repositories") {
+        if let (Some(installation), Some(_), false) = (
+            &payload.installation,
+            &state.github_app,
+            payload.action.as_deref() == Some("deleted"),
+        ) {
+            tokio::spawn(configure_app_webhooks(state.clone(), Some(installation.id)));
+        }
+        return Ok(StatusCode::OK.into_response());
+    }
+    if event_type == "ping" {
+        return Ok(Json(ping_response(state, &payload).await).into_response());
+    }
+
+    let mut repository = match &payload.repository {
+        Some(repo) => Repository {
+            owner: repo.owner.login.clone(),
+            name: repo.name.clone(),
+            full_name: repo.full_name.clone(),
+            clone_url: repo.clone_url.clone(),
+            default_branch: repo.default_branch.clone(),
+        },
+        None => {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    let settings = state
+        .storage
+        .read()
+        .await
+        .get_registered_repo(&repository.full_name)
+        .map(|registered| registered.settings)
+        .unwrap_or_default();
+    if let Some(default_branch) = &settings.default_branch {
+        repository.default_branch = default_branch.clone();
+    }
+
+    let git_event = match event_type {
+        "push" => create_push_event(repository, &payload),
+        "pull_request" => match create_pull_request_event(repository, &payload) {
+            Some(event) => event,
+            None => {
+                info!(action = ?payload.action, "Ignoring pull request action");
+                return Ok(StatusCode::OK.into_response());
+            }
+        },
+        "create" => create_create_event(repository, &payload),
+        "delete" => create_delete_event(repository, &payload),
+        "release" => match create_release_event(repository, &payload) {
+            Some(event) => event,
+            None => {
+                info!(action = ?payload.action, "Ignoring release action");
+                return Ok(StatusCode::OK.into_response());
+            }
+        },
+        _ => {
+            info!(event_type, "Unhandled event type, skipping");
+            return Ok(StatusCode::OK.into_response());
+        }
+    };
+    if !settings.allows(git_event.event_type) {
+        info!(event = git_event.event_type.as_str(), "Event isn't allowed for the repository, skipping");
+        return Ok(StatusCode::OK.into_response());
+    }
+
+    let mut pulsefiles: Vec<_> = event_pulsefile(state, &git_event).await.into_iter().collect();
+    let registered = state.storage.read().await.get_registered_repo(&git_event.repository.full_name);
+    if let Some(registered) = registered.filter(|registered| !registered.path_pipelines.is_empty()) {
+        let changed_files = event_changed_files(state, &git_event, &payload).await;
+        pulsefiles.extend(path_pipelines_for(&registered, changed_files.as_deref()));
+    }
+    if pulsefiles.is_empty() {
+        return Ok(StatusCode::OK.into_response()); // Not an error, just no pipeline to run
+    }
+    queue_jobs(state, git_event, pulsefiles).await
+}
+
+/// Files a GitHub event changed: those of a push's commits, or of a pull
+/// request as GitHub lists them. None when they aren't known, e.g. for tags,
+/// releases and pushes with more commits than the payload lists.
+async fn event_changed_files(
+    state: &AppState,
+    git_event: &GitEvent,
+    payload: &GitHubWebhookPayload,
+) -> Option<Vec<String>> {
+    if let Some(pull_request) = &git_event.pull_request {
+        let token = github_token(state, &git_event.repository.full_name).await?;
+        return match pull_request_files(&token, &git_event.repository, pull_request.number).await {
+            Ok(files) => Some(files),
+            Err(e) => {
+                warn!(error = %e, "Failed to list pull request files, running every path-scoped pipeline");
+                None
+            }
+        };
+    }
+    match git_event.event_type {
+        GitEventType::Push => push_changed_files(payload),
+        _ => None,
+    }
+}
+
+/// Files changed by the commits of a push payload, if it lists all of them
+fn push_changed_files(payload: &GitHubWebhookPayload) -> Option<Vec<String>> {
+    let commits = payload.commits.as_ref()?;
+    if commits.is_empty() || commits.len() >= MAX_PUSH_PAYLOAD_COMMITS {
+        return None;
+    }
+    let mut files: Vec<String> = commits
+        .iter()
+        .flat_map(|commit| commit.added.iter().chain(&commit.removed).chain(&commit.modified))
+        .cloned()
+        .collect();
+    files.sort();
+    files.dedup();
+    Some(files)
+}
+
+/// Pulsefiles of a repo's path-scoped pipelines that run for an event that
+/// changed `changed_files`, or of all of them when those aren't known
+fn path_pipelines_for(
+    registered: &storage::RegisteredRepo,
+    changed_files: Option<&[String]>,
+) -> Vec<(String, Option<u32>)> {
+    registered
+        .path_pipelines
+        .iter()
+        .filter(|pipeline| pipeline.runs_for(changed_files))
+        .map(|pipeline| {
+            info!(repo = %registered.repo_identifier, pipeline = %pipeline.name, "Running path-scoped pipeline");
+            (pipeline.pulsefile.clone(), None)
+        })
+        .collect()
+}
+
+/// The Pulsefile a GitHub event runs, with its revision if it's the stored
+/// one. Registered repos run their stored Pulsefile or the one at the event's
+/// commit, as their `pulsefile_source` says; others run the one at the
+/// event's commit, else the one on their default branch.
+async fn event_pulsefile(state: &AppState, git_event: &GitEvent) -> Option<(String, Option<u32>)> {
+    let repo_identifier = &git_event.repository.full_name;
+    let registered = state.storage.read().await.get_registered_repo(repo_identifier);
+    let source_ref = git_event.source_ref();
+    if let Some(registered) = &registered {
+        if registered.pulsefile_source == PulsefileSource::Stored {
+            info!("Using stored Pulsefile for {}", repo_identifier);
+            return Some((registered.pulsefile.clone(), Some(registered.pulsefile_revision)));
+        }
+    }
+
+    let token = github_token(state, repo_identifier).await;
+    let fetched = fetch_pulsefile(&git_event.repository, source_ref.as_deref(), token.as_deref()).await;
+    let error = match fetched {
+        Ok(content) => return Some((content, None)),
+        Err(e) => e,
+    };
+    match registered {
+        Some(registered) if registered.pulsefile_source == PulsefileSource::Commit => {
+            info!(error = %error, git_ref = ?source_ref, "Failed to fetch Pulsefile, using the stored one");
+            Some((registered.pulsefile, Some(registered.pulsefile_revision)))
+        }
+        Some(_) => {
+            info!(error = %error, git_ref = ?source_ref, "Failed to fetch Pulsefile");
+            None
+        }
+        None if source_ref.is_some() => {
+            info!(error = %error, git_ref = ?source_ref, "Failed to fetch Pulsefile, trying the default branch");
+            match fetch_pulsefile(&git_event.repository, None, token.as_deref()).await {
+                Ok(content) => Some((content, None)),
+                Err(e) => {
+                    info!(error = %e, "Failed to fetch Pulsefile");
+                    None
+                }
+            }
+        }
+        None => {
+            info!(error = %error, "Failed to fetch Pulsefile");
+            None
+        }
+    }
+}
+
+/// Whether the caller may see or replay a delivery: those for registered
+/// repos need the given role on it, others an instance admin
+fn authorize_delivery(
+    storage: &InMemoryStorage,
+    headers: &axum::http::HeaderMap,
+    delivery: &WebhookDelivery,
+    required: RepoRole,
+) -> Result<String, StatusCode> {
+    let user = request_user(storage, headers)?;
+    let allowed = match delivery.repository.as_deref().filter(|repo| storage.is_repo_registered(repo)) {
+        Some(repo) => storage.repo_role(user, repo) >= Some(required),
+        None => user.admin,
+    };
+    if allowed {
+        Ok(user.username.clone())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// A stored delivery as listed, without its payload
+#[derive(Serialize)]
+struct DeliverySummary {
+    id: String,
+    source: DeliverySource,
+    repository: Option<String>,
+    received_at: chrono::DateTime<chrono::Utc>,
+    status: u16,
+    execution_ids: Vec<Uuid>,
+    replays: u32,
+    replayed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<WebhookDelivery> for DeliverySummary {
+    fn from(delivery: WebhookDelivery) -> Self {
+        Self {
+            id: delivery.id,
+            source: delivery.source,
+            repository: delivery.repository,
+            received_at: delivery.received_at,
+            status: delivery.status,
+            execution_ids: delivery.execution_ids,
+            replays: delivery.replays,
+            replayed_at: delivery.replayed_at,
+        }
+    }
+}
+
+/// Stored webhook deliveries the caller may see, newest first
+async fn list_deliveries(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<DeliverySummary>>, StatusCode> {
+    let deliveries = state.deliveries.list().map_err(|e| {
+        warn!(error = %e, "Failed to list webhook deliveries");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let storage = state.storage.read().await;
+    request_user(&storage, &headers)?;
+    Ok(Json(
+        deliveries
+            .into_iter()
+            .filter(|delivery| authorize_delivery(&storage, &headers, delivery, RepoRole::Viewer).is_ok())
+            .map(Into::into)
+            .collect(),
+    ))
+}
+
+/// A stored webhook delivery with its payload
+async fn get_delivery(
+    State(state): State<AppState>,
+    Path(delivery_id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<WebhookDelivery>, StatusCode> {
+    let delivery = stored_delivery(&state, &delivery_id)?;
+    authorize_delivery(&*state.storage.read().await, &headers, &delivery, RepoRole::Viewer)?;
+    Ok(Json(delivery))
+}
+
+fn stored_delivery(state: &AppState, delivery_id: &str) -> Result<WebhookDelivery, StatusCode> {
+    match state.deliveries.get(delivery_id) {
+        Ok(Some(delivery)) => Ok(delivery),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!(delivery_id, error = %e, "Failed to read webhook delivery");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Handle a stored delivery again, as if it had just been received, and
+/// answer as the webhook would. Needs an admin of its repository.
+async fn replay_delivery(
+    State(state): State<AppState>,
+    Path(delivery_id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, StatusCode> {
+    let mut delivery = stored_delivery(&state, &delivery_id)?;
+    let actor = authorize_delivery(&*state.storage.read().await, &headers, &delivery, RepoRole::Admin)?;
+    if !state.deliveries.claim(&delivery.id) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    info!(delivery_id = %delivery.id, user = %actor, "Replaying webhook delivery");
+    let result = handle_delivery(&state, &delivery).await;
+    delivery.replays += 1;
+    delivery.replayed_at = Some(chrono::Utc::now());
+    record_delivery(&state, &mut delivery, &result);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::WebhookReplayed {
+            delivery_id: delivery.id,
+            repository: delivery.repository,
+            execution_ids: delivery.execution_ids,
+        },
+    );
+    result
+}
+
+/// Trigger a registered repo's pipelines from any JSON payload, with event
+/// details found by the repo's webhook mapping
+async fn handle_generic_webhook(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Response, StatusCode> {
+    info!(repo = %repo, "Received generic webhook");
+    // Payloads for repos that aren't registered aren't kept
+    if !state.storage.read().await.is_repo_registered(&repo) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    receive_delivery(&state, &headers, DeliverySource::Generic { repository: repo }, body).await
+}
+
+async fn handle_generic_event(state: &AppState, repo: &str, payload: serde_json::Value) -> Result<Response, StatusCode> {
+    let registered = state
+        .storage
+        .read()
+        .await
+        .get_registered_repo(repo)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let git_event = match generic_event(&registered, &payload) {
+        Ok(Some(event)) => event,
+        Ok(None) => {
+            info!(repo = %repo, "Generic webhook has no known event type, skipping");
+            return Ok(StatusCode::OK.into_response());
+        }
+        Err(e) => {
+            warn!(repo = %repo, error = %e, "Failed to map generic webhook");
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    };
+    if !registered.settings.allows(git_event.event_type) {
+        info!(repo = %repo, event = git_event.event_type.as_str(), "Event isn't allowed for the repository, skipping");
+        return Ok(StatusCode::OK.into_response());
+    }
+    // The payload doesn't say which files changed
+    let mut pulsefiles = vec![(registered.pulsefile.clone(), Some(registered.pulsefile_revision))];
+    pulsefiles.extend(path_pipelines_for(&registered, None));
+    queue_jobs(state, git_event, pulsefiles).await
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RunRequest {
+    /// Branch to run on; the repository's default branch when unset
+    branch: Option<String>,
+    /// Commit to check out instead of the branch head
+    commit: Option<String>,
+    /// Passed to steps as `PULSE_INPUT_<NAME>`
+    inputs: std::collections::BTreeMap<String, String>,
+}
+
+/// Start a registered repo's pipeline on request. The pipeline needs a
+/// `manual` trigger; it runs as a `manual` event sent by the caller.
+async fn run_pipeline(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    request: Option<Json<RunRequest>>,
+) -> Result<Response, StatusCode> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let (registered, actor) = {
+        let storage = state.storage.read().await;
+        let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Developer)?;
+        (storage.get_registered_repo(&repo).ok_or(StatusCode::NOT_FOUND)?, actor)
+    };
+    let valid_name = |name: &String| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !request.inputs.keys().all(valid_name) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    match pulsiora_parser::parse_pulsefile(&registered.pulsefile) {
+        Ok(pipeline) if pipeline.triggers.matches(&TriggerEvent::Manual) => {}
+        Ok(_) => {
+            info!(repo = %repo, "Pipeline has no manual trigger, not running it");
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        Err(e) => {
+            warn!(repo = %repo, error = %e, "Failed to parse Pulsefile for manual run");
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+
+    info!(repo = %repo, user = %actor, "Manual pipeline run requested");
+    let git_event = GitEvent {
+        event_type: GitEventType::Manual,
+        repository: registered.repository(),
+        // Without a branch or commit, the repository's default branch setting,
+        // else the default branch of the clone
+        branch: request
+            .branch
+            .or_else(|| request.commit.is_none().then(|| registered.settings.default_branch.clone()).flatten()),
+        tag: None,
+        release_name: None,
+        pull_request: None,
+        commit_sha: request.commit,
+        commit_message: None,
+        sender: actor,
+        inputs: request.inputs,
+    };
+    queue_job(&state, git_event, registered.pulsefile, Some(registered.pulsefile_revision)).await
+}
+
+#[derive(Serialize)]
+struct QueuedResponse {
+    /// ID the execution will be stored under
+    execution_id: Uuid,
+    /// IDs of all the executions queued, when the event started several,
+    /// e.g. path-scoped pipelines; the first is `execution_id`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    execution_ids: Vec<Uuid>,
+}
+
+/// Executions a response queued, as a response extension
+#[derive(Clone)]
+struct QueuedExecutions(Vec<Uuid>);
+
+/// Queue a job running `pulsefile` for the event; see `queue_jobs`
+async fn queue_job(
+    state: &AppState,
+    git_event: GitEvent,
+    pulsefile: String,
+    pulsefile_revision: Option<u32>,
+) -> Result<Response, StatusCode> {
+    queue_jobs(state, git_event, vec![(pulsefile, pulsefile_revision)]).await
+}
+
+/// Journal a job for the event per Pulsefile, with its revision if it's a
+/// stored one, record them in the audit log and hand them to the workers.
+/// Answers `202 Accepted` without waiting for the pipelines,
+/// `429 Too Many Requests` when the jobs don't all fit in the queue, or
+/// `503 Service Unavailable` while the server is shutting down.
+#[tracing::instrument(
+    name = "queue",
+    skip_all,
+    fields(repository = %git_event.repository.full_name, jobs = pulsefiles.len())
+)]
+async fn queue_jobs(
+    state: &AppState,
+    git_event: GitEvent,
+    pulsefiles: Vec<(String, Option<u32>)>,
+) -> Result<Response, StatusCode> {
+    if state.shutdown.is_cancelled() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let queued = state.scheduler.lock().unwrap().pending().count();
+    if queued + pulsefiles.len() > state.max_queued_jobs {
+        warn!(queued, repository = %git_event.repository.full_name, "Queue is full, turning job away");
+        return Ok(too_many_requests(QUEUE_FULL_RETRY_AFTER));
+    }
+    let organization = {
+        let storage = state.storage.read().await;
+        storage
+            .organization_for_repo(&git_event.repository.full_name)
+            .filter(|org| org.quotas.max_storage_bytes.is_some())
+            .cloned()
+    };
+    if let Some(organization) = organization {
+        let used = organization_storage_bytes(state, &organization.name).await;
+        if organization.quotas.storage_exceeded(used) {
+            warn!(organization = %organization.name, used, "Organization is over its storage quota, turning job away");
+            return Err(StatusCode::INSUFFICIENT_STORAGE);
+        }
+    }
+    let mut execution_ids = Vec::with_capacity(pulsefiles.len());
+    for (pulsefile, pulsefile_revision) in pulsefiles {
+        // Persist the job before queueing it so a restart doesn't drop it
+        let job = QueuedJob::new(git_event.clone(), pulsefile)
+            .with_pulsefile_revision(pulsefile_revision)
+            .with_trace_parent(current_trace_parent());
+        if let Err(e) = state.journal.persist(&job) {
+            warn!(error = %e, "Failed to persist queued job");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        state.audit.record(
+            None,
+            AuditEvent::ExecutionQueued {
+                execution_id: job.id,
+                repository: job.git_event.repository.full_name.clone(),
+                trigger: job.git_event.event_type,
+                commit_sha: job.git_event.commit_sha.clone(),
+                sender: job.git_event.sender.clone(),
+            },
+        );
+
+        let execution_id = job.id;
+        enqueue(state, job).await;
+        let queued = state.storage.read().await.get_execution(&execution_id.to_string()).cloned();
+        if let Some(execution) = queued {
+            send_webhooks(state, WebhookEvent::ExecutionQueued, &execution).await;
+        }
+        execution_ids.push(execution_id);
+    }
+    let queued = axum::Extension(QueuedExecutions(execution_ids.clone()));
+    let response = QueuedResponse {
+        execution_id: execution_ids[0],
+        execution_ids: if execution_ids.len() > 1 { execution_ids } else { Vec::new() },
+    };
+    Ok((StatusCode::ACCEPTED, queued, Json(response)).into_response())
+}
+
+/// Send an execution event to the webhooks of its repository that want it.
+/// Deliveries run in the background and failures are only logged.
+async fn send_webhooks(state: &AppState, event: WebhookEvent, execution: &PipelineExecution) {
+    post_webhooks(state, &execution.repository.full_name, event, execution.id, |execution_url| {
+        webhook_payload(event, execution, execution_url)
+    })
+    .await;
+}
+
+/// Send a repository's webhooks that want `deployment_created` a deployment
+async fn send_deployment_webhooks(state: &AppState, repo: &str, deployment: &Deployment) {
+    post_webhooks(state, repo, WebhookEvent::DeploymentCreated, deployment.execution_id, |execution_url| {
+        deployment_payload(repo, deployment, execution_url)
+    })
+    .await;
+}
+
+/// Send the payload `payload` builds from the URL of `execution_id` to a
+/// repository's webhooks that want `event`
+async fn post_webhooks(
+    state: &AppState,
+    repo: &str,
+    event: WebhookEvent,
+    execution_id: Uuid,
+    payload: impl FnOnce(Option<&str>) -> serde_json::Value,
+) {
+    let (hooks, public_url) = {
+        let storage = state.storage.read().await;
+        let hooks: Vec<OutgoingWebhook> =
+            storage.repo_hooks(repo).into_iter().filter(|hook| hook.wants(event)).collect();
+        (hooks, storage.settings().public_url.clone())
+    };
+    if hooks.is_empty() {
+        return;
+    }
+    let execution_url = public_url.map(|url| format!("{}/api/v1/executions/{}", url, execution_id));
+    let body = payload(execution_url.as_deref()).to_string().into_bytes();
+    let client = reqwest::Client::new();
+    for hook in hooks {
+        let (client, body) = (client.clone(), body.clone());
+        tokio::spawn(async move {
+            if let Err(e) = deliver_webhook(&client, &hook, event, body).await {
+                warn!(hook_id = %hook.id, event = event.as_str(), error = %e, "Failed to deliver webhook");
+            }
+        });
+    }
+}
+
+/// Store a journaled job as a `Pending` execution and queue it for the workers
+async fn enqueue(state: &AppState, job: QueuedJob) {
+    let pipeline = resolve_pipeline(state, &job).await.ok();
+    let pending = job_execution(&job, pipeline, PipelineStatus::Pending);
+    state.storage.write().await.store_execution(pending);
+    state.scheduler.lock().unwrap().push(job);
+    state.jobs_changed.notify_one();
+}
+
+/// Start queued jobs whenever a worker is free and their repository is under
+/// its limit on concurrent runs
+async fn dispatch_jobs(state: AppState) {
+    loop {
+        // While shutting down, queued jobs stay in the journal for the restart
+        let started: Vec<QueuedJob> = if state.shutdown.is_cancelled() {
+            Vec::new()
+        } else {
+            let storage = state.storage.read().await;
+            let mut scheduler = state.scheduler.lock().unwrap();
+            std::iter::from_fn(|| scheduler.next(|repo| storage.run_limits(repo))).collect()
+        };
+        for job in started {
+            state.storage.write().await.set_execution_status(job.id, PipelineStatus::Running);
+            let state = state.clone();
+            let span = tracing::info_span!(
+                parent: None,
+                "execution",
+                execution_id = %job.id,
+                repository = %job.git_event.repository.full_name,
+                attempt = job.attempts.len() + 1
+            );
+            continue_trace(&span, job.trace_parent.as_deref());
+            tokio::spawn(
+                async move {
+                    if let Err(e) = run_job(&state, &job).await {
+                        warn!(job_id = %job.id, error = %e, "Pipeline execution failed");
+                    }
+                    state.scheduler.lock().unwrap().finish(job.id);
+                    state.jobs_changed.notify_one();
+                }
+                .instrument(span),
+            );
+        }
+        // Jobs waiting to retry are due without anything else changing
+        let retry_at = state.scheduler.lock().unwrap().next_retry_at();
+        match retry_at {
+            Some(at) => {
+                let wait = (at - chrono::Utc::now()).to_std().unwrap_or_default();
+                tokio::select! {
+                    _ = state.jobs_changed.notified() => {}
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+            None => state.jobs_changed.notified().await,
+        }
+    }
+}
+
+/// Execute a queued job, store the result and drop it from the journal. Each
+/// step's result is checkpointed in the journal as it finishes, and a job
+/// that already finished some steps continues from the first unfinished one.
+/// The job stops early when it is cancelled through the API. One that hits
+/// an infrastructure error is queued again while it has retries left, and
+/// fails otherwise.
+async fn run_job(state: &AppState, job: &QueuedJob) -> pulsiora_core::Result<PipelineExecution> {
+    let started_at = chrono::Utc::now();
+    let cancel = CancellationToken::new();
+    state.running.lock().unwrap().insert(job.id, cancel.clone());
+    let result = execute_job(state, job, cancel.clone()).await;
+    state.running.lock().unwrap().remove(&job.id);
+
+    // A job that can't be executed will never succeed on replay either
+    if let Err(e) = state.journal.remove(job.id) {
+        warn!(job_id = %job.id, error = %e, "Failed to remove job from journal");
+    }
+    let mut execution = match result {
+        Ok(execution) => execution,
+        // E.g. withdrawn before an agent took it
+        Err(e) if cancel.is_cancelled() => {
+            info!(execution_id = %job.id, error = %e, "Cancelled execution stopped");
+            let cancelled = state.storage.write().await.end_execution(job.id, PipelineStatus::Cancelled);
+            if let Some(execution) = &cancelled {
+                send_webhooks(state, WebhookEvent::ExecutionFinished, execution).await;
+            }
+            return cancelled.ok_or(e);
+        }
+        Err(e) => {
+            state.audit.record(
+                None,
+                AuditEvent::ExecutionErrored {
+                    execution_id: job.id,
+                    repository: job.git_event.repository.full_name.clone(),
+                    error: e.to_string(),
+                },
+            );
+            let mut job = job.clone();
+            job.started_at.get_or_insert(started_at);
+            let retries = job.attempts.len() as u32 + 1;
+            match state.retry_policy.delay(retries).filter(|_| e.is_infrastructure()) {
+                Some(delay) => retry_job(state, job, &e, delay).await,
+                None => fail_job(state, job, &e).await,
+            }
+            return Err(e);
+        }
+    };
+    execution.pulsefile_revision = job.pulsefile_revision;
+    execution.attempts = job.attempts.clone();
+    state.audit.record(
+        None,
+        AuditEvent::ExecutionFinished {
+            execution_id: execution.id,
+            repository: execution.repository.full_name.clone(),
+            pipeline: execution.pipeline_name.clone(),
+            status: execution.status,
+            duration_ms: execution
+                .completed_at
+                .map(|end| (end - execution.started_at).num_milliseconds().max(0) as u64)
+                .unwrap_or_default(),
+            limit_exceeded: execution.limit_exceeded.clone(),
+        },
+    );
+
+    let (status_context, deployment) = {
+        let mut storage = state.storage.write().await;
+        storage.store_execution(execution.clone());
+        let deployment = Deployment::from_execution(&execution)
+            .filter(|deployment| storage.record_deployment(&execution.repository.full_name, deployment.clone()));
+        let status_context = storage.status_context_for(&execution.repository.full_name, &execution.pipeline_name);
+        (status_context, deployment)
+    };
+
+    send_webhooks(state, WebhookEvent::ExecutionFinished, &execution).await;
+    if let Some(deployment) = &deployment {
+        info!(
+            execution_id = %execution.id,
+            environment = %deployment.environment,
+            "Recorded deployment"
+        );
+        send_deployment_webhooks(state, &execution.repository.full_name, deployment).await;
+    }
+    report_execution_status(state, &execution, &status_context).await;
+    comment_on_pull_request(state, &execution, &status_context).await;
+    offload_execution_files(state, &execution);
+
+    info!(
+        execution_id = %execution.id,
+        status = ?execution.status,
+        "Pipeline execution completed"
+    );
+
+    Ok(execution)
+}
+
+/// Queue a job that hit an infrastructure error to run again after `delay`,
+/// from its first step
+async fn retry_job(state: &AppState, mut job: QueuedJob, error: &pulsiora_core::PulsioraError, delay: chrono::Duration) {
+    job.retry_after(error.to_string(), delay);
+    if let Err(e) = state.journal.persist(&job) {
+        warn!(job_id = %job.id, error = %e, "Failed to journal retried job");
+    }
+    let attempt = job.attempts.len() as u32;
+    let retry_at = job.retry_at.unwrap_or_else(chrono::Utc::now);
+    warn!(
+        execution_id = %job.id,
+        attempt,
+        retry_in_secs = delay.num_seconds(),
+        error = %error,
+        "Execution hit an infrastructure error; retrying"
+    );
+    state.audit.record(
+        None,
+        AuditEvent::ExecutionRetried {
+            execution_id: job.id,
+            repository: job.git_event.repository.full_name.clone(),
+            attempt,
+            retry_at,
+            error: error.to_string(),
+        },
+    );
+    enqueue(state, job).await;
+}
+
+/// End a job that couldn't be run and won't be retried as a failed execution
+/// holding its tries
+async fn fail_job(state: &AppState, mut job: QueuedJob, error: &pulsiora_core::PulsioraError) {
+    job.record_attempt(error.to_string());
+    let failed = {
+        let mut storage = state.storage.write().await;
+        let mut execution = storage
+            .get_execution(&job.id.to_string())
+            .cloned()
+            .unwrap_or_else(|| job_execution(&job, None, PipelineStatus::Failed));
+        execution.status = PipelineStatus::Failed;
+        execution.attempts = job.attempts;
+        execution.completed_at = Some(chrono::Utc::now());
+        storage.store_execution(execution.clone());
+        execution
+    };
+    send_webhooks(state, WebhookEvent::ExecutionFinished, &failed).await;
+}
+
+/// Run a job on this server or an agent, stopping when `cancel` fires
+async fn execute_job(
+    state: &AppState,
+    job: &QueuedJob,
+    cancel: CancellationToken,
+) -> pulsiora_core::Result<PipelineExecution> {
+    let (limits, sandbox, env, secrets) = {
+        let storage = state.storage.read().await;
+        let repo = &job.git_event.repository.full_name;
+        (
+            storage.effective_limits(repo),
+            storage.get_repo_sandbox(repo).unwrap_or_default(),
+            storage.get_repo_env(repo).unwrap_or_default(),
+            job_secrets(state, &storage, &job.git_event),
+        )
+    };
+    let mut executor = state
+        .executor
+        .clone()
+        .with_limits(limits.clone())
+        .with_env(env.clone())
+        .with_secrets(secrets.clone())
+        .with_queued_at(job.queued_at);
+    if let Some(host_sandbox) = &state.host_sandbox {
+        executor = match host_sandbox.backend_for(&sandbox) {
+            Some(backend) => executor.with_backend(backend),
+            // Repos that aren't sandboxed run directly on the host
+            None => executor.with_backend(pulsiora_runner::HostBackend),
+        };
+    }
+    match resolve_pipeline(state, job).await {
+        Ok(pipeline) => {
+            // Record what is about to run so it can be replayed locally
+            let bundle = ReplayBundle::new(job.id, pipeline.clone(), job.git_event.clone())
+                .with_env(executor.resolved_env(job.id, &pipeline, &job.git_event));
+            state.storage.write().await.store_replay(bundle);
+            state.audit.record(
+                None,
+                AuditEvent::ExecutionStarted {
+                    execution_id: job.id,
+                    repository: job.git_event.repository.full_name.clone(),
+                    pipeline: pipeline.name.clone(),
+                },
+            );
+            let started = state.storage.read().await.get_execution(&job.id.to_string()).cloned();
+            if let Some(execution) = started {
+                send_webhooks(state, WebhookEvent::ExecutionStarted, &execution).await;
+            }
+
+            let mut checkpoint = job.clone();
+            checkpoint.started_at.get_or_insert_with(chrono::Utc::now);
+            checkpoint
+                .completed_steps
+                .truncate(resume_point(&pipeline, &checkpoint.completed_steps));
+            if let Err(e) = state.journal.persist(&checkpoint) {
+                warn!(job_id = %job.id, error = %e, "Failed to checkpoint job");
+            }
+            let completed = checkpoint.completed_steps.clone();
+            let (check_updates, check_run) = match start_check_run(state, job, &pipeline.name).await {
+                Some(check_run) => {
+                    let (updates, receiver) = mpsc::unbounded_channel();
+                    (Some(updates), Some(tokio::spawn(send_check_run_updates(check_run, receiver))))
+                }
+                None => (None, None),
+            };
+            // A resumed job's check run starts with the steps that already ran
+            if let (Some(updates), false) = (&check_updates, completed.is_empty()) {
+                let _ = updates.send(CheckRunUpdate {
+                    title: "Resumed".to_string(),
+                    summary: check_run_summary(&completed),
+                    annotations: completed.iter().flat_map(|step| step.annotations.clone()).collect(),
+                    conclusion: None,
+                });
+            }
+            let (events, receiver) = mpsc::channel(16);
+            let checkpoints = tokio::spawn(checkpoint_steps(
+                state.journal.clone(),
+                state.updates.clone(),
+                checkpoint,
+                receiver,
+                check_updates.clone(),
+            ));
+            let result = match &state.agents {
+                Some(agents) => {
+                    let job = AgentJob {
+                        execution_id: job.id,
+                        pipeline,
+                        git_event: job.git_event.clone(),
+                        limits,
+                        completed_steps: completed,
+                        queued_at: job.queued_at,
+                        env,
+                        secrets,
+                        sandbox: agent_sandbox(state.host_sandbox.as_ref(), &sandbox),
+                    };
+                    agents.run(job, events, cancel).await
+                }
+                None => {
+                    executor
+                        .resume_with_events(job.id, &pipeline, &job.git_event, completed, events, cancel)
+                        .await
+                }
+            };
+            // Checkpoints are written before the job leaves the journal
+            let _ = checkpoints.await;
+            if let (Some(updates), Some(check_run)) = (check_updates, check_run) {
+                let _ = updates.send(final_check_run_update(&result));
+                drop(updates);
+                let _ = check_run.await;
+            }
+            result
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Record each step's result in the job's journal entry as the step
+/// finishes, and pass the job's events on to live update clients and the
+/// job's check run
+async fn checkpoint_steps(
+    journal: Arc<JobJournal>,
+    updates: UpdateHub,
+    mut job: QueuedJob,
+    mut events: mpsc::Receiver<ExecutionEvent>,
+    check_updates: Option<mpsc::UnboundedSender<CheckRunUpdate>>,
+) {
+    while let Some(event) = events.recv().await {
+        updates.publish(Update::Step {
+            repository: job.git_event.repository.full_name.clone(),
+            event: event.clone(),
+        });
+        match event {
+            ExecutionEvent::StepStarted { step_name, .. } => {
+                if let Some(check_updates) = &check_updates {
+                    let _ = check_updates.send(CheckRunUpdate {
+                        title: format!("Running {}", step_name),
+                        summary: check_run_summary(&job.completed_steps),
+                        annotations: vec![],
+                        conclusion: None,
+                    });
+                }
+            }
+            ExecutionEvent::StepFinished { result, .. } => {
+                let annotations = result.annotations.clone();
+                let title = format!("{} {:?}", result.step_name, result.status);
+                job.completed_steps.push(*result);
+                if let Err(e) = journal.persist(&job) {
+                    warn!(job_id = %job.id, error = %e, "Failed to checkpoint job");
+                }
+                if let Some(check_updates) = &check_updates {
+                    let _ = check_updates.send(CheckRunUpdate {
+                        title,
+                        summary: check_run_summary(&job.completed_steps),
+                        annotations,
+                        conclusion: None,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Token for GitHub API calls about a repository: an installation token when
+/// the server runs as a GitHub App, else `GITHUB_TOKEN`
+async fn github_token(state: &AppState, repo: &str) -> Option<String> {
+    if let Some(app) = &state.github_app {
+        match app.token_for_repo(repo).await {
+            Ok(token) => return Some(token),
+            Err(e) => warn!(repo, error = %e, "Failed to get a GitHub App installation token"),
+        }
+    }
+    state.storage.read().await.settings().github_token.clone()
+}
+
+/// Point the webhooks of the GitHub App's installed repositories at this
+/// server, for one installation or all of them. Needs `public_url`.
+async fn configure_app_webhooks(state: AppState, installation: Option<u64>) {
+    let Some(app) = state.github_app.clone() else {
+        return;
+    };
+    let Some(public_url) = state.storage.read().await.settings().public_url.clone() else {
+        info!("Set public_url to have repository webhooks of the GitHub App's installations configured");
+        return;
+    };
+    let url = format!("{}{}", public_url.trim_end_matches('/'), GITHUB_WEBHOOK_PATH);
+    let installations = match installation {
+        Some(installation) => vec![installation],
+        None => match app.installations().await {
+            Ok(installations) => installations,
+            Err(e) => {
+                warn!(error = %e, "Failed to list GitHub App installations");
+                return;
+            }
+        },
+    };
+    for installation in installations {
+        match app.configure_webhooks(installation, &url, state.webhook_secret.as_deref()).await {
+            Ok(created) => info!(installation, created, "Configured repository webhooks"),
+            Err(e) => warn!(installation, error = %e, "Failed to configure repository webhooks"),
+        }
+    }
+}
+
+/// Start a check run for the job when check runs are turned on and its
+/// event names a commit
+async fn start_check_run(state: &AppState, job: &QueuedJob, pipeline: &str) -> Option<CheckRun> {
+    let sha = job.git_event.commit_sha.as_ref()?;
+    let repository = &job.git_event.repository;
+    let name = {
+        let storage = state.storage.read().await;
+        if !storage.settings().github_checks {
+            return None;
+        }
+        storage.status_context_for(&repository.full_name, pipeline)
+    };
+    let token = github_token(state, &repository.full_name).await?;
+    match CheckRun::create(&token, repository, sha, &name, &job.id.to_string()).await {
+        Ok(check_run) => Some(check_run),
+        Err(e) => {
+            warn!(job_id = %job.id, error = %e, "Failed to create check run");
+            None
+        }
+    }
+}
+
+/// Send a job's check run updates one at a time and in order, so a slow
+/// GitHub API doesn't hold the job's steps up
+async fn send_check_run_updates(check_run: CheckRun, mut updates: mpsc::UnboundedReceiver<CheckRunUpdate>) {
+    while let Some(update) = updates.recv().await {
+        if let Err(e) = check_run.update(&update).await {
+            warn!(error = %e, "Failed to update check run");
+        }
+    }
+}
+
+/// The check run update for how the job ended. Interrupted jobs leave their
+/// check run in progress until they are resumed.
+fn final_check_run_update(result: &pulsiora_core::Result<PipelineExecution>) -> CheckRunUpdate {
+    match result {
+        Ok(execution) => CheckRunUpdate {
+            title: format!("{} {:?}", execution.pipeline_name, execution.status),
+            summary: check_run_summary(&execution.step_results),
+            annotations: vec![],
+            conclusion: check_run_conclusion(execution.status),
+        },
+        Err(e) => CheckRunUpdate {
+            title: "Execution errored".to_string(),
+            summary: e.to_string(),
+            annotations: vec![],
+            conclusion: Some("failure"),
+        },
+    }
+}
+
+/// Parse the job's Pulsefile and merge in its organization's base pipeline
+async fn resolve_pipeline(state: &AppState, job: &QueuedJob) -> pulsiora_core::Result<Pipeline> {
+    let mut pipeline = pulsiora_parser::parse_pulsefile(&job.pulsefile)?;
+    let repo = &job.git_event.repository.full_name;
+    let base = state.storage.read().await.base_pulsefile_for(repo);
+    if let Some(base) = base {
+        let base = pulsiora_parser::parse_pulsefile(&base)?;
+        for step in pipeline.apply_base(&base) {
+            warn!(repo = %repo, step = %step, "Step replaced by organization base pipeline");
+        }
+    }
+    Ok(pipeline)
+}
+
+/// Re-run jobs accepted before the last shutdown, skipping ones that already
+/// completed. Jobs that had started are marked interrupted instead, to be
+/// resumed through the API.
+async fn recover_queued_jobs(state: &AppState) -> anyhow::Result<()> {
+    for job in state.journal.load_all()? {
+        // Queued jobs are stored as pending, and then running, until they end
+        let completed = state
+            .storage
+            .read()
+            .await
+            .get_execution(&job.id.to_string())
+            .is_some_and(|execution| !matches!(execution.status, PipelineStatus::Pending | PipelineStatus::Running));
+        if completed {
+            state.journal.remove(job.id)?;
+            continue;
+        }
+        if job.started_at.is_some() {
+            mark_interrupted(state, &job).await;
+            continue;
+        }
+
+        info!(job_id = %job.id, "Resuming job queued before restart");
+        enqueue(state, job).await;
+    }
+    Ok(())
+}
+
+/// The execution record of a job that hasn't finished: queued, or stopped
+/// by a restart with the steps it finished
+fn job_execution(job: &QueuedJob, pipeline: Option<Pipeline>, status: PipelineStatus) -> PipelineExecution {
+    PipelineExecution {
+        id: job.id,
+        pipeline_name: pipeline.as_ref().map(|p| p.name.clone()).unwrap_or_default(),
+        pipeline_version: pipeline.as_ref().map(|p| p.version.clone()).unwrap_or_default(),
+        pipeline_labels: pipeline.as_ref().map(|p| p.labels.clone()).unwrap_or_default(),
+        environment: pipeline.and_then(|p| p.environment),
+        repository: job.git_event.repository.clone(),
+        git_event: job.git_event.clone(),
+        status,
+        step_results: job.completed_steps.clone(),
+        phases: vec![],
+        limit_exceeded: None,
+        coverage: None,
+        metrics: ExecutionMetrics::default(),
+        pulsefile_revision: job.pulsefile_revision,
+        attempts: job.attempts.clone(),
+        started_at: job.started_at.unwrap_or(job.queued_at),
+        completed_at: None,
+    }
+}
+
+/// Store a job that was running when the server stopped as an `Interrupted`
+/// execution holding the steps it finished
+async fn mark_interrupted(state: &AppState, job: &QueuedJob) {
+    let pipeline = resolve_pipeline(state, job).await.ok();
+    let execution = job_execution(job, pipeline, PipelineStatus::Interrupted);
+    warn!(
+        execution_id = %job.id,
+        completed_steps = job.completed_steps.len(),
+        "Execution was interrupted by a restart; resume it with POST /api/v1/executions/{}/resume",
+        job.id
+    );
+    state.audit.record(
+        None,
+        AuditEvent::ExecutionInterrupted {
+            execution_id: job.id,
+            repository: job.git_event.repository.full_name.clone(),
+            completed_steps: job.completed_steps.len(),
+        },
+    );
+    state.storage.write().await.store_execution(execution);
+}
+
+#[derive(Serialize)]
+struct ResumeResponse {
+    execution_id: Uuid,
+    /// Index of the first step that runs again
+    resume_from: usize,
+}
+
+/// Continue an interrupted execution from its first unfinished step. The
+/// steps run in the background; the execution is `Running` until they end.
+async fn resume_execution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<(StatusCode, Json<ResumeResponse>), StatusCode> {
+    let execution_id = Uuid::parse_str(&id).map_err(|_| StatusCode::NOT_FOUND)?;
+    if state.shutdown.is_cancelled() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let job = state.journal.load(execution_id).map_err(|e| {
+        warn!(execution_id = %execution_id, error = %e, "Failed to load interrupted job");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut storage = state.storage.write().await;
+    request_user(&storage, &headers)?;
+    let execution = storage.get_execution(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let interrupted = execution.status == PipelineStatus::Interrupted;
+    let actor = authorize_repo(&storage, &headers, &execution.repository.full_name, RepoRole::Developer)?;
+    // Only interrupted executions still have their job in the journal
+    let job = job.filter(|_| interrupted).ok_or(StatusCode::CONFLICT)?;
+    storage.set_execution_status(execution_id, PipelineStatus::Running);
+    drop(storage);
+
+    let resume_from = match resolve_pipeline(&state, &job).await {
+        Ok(pipeline) => resume_point(&pipeline, &job.completed_steps),
+        Err(_) => 0,
+    };
+    info!(execution_id = %execution_id, resume_from, "Resuming interrupted execution");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::ExecutionResumed {
+            execution_id,
+            repository: job.git_event.repository.full_name.clone(),
+            from_step: resume_from,
+        },
+    );
+
+    let background = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_job(&background, &job).await {
+            warn!(execution_id = %job.id, error = %e, "Resumed execution failed");
+        }
+    });
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ResumeResponse {
+            execution_id,
+            resume_from,
+        }),
+    ))
+}
+
+/// Push execution updates to a WebSocket client. The client sends
+/// `subscribe` and `unsubscribe` messages naming repositories and executions,
+/// and gets their status changes and step events as they happen.
+async fn execution_updates(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    let visible = visible_repos(&*state.storage.read().await, &headers)?;
+    let updates = state.updates.subscribe();
+    Ok(ws.on_upgrade(move |socket| stream_updates(socket, updates, visible)))
+}
+
+/// Send a client the updates it subscribed to, out of those of `visible`
+/// repositories
+async fn stream_updates(
+    mut socket: WebSocket,
+    mut updates: broadcast::Receiver<Update>,
+    visible: Option<HashSet<String>>,
+) {
+    let mut subscription = Subscription::default();
+    loop {
+        let outgoing = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(message) => {
+                        subscription.apply(message);
+                        serde_json::to_string(&subscription.notice())
+                    }
+                    Err(e) => serde_json::to_string(&Notice::Error {
+                        message: format!("Invalid message: {}", e),
+                    }),
+                },
+                // Pings are answered by the socket itself
+                Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => continue,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+            },
+            update = updates.recv() => match update {
+                Ok(update)
+                    if subscription.wants(&update)
+                        && visible.as_ref().is_none_or(|repos| repos.contains(update.repository())) =>
+                {
+                    serde_json::to_string(&update)
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => serde_json::to_string(&Notice::Lagged { missed }),
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+        let Ok(text) = outgoing else { continue };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// How long a cancel request waits for a running execution to stop
+const CANCEL_WAIT: Duration = Duration::from_secs(10);
+
+/// Cancel an execution that hasn't finished. Queued and interrupted ones are
+/// marked `Cancelled` at once; a running one has its current step killed and
+/// the rest skipped. Answers with the execution as it is afterwards, which is
+/// still `Running` if it didn't stop within `CANCEL_WAIT`.
+async fn cancel_execution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<PipelineExecution>, StatusCode> {
+    let execution_id = Uuid::parse_str(&id).map_err(|_| StatusCode::NOT_FOUND)?;
+    let (status, repository, actor) = {
+        let storage = state.storage.read().await;
+        request_user(&storage, &headers)?;
+        let execution = storage.get_execution(&id).ok_or(StatusCode::NOT_FOUND)?;
+        let repository = execution.repository.full_name.clone();
+        let actor = authorize_repo(&storage, &headers, &repository, RepoRole::Developer)?;
+        (execution.status, repository, actor)
+    };
+    if !matches!(
+        status,
+        PipelineStatus::Pending | PipelineStatus::Running | PipelineStatus::Interrupted
+    ) {
+        return Err(StatusCode::CONFLICT);
+    }
+    info!(execution_id = %execution_id, "Cancelling execution");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::ExecutionCancelled {
+            execution_id,
+            repository,
+        },
+    );
+
+    // Jobs that haven't started, or were stopped by a restart, never run
+    let waiting = match status {
+        PipelineStatus::Pending => state.scheduler.lock().unwrap().remove(execution_id).is_some(),
+        PipelineStatus::Interrupted => true,
+        _ => false,
+    };
+    if waiting {
+        if let Err(e) = state.journal.remove(execution_id) {
+            warn!(execution_id = %execution_id, error = %e, "Failed to remove job from journal");
+        }
+        state.jobs_changed.notify_one();
+        let cancelled = state.storage.write().await.end_execution(execution_id, PipelineStatus::Cancelled);
+        if let Some(execution) = &cancelled {
+            send_webhooks(&state, WebhookEvent::ExecutionFinished, execution).await;
+        }
+        return cancelled.map(Json).ok_or(StatusCode::NOT_FOUND);
+    }
+
+    // A job taken from the queue a moment ago may not have its token yet
+    let deadline = tokio::time::Instant::now() + CANCEL_WAIT;
+    let mut signalled = false;
+    loop {
+        if !signalled {
+            if let Some(cancel) = state.running.lock().unwrap().get(&execution_id) {
+                cancel.cancel();
+                signalled = true;
+            }
+        }
+        let execution = state
+            .storage
+            .read()
+            .await
+            .get_execution(&id)
+            .cloned()
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let stopped = !matches!(execution.status, PipelineStatus::Pending | PipelineStatus::Running);
+        if stopped || tokio::time::Instant::now() >= deadline {
+            return Ok(Json(execution));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// The agent pool; agent routes are not found when jobs run on this server
+fn agent_pool(state: &AppState) -> Result<&Arc<AgentPool>, StatusCode> {
+    state.agents.as_ref().ok_or(StatusCode::NOT_FOUND)
+}
+
+/// The registered agent making the call
+fn calling_agent(agents: &AgentPool, headers: &axum::http::HeaderMap) -> Result<Uuid, StatusCode> {
+    bearer_token(headers)
+        .and_then(|token| agents.authenticate(token))
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// Register an agent presenting the agent token; answers with its own token
+async fn register_agent(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(registration): Json<AgentRegistration>,
+) -> Result<Json<AgentRegistered>, StatusCode> {
+    let agents = agent_pool(&state)?;
+    let token = bearer_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    let registered = agents
+        .register(token, registration.clone())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    info!(
+        agent_id = %registered.agent_id,
+        name = %registration.name,
+        os = %registration.os,
+        "Agent registered"
+    );
+    state.audit.record(
+        None,
+        AuditEvent::AgentRegistered {
+            agent_id: registered.agent_id,
+            name: registration.name,
+            os: registration.os,
+            arch: registration.arch,
+        },
+    );
+    Ok(Json(registered))
+}
+
+/// The registered agents and their jobs; only operators may list them
+async fn list_agents(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<AgentInfo>>, StatusCode> {
+    request_operator(&*state.storage.read().await, &headers)?;
+    Ok(Json(agent_pool(&state)?.agents()))
+}
+
+/// Hand the calling agent a job, waiting a while for one to be queued
+async fn poll_agent_job(State(state): State<AppState>, headers: axum::http::HeaderMap) -> Result<Response, StatusCode> {
+    let agents = agent_pool(&state)?;
+    let agent_id = calling_agent(agents, &headers)?;
+    Ok(match agents.poll(agent_id, AGENT_POLL_WAIT).await {
+        Some(job) => {
+            info!(agent_id = %agent_id, execution_id = %job.execution_id, "Assigned job to agent");
+            Json(job).into_response()
+        }
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+/// Events from a job running on the calling agent. The answer tells the
+/// agent whether the job was cancelled; `409 Conflict` tells it the job
+/// isn't its own any more.
+async fn report_agent_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(report): Json<AgentReport>,
+) -> Result<Json<AgentReportAck>, StatusCode> {
+    let agents = agent_pool(&state)?;
+    let agent_id = calling_agent(agents, &headers)?;
+    let execution_id = Uuid::parse_str(&id).map_err(|_| StatusCode::NOT_FOUND)?;
+    agents
+        .report(agent_id, execution_id, report.events)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::CONFLICT)
+}
+
+/// How a job on the calling agent ended
+async fn finish_agent_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(outcome): Json<AgentOutcome>,
+) -> Result<StatusCode, StatusCode> {
+    let agents = agent_pool(&state)?;
+    let agent_id = calling_agent(agents, &headers)?;
+    let execution_id = Uuid::parse_str(&id).map_err(|_| StatusCode::NOT_FOUND)?;
+    match agents.finish(agent_id, execution_id, outcome) {
+        true => Ok(StatusCode::NO_CONTENT),
+        false => Err(StatusCode::CONFLICT),
+    }
+}
+
+/// Report the execution outcome as a commit status when a GitHub token is
+/// configured, unless check runs report it instead
+async fn report_execution_status(state: &AppState, execution: &PipelineExecution, context: &str) {
+    if state.storage.read().await.settings().github_checks {
+        return;
+    }
+    let (Some(sha), Some(commit_state)) = (&execution.git_event.commit_sha, commit_status_state(execution.status)) else {
+        return;
+    };
+    let Some(token) = github_token(state, &execution.repository.full_name).await else {
+        return;
+    };
+
+    let description = format!("{} {:?}", execution.pipeline_name, execution.status);
+    if let Err(e) = report_commit_status(
+        &token,
+        &execution.repository,
+        sha,
+        context,
+        commit_state,
+        &description,
+    )
+    .await
+    {
+        warn!(error = %e, context, "Failed to report commit status");
+    }
+}
+
+/// Move a finished execution's logs and artifacts to object storage in the
+/// background, when a bucket is configured
+fn offload_execution_files(state: &AppState, execution: &PipelineExecution) {
+    // Resuming an interrupted execution adds to its logs and artifacts
+    let (Some(objects), false) = (state.objects.clone(), execution.status == PipelineStatus::Interrupted) else {
+        return;
+    };
+    let data_dir = state.data_dir.clone();
+    let execution_id = execution.id;
+    tokio::spawn(async move {
+        match offload_execution(objects.as_ref(), &data_dir, execution_id).await {
+            Ok(files) => info!(%execution_id, files, "Moved logs and artifacts to object storage"),
+            Err(e) => warn!(%execution_id, error = %e, "Failed to move logs and artifacts to object storage"),
+        }
+    });
+}
+
+/// Comment on the execution's pull request with a summary of how it went,
+/// when pull request comments are turned on
+async fn comment_on_pull_request(state: &AppState, execution: &PipelineExecution, context: &str) {
+    let Some(pull_request) = &execution.git_event.pull_request else {
+        return;
+    };
+    let public_url = {
+        let storage = state.storage.read().await;
+        let settings = storage.settings();
+        if !settings.github_pr_comments {
+            return;
+        }
+        settings.public_url.clone()
+    };
+    let Some(token) = github_token(state, &execution.repository.full_name).await else {
+        return;
+    };
+
+    let execution_url = public_url.map(|url| format!("{}/api/v1/executions/{}", url, execution.id));
+    let body = pr_comment_body(context, execution, execution_url.as_deref());
+    if let Err(e) = upsert_pr_comment(&token, &execution.repository, pull_request.number, context, &body).await {
+        warn!(error = %e, context, "Failed to comment on pull request");
+    }
+}
+
+async fn get_execution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<PipelineExecution>, StatusCode> {
+    let storage = state.storage.read().await;
+    let execution = storage
+        .get_execution(&id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+    authorize_execution_read(&storage, &headers, &execution.repository.full_name)?;
+    Ok(Json(execution))
+}
+
+/// When each phase and step of an execution ran, for waterfall views
+async fn get_execution_timeline(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Timeline>, StatusCode> {
+    let storage = state.storage.read().await;
+    let execution = storage.get_execution(&id).ok_or(StatusCode::NOT_FOUND)?;
+    authorize_execution_read(&storage, &headers, &execution.repository.full_name)?;
+    Ok(Json(Timeline::new(execution, chrono::Utc::now())))
+}
+
+#[derive(Deserialize)]
+struct StepLogQuery {
+    /// `stdout` or `stderr` [default: stdout]
+    stream: Option<String>,
+}
+
+/// One stream of a step's output in full: streamed from its log file when
+/// the runner logged it, else the output kept on the step result
+async fn get_step_log(
+    State(state): State<AppState>,
+    Path((id, index)): Path<(String, usize)>,
+    Query(query): Query<StepLogQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, StatusCode> {
+    let stream = match query.stream.as_deref() {
+        None | Some("stdout") => OutputStream::Stdout,
+        Some("stderr") => OutputStream::Stderr,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+    let step = {
+        let storage = state.storage.read().await;
+        let execution = storage.get_execution(&id).ok_or(StatusCode::NOT_FOUND)?;
+        authorize_execution_read(&storage, &headers, &execution.repository.full_name)?;
+        execution.step_results.get(index).cloned().ok_or(StatusCode::NOT_FOUND)?
+    };
+
+    let log_path = step
+        .log_ref
+        .as_deref()
+        .and_then(|log_ref| state.executor.step_log_path(log_ref, stream));
+    if let Some(path) = log_path {
+        if let Ok(file) = tokio::fs::File::open(&path).await {
+            // The file holds the bytes as the step wrote them
+            let charset = step.encoding.as_deref().unwrap_or("utf-8");
+            return Ok((
+                [(header::CONTENT_TYPE, format!("text/plain; charset={}", charset))],
+                Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+            )
+                .into_response());
+        }
+    }
+    // Logs of finished executions may have been moved to object storage
+    let key = step.log_ref.as_deref().and_then(|log_ref| step_log_key(log_ref, stream));
+    if let (Some(objects), Some(key)) = (&state.objects, key) {
+        let stored = objects.list(&key).await.is_ok_and(|objects| objects.iter().any(|(name, _)| *name == key));
+        if stored {
+            let url = objects.signed_url(&key, SIGNED_URL_TTL).await.map_err(|e| {
+                warn!(error = %e, "Failed to sign step log URL");
+                StatusCode::BAD_GATEWAY
+            })?;
+            return Ok(Redirect::temporary(&url).into_response());
+        }
+    }
+    let text = match stream {
+        OutputStream::Stdout => step.stdout,
+        OutputStream::Stderr => step.stderr,
+    };
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], text).into_response())
+}
+
+#[derive(Deserialize, Default)]
+struct CreateShareRequest {
+    /// Lifetime of the link [default: 7 days]
+    expires_in_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct CreateShareResponse {
+    id: Uuid,
+    /// Shown only once; the server keeps a hash
+    token: String,
+    /// Path of the shared execution on this server
+    url: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Create a link that grants read-only access to one execution; only users
+/// who may see the execution can share it
+async fn create_share_link(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+    body: Option<Json<CreateShareRequest>>,
+) -> Result<(StatusCode, Json<CreateShareResponse>), StatusCode> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let ttl = req.expires_in_secs.unwrap_or(DEFAULT_SHARE_TTL_SECS);
+    if ttl == 0 || ttl > MAX_SHARE_TTL_SECS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut storage = state.storage.write().await;
+    let actor = request_actor(&storage, &headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    let execution = storage.get_execution(&id).ok_or(StatusCode::NOT_FOUND)?;
+    authorize_execution_read(&storage, &headers, &execution.repository.full_name)?;
+    let execution_id = execution.id;
+
+    let token = generate_token();
+    let now = chrono::Utc::now();
+    let link = ShareLink {
+        id: Uuid::new_v4(),
+        execution_id,
+        token_hash: hash_token(&token),
+        created_by: actor.clone(),
+        created_at: now,
+        expires_at: now + chrono::Duration::seconds(ttl as i64),
+        revoked_at: None,
+    };
+    let response = CreateShareResponse {
+        id: link.id,
+        url: format!("/shared/{}", token),
+        token,
+        expires_at: link.expires_at,
+    };
+    storage.create_share_link(link);
+    drop(storage);
+
+    info!(execution_id = %execution_id, share_id = %response.id, "Created share link");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::ExecutionShared {
+            execution_id,
+            share_id: response.id,
+            expires_at: response.expires_at,
+        },
+    );
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+async fn list_share_links(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<ShareLink>>, StatusCode> {
+    let storage = state.storage.read().await;
+    request_actor(&storage, &headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    let execution = storage.get_execution(&id).ok_or(StatusCode::NOT_FOUND)?;
+    authorize_execution_read(&storage, &headers, &execution.repository.full_name)?;
+    Ok(Json(storage.share_links_for(&id)))
+}
+
+/// Revoke a share link; only its creator, an admin of the execution's
+/// repository or an operator may
+async fn revoke_share_link(
+    State(state): State<AppState>,
+    Path((id, share_id)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let user = request_user(&storage, &headers)?;
+    let (actor, operator) = (user.username.clone(), storage.is_operator(user));
+    let repo = storage
+        .get_execution(&id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .repository
+        .full_name
+        .clone();
+    authorize_execution_read(&storage, &headers, &repo)?;
+
+    let link = storage
+        .share_links_for(&id)
+        .into_iter()
+        .find(|link| link.id.to_string() == share_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if link.created_by != actor && !operator {
+        authorize_repo(&storage, &headers, &repo, RepoRole::Admin).map_err(|_| StatusCode::FORBIDDEN)?;
+    }
+    storage.revoke_share_link(&id, &share_id, chrono::Utc::now());
+    drop(storage);
+
+    info!(execution_id = %link.execution_id, share_id = %link.id, "Revoked share link");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::ShareRevoked {
+            execution_id: link.execution_id,
+            share_id: link.id,
+        },
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The execution a share token grants access to. Unknown, expired and
+/// revoked tokens all look the same.
+async fn shared_execution(state: &AppState, token: &str) -> Result<PipelineExecution, StatusCode> {
+    let storage = state.storage.read().await;
+    let link = storage
+        .find_share_link(token, chrono::Utc::now())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    storage
+        .get_execution(&link.execution_id.to_string())
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn get_shared_execution(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<PipelineExecution>, StatusCode> {
+    shared_execution(&state, &token).await.map(Json)
+}
+
+async fn get_shared_execution_logs(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<ExecutionLogs>, StatusCode> {
+    let execution = shared_execution(&state, &token).await?;
+    Ok(Json(ExecutionLogs::from_execution(&execution)))
+}
+
+async fn get_replay_bundle(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<ReplayBundle>, StatusCode> {
+    let storage = state.storage.read().await;
+    let bundle = storage.get_replay(&id).cloned().ok_or(StatusCode::NOT_FOUND)?;
+    authorize_execution_read(&storage, &headers, &bundle.git_event.repository.full_name)?;
+    Ok(Json(bundle))
+}
+
+/// ID of a stored execution the caller may see
+async fn readable_execution_id(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    id: &str,
+) -> Result<Uuid, StatusCode> {
+    let storage = state.storage.read().await;
+    let execution = storage.get_execution(id).ok_or(StatusCode::NOT_FOUND)?;
+    authorize_execution_read(&storage, headers, &execution.repository.full_name)?;
+    Ok(execution.id)
+}
+
+/// Artifact directory of a stored execution
+async fn execution_artifact_root(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    id: &str,
+) -> Result<std::path::PathBuf, StatusCode> {
+    let execution_id = readable_execution_id(state, headers, id).await?;
+    state
+        .executor
+        .artifacts_path(execution_id)
+        .filter(|path| path.is_dir())
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Artifacts of an execution that were moved to object storage, with the
+/// store and the execution's ID
+async fn stored_artifacts(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    id: &str,
+) -> Result<(Arc<dyn ObjectStore>, Uuid, Vec<ArtifactEntry>), StatusCode> {
+    let objects = state.objects.clone().ok_or(StatusCode::NOT_FOUND)?;
+    let execution_id = readable_execution_id(state, headers, id).await?;
+    let files = list_stored_artifacts(objects.as_ref(), execution_id).await.map_err(|e| {
+        warn!(error = %e, "Failed to list stored artifacts");
+        StatusCode::BAD_GATEWAY
+    })?;
+    if files.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok((objects, execution_id, files))
+}
+
+async fn list_execution_artifacts(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<ArtifactEntry>>, StatusCode> {
+    let root = match execution_artifact_root(&state, &headers, &id).await {
+        Ok(root) => root,
+        Err(StatusCode::NOT_FOUND) if state.objects.is_some() => {
+            return stored_artifacts(&state, &headers, &id).await.map(|(_, _, files)| Json(files));
+        }
+        Err(status) => return Err(status),
+    };
+    tokio::task::spawn_blocking(move || list_artifact_files(&root))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Relative links in listings and HTML reports need the trailing slash
+async fn redirect_artifact_root(Path(id): Path<String>) -> Redirect {
+    Redirect::permanent(&format!("/ui/executions/{}/artifacts/", id))
+}
+
+async fn browse_artifact_root(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, StatusCode> {
+    serve_artifact(&state, &headers, &id, "").await
+}
+
+async fn browse_artifact(
+    State(state): State<AppState>,
+    Path((id, path)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, StatusCode> {
+    serve_artifact(&state, &headers, &id, &path).await
+}
+
+/// Serve an artifact file inline, or a directory as its `index.html` or a listing
+async fn serve_artifact(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    id: &str,
+    relative: &str,
+) -> Result<Response, StatusCode> {
+    let root = match execution_artifact_root(state, headers, id).await {
+        Ok(root) => root,
+        Err(StatusCode::NOT_FOUND) if state.objects.is_some() => {
+            return serve_stored_artifact(state, headers, id, relative).await;
+        }
+        Err(status) => return Err(status),
+    };
+    let path = resolve_artifact_path(&root, relative).ok_or(StatusCode::NOT_FOUND)?;
+
+    if path.is_dir() {
+        if !relative.is_empty() && !relative.ends_with('/') {
+            let location = format!("/ui/executions/{}/artifacts/{}/", id, relative);
+            return Ok(Redirect::permanent(&location).into_response());
+        }
+        let index = path.join("index.html");
+        if !index.is_file() {
+            let listing = list_artifact_dir(&root, &path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let html = render_artifact_listing(id, relative.trim_end_matches('/'), &listing);
+            return Ok((
+                ARTIFACT_PAGE_HEADERS,
+                [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+                html,
+            )
+                .into_response());
+        }
+        return artifact_file_response(&index).await;
+    }
+    artifact_file_response(&path).await
+}
+
+/// Serve an artifact moved to object storage: a file as a redirect to a
+/// signed URL, a directory as its `index.html` or a listing
+async fn serve_stored_artifact(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    id: &str,
+    relative: &str,
+) -> Result<Response, StatusCode> {
+    let (objects, execution_id, files) = stored_artifacts(state, headers, id).await?;
+    let path = relative.trim_matches('/');
+    let is_dir = path.is_empty() || files.iter().any(|file| file.path.starts_with(&format!("{}/", path)));
+
+    let file = if files.iter().any(|file| file.path == path) {
+        path.to_string()
+    } else if is_dir {
+        if !relative.is_empty() && !relative.ends_with('/') {
+            let location = format!("/ui/executions/{}/artifacts/{}/", id, relative);
+            return Ok(Redirect::permanent(&location).into_response());
+        }
+        let index = if path.is_empty() { "index.html".to_string() } else { format!("{}/index.html", path) };
+        if !files.iter().any(|file| file.path == index) {
+            let html = render_artifact_listing(id, path, &artifact_dir_entries(&files, path));
+            return Ok((
+                ARTIFACT_PAGE_HEADERS,
+                [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+                html,
+            )
+                .into_response());
+        }
+        index
+    } else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let key = artifact_key(execution_id, &file).ok_or(StatusCode::NOT_FOUND)?;
+    let url = objects.signed_url(&key, SIGNED_URL_TTL).await.map_err(|e| {
+        warn!(error = %e, "Failed to sign artifact URL");
+        StatusCode::BAD_GATEWAY
+    })?;
+    Ok(Redirect::temporary(&url).into_response())
+}
+
+/// Headers of every artifact page. Artifacts are build output, so their type
+/// isn't sniffed and sandboxing keeps their scripts away from the API origin.
+const ARTIFACT_PAGE_HEADERS: [(header::HeaderName, &str); 2] = [
+    (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    (header::CONTENT_SECURITY_POLICY, "sandbox allow-scripts"),
+];
+
+async fn artifact_file_response(path: &std::path::Path) -> Result<Response, StatusCode> {
+    let bytes = tokio::fs::read(path).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let content_type = content_type_for(path, &bytes[..bytes.len().min(512)]);
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().replace('"', ""))
+        .unwrap_or_default();
+    let disposition = if is_inline_type(content_type) {
+        "inline".to_string()
+    } else {
+        format!("attachment; filename=\"{}\"", file_name)
+    };
+    Ok((
+        ARTIFACT_PAGE_HEADERS,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Response header with the number of executions across all pages
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Executions selected by the `label`, `status`, `branch`, `repo` and `since`
+/// query parameters. `since` is an RFC 3339 time or a `YYYY-MM-DD` date
+/// (midnight UTC).
+fn execution_query(params: &HashMap<String, String>) -> Result<ExecutionQuery, String> {
+    let status = params
+        .get("status")
+        .map(|status| PipelineStatus::parse(status).ok_or_else(|| format!("Unknown status {:?}", status)))
+        .transpose()?;
+    let since = params
+        .get("since")
+        .map(|since| {
+            chrono::DateTime::parse_from_rfc3339(since)
+                .map(|time| time.with_timezone(&chrono::Utc))
+                .or_else(|_| {
+                    chrono::NaiveDate::parse_from_str(since, "%Y-%m-%d")
+                        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+                })
+                .map_err(|_| format!("since must be an RFC 3339 time or a date, got {:?}", since))
+        })
+        .transpose()?;
+    Ok(ExecutionQuery {
+        repository: params.get("repo").cloned(),
+        label: params.get("label").cloned(),
+        status,
+        branch: params.get("branch").cloned(),
+        since,
+        limit: None,
+    })
+}
+
+/// Executions, newest first, optionally filtered (see `execution_query`).
+/// `page` (from 1) and `per_page` return one page; the total across pages is
+/// in the `X-Total-Count` header.
+async fn list_executions(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, StatusCode> {
+    let query = execution_query(&params).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let number = |name: &str| {
+        params
+            .get(name)
+            .map(|value| value.parse::<usize>().map_err(|_| StatusCode::BAD_REQUEST))
+            .transpose()
+    };
+    let (page, per_page) = (number("page")?, number("per_page")?);
+
+    let executions = visible_executions(&state, &headers, &query).await?;
+    let total = executions.len();
+    let executions = if page.is_some() || per_page.is_some() {
+        let page = page.unwrap_or(1);
+        let per_page = per_page.unwrap_or(v2::DEFAULT_PAGE_SIZE);
+        if page == 0 || per_page == 0 || per_page > v2::MAX_PAGE_SIZE {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Page::from_items(executions, (page - 1).saturating_mul(per_page), per_page).items
+    } else {
+        executions
+    };
+    Ok(([(TOTAL_COUNT_HEADER, total.to_string())], Json(executions)).into_response())
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+    repo: Option<String>,
+    limit: Option<usize>,
+}
+
+/// Executions whose step names or output hold every term of `q`, newest
+/// first, with the lines the terms were found on (see `LogSearch`). `repo`
+/// searches only one repository and `limit` returns at most that many
+/// executions, up to 100. The database's search index is used when there is
+/// one; executions still being written to it may be missing.
+async fn search_logs(
+    State(state): State<AppState>,
+    Query(params): Query<SearchParams>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<SearchResult>>, StatusCode> {
+    let mut search = LogSearch::parse(&params.q).map_err(|_| StatusCode::BAD_REQUEST)?;
+    search.limit = match params.limit {
+        Some(limit) if limit == 0 || limit > MAX_SEARCH_LIMIT => return Err(StatusCode::BAD_REQUEST),
+        Some(limit) => limit,
+        None => DEFAULT_SEARCH_LIMIT,
+    };
+    let visible = visible_repos(&*state.storage.read().await, &headers)?;
+    search.repositories = match (params.repo, visible) {
+        (Some(repo), Some(visible)) => Some(visible.contains(&repo).then_some(repo).into_iter().collect()),
+        (Some(repo), None) => Some(vec![repo]),
+        (None, Some(visible)) => Some(visible.into_iter().collect()),
+        (None, None) => None,
+    };
+    let executions = match &state.database {
+        Some(database) => database.search_executions(&search).await.map_err(|e| {
+            warn!(error = %e, "Failed to search step logs");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        None => state.storage.read().await.search_executions(&search),
+    };
+    Ok(Json(
+        executions
+            .iter()
+            .map(|execution| SearchResult::new(execution, &search))
+            .filter(|result| !result.matches.is_empty())
+            .collect(),
+    ))
+}
+
+/// Executions matching `query` that the caller may see
+async fn visible_executions(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    query: &ExecutionQuery,
+) -> Result<Vec<PipelineExecution>, StatusCode> {
+    let storage = state.storage.read().await;
+    let visible = visible_repos(&storage, headers)?;
+    let mut executions = storage.query_executions(query);
+    if let Some(repos) = visible {
+        executions.retain(|execution| repos.contains(&execution.repository.full_name));
+    }
+    Ok(executions)
+}
+
+/// A job waiting to start, as the queue shows it
+#[derive(Serialize)]
+struct PendingJobResponse {
+    execution_id: Uuid,
+    repository: String,
+    pipeline: Option<String>,
+    trigger: GitEventType,
+    branch: Option<String>,
+    sender: String,
+    priority: JobPriority,
+    /// Place in the queue, from 1
+    position: usize,
+    queued_at: chrono::DateTime<chrono::Utc>,
+    waiting_ms: u64,
+    reason: WaitReason,
+}
+
+/// A job a worker is running, as the queue shows it
+#[derive(Serialize)]
+struct RunningJobResponse {
+    execution_id: Uuid,
+    repository: String,
+    pipeline: Option<String>,
+    worker: usize,
+    /// Agent running the job, on servers with agents; unset until one takes it
+    agent: Option<String>,
+    queued_at: chrono::DateTime<chrono::Utc>,
+    started_at: chrono::DateTime<chrono::Utc>,
+    /// How long it waited in the queue
+    waited_ms: u64,
+    running_ms: u64,
+}
+
+#[derive(Serialize)]
+struct QueueResponse {
+    workers: usize,
+    busy_workers: usize,
+    /// Jobs that may wait at once before webhooks are turned away
+    max_queued_jobs: usize,
+    pending: Vec<PendingJobResponse>,
+    running: Vec<RunningJobResponse>,
+}
+
+/// Jobs waiting to start, by priority and then in queue order with why each
+/// one waits, and the jobs running on each worker
+async fn get_queue(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<QueueResponse>, StatusCode> {
+    let now = chrono::Utc::now();
+    let elapsed_ms = |from: chrono::DateTime<chrono::Utc>, to: chrono::DateTime<chrono::Utc>| {
+        (to - from).num_milliseconds().max(0) as u64
+    };
+    let agents = state.agents.as_ref().map(|agents| agents.agents()).unwrap_or_default();
+    let storage = state.storage.read().await;
+    let visible = visible_repos(&storage, &headers)?;
+    let shown = |repo: &str| visible.as_ref().is_none_or(|repos| repos.contains(repo));
+    let pipeline = |id: Uuid| {
+        storage
+            .get_execution(&id.to_string())
+            .map(|execution| execution.pipeline_name.clone())
+            .filter(|name| !name.is_empty())
+    };
+    let scheduler = state.scheduler.lock().unwrap();
+    let limit = |repo: &str| storage.run_limits(repo);
+    let pending = scheduler
+        .pending()
+        .enumerate()
+        .filter(|(_, job)| shown(&job.git_event.repository.full_name))
+        .map(|(index, job)| PendingJobResponse {
+            execution_id: job.id,
+            repository: job.git_event.repository.full_name.clone(),
+            pipeline: pipeline(job.id),
+            trigger: job.git_event.event_type,
+            branch: job.git_event.branch.clone(),
+            sender: job.git_event.sender.clone(),
+            priority: job.priority(),
+            position: index + 1,
+            queued_at: job.queued_at,
+            waiting_ms: elapsed_ms(job.queued_at, now),
+            reason: if state.shutdown.is_cancelled() {
+                WaitReason::ShuttingDown
+            } else {
+                scheduler.wait_reason(job, limit)
+            },
+        })
+        .collect();
+    let running = scheduler
+        .running()
+        .into_iter()
+        .filter(|job| shown(&job.repository))
+        .map(|job| RunningJobResponse {
+            pipeline: pipeline(job.id),
+            agent: agents
+                .iter()
+                .find(|agent| agent.execution_id == Some(job.id))
+                .map(|agent| agent.registration.name.clone()),
+            waited_ms: elapsed_ms(job.queued_at, job.started_at),
+            running_ms: elapsed_ms(job.started_at, now),
+            execution_id: job.id,
+            repository: job.repository,
+            worker: job.worker,
+            queued_at: job.queued_at,
+            started_at: job.started_at,
+        })
+        .collect();
+    Ok(Json(QueueResponse {
+        workers: scheduler.workers(),
+        busy_workers: scheduler.running_count(),
+        max_queued_jobs: state.max_queued_jobs,
+        pending,
+        running,
+    }))
+}
+
+fn create_push_event(repo: Repository, payload: &GitHubWebhookPayload) -> GitEvent {
+    let branch = payload
+        .ref_field
+        .as_ref()
+        .and_then(|r| r.strip_prefix("refs/heads/").map(String::from));
+
+    GitEvent {
+        event_type: GitEventType::Push,
+        repository: repo,
+        branch,
+        tag: None,
+        release_name: None,
+        pull_request: None,
+        commit_sha: payload
+            .head_commit
+            .as_ref()
+            .and_then(|h| h.get("id"))
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        commit_message: payload
+            .head_commit
+            .as_ref()
+            .and_then(|h| h.get("message"))
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        sender: payload
+            .sender
+            .as_ref()
+            .map(|s| s.login.clone())
+            .unwrap_or_default(),
+        inputs: Default::default(),
+    }
+}
+
+/// Only actions that change the code build: `opened`, `synchronize` and
+/// `reopened`, and `closed` when the pull request was merged. Others, such
+/// as `labeled` or closing without merging, don't.
+fn create_pull_request_event(repo: Repository, payload: &GitHubWebhookPayload) -> Option<GitEvent> {
+    let pr = payload.pull_request.as_ref().and_then(|pr| {
+        let number = pr.get("number")?.as_u64()?;
+        let title = pr.get("title")?.as_str()?.to_string();
+        let base = pr.get("base")?;
+        let head = pr.get("head")?;
+        let base_branch = base.get("ref")?.as_str()?.to_string();
+        let head_branch = head.get("ref")?.as_str()?.to_string();
+        // Null when the fork it came from was deleted
+        let head_repository = head
+            .get("repo")
+            .and_then(|repo| repo.get("full_name")?.as_str())
+            .map(String::from);
+        let state = pr.get("state")?.as_str()?.to_string();
+        let draft = pr.get("draft").and_then(|d| d.as_bool()).unwrap_or(false);
+        let labels = pr
+            .get("labels")
+            .and_then(|l| l.as_array())
+            .map(|labels| {
+                labels
+                    .iter()
+                    .filter_map(|l| l.get("name")?.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(pulsiora_core::PullRequest {
+            number,
+            title,
+            base_branch,
+            head_branch,
+            state,
+            draft,
+            labels,
+            head_repository,
+        })
+    });
+
+    // A merged PR arrives as "closed" with merged=true; it lands on the base branch
+    let merged = payload
+        .pull_request
+        .as_ref()
+        .and_then(|pr| pr.get("merged"))
+        .and_then(|m| m.as_bool())
+        .unwrap_or(false);
+    let merged = match payload.action.as_deref() {
+        Some("opened" | "synchronize" | "reopened") => false,
+        Some("closed") if merged => true,
+        _ => return None,
+    };
+
+    let (event_type, branch, commit_sha) = if merged {
+        let merge_commit = payload
+            .pull_request
+            .as_ref()
+            .and_then(|pr| pr.get("merge_commit_sha"))
+            .and_then(|v| v.as_str())
+            .map(String::from);
+        (
+            GitEventType::Merge,
+            pr.as_ref().map(|pr| pr.base_branch.clone()),
+            merge_commit,
+        )
+    } else {
+        // Check runs and statuses are reported on the head commit
+        let head_sha = payload
+            .pull_request
+            .as_ref()
+            .and_then(|pr| pr.get("head")?.get("sha")?.as_str())
+            .map(String::from);
+        (GitEventType::PullRequest, None, head_sha)
+    };
+
+    Some(GitEvent {
+        event_type,
+        repository: repo,
+        branch,
+        tag: None,
+        release_name: None,
+        pull_request: pr,
+        commit_sha,
+        commit_message: None,
+        sender: payload
+            .sender
+            .as_ref()
+            .map(|s| s.login.clone())
+            .unwrap_or_default(),
+        inputs: Default::default(),
+    })
+}
+
+fn create_create_event(repo: Repository, payload: &GitHubWebhookPayload) -> GitEvent {
+    let ref_str = payload.ref_field.as_deref().unwrap_or("");
+    let is_tag = ref_str.starts_with("refs/tags/");
+    let branch = if !is_tag {
+        ref_str.strip_prefix("refs/heads/").map(String::from)
+    } else {
+        None
+    };
+    let tag = if is_tag {
+        ref_str.strip_prefix("refs/tags/").map(String::from)
     } else {
         None
     };
-    let tag = if is_tag {
-        ref_str.strip_prefix("refs/tags/").map(String::from)
-    } else {
-        None
+
+    let event_type = if is_tag {
+        GitEventType::Tag
+    } else {
+        GitEventType::BranchCreate
+    };
+
+    GitEvent {
+        event_type,
+        repository: repo,
+        branch,
+        tag,
+        release_name: None,
+        pull_request: None,
+        commit_sha: None,
+        commit_message: None,
+        sender: payload
+            .sender
+            .as_ref()
+            .map(|s| s.login.clone())
+            .unwrap_or_default(),
+        inputs: Default::default(),
+    }
+}
+
+fn create_delete_event(repo: Repository, payload: &GitHubWebhookPayload) -> GitEvent {
+    let branch = payload
+        .ref_field
+        .as_ref()
+        .and_then(|r| r.strip_prefix("refs/heads/").map(String::from));
+
+    GitEvent {
+        event_type: GitEventType::BranchDelete,
+        repository: repo,
+        branch,
+        tag: None,
+        release_name: None,
+        pull_request: None,
+        commit_sha: None,
+        commit_message: None,
+        sender: payload
+            .sender
+            .as_ref()
+            .map(|s| s.login.clone())
+            .unwrap_or_default(),
+        inputs: Default::default(),
+    }
+}
+
+/// GitHub sends `created` and then `published` for a release published right
+/// away, so `created` is only used for drafts to avoid running twice.
+fn create_release_event(repo: Repository, payload: &GitHubWebhookPayload) -> Option<GitEvent> {
+    let release = payload.release.as_ref()?;
+    let draft = release.get("draft").and_then(|d| d.as_bool()).unwrap_or(false);
+    match payload.action.as_deref() {
+        Some("published") => {}
+        Some("created") if draft => {}
+        _ => return None,
+    }
+
+    Some(GitEvent {
+        event_type: GitEventType::Release,
+        repository: repo,
+        branch: None,
+        tag: release
+            .get("tag_name")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        release_name: release
+            .get("name")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        pull_request: None,
+        commit_sha: None,
+        commit_message: None,
+        sender: payload
+            .sender
+            .as_ref()
+            .map(|s| s.login.clone())
+            .unwrap_or_default(),
+        inputs: Default::default(),
+    })
+}
+
+#[derive(Deserialize)]
+struct RegisterRepoRequest {
+    repo_url: String,
+    repo_identifier: String,
+    pulsefile: String,
+    repo_type: Option<String>, // "github", "local", or other SCM type
+    /// Organization whose base pipeline applies; defaults to the repo owner
+    organization: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RegisterRepoResponse {
+    message: String,
+    repo_identifier: String,
+    /// Syntax the parser tolerated but that should be normalized
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// Organization a repository is registered into. On a server hosting
+/// several organizations, users register repositories into their own one,
+/// and a repository whose owner is named like an organization can only go
+/// into that one; operators may register repositories anywhere.
+fn registration_organization(
+    storage: &InMemoryStorage,
+    headers: &axum::http::HeaderMap,
+    requested: Option<&str>,
+    repo_identifier: &str,
+) -> Result<Option<String>, StatusCode> {
+    if requested.is_some_and(|org| storage.get_organization(org).is_none()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !storage.is_multi_tenant() {
+        return Ok(requested.map(String::from));
+    }
+    let user = request_user(storage, headers)?;
+    if storage.is_operator(user) {
+        return Ok(requested.map(String::from));
+    }
+    let organization = requested.unwrap_or(&user.organization);
+    let owner = repo_identifier.split('/').next().filter(|owner| storage.get_organization(owner).is_some());
+    if organization != user.organization || owner.is_some_and(|owner| owner != organization) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(Some(organization.to_string()))
+}
+
+async fn register_repo(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<RegisterRepoRequest>,
+) -> Result<Json<RegisterRepoResponse>, StatusCode> {
+    // Validate Pulsefile by parsing it
+    let warnings = match pulsiora_parser::parse_pulsefile_with_warnings(&req.pulsefile) {
+        Ok((_, warnings)) => warnings.iter().map(|w| w.to_string()).collect(),
+        Err(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let organization = {
+        let storage = state.storage.read().await;
+        registration_organization(&storage, &headers, req.organization.as_deref(), &req.repo_identifier)?
+    };
+
+    let repo_type = match req.repo_type.as_deref() {
+        Some("local") => storage::RepoType::Local,
+        Some(other) => storage::RepoType::Other(other.to_string()),
+        None => storage::RepoType::GitHub, // Default to GitHub
+    };
+
+    let mut repo = storage::RegisteredRepo {
+        repo_url: req.repo_url.clone(),
+        repo_identifier: req.repo_identifier.clone(),
+        pulsefile: req.pulsefile,
+        pulsefile_revision: 1,
+        pulsefile_history: Vec::new(),
+        repo_type,
+        status_contexts: Vec::new(),
+        limits: ExecutionLimits::default(),
+        sandbox: SandboxPolicy::default(),
+        webhook_mapping: Default::default(),
+        organization: organization.clone(),
+        roles: Default::default(),
+        env: Default::default(),
+        hooks: Default::default(),
+        pulsefile_source: Default::default(),
+        path_pipelines: Vec::new(),
+        deployments: Vec::new(),
+        settings: Default::default(),
+    };
+
+    let actor = {
+        let mut storage = state.storage.write().await;
+        let user = bearer_token(&headers).and_then(|token| storage.find_user_by_token(token)).cloned();
+        // Registering a repository again replaces it, which only its admins
+        // may do; roles granted on it, its variables, webhooks, path-scoped
+        // pipelines, deployments and settings are kept, and a changed
+        // Pulsefile becomes its next revision
+        if let Some(existing) = storage.get_registered_repo(&req.repo_identifier) {
+            authorize_repo(&storage, &headers, &req.repo_identifier, RepoRole::Admin)?;
+            repo.roles = existing.roles;
+            repo.env = existing.env;
+            repo.hooks = existing.hooks;
+            repo.pulsefile_source = existing.pulsefile_source;
+            repo.path_pipelines = existing.path_pipelines;
+            repo.deployments = existing.deployments;
+            repo.settings = existing.settings;
+            let pulsefile = std::mem::replace(&mut repo.pulsefile, existing.pulsefile);
+            repo.pulsefile_revision = existing.pulsefile_revision;
+            repo.pulsefile_history = existing.pulsefile_history;
+            repo.replace_pulsefile(pulsefile, user.as_ref().map(|user| user.username.clone()));
+        }
+        storage.register_repo(repo);
+        // Whoever registers a repository may manage it
+        if let Some(user) = &user {
+            if storage.repo_role(user, &req.repo_identifier) < Some(RepoRole::Admin) {
+                storage.set_repo_role(&req.repo_identifier, &user.username, Some(RepoRole::Admin));
+            }
+        }
+        user.map(|user| user.username)
+    };
+
+    info!("Registered repository: {}", req.repo_identifier);
+    state.audit.record(
+        actor.as_deref(),
+        AuditEvent::RepoRegistered {
+            repository: req.repo_identifier.clone(),
+            organization,
+        },
+    );
+
+    Ok(Json(RegisterRepoResponse {
+        message: "Repository registered successfully".to_string(),
+        repo_identifier: req.repo_identifier,
+        warnings,
+    }))
+}
+
+/// A registered repository as listed by the API
+#[derive(Serialize)]
+struct RepoSummary {
+    repo_identifier: String,
+    repo_url: String,
+    repo_type: storage::RepoType,
+    organization: Option<String>,
+    /// SHA-256 of the registered Pulsefile, in hex
+    pulsefile_sha256: String,
+    pulsefile_revision: u32,
+    last_execution: Option<LastExecution>,
+}
+
+#[derive(Serialize)]
+struct LastExecution {
+    id: Uuid,
+    pipeline: String,
+    status: PipelineStatus,
+    started_at: chrono::DateTime<chrono::Utc>,
+    completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl RepoSummary {
+    fn new(storage: &InMemoryStorage, repo: &storage::RegisteredRepo) -> Self {
+        let last_execution = storage
+            .get_executions_by_repo(&repo.repo_identifier, 1)
+            .into_iter()
+            .next()
+            .map(|execution| LastExecution {
+                id: execution.id,
+                pipeline: execution.pipeline_name,
+                status: execution.status,
+                started_at: execution.started_at,
+                completed_at: execution.completed_at,
+            });
+        Self {
+            repo_identifier: repo.repo_identifier.clone(),
+            repo_url: repo.repo_url.clone(),
+            repo_type: repo.repo_type.clone(),
+            organization: repo.organization.clone(),
+            pulsefile_sha256: repo.pulsefile_hash(),
+            pulsefile_revision: repo.pulsefile_revision,
+            last_execution,
+        }
+    }
+}
+
+/// A registered repository with its settings, for `GET /api/v1/repos/:repo`
+#[derive(Serialize)]
+struct RepoDetails {
+    #[serde(flatten)]
+    summary: RepoSummary,
+    pulsefile: String,
+    status_contexts: Vec<StatusContext>,
+    limits: ExecutionLimits,
+    sandbox: SandboxPolicy,
+    env: std::collections::BTreeMap<String, String>,
+    pulsefile_source: PulsefileSource,
+}
+
+/// Repositories the calling user may see, by identifier
+async fn list_repos(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<RepoSummary>>, StatusCode> {
+    let storage = state.storage.read().await;
+    let user = request_user(&storage, &headers)?;
+    let repos = storage
+        .registered_repos()
+        .into_iter()
+        .filter(|repo| storage.repo_role(user, &repo.repo_identifier) >= Some(RepoRole::Viewer))
+        .map(|repo| RepoSummary::new(&storage, repo))
+        .collect();
+    Ok(Json(repos))
+}
+
+async fn get_repo(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<RepoDetails>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    let registered = storage.get_registered_repo(&repo).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(RepoDetails {
+        summary: RepoSummary::new(&storage, &registered),
+        pulsefile: registered.pulsefile,
+        status_contexts: registered.status_contexts,
+        limits: registered.limits,
+        sandbox: registered.sandbox,
+        env: registered.env,
+        pulsefile_source: registered.pulsefile_source,
+    }))
+}
+
+async fn unregister_repo(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    storage.unregister_repo(&repo);
+    info!("Unregistered repository: {}", repo);
+    state.audit.record(Some(&actor), AuditEvent::RepoUnregistered { repository: repo });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct UpdatePulsefileRequest {
+    pulsefile: String,
+}
+
+#[derive(Serialize)]
+struct UpdatePulsefileResponse {
+    repo_identifier: String,
+    /// Revision of the stored Pulsefile now; unchanged if it was the same
+    pulsefile_revision: u32,
+    pulsefile_sha256: String,
+    /// Syntax the parser tolerated but that should be normalized
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// Replace a registered repository's Pulsefile. The one it replaces is kept
+/// as a previous revision; executions record the revision they ran.
+async fn update_repo_pulsefile(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<UpdatePulsefileRequest>,
+) -> Result<Json<UpdatePulsefileResponse>, StatusCode> {
+    let warnings = match pulsiora_parser::parse_pulsefile_with_warnings(&req.pulsefile) {
+        Ok((_, warnings)) => warnings.iter().map(|w| w.to_string()).collect(),
+        Err(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    let previous = storage.get_repo_pulsefile(&repo).map(|(_, revision)| revision);
+    let revision = storage
+        .update_repo_pulsefile(&repo, req.pulsefile, Some(&actor))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let pulsefile_sha256 = storage
+        .get_registered_repo(&repo)
+        .map(|registered| registered.pulsefile_hash())
+        .unwrap_or_default();
+    drop(storage);
+
+    if previous != Some(revision) {
+        info!(revision, "Updated Pulsefile of {}", repo);
+        state.audit.record(
+            Some(&actor),
+            AuditEvent::RepoPulsefileUpdated {
+                repository: repo.clone(),
+                revision,
+                pulsefile_sha256: pulsefile_sha256.clone(),
+            },
+        );
+    }
+    Ok(Json(UpdatePulsefileResponse {
+        repo_identifier: repo,
+        pulsefile_revision: revision,
+        pulsefile_sha256,
+        warnings,
+    }))
+}
+
+/// A revision of a repository's stored Pulsefile
+#[derive(Serialize)]
+struct PulsefileRevisionResponse {
+    revision: u32,
+    pulsefile_sha256: String,
+    /// Whether it's the Pulsefile the repository runs now
+    current: bool,
+    replaced_at: Option<chrono::DateTime<chrono::Utc>>,
+    replaced_by: Option<String>,
+    pulsefile: String,
+}
+
+/// Every revision of a repository's stored Pulsefile that is kept, newest
+/// first; its viewers may see them
+async fn list_pulsefile_revisions(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<PulsefileRevisionResponse>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    let registered = storage.get_registered_repo(&repo).ok_or(StatusCode::NOT_FOUND)?;
+    let mut revisions = vec![PulsefileRevisionResponse {
+        revision: registered.pulsefile_revision,
+        pulsefile_sha256: registered.pulsefile_hash(),
+        current: true,
+        replaced_at: None,
+        replaced_by: None,
+        pulsefile: registered.pulsefile,
+    }];
+    revisions.extend(registered.pulsefile_history.into_iter().rev().map(|previous| PulsefileRevisionResponse {
+        revision: previous.revision,
+        pulsefile_sha256: previous.pulsefile_hash(),
+        current: false,
+        replaced_at: Some(previous.replaced_at),
+        replaced_by: previous.replaced_by,
+        pulsefile: previous.pulsefile,
+    }));
+    Ok(Json(revisions))
+}
+
+#[derive(Serialize, Deserialize)]
+struct PulsefileSourceBody {
+    source: PulsefileSource,
+}
+
+/// Which Pulsefile a repository's webhook events run; its viewers may see it
+async fn get_pulsefile_source(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<PulsefileSourceBody>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    let registered = storage.get_registered_repo(&repo).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(PulsefileSourceBody {
+        source: registered.pulsefile_source,
+    }))
+}
+
+/// Choose whether a repository's webhook events run its stored Pulsefile or
+/// the one at their commit
+async fn update_pulsefile_source(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(body): Json<PulsefileSourceBody>,
+) -> Result<Json<PulsefileSourceBody>, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    storage.set_repo_pulsefile_source(&repo, body.source);
+    drop(storage);
+
+    info!(source = ?body.source, "Updated Pulsefile source for {}", repo);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoPulsefileSourceUpdated {
+            repository: repo,
+            source: body.source,
+        },
+    );
+    Ok(Json(body))
+}
+
+/// A repository's path-scoped pipeline as listed
+#[derive(Serialize)]
+struct PathPipelineResponse {
+    #[serde(flatten)]
+    pipeline: PathPipeline,
+    pulsefile_sha256: String,
+}
+
+impl From<PathPipeline> for PathPipelineResponse {
+    fn from(pipeline: PathPipeline) -> Self {
+        let pulsefile_sha256 = pipeline.pulsefile_hash();
+        Self {
+            pipeline,
+            pulsefile_sha256,
+        }
+    }
+}
+
+/// A repository's path-scoped pipelines; its viewers may see them
+async fn list_path_pipelines(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<PathPipelineResponse>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    let registered = storage.get_registered_repo(&repo).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(registered.path_pipelines.into_iter().map(Into::into).collect()))
+}
+
+#[derive(Deserialize)]
+struct SetPathPipelineRequest {
+    paths: Vec<String>,
+    pulsefile: String,
+}
+
+/// Add a path-scoped pipeline to a repository, or replace the one with the
+/// same name
+async fn set_path_pipeline(
+    State(state): State<AppState>,
+    Path((repo, name)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<SetPathPipelineRequest>,
+) -> Result<Json<PathPipelineResponse>, StatusCode> {
+    if !valid_pipeline_name(&name) || req.paths.iter().all(|path| path.trim_matches('/').is_empty()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if pulsiora_parser::parse_pulsefile(&req.pulsefile).is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let pipeline = PathPipeline {
+        name,
+        paths: req.paths,
+        pulsefile: req.pulsefile,
+    };
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !storage.set_path_pipeline(&repo, pipeline.clone()) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    drop(storage);
+
+    let response = PathPipelineResponse::from(pipeline);
+    info!(repository = %repo, pipeline = %response.pipeline.name, "Set path-scoped pipeline");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoPathPipelineSet {
+            repository: repo,
+            name: response.pipeline.name.clone(),
+            paths: response.pipeline.paths.clone(),
+            pulsefile_sha256: response.pulsefile_sha256.clone(),
+        },
+    );
+    Ok(Json(response))
+}
+
+async fn remove_path_pipeline(
+    State(state): State<AppState>,
+    Path((repo, name)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !storage.remove_path_pipeline(&repo, &name) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    drop(storage);
+
+    info!(repository = %repo, pipeline = %name, "Removed path-scoped pipeline");
+    state.audit.record(Some(&actor), AuditEvent::RepoPathPipelineRemoved { repository: repo, name });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Roles granted on a repository, by username; its viewers may see them
+async fn get_repo_roles(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<std::collections::BTreeMap<String, RepoRole>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    let roles = storage.get_registered_repo(&repo).map(|r| r.roles).unwrap_or_default();
+    Ok(Json(roles))
+}
+
+/// Variables set for every step of a repository; its viewers may see them
+async fn get_repo_env(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<std::collections::BTreeMap<String, String>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    Ok(Json(storage.get_repo_env(&repo).unwrap_or_default()))
+}
+
+/// Replace the variables set for every step of a repository
+async fn update_repo_env(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(env): Json<std::collections::BTreeMap<String, String>>,
+) -> Result<Json<std::collections::BTreeMap<String, String>>, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !env.keys().all(|name| valid_env_name(name)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    storage.set_repo_env(&repo, env.clone());
+    drop(storage);
+
+    info!("Updated environment variables for {}", repo);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoEnvUpdated {
+            repository: repo,
+            env: env.clone(),
+        },
+    );
+    Ok(Json(env))
+}
+
+#[derive(Deserialize)]
+struct AddHookRequest {
+    url: String,
+    /// Key payloads are signed with [default: a random one]
+    secret: Option<String>,
+    /// Events to send [default: all of them]
+    #[serde(default)]
+    events: Vec<WebhookEvent>,
+}
+
+/// An outgoing webhook as the API shows it; the secret is only shown when
+/// the webhook is added
+#[derive(Serialize)]
+struct HookResponse {
+    id: Uuid,
+    url: String,
+    events: Vec<WebhookEvent>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl HookResponse {
+    fn new(hook: OutgoingWebhook, show_secret: bool) -> Self {
+        Self {
+            id: hook.id,
+            url: hook.url,
+            events: hook.events,
+            created_at: hook.created_at,
+            secret: show_secret.then_some(hook.secret),
+        }
+    }
+}
+
+async fn list_repo_hooks(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<HookResponse>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    let hooks = storage.repo_hooks(&repo).into_iter().map(|hook| HookResponse::new(hook, false));
+    Ok(Json(hooks.collect()))
+}
+
+#[derive(Default, Deserialize)]
+struct WebhookTestRequest {
+    /// The secret set on the webhook, to compare with the server's
+    secret: Option<String>,
+}
+
+/// Check a repository's webhook setup, for repository admins: the secret in
+/// the body against the server's, a signed ping sent to the server through
+/// `public_url`, and for GitHub repositories the webhook as GitHub has it.
+/// The report is failing if any check is.
+async fn test_repo_webhook(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    request: Option<Json<WebhookTestRequest>>,
+) -> Result<Json<HealthReport>, StatusCode> {
+    let (registered, public_url) = {
+        let storage = state.storage.read().await;
+        authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+        let registered = storage.get_registered_repo(&repo).ok_or(StatusCode::NOT_FOUND)?;
+        (registered, storage.settings().public_url.clone())
+    };
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let secret = registered.settings.webhook_secret.as_deref().or(state.webhook_secret.as_deref());
+    let mut checks = vec![secret_check(secret, request.secret.as_deref())];
+    let Some(public_url) = public_url else {
+        checks.push(HealthCheck::failing(
+            "public_url",
+            "Set public_url to the address webhooks reach the server at",
+        ));
+        return Ok(Json(HealthReport::new(checks)));
+    };
+    let url = format!("{}{}", public_url.trim_end_matches('/'), GITHUB_WEBHOOK_PATH);
+    checks.push(delivery_check(&url, send_test_ping(&registered, secret, &url).await));
+    if registered.repo_type == RepoType::GitHub {
+        checks.push(match github_token(&state, &repo).await {
+            Some(token) => match fetch_repo_webhooks(&token, &repo).await {
+                Ok(hooks) => github_hook_check(&hooks, &url),
+                Err(e) => HealthCheck::failing("github", e.to_string()),
+            },
+            None => HealthCheck::ok("github", "Set github_token or a GitHub App to also check the webhook on GitHub"),
+        });
+    }
+    let report = HealthReport::new(checks);
+    info!(repo = %repo, ok = report.is_ok(), "Tested repository webhook");
+    Ok(Json(report))
+}
+
+/// Send a `ping` for `repo` to `url`, signed with `secret`; returns the
+/// status it was answered with
+async fn send_test_ping(repo: &RegisteredRepo, secret: Option<&str>, url: &str) -> Result<u16, String> {
+    let body = ping_payload(repo).to_string();
+    let mut request = reqwest::Client::new()
+        .post(url)
+        .timeout(WEBHOOK_TEST_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("X-GitHub-Event", "ping")
+        .header("X-GitHub-Delivery", format!("webhook-test-{}", Uuid::new_v4()));
+    if let Some(secret) = secret {
+        request = request.header(SIGNATURE_HEADER, sign_payload(secret, body.as_bytes()));
+    }
+    match request.body(body).send().await {
+        Ok(response) => Ok(response.status().as_u16()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A repository's settings, for its viewers; its webhook secret only shows
+/// as whether it's set
+async fn get_repo_settings(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<RepoSettingsView>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    let registered = storage.get_registered_repo(&repo).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(RepoSettingsView::new(&registered)))
+}
+
+/// Change some of a repository's settings, for its admins
+async fn update_repo_settings(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(patch): Json<RepoSettingsPatch>,
+) -> Result<Json<RepoSettingsView>, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    let updated = storage
+        .update_repo_settings(&repo, patch)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .map_err(|e| {
+            info!(repository = %repo, error = %e, "Rejected repository settings");
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+    drop(storage);
+
+    info!(repository = %repo, "Updated repository settings");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoSettingsUpdated {
+            repository: repo,
+            webhook_secret_set: updated.settings.webhook_secret.is_some(),
+            default_branch: updated.settings.default_branch.clone(),
+            allowed_events: updated.settings.allowed_events.clone(),
+            execution_timeout_secs: updated.limits.max_runtime_secs,
+        },
+    );
+    Ok(Json(RepoSettingsView::new(&updated)))
+}
+
+/// Environments a repository deployed to, with their current and previous
+/// deployments
+async fn list_environments(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<EnvironmentSummary>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    if !storage.is_repo_registered(&repo) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(environments(&storage.repo_deployments(&repo))))
+}
+
+/// An environment's deployments, newest first
+async fn list_environment_deployments(
+    State(state): State<AppState>,
+    Path((repo, environment)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<Deployment>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    let deployments = storage.repo_deployments(&repo);
+    let history: Vec<Deployment> = environment_deployments(&deployments, &environment).into_iter().cloned().collect();
+    if history.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(history))
+}
+
+#[derive(Default, Deserialize)]
+struct RollbackRequest {
+    /// Deployment to go back to; the previous one when unset
+    to: Option<Uuid>,
+}
+
+/// Mark an environment as rolled back to an earlier deployment, for
+/// developers of the repository. This only records the rollback; it doesn't
+/// run anything.
+async fn rollback_environment(
+    State(state): State<AppState>,
+    Path((repo, environment)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+    request: Option<Json<RollbackRequest>>,
+) -> Result<Json<Deployment>, StatusCode> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let (actor, rollback) = {
+        let mut storage = state.storage.write().await;
+        let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Developer)?;
+        let rollback = storage
+            .mark_rollback(&repo, &environment, request.to, &actor)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .map_err(|e| match e {
+                RollbackError::UnknownEnvironment | RollbackError::UnknownDeployment => StatusCode::NOT_FOUND,
+                RollbackError::NothingToRollBack => StatusCode::CONFLICT,
+            })?;
+        (actor, rollback)
+    };
+
+    info!(repository = %repo, environment = %environment, deployment_id = %rollback.id, "Marked rollback");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::DeploymentRolledBack {
+            repository: repo.clone(),
+            environment,
+            deployment_id: rollback.id,
+            execution_id: rollback.execution_id,
+        },
+    );
+    send_deployment_webhooks(&state, &repo, &rollback).await;
+    Ok(Json(rollback))
+}
+
+/// Send a repository's execution events to a URL from now on
+async fn add_repo_hook(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<AddHookRequest>,
+) -> Result<(StatusCode, Json<HookResponse>), StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    let hook = OutgoingWebhook::new(req.url, req.secret, req.events).map_err(|e| {
+        info!(error = %e, "Rejected webhook for {}", repo);
+        StatusCode::BAD_REQUEST
+    })?;
+    storage.add_repo_hook(&repo, hook.clone());
+    drop(storage);
+
+    info!(repository = %repo, url = %hook.url, "Added outgoing webhook");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoHookAdded {
+            repository: repo,
+            hook_id: hook.id,
+            url: hook.url.clone(),
+        },
+    );
+    Ok((StatusCode::CREATED, Json(HookResponse::new(hook, true))))
+}
+
+async fn remove_repo_hook(
+    State(state): State<AppState>,
+    Path((repo, id)): Path<(String, Uuid)>,
+    headers: axum::http::HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !storage.remove_repo_hook(&repo, id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    drop(storage);
+
+    info!(repository = %repo, hook_id = %id, "Removed outgoing webhook");
+    state.audit.record(Some(&actor), AuditEvent::RepoHookRemoved { repository: repo, hook_id: id });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct SetSecretRequest {
+    name: String,
+    value: String,
+}
+
+/// Names of a repository's secrets; values are never returned
+async fn list_repo_secrets(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    Ok(Json(storage.repo_secrets(&repo).into_iter().map(|(name, _)| name).collect()))
+}
+
+/// Store a repository secret, encrypted, replacing one with the same name
+async fn set_repo_secret(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<SetSecretRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    let key = state.secrets_key.as_ref().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    if !valid_env_name(&req.name) || req.value.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let sealed = key.encrypt(&repo, &req.name, &req.value).map_err(|e| {
+        warn!(repository = %repo, error = %e, "Failed to encrypt secret");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    storage.set_secret(&repo, &req.name, sealed);
+    drop(storage);
+
+    info!(repository = %repo, name = %req.name, "Set repository secret");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::SecretSet {
+            repository: repo,
+            name: req.name,
+        },
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_repo_secret(
+    State(state): State<AppState>,
+    Path((repo, name)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !storage.remove_secret(&repo, &name) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    drop(storage);
+
+    info!(repository = %repo, name = %name, "Removed repository secret");
+    state.audit.record(Some(&actor), AuditEvent::SecretRemoved { repository: repo, name });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Secrets for the steps of a job. Pull requests from forks get none: their
+/// steps, and possibly their Pulsefile, come from whoever opened them, and
+/// masking output doesn't keep a step from sending a secret elsewhere.
+fn job_secrets(state: &AppState, storage: &InMemoryStorage, event: &GitEvent) -> std::collections::BTreeMap<String, String> {
+    let repo = &event.repository.full_name;
+    if event.from_fork() {
+        if !storage.repo_secrets(repo).is_empty() {
+            info!(repository = %repo, "Not passing secrets to a pull request from a fork");
+        }
+        return Default::default();
+    }
+    decrypt_repo_secrets(state, storage, repo)
+}
+
+/// A repository's secrets, decrypted for its steps. Secrets that can't be
+/// decrypted, e.g. after the key changed, are left out.
+fn decrypt_repo_secrets(state: &AppState, storage: &InMemoryStorage, repo: &str) -> std::collections::BTreeMap<String, String> {
+    let sealed = storage.repo_secrets(repo);
+    let Some(key) = &state.secrets_key else {
+        if !sealed.is_empty() {
+            warn!(repository = %repo, "No secrets key is set; the repository's secrets aren't passed to its steps");
+        }
+        return Default::default();
+    };
+    sealed
+        .into_iter()
+        .filter_map(|(name, value)| match key.decrypt(repo, &name, &value) {
+            Ok(value) => Some((name, value)),
+            Err(e) => {
+                warn!(repository = %repo, error = %e, "Leaving out secret");
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct GrantRoleRequest {
+    role: RepoRole,
+}
+
+/// Grant a user a role on a repository, replacing the one they had
+async fn grant_repo_role(
+    State(state): State<AppState>,
+    Path((repo, username)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<GrantRoleRequest>,
+) -> Result<StatusCode, StatusCode> {
+    change_repo_role(&state, &repo, &username, &headers, Some(req.role)).await
+}
+
+async fn revoke_repo_role(
+    State(state): State<AppState>,
+    Path((repo, username)): Path<(String, String)>,
+    headers: axum::http::HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    change_repo_role(&state, &repo, &username, &headers, None).await
+}
+
+/// Set or remove a user's role on a repository; only its admins may
+async fn change_repo_role(
+    state: &AppState,
+    repo: &str,
+    username: &str,
+    headers: &axum::http::HeaderMap,
+    role: Option<RepoRole>,
+) -> Result<StatusCode, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, headers, repo, RepoRole::Admin)?;
+    match role {
+        Some(_) if storage.get_user(username).is_none() => return Err(StatusCode::UNPROCESSABLE_ENTITY),
+        None if !storage.get_registered_repo(repo).is_some_and(|r| r.roles.contains_key(username)) => {
+            return Err(StatusCode::NOT_FOUND)
+        }
+        _ => {}
+    }
+    storage.set_repo_role(repo, username, role);
+    drop(storage);
+
+    info!(repository = %repo, username = %username, role = ?role, "Changed repository role");
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoRoleChanged {
+            repository: repo.to_string(),
+            username: username.to_string(),
+            role,
+        },
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+
+async fn get_pipeline_status(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<PipelineExecution>>, StatusCode> {
+    let limit = params
+        .get("limit")
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(10);
+
+    let storage = state.storage.read().await;
+    let executions = storage.get_executions_by_repo(&repo, limit);
+
+    if executions.is_empty() && !storage.is_repo_registered(&repo) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    authorize_execution_read(&storage, &headers, &repo)?;
+
+    Ok(Json(executions))
+}
+
+/// Days of history returned by the activity endpoint when `days` isn't given
+const DEFAULT_ACTIVITY_DAYS: u64 = 365;
+
+/// Execution counts per day, week or month for heat maps
+async fn get_pipeline_activity(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<ActivityBucket>>, StatusCode> {
+    let granularity = match params.get("granularity") {
+        Some(value) => value.parse().map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => ActivityGranularity::Day,
+    };
+    let days = match params.get("days") {
+        Some(value) => value.parse::<u64>().map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => DEFAULT_ACTIVITY_DAYS,
     };
+    let since = chrono::Utc::now().date_naive() - chrono::Days::new(days.saturating_sub(1));
 
-    let event_type = if is_tag {
-        GitEventType::Tag
-    } else {
-        GitEventType::BranchCreate
+    let storage = state.storage.read().await;
+    let executions = storage.get_executions_by_repo(&repo, usize::MAX);
+    if executions.is_empty() && !storage.is_repo_registered(&repo) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    authorize_execution_read(&storage, &headers, &repo)?;
+
+    Ok(Json(activity_series(&executions, granularity, since)))
+}
+
+/// Days of history the stats endpoint covers when `days` isn't given
+const DEFAULT_STATS_DAYS: u64 = 30;
+
+/// Failure rates and durations of a repository's steps over recent days
+async fn get_repo_stats(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<RepoStats>, StatusCode> {
+    let days = match params.get("days") {
+        Some(value) => value.parse::<u64>().map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => DEFAULT_STATS_DAYS,
     };
+    let since = chrono::Utc::now().date_naive() - chrono::Days::new(days.saturating_sub(1));
 
-    GitEvent {
-        event_type,
-        repository: repo,
-        branch,
-        tag,
-        pull_request: None,
-        commit_sha: None,
-        sender: payload
-            .sender
-            .as_ref()
-            .map(|s| s.login.clone())
-            .unwrap_or_default(),
+    let storage = state.storage.read().await;
+    let executions = storage.get_executions_by_repo(&repo, usize::MAX);
+    if executions.is_empty() && !storage.is_repo_registered(&repo) {
+        return Err(StatusCode::NOT_FOUND);
     }
+    authorize_execution_read(&storage, &headers, &repo)?;
+
+    Ok(Json(repo_stats(&repo, &executions, since, params.get("pipeline").map(String::as_str))))
 }
 
-fn create_delete_event(repo: Repository, payload: &GitHubWebhookPayload) -> GitEvent {
-    let branch = payload
-        .ref_field
-        .as_ref()
-        .and_then(|r| r.strip_prefix("refs/heads/").map(String::from));
+async fn get_status_contexts(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<StatusContext>>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    storage
+        .get_status_contexts(&repo)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
 
-    GitEvent {
-        event_type: GitEventType::BranchDelete,
-        repository: repo,
-        branch,
-        tag: None,
-        pull_request: None,
-        commit_sha: None,
-        sender: payload
-            .sender
-            .as_ref()
-            .map(|s| s.login.clone())
-            .unwrap_or_default(),
-    }
+async fn get_repo_limits(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<ExecutionLimits>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    storage
+        .get_repo_limits(&repo)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
 }
 
-#[derive(Deserialize)]
-struct RegisterRepoRequest {
-    repo_url: String,
-    repo_identifier: String,
-    pulsefile: String,
-    repo_type: Option<String>, // "github", "local", or other SCM type
+async fn update_repo_limits(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(limits): Json<ExecutionLimits>,
+) -> Result<Json<ExecutionLimits>, StatusCode> {
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !storage.set_repo_limits(&repo, limits.clone()) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    info!("Updated execution limits for {}", repo);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoLimitsUpdated {
+            repository: repo,
+            limits: limits.clone(),
+        },
+    );
+    Ok(Json(limits))
 }
 
-#[derive(Serialize)]
-struct RegisterRepoResponse {
-    message: String,
-    repo_identifier: String,
+async fn get_repo_sandbox(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<SandboxPolicy>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    storage
+        .get_repo_sandbox(&repo)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
 }
 
-async fn register_repo(
+async fn update_repo_sandbox(
     State(state): State<AppState>,
-    Json(req): Json<RegisterRepoRequest>,
-) -> Result<Json<RegisterRepoResponse>, StatusCode> {
-    // Validate Pulsefile by parsing it
-    if pulsiora_parser::parse_pulsefile(&req.pulsefile).is_err() {
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(sandbox): Json<SandboxPolicy>,
+) -> Result<Json<SandboxPolicy>, StatusCode> {
+    if sandbox.read_only_paths.iter().any(|path| !path.starts_with('/')) {
         return Err(StatusCode::BAD_REQUEST);
     }
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !storage.set_repo_sandbox(&repo, sandbox.clone()) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    info!("Updated sandbox policy for {}", repo);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoSandboxUpdated {
+            repository: repo,
+            sandbox: sandbox.clone(),
+        },
+    );
+    Ok(Json(sandbox))
+}
 
-    let repo_type = match req.repo_type.as_deref() {
-        Some("local") => storage::RepoType::Local,
-        Some(other) => storage::RepoType::Other(other.to_string()),
-        None => storage::RepoType::GitHub, // Default to GitHub
-    };
-
-    let repo = storage::RegisteredRepo {
-        repo_url: req.repo_url.clone(),
-        repo_identifier: req.repo_identifier.clone(),
-        pulsefile: req.pulsefile,
-        repo_type,
-    };
+async fn get_repo_webhook_mapping(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<WebhookMapping>, StatusCode> {
+    let storage = state.storage.read().await;
+    authorize_repo(&storage, &headers, &repo, RepoRole::Viewer)?;
+    storage
+        .get_repo_webhook_mapping(&repo)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
 
-    {
-        let mut storage = state.storage.write().await;
-        storage.register_repo(repo);
+async fn update_repo_webhook_mapping(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(mapping): Json<WebhookMapping>,
+) -> Result<Json<WebhookMapping>, StatusCode> {
+    if let Err(e) = validate_mapping(&mapping) {
+        info!(error = %e, "Rejected webhook mapping for {}", repo);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut storage = state.storage.write().await;
+    let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+    if !storage.set_repo_webhook_mapping(&repo, mapping.clone()) {
+        return Err(StatusCode::NOT_FOUND);
     }
+    info!("Updated webhook mapping for {}", repo);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::RepoWebhookMappingUpdated {
+            repository: repo,
+            mapping: mapping.clone(),
+        },
+    );
+    Ok(Json(mapping))
+}
 
-    info!("Registered repository: {}", req.repo_identifier);
+#[derive(Serialize, Deserialize)]
+struct BasePulsefile {
+    /// Base Pulsefile for the organization; null clears it
+    pulsefile: Option<String>,
+}
 
-    Ok(Json(RegisterRepoResponse {
-        message: "Repository registered successfully".to_string(),
-        repo_identifier: req.repo_identifier,
+/// An organization's base Pulsefile, for its members and operators; other
+/// organizations are `404 Not Found`
+async fn get_base_pulsefile(
+    State(state): State<AppState>,
+    Path(org): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<BasePulsefile>, StatusCode> {
+    let storage = state.storage.read().await;
+    let user = request_user(&storage, &headers)?;
+    if user.organization != org && !storage.is_operator(user) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let organization = storage.get_organization(&org).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(BasePulsefile {
+        pulsefile: organization.base_pulsefile.clone(),
     }))
 }
 
-async fn unregister_repo(
+/// Replace an organization's base Pulsefile; only its admins may change it
+async fn update_base_pulsefile(
     State(state): State<AppState>,
-    Path(repo): Path<String>,
-) -> Result<StatusCode, StatusCode> {
+    Path(org): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<BasePulsefile>,
+) -> Result<Json<BasePulsefile>, StatusCode> {
     let mut storage = state.storage.write().await;
-    
-    if storage.unregister_repo(&repo) {
-        info!("Unregistered repository: {}", repo);
-        Ok(StatusCode::NO_CONTENT)
-    } else {
-        Err(StatusCode::NOT_FOUND)
+    let user = bearer_token(&headers)
+        .and_then(|token| storage.find_user_by_token(token))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if !user.admin || user.organization != org {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let actor = user.username.clone();
+
+    if let Some(pulsefile) = &req.pulsefile {
+        if pulsiora_parser::parse_pulsefile(pulsefile).is_err() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
     }
+
+    if !storage.set_base_pulsefile(&org, req.pulsefile.clone()) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    info!("Updated base Pulsefile for organization {}", org);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::BasePulsefileUpdated {
+            organization: org,
+            cleared: req.pulsefile.is_none(),
+        },
+    );
+    Ok(Json(req))
 }
 
+#[derive(Deserialize)]
+struct UpdateStatusContextsRequest {
+    contexts: Vec<StatusContext>,
+    /// Protected branch whose required checks should be synced
+    branch: Option<String>,
+}
 
-async fn get_pipeline_status(
+#[derive(Serialize)]
+struct UpdateStatusContextsResponse {
+    contexts: Vec<StatusContext>,
+    /// Whether required contexts were pushed to GitHub branch protection
+    synced: bool,
+    sync_error: Option<String>,
+}
+
+async fn update_status_contexts(
     State(state): State<AppState>,
     Path(repo): Path<String>,
-    Query(params): Query<HashMap<String, String>>,
-) -> Result<Json<Vec<PipelineExecution>>, StatusCode> {
-    let limit = params
-        .get("limit")
-        .and_then(|s| s.parse::<usize>().ok())
-        .unwrap_or(10);
+    headers: axum::http::HeaderMap,
+    Json(req): Json<UpdateStatusContextsRequest>,
+) -> Result<Json<UpdateStatusContextsResponse>, StatusCode> {
+    if req
+        .contexts
+        .iter()
+        .any(|c| c.pipeline.is_empty() || c.context.is_empty())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
-    let storage = state.storage.read().await;
-    let executions = storage.get_executions_by_repo(&repo, limit);
+    let (is_github, actor, previous) = {
+        let mut storage = state.storage.write().await;
+        let actor = authorize_repo(&storage, &headers, &repo, RepoRole::Admin)?;
+        let previous = storage.get_status_contexts(&repo).unwrap_or_default();
+        if !storage.set_status_contexts(&repo, req.contexts.clone()) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        (storage.get_repo_type(&repo) == Some(RepoType::GitHub), actor, previous)
+    };
 
-    if executions.is_empty() && !storage.is_repo_registered(&repo) {
-        return Err(StatusCode::NOT_FOUND);
+    info!("Updated status contexts for {}", repo);
+    state.audit.record(
+        Some(&actor),
+        AuditEvent::StatusContextsUpdated {
+            repository: repo.clone(),
+            contexts: req.contexts.iter().map(|c| c.context.clone()).collect(),
+        },
+    );
+
+    let mut synced = false;
+    let mut sync_error = None;
+    let token = match (&req.branch, is_github) {
+        (Some(_), true) => github_token(&state, &repo).await,
+        _ => None,
+    };
+    if let (Some(token), Some(branch)) = (&token, &req.branch) {
+        let required: Vec<String> = req
+            .contexts
+            .iter()
+            .filter(|c| c.required)
+            .map(|c| c.context.clone())
+            .collect();
+        // Only contexts Pulsiora has configured are removed from the branch
+        let managed: Vec<String> = previous
+            .iter()
+            .chain(&req.contexts)
+            .map(|c| c.context.clone())
+            .collect();
+        match sync_required_status_checks(token, &repo, branch, &managed, &required).await {
+            Ok(()) => synced = true,
+            Err(e) => {
+                warn!(error = %e, "Failed to sync required status checks");
+                sync_error = Some(e.to_string());
+            }
+        }
     }
 
-    Ok(Json(executions))
+    Ok(Json(UpdateStatusContextsResponse {
+        contexts: req.contexts,
+        synced,
+        sync_error,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payload(value: serde_json::Value) -> GitHubWebhookPayload {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// State over a fresh data directory for calling handlers. The server
+    /// hosts the organizations `ops`, which runs it, `acme` and `globex`,
+    /// with admins `olga`, `alice` and `carol` and `bob`, an acme member;
+    /// each user's token is their name. `acme/shop` is registered.
+    fn test_state() -> AppState {
+        let data_dir = std::env::temp_dir().join(format!("pulsiora-server-main-{}", Uuid::new_v4()));
+        let mut storage = InMemoryStorage::new();
+        let now = chrono::Utc::now();
+        for (i, name) in ["ops", "acme", "globex"].into_iter().enumerate() {
+            storage.create_organization(Organization {
+                name: name.to_string(),
+                base_pulsefile: None,
+                quotas: Default::default(),
+                created_at: now + chrono::Duration::seconds(i as i64),
+            });
+        }
+        for (username, organization, admin) in
+            [("olga", "ops", true), ("alice", "acme", true), ("bob", "acme", false), ("carol", "globex", true)]
+        {
+            storage.create_user(User {
+                username: username.to_string(),
+                organization: organization.to_string(),
+                admin,
+                token_hash: hash_token(username),
+                created_at: now,
+            });
+        }
+        storage.register_repo(RegisteredRepo {
+            repo_url: "https://github.com/acme/shop".to_string(),
+            repo_identifier: "acme/shop".to_string(),
+            pulsefile: "pipeline {}".to_string(),
+            pulsefile_revision: 1,
+            pulsefile_history: Vec::new(),
+            repo_type: RepoType::GitHub,
+            status_contexts: Vec::new(),
+            limits: Default::default(),
+            sandbox: Default::default(),
+            webhook_mapping: Default::default(),
+            organization: Some("acme".to_string()),
+            roles: Default::default(),
+            env: Default::default(),
+            hooks: Default::default(),
+            pulsefile_source: Default::default(),
+            path_pipelines: Vec::new(),
+            deployments: Vec::new(),
+            settings: Default::default(),
+        });
+
+        AppState {
+            executor: PipelineExecutor::new(),
+            storage: Arc::new(RwLock::new(storage)),
+            bootstrap_token: Arc::new(Mutex::new(None)),
+            journal: Arc::new(JobJournal::open(data_dir.join("queue")).unwrap()),
+            deliveries: Arc::new(DeliveryLog::open(data_dir.join("deliveries")).unwrap()),
+            webhook_allowlist: Arc::new(RwLock::new(IpAllowList::new(Vec::new(), false))),
+            audit: Arc::new(AuditLog::open(data_dir.join("audit.log")).unwrap()),
+            host_sandbox: None,
+            scheduler: Arc::new(std::sync::Mutex::new(JobScheduler::new(1))),
+            jobs_changed: Arc::new(Notify::new()),
+            retry_policy: RetryPolicy::default(),
+            running: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            updates: UpdateHub::default(),
+            agents: None,
+            database: None,
+            dispatcher: Arc::new(std::sync::OnceLock::new()),
+            shutdown: CancellationToken::new(),
+            webhook_secret: None,
+            rate_limiter: None,
+            max_queued_jobs: DEFAULT_MAX_QUEUED_JOBS,
+            webhook_body_limit: DEFAULT_WEBHOOK_BODY_LIMIT,
+            objects: None,
+            data_dir,
+            secrets_key: None,
+            github_app: None,
+        }
+    }
+
+    /// Request headers carrying `user`'s token, or none
+    fn auth(user: Option<&str>) -> axum::http::HeaderMap {
+        let mut headers = axum::http::HeaderMap::new();
+        if let Some(user) = user {
+            headers.insert(axum::http::header::AUTHORIZATION, format!("Bearer {}", user).parse().unwrap());
+        }
+        headers
+    }
+
+    fn test_repo() -> Repository {
+        Repository {
+            owner: "test".to_string(),
+            name: "repo".to_string(),
+            full_name: "test/repo".to_string(),
+            clone_url: "https://github.com/test/repo.git".to_string(),
+            default_branch: "main".to_string(),
+        }
+    }
+
+    fn pull_request_payload(action: &str, merged: bool) -> GitHubWebhookPayload {
+        payload(json!({
+            "action": action,
+            "pull_request": {
+                "number": 7,
+                "title": "Add feature",
+                "state": if action == "closed" { "closed" } else { "open" },
+                "merged": merged,
+                "merge_commit_sha": "abc123",
+                "base": { "ref": "main" },
+                "head": { "ref": "feature/x", "sha": "def4567890" },
+            },
+            "sender": { "login": "octocat" },
+        }))
+    }
+
+    #[test]
+    fn test_pull_request_event_has_head_commit() {
+        let event = create_pull_request_event(test_repo(), &pull_request_payload("opened", false)).unwrap();
+        assert_eq!(event.event_type, GitEventType::PullRequest);
+        assert_eq!(event.commit_sha.as_deref(), Some("def4567890"));
+        assert_eq!(event.source_ref().as_deref(), Some("def4567890"));
+    }
+
+    #[test]
+    fn test_merged_pull_request_is_merge_event() {
+        let event = create_pull_request_event(test_repo(), &pull_request_payload("closed", true)).unwrap();
+        assert_eq!(event.event_type, GitEventType::Merge);
+        assert_eq!(event.branch.as_deref(), Some("main"));
+        assert_eq!(event.commit_sha.as_deref(), Some("abc123"));
+        assert_eq!(event.pull_request.unwrap().number, 7);
+    }
+
+    #[test]
+    fn test_pull_request_actions_without_code_changes_are_ignored() {
+        for action in ["opened", "synchronize", "reopened"] {
+            let event = create_pull_request_event(test_repo(), &pull_request_payload(action, false));
+            assert_eq!(event.map(|e| e.event_type), Some(GitEventType::PullRequest), "{}", action);
+        }
+        for action in ["closed", "labeled", "edited", "assigned"] {
+            assert!(create_pull_request_event(test_repo(), &pull_request_payload(action, false)).is_none(), "{}", action);
+        }
+    }
+
+    #[test]
+    fn test_release_event_actions() {
+        let release = |action: &str, draft: bool| {
+            payload(json!({
+                "action": action,
+                "release": { "tag_name": "v1.2.0", "name": "Spring release", "draft": draft },
+            }))
+        };
+
+        let event = create_release_event(test_repo(), &release("published", false)).unwrap();
+        assert_eq!(event.event_type, GitEventType::Release);
+        assert_eq!(event.tag.as_deref(), Some("v1.2.0"));
+        assert_eq!(event.release_name.as_deref(), Some("Spring release"));
+
+        assert!(create_release_event(test_repo(), &release("created", false)).is_none());
+        assert!(create_release_event(test_repo(), &release("created", true)).is_some());
+        assert!(create_release_event(test_repo(), &release("deleted", false)).is_none());
+    }
+
+    #[test]
+    fn test_push_changed_files() {
+        let push = payload(json!({
+            "ref": "refs/heads/main",
+            "commits": [
+                { "added": ["services/api/new.rs"], "modified": ["README.md"] },
+                { "removed": ["services/web/old.ts"], "modified": ["README.md"] },
+            ],
+        }));
+        assert_eq!(
+            push_changed_files(&push).unwrap(),
+            ["README.md", "services/api/new.rs", "services/web/old.ts"]
+        );
+
+        // Without commits, or with as many as GitHub lists, the changes aren't known
+        assert!(push_changed_files(&payload(json!({ "commits": [] }))).is_none());
+        assert!(push_changed_files(&payload(json!({}))).is_none());
+        let commits = vec![json!({ "modified": ["a"] }); MAX_PUSH_PAYLOAD_COMMITS];
+        assert!(push_changed_files(&payload(json!({ "commits": commits }))).is_none());
+
+        // Commits past what a push payload lists aren't kept
+        let commits = vec![json!({ "modified": ["a"] }); 5000];
+        let body = json!({ "commits": commits, "ref": "refs/heads/main" }).to_string();
+        let parsed: GitHubWebhookPayload = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.commits.as_ref().map(Vec::len), Some(MAX_PUSH_PAYLOAD_COMMITS));
+        assert_eq!(parsed.ref_field.as_deref(), Some("refs/heads/main"));
+        assert!(payload(json!({ "commits": null })).commits.is_none());
+        assert!(serde_json::from_str::<GitHubWebhookPayload>(r#"{"commits": 3}"#).is_err());
+    }
+
+    /// A finished execution of `acme/shop`, stored in `state`; returns its ID
+    async fn store_test_execution(state: &AppState) -> String {
+        let repository = Repository {
+            owner: "acme".to_string(),
+            name: "shop".to_string(),
+            full_name: "acme/shop".to_string(),
+            ..test_repo()
+        };
+        let execution: PipelineExecution = serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "pipeline_name": "ci",
+            "pipeline_version": "1.0",
+            "repository": repository,
+            "git_event": {
+                "event_type": "Push",
+                "repository": repository,
+                "branch": "main",
+                "tag": null,
+                "pull_request": null,
+                "commit_sha": "abc123",
+                "sender": "alice",
+            },
+            "status": "Success",
+            "step_results": [],
+            "started_at": chrono::Utc::now(),
+            "completed_at": chrono::Utc::now(),
+        }))
+        .unwrap();
+        let id = execution.id.to_string();
+        state.storage.write().await.store_execution(execution);
+        id
+    }
+
+    #[tokio::test]
+    async fn test_share_links_need_read_access() {
+        let state = test_state();
+        let id = store_test_execution(&state).await;
+
+        let share = |user| create_share_link(State(state.clone()), Path(id.clone()), auth(user), None);
+        assert_eq!(share(None).await.err(), Some(StatusCode::UNAUTHORIZED));
+        // Another organization's users don't get to know it exists
+        assert_eq!(share(Some("carol")).await.err(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(share(Some("bob")).await.unwrap().0, StatusCode::CREATED);
+
+        let list = |user| list_share_links(State(state.clone()), Path(id.clone()), auth(user));
+        assert_eq!(list(Some("carol")).await.err(), Some(StatusCode::NOT_FOUND));
+        let links = list(Some("alice")).await.unwrap().0;
+        assert_eq!(links.len(), 1);
+
+        // Another organization's admin can't revoke it, nor can a member who didn't create it
+        let share_id = links[0].id.to_string();
+        let revoke = |user| revoke_share_link(State(state.clone()), Path((id.clone(), share_id.clone())), auth(user));
+        assert_eq!(revoke(Some("carol")).await, Err(StatusCode::NOT_FOUND));
+        assert_eq!(share(Some("alice")).await.unwrap().0, StatusCode::CREATED);
+        let alices = list(Some("alice")).await.unwrap().0[1].id.to_string();
+        assert_eq!(
+            revoke_share_link(State(state.clone()), Path((id.clone(), alices)), auth(Some("bob"))).await,
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(revoke(Some("alice")).await, Ok(StatusCode::NO_CONTENT));
+    }
+
+    #[tokio::test]
+    async fn test_base_pulsefile_and_agents_need_tokens() {
+        let state = test_state();
+        let base = |user| get_base_pulsefile(State(state.clone()), Path("acme".to_string()), auth(user));
+        assert_eq!(base(None).await.err(), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(base(Some("carol")).await.err(), Some(StatusCode::NOT_FOUND));
+        assert!(base(Some("bob")).await.is_ok());
+        assert!(base(Some("olga")).await.is_ok());
+
+        let agents = |user| list_agents(State(state.clone()), auth(user));
+        assert_eq!(agents(None).await.err(), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(agents(Some("alice")).await.err(), Some(StatusCode::FORBIDDEN));
+        // Past authentication; this server has no agents
+        assert_eq!(agents(Some("olga")).await.err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_agent_sandbox() {
+        let host = |enabled_by_default| HostSandbox {
+            tool: SandboxTool::Bubblewrap,
+            enabled_by_default,
+        };
+        let opted_in = SandboxPolicy {
+            enabled: Some(true),
+            network: true,
+            read_only_paths: vec![],
+        };
+        let opted_out = SandboxPolicy {
+            enabled: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(agent_sandbox(Some(&host(false)), &opted_in), Some(opted_in.clone()));
+        assert_eq!(agent_sandbox(None, &opted_in), Some(opted_in));
+        assert_eq!(agent_sandbox(Some(&host(false)), &SandboxPolicy::default()), None);
+        assert_eq!(agent_sandbox(Some(&host(true)), &SandboxPolicy::default()), Some(SandboxPolicy::default()));
+        assert_eq!(agent_sandbox(Some(&host(true)), &opted_out), None);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_body_limits_and_parse_failures() {
+        let chunked = |chunks: usize| {
+            Body::from_stream(futures::stream::iter(
+                (0..chunks).map(|_| Ok::<_, std::io::Error>(vec![b'x'; 1024])),
+            ))
+        };
+        assert_eq!(read_webhook_body(chunked(4), 4096).await.unwrap().len(), 4096);
+        assert_eq!(read_webhook_body(chunked(5), 4096).await, Err(StatusCode::PAYLOAD_TOO_LARGE));
+
+        let large = vec![b'{'; WEBHOOK_INLINE_PARSE_LIMIT + 1];
+        let parsed = parse_webhook_body(large, |_| -> () { panic!("parser bug") }).await;
+        assert_eq!(parsed, Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_failing_step_is_not_retried() {
+        let state = test_state();
+        let repository = Repository {
+            owner: "acme".to_string(),
+            name: "shop".to_string(),
+            full_name: "acme/shop".to_string(),
+            ..test_repo()
+        };
+        let event = GitEvent {
+            event_type: GitEventType::Manual,
+            repository,
+            branch: None,
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: None,
+            sender: "alice".to_string(),
+            inputs: Default::default(),
+        };
+        let pulsefile = r#"
+pipeline {
+  name: "ci";
+  triggers {
+    manual { }
+  }
+  steps {
+    step "test" {
+      run: """exit 1""";
+    }
+  }
 }
+"#;
+        let job = QueuedJob::new(event, pulsefile.to_string());
+        let execution = run_job(&state, &job).await.unwrap();
+        assert_eq!(execution.status, PipelineStatus::Failed);
+        assert!(execution.attempts.is_empty());
+        assert!(state.journal.load_all().unwrap().is_empty());
+        assert_eq!(state.scheduler.lock().unwrap().pending().count(), 0);
+    }
 
+    #[tokio::test]
+    async fn test_artifact_pages_are_sandboxed() {
+        let mut state = test_state();
+        state.executor = state.executor.clone().with_artifact_dir(state.data_dir.join("artifacts"));
+        let id = store_test_execution(&state).await;
+        let root = state.executor.artifacts_path(id.parse().unwrap()).unwrap();
+        std::fs::create_dir_all(root.join("coverage")).unwrap();
+        std::fs::write(root.join("coverage/report.html"), "<script>fetch('/api/v1/executions')</script>").unwrap();
+
+        // The listing shows names the pipeline chose, so it's sandboxed too
+        for path in ["", "coverage/", "coverage/report.html"] {
+            let response = serve_artifact(&state, &auth(Some("bob")), &id, path).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let headers = response.headers();
+            assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff", "{}", path);
+            assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "sandbox allow-scripts", "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repo_settings_need_roles() {
+        let state = test_state();
+        let repo = || Path("acme/shop".to_string());
+        // Nobody, another organization's admin, a member and an admin of the repository's
+        let users = [None, Some("carol"), Some("bob"), Some("alice")];
+
+        let mut answers = Vec::new();
+        for user in users {
+            let state = || State(state.clone());
+            answers.push([
+                get_status_contexts(state(), repo(), auth(user)).await.err(),
+                get_repo_limits(state(), repo(), auth(user)).await.err(),
+                get_repo_sandbox(state(), repo(), auth(user)).await.err(),
+                get_repo_webhook_mapping(state(), repo(), auth(user)).await.err(),
+            ]);
+        }
+        assert_eq!(answers[0], [Some(StatusCode::UNAUTHORIZED); 4]);
+        assert_eq!(answers[1], [Some(StatusCode::FORBIDDEN); 4]);
+        assert_eq!(answers[2], [None; 4]);
+        assert_eq!(answers[3], [None; 4]);
+
+        let mut answers = Vec::new();
+        for user in users {
+            let state = || State(state.clone());
+            let contexts = UpdateStatusContextsRequest {
+                contexts: Vec::new(),
+                branch: None,
+            };
+            answers.push([
+                update_status_contexts(state(), repo(), auth(user), Json(contexts)).await.err(),
+                update_repo_limits(state(), repo(), auth(user), Json(Default::default())).await.err(),
+                update_repo_sandbox(state(), repo(), auth(user), Json(Default::default())).await.err(),
+                update_repo_webhook_mapping(state(), repo(), auth(user), Json(Default::default())).await.err(),
+            ]);
+        }
+        assert_eq!(answers[0], [Some(StatusCode::UNAUTHORIZED); 4]);
+        assert_eq!(answers[1], [Some(StatusCode::FORBIDDEN); 4]);
+        assert_eq!(answers[2], [Some(StatusCode::FORBIDDEN); 4]);
+        assert_eq!(answers[3], [None; 4]);
+    }
+
+    #[test]
+    fn test_fork_pull_requests_get_no_secrets() {
+        let mut state = test_state();
+        let key = SecretsKey::from_hex(&"ab".repeat(32)).unwrap();
+        let mut storage = InMemoryStorage::new();
+        storage.set_secret("test/repo", "TOKEN", key.encrypt("test/repo", "TOKEN", "hunter2").unwrap());
+        state.secrets_key = Some(key);
+
+        let pull_request = |head: serde_json::Value| {
+            create_pull_request_event(
+                test_repo(),
+                &payload(json!({
+                    "action": "opened",
+                    "pull_request": {
+                        "number": 7,
+                        "title": "Add feature",
+                        "state": "open",
+                        "base": { "ref": "main" },
+                        "head": { "ref": "feature/x", "repo": head },
+                    },
+                    "sender": { "login": "octocat" },
+                })),
+            )
+            .unwrap()
+        };
+        let secrets = |event: &GitEvent| job_secrets(&state, &storage, event);
+        let same_repo = pull_request(json!({ "full_name": "test/repo" }));
+        assert_eq!(secrets(&same_repo).get("TOKEN").map(String::as_str), Some("hunter2"));
+        assert!(secrets(&pull_request(json!({ "full_name": "someone/repo" }))).is_empty());
+        // A deleted fork
+        assert!(secrets(&pull_request(serde_json::Value::Null)).is_empty());
+    }
+
+    #[test]
+    fn test_execution_query_params() {
+        let params = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let query = execution_query(&params(&[
+            ("status", "failed"),
+            ("branch", "main"),
+            ("repo", "acme/shop"),
+            ("since", "2026-03-01"),
+        ]))
+        .unwrap();
+        assert_eq!(query.status, Some(PipelineStatus::Failed));
+        assert_eq!(query.branch.as_deref(), Some("main"));
+        assert_eq!(query.repository.as_deref(), Some("acme/shop"));
+        assert_eq!(query.since.unwrap().to_rfc3339(), "2026-03-01T00:00:00+00:00");
+
+        let query = execution_query(&params(&[("since", "2026-03-01T12:00:00+02:00")])).unwrap();
+        assert_eq!(query.since.unwrap().to_rfc3339(), "2026-03-01T10:00:00+00:00");
+        assert_eq!(execution_query(&params(&[])).unwrap(), ExecutionQuery::default());
+        assert!(execution_query(&params(&[("status", "done")])).is_err());
+        assert!(execution_query(&params(&[("since", "yesterday")])).is_err());
+    }
+}
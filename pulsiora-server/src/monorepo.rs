@@ -0,0 +1,117 @@
+// Path-scoped pipelines, for repositories holding several projects: each one
+// has its own Pulsefile and only runs for events that change files under its
+// paths, next to the repository's own Pulsefile, which runs for every event.
+
+use crate::storage::pulsefile_hash;
+use serde::{Deserialize, Serialize};
+
+/// A pipeline of a repository that runs for changes under some paths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathPipeline {
+    /// Identifies it among the repository's path-scoped pipelines
+    pub name: String,
+    /// Patterns such as `services/api/**`; a pattern naming a directory covers
+    /// the files under it
+    pub paths: Vec<String>,
+    pub pulsefile: String,
+}
+
+impl PathPipeline {
+    /// Whether the pipeline runs for an event that changed `changed_files`;
+    /// when they aren't known it always does
+    pub fn runs_for(&self, changed_files: Option<&[String]>) -> bool {
+        changed_files.is_none_or(|files| {
+            files
+                .iter()
+                .any(|file| self.paths.iter().any(|pattern| path_matches(pattern, file)))
+        })
+    }
+
+    pub fn pulsefile_hash(&self) -> String {
+        pulsefile_hash(&self.pulsefile)
+    }
+}
+
+/// Whether `name` can name a path-scoped pipeline: letters, digits, `-`, `_`
+/// and `.`
+pub fn valid_pipeline_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Whether `pattern` covers the file at `path`, relative to the repository
+/// root. `*` matches within one path segment and `**` any number of
+/// segments. A pattern matching one of the file's directories covers it too.
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.trim_matches('/').split('/').filter(|s| !s.is_empty()).collect();
+    let path: Vec<&str> = path.trim_matches('/').split('/').filter(|s| !s.is_empty()).collect();
+    !pattern.is_empty() && segments_match(&pattern, &path)
+}
+
+/// Whether `pattern` matches `path` or one of its leading directories
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => true,
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => path
+            .split_first()
+            .is_some_and(|(name, path)| wildcard_match(segment, name) && segments_match(rest, path)),
+    }
+}
+
+/// Match one path segment against a pattern where `*` stands for any run of
+/// characters
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|&skip| name.is_char_boundary(skip))
+                .any(|skip| wildcard_match(rest, &name[skip..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_matches() {
+        assert!(path_matches("services/api/**", "services/api/src/main.rs"));
+        assert!(path_matches("services/api/**", "services/api/Pulsefile"));
+        assert!(path_matches("services/api", "services/api/src/main.rs"));
+        assert!(path_matches("/services/api/", "services/api/go.mod"));
+        assert!(!path_matches("services/api/**", "services/apigw/main.go"));
+        assert!(!path_matches("services/api/**", "services/web/index.ts"));
+
+        assert!(path_matches("services/*/Dockerfile", "services/web/Dockerfile"));
+        assert!(!path_matches("services/*/Dockerfile", "services/web/build/Dockerfile"));
+        assert!(path_matches("**/*.proto", "proto/v1/users.proto"));
+        assert!(path_matches("*.md", "README.md"));
+        assert!(!path_matches("*.md", "docs/guide.md"));
+        assert!(path_matches("libs/**/test_*.py", "libs/core/tests/test_io.py"));
+        assert!(!path_matches("", "README.md"));
+    }
+
+    #[test]
+    fn test_path_pipeline_runs_for() {
+        let pipeline = PathPipeline {
+            name: "api".to_string(),
+            paths: vec!["services/api/**".to_string(), "libs/shared/**".to_string()],
+            pulsefile: "pipeline {}".to_string(),
+        };
+        let changed = |files: &[&str]| files.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert!(pipeline.runs_for(Some(&changed(&["README.md", "libs/shared/log.rs"]))));
+        assert!(!pipeline.runs_for(Some(&changed(&["services/web/app.ts"]))));
+        assert!(!pipeline.runs_for(Some(&[])));
+        // Events whose changes aren't known run every pipeline
+        assert!(pipeline.runs_for(None));
+
+        assert!(valid_pipeline_name("api-v2.1_x"));
+        assert!(!valid_pipeline_name(""));
+        assert!(!valid_pipeline_name("services/api"));
+    }
+}
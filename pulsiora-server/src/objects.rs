@@ -0,0 +1,346 @@
+// Object storage for the large files an execution leaves behind. Once an
+// execution ends, its step logs and artifacts are uploaded to an
+// S3-compatible bucket and removed from the data directory; the API then
+// answers requests for them with redirects to signed download URLs.
+
+use crate::artifacts::{content_type_for, list_artifact_files, ArtifactEntry};
+use async_trait::async_trait;
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use pulsiora_core::{OutputStream, PulsioraError, Result};
+use std::io::Read;
+use std::path::{Component, Path};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How long a signed download URL works
+pub const SIGNED_URL_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Directories of the data directory whose per-execution files are offloaded
+const OFFLOADED_DIRS: [&str; 2] = ["logs", "artifacts"];
+
+/// Where files are kept under keys like `artifacts/<execution id>/report.html`
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<()>;
+    /// URL anyone can download the object from until `expires_in` passes
+    async fn signed_url(&self, key: &str, expires_in: Duration) -> Result<String>;
+    /// Objects whose key starts with `prefix`, with their size
+    async fn list(&self, prefix: &str) -> Result<Vec<(String, u64)>>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Bucket settings; credentials come from the usual AWS environment
+/// variables, profile or instance role
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct S3Options {
+    pub bucket: String,
+    /// Prepended to every key, e.g. `pulsiora/`
+    pub prefix: String,
+    /// Endpoint of an S3-compatible service such as MinIO, instead of AWS
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    /// Address buckets as `<endpoint>/<bucket>` rather than as a subdomain,
+    /// which most S3-compatible services need
+    pub path_style: bool,
+}
+
+pub struct S3ObjectStore {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    prefix: String,
+}
+
+impl S3ObjectStore {
+    pub async fn new(options: S3Options) -> Self {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = options.region {
+            loader = loader.region(aws_config::Region::new(region));
+        }
+        if let Some(endpoint) = &options.endpoint {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let shared = loader.load().await;
+        let config = aws_sdk_s3::config::Builder::from(&shared)
+            .force_path_style(options.path_style)
+            .build();
+        let prefix = match options.prefix.trim_matches('/') {
+            "" => String::new(),
+            prefix => format!("{}/", prefix),
+        };
+        Self {
+            client: aws_sdk_s3::Client::from_conf(config),
+            bucket: options.bucket,
+            prefix,
+        }
+    }
+
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+fn s3_error(action: &str, key: &str, error: impl std::error::Error) -> PulsioraError {
+    PulsioraError::StorageError(format!("Failed to {} {}: {}", action, key, DisplayErrorContext(error)))
+}
+
+#[async_trait]
+impl ObjectStore for S3ObjectStore {
+    async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<()> {
+        let body = ByteStream::from_path(path)
+            .await
+            .map_err(|e| s3_error("read", &path.display().to_string(), e))?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.full_key(key))
+            .content_type(content_type)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| s3_error("upload", key, e))?;
+        Ok(())
+    }
+
+    async fn signed_url(&self, key: &str, expires_in: Duration) -> Result<String> {
+        let presigning = PresigningConfig::expires_in(expires_in).map_err(|e| s3_error("sign", key, e))?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.full_key(key))
+            .presigned(presigning)
+            .await
+            .map_err(|e| s3_error("sign", key, e))?;
+        Ok(request.uri().to_string())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<(String, u64)>> {
+        let mut objects = Vec::new();
+        let mut continuation = None;
+        loop {
+            let page = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(self.full_key(prefix))
+                .set_continuation_token(continuation)
+                .send()
+                .await
+                .map_err(|e| s3_error("list", prefix, e))?;
+            for object in page.contents() {
+                if let Some(key) = object.key().and_then(|key| key.strip_prefix(&self.prefix)) {
+                    objects.push((key.to_string(), object.size().unwrap_or_default().max(0) as u64));
+                }
+            }
+            match page.next_continuation_token() {
+                Some(token) if page.is_truncated().unwrap_or(false) => continuation = Some(token.to_string()),
+                _ => return Ok(objects),
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.full_key(key))
+            .send()
+            .await
+            .map_err(|e| s3_error("delete", key, e))?;
+        Ok(())
+    }
+}
+
+/// Key of one stream of a step's log, from the step result's `log_ref`
+pub fn step_log_key(log_ref: &str, stream: OutputStream) -> Option<String> {
+    // References are `<uuid>/<index>`, as for the runner's log files
+    let (execution_id, index) = log_ref.split_once('/')?;
+    let execution_id = Uuid::parse_str(execution_id).ok()?;
+    let index: usize = index.parse().ok()?;
+    let suffix = match stream {
+        OutputStream::Stdout => "stdout.log",
+        OutputStream::Stderr => "stderr.log",
+    };
+    Some(format!("logs/{}/{}.{}", execution_id, index, suffix))
+}
+
+/// Key of an execution's artifact at `relative`, rejecting paths that could
+/// name another execution's objects
+pub fn artifact_key(execution_id: Uuid, relative: &str) -> Option<String> {
+    let relative = relative.trim_start_matches('/');
+    Path::new(relative)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        .then(|| format!("artifacts/{}/{}", execution_id, relative))
+}
+
+/// Every artifact file of an execution in the store, sorted by path
+pub async fn list_stored_artifacts(store: &dyn ObjectStore, execution_id: Uuid) -> Result<Vec<ArtifactEntry>> {
+    let prefix = format!("artifacts/{}/", execution_id);
+    let mut files: Vec<ArtifactEntry> = store
+        .list(&prefix)
+        .await?
+        .into_iter()
+        .filter_map(|(key, size)| {
+            key.strip_prefix(&prefix).map(|path| ArtifactEntry {
+                path: path.to_string(),
+                size,
+                is_dir: false,
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Entries directly inside `dir` among artifact files, directories first,
+/// then by name, as `list_artifact_dir` gives them for files on disk
+pub fn artifact_dir_entries(files: &[ArtifactEntry], dir: &str) -> Vec<ArtifactEntry> {
+    let dir = dir.trim_matches('/');
+    let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+    let mut entries: Vec<ArtifactEntry> = Vec::new();
+    for file in files {
+        let Some(rest) = file.path.strip_prefix(&prefix) else {
+            continue;
+        };
+        match rest.split_once('/') {
+            Some((subdir, _)) => {
+                let path = format!("{}{}", prefix, subdir);
+                if !entries.iter().any(|entry| entry.path == path) {
+                    entries.push(ArtifactEntry {
+                        path,
+                        size: 0,
+                        is_dir: true,
+                    });
+                }
+            }
+            None => entries.push(file.clone()),
+        }
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.cmp(&b.path)));
+    entries
+}
+
+/// Upload an execution's step logs and artifacts from `data_dir`, removing
+/// each directory once all of its files are stored. Returns the number of
+/// files uploaded.
+pub async fn offload_execution(store: &dyn ObjectStore, data_dir: &Path, execution_id: Uuid) -> Result<usize> {
+    let mut uploaded = 0;
+    for dir in OFFLOADED_DIRS {
+        let root = data_dir.join(dir).join(execution_id.to_string());
+        if !root.is_dir() {
+            continue;
+        }
+        for file in list_artifact_files(&root)? {
+            let path = root.join(&file.path);
+            let mut head = Vec::with_capacity(512);
+            std::fs::File::open(&path)?.take(512).read_to_end(&mut head)?;
+            let key = format!("{}/{}/{}", dir, execution_id, file.path);
+            store.put_file(&key, &path, content_type_for(&path, &head)).await?;
+            uploaded += 1;
+        }
+        tokio::fs::remove_dir_all(&root).await?;
+    }
+    Ok(uploaded)
+}
+
+/// Delete what was offloaded of an execution. Returns the number of objects
+/// deleted.
+pub async fn delete_execution_objects(store: &dyn ObjectStore, execution_id: Uuid) -> Result<usize> {
+    let mut deleted = 0;
+    for dir in OFFLOADED_DIRS {
+        for (key, _) in store.list(&format!("{}/{}/", dir, execution_id)).await? {
+            store.delete(&key).await?;
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    /// Keeps objects in memory, signing URLs with a fake query string
+    #[derive(Default)]
+    struct MemoryObjectStore {
+        objects: Mutex<BTreeMap<String, (Vec<u8>, String)>>,
+    }
+
+    #[async_trait]
+    impl ObjectStore for MemoryObjectStore {
+        async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<()> {
+            let bytes = std::fs::read(path)?;
+            self.objects
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), (bytes, content_type.to_string()));
+            Ok(())
+        }
+
+        async fn signed_url(&self, key: &str, expires_in: Duration) -> Result<String> {
+            Ok(format!("https://objects.example.com/{}?expires={}", key, expires_in.as_secs()))
+        }
+
+        async fn list(&self, prefix: &str) -> Result<Vec<(String, u64)>> {
+            let objects = self.objects.lock().unwrap();
+            Ok(objects
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, (bytes, _))| (key.clone(), bytes.len() as u64))
+                .collect())
+        }
+
+        async fn delete(&self, key: &str) -> Result<()> {
+            self.objects.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offload_execution() {
+        let data_dir = std::env::temp_dir().join(format!("pulsiora-server-objects-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let execution_id = Uuid::new_v4();
+        let logs = data_dir.join("logs").join(execution_id.to_string());
+        let artifacts = data_dir.join("artifacts").join(execution_id.to_string());
+        std::fs::create_dir_all(&logs).unwrap();
+        std::fs::create_dir_all(artifacts.join("coverage")).unwrap();
+        std::fs::write(logs.join("0.stdout.log"), "building\n").unwrap();
+        std::fs::write(artifacts.join("coverage/index.html"), "<h1>ok</h1>").unwrap();
+        std::fs::write(artifacts.join("junit.xml"), "<testsuite/>").unwrap();
+
+        let store = MemoryObjectStore::default();
+        assert_eq!(offload_execution(&store, &data_dir, execution_id).await.unwrap(), 3);
+        assert!(!logs.exists() && !artifacts.exists());
+
+        let log_key = step_log_key(&format!("{}/0", execution_id), OutputStream::Stdout).unwrap();
+        {
+            let objects = store.objects.lock().unwrap();
+            assert_eq!(objects[&log_key].0, b"building\n");
+            let report = &objects[&artifact_key(execution_id, "/coverage/index.html").unwrap()];
+            assert_eq!(report.1, "text/html; charset=utf-8");
+        }
+
+        let files = list_stored_artifacts(&store, execution_id).await.unwrap();
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["coverage/index.html", "junit.xml"]);
+        let root = artifact_dir_entries(&files, "");
+        assert_eq!(root[0].path, "coverage");
+        assert!(root[0].is_dir);
+        assert_eq!(root[1].path, "junit.xml");
+        assert_eq!(artifact_dir_entries(&files, "coverage/")[0].path, "coverage/index.html");
+
+        // Nothing is left to upload the second time
+        assert_eq!(offload_execution(&store, &data_dir, execution_id).await.unwrap(), 0);
+        assert_eq!(delete_execution_objects(&store, execution_id).await.unwrap(), 3);
+        assert!(store.objects.lock().unwrap().is_empty());
+        assert!(step_log_key("../0", OutputStream::Stdout).is_none());
+        assert!(artifact_key(execution_id, "../other/secret").is_none());
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
@@ -0,0 +1,637 @@
+use chrono::{DateTime, Duration, Utc};
+use pulsiora_core::{ExecutionAttempt, GitEvent, GitEventType, PulsioraError, Result, StepResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// A pipeline run accepted from a webhook that hasn't completed yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedJob {
+    /// Also used as the execution ID, so completed jobs can be recognised
+    pub id: Uuid,
+    pub git_event: GitEvent,
+    pub pulsefile: String,
+    /// Revision of the repository's stored Pulsefile, when `pulsefile` is one
+    #[serde(default)]
+    pub pulsefile_revision: Option<u32>,
+    pub queued_at: DateTime<Utc>,
+    /// Set once the job starts running; a started job still in the journal
+    /// after a restart was interrupted
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+    /// Results of the steps finished so far, checkpointed as each one ends
+    #[serde(default)]
+    pub completed_steps: Vec<StepResult>,
+    /// Earlier tries that ended in an infrastructure error
+    #[serde(default)]
+    pub attempts: Vec<ExecutionAttempt>,
+    /// A job being retried doesn't start before this
+    #[serde(default)]
+    pub retry_at: Option<DateTime<Utc>>,
+    /// W3C `traceparent` of the request that queued the job, so its
+    /// execution continues that trace
+    #[serde(default)]
+    pub trace_parent: Option<String>,
+}
+
+impl QueuedJob {
+    pub fn new(git_event: GitEvent, pulsefile: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            git_event,
+            pulsefile,
+            pulsefile_revision: None,
+            queued_at: Utc::now(),
+            started_at: None,
+            completed_steps: Vec::new(),
+            attempts: Vec::new(),
+            retry_at: None,
+            trace_parent: None,
+        }
+    }
+
+    /// Record that the job runs the given revision of the stored Pulsefile
+    pub fn with_pulsefile_revision(mut self, revision: Option<u32>) -> Self {
+        self.pulsefile_revision = revision;
+        self
+    }
+
+    /// Continue the trace `trace_parent` names when the job runs
+    pub fn with_trace_parent(mut self, trace_parent: Option<String>) -> Self {
+        self.trace_parent = trace_parent;
+        self
+    }
+
+    /// Record that a try ended in an infrastructure error
+    pub fn record_attempt(&mut self, error: String) {
+        let now = Utc::now();
+        self.attempts.push(ExecutionAttempt {
+            attempt: self.attempts.len() as u32 + 1,
+            started_at: self.started_at.unwrap_or(now),
+            failed_at: now,
+            error,
+        });
+    }
+
+    /// Record that a try ended in an infrastructure error and set the job up
+    /// to run again from scratch after `delay`
+    pub fn retry_after(&mut self, error: String, delay: Duration) {
+        self.record_attempt(error);
+        self.started_at = None;
+        self.completed_steps.clear();
+        self.retry_at = Some(Utc::now() + delay);
+    }
+
+    fn waiting_to_retry(&self, now: DateTime<Utc>) -> bool {
+        self.retry_at.is_some_and(|at| at > now)
+    }
+
+    pub fn priority(&self) -> JobPriority {
+        JobPriority::for_event(&self.git_event)
+    }
+}
+
+/// How soon a job runs compared to others waiting: jobs of a higher class
+/// start before any of a lower one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    /// Pull request builds
+    Low,
+    /// Everything else, such as pushes to other branches
+    Normal,
+    /// Tags, releases, and runs of the default branch
+    High,
+}
+
+impl JobPriority {
+    pub fn for_event(event: &GitEvent) -> Self {
+        match event.event_type {
+            GitEventType::Tag | GitEventType::Release => Self::High,
+            GitEventType::PullRequest => Self::Low,
+            _ if event.branch.as_deref() == Some(event.repository.default_branch.as_str()) => Self::High,
+            _ => Self::Normal,
+        }
+    }
+}
+
+/// How often, and after how long, a job that hit an infrastructure error is
+/// run again. Step failures are never retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first try
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after it
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff: Duration::seconds(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Longest wait before a retry
+    const MAX_BACKOFF: Duration = Duration::hours(1);
+
+    /// Wait before retry `retry`, counting from 1; None when no retries are left
+    pub fn delay(&self, retry: u32) -> Option<Duration> {
+        if retry == 0 || retry > self.max_retries {
+            return None;
+        }
+        let factor = 1i32.checked_shl(retry - 1).unwrap_or(i32::MAX);
+        Some(self.backoff.checked_mul(factor).unwrap_or(Self::MAX_BACKOFF).min(Self::MAX_BACKOFF))
+    }
+}
+
+/// Durable record of queued jobs, one JSON file per job
+pub struct JobJournal {
+    dir: PathBuf,
+}
+
+impl JobJournal {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn job_path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Write a job to disk; the temp file + rename keeps a crash from leaving half a job
+    pub fn persist(&self, job: &QueuedJob) -> Result<()> {
+        let json = serde_json::to_vec(job)
+            .map_err(|e| PulsioraError::ExecutionError(format!("Failed to serialize job: {}", e)))?;
+        let tmp = self.dir.join(format!("{}.json.tmp", job.id));
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, self.job_path(job.id))?;
+        Ok(())
+    }
+
+    /// A persisted job, if it is still in the journal
+    pub fn load(&self, id: Uuid) -> Result<Option<QueuedJob>> {
+        match fs::read(self.job_path(id)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| PulsioraError::ExecutionError(format!("Failed to read job {}: {}", id, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn remove(&self, id: Uuid) -> Result<()> {
+        match fs::remove_file(self.job_path(id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Load all persisted jobs, oldest first. Unreadable entries are skipped.
+    pub fn load_all(&self) -> Result<Vec<QueuedJob>> {
+        let mut jobs = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read(&path).map(|bytes| serde_json::from_slice::<QueuedJob>(&bytes)) {
+                Ok(Ok(job)) => jobs.push(job),
+                _ => tracing::warn!(path = %path.display(), "Skipping unreadable queued job"),
+            }
+        }
+        jobs.sort_by_key(|j| j.queued_at);
+        Ok(jobs)
+    }
+}
+
+/// A job a worker is running
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunningJob {
+    pub id: Uuid,
+    pub repository: String,
+    /// Worker running it, from 1 to the number of workers
+    pub worker: usize,
+    /// Organization whose quota it counts against, if that has one
+    #[serde(skip)]
+    pub organization: Option<String>,
+    pub queued_at: DateTime<Utc>,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Why a job that's waiting to run hasn't started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitReason {
+    /// Every worker is running a job
+    WorkersBusy,
+    /// Its repository already runs as many jobs as it may at once
+    RepositoryLimit,
+    /// Its organization already runs as many jobs as its quota allows
+    OrganizationLimit,
+    /// It's waiting to be retried after an infrastructure error
+    RetryBackoff,
+    /// Nothing holds it back; it starts as soon as the dispatcher gets to it
+    Starting,
+    /// The server is shutting down; it runs once the server is back
+    ShuttingDown,
+}
+
+/// Caps on the running jobs of a repository; caps left unset don't apply
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunLimits {
+    /// Jobs of the repository itself
+    pub repository: Option<usize>,
+    /// The organization it belongs to, and how many jobs of that
+    /// organization's repositories may run at once
+    pub organization: Option<(String, usize)>,
+}
+
+/// Jobs waiting to run, started as long as a worker is free and their
+/// repository is under its concurrency limit. Jobs of a higher priority go
+/// first. Among those, the job whose repository runs the fewest jobs goes
+/// next, then the one whose repository started a job least recently, and
+/// then the one queued first, so a busy repository takes turns with the
+/// others rather than holding them up. Jobs that can't start yet are skipped
+/// over.
+#[derive(Debug)]
+pub struct JobScheduler {
+    workers: usize,
+    /// By priority, then in the order they were queued
+    pending: VecDeque<QueuedJob>,
+    running: Vec<RunningJob>,
+    /// Jobs started so far, counting up
+    started: u64,
+    /// What `started` was when each repository last started a job
+    last_started: HashMap<String, u64>,
+}
+
+impl JobScheduler {
+    /// A scheduler running at most `workers` jobs at once
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            pending: VecDeque::new(),
+            running: Vec::new(),
+            started: 0,
+            last_started: HashMap::new(),
+        }
+    }
+
+    /// Queue a job behind the pending jobs of its priority or a higher one
+    pub fn push(&mut self, job: QueuedJob) {
+        let priority = job.priority();
+        let index = self
+            .pending
+            .iter()
+            .position(|pending| pending.priority() < priority)
+            .unwrap_or(self.pending.len());
+        self.pending.insert(index, job);
+    }
+
+    /// Take the pending job that goes next among those that can start now,
+    /// given the limits on running jobs of each repository, and give it the
+    /// first free worker. It counts as running until `finish` is called for
+    /// it.
+    pub fn next(&mut self, limits: impl Fn(&str) -> RunLimits) -> Option<QueuedJob> {
+        if self.running_count() >= self.workers {
+            return None;
+        }
+        let now = Utc::now();
+        let (_, index, limits) = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter_map(|(index, job)| {
+                let repository = &job.git_event.repository.full_name;
+                let limits = limits(repository);
+                if job.waiting_to_retry(now) || self.limit_reached(repository, &limits).is_some() {
+                    return None;
+                }
+                let turn = (
+                    std::cmp::Reverse(job.priority()),
+                    self.running.iter().filter(|running| &running.repository == repository).count(),
+                    self.last_started.get(repository).copied(),
+                );
+                Some((turn, index, limits))
+            })
+            .min_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)))?;
+        let job = self.pending.remove(index)?;
+        self.started += 1;
+        self.last_started.insert(job.git_event.repository.full_name.clone(), self.started);
+        let worker = (1..=self.workers)
+            .find(|worker| self.running.iter().all(|running| running.worker != *worker))
+            .unwrap_or(self.workers);
+        self.running.push(RunningJob {
+            id: job.id,
+            repository: job.git_event.repository.full_name.clone(),
+            worker,
+            organization: limits.organization.map(|(name, _)| name),
+            queued_at: job.queued_at,
+            started_at: Utc::now(),
+        });
+        Some(job)
+    }
+
+    /// Why a job of `repository` can't start yet given its limits, if it can't
+    fn limit_reached(&self, repository: &str, limits: &RunLimits) -> Option<WaitReason> {
+        let repository_runs = self.running.iter().filter(|job| job.repository == repository).count();
+        if limits.repository.is_some_and(|max| repository_runs >= max) {
+            return Some(WaitReason::RepositoryLimit);
+        }
+        let (organization, max) = limits.organization.as_ref()?;
+        let organization_runs = self
+            .running
+            .iter()
+            .filter(|job| job.organization.as_ref() == Some(organization))
+            .count();
+        (organization_runs >= *max).then_some(WaitReason::OrganizationLimit)
+    }
+
+    /// Take a job that hasn't started out of the queue
+    pub fn remove(&mut self, id: Uuid) -> Option<QueuedJob> {
+        let index = self.pending.iter().position(|job| job.id == id)?;
+        self.pending.remove(index)
+    }
+
+    /// Record that a job stopped running, freeing its worker
+    pub fn finish(&mut self, id: Uuid) {
+        self.running.retain(|job| job.id != id);
+    }
+
+    /// Jobs waiting to start, by priority and then in queue order
+    pub fn pending(&self) -> impl Iterator<Item = &QueuedJob> {
+        self.pending.iter()
+    }
+
+    /// Jobs running now, by worker
+    pub fn running(&self) -> Vec<RunningJob> {
+        let mut running = self.running.clone();
+        running.sort_by_key(|job| job.worker);
+        running
+    }
+
+    /// Why a pending job hasn't started, given the limits on running jobs
+    /// of each repository
+    pub fn wait_reason(&self, job: &QueuedJob, limits: impl Fn(&str) -> RunLimits) -> WaitReason {
+        let repository = &job.git_event.repository.full_name;
+        if job.waiting_to_retry(Utc::now()) {
+            WaitReason::RetryBackoff
+        } else if self.running_count() >= self.workers {
+            WaitReason::WorkersBusy
+        } else {
+            self.limit_reached(repository, &limits(repository)).unwrap_or(WaitReason::Starting)
+        }
+    }
+
+    /// When the first job waiting to be retried may start
+    pub fn next_retry_at(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        self.pending
+            .iter()
+            .filter(|job| job.waiting_to_retry(now))
+            .filter_map(|job| job.retry_at)
+            .min()
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    pub fn running_count(&self) -> usize {
+        self.running.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulsiora_core::{GitEventType, Repository};
+
+    fn create_test_event() -> GitEvent {
+        GitEvent {
+            event_type: GitEventType::Push,
+            repository: Repository {
+                owner: "test".to_string(),
+                name: "repo".to_string(),
+                full_name: "test/repo".to_string(),
+                clone_url: "https://github.com/test/repo.git".to_string(),
+                default_branch: "main".to_string(),
+            },
+            branch: Some("main".to_string()),
+            tag: None,
+            release_name: None,
+            pull_request: None,
+            commit_sha: None,
+            commit_message: None,
+            sender: "test".to_string(),
+            inputs: Default::default(),
+        }
+    }
+
+    fn job_for(repo: &str) -> QueuedJob {
+        let mut event = create_test_event();
+        event.repository.full_name = repo.to_string();
+        QueuedJob::new(event, String::new())
+    }
+
+    #[test]
+    fn test_scheduler_limits() {
+        let mut scheduler = JobScheduler::new(3);
+        let jobs: Vec<_> = ["a/busy", "a/busy", "a/busy", "b/quiet", "c/other"]
+            .into_iter()
+            .map(job_for)
+            .collect();
+        for job in &jobs {
+            scheduler.push(job.clone());
+        }
+        let limit = |repo: &str| RunLimits {
+            repository: (repo == "a/busy").then_some(2),
+            organization: None,
+        };
+
+        // b/quiet and c/other get a turn before a/busy starts a second job
+        let started: Vec<_> = std::iter::from_fn(|| scheduler.next(limit)).map(|j| j.id).collect();
+        assert_eq!(started, [jobs[0].id, jobs[3].id, jobs[4].id]);
+        assert_eq!(scheduler.running_count(), 3);
+        let pending: Vec<_> = scheduler.pending().map(|j| j.id).collect();
+        assert_eq!(pending, [jobs[1].id, jobs[2].id]);
+        assert!(scheduler.remove(jobs[0].id).is_none());
+        let workers: Vec<_> = scheduler.running().iter().map(|j| (j.worker, j.id)).collect();
+        assert_eq!(workers, [(1, jobs[0].id), (2, jobs[3].id), (3, jobs[4].id)]);
+        assert_eq!(scheduler.wait_reason(&jobs[1], limit), WaitReason::WorkersBusy);
+
+        // The third job of a/busy waits for one of its first two
+        scheduler.finish(jobs[3].id);
+        assert_eq!(scheduler.wait_reason(&jobs[1], limit), WaitReason::Starting);
+        assert_eq!(scheduler.next(limit).map(|j| j.id), Some(jobs[1].id));
+        assert_eq!(scheduler.running().iter().find(|j| j.id == jobs[1].id).unwrap().worker, 2);
+        scheduler.finish(jobs[4].id);
+        assert_eq!(scheduler.wait_reason(&jobs[2], limit), WaitReason::RepositoryLimit);
+        assert!(scheduler.next(limit).is_none());
+        scheduler.finish(jobs[0].id);
+        assert_eq!(scheduler.next(limit).map(|j| j.id), Some(jobs[2].id));
+        assert_eq!(scheduler.running().iter().find(|j| j.id == jobs[2].id).unwrap().worker, 1);
+        assert_eq!(scheduler.pending().count(), 0);
+
+        // A cancelled job leaves the queue without running
+        let cancelled = job_for("d/cancelled");
+        scheduler.push(cancelled.clone());
+        assert_eq!(scheduler.remove(cancelled.id), Some(cancelled));
+        assert_eq!(scheduler.pending().count(), 0);
+    }
+
+    #[test]
+    fn test_scheduler_organization_limit() {
+        let mut scheduler = JobScheduler::new(4);
+        let jobs: Vec<_> = ["acme/api", "acme/web", "acme/api", "globex/app"]
+            .into_iter()
+            .map(job_for)
+            .collect();
+        for job in &jobs {
+            scheduler.push(job.clone());
+        }
+        let limits = |repo: &str| RunLimits {
+            repository: None,
+            organization: repo.starts_with("acme/").then(|| ("acme".to_string(), 2)),
+        };
+
+        // The acme repos share two runs; globex isn't held up by them
+        let started: Vec<_> = std::iter::from_fn(|| scheduler.next(limits)).map(|j| j.id).collect();
+        assert_eq!(started, [jobs[0].id, jobs[1].id, jobs[3].id]);
+        assert_eq!(scheduler.wait_reason(&jobs[2], limits), WaitReason::OrganizationLimit);
+        scheduler.finish(jobs[1].id);
+        assert_eq!(scheduler.next(limits).map(|j| j.id), Some(jobs[2].id));
+    }
+
+    #[test]
+    fn test_scheduler_priority_and_fairness() {
+        let mut pull_request = job_for("a/busy");
+        pull_request.git_event.event_type = GitEventType::PullRequest;
+        let mut feature = job_for("a/busy");
+        feature.git_event.branch = Some("feature".to_string());
+        let mut tag = job_for("b/quiet");
+        tag.git_event.event_type = GitEventType::Tag;
+        tag.git_event.branch = None;
+        assert_eq!(pull_request.priority(), JobPriority::Low);
+        assert_eq!(feature.priority(), JobPriority::Normal);
+        assert_eq!(tag.priority(), JobPriority::High);
+        assert_eq!(job_for("a/busy").priority(), JobPriority::High);
+
+        // Higher priorities jump the queue
+        let mut scheduler = JobScheduler::new(1);
+        for job in [&pull_request, &feature, &tag] {
+            scheduler.push(job.clone());
+        }
+        let pending: Vec<_> = scheduler.pending().map(|j| j.id).collect();
+        assert_eq!(pending, [tag.id, feature.id, pull_request.id]);
+        let mut started = Vec::new();
+        while let Some(job) = scheduler.next(|_| RunLimits::default()) {
+            scheduler.finish(job.id);
+            started.push(job.id);
+        }
+        assert_eq!(started, [tag.id, feature.id, pull_request.id]);
+
+        // A repository with many jobs queued takes turns with the others
+        let jobs: Vec<_> = ["a/busy", "a/busy", "a/busy", "b/quiet", "c/other", "b/quiet"]
+            .into_iter()
+            .map(job_for)
+            .collect();
+        for job in &jobs {
+            scheduler.push(job.clone());
+        }
+        let mut started = Vec::new();
+        while let Some(job) = scheduler.next(|_| RunLimits::default()) {
+            scheduler.finish(job.id);
+            started.push(job.git_event.repository.full_name);
+        }
+        assert_eq!(started, ["c/other", "b/quiet", "a/busy", "b/quiet", "a/busy", "a/busy"]);
+    }
+
+    #[test]
+    fn test_retries() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1), Some(Duration::seconds(30)));
+        assert_eq!(policy.delay(2), Some(Duration::seconds(60)));
+        assert_eq!(policy.delay(3), None);
+        let long = RetryPolicy {
+            max_retries: 40,
+            backoff: Duration::minutes(10),
+        };
+        assert_eq!(long.delay(40), Some(Duration::hours(1)));
+        assert!(pulsiora_core::PulsioraError::CloneError("git failed".to_string()).is_infrastructure());
+        assert!(!pulsiora_core::PulsioraError::ParseError("bad step".to_string()).is_infrastructure());
+
+        let mut scheduler = JobScheduler::new(2);
+        let mut retried = job_for("a/repo");
+        retried.started_at = Some(Utc::now());
+        retried.retry_after("git failed".to_string(), Duration::minutes(5));
+        assert_eq!(retried.attempts[0].attempt, 1);
+        assert_eq!(retried.attempts[0].error, "git failed");
+        assert!(retried.started_at.is_none());
+        let waiting = job_for("b/repo");
+        scheduler.push(retried.clone());
+        scheduler.push(waiting.clone());
+
+        // A job waiting out its backoff lets the ones behind it go first
+        assert_eq!(scheduler.wait_reason(&retried, |_| RunLimits::default()), WaitReason::RetryBackoff);
+        assert_eq!(scheduler.next(|_| RunLimits::default()).map(|j| j.id), Some(waiting.id));
+        assert!(scheduler.next(|_| RunLimits::default()).is_none());
+        assert_eq!(scheduler.next_retry_at(), retried.retry_at);
+
+        retried.retry_after("agent lost".to_string(), Duration::zero());
+        scheduler.remove(retried.id);
+        scheduler.push(retried.clone());
+        assert_eq!(scheduler.next_retry_at(), None);
+        assert_eq!(scheduler.next(|_| RunLimits::default()).map(|j| j.attempts.len()), Some(2));
+    }
+
+    fn temp_journal(name: &str) -> (PathBuf, JobJournal) {
+        let dir = std::env::temp_dir().join(format!("pulsiora-queue-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let journal = JobJournal::open(&dir).unwrap();
+        (dir, journal)
+    }
+
+    #[test]
+    fn test_journal_persist_and_reload() {
+        let (dir, journal) = temp_journal("reload");
+        let first = QueuedJob::new(create_test_event(), "pipeline {}".to_string());
+        let second = QueuedJob::new(create_test_event(), "pipeline {}".to_string());
+        journal.persist(&second).unwrap();
+        journal.persist(&first).unwrap();
+
+        let reopened = JobJournal::open(&dir).unwrap();
+        let jobs = reopened.load_all().unwrap();
+        assert_eq!(jobs, vec![first.clone(), second]);
+
+        assert_eq!(reopened.load(first.id).unwrap(), Some(first.clone()));
+        reopened.remove(first.id).unwrap();
+        reopened.remove(first.id).unwrap();
+        assert_eq!(reopened.load(first.id).unwrap(), None);
+        assert_eq!(reopened.load_all().unwrap().len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_journal_skips_corrupt_entries() {
+        let (dir, journal) = temp_journal("corrupt");
+        fs::write(dir.join("broken.json"), "{not json").unwrap();
+        journal
+            .persist(&QueuedJob::new(create_test_event(), String::new()))
+            .unwrap();
+
+        assert_eq!(journal.load_all().unwrap().len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
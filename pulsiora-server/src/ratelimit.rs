@@ -0,0 +1,95 @@
+// Request rate limits per client. Each client, named by its API token or
+// else its address, has a bucket that holds up to a minute's worth of
+// requests and refills steadily, so short bursts are fine but sustained
+// floods are turned away.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets kept before full ones, which behave like new ones, are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Allows each client `per_minute` requests a minute
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute: per_minute.max(1),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a request from `client`'s bucket at `now`, or say how long until
+    /// the bucket has one
+    pub fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(self.per_minute);
+        let per_second = capacity / 60.0;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(client) {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.saturating_duration_since(bucket.updated).as_secs_f64() * per_second < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+}
+
+/// Seconds for a `Retry-After` header, rounded up so clients don't retry early
+pub fn retry_after_secs(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(60);
+        let start = Instant::now();
+        for _ in 0..60 {
+            limiter.check("ip:10.0.0.1", start).unwrap();
+        }
+        let wait = limiter.check("ip:10.0.0.1", start).unwrap_err();
+        assert_eq!(retry_after_secs(wait), 1);
+        // Other clients have their own bucket
+        limiter.check("ip:10.0.0.2", start).unwrap();
+
+        // A request a second comes back
+        limiter.check("ip:10.0.0.1", start + Duration::from_secs(1)).unwrap();
+        assert!(limiter.check("ip:10.0.0.1", start + Duration::from_secs(1)).is_err());
+        // The bucket never holds more than a minute's worth
+        let later = start + Duration::from_secs(3600);
+        for _ in 0..60 {
+            limiter.check("ip:10.0.0.1", later).unwrap();
+        }
+        assert!(limiter.check("ip:10.0.0.1", later).is_err());
+
+        assert_eq!(retry_after_secs(Duration::from_millis(1500)), 2);
+        assert_eq!(retry_after_secs(Duration::from_secs(3)), 3);
+    }
+}
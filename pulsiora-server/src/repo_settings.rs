@@ -0,0 +1,176 @@
+// Settings of a registered repository that change how its webhooks are
+// handled: the secret its payloads are signed with, the branch taken as its
+// default, and which events run pipelines. They're changed together with its
+// execution timeout through `PATCH /api/v1/repos/:repo/settings`, where
+// fields left out stay as they are and `null` clears one.
+
+use crate::storage::RegisteredRepo;
+use pulsiora_core::GitEventType;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Settings of a registered repository
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepoSettings {
+    /// Key its webhook payloads are signed with, instead of the server's
+    /// `webhook_secret`
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Branch runs without one use, and Pulsefiles are fetched from, instead
+    /// of the default branch events report
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// Webhook events that run pipelines; empty runs them for all
+    #[serde(default)]
+    pub allowed_events: Vec<GitEventType>,
+}
+
+impl RepoSettings {
+    /// Whether a webhook event of `event_type` runs pipelines
+    pub fn allows(&self, event_type: GitEventType) -> bool {
+        self.allowed_events.is_empty() || self.allowed_events.contains(&event_type)
+    }
+}
+
+/// Tell a field set to `null` from one left out, which serde's default
+/// leaves as None
+fn present<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// A change to a repository's settings. Fields left out are kept, and
+/// `null` clears one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoSettingsPatch {
+    #[serde(default, deserialize_with = "present")]
+    pub webhook_secret: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub default_branch: Option<Option<String>>,
+    /// Event names as in webhooks, e.g. `pull_request`
+    #[serde(default, deserialize_with = "present")]
+    pub allowed_events: Option<Option<Vec<String>>>,
+    /// Longest an execution may run, as its `max_runtime_secs` limit
+    #[serde(default, deserialize_with = "present")]
+    pub execution_timeout_secs: Option<Option<u64>>,
+}
+
+impl RepoSettingsPatch {
+    /// Apply the change to `repo`, or say why it's invalid and leave `repo`
+    /// as it was
+    pub fn apply(self, repo: &mut RegisteredRepo) -> Result<(), String> {
+        let mut settings = repo.settings.clone();
+        let mut limits = repo.limits.clone();
+        if let Some(secret) = self.webhook_secret {
+            if secret.as_deref().is_some_and(str::is_empty) {
+                return Err("webhook_secret can't be empty; set it to null to use the server's".to_string());
+            }
+            settings.webhook_secret = secret;
+        }
+        if let Some(branch) = self.default_branch {
+            if let Some(branch) = branch.as_deref().filter(|branch| !valid_branch_name(branch)) {
+                return Err(format!("Invalid default_branch {:?}", branch));
+            }
+            settings.default_branch = branch;
+        }
+        if let Some(events) = self.allowed_events {
+            settings.allowed_events = events
+                .unwrap_or_default()
+                .iter()
+                .map(|name| GitEventType::parse(name).ok_or_else(|| format!("Unknown event {:?}", name)))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(timeout) = self.execution_timeout_secs {
+            if timeout == Some(0) {
+                return Err("execution_timeout_secs must be at least 1".to_string());
+            }
+            limits.max_runtime_secs = timeout;
+        }
+        repo.settings = settings;
+        repo.limits = limits;
+        Ok(())
+    }
+}
+
+/// Whether `name` could be a git branch: no whitespace or characters git
+/// forbids in refs, and no leading `-` or `/`
+fn valid_branch_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(['-', '/'])
+        && !name.ends_with(['/', '.'])
+        && !name.contains("..")
+        && !name.chars().any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c))
+}
+
+/// A repository's settings as the API shows them, without the secret
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepoSettingsView {
+    /// Whether the repository has its own webhook secret
+    pub webhook_secret_set: bool,
+    pub default_branch: Option<String>,
+    pub allowed_events: Vec<&'static str>,
+    pub execution_timeout_secs: Option<u64>,
+}
+
+impl RepoSettingsView {
+    pub fn new(repo: &RegisteredRepo) -> Self {
+        Self {
+            webhook_secret_set: repo.settings.webhook_secret.is_some(),
+            default_branch: repo.settings.default_branch.clone(),
+            allowed_events: repo.settings.allowed_events.iter().map(|event| event.as_str()).collect(),
+            execution_timeout_secs: repo.limits.max_runtime_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance::repo;
+
+    fn patch(json: &str) -> RepoSettingsPatch {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_patch_repo_settings() {
+        let mut repo = repo();
+        patch(
+            r#"{"webhook_secret": "s3cret", "default_branch": "develop",
+                "allowed_events": ["push", "tag"], "execution_timeout_secs": 600}"#,
+        )
+        .apply(&mut repo)
+        .unwrap();
+        assert_eq!(repo.settings.webhook_secret.as_deref(), Some("s3cret"));
+        assert_eq!(repo.settings.default_branch.as_deref(), Some("develop"));
+        assert!(repo.settings.allows(GitEventType::Tag));
+        assert!(!repo.settings.allows(GitEventType::PullRequest));
+        assert_eq!(repo.limits.max_runtime_secs, Some(600));
+        let view = RepoSettingsView::new(&repo);
+        assert!(view.webhook_secret_set);
+        assert_eq!(view.allowed_events, ["push", "tag"]);
+
+        // Left out stays, null clears
+        patch(r#"{"default_branch": null, "allowed_events": null}"#).apply(&mut repo).unwrap();
+        assert_eq!(repo.settings.webhook_secret.as_deref(), Some("s3cret"));
+        assert_eq!(repo.settings.default_branch, None);
+        assert!(repo.settings.allows(GitEventType::PullRequest));
+        assert_eq!(repo.limits.max_runtime_secs, Some(600));
+
+        // Invalid changes leave everything as it was
+        let before = repo.clone();
+        for invalid in [
+            r#"{"default_branch": "main", "allowed_events": ["push", "deploy"]}"#,
+            r#"{"default_branch": "bad branch"}"#,
+            r#"{"webhook_secret": ""}"#,
+            r#"{"execution_timeout_secs": 0}"#,
+        ] {
+            assert!(patch(invalid).apply(&mut repo).is_err(), "{}", invalid);
+        }
+        assert_eq!(repo, before);
+        assert!(serde_json::from_str::<RepoSettingsPatch>(r#"{"timeout": 5}"#).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+// Deleting what the server keeps once it is older or larger than configured:
+// executions, with their logs and artifacts, and step logs on their own.
+
+use chrono::{DateTime, Utc};
+use pulsiora_core::{PipelineExecution, PipelineStatus, Result};
+use pulsiora_runner::dir_size;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+/// How much history is kept; limits left unset don't apply
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Finished executions kept per repository, newest first
+    pub max_executions_per_repo: Option<usize>,
+    /// Finished executions that ended longer ago than this are removed
+    pub max_execution_age: Option<Duration>,
+    /// Step logs not written to for this long are deleted
+    pub max_log_age: Option<Duration>,
+    /// Total size of the step logs on disk; the oldest are deleted first
+    pub max_log_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn prunes_executions(&self) -> bool {
+        self.max_executions_per_repo.is_some() || self.max_execution_age.is_some()
+    }
+
+    pub fn prunes_logs(&self) -> bool {
+        self.max_log_age.is_some() || self.max_log_bytes.is_some()
+    }
+}
+
+/// Executions the policy no longer keeps. Only finished ones are removed;
+/// running and interrupted executions don't count towards the limit either.
+pub fn expired_executions(
+    executions: &[PipelineExecution],
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Vec<Uuid> {
+    let cutoff = policy
+        .max_execution_age
+        .and_then(|age| chrono::Duration::from_std(age).ok())
+        .and_then(|age| now.checked_sub_signed(age));
+    let mut by_repo: HashMap<&str, Vec<&PipelineExecution>> = HashMap::new();
+    for execution in executions {
+        let finished = !matches!(
+            execution.status,
+            PipelineStatus::Pending | PipelineStatus::Running | PipelineStatus::Interrupted
+        );
+        if finished {
+            by_repo.entry(&execution.repository.full_name).or_default().push(execution);
+        }
+    }
+
+    let mut expired = Vec::new();
+    for mut finished in by_repo.into_values() {
+        finished.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+        for (rank, execution) in finished.into_iter().enumerate() {
+            let too_many = policy.max_executions_per_repo.is_some_and(|max| rank >= max);
+            let ended = execution.completed_at.unwrap_or(execution.started_at);
+            let too_old = cutoff.is_some_and(|cutoff| ended < cutoff);
+            if too_many || too_old {
+                expired.push(execution.id);
+            }
+        }
+    }
+    expired
+}
+
+/// Delete execution log directories under `dir`, least recently written
+/// first, until they take up no more than `max_bytes`. Directories named in
+/// `keep`, e.g. those of running executions, are left alone. Returns how
+/// many were deleted.
+pub fn prune_log_bytes(dir: &Path, max_bytes: u64, keep: &HashSet<String>) -> Result<usize> {
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let mut logs = Vec::new();
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_dir() {
+            continue;
+        }
+        let mut size = 0;
+        for file in fs::read_dir(entry.path())? {
+            size += file?.metadata()?.len();
+        }
+        total += size;
+        if !keep.contains(entry.file_name().to_string_lossy().as_ref()) {
+            logs.push((metadata.modified()?, entry.path(), size));
+        }
+    }
+
+    logs.sort();
+    let mut pruned = 0;
+    for (_, path, size) in logs {
+        if total <= max_bytes {
+            break;
+        }
+        fs::remove_dir_all(path)?;
+        total -= size;
+        pruned += 1;
+    }
+    Ok(pruned)
+}
+
+/// Delete the log directory of every execution under `dir` that hasn't been
+/// written to for `max_age`. Returns how many were deleted.
+pub fn prune_logs(dir: &Path, max_age: Duration) -> Result<usize> {
+    let Some(cutoff) = SystemTime::now().checked_sub(max_age) else {
+        return Ok(0);
+    };
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let mut pruned = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() && metadata.modified()? < cutoff {
+            fs::remove_dir_all(entry.path())?;
+            pruned += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+/// Bytes the step logs and artifacts of `executions` take up under the data
+/// directory; those moved to object storage don't count
+pub fn execution_disk_usage(data_dir: &Path, executions: &[Uuid]) -> Result<u64> {
+    let mut total = 0;
+    for execution_id in executions {
+        for dir in ["logs", "artifacts"] {
+            total += match dir_size(&data_dir.join(dir).join(execution_id.to_string())) {
+                Ok(size) => size,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+                Err(e) => return Err(e.into()),
+            };
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_prune_logs() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-logs-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("old")).unwrap();
+        fs::write(dir.join("old").join("0.stdout.log"), "done\n").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        fs::create_dir_all(dir.join("new")).unwrap();
+
+        assert_eq!(prune_logs(&dir, Duration::from_secs(3600)).unwrap(), 0);
+        assert_eq!(prune_logs(&dir, Duration::from_millis(25)).unwrap(), 1);
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("new").exists());
+        assert_eq!(prune_logs(&dir.join("missing"), Duration::ZERO).unwrap(), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prune_log_bytes() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-logs-{}", Uuid::new_v4()));
+        for name in ["oldest", "older", "running", "newest"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(dir.join(name).join("0.stdout.log"), [b'x'; 100]).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let keep: HashSet<String> = ["running".to_string()].into();
+
+        assert_eq!(prune_log_bytes(&dir, 400, &keep).unwrap(), 0);
+        assert_eq!(prune_log_bytes(&dir, 250, &keep).unwrap(), 2);
+        assert!(!dir.join("oldest").exists() && !dir.join("older").exists());
+        // Running executions keep their logs even over the limit
+        assert_eq!(prune_log_bytes(&dir, 0, &keep).unwrap(), 1);
+        assert!(dir.join("running").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_execution_disk_usage() {
+        let dir = std::env::temp_dir().join(format!("pulsiora-data-{}", Uuid::new_v4()));
+        let (counted, other) = (Uuid::new_v4(), Uuid::new_v4());
+        fs::create_dir_all(dir.join("logs").join(counted.to_string())).unwrap();
+        fs::write(dir.join("logs").join(counted.to_string()).join("0.stdout.log"), [b'x'; 100]).unwrap();
+        let reports = dir.join("artifacts").join(counted.to_string()).join("reports");
+        fs::create_dir_all(&reports).unwrap();
+        fs::write(reports.join("index.html"), [b'x'; 50]).unwrap();
+        fs::create_dir_all(dir.join("logs").join(other.to_string())).unwrap();
+        fs::write(dir.join("logs").join(other.to_string()).join("0.stdout.log"), [b'x'; 10]).unwrap();
+
+        assert_eq!(execution_disk_usage(&dir, &[counted]).unwrap(), 150);
+        assert_eq!(execution_disk_usage(&dir, &[counted, other, Uuid::new_v4()]).unwrap(), 160);
+        assert_eq!(execution_disk_usage(&dir, &[]).unwrap(), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_expired_executions() {
+        let now = Utc::now();
+        let execution = |repo: &str, days_ago: i64, status: PipelineStatus| {
+            let mut execution = crate::storage::conformance::execution(&[]);
+            execution.repository.full_name = repo.to_string();
+            execution.status = status;
+            execution.started_at = now - chrono::Duration::days(days_ago);
+            execution.completed_at = Some(execution.started_at);
+            execution
+        };
+        let executions = vec![
+            execution("acme/shop", 1, PipelineStatus::Success),
+            execution("acme/shop", 2, PipelineStatus::Failed),
+            execution("acme/shop", 3, PipelineStatus::Success),
+            execution("acme/shop", 40, PipelineStatus::Interrupted),
+            execution("acme/site", 40, PipelineStatus::Success),
+        ];
+
+        let per_repo = RetentionPolicy {
+            max_executions_per_repo: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(expired_executions(&executions, &per_repo, now), [executions[2].id]);
+
+        let by_age = RetentionPolicy {
+            max_execution_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            ..Default::default()
+        };
+        assert!(by_age.prunes_executions() && !by_age.prunes_logs());
+        assert_eq!(expired_executions(&executions, &by_age, now), [executions[4].id]);
+        assert!(expired_executions(&executions, &RetentionPolicy::default(), now).is_empty());
+    }
+}
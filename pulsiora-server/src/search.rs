@@ -0,0 +1,261 @@
+// Full-text search over the names and output of executions' steps, for
+// hunting errors that keep coming back. A query is a list of terms, with
+// double quotes keeping a phrase together; a step matches when its name,
+// stdout and stderr hold every term, ignoring ASCII case. Terms match
+// anywhere in a word, so `refus` finds "Connection refused". SQLite storage
+// keeps a trigram index of step output for this; otherwise executions are
+// scanned.
+
+use chrono::{DateTime, Utc};
+use pulsiora_core::{PipelineExecution, PipelineStatus, StepResult};
+use pulsiora_runner::strip_ansi;
+use serde::Serialize;
+use uuid::Uuid;
+
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+/// Shortest term searched for; the trigram index can't look up shorter ones
+pub const MIN_TERM_LEN: usize = 3;
+
+/// Longest snippet, in bytes, not counting the `…` marking a cut
+const MAX_SNIPPET_LEN: usize = 200;
+
+/// Most step matches returned per execution
+const MAX_MATCHES_PER_EXECUTION: usize = 10;
+
+/// A search of step names and output
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogSearch {
+    /// ASCII-lowercased terms, all of which a step must hold
+    pub terms: Vec<String>,
+    /// Only executions of these repositories, when set
+    pub repositories: Option<Vec<String>>,
+    /// Return at most this many executions, newest first
+    pub limit: usize,
+}
+
+impl LogSearch {
+    /// A search for the terms in `query`: words, or phrases in double quotes
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut terms = Vec::new();
+        for (index, part) in query.split('"').enumerate() {
+            // Odd parts were between quotes
+            if index % 2 == 1 {
+                terms.push(part.trim().to_string());
+            } else {
+                terms.extend(part.split_whitespace().map(String::from));
+            }
+        }
+        terms.retain(|term| !term.is_empty());
+        if terms.is_empty() {
+            return Err("Search for at least one term".to_string());
+        }
+        if let Some(short) = terms.iter().find(|term| term.chars().count() < MIN_TERM_LEN) {
+            return Err(format!("Search terms need at least {} characters, got {:?}", MIN_TERM_LEN, short));
+        }
+        Ok(Self {
+            terms: terms.iter().map(|term| term.to_ascii_lowercase()).collect(),
+            repositories: None,
+            limit: DEFAULT_SEARCH_LIMIT,
+        })
+    }
+
+    /// The search as an FTS5 query: every term as a quoted string
+    pub fn fts_query(&self) -> String {
+        self.terms
+            .iter()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Whether a step holds every term in its name or output
+    pub fn step_matches(&self, step: &StepResult) -> bool {
+        let text = format!("{}\n{}\n{}", step.step_name, strip_ansi(&step.stdout), strip_ansi(&step.stderr))
+            .to_ascii_lowercase();
+        self.terms.iter().all(|term| text.contains(term.as_str()))
+    }
+
+    /// Whether the execution is one searched and has a matching step
+    pub fn matches(&self, execution: &PipelineExecution) -> bool {
+        self.repositories
+            .as_ref()
+            .is_none_or(|repos| repos.contains(&execution.repository.full_name))
+            && execution.step_results.iter().any(|step| self.step_matches(step))
+    }
+}
+
+/// Where in a step a match was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogStream {
+    Name,
+    Stdout,
+    Stderr,
+}
+
+/// A line of a matching step holding one of the terms
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepMatch {
+    pub step_index: usize,
+    pub step_name: String,
+    pub stream: LogStream,
+    /// Line of the output, from 1; None for the step name
+    pub line: Option<usize>,
+    /// The line, cut around the first term found if it's long
+    pub snippet: String,
+    /// Byte ranges of the snippet holding a term, in order
+    pub highlights: Vec<(usize, usize)>,
+}
+
+/// An execution with steps matching a search
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub execution_id: Uuid,
+    pub repository: String,
+    pub pipeline_name: String,
+    pub status: PipelineStatus,
+    pub started_at: DateTime<Utc>,
+    pub matches: Vec<StepMatch>,
+}
+
+impl SearchResult {
+    /// The execution's matching steps, with the first line holding a term in
+    /// each of their name, stdout and stderr
+    pub fn new(execution: &PipelineExecution, search: &LogSearch) -> Self {
+        let mut matches = Vec::new();
+        for (step_index, step) in execution.step_results.iter().enumerate() {
+            if !search.step_matches(step) {
+                continue;
+            }
+            let streams = [
+                (LogStream::Name, strip_ansi(&step.step_name)),
+                (LogStream::Stdout, strip_ansi(&step.stdout)),
+                (LogStream::Stderr, strip_ansi(&step.stderr)),
+            ];
+            for (stream, text) in streams {
+                let Some((line, text)) = text
+                    .lines()
+                    .enumerate()
+                    .find(|(_, line)| !term_ranges(line, &search.terms).is_empty())
+                else {
+                    continue;
+                };
+                let (snippet, highlights) = snippet(text, &search.terms);
+                matches.push(StepMatch {
+                    step_index,
+                    step_name: step.step_name.clone(),
+                    stream,
+                    line: (stream != LogStream::Name).then_some(line + 1),
+                    snippet,
+                    highlights,
+                });
+            }
+        }
+        matches.truncate(MAX_MATCHES_PER_EXECUTION);
+        Self {
+            execution_id: execution.id,
+            repository: execution.repository.full_name.clone(),
+            pipeline_name: execution.pipeline_name.clone(),
+            status: execution.status,
+            started_at: execution.started_at,
+            matches,
+        }
+    }
+}
+
+/// Byte ranges of `text` holding one of `terms`, in order, overlapping ones
+/// merged
+fn term_ranges(text: &str, terms: &[String]) -> Vec<(usize, usize)> {
+    let lower = text.to_ascii_lowercase();
+    let mut ranges: Vec<(usize, usize)> = terms
+        .iter()
+        .flat_map(|term| lower.match_indices(term.as_str()).map(|(start, _)| (start, start + term.len())))
+        .collect();
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// `line` cut to at most `MAX_SNIPPET_LEN` bytes around its first term, with
+/// the ranges of the terms in what's left
+fn snippet(line: &str, terms: &[String]) -> (String, Vec<(usize, usize)>) {
+    let line = line.trim_end();
+    let first = term_ranges(line, terms).first().map_or(0, |range| range.0);
+    let mut start = 0;
+    let mut end = line.len();
+    if line.len() > MAX_SNIPPET_LEN {
+        start = first.saturating_sub(MAX_SNIPPET_LEN / 4);
+        while !line.is_char_boundary(start) {
+            start -= 1;
+        }
+        end = (start + MAX_SNIPPET_LEN).min(line.len());
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+    }
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    snippet.push_str(&line[start..end]);
+    if end < line.len() {
+        snippet.push('…');
+    }
+    let highlights = term_ranges(&snippet, terms);
+    (snippet, highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance::execution;
+
+    #[test]
+    fn test_log_search_parse() {
+        let search = LogSearch::parse(r#"  Timeout "Connection REFUSED"  "#).unwrap();
+        assert_eq!(search.terms, ["timeout", "connection refused"]);
+        assert_eq!(search.fts_query(), r#""timeout" "connection refused""#);
+        assert_eq!(search.limit, DEFAULT_SEARCH_LIMIT);
+        assert!(LogSearch::parse("  ").is_err());
+        assert!(LogSearch::parse(r#""""#).is_err());
+        assert!(LogSearch::parse("panic at").is_err());
+    }
+
+    #[test]
+    fn test_search_result_snippets() {
+        let mut failed = execution(&["build", "test"]);
+        failed.step_results[1].stdout = "running 3 tests\n\u{1b}[31mError\u{1b}[0m: connection refused (os error 111)\n".to_string();
+        failed.step_results[1].stderr = format!("{}ECONNREFUSED\n", "x".repeat(300));
+
+        let search = LogSearch::parse("refused").unwrap();
+        assert!(search.matches(&failed));
+        let result = SearchResult::new(&failed, &search);
+        assert_eq!(result.matches.len(), 2);
+        let stdout = &result.matches[0];
+        assert_eq!((stdout.step_index, stdout.stream, stdout.line), (1, LogStream::Stdout, Some(2)));
+        assert_eq!(stdout.snippet, "Error: connection refused (os error 111)");
+        assert_eq!(stdout.highlights, [(18, 25)]);
+        // Long lines are cut around the match
+        let stderr = &result.matches[1];
+        assert!(stderr.snippet.starts_with('…') && stderr.snippet.ends_with("ECONNREFUSED"));
+        let (start, end) = stderr.highlights[0];
+        assert_eq!(&stderr.snippet[start..end], "REFUSED");
+
+        // Every term has to be in the same step
+        assert!(LogSearch::parse("test refused").unwrap().matches(&failed));
+        assert!(!LogSearch::parse("build refused").unwrap().matches(&failed));
+        let mut elsewhere = LogSearch::parse("refused").unwrap();
+        elsewhere.repositories = Some(vec!["test/other".to_string()]);
+        assert!(!elsewhere.matches(&failed));
+
+        assert_eq!(term_ranges("abcabc", &["bca".to_string(), "cab".to_string()]), [(1, 5)]);
+    }
+}
@@ -0,0 +1,115 @@
+// Repository secrets, encrypted at rest with the server's master key. Each
+// value is sealed with AES-256-GCM under a fresh nonce and bound to its
+// repository and name, so a sealed value copied to another secret won't open.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use pulsiora_core::{PulsioraError, Result};
+
+/// Bytes of the nonce stored in front of each sealed value
+const NONCE_LEN: usize = 12;
+
+/// The master key secrets are encrypted with
+#[derive(Clone)]
+pub struct SecretsKey {
+    cipher: Aes256Gcm,
+}
+
+impl std::fmt::Debug for SecretsKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretsKey(..)")
+    }
+}
+
+impl SecretsKey {
+    /// A key given as 64 hex characters, e.g. from `openssl rand -hex 32`
+    pub fn from_hex(hex_key: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_key.trim())
+            .ok()
+            .filter(|bytes| bytes.len() == 32)
+            .ok_or_else(|| PulsioraError::InvalidConfiguration("secrets key must be 64 hex characters".to_string()))?;
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)),
+        })
+    }
+
+    /// `value` sealed for the secret `name` of `repo`, nonce first
+    pub fn encrypt(&self, repo: &str, name: &str, value: &str) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = associated_data(repo, name);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: value.as_bytes(), aad: &aad })
+            .map_err(|_| PulsioraError::StorageError("failed to encrypt secret".to_string()))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(sealed)
+    }
+
+    /// The value of a secret sealed by `encrypt`; fails if it was sealed with
+    /// another key or for another secret, or has been changed
+    pub fn decrypt(&self, repo: &str, name: &str, sealed: &[u8]) -> Result<String> {
+        let failed = || PulsioraError::StorageError(format!("failed to decrypt secret {} of {}", name, repo));
+        if sealed.len() < NONCE_LEN {
+            return Err(failed());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let aad = associated_data(repo, name);
+        let value = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| failed())?;
+        String::from_utf8(value).map_err(|_| failed())
+    }
+}
+
+fn associated_data(repo: &str, name: &str) -> Vec<u8> {
+    format!("{}\0{}", repo, name).into_bytes()
+}
+
+/// Whether `name` can name a secret or repository variable: an environment
+/// variable name that doesn't shadow the built-in `PULSE_*` variables
+pub fn valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("PULSE_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_key() {
+        let key = SecretsKey::from_hex(&"ab".repeat(32)).unwrap();
+        let sealed = key.encrypt("team/app", "TOKEN", "hunter2").unwrap();
+        assert!(!sealed.windows(7).any(|window| window == b"hunter2"));
+        assert_eq!(key.decrypt("team/app", "TOKEN", &sealed).unwrap(), "hunter2");
+        // Every value gets its own nonce
+        assert_ne!(key.encrypt("team/app", "TOKEN", "hunter2").unwrap(), sealed);
+
+        // Bound to the secret it was sealed for, and to the key
+        assert!(key.decrypt("team/other", "TOKEN", &sealed).is_err());
+        assert!(key.decrypt("team/app", "KEY", &sealed).is_err());
+        let other = SecretsKey::from_hex(&"cd".repeat(32)).unwrap();
+        assert!(other.decrypt("team/app", "TOKEN", &sealed).is_err());
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(key.decrypt("team/app", "TOKEN", &tampered).is_err());
+        assert!(key.decrypt("team/app", "TOKEN", &sealed[..4]).is_err());
+
+        assert!(SecretsKey::from_hex("abcd").is_err());
+        assert!(SecretsKey::from_hex(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_valid_env_name() {
+        assert!(valid_env_name("DEPLOY_TOKEN"));
+        assert!(valid_env_name("_key2"));
+        assert!(!valid_env_name(""));
+        assert!(!valid_env_name("2FA"));
+        assert!(!valid_env_name("MY-TOKEN"));
+        assert!(!valid_env_name("PULSE_BRANCH"));
+    }
+}
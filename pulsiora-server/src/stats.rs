@@ -0,0 +1,209 @@
+// Step analytics for a repository: how often each step of its pipelines
+// failed and how long it took over a window of recent executions, to find
+// the steps that fail the most and the flaky ones, which both failed and
+// passed on the same commit.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use pulsiora_core::{PipelineExecution, PipelineStatus, StepStatus};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// How a step of a pipeline fared over the window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepStats {
+    pub pipeline: String,
+    pub name: String,
+    /// Times it ran to success or failure
+    pub runs: usize,
+    pub failures: usize,
+    /// Share of its runs that failed, from 0 to 1
+    pub failure_rate: f64,
+    pub avg_duration_ms: u64,
+    pub max_duration_ms: u64,
+    /// Commits it both failed and passed on
+    pub flaky_commits: usize,
+    pub last_failed_at: Option<DateTime<Utc>>,
+}
+
+/// A repository's executions and steps over a window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepoStats {
+    pub repository: String,
+    /// First day of the window (UTC)
+    pub since: NaiveDate,
+    /// Only executions of this pipeline were counted
+    pub pipeline: Option<String>,
+    /// Executions that succeeded or failed; cancelled and skipped ones aren't
+    /// counted
+    pub executions: usize,
+    pub failed_executions: usize,
+    pub failure_rate: f64,
+    pub avg_duration_ms: u64,
+    /// Most failing first
+    pub steps: Vec<StepStats>,
+}
+
+/// Running totals of a step
+#[derive(Default)]
+struct StepTotals {
+    runs: usize,
+    failures: usize,
+    total_ms: u64,
+    max_ms: u64,
+    last_failed_at: Option<DateTime<Utc>>,
+    passed_on: HashSet<String>,
+    failed_on: HashSet<String>,
+}
+
+fn rate(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+/// Stats of `repository` from its executions started on or after `since`,
+/// of `pipeline` if given
+pub fn repo_stats(
+    repository: &str,
+    executions: &[PipelineExecution],
+    since: NaiveDate,
+    pipeline: Option<&str>,
+) -> RepoStats {
+    let mut counted = 0;
+    let mut failed = 0;
+    let mut total_ms = 0;
+    let mut steps: BTreeMap<(String, String), StepTotals> = BTreeMap::new();
+
+    for execution in executions {
+        if execution.started_at.date_naive() < since
+            || pipeline.is_some_and(|name| name != execution.pipeline_name)
+            || !matches!(execution.status, PipelineStatus::Success | PipelineStatus::Failed)
+        {
+            continue;
+        }
+        counted += 1;
+        if execution.status == PipelineStatus::Failed {
+            failed += 1;
+        }
+        total_ms += execution.metrics.wall_ms;
+
+        for step in &execution.step_results {
+            if !matches!(step.status, StepStatus::Success | StepStatus::Failed) {
+                continue;
+            }
+            let totals = steps
+                .entry((execution.pipeline_name.clone(), step.step_name.clone()))
+                .or_default();
+            totals.runs += 1;
+            totals.total_ms += step.duration_ms;
+            totals.max_ms = totals.max_ms.max(step.duration_ms);
+            let commits = if step.status == StepStatus::Failed {
+                totals.failures += 1;
+                let at = step.completed_at.unwrap_or(step.started_at);
+                totals.last_failed_at = totals.last_failed_at.max(Some(at));
+                &mut totals.failed_on
+            } else {
+                &mut totals.passed_on
+            };
+            if let Some(sha) = &execution.git_event.commit_sha {
+                commits.insert(sha.clone());
+            }
+        }
+    }
+
+    let mut steps: Vec<StepStats> = steps
+        .into_iter()
+        .map(|((pipeline, name), totals)| StepStats {
+            pipeline,
+            name,
+            runs: totals.runs,
+            failures: totals.failures,
+            failure_rate: rate(totals.failures, totals.runs),
+            avg_duration_ms: totals.total_ms / totals.runs.max(1) as u64,
+            max_duration_ms: totals.max_ms,
+            flaky_commits: totals.failed_on.intersection(&totals.passed_on).count(),
+            last_failed_at: totals.last_failed_at,
+        })
+        .collect();
+    // Stable, so steps failing as often stay in name order
+    steps.sort_by(|a, b| {
+        b.failures
+            .cmp(&a.failures)
+            .then(b.failure_rate.total_cmp(&a.failure_rate))
+    });
+
+    RepoStats {
+        repository: repository.to_string(),
+        since,
+        pipeline: pipeline.map(str::to_string),
+        executions: counted,
+        failed_executions: failed,
+        failure_rate: rate(failed, counted),
+        avg_duration_ms: total_ms / counted.max(1) as u64,
+        steps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance::execution;
+    use chrono::Duration;
+
+    fn run(commit: &str, outcomes: &[(&str, StepStatus, u64)]) -> PipelineExecution {
+        let names: Vec<&str> = outcomes.iter().map(|(name, _, _)| *name).collect();
+        let mut execution = execution(&names);
+        execution.git_event.commit_sha = Some(commit.to_string());
+        for (step, (_, status, ms)) in execution.step_results.iter_mut().zip(outcomes) {
+            step.status = *status;
+            step.duration_ms = *ms;
+        }
+        let failed = outcomes.iter().any(|(_, status, _)| *status == StepStatus::Failed);
+        execution.status = if failed { PipelineStatus::Failed } else { PipelineStatus::Success };
+        execution.metrics.wall_ms = outcomes.iter().map(|(_, _, ms)| ms).sum();
+        execution
+    }
+
+    #[test]
+    fn test_repo_stats() {
+        use StepStatus::*;
+        let mut old = run("a0", &[("build", Failed, 1)]);
+        old.started_at -= Duration::days(60);
+        let mut cancelled = run("a3", &[("build", Failed, 1)]);
+        cancelled.status = PipelineStatus::Cancelled;
+        let executions = vec![
+            run("a1", &[("build", Success, 100), ("test", Failed, 400)]),
+            // Retried on the same commit: `test` is flaky
+            run("a1", &[("build", Success, 300), ("test", Success, 200)]),
+            run("a2", &[("build", Failed, 200), ("test", Skipped, 0)]),
+            run("a2", &[("build", Success, 200), ("test", Failed, 600)]),
+            old,
+            cancelled,
+        ];
+        let since = Utc::now().date_naive() - chrono::Days::new(29);
+
+        let stats = repo_stats("test/repo", &executions, since, None);
+        assert_eq!(stats.executions, 4);
+        assert_eq!(stats.failed_executions, 3);
+        assert_eq!(stats.failure_rate, 0.75);
+        assert_eq!(stats.avg_duration_ms, 500);
+
+        let test = &stats.steps[0];
+        assert_eq!((test.name.as_str(), test.runs, test.failures), ("test", 3, 2));
+        assert_eq!(test.avg_duration_ms, 400);
+        assert_eq!(test.max_duration_ms, 600);
+        assert_eq!(test.flaky_commits, 1);
+        assert!(test.last_failed_at.is_some());
+        let build = &stats.steps[1];
+        assert_eq!((build.name.as_str(), build.runs, build.failures), ("build", 4, 1));
+        assert_eq!(build.failure_rate, 0.25);
+        assert_eq!(build.flaky_commits, 1);
+
+        let none = repo_stats("test/repo", &executions, since, Some("deploy"));
+        assert_eq!(none.executions, 0);
+        assert_eq!(none.failure_rate, 0.0);
+        assert!(none.steps.is_empty());
+    }
+}
@@ -1,9 +1,31 @@
-use pulsiora_core::PipelineExecution;
-use std::collections::HashMap;
+use crate::accounts::{hash_token, InstanceSettings, Organization, OrganizationQuotas, ShareLink, User};
+use crate::deployments::{self, Deployment, RollbackError};
+use crate::hooks::OutgoingWebhook;
+use crate::monorepo::PathPipeline;
+use crate::queue::RunLimits;
+use crate::repo_settings::{RepoSettings, RepoSettingsPatch};
+use crate::search::LogSearch;
+use crate::updates::{Update, UpdateHub};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use pulsiora_core::{
+    ExecutionLimits, PipelineExecution, PipelineStatus, PulsefileSource, RepoRole, Repository, Result, SandboxPolicy,
+    WebhookMapping,
+};
+use pulsiora_runner::ReplayBundle;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+use tracing::{info_span, warn, Instrument, Span};
 use uuid::Uuid;
 
+/// How many replaced Pulsefiles are kept per repository
+pub const MAX_PULSEFILE_HISTORY: usize = 50;
+
 /// Repository type
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RepoType {
     GitHub,
     Local,
@@ -11,20 +33,429 @@ pub enum RepoType {
 }
 
 /// Repository registration information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RegisteredRepo {
     pub repo_url: String,
     pub repo_identifier: String, // owner/repo format
     pub pulsefile: String,
+    /// Revision of `pulsefile`, counting from 1 and bumped each time it's replaced
+    #[serde(default = "first_pulsefile_revision")]
+    pub pulsefile_revision: u32,
+    /// Pulsefiles `pulsefile` replaced, oldest first, at most
+    /// `MAX_PULSEFILE_HISTORY` of them
+    #[serde(default)]
+    pub pulsefile_history: Vec<PulsefileRevision>,
     pub repo_type: RepoType,
+    pub status_contexts: Vec<StatusContext>,
+    /// Overrides for the instance-wide execution limits
+    pub limits: ExecutionLimits,
+    /// How steps are sandboxed when they run on the server host
+    #[serde(default)]
+    pub sandbox: SandboxPolicy,
+    /// Where the generic webhook finds event details in its payload
+    #[serde(default)]
+    pub webhook_mapping: WebhookMapping,
+    /// Organization whose base pipeline applies; defaults to the repo owner
+    pub organization: Option<String>,
+    /// Roles granted to users on this repository, by username
+    #[serde(default)]
+    pub roles: BTreeMap<String, RepoRole>,
+    /// Variables set for every step of the repository's executions, under
+    /// the step's own `env`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// URLs sent the repository's execution events
+    #[serde(default)]
+    pub hooks: Vec<OutgoingWebhook>,
+    /// Which Pulsefile its GitHub webhook events run
+    #[serde(default)]
+    pub pulsefile_source: PulsefileSource,
+    /// Pipelines run for changes under some paths, next to `pulsefile`
+    #[serde(default)]
+    pub path_pipelines: Vec<PathPipeline>,
+    /// Executions that deployed to an environment, oldest first
+    #[serde(default)]
+    pub deployments: Vec<Deployment>,
+    /// Webhook secret, default branch and events that run pipelines
+    #[serde(default)]
+    pub settings: RepoSettings,
+}
+
+fn first_pulsefile_revision() -> u32 {
+    1
+}
+
+/// A Pulsefile a repository ran before it was replaced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PulsefileRevision {
+    pub revision: u32,
+    pub pulsefile: String,
+    pub replaced_at: DateTime<Utc>,
+    /// User who replaced it, if it was replaced with a token
+    pub replaced_by: Option<String>,
+}
+
+impl PulsefileRevision {
+    pub fn pulsefile_hash(&self) -> String {
+        pulsefile_hash(&self.pulsefile)
+    }
+}
+
+pub(crate) fn pulsefile_hash(pulsefile: &str) -> String {
+    hex::encode(Sha256::digest(pulsefile.as_bytes()))
+}
+
+impl RegisteredRepo {
+    /// The repository as events describe it, for events that don't come
+    /// with the host's own details
+    pub fn repository(&self) -> Repository {
+        let (owner, name) = self
+            .repo_identifier
+            .split_once('/')
+            .unwrap_or(("", self.repo_identifier.as_str()));
+        Repository {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            full_name: self.repo_identifier.clone(),
+            clone_url: self.repo_url.clone(),
+            default_branch: self.settings.default_branch.clone().unwrap_or_else(|| "main".to_string()),
+        }
+    }
+
+    /// SHA-256 of the registered Pulsefile, in hex, to tell which version
+    /// the server has
+    pub fn pulsefile_hash(&self) -> String {
+        pulsefile_hash(&self.pulsefile)
+    }
+
+    /// Replace the Pulsefile, keeping the current one as a previous revision.
+    /// False, with nothing changed, if it's the same as the current one.
+    pub fn replace_pulsefile(&mut self, pulsefile: String, replaced_by: Option<String>) -> bool {
+        if pulsefile == self.pulsefile {
+            return false;
+        }
+        self.pulsefile_history.push(PulsefileRevision {
+            revision: self.pulsefile_revision,
+            pulsefile: std::mem::replace(&mut self.pulsefile, pulsefile),
+            replaced_at: Utc::now(),
+            replaced_by,
+        });
+        let excess = self.pulsefile_history.len().saturating_sub(MAX_PULSEFILE_HISTORY);
+        self.pulsefile_history.drain(..excess);
+        self.pulsefile_revision += 1;
+        true
+    }
+}
+
+/// Which executions `Storage::query_executions` returns; unset filters match
+/// every execution
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionQuery {
+    /// Repository identifier, `owner/repo`
+    pub repository: Option<String>,
+    /// Pipeline label
+    pub label: Option<String>,
+    pub status: Option<PipelineStatus>,
+    /// Branch the triggering event was for
+    pub branch: Option<String>,
+    /// Started at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Return at most this many, newest first
+    pub limit: Option<usize>,
+}
+
+impl ExecutionQuery {
+    pub fn matches(&self, execution: &PipelineExecution) -> bool {
+        self.repository.as_ref().is_none_or(|repo| &execution.repository.full_name == repo)
+            && self.label.as_ref().is_none_or(|label| execution.pipeline_labels.contains(label))
+            && self.status.is_none_or(|status| execution.status == status)
+            && self.branch.as_ref().is_none_or(|branch| execution.git_event.branch.as_ref() == Some(branch))
+            && self.since.is_none_or(|since| execution.started_at >= since)
+    }
+}
+
+/// Durable storage for executions, with their step results, registered
+/// repositories, repository secrets, users and organizations, so they survive
+/// a restart.
+/// Implementations are checked against the same conformance tests.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Store an execution, replacing one with the same ID
+    async fn store_execution(&self, execution: &PipelineExecution) -> Result<()>;
+    async fn get_execution(&self, id: Uuid) -> Result<Option<PipelineExecution>>;
+    /// Every execution, oldest first
+    async fn list_executions(&self) -> Result<Vec<PipelineExecution>>;
+    /// Executions matching `query`, newest first
+    async fn query_executions(&self, query: &ExecutionQuery) -> Result<Vec<PipelineExecution>>;
+    /// Executions with a step matching `search`, newest first
+    async fn search_executions(&self, search: &LogSearch) -> Result<Vec<PipelineExecution>>;
+    /// Remove an execution; false if it wasn't stored
+    async fn remove_execution(&self, id: Uuid) -> Result<bool>;
+
+    /// Store a repository, replacing one with the same identifier
+    async fn store_repo(&self, repo: &RegisteredRepo) -> Result<()>;
+    async fn get_repo(&self, repo_identifier: &str) -> Result<Option<RegisteredRepo>>;
+    /// Every repository, by identifier
+    async fn list_repos(&self) -> Result<Vec<RegisteredRepo>>;
+    /// Remove a repository, along with its secrets; false if it wasn't stored
+    async fn remove_repo(&self, repo_identifier: &str) -> Result<bool>;
+
+    /// Store a repository secret, replacing one with the same name. Values
+    /// are stored as given; encrypting them is up to the caller.
+    async fn store_secret(&self, repo_identifier: &str, name: &str, value: &[u8]) -> Result<()>;
+    async fn get_secret(&self, repo_identifier: &str, name: &str) -> Result<Option<Vec<u8>>>;
+    /// Names of a repository's secrets, sorted
+    async fn list_secrets(&self, repo_identifier: &str) -> Result<Vec<String>>;
+    /// Remove a secret; false if it wasn't stored
+    async fn remove_secret(&self, repo_identifier: &str, name: &str) -> Result<bool>;
+
+    /// Store a user, replacing one with the same username
+    async fn store_user(&self, user: &User) -> Result<()>;
+    /// Every user, by username
+    async fn list_users(&self) -> Result<Vec<User>>;
+    /// Remove a user; false if it wasn't stored
+    async fn remove_user(&self, username: &str) -> Result<bool>;
+
+    /// Store an organization, replacing one with the same name
+    async fn store_organization(&self, organization: &Organization) -> Result<()>;
+    /// Every organization, by name
+    async fn list_organizations(&self) -> Result<Vec<Organization>>;
+
+    /// Check that the storage can be reached, for readiness checks
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write a change made to the in-memory storage
+    async fn apply(&self, change: StorageChange) -> Result<()> {
+        match change {
+            StorageChange::Execution(execution) => self.store_execution(&execution).await,
+            StorageChange::ExecutionRemoved(id) => self.remove_execution(id).await.map(|_| ()),
+            StorageChange::Repo(repo) => self.store_repo(&repo).await,
+            StorageChange::RepoRemoved(repo_identifier) => self.remove_repo(&repo_identifier).await.map(|_| ()),
+            StorageChange::User(user) => self.store_user(&user).await,
+            StorageChange::UserRemoved(username) => self.remove_user(&username).await.map(|_| ()),
+            StorageChange::Organization(organization) => self.store_organization(&organization).await,
+            StorageChange::Secret { repo, name, value } => self.store_secret(&repo, &name, &value).await,
+            StorageChange::SecretRemoved { repo, name } => self.remove_secret(&repo, &name).await.map(|_| ()),
+        }
+    }
+}
+
+/// `Storage` kept in memory, for tests and servers that don't need to keep
+/// anything across restarts
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    executions: RwLock<HashMap<Uuid, PipelineExecution>>,
+    repos: RwLock<BTreeMap<String, RegisteredRepo>>,
+    /// Keyed by repository, then name
+    secrets: RwLock<BTreeMap<(String, String), Vec<u8>>>,
+    users: RwLock<BTreeMap<String, User>>,
+    organizations: RwLock<BTreeMap<String, Organization>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn store_execution(&self, execution: &PipelineExecution) -> Result<()> {
+        self.executions.write().unwrap().insert(execution.id, execution.clone());
+        Ok(())
+    }
+
+    async fn get_execution(&self, id: Uuid) -> Result<Option<PipelineExecution>> {
+        Ok(self.executions.read().unwrap().get(&id).cloned())
+    }
+
+    async fn list_executions(&self) -> Result<Vec<PipelineExecution>> {
+        let mut executions: Vec<_> = self.executions.read().unwrap().values().cloned().collect();
+        executions.sort_by_key(|e| e.started_at);
+        Ok(executions)
+    }
+
+    async fn query_executions(&self, query: &ExecutionQuery) -> Result<Vec<PipelineExecution>> {
+        let mut executions: Vec<_> = self
+            .executions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|e| query.matches(e))
+            .cloned()
+            .collect();
+        executions.sort_by_key(|e| std::cmp::Reverse(e.started_at));
+        executions.truncate(query.limit.unwrap_or(usize::MAX));
+        Ok(executions)
+    }
+
+    async fn search_executions(&self, search: &LogSearch) -> Result<Vec<PipelineExecution>> {
+        let mut executions: Vec<_> = self
+            .executions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|e| search.matches(e))
+            .cloned()
+            .collect();
+        executions.sort_by_key(|e| std::cmp::Reverse(e.started_at));
+        executions.truncate(search.limit);
+        Ok(executions)
+    }
+
+    async fn remove_execution(&self, id: Uuid) -> Result<bool> {
+        Ok(self.executions.write().unwrap().remove(&id).is_some())
+    }
+
+    async fn store_repo(&self, repo: &RegisteredRepo) -> Result<()> {
+        self.repos.write().unwrap().insert(repo.repo_identifier.clone(), repo.clone());
+        Ok(())
+    }
+
+    async fn get_repo(&self, repo_identifier: &str) -> Result<Option<RegisteredRepo>> {
+        Ok(self.repos.read().unwrap().get(repo_identifier).cloned())
+    }
+
+    async fn list_repos(&self) -> Result<Vec<RegisteredRepo>> {
+        Ok(self.repos.read().unwrap().values().cloned().collect())
+    }
+
+    async fn remove_repo(&self, repo_identifier: &str) -> Result<bool> {
+        self.secrets.write().unwrap().retain(|(repo, _), _| repo != repo_identifier);
+        Ok(self.repos.write().unwrap().remove(repo_identifier).is_some())
+    }
+
+    async fn store_secret(&self, repo_identifier: &str, name: &str, value: &[u8]) -> Result<()> {
+        let key = (repo_identifier.to_string(), name.to_string());
+        self.secrets.write().unwrap().insert(key, value.to_vec());
+        Ok(())
+    }
+
+    async fn get_secret(&self, repo_identifier: &str, name: &str) -> Result<Option<Vec<u8>>> {
+        let key = (repo_identifier.to_string(), name.to_string());
+        Ok(self.secrets.read().unwrap().get(&key).cloned())
+    }
+
+    async fn list_secrets(&self, repo_identifier: &str) -> Result<Vec<String>> {
+        let secrets = self.secrets.read().unwrap();
+        Ok(secrets
+            .keys()
+            .filter(|(repo, _)| repo == repo_identifier)
+            .map(|(_, name)| name.clone())
+            .collect())
+    }
+
+    async fn remove_secret(&self, repo_identifier: &str, name: &str) -> Result<bool> {
+        let key = (repo_identifier.to_string(), name.to_string());
+        Ok(self.secrets.write().unwrap().remove(&key).is_some())
+    }
+
+    async fn store_user(&self, user: &User) -> Result<()> {
+        self.users.write().unwrap().insert(user.username.clone(), user.clone());
+        Ok(())
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>> {
+        Ok(self.users.read().unwrap().values().cloned().collect())
+    }
+
+    async fn remove_user(&self, username: &str) -> Result<bool> {
+        Ok(self.users.write().unwrap().remove(username).is_some())
+    }
+
+    async fn store_organization(&self, organization: &Organization) -> Result<()> {
+        self.organizations
+            .write()
+            .unwrap()
+            .insert(organization.name.clone(), organization.clone());
+        Ok(())
+    }
+
+    async fn list_organizations(&self) -> Result<Vec<Organization>> {
+        Ok(self.organizations.read().unwrap().values().cloned().collect())
+    }
+}
+
+/// A change `InMemoryStorage` passes on to durable storage
+#[derive(Debug, Clone)]
+pub enum StorageChange {
+    Execution(Box<PipelineExecution>),
+    ExecutionRemoved(Uuid),
+    Repo(Box<RegisteredRepo>),
+    RepoRemoved(String),
+    User(Box<User>),
+    UserRemoved(String),
+    Organization(Box<Organization>),
+    /// A repository secret, already encrypted
+    Secret { repo: String, name: String, value: Vec<u8> },
+    SecretRemoved { repo: String, name: String },
+}
+
+impl StorageChange {
+    /// What changed, for logs and traces
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StorageChange::Execution(_) => "execution",
+            StorageChange::ExecutionRemoved(_) => "execution_removed",
+            StorageChange::Repo(_) => "repo",
+            StorageChange::RepoRemoved(_) => "repo_removed",
+            StorageChange::User(_) => "user",
+            StorageChange::UserRemoved(_) => "user_removed",
+            StorageChange::Organization(_) => "organization",
+            StorageChange::Secret { .. } => "secret",
+            StorageChange::SecretRemoved { .. } => "secret_removed",
+        }
+    }
+}
+
+/// A change on its way to durable storage, with the span it was made in so
+/// the write shows up in the same trace
+#[derive(Debug)]
+pub struct StorageWrite {
+    pub change: StorageChange,
+    pub span: Span,
 }
 
-/// In-memory storage for pipeline executions and registered repos
-/// In production, this would be replaced with a database
+/// Write changes to `storage` as they arrive, in order, until every sender
+/// is gone. Failures are logged; the in-memory copy stays authoritative.
+pub async fn write_through(storage: Arc<dyn Storage>, mut changes: mpsc::UnboundedReceiver<StorageWrite>) {
+    while let Some(write) = changes.recv().await {
+        let span = info_span!(parent: &write.span, "storage.write", change = write.change.kind());
+        if let Err(e) = storage.apply(write.change).instrument(span).await {
+            warn!(error = %e, "Failed to write to storage");
+        }
+    }
+}
+
+/// Maps a pipeline to the GitHub commit status context it reports under
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusContext {
+    pub pipeline: String,
+    pub context: String,
+    /// Required contexts are enforced through branch protection
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// In-memory storage for pipeline executions and registered repos. Executions
+/// and repos can be loaded from durable `Storage` and written through to it.
 pub struct InMemoryStorage {
     executions: HashMap<Uuid, PipelineExecution>,
     registered_repos: HashMap<String, RegisteredRepo>, // key: repo_identifier
     executions_by_repo: HashMap<String, Vec<Uuid>>, // repo_identifier -> execution IDs
+    users: HashMap<String, User>,                    // key: username
+    organizations: HashMap<String, Organization>,    // key: name
+    settings: InstanceSettings,
+    replays: HashMap<Uuid, ReplayBundle>, // key: execution ID
+    share_links: HashMap<Uuid, ShareLink>, // key: link ID
+    /// Encrypted repository secrets, keyed by repository, then name
+    secrets: BTreeMap<(String, String), Vec<u8>>,
+    /// Where changes to executions and repos are sent, if they're persisted
+    changes: Option<mpsc::UnboundedSender<StorageWrite>>,
+    /// Where execution status changes are announced to live clients
+    updates: Option<UpdateHub>,
 }
 
 impl InMemoryStorage {
@@ -33,18 +464,133 @@ impl InMemoryStorage {
             executions: HashMap::new(),
             registered_repos: HashMap::new(),
             executions_by_repo: HashMap::new(),
+            users: HashMap::new(),
+            organizations: HashMap::new(),
+            settings: InstanceSettings::default(),
+            replays: HashMap::new(),
+            share_links: HashMap::new(),
+            secrets: BTreeMap::new(),
+            changes: None,
+            updates: None,
+        }
+    }
+
+    /// Load what durable storage holds and send later changes to `changes`
+    pub async fn restore(&mut self, storage: &dyn Storage, changes: mpsc::UnboundedSender<StorageWrite>) -> Result<()> {
+        for execution in storage.list_executions().await? {
+            self.store_execution(execution);
+        }
+        for repo in storage.list_repos().await? {
+            for name in storage.list_secrets(&repo.repo_identifier).await? {
+                if let Some(value) = storage.get_secret(&repo.repo_identifier, &name).await? {
+                    self.secrets.insert((repo.repo_identifier.clone(), name), value);
+                }
+            }
+            self.register_repo(repo);
+        }
+        for user in storage.list_users().await? {
+            self.create_user(user);
+        }
+        for organization in storage.list_organizations().await? {
+            self.create_organization(organization);
+        }
+        self.changes = Some(changes);
+        Ok(())
+    }
+
+    fn changed(&self, change: impl FnOnce() -> StorageChange) {
+        if let Some(changes) = &self.changes {
+            // Only fails once the writer has stopped, at shutdown
+            let _ = changes.send(StorageWrite {
+                change: change(),
+                span: Span::current(),
+            });
+        }
+    }
+
+    /// Announce execution status changes, e.g. `Pending` to `Running`, on `hub`
+    pub fn announce_updates(&mut self, hub: UpdateHub) {
+        self.updates = Some(hub);
+    }
+
+    fn status_changed(&self, execution: &PipelineExecution) {
+        if let Some(updates) = &self.updates {
+            updates.publish(Update::status(execution));
+        }
+    }
+
+    /// Whether changes are still written to durable storage; false if they
+    /// never were or the writer has stopped
+    pub fn persisting(&self) -> bool {
+        self.changes.as_ref().is_some_and(|changes| !changes.is_closed())
+    }
+
+    /// Stop sending changes to durable storage, so the writer finishes once it
+    /// has written the ones already sent. Later changes stay in memory only.
+    pub fn stop_persisting(&mut self) {
+        self.changes = None;
+    }
+
+    fn repo_changed(&self, repo_identifier: &str) {
+        if let Some(repo) = self.registered_repos.get(repo_identifier) {
+            self.changed(|| StorageChange::Repo(Box::new(repo.clone())));
         }
     }
 
     pub fn store_execution(&mut self, execution: PipelineExecution) {
+        self.changed(|| StorageChange::Execution(Box::new(execution.clone())));
         let repo_id = execution.repository.full_name.clone();
-        self.executions.insert(execution.id, execution.clone());
+        let id = execution.id;
+        if self.executions.get(&id).map(|stored| stored.status) != Some(execution.status) {
+            self.status_changed(&execution);
+        }
+        // Storing an execution again, e.g. once it's resumed, replaces it
+        if self.executions.insert(id, execution).is_some() {
+            return;
+        }
         
         // Track executions by repo
         self.executions_by_repo
             .entry(repo_id)
-            .or_insert_with(Vec::new)
-            .push(execution.id);
+            .or_default()
+            .push(id);
+    }
+
+    /// Change a stored execution's status; false if there is no such execution
+    pub fn set_execution_status(&mut self, id: Uuid, status: PipelineStatus) -> bool {
+        match self.executions.get_mut(&id) {
+            Some(execution) => {
+                execution.status = status;
+                let execution = execution.clone();
+                self.status_changed(&execution);
+                self.changed(|| StorageChange::Execution(Box::new(execution)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark an execution that hasn't finished as ended now with `status`
+    pub fn end_execution(&mut self, id: Uuid, status: PipelineStatus) -> Option<PipelineExecution> {
+        let execution = self.executions.get_mut(&id)?;
+        execution.status = status;
+        execution.completed_at = Some(Utc::now());
+        let execution = execution.clone();
+        self.status_changed(&execution);
+        self.changed(|| StorageChange::Execution(Box::new(execution.clone())));
+        Some(execution)
+    }
+
+    /// Forget an execution, along with its replay bundle and share links
+    pub fn remove_execution(&mut self, id: Uuid) -> Option<PipelineExecution> {
+        let execution = self.executions.remove(&id)?;
+        if let Some(ids) = self.executions_by_repo.get_mut(&execution.repository.full_name) {
+            ids.retain(|other| *other != id);
+        }
+        self.replays.remove(&id);
+        self.share_links.retain(|_, link| link.execution_id != id);
+        self.changed(|| StorageChange::ExecutionRemoved(id));
+        Some(execution)
     }
 
     pub fn get_execution(&self, id: &str) -> Option<&PipelineExecution> {
@@ -56,6 +602,48 @@ impl InMemoryStorage {
         self.executions.values().cloned().collect()
     }
 
+    /// Executions matching `query`, newest first; the ID breaks ties so the
+    /// order is the same on every call
+    pub fn query_executions(&self, query: &ExecutionQuery) -> Vec<PipelineExecution> {
+        let mut executions: Vec<PipelineExecution> =
+            self.executions.values().filter(|e| query.matches(e)).cloned().collect();
+        executions.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+        executions.truncate(query.limit.unwrap_or(usize::MAX));
+        executions
+    }
+
+    /// Executions with a step matching `search`, newest first
+    pub fn search_executions(&self, search: &LogSearch) -> Vec<PipelineExecution> {
+        let mut executions: Vec<PipelineExecution> =
+            self.executions.values().filter(|e| search.matches(e)).cloned().collect();
+        executions.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+        executions.truncate(search.limit);
+        executions
+    }
+
+    pub fn store_replay(&mut self, bundle: ReplayBundle) {
+        self.replays.insert(bundle.execution_id, bundle);
+    }
+
+    pub fn get_replay(&self, execution_id: &str) -> Option<&ReplayBundle> {
+        let uuid = Uuid::parse_str(execution_id).ok()?;
+        self.replays.get(&uuid)
+    }
+
+    /// Executions of pipelines carrying `label`
+    pub fn list_executions_with_label(&self, label: &str) -> Vec<PipelineExecution> {
+        self.executions
+            .values()
+            .filter(|e| e.pipeline_labels.iter().any(|l| l == label))
+            .cloned()
+            .collect()
+    }
+
+    /// IDs of a repository's executions, in the order they were stored
+    pub fn execution_ids_by_repo(&self, repo_identifier: &str) -> Vec<Uuid> {
+        self.executions_by_repo.get(repo_identifier).cloned().unwrap_or_default()
+    }
+
     pub fn get_executions_by_repo(&self, repo_identifier: &str, limit: usize) -> Vec<PipelineExecution> {
         let execution_ids = self.executions_by_repo
             .get(repo_identifier)
@@ -68,28 +656,550 @@ impl InMemoryStorage {
             .collect();
         
         // Sort by started_at descending (most recent first)
-        executions.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        executions.sort_by_key(|e| std::cmp::Reverse(e.started_at));
         
         executions.into_iter().take(limit).collect()
     }
 
     pub fn register_repo(&mut self, repo: RegisteredRepo) {
+        self.changed(|| StorageChange::Repo(Box::new(repo.clone())));
         self.registered_repos.insert(repo.repo_identifier.clone(), repo);
     }
 
     pub fn unregister_repo(&mut self, repo_identifier: &str) -> bool {
-        self.registered_repos.remove(repo_identifier).is_some()
+        let removed = self.registered_repos.remove(repo_identifier).is_some();
+        if removed {
+            self.secrets.retain(|(repo, _), _| repo != repo_identifier);
+            self.changed(|| StorageChange::RepoRemoved(repo_identifier.to_string()));
+        }
+        removed
     }
 
-    pub fn get_repo_pulsefile(&self, repo_identifier: &str) -> Option<String> {
+    /// Store an encrypted secret of a repository, replacing one with the same name
+    pub fn set_secret(&mut self, repo_identifier: &str, name: &str, value: Vec<u8>) {
+        self.changed(|| StorageChange::Secret {
+            repo: repo_identifier.to_string(),
+            name: name.to_string(),
+            value: value.clone(),
+        });
+        self.secrets.insert((repo_identifier.to_string(), name.to_string()), value);
+    }
+
+    /// Remove a secret of a repository; false if it wasn't stored
+    pub fn remove_secret(&mut self, repo_identifier: &str, name: &str) -> bool {
+        let removed = self.secrets.remove(&(repo_identifier.to_string(), name.to_string())).is_some();
+        if removed {
+            self.changed(|| StorageChange::SecretRemoved {
+                repo: repo_identifier.to_string(),
+                name: name.to_string(),
+            });
+        }
+        removed
+    }
+
+    /// A repository's encrypted secrets by name, sorted
+    pub fn repo_secrets(&self, repo_identifier: &str) -> Vec<(String, Vec<u8>)> {
+        self.secrets
+            .iter()
+            .filter(|((repo, _), _)| repo == repo_identifier)
+            .map(|((_, name), value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// A repo's stored Pulsefile and its revision
+    pub fn get_repo_pulsefile(&self, repo_identifier: &str) -> Option<(String, u32)> {
         self.registered_repos
             .get(repo_identifier)
-            .map(|r| r.pulsefile.clone())
+            .map(|r| (r.pulsefile.clone(), r.pulsefile_revision))
+    }
+
+    /// Replace a repo's stored Pulsefile, see `RegisteredRepo::replace_pulsefile`.
+    /// Returns its revision afterwards, or None if the repo isn't registered.
+    pub fn update_repo_pulsefile(&mut self, repo_identifier: &str, pulsefile: String, replaced_by: Option<&str>) -> Option<u32> {
+        let repo = self.registered_repos.get_mut(repo_identifier)?;
+        let revision = if repo.replace_pulsefile(pulsefile, replaced_by.map(str::to_string)) {
+            let revision = repo.pulsefile_revision;
+            self.repo_changed(repo_identifier);
+            revision
+        } else {
+            repo.pulsefile_revision
+        };
+        Some(revision)
+    }
+
+    pub fn get_registered_repo(&self, repo_identifier: &str) -> Option<RegisteredRepo> {
+        self.registered_repos.get(repo_identifier).cloned()
+    }
+
+    /// Every registered repository, by identifier
+    pub fn registered_repos(&self) -> Vec<&RegisteredRepo> {
+        let mut repos: Vec<_> = self.registered_repos.values().collect();
+        repos.sort_by(|a, b| a.repo_identifier.cmp(&b.repo_identifier));
+        repos
     }
 
     pub fn is_repo_registered(&self, repo_identifier: &str) -> bool {
         self.registered_repos.contains_key(repo_identifier)
     }
+
+    /// Whether first-run setup has created an admin account
+    pub fn is_initialized(&self) -> bool {
+        self.users.values().any(|u| u.admin)
+    }
+
+    pub fn create_organization(&mut self, organization: Organization) {
+        self.changed(|| StorageChange::Organization(Box::new(organization.clone())));
+        self.organizations.insert(organization.name.clone(), organization);
+    }
+
+    pub fn get_organization(&self, name: &str) -> Option<&Organization> {
+        self.organizations.get(name)
+    }
+
+    /// Organizations, by name
+    pub fn list_organizations(&self) -> Vec<Organization> {
+        let mut organizations: Vec<Organization> = self.organizations.values().cloned().collect();
+        organizations.sort_by(|a, b| a.name.cmp(&b.name));
+        organizations
+    }
+
+    /// Whether the instance hosts more than one organization; each one is
+    /// then kept to its own repositories and executions
+    pub fn is_multi_tenant(&self) -> bool {
+        self.organizations.len() > 1
+    }
+
+    /// The organization set up first. Its admins run the instance: they
+    /// create the other organizations and set their quotas.
+    pub fn operator_organization(&self) -> Option<&Organization> {
+        self.organizations
+            .values()
+            .min_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.name.cmp(&b.name)))
+    }
+
+    pub fn is_operator(&self, user: &User) -> bool {
+        user.admin && self.operator_organization().is_some_and(|org| org.name == user.organization)
+    }
+
+    /// Set an organization's quotas; false if the organization doesn't exist
+    pub fn set_organization_quotas(&mut self, organization: &str, quotas: OrganizationQuotas) -> bool {
+        match self.organizations.get_mut(organization) {
+            Some(org) => {
+                org.quotas = quotas;
+                let org = org.clone();
+                self.changed(|| StorageChange::Organization(Box::new(org)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Registered repositories that belong to an organization, by identifier
+    pub fn organization_repos(&self, organization: &str) -> Vec<String> {
+        self.registered_repos()
+            .into_iter()
+            .map(|repo| repo.repo_identifier.clone())
+            .filter(|repo| self.organization_for_repo(repo).is_some_and(|org| org.name == organization))
+            .collect()
+    }
+
+    /// Caps on running jobs that apply to a repository: its own, and its
+    /// organization's
+    pub fn run_limits(&self, repo_identifier: &str) -> RunLimits {
+        RunLimits {
+            repository: self.effective_limits(repo_identifier).max_concurrent_runs,
+            organization: self
+                .organization_for_repo(repo_identifier)
+                .and_then(|org| org.quotas.max_concurrent_runs.map(|max| (org.name.clone(), max))),
+        }
+    }
+
+    /// Set or clear an organization's base Pulsefile; false if the organization doesn't exist
+    pub fn set_base_pulsefile(&mut self, organization: &str, pulsefile: Option<String>) -> bool {
+        match self.organizations.get_mut(organization) {
+            Some(org) => {
+                org.base_pulsefile = pulsefile;
+                let org = org.clone();
+                self.changed(|| StorageChange::Organization(Box::new(org)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Organization a repository belongs to: the one it was registered with,
+    /// or else the organization named like the repository owner
+    pub fn organization_for_repo(&self, repo_identifier: &str) -> Option<&Organization> {
+        let name = self
+            .registered_repos
+            .get(repo_identifier)
+            .and_then(|r| r.organization.as_deref())
+            .or_else(|| repo_identifier.split('/').next())?;
+        self.organizations.get(name)
+    }
+
+    /// Base Pulsefile that applies to a repository's pipelines, if any
+    pub fn base_pulsefile_for(&self, repo_identifier: &str) -> Option<String> {
+        self.organization_for_repo(repo_identifier)?.base_pulsefile.clone()
+    }
+
+    pub fn create_user(&mut self, user: User) {
+        self.changed(|| StorageChange::User(Box::new(user.clone())));
+        self.users.insert(user.username.clone(), user);
+    }
+
+    pub fn get_user(&self, username: &str) -> Option<&User> {
+        self.users.get(username)
+    }
+
+    /// Users of an organization, by username
+    pub fn list_users(&self, organization: &str) -> Vec<User> {
+        let mut users: Vec<User> = self
+            .users
+            .values()
+            .filter(|u| u.organization == organization)
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        users
+    }
+
+    /// Remove a user along with the roles they were granted; false if there
+    /// is no such user
+    pub fn remove_user(&mut self, username: &str) -> bool {
+        if self.users.remove(username).is_none() {
+            return false;
+        }
+        self.changed(|| StorageChange::UserRemoved(username.to_string()));
+        let granted: Vec<String> = self
+            .registered_repos
+            .values_mut()
+            .filter_map(|repo| repo.roles.remove(username).map(|_| repo.repo_identifier.clone()))
+            .collect();
+        for repo_identifier in granted {
+            self.repo_changed(&repo_identifier);
+        }
+        true
+    }
+
+    /// Grant a user a role on a repository, or take it away with None; false
+    /// if the repository isn't registered
+    pub fn set_repo_role(&mut self, repo_identifier: &str, username: &str, role: Option<RepoRole>) -> bool {
+        let Some(repo) = self.registered_repos.get_mut(repo_identifier) else { return false };
+        match role {
+            Some(role) => repo.roles.insert(username.to_string(), role),
+            None => repo.roles.remove(username),
+        };
+        self.repo_changed(repo_identifier);
+        true
+    }
+
+    /// What `user` may do with a repository. Admins have every permission on
+    /// their organization's repositories and on those that belong to no
+    /// organization, other members of the organization can view them, and
+    /// roles granted on the repository add to that. Once the instance hosts
+    /// several organizations, only operators manage repositories outside them.
+    pub fn repo_role(&self, user: &User, repo_identifier: &str) -> Option<RepoRole> {
+        let repo = self.registered_repos.get(repo_identifier)?;
+        let implied = match self.organization_for_repo(repo_identifier) {
+            Some(organization) if organization.name != user.organization => None,
+            Some(_) if user.admin => Some(RepoRole::Admin),
+            Some(_) => Some(RepoRole::Viewer),
+            None if user.admin && (!self.is_multi_tenant() || self.is_operator(user)) => Some(RepoRole::Admin),
+            None => None,
+        };
+        implied.max(repo.roles.get(&user.username).copied())
+    }
+
+    pub fn find_user_by_token(&self, token: &str) -> Option<&User> {
+        let hash = hash_token(token);
+        self.users.values().find(|u| u.token_hash == hash)
+    }
+
+    pub fn create_share_link(&mut self, link: ShareLink) {
+        self.share_links.insert(link.id, link);
+    }
+
+    /// The link a token belongs to, if it is still active
+    pub fn find_share_link(&self, token: &str, now: DateTime<Utc>) -> Option<&ShareLink> {
+        let hash = hash_token(token);
+        self.share_links
+            .values()
+            .find(|link| link.token_hash == hash && link.is_active(now))
+    }
+
+    /// Links created for an execution, oldest first, including revoked and expired ones
+    pub fn share_links_for(&self, execution_id: &str) -> Vec<ShareLink> {
+        let Ok(execution_id) = Uuid::parse_str(execution_id) else { return Vec::new() };
+        let mut links: Vec<ShareLink> = self
+            .share_links
+            .values()
+            .filter(|link| link.execution_id == execution_id)
+            .cloned()
+            .collect();
+        links.sort_by_key(|link| link.created_at);
+        links
+    }
+
+    /// Revoke a link of an execution; revoking twice keeps the first time
+    pub fn revoke_share_link(&mut self, execution_id: &str, link_id: &str, now: DateTime<Utc>) -> Option<&ShareLink> {
+        let execution_id = Uuid::parse_str(execution_id).ok()?;
+        let link = self
+            .share_links
+            .get_mut(&Uuid::parse_str(link_id).ok()?)
+            .filter(|link| link.execution_id == execution_id)?;
+        link.revoked_at.get_or_insert(now);
+        Some(link)
+    }
+
+    pub fn settings(&self) -> &InstanceSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut InstanceSettings {
+        &mut self.settings
+    }
+
+    pub fn get_repo_type(&self, repo_identifier: &str) -> Option<RepoType> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.repo_type.clone())
+    }
+
+    pub fn get_status_contexts(&self, repo_identifier: &str) -> Option<Vec<StatusContext>> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.status_contexts.clone())
+    }
+
+    /// Replace a repo's status contexts; returns false if the repo isn't registered
+    pub fn set_status_contexts(&mut self, repo_identifier: &str, contexts: Vec<StatusContext>) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                repo.status_contexts = contexts;
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_repo_limits(&self, repo_identifier: &str) -> Option<ExecutionLimits> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.limits.clone())
+    }
+
+    /// Replace a repo's limit overrides; returns false if the repo isn't registered
+    pub fn set_repo_limits(&mut self, repo_identifier: &str, limits: ExecutionLimits) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                repo.limits = limits;
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_repo_sandbox(&self, repo_identifier: &str) -> Option<SandboxPolicy> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.sandbox.clone())
+    }
+
+    /// Replace a repo's sandbox policy; returns false if the repo isn't registered
+    pub fn set_repo_sandbox(&mut self, repo_identifier: &str, sandbox: SandboxPolicy) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                repo.sandbox = sandbox;
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_repo_env(&self, repo_identifier: &str) -> Option<BTreeMap<String, String>> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.env.clone())
+    }
+
+    /// Replace a repo's environment variables; returns false if the repo isn't registered
+    pub fn set_repo_env(&mut self, repo_identifier: &str, env: BTreeMap<String, String>) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                repo.env = env;
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Choose which Pulsefile a repo's webhook events run; false if the repo
+    /// isn't registered
+    pub fn set_repo_pulsefile_source(&mut self, repo_identifier: &str, source: PulsefileSource) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                repo.pulsefile_source = source;
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a path-scoped pipeline to a repo, replacing the one with the same
+    /// name; false if the repo isn't registered
+    pub fn set_path_pipeline(&mut self, repo_identifier: &str, pipeline: PathPipeline) -> bool {
+        let Some(repo) = self.registered_repos.get_mut(repo_identifier) else {
+            return false;
+        };
+        match repo.path_pipelines.iter_mut().find(|p| p.name == pipeline.name) {
+            Some(existing) => *existing = pipeline,
+            None => repo.path_pipelines.push(pipeline),
+        }
+        self.repo_changed(repo_identifier);
+        true
+    }
+
+    /// Remove a repo's path-scoped pipeline; false if it has no such pipeline
+    pub fn remove_path_pipeline(&mut self, repo_identifier: &str, name: &str) -> bool {
+        let Some(repo) = self.registered_repos.get_mut(repo_identifier) else {
+            return false;
+        };
+        let before = repo.path_pipelines.len();
+        repo.path_pipelines.retain(|p| p.name != name);
+        let removed = repo.path_pipelines.len() < before;
+        if removed {
+            self.repo_changed(repo_identifier);
+        }
+        removed
+    }
+
+    /// Webhooks sent a repo's execution events; empty if the repo isn't registered
+    pub fn repo_hooks(&self, repo_identifier: &str) -> Vec<OutgoingWebhook> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.hooks.clone())
+            .unwrap_or_default()
+    }
+
+    /// Change a repo's settings, returning the repo as changed; None if it
+    /// isn't registered
+    pub fn update_repo_settings(
+        &mut self,
+        repo_identifier: &str,
+        patch: RepoSettingsPatch,
+    ) -> Option<std::result::Result<RegisteredRepo, String>> {
+        let repo = self.registered_repos.get_mut(repo_identifier)?;
+        let updated = patch.apply(repo).map(|()| repo.clone());
+        if updated.is_ok() {
+            self.repo_changed(repo_identifier);
+        }
+        Some(updated)
+    }
+
+    /// Deployments of a repo, oldest first
+    pub fn repo_deployments(&self, repo_identifier: &str) -> Vec<Deployment> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.deployments.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record a deployment of a repo; returns false if the repo isn't
+    /// registered
+    pub fn record_deployment(&mut self, repo_identifier: &str, deployment: Deployment) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                deployments::record_deployment(&mut repo.deployments, deployment);
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark an environment of a repo as rolled back, see
+    /// `deployments::mark_rollback`; None if the repo isn't registered
+    pub fn mark_rollback(
+        &mut self,
+        repo_identifier: &str,
+        environment: &str,
+        to: Option<Uuid>,
+        by: &str,
+    ) -> Option<std::result::Result<Deployment, RollbackError>> {
+        let repo = self.registered_repos.get_mut(repo_identifier)?;
+        let marked = deployments::mark_rollback(&mut repo.deployments, environment, to, by);
+        if marked.is_ok() {
+            self.repo_changed(repo_identifier);
+        }
+        Some(marked)
+    }
+
+    /// Add a webhook to a repo; returns false if the repo isn't registered
+    pub fn add_repo_hook(&mut self, repo_identifier: &str, hook: OutgoingWebhook) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                repo.hooks.push(hook);
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a webhook from a repo; false if it has no such webhook
+    pub fn remove_repo_hook(&mut self, repo_identifier: &str, id: Uuid) -> bool {
+        let Some(repo) = self.registered_repos.get_mut(repo_identifier) else {
+            return false;
+        };
+        let before = repo.hooks.len();
+        repo.hooks.retain(|hook| hook.id != id);
+        let removed = repo.hooks.len() < before;
+        if removed {
+            self.repo_changed(repo_identifier);
+        }
+        removed
+    }
+
+    pub fn get_repo_webhook_mapping(&self, repo_identifier: &str) -> Option<WebhookMapping> {
+        self.registered_repos
+            .get(repo_identifier)
+            .map(|r| r.webhook_mapping.clone())
+    }
+
+    /// Replace a repo's generic webhook mapping; returns false if the repo isn't registered
+    pub fn set_repo_webhook_mapping(&mut self, repo_identifier: &str, mapping: WebhookMapping) -> bool {
+        match self.registered_repos.get_mut(repo_identifier) {
+            Some(repo) => {
+                repo.webhook_mapping = mapping;
+                self.repo_changed(repo_identifier);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Limits for executions of a repo: instance-wide limits with repo overrides applied
+    pub fn effective_limits(&self, repo_identifier: &str) -> ExecutionLimits {
+        match self.registered_repos.get(repo_identifier) {
+            Some(repo) => self.settings.limits.overridden_by(&repo.limits),
+            None => self.settings.limits.clone(),
+        }
+    }
+
+    /// Status context for a pipeline, falling back to `pulsiora/<pipeline>`
+    pub fn status_context_for(&self, repo_identifier: &str, pipeline: &str) -> String {
+        self.registered_repos
+            .get(repo_identifier)
+            .and_then(|r| r.status_contexts.iter().find(|c| c.pipeline == pipeline))
+            .map(|c| c.context.clone())
+            .unwrap_or_else(|| format!("pulsiora/{}", pipeline))
+    }
 }
 
 impl Default for InMemoryStorage {
@@ -98,10 +1208,224 @@ impl Default for InMemoryStorage {
     }
 }
 
+/// Checks every `Storage` implementation is run against, so they behave the same
+#[cfg(test)]
+pub(crate) mod conformance {
+    use super::*;
+    use pulsiora_core::{ExecutionMetrics, GitEvent, GitEventType, Repository, StepResult, StepStatus};
+
+    pub fn execution(steps: &[&str]) -> PipelineExecution {
+        let repository = Repository {
+            owner: "test".to_string(),
+            name: "repo".to_string(),
+            full_name: "test/repo".to_string(),
+            clone_url: "https://github.com/test/repo.git".to_string(),
+            default_branch: "main".to_string(),
+        };
+        PipelineExecution {
+            id: Uuid::new_v4(),
+            pipeline_name: "ci".to_string(),
+            pipeline_version: "1.0".to_string(),
+            pipeline_labels: Vec::new(),
+            environment: None,
+            repository: repository.clone(),
+            git_event: GitEvent {
+                event_type: GitEventType::Push,
+                repository,
+                branch: Some("main".to_string()),
+                tag: None,
+                release_name: None,
+                pull_request: None,
+                commit_sha: None,
+                commit_message: None,
+                sender: "test".to_string(),
+                inputs: Default::default(),
+            },
+            status: PipelineStatus::Success,
+            step_results: steps
+                .iter()
+                .map(|name| StepResult {
+                    step_name: name.to_string(),
+                    status: StepStatus::Success,
+                    stdout: format!("{} ok\n", name),
+                    stderr: String::new(),
+                    exit_code: Some(0),
+                    duration_ms: 5,
+                    started_at: Utc::now(),
+                    completed_at: Some(Utc::now()),
+                    encoding: None,
+                    replacement_chars: 0,
+                    stdout_bytes: 0,
+                    stderr_bytes: 0,
+                    log_ref: None,
+                    hooks: vec![],
+                    annotations: vec![],
+                    coverage: None,
+                    resources: None,
+                })
+                .collect(),
+            phases: Vec::new(),
+            limit_exceeded: None,
+            coverage: None,
+            metrics: ExecutionMetrics::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
+            started_at: Utc::now(),
+            completed_at: Some(Utc::now()),
+        }
+    }
+
+    pub fn repo() -> RegisteredRepo {
+        RegisteredRepo {
+            repo_url: "https://github.com/test/repo".to_string(),
+            repo_identifier: "test/repo".to_string(),
+            pulsefile: "pipeline {}".to_string(),
+            pulsefile_revision: 1,
+            pulsefile_history: Vec::new(),
+            repo_type: RepoType::Other("gitea".to_string()),
+            status_contexts: vec![],
+            limits: Default::default(),
+            sandbox: Default::default(),
+            webhook_mapping: Default::default(),
+            organization: None,
+            roles: Default::default(),
+            env: Default::default(),
+            hooks: Default::default(),
+            pulsefile_source: Default::default(),
+            path_pipelines: Vec::new(),
+            deployments: Vec::new(),
+            settings: Default::default(),
+        }
+    }
+
+    pub async fn check_storage(storage: &dyn Storage) {
+        // Executions, replaced whole when stored again
+        let mut first = execution(&["build", "test"]);
+        first.started_at = Utc::now() - chrono::Duration::minutes(2);
+        first.pipeline_labels = vec!["deploy".to_string()];
+        storage.store_execution(&first).await.unwrap();
+        first.step_results.truncate(1);
+        first.status = PipelineStatus::Failed;
+        storage.store_execution(&first).await.unwrap();
+        let mut second = execution(&[]);
+        second.started_at = Utc::now() - chrono::Duration::minutes(1);
+        second.repository.full_name = "test/other".to_string();
+        storage.store_execution(&second).await.unwrap();
+        let mut third = execution(&["lint"]);
+        third.git_event.branch = Some("release".to_string());
+        storage.store_execution(&third).await.unwrap();
+
+        let stored = storage.get_execution(first.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, PipelineStatus::Failed);
+        assert_eq!(stored.step_results.len(), 1);
+        assert_eq!(stored.step_results[0].stdout, "build ok\n");
+        assert!(storage.get_execution(Uuid::new_v4()).await.unwrap().is_none());
+        let ids = |executions: Vec<PipelineExecution>| executions.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(storage.list_executions().await.unwrap()), [first.id, second.id, third.id]);
+
+        // Queries, newest first
+        let query = |query: ExecutionQuery| async move { ids(storage.query_executions(&query).await.unwrap()) };
+        assert_eq!(query(ExecutionQuery::default()).await, [third.id, second.id, first.id]);
+        let repository = Some("test/repo".to_string());
+        assert_eq!(query(ExecutionQuery { repository, ..Default::default() }).await, [third.id, first.id]);
+        let label = Some("deploy".to_string());
+        assert_eq!(query(ExecutionQuery { label, ..Default::default() }).await, [first.id]);
+        let status = Some(PipelineStatus::Success);
+        assert_eq!(query(ExecutionQuery { status, limit: Some(1), ..Default::default() }).await, [third.id]);
+        let unknown = Some("nope".to_string());
+        assert!(query(ExecutionQuery { label: unknown, ..Default::default() }).await.is_empty());
+        let branch = Some("release".to_string());
+        assert_eq!(query(ExecutionQuery { branch, ..Default::default() }).await, [third.id]);
+        let since = Some(second.started_at);
+        assert_eq!(query(ExecutionQuery { since, ..Default::default() }).await, [third.id, second.id]);
+        let found = storage.query_executions(&ExecutionQuery { limit: Some(3), ..Default::default() }).await.unwrap();
+        assert_eq!(found[0].step_results[0].step_name, "lint");
+
+        // Searches of step names and output, newest first; output replaced
+        // when an execution is stored again is no longer found
+        let search = |terms: &str, limit: usize| {
+            let mut search = LogSearch::parse(terms).unwrap();
+            search.limit = limit;
+            async move { ids(storage.search_executions(&search).await.unwrap()) }
+        };
+        assert_eq!(search("LINT", 10).await, [third.id]);
+        assert_eq!(search("\"build ok\"", 10).await, [first.id]);
+        assert!(search("\"test ok\"", 10).await.is_empty());
+        third.step_results[0].stderr = "warning: unused build cache\n".to_string();
+        storage.store_execution(&third).await.unwrap();
+        assert_eq!(search("build", 10).await, [third.id, first.id]);
+        assert_eq!(search("build", 1).await, [third.id]);
+        assert_eq!(search("\"unused build\"", 10).await, [third.id]);
+        let mut elsewhere = LogSearch::parse("build").unwrap();
+        elsewhere.repositories = Some(vec!["test/other".to_string()]);
+        assert!(storage.search_executions(&elsewhere).await.unwrap().is_empty());
+
+        assert!(storage.remove_execution(second.id).await.unwrap());
+        assert!(!storage.remove_execution(second.id).await.unwrap());
+        assert_eq!(ids(storage.list_executions().await.unwrap()), [first.id, third.id]);
+
+        // Repositories, with their secrets
+        let mut other = repo();
+        other.repo_identifier = "test/other".to_string();
+        storage.store_repo(&other).await.unwrap();
+        storage.store_repo(&repo()).await.unwrap();
+        assert_eq!(storage.list_repos().await.unwrap(), vec![other.clone(), repo()]);
+        assert_eq!(storage.get_repo("test/repo").await.unwrap(), Some(repo()));
+        assert_eq!(storage.get_repo("test/none").await.unwrap(), None);
+
+        storage.store_secret("test/repo", "TOKEN", b"one").await.unwrap();
+        storage.store_secret("test/repo", "TOKEN", b"two").await.unwrap();
+        storage.store_secret("test/repo", "KEY", &[0, 255]).await.unwrap();
+        storage.store_secret("test/other", "TOKEN", b"other").await.unwrap();
+        assert_eq!(storage.get_secret("test/repo", "TOKEN").await.unwrap(), Some(b"two".to_vec()));
+        assert_eq!(storage.get_secret("test/repo", "KEY").await.unwrap(), Some(vec![0, 255]));
+        assert_eq!(storage.get_secret("test/repo", "NONE").await.unwrap(), None);
+        assert_eq!(storage.list_secrets("test/repo").await.unwrap(), ["KEY", "TOKEN"]);
+        assert!(storage.remove_secret("test/repo", "KEY").await.unwrap());
+        assert!(!storage.remove_secret("test/repo", "KEY").await.unwrap());
+        assert_eq!(storage.list_secrets("test/repo").await.unwrap(), ["TOKEN"]);
+
+        assert!(storage.remove_repo("test/repo").await.unwrap());
+        assert!(!storage.remove_repo("test/repo").await.unwrap());
+        assert!(storage.list_secrets("test/repo").await.unwrap().is_empty());
+        assert_eq!(storage.list_secrets("test/other").await.unwrap(), ["TOKEN"]);
+        assert_eq!(storage.list_repos().await.unwrap(), vec![other]);
+
+        // Users and organizations; times are whole seconds, which every
+        // backend keeps exactly
+        let created_at = DateTime::from_timestamp(1_767_225_600, 0).unwrap();
+        let user = |username: &str, admin: bool| User {
+            username: username.to_string(),
+            organization: "acme".to_string(),
+            admin,
+            token_hash: hash_token(username),
+            created_at,
+        };
+        storage.store_user(&user("bob", false)).await.unwrap();
+        storage.store_user(&user("alice", false)).await.unwrap();
+        storage.store_user(&user("alice", true)).await.unwrap();
+        assert_eq!(storage.list_users().await.unwrap(), vec![user("alice", true), user("bob", false)]);
+        assert!(storage.remove_user("bob").await.unwrap());
+        assert!(!storage.remove_user("bob").await.unwrap());
+        assert_eq!(storage.list_users().await.unwrap(), vec![user("alice", true)]);
+
+        let mut acme = Organization {
+            name: "acme".to_string(),
+            base_pulsefile: None,
+            quotas: Default::default(),
+            created_at,
+        };
+        storage.store_organization(&acme).await.unwrap();
+        acme.base_pulsefile = Some("pipeline {}".to_string());
+        storage.store_organization(&acme).await.unwrap();
+        assert_eq!(storage.list_organizations().await.unwrap(), vec![acme]);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pulsiora_core::{GitEvent, GitEventType, Repository, PipelineStatus};
+    use pulsiora_core::{ExecutionMetrics, GitEvent, GitEventType, Repository, PipelineStatus};
     use chrono::Utc;
     use uuid::Uuid;
 
@@ -119,19 +1443,30 @@ mod tests {
             repository: repo.clone(),
             branch: Some("main".to_string()),
             tag: None,
+            release_name: None,
             pull_request: None,
             commit_sha: None,
+            commit_message: None,
             sender: "test".to_string(),
+            inputs: Default::default(),
         };
 
         PipelineExecution {
             id,
             pipeline_name: "test".to_string(),
             pipeline_version: "1.0".to_string(),
+            pipeline_labels: vec![],
+            environment: None,
             repository: repo,
             git_event: event,
             status: PipelineStatus::Success,
             step_results: vec![],
+            phases: vec![],
+            limit_exceeded: None,
+            coverage: None,
+            metrics: ExecutionMetrics::default(),
+            pulsefile_revision: None,
+            attempts: Vec::new(),
             started_at: Utc::now(),
             completed_at: Some(Utc::now()),
         }
@@ -148,6 +1483,51 @@ mod tests {
 
         assert!(retrieved.is_some());
         assert_eq!(retrieved.unwrap().id, id);
+
+        // Stored again once an interrupted execution is resumed
+        assert!(storage.set_execution_status(id, PipelineStatus::Interrupted));
+        assert_eq!(storage.get_execution(&id.to_string()).unwrap().status, PipelineStatus::Interrupted);
+        storage.store_execution(execution);
+        assert_eq!(storage.get_executions_by_repo("test/repo", 10).len(), 1);
+        assert!(!storage.set_execution_status(Uuid::new_v4(), PipelineStatus::Running));
+
+        let cancelled = storage.end_execution(id, PipelineStatus::Cancelled).unwrap();
+        assert_eq!(cancelled.status, PipelineStatus::Cancelled);
+        assert!(cancelled.completed_at.is_some());
+        assert!(storage.end_execution(Uuid::new_v4(), PipelineStatus::Cancelled).is_none());
+    }
+
+    #[test]
+    fn test_storage_share_links() {
+        let mut storage = InMemoryStorage::new();
+        let execution_id = Uuid::new_v4();
+        let now = Utc::now();
+        let link = ShareLink {
+            id: Uuid::new_v4(),
+            execution_id,
+            token_hash: hash_token("pls_share"),
+            created_by: "alice".to_string(),
+            created_at: now,
+            expires_at: now + chrono::Duration::days(1),
+            revoked_at: None,
+        };
+        storage.create_share_link(link.clone());
+
+        assert_eq!(storage.find_share_link("pls_share", now).unwrap().id, link.id);
+        assert!(storage.find_share_link("pls_other", now).is_none());
+        assert!(storage
+            .find_share_link("pls_share", now + chrono::Duration::days(2))
+            .is_none());
+
+        // Links can only be revoked through their own execution
+        let other = Uuid::new_v4().to_string();
+        assert!(storage.revoke_share_link(&other, &link.id.to_string(), now).is_none());
+        let revoked = storage
+            .revoke_share_link(&execution_id.to_string(), &link.id.to_string(), now)
+            .unwrap();
+        assert_eq!(revoked.revoked_at, Some(now));
+        assert!(storage.find_share_link("pls_share", now).is_none());
+        assert_eq!(storage.share_links_for(&execution_id.to_string()).len(), 1);
     }
 
     #[test]
@@ -169,5 +1549,399 @@ mod tests {
 
         let executions = storage.list_executions();
         assert_eq!(executions.len(), 2);
+
+        assert_eq!(storage.remove_execution(id1).unwrap().id, id1);
+        assert!(storage.remove_execution(id1).is_none());
+        assert!(storage.get_execution(&id1.to_string()).is_none());
+        assert_eq!(storage.get_executions_by_repo("test/repo", 10).len(), 1);
+    }
+
+    #[test]
+    fn test_storage_filters_executions_by_label() {
+        let mut storage = InMemoryStorage::new();
+        let mut labelled = create_test_execution(Uuid::new_v4());
+        labelled.pipeline_labels = vec!["deploy".to_string()];
+        storage.store_execution(labelled.clone());
+        storage.store_execution(create_test_execution(Uuid::new_v4()));
+
+        let found = storage.list_executions_with_label("deploy");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, labelled.id);
+        assert!(storage.list_executions_with_label("nightly").is_empty());
+    }
+
+    #[test]
+    fn test_storage_users_and_initialization() {
+        let mut storage = InMemoryStorage::new();
+        assert!(!storage.is_initialized());
+
+        storage.create_user(User {
+            username: "alice".to_string(),
+            organization: "acme".to_string(),
+            admin: true,
+            token_hash: hash_token("pls_secret"),
+            created_at: Utc::now(),
+        });
+
+        assert!(storage.is_initialized());
+        assert_eq!(storage.find_user_by_token("pls_secret").unwrap().username, "alice");
+        assert!(storage.find_user_by_token("pls_other").is_none());
+    }
+
+    #[test]
+    fn test_storage_repo_roles() {
+        let mut storage = InMemoryStorage::new();
+        let now = Utc::now();
+        storage.create_organization(Organization {
+            name: "acme".to_string(),
+            base_pulsefile: None,
+            quotas: Default::default(),
+            created_at: now,
+        });
+        let user = |username: &str, organization: &str, admin: bool| User {
+            username: username.to_string(),
+            organization: organization.to_string(),
+            admin,
+            token_hash: hash_token(username),
+            created_at: now,
+        };
+        let admin = user("alice", "acme", true);
+        let member = user("bob", "acme", false);
+        let outsider = user("carol", "globex", true);
+        for user in [&admin, &member, &outsider] {
+            storage.create_user(user.clone());
+        }
+        let mut repo = conformance::repo();
+        repo.repo_identifier = "acme/shop".to_string();
+        storage.register_repo(repo.clone());
+        repo.repo_identifier = "someone/tool".to_string();
+        storage.register_repo(repo);
+
+        assert_eq!(storage.repo_role(&admin, "acme/shop"), Some(RepoRole::Admin));
+        assert_eq!(storage.repo_role(&member, "acme/shop"), Some(RepoRole::Viewer));
+        assert_eq!(storage.repo_role(&outsider, "acme/shop"), None);
+        // Repositories outside any organization are only open to admins
+        assert_eq!(storage.repo_role(&outsider, "someone/tool"), Some(RepoRole::Admin));
+        assert_eq!(storage.repo_role(&member, "someone/tool"), None);
+        assert_eq!(storage.repo_role(&admin, "acme/none"), None);
+
+        // Granted roles add to what membership gives, never take away
+        assert!(storage.set_repo_role("acme/shop", "bob", Some(RepoRole::Developer)));
+        assert!(storage.set_repo_role("acme/shop", "carol", Some(RepoRole::Viewer)));
+        assert!(storage.set_repo_role("acme/shop", "alice", Some(RepoRole::Viewer)));
+        assert!(!storage.set_repo_role("acme/none", "bob", Some(RepoRole::Admin)));
+        assert_eq!(storage.repo_role(&member, "acme/shop"), Some(RepoRole::Developer));
+        assert_eq!(storage.repo_role(&outsider, "acme/shop"), Some(RepoRole::Viewer));
+        assert_eq!(storage.repo_role(&admin, "acme/shop"), Some(RepoRole::Admin));
+        assert!(storage.set_repo_role("acme/shop", "bob", None));
+        assert_eq!(storage.repo_role(&member, "acme/shop"), Some(RepoRole::Viewer));
+
+        assert_eq!(storage.list_users("acme"), vec![admin, member]);
+        assert!(storage.remove_user("carol"));
+        assert!(!storage.remove_user("carol"));
+        assert!(storage.get_user("carol").is_none());
+        assert!(!storage.get_registered_repo("acme/shop").unwrap().roles.contains_key("carol"));
+    }
+
+    #[test]
+    fn test_storage_tenancy() {
+        let mut storage = InMemoryStorage::new();
+        let now = Utc::now();
+        for (name, created_at) in [("ops", now), ("acme", now + chrono::Duration::seconds(1))] {
+            storage.create_organization(Organization {
+                name: name.to_string(),
+                base_pulsefile: None,
+                quotas: Default::default(),
+                created_at,
+            });
+        }
+        let user = |username: &str, organization: &str| User {
+            username: username.to_string(),
+            organization: organization.to_string(),
+            admin: true,
+            token_hash: hash_token(username),
+            created_at: now,
+        };
+        let (operator, tenant) = (user("olga", "ops"), user("alice", "acme"));
+        assert!(storage.is_multi_tenant());
+        assert_eq!(storage.operator_organization().unwrap().name, "ops");
+        assert!(storage.is_operator(&operator));
+        assert!(!storage.is_operator(&tenant));
+
+        let mut repo = conformance::repo();
+        repo.repo_identifier = "acme/shop".to_string();
+        storage.register_repo(repo.clone());
+        repo.repo_identifier = "someone/tool".to_string();
+        storage.register_repo(repo);
+        assert_eq!(storage.organization_repos("acme"), ["acme/shop"]);
+        // Repositories outside every organization are left to the operators
+        assert_eq!(storage.repo_role(&tenant, "someone/tool"), None);
+        assert_eq!(storage.repo_role(&operator, "someone/tool"), Some(RepoRole::Admin));
+
+        assert_eq!(storage.run_limits("acme/shop"), RunLimits::default());
+        let quotas = OrganizationQuotas {
+            max_concurrent_runs: Some(3),
+            max_storage_bytes: Some(1024),
+        };
+        assert!(storage.set_organization_quotas("acme", quotas));
+        assert!(!storage.set_organization_quotas("globex", quotas));
+        assert_eq!(storage.run_limits("acme/shop").organization, Some(("acme".to_string(), 3)));
+        assert_eq!(storage.run_limits("someone/tool").organization, None);
+        assert!(quotas.storage_exceeded(1024));
+        assert!(!quotas.storage_exceeded(1023));
+        assert!(!OrganizationQuotas::default().storage_exceeded(u64::MAX));
+    }
+
+    #[test]
+    fn test_storage_status_contexts() {
+        let mut storage = InMemoryStorage::new();
+        storage.register_repo(RegisteredRepo {
+            repo_url: "https://github.com/test/repo".to_string(),
+            repo_identifier: "test/repo".to_string(),
+            pulsefile: String::new(),
+            pulsefile_revision: 1,
+            pulsefile_history: Vec::new(),
+            repo_type: RepoType::GitHub,
+            status_contexts: vec![],
+            limits: ExecutionLimits::default(),
+            sandbox: SandboxPolicy::default(),
+            webhook_mapping: Default::default(),
+            organization: None,
+            roles: Default::default(),
+            env: Default::default(),
+            hooks: Default::default(),
+            pulsefile_source: Default::default(),
+            path_pipelines: Vec::new(),
+            deployments: Vec::new(),
+            settings: Default::default(),
+        });
+
+        assert_eq!(storage.status_context_for("test/repo", "build"), "pulsiora/build");
+
+        let contexts = vec![StatusContext {
+            pipeline: "build".to_string(),
+            context: "ci/build".to_string(),
+            required: true,
+        }];
+        assert!(storage.set_status_contexts("test/repo", contexts.clone()));
+        assert!(!storage.set_status_contexts("other/repo", contexts.clone()));
+        assert_eq!(storage.get_status_contexts("test/repo"), Some(contexts));
+        assert_eq!(storage.status_context_for("test/repo", "build"), "ci/build");
+    }
+
+    #[test]
+    fn test_storage_effective_limits() {
+        let mut storage = InMemoryStorage::new();
+        storage.settings_mut().limits = ExecutionLimits {
+            max_steps: Some(50),
+            max_runtime_secs: Some(3600),
+            max_artifact_bytes: None,
+            max_output_bytes: None,
+            max_concurrent_runs: Some(4),
+        };
+        storage.register_repo(RegisteredRepo {
+            repo_url: "https://github.com/test/repo".to_string(),
+            repo_identifier: "test/repo".to_string(),
+            pulsefile: String::new(),
+            pulsefile_revision: 1,
+            pulsefile_history: Vec::new(),
+            repo_type: RepoType::GitHub,
+            status_contexts: vec![],
+            limits: ExecutionLimits::default(),
+            sandbox: SandboxPolicy::default(),
+            webhook_mapping: Default::default(),
+            organization: None,
+            roles: Default::default(),
+            env: Default::default(),
+            hooks: Default::default(),
+            pulsefile_source: Default::default(),
+            path_pipelines: Vec::new(),
+            deployments: Vec::new(),
+            settings: Default::default(),
+        });
+
+        assert!(storage.set_repo_limits(
+            "test/repo",
+            ExecutionLimits {
+                max_runtime_secs: Some(60),
+                ..Default::default()
+            }
+        ));
+
+        let limits = storage.effective_limits("test/repo");
+        assert_eq!(limits.max_steps, Some(50));
+        assert_eq!(limits.max_runtime_secs, Some(60));
+        assert_eq!(limits.max_concurrent_runs, Some(4));
+        assert_eq!(storage.effective_limits("other/repo").max_runtime_secs, Some(3600));
+
+        let sandbox = SandboxPolicy {
+            enabled: Some(true),
+            network: true,
+            read_only_paths: vec!["/opt/toolchain".to_string()],
+        };
+        assert_eq!(storage.get_repo_sandbox("test/repo"), Some(SandboxPolicy::default()));
+        assert!(storage.set_repo_sandbox("test/repo", sandbox.clone()));
+        assert!(!storage.set_repo_sandbox("other/repo", sandbox.clone()));
+        assert_eq!(storage.get_repo_sandbox("test/repo"), Some(sandbox));
+
+        let env = BTreeMap::from([("REGISTRY".to_string(), "registry.example.com".to_string())]);
+        assert_eq!(storage.get_repo_env("test/repo"), Some(BTreeMap::new()));
+        assert!(storage.set_repo_env("test/repo", env.clone()));
+        assert!(!storage.set_repo_env("other/repo", env.clone()));
+        assert_eq!(storage.get_repo_env("test/repo"), Some(env));
+
+        let hook = OutgoingWebhook::new("https://chat.example.com/hook".to_string(), None, vec![]).unwrap();
+        assert!(storage.add_repo_hook("test/repo", hook.clone()));
+        assert!(!storage.add_repo_hook("other/repo", hook.clone()));
+        assert_eq!(storage.repo_hooks("test/repo"), vec![hook.clone()]);
+        assert!(!storage.remove_repo_hook("test/repo", Uuid::new_v4()));
+        assert!(storage.remove_repo_hook("test/repo", hook.id));
+        assert!(storage.repo_hooks("test/repo").is_empty());
+    }
+
+    #[test]
+    fn test_storage_base_pulsefile_for_repo() {
+        let mut storage = InMemoryStorage::new();
+        storage.create_organization(Organization {
+            name: "acme".to_string(),
+            base_pulsefile: None,
+            quotas: Default::default(),
+            created_at: Utc::now(),
+        });
+        storage.register_repo(RegisteredRepo {
+            repo_url: "https://github.com/someone/tool".to_string(),
+            repo_identifier: "someone/tool".to_string(),
+            pulsefile: String::new(),
+            pulsefile_revision: 1,
+            pulsefile_history: Vec::new(),
+            repo_type: RepoType::GitHub,
+            status_contexts: vec![],
+            limits: ExecutionLimits::default(),
+            sandbox: SandboxPolicy::default(),
+            webhook_mapping: Default::default(),
+            organization: Some("acme".to_string()),
+            roles: Default::default(),
+            env: Default::default(),
+            hooks: Default::default(),
+            pulsefile_source: Default::default(),
+            path_pipelines: Vec::new(),
+            deployments: Vec::new(),
+            settings: Default::default(),
+        });
+
+        assert!(storage.set_base_pulsefile("acme", Some("pipeline {}".to_string())));
+        assert!(!storage.set_base_pulsefile("other", None));
+
+        // Explicit membership, then membership by owner name
+        assert_eq!(storage.base_pulsefile_for("someone/tool").as_deref(), Some("pipeline {}"));
+        assert_eq!(storage.base_pulsefile_for("acme/shop").as_deref(), Some("pipeline {}"));
+        assert_eq!(storage.base_pulsefile_for("someone/else"), None);
+    }
+
+    #[test]
+    fn test_storage_registered_repos() {
+        let mut storage = InMemoryStorage::new();
+        assert!(storage.registered_repos().is_empty());
+        storage.register_repo(RegisteredRepo {
+            repo_identifier: "zeta/app".to_string(),
+            ..conformance::repo()
+        });
+        storage.register_repo(conformance::repo());
+
+        let repos = storage.registered_repos();
+        let identifiers: Vec<&str> = repos.iter().map(|repo| repo.repo_identifier.as_str()).collect();
+        assert_eq!(identifiers, ["test/repo", "zeta/app"]);
+        // `pipeline {}` as `sha256sum` hashes it
+        assert_eq!(
+            repos[0].pulsefile_hash(),
+            "6c91beea4924b53f47c056c8bce3a0e616bfd4748d5d0b2034b448253c8f14c4"
+        );
+    }
+
+    #[test]
+    fn test_storage_pulsefile_revisions() {
+        let mut storage = InMemoryStorage::new();
+        assert_eq!(storage.update_repo_pulsefile("test/repo", "pipeline {}".to_string(), None), None);
+        storage.register_repo(conformance::repo());
+        assert_eq!(storage.get_repo_pulsefile("test/repo"), Some(("pipeline {}".to_string(), 1)));
+
+        // The same Pulsefile isn't a new revision
+        assert_eq!(storage.update_repo_pulsefile("test/repo", "pipeline {}".to_string(), Some("alice")), Some(1));
+        assert_eq!(storage.update_repo_pulsefile("test/repo", "pipeline { }".to_string(), Some("alice")), Some(2));
+        let repo = storage.get_registered_repo("test/repo").unwrap();
+        assert_eq!(repo.pulsefile, "pipeline { }");
+        assert_eq!(repo.pulsefile_history.len(), 1);
+        assert_eq!(repo.pulsefile_history[0].revision, 1);
+        assert_eq!(repo.pulsefile_history[0].pulsefile, "pipeline {}");
+        assert_eq!(repo.pulsefile_history[0].replaced_by.as_deref(), Some("alice"));
+
+        // Only the latest replaced Pulsefiles are kept
+        for n in 0..MAX_PULSEFILE_HISTORY {
+            storage.update_repo_pulsefile("test/repo", format!("pipeline {{}} // {}", n), None);
+        }
+        let repo = storage.get_registered_repo("test/repo").unwrap();
+        assert_eq!(repo.pulsefile_revision, MAX_PULSEFILE_HISTORY as u32 + 2);
+        assert_eq!(repo.pulsefile_history.len(), MAX_PULSEFILE_HISTORY);
+        assert_eq!(repo.pulsefile_history[0].revision, 2);
+
+        // Repos stored before revisions were kept start at the first one
+        let mut stored = serde_json::to_value(conformance::repo()).unwrap();
+        stored.as_object_mut().unwrap().remove("pulsefile_revision");
+        stored.as_object_mut().unwrap().remove("pulsefile_history");
+        let repo: RegisteredRepo = serde_json::from_value(stored).unwrap();
+        assert_eq!(repo.pulsefile_revision, 1);
+        assert!(repo.pulsefile_history.is_empty());
+    }
+
+    #[test]
+    fn test_storage_path_pipelines() {
+        let pipeline = |name: &str, path: &str| PathPipeline {
+            name: name.to_string(),
+            paths: vec![path.to_string()],
+            pulsefile: "pipeline {}".to_string(),
+        };
+        let mut storage = InMemoryStorage::new();
+        assert!(!storage.set_path_pipeline("test/repo", pipeline("api", "services/api/**")));
+        storage.register_repo(conformance::repo());
+        assert!(storage.set_path_pipeline("test/repo", pipeline("api", "services/api/**")));
+        assert!(storage.set_path_pipeline("test/repo", pipeline("web", "services/web/**")));
+        // A pipeline with the same name replaces the existing one
+        assert!(storage.set_path_pipeline("test/repo", pipeline("api", "api/**")));
+        let repo = storage.get_registered_repo("test/repo").unwrap();
+        assert_eq!(repo.path_pipelines, vec![pipeline("api", "api/**"), pipeline("web", "services/web/**")]);
+
+        assert!(storage.remove_path_pipeline("test/repo", "api"));
+        assert!(!storage.remove_path_pipeline("test/repo", "api"));
+        assert_eq!(storage.get_registered_repo("test/repo").unwrap().path_pipelines.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_storage_secrets_written_through() {
+        let durable = Arc::new(MemoryStorage::new());
+        durable.store_repo(&conformance::repo()).await.unwrap();
+        durable.store_secret("test/repo", "TOKEN", b"sealed").await.unwrap();
+
+        let (changes, received) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(write_through(durable.clone(), received));
+        let mut storage = InMemoryStorage::new();
+        storage.restore(durable.as_ref(), changes).await.unwrap();
+        assert_eq!(storage.repo_secrets("test/repo"), vec![("TOKEN".to_string(), b"sealed".to_vec())]);
+
+        storage.set_secret("test/repo", "KEY", b"key".to_vec());
+        assert!(storage.remove_secret("test/repo", "TOKEN"));
+        assert!(!storage.remove_secret("test/repo", "TOKEN"));
+        assert!(storage.repo_secrets("test/other").is_empty());
+        storage.stop_persisting();
+        writer.await.unwrap();
+        assert_eq!(durable.list_secrets("test/repo").await.unwrap(), ["KEY"]);
+
+        // Secrets go with their repository
+        assert!(storage.unregister_repo("test/repo"));
+        assert!(storage.repo_secrets("test/repo").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage_conformance() {
+        conformance::check_storage(&MemoryStorage::new()).await;
     }
 }
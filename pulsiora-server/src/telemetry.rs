@@ -0,0 +1,124 @@
+// Tracing output: log lines filtered by `RUST_LOG`, and, with an OTLP
+// endpoint set, spans exported to an OpenTelemetry collector, Jaeger or
+// Tempo. A webhook can then be followed through queueing, each step of the
+// execution it started and the writes to storage. Traces cross the job queue
+// as W3C `traceparent` values, so a job a worker picks up later, or after a
+// restart, continues the trace of the request that queued it.
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use pulsiora_core::{PulsioraError, Result};
+use std::collections::HashMap;
+use tracing::{Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::{EnvFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+pub const DEFAULT_OTEL_SERVICE_NAME: &str = "pulsiora-server";
+
+/// Header holding the W3C trace context of a request
+pub const TRACE_PARENT_HEADER: &str = "traceparent";
+
+/// Span exporting, shut down when the server stops so the last spans are sent
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    /// Whether spans are exported
+    pub fn exporting(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    /// Send the spans not exported yet and stop exporting
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to export the last spans: {}", e);
+            }
+        }
+    }
+}
+
+/// Install the global subscriber. Spans of Pulsiora's crates and of HTTP
+/// requests are exported to `otlp_endpoint` over OTLP/gRPC, whatever
+/// `RUST_LOG` says; it only filters log lines.
+pub fn init_tracing(otlp_endpoint: Option<&str>, service_name: &str) -> Result<Telemetry> {
+    let fmt = tracing_subscriber::fmt::layer().with_filter(EnvFilter::from_default_env());
+    let Some(endpoint) = otlp_endpoint else {
+        tracing_subscriber::registry().with(fmt).init();
+        return Ok(Telemetry { provider: None });
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| PulsioraError::InvalidConfiguration(format!("Invalid OTLP endpoint {:?}: {}", endpoint, e)))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name.to_string()).build())
+        .build();
+    let spans = Targets::new()
+        .with_target("pulsiora_server", Level::INFO)
+        .with_target("pulsiora_runner", Level::INFO)
+        .with_target("tower_http", Level::INFO);
+    let otel = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("pulsiora"))
+        .with_filter(spans);
+    tracing_subscriber::registry().with(fmt).with(otel).init();
+    Ok(Telemetry { provider: Some(provider) })
+}
+
+/// The `traceparent` of the current span, to continue its trace elsewhere;
+/// None when spans aren't exported
+pub fn current_trace_parent() -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Span::current().context(), &mut carrier);
+    carrier.remove(TRACE_PARENT_HEADER)
+}
+
+/// Make `span` part of the trace `trace_parent` continues, if it's a valid
+/// `traceparent`
+pub fn continue_trace(span: &Span, trace_parent: Option<&str>) {
+    let Some(trace_parent) = trace_parent else {
+        return;
+    };
+    let carrier = HashMap::from([(TRACE_PARENT_HEADER.to_string(), trace_parent.to_string())]);
+    let context = TraceContextPropagator::new().extract(&carrier);
+    let _ = span.set_parent(context);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_parent_round_trip() {
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let trace_parent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+            let span = tracing::info_span!("execution");
+            continue_trace(&span, Some(trace_parent));
+            let continued = span.in_scope(current_trace_parent).unwrap();
+            // Same trace, with the execution's own span as the parent
+            assert!(continued.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+            assert_ne!(continued, trace_parent);
+
+            // Garbage doesn't break the span
+            let span = tracing::info_span!("request");
+            continue_trace(&span, Some("not a trace parent"));
+            assert!(span.in_scope(current_trace_parent).is_some());
+        });
+        // Without spans being exported there is nothing to pass on
+        assert!(current_trace_parent().is_none());
+    }
+}
@@ -0,0 +1,188 @@
+// Where an execution's time went, for waterfall views: its clone and cache
+// phases and its steps as offsets from the start of the execution. Each entry
+// sits on a lane, the first one free when it started, so entries that
+// overlapped, such as a background step and the steps using it, are side by
+// side and sequential ones share lane 0.
+
+use chrono::{DateTime, Utc};
+use pulsiora_core::{PhaseKind, PipelineExecution, PipelineStatus, StepStatus};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// What a timeline entry times
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineKind {
+    Phase,
+    Step,
+}
+
+/// A phase or step of an execution on its timeline
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEntry {
+    pub kind: TimelineKind,
+    /// The step's name, or the phase's, e.g. `clone`
+    pub name: String,
+    /// Index of the step in the execution's results
+    pub step_index: Option<usize>,
+    /// The step's status; phases don't have one
+    pub status: Option<StepStatus>,
+    /// Milliseconds from the start of the execution
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Still running, so `end_ms` is when the timeline was made
+    pub running: bool,
+    /// Row of the waterfall, from 0
+    pub lane: usize,
+}
+
+/// An execution's phases and steps as a waterfall
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Timeline {
+    pub execution_id: Uuid,
+    pub status: PipelineStatus,
+    pub started_at: DateTime<Utc>,
+    /// Time spent waiting for a worker before it started, when it was queued
+    pub queue_ms: Option<u64>,
+    /// Milliseconds from its start to its end, or to the end of its latest
+    /// entry while it runs
+    pub duration_ms: u64,
+    /// Number of lanes the entries use
+    pub lanes: usize,
+    /// Ordered by start
+    pub entries: Vec<TimelineEntry>,
+}
+
+impl Timeline {
+    /// The timeline of `execution` as of `now`. Steps that didn't run, being
+    /// skipped or not started yet, are left out.
+    pub fn new(execution: &PipelineExecution, now: DateTime<Utc>) -> Self {
+        let offset = |at: DateTime<Utc>| (at - execution.started_at).num_milliseconds().max(0) as u64;
+        let mut entries: Vec<TimelineEntry> = execution
+            .phases
+            .iter()
+            .map(|phase| TimelineEntry {
+                kind: TimelineKind::Phase,
+                name: phase_name(phase.phase).to_string(),
+                step_index: None,
+                status: None,
+                start_ms: offset(phase.started_at),
+                end_ms: offset(phase.completed_at),
+                running: false,
+                lane: 0,
+            })
+            .collect();
+        entries.extend(
+            execution
+                .step_results
+                .iter()
+                .enumerate()
+                .filter(|(_, step)| !matches!(step.status, StepStatus::Skipped | StepStatus::Pending))
+                .map(|(index, step)| TimelineEntry {
+                    kind: TimelineKind::Step,
+                    name: step.step_name.clone(),
+                    step_index: Some(index),
+                    status: Some(step.status),
+                    start_ms: offset(step.started_at),
+                    end_ms: offset(step.completed_at.unwrap_or(now)),
+                    running: step.completed_at.is_none(),
+                    lane: 0,
+                }),
+        );
+        // Stable, so entries starting together keep phases before steps, in order
+        entries.sort_by_key(|entry| entry.start_ms);
+
+        // Each lane's end; an entry takes the first lane free when it starts
+        let mut lane_ends: Vec<u64> = Vec::new();
+        for entry in &mut entries {
+            entry.end_ms = entry.end_ms.max(entry.start_ms);
+            entry.lane = match lane_ends.iter().position(|&end| end <= entry.start_ms) {
+                Some(lane) => lane,
+                None => {
+                    lane_ends.push(0);
+                    lane_ends.len() - 1
+                }
+            };
+            lane_ends[entry.lane] = entry.end_ms;
+        }
+
+        let last_end = entries.iter().map(|entry| entry.end_ms).max().unwrap_or(0);
+        Self {
+            execution_id: execution.id,
+            status: execution.status,
+            started_at: execution.started_at,
+            queue_ms: execution.metrics.queue_ms,
+            duration_ms: execution.completed_at.map(offset).unwrap_or(last_end).max(last_end),
+            lanes: lane_ends.len(),
+            entries,
+        }
+    }
+}
+
+/// A phase's name as shown on timelines, e.g. `cache_restore`
+fn phase_name(phase: PhaseKind) -> &'static str {
+    match phase {
+        PhaseKind::Clone => "clone",
+        PhaseKind::CacheRestore => "cache_restore",
+        PhaseKind::CacheSave => "cache_save",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance::execution;
+    use chrono::Duration;
+    use pulsiora_core::PhaseResult;
+
+    #[test]
+    fn test_timeline_lanes_and_offsets() {
+        let mut execution = execution(&["db", "migrate", "test", "deploy"]);
+        let start = execution.started_at;
+        let at = |ms: i64| start + Duration::milliseconds(ms);
+        execution.phases = vec![PhaseResult {
+            phase: PhaseKind::Clone,
+            duration_ms: 500,
+            bytes_transferred: None,
+            started_at: at(0),
+            completed_at: at(500),
+        }];
+        // `db` is a background service running next to the steps after it
+        let spans = [(500, 9_000), (600, 2_000), (2_000, 8_000)];
+        for (step, (from, to)) in execution.step_results.iter_mut().zip(spans) {
+            step.started_at = at(from);
+            step.completed_at = Some(at(to));
+        }
+        execution.step_results[3].status = StepStatus::Skipped;
+        execution.completed_at = Some(at(9_500));
+
+        let timeline = Timeline::new(&execution, at(20_000));
+        let entries: Vec<(&str, u64, u64, usize)> = timeline
+            .entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.start_ms, entry.end_ms, entry.lane))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("clone", 0, 500, 0),
+                ("db", 500, 9_000, 0),
+                ("migrate", 600, 2_000, 1),
+                ("test", 2_000, 8_000, 1),
+            ]
+        );
+        assert_eq!(timeline.entries[0].kind, TimelineKind::Phase);
+        assert_eq!(timeline.entries[3].step_index, Some(2));
+        assert_eq!(timeline.lanes, 2);
+        assert_eq!(timeline.duration_ms, 9_500);
+
+        // A running step ends now
+        execution.completed_at = None;
+        execution.step_results[2].completed_at = None;
+        let timeline = Timeline::new(&execution, at(12_000));
+        let test = &timeline.entries[3];
+        assert!(test.running);
+        assert_eq!(test.end_ms, 12_000);
+        assert_eq!(timeline.duration_ms, 12_000);
+    }
+}
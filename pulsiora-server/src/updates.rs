@@ -0,0 +1,203 @@
+// Live execution updates for WebSocket clients: status changes announced by
+// storage and step events from running jobs. Each client subscribes to the
+// repositories and executions it wants and only gets updates about those.
+
+use pulsiora_core::{ExecutionEvent, PipelineExecution, PipelineStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Updates kept for clients that fall behind before they miss some
+pub const UPDATE_BUFFER: usize = 1024;
+
+/// Something that happened to an execution
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Update {
+    /// The execution was queued, started or ended
+    Status {
+        execution_id: Uuid,
+        repository: String,
+        status: PipelineStatus,
+    },
+    /// A step started, printed output or finished, or the pipeline ended
+    Step { repository: String, event: ExecutionEvent },
+}
+
+impl Update {
+    pub fn status(execution: &PipelineExecution) -> Self {
+        Update::Status {
+            execution_id: execution.id,
+            repository: execution.repository.full_name.clone(),
+            status: execution.status,
+        }
+    }
+
+    pub fn execution_id(&self) -> Uuid {
+        match self {
+            Update::Status { execution_id, .. } => *execution_id,
+            Update::Step { event, .. } => event.execution_id(),
+        }
+    }
+
+    pub fn repository(&self) -> &str {
+        match self {
+            Update::Status { repository, .. } | Update::Step { repository, .. } => repository,
+        }
+    }
+}
+
+/// Hands every update to all connected clients
+#[derive(Clone)]
+pub struct UpdateHub {
+    sender: broadcast::Sender<Update>,
+}
+
+impl UpdateHub {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity.max(1)).0,
+        }
+    }
+
+    pub fn publish(&self, update: Update) {
+        // Fails only when no client is connected
+        let _ = self.sender.send(update);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Update> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for UpdateHub {
+    fn default() -> Self {
+        Self::new(UPDATE_BUFFER)
+    }
+}
+
+/// A message from a client, e.g.
+/// `{"action": "subscribe", "repos": ["acme/shop"], "executions": []}`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe {
+        #[serde(default)]
+        repos: Vec<String>,
+        #[serde(default)]
+        executions: Vec<Uuid>,
+    },
+    Unsubscribe {
+        #[serde(default)]
+        repos: Vec<String>,
+        #[serde(default)]
+        executions: Vec<Uuid>,
+    },
+}
+
+/// A message to a client that isn't an update
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notice {
+    /// What the client is subscribed to after its last message
+    Subscribed { repos: Vec<String>, executions: Vec<Uuid> },
+    /// The client fell behind and this many updates were dropped
+    Lagged { missed: u64 },
+    Error { message: String },
+}
+
+/// The repositories and executions a client gets updates about
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subscription {
+    repos: BTreeSet<String>,
+    executions: BTreeSet<Uuid>,
+}
+
+impl Subscription {
+    pub fn apply(&mut self, message: ClientMessage) {
+        match message {
+            ClientMessage::Subscribe { repos, executions } => {
+                self.repos.extend(repos);
+                self.executions.extend(executions);
+            }
+            ClientMessage::Unsubscribe { repos, executions } => {
+                for repo in repos {
+                    self.repos.remove(&repo);
+                }
+                for execution in executions {
+                    self.executions.remove(&execution);
+                }
+            }
+        }
+    }
+
+    /// Whether the update is about a subscribed repository or execution
+    pub fn wants(&self, update: &Update) -> bool {
+        self.repos.contains(update.repository()) || self.executions.contains(&update.execution_id())
+    }
+
+    pub fn notice(&self) -> Notice {
+        Notice::Subscribed {
+            repos: self.repos.iter().cloned().collect(),
+            executions: self.executions.iter().copied().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(execution_id: Uuid, repository: &str) -> Update {
+        Update::Status {
+            execution_id,
+            repository: repository.to_string(),
+            status: PipelineStatus::Running,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions() {
+        let hub = UpdateHub::new(8);
+        let mut received = hub.subscribe();
+        let watched = Uuid::new_v4();
+        let mut subscription = Subscription::default();
+        let message: ClientMessage = serde_json::from_str(&format!(
+            r#"{{"action": "subscribe", "repos": ["acme/shop"], "executions": ["{}"]}}"#,
+            watched
+        ))
+        .unwrap();
+        subscription.apply(message);
+
+        hub.publish(status(Uuid::new_v4(), "acme/shop"));
+        hub.publish(status(watched, "acme/other"));
+        hub.publish(Update::Step {
+            repository: "acme/other".to_string(),
+            event: ExecutionEvent::StepStarted {
+                execution_id: Uuid::new_v4(),
+                step_name: "build".to_string(),
+            },
+        });
+        let wanted: Vec<bool> = (0..3).map(|_| subscription.wants(&received.try_recv().unwrap())).collect();
+        assert_eq!(wanted, [true, true, false]);
+
+        subscription.apply(ClientMessage::Unsubscribe {
+            repos: vec!["acme/shop".to_string()],
+            executions: vec![],
+        });
+        assert!(!subscription.wants(&status(Uuid::new_v4(), "acme/shop")));
+        assert_eq!(
+            subscription.notice(),
+            Notice::Subscribed {
+                repos: vec![],
+                executions: vec![watched]
+            }
+        );
+
+        let json = serde_json::to_value(status(watched, "acme/shop")).unwrap();
+        assert_eq!(json["type"], "status");
+        assert_eq!(json["status"], "Running");
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"action": "watch"}"#).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+// API v2 handlers. v2 runs alongside v1 on the same state; it returns list
+// results in `Page` envelopes, step output as structured lines, and errors as
+// JSON bodies instead of bare status codes. Routes not listed here are v1 only.
+
+use crate::{authorize_execution_read, execution_query, visible_executions, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use pulsiora_core::{ExecutionLogs, Page, PipelineExecution};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Response header naming the API version that served the request
+pub const API_VERSION_HEADER: &str = "pulsiora-api-version";
+
+/// Page size when `limit` isn't given
+pub(crate) const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Largest page a client may request
+pub(crate) const MAX_PAGE_SIZE: usize = 500;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/executions", get(list_executions))
+        .route("/executions/:id", get(get_execution))
+        .route("/executions/:id/logs", get(get_execution_logs))
+        .route("/pipelines/:repo/executions", get(list_pipeline_executions))
+        .layer(middleware::from_fn(tag_version))
+}
+
+async fn tag_version(request: axum::extract::Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(API_VERSION_HEADER, HeaderValue::from_static("2"));
+    response
+}
+
+/// Error body returned by every v2 route
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    error: String,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            error: status
+                .canonical_reason()
+                .unwrap_or("error")
+                .to_ascii_lowercase()
+                .replace(' ', "_"),
+            message: message.into(),
+        }
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+}
+
+/// Error for a failed access check; executions the caller may not see are
+/// reported as missing, with `not_found` as the message
+fn access_error(status: StatusCode, not_found: impl Into<String>) -> ApiError {
+    match status {
+        StatusCode::NOT_FOUND => ApiError::not_found(not_found),
+        StatusCode::UNAUTHORIZED => ApiError::new(status, "An API token is required"),
+        status => ApiError::new(status, status.canonical_reason().unwrap_or("error")),
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(&self)).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PageParams {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+impl PageParams {
+    fn page<T>(&self, items: Vec<T>) -> Result<Page<T>, ApiError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit == 0 || limit > MAX_PAGE_SIZE {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("limit must be between 1 and {}", MAX_PAGE_SIZE),
+            ));
+        }
+        Ok(Page::from_items(items, self.offset.unwrap_or(0), limit))
+    }
+}
+
+/// Newest first; the ID breaks ties so pages don't shift between requests
+fn sort_newest_first(executions: &mut [PipelineExecution]) {
+    executions.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+}
+
+/// Takes the same filters as v1, see `execution_query`
+async fn list_executions(
+    State(state): State<AppState>,
+    Query(params): Query<PageParams>,
+    Query(filters): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Json<Page<PipelineExecution>>, ApiError> {
+    let query = execution_query(&filters).map_err(|message| ApiError::new(StatusCode::BAD_REQUEST, message))?;
+    let executions = visible_executions(&state, &headers, &query)
+        .await
+        .map_err(|status| access_error(status, "No executions"))?;
+    params.page(executions).map(Json)
+}
+
+async fn get_execution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<PipelineExecution>, ApiError> {
+    let storage = state.storage.read().await;
+    let execution = storage
+        .get_execution(&id)
+        .ok_or_else(|| ApiError::not_found(format!("No execution {}", id)))?;
+    authorize_execution_read(&storage, &headers, &execution.repository.full_name)
+        .map_err(|status| access_error(status, format!("No execution {}", id)))?;
+    Ok(Json(execution.clone()))
+}
+
+async fn get_execution_logs(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ExecutionLogs>, ApiError> {
+    let storage = state.storage.read().await;
+    let execution = storage
+        .get_execution(&id)
+        .ok_or_else(|| ApiError::not_found(format!("No execution {}", id)))?;
+    authorize_execution_read(&storage, &headers, &execution.repository.full_name)
+        .map_err(|status| access_error(status, format!("No execution {}", id)))?;
+    Ok(Json(ExecutionLogs::from_execution(execution)))
+}
+
+async fn list_pipeline_executions(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+    Query(params): Query<PageParams>,
+    headers: HeaderMap,
+) -> Result<Json<Page<PipelineExecution>>, ApiError> {
+    let mut executions = {
+        let storage = state.storage.read().await;
+        let executions = storage.get_executions_by_repo(&repo, usize::MAX);
+        let not_registered = format!("Repository {} is not registered", repo);
+        if executions.is_empty() && !storage.is_repo_registered(&repo) {
+            return Err(ApiError::not_found(not_registered));
+        }
+        authorize_execution_read(&storage, &headers, &repo).map_err(|status| access_error(status, not_registered))?;
+        executions
+    };
+    sort_newest_first(&mut executions);
+    params.page(executions).map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_body() {
+        let error = ApiError::not_found("No execution abc");
+        let body = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"error": "not_found", "message": "No execution abc"})
+        );
+        assert_eq!(error.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_page_params_bounds() {
+        let params = PageParams {
+            offset: Some(1),
+            limit: None,
+        };
+        let page = params.page(vec![1, 2, 3]).unwrap();
+        assert_eq!(page.items, vec![2, 3]);
+        assert_eq!(page.limit, DEFAULT_PAGE_SIZE);
+
+        let params = PageParams {
+            offset: None,
+            limit: Some(MAX_PAGE_SIZE + 1),
+        };
+        assert!(params.page(vec![1]).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+// Generic webhooks: any SCM or tool that can POST JSON can trigger a
+// repository's pipelines. Where the event type, branch, commit and so on are
+// found in the payload is configured per repository as JSONPath expressions.
+
+use crate::storage::RegisteredRepo;
+use hmac::{Hmac, Mac};
+use jsonpath_rust::JsonPath;
+use pulsiora_core::{GitEvent, GitEventType, PulsioraError, Result, WebhookMapping};
+use serde_json::Value;
+use sha2::Sha256;
+use std::str::FromStr;
+
+/// Sender recorded when the mapping has no `sender` or the payload lacks one
+const DEFAULT_SENDER: &str = "webhook";
+
+/// Header holding a payload's signature, as GitHub sends it
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+
+/// Whether `signature` (`sha256=<hex>`) is the HMAC-SHA256 of `body` keyed
+/// with `secret`. The comparison takes the same time wherever they differ.
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(expected) = signature.strip_prefix("sha256=").and_then(|hex| hex::decode(hex).ok()) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Check that every expression in a mapping is valid JSONPath
+pub fn validate_mapping(mapping: &WebhookMapping) -> Result<()> {
+    for (field, path) in mapping_fields(mapping) {
+        if let Some(path) = path {
+            JsonPath::<Value>::from_str(path).map_err(|e| {
+                PulsioraError::InvalidConfiguration(format!("Invalid JSONPath for `{}` ({}): {}", field, path, e))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn mapping_fields(mapping: &WebhookMapping) -> [(&'static str, Option<&String>); 6] {
+    [
+        ("event", mapping.event.as_ref()),
+        ("branch", mapping.branch.as_ref()),
+        ("tag", mapping.tag.as_ref()),
+        ("commit", mapping.commit.as_ref()),
+        ("commit_message", mapping.commit_message.as_ref()),
+        ("sender", mapping.sender.as_ref()),
+    ]
+}
+
+/// The first value `path` finds in `payload`, as text. Strings are taken as
+/// they are and numbers and booleans are formatted; nulls, objects and arrays
+/// count as missing.
+fn extract(path: Option<&String>, payload: &Value) -> Result<Option<String>> {
+    let Some(path) = path else { return Ok(None) };
+    let path = JsonPath::<Value>::from_str(path)
+        .map_err(|e| PulsioraError::InvalidConfiguration(format!("Invalid JSONPath {}: {}", path, e)))?;
+    let found = path.find_slice_ptr(payload);
+    Ok(found.first().and_then(|value| match &**value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }))
+}
+
+/// The event a generic webhook payload describes for `repo`, or None if it
+/// should be ignored: the mapping's `event` finds nothing in the payload or
+/// names an event type Pulsiora doesn't know.
+pub fn generic_event(repo: &RegisteredRepo, payload: &Value) -> Result<Option<GitEvent>> {
+    let mapping = &repo.webhook_mapping;
+    let event_type = match extract(mapping.event.as_ref(), payload)? {
+        // `GitEventType::from` falls back to push for unknown names, and
+        // manual runs are started through the API by a user
+        Some(name) => match GitEventType::from(name.as_str()) {
+            event_type if event_type.as_str() == name && event_type != GitEventType::Manual => event_type,
+            _ => return Ok(None),
+        },
+        None if mapping.event.is_some() => return Ok(None),
+        None => GitEventType::Push,
+    };
+    let branch = extract(mapping.branch.as_ref(), payload)?
+        .map(|branch| branch.strip_prefix("refs/heads/").map(str::to_string).unwrap_or(branch));
+    let tag = extract(mapping.tag.as_ref(), payload)?
+        .map(|tag| tag.strip_prefix("refs/tags/").map(str::to_string).unwrap_or(tag));
+
+    Ok(Some(GitEvent {
+        event_type,
+        repository: repo.repository(),
+        branch,
+        tag,
+        release_name: None,
+        pull_request: None,
+        commit_sha: extract(mapping.commit.as_ref(), payload)?,
+        commit_message: extract(mapping.commit_message.as_ref(), payload)?,
+        sender: extract(mapping.sender.as_ref(), payload)?.unwrap_or_else(|| DEFAULT_SENDER.to_string()),
+        inputs: Default::default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::RepoType;
+    use serde_json::json;
+
+    fn repo(mapping: WebhookMapping) -> RegisteredRepo {
+        RegisteredRepo {
+            repo_url: "https://git.example.com/team/app.git".to_string(),
+            repo_identifier: "team/app".to_string(),
+            pulsefile: "pipeline {}".to_string(),
+            pulsefile_revision: 1,
+            pulsefile_history: Vec::new(),
+            repo_type: RepoType::Other("gitea".to_string()),
+            status_contexts: vec![],
+            limits: Default::default(),
+            sandbox: Default::default(),
+            webhook_mapping: mapping,
+            organization: None,
+            roles: Default::default(),
+            env: Default::default(),
+            hooks: Default::default(),
+            pulsefile_source: Default::default(),
+            path_pipelines: Vec::new(),
+            deployments: Vec::new(),
+            settings: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_generic_event() {
+        let mapping = WebhookMapping {
+            event: Some("$.kind".to_string()),
+            branch: Some("$.ref".to_string()),
+            commit: Some("$.commits[0].id".to_string()),
+            commit_message: Some("$.commits[0].message".to_string()),
+            sender: Some("$.user.name".to_string()),
+            ..Default::default()
+        };
+        validate_mapping(&mapping).unwrap();
+        let payload = json!({
+            "kind": "push",
+            "ref": "refs/heads/release",
+            "commits": [{"id": "abc123", "message": "Fix build"}, {"id": "def456"}],
+            "user": {"name": "dana"}
+        });
+        let event = generic_event(&repo(mapping.clone()), &payload).unwrap().unwrap();
+        assert_eq!(event.event_type, GitEventType::Push);
+        assert_eq!(event.branch.as_deref(), Some("release"));
+        assert_eq!(event.tag, None);
+        assert_eq!(event.commit_sha.as_deref(), Some("abc123"));
+        assert_eq!(event.commit_message.as_deref(), Some("Fix build"));
+        assert_eq!(event.sender, "dana");
+        assert_eq!(event.repository.full_name, "team/app");
+        assert_eq!(event.repository.clone_url, "https://git.example.com/team/app.git");
+
+        // Unknown or missing event types are ignored
+        assert!(generic_event(&repo(mapping.clone()), &json!({"kind": "comment"})).unwrap().is_none());
+        assert!(generic_event(&repo(mapping.clone()), &json!({"kind": "manual"})).unwrap().is_none());
+        assert!(generic_event(&repo(mapping), &json!({})).unwrap().is_none());
+
+        // Without an event path every call is a push
+        let event = generic_event(&repo(WebhookMapping::default()), &json!({"anything": 1})).unwrap().unwrap();
+        assert_eq!(event.event_type, GitEventType::Push);
+        assert_eq!(event.branch, None);
+        assert_eq!(event.sender, "webhook");
+
+        let invalid = WebhookMapping {
+            branch: Some("$.[".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_mapping(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        // From GitHub's webhook documentation
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify_signature("It's a Secret to Everybody", b"Hello, World!", signature));
+        assert!(!verify_signature("It's a Secret to Everybody", b"Hello, World?", signature));
+        assert!(!verify_signature("another secret", b"Hello, World!", signature));
+        assert!(!verify_signature("It's a Secret to Everybody", b"Hello, World!", &signature[7..]));
+        assert!(!verify_signature("It's a Secret to Everybody", b"Hello, World!", "sha256=zz"));
+    }
+}
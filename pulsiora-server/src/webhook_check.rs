@@ -0,0 +1,195 @@
+// Checks for debugging a repository's webhook setup. They cover whether the
+// secret someone configured in GitHub is the server's, whether the server's
+// public URL takes a signed delivery, and how GitHub sees the hook: whether
+// it's active, sends JSON and the events pipelines run on, and how its last
+// delivery went. Each is reported as a `HealthCheck`.
+
+use crate::github::GitHubHook;
+use crate::health::HealthCheck;
+use crate::hooks::sign_payload;
+use crate::storage::RegisteredRepo;
+use crate::webhook::verify_signature;
+use serde_json::{json, Value};
+
+/// Path of the endpoint GitHub webhooks are sent to
+pub const GITHUB_WEBHOOK_PATH: &str = "/api/v1/webhook/github";
+
+/// Events a GitHub webhook should send for pipelines to run on pushes and
+/// pull requests
+pub const EXPECTED_GITHUB_EVENTS: [&str; 2] = ["push", "pull_request"];
+
+/// Warnings about a GitHub webhook that leaves out `EXPECTED_GITHUB_EVENTS`
+pub fn missing_event_warnings(events: &[String]) -> Vec<String> {
+    if events.iter().any(|event| event == "*") {
+        return Vec::new();
+    }
+    EXPECTED_GITHUB_EVENTS
+        .iter()
+        .filter(|expected| !events.iter().any(|event| event == *expected))
+        .map(|event| format!("The webhook doesn't send {} events, so they don't run pipelines", event))
+        .collect()
+}
+
+/// Whether `presented`, the secret set on the sender's side, is the server's
+/// `configured` one. Compared through signatures, so it takes the same time
+/// wherever they differ.
+pub fn secret_check(configured: Option<&str>, presented: Option<&str>) -> HealthCheck {
+    match (configured, presented) {
+        (Some(configured), Some(presented)) => {
+            let probe = b"pulsiora webhook test";
+            if verify_signature(configured, probe, &sign_payload(presented, probe)) {
+                HealthCheck::ok("secret", "Matches the server's webhook secret")
+            } else {
+                HealthCheck::failing(
+                    "secret",
+                    "Doesn't match the server's webhook secret; deliveries get 401 Unauthorized",
+                )
+            }
+        }
+        (Some(_), None) => HealthCheck::ok(
+            "secret",
+            "The server checks signatures; send the webhook's secret to compare it",
+        ),
+        (None, Some(_)) => HealthCheck::failing(
+            "secret",
+            "The server has no webhook secret, so the one set on the webhook isn't checked",
+        ),
+        (None, None) => HealthCheck::ok("secret", "No webhook secret is set; payloads aren't checked for a signature"),
+    }
+}
+
+/// A `ping` delivery for `repo`, as GitHub sends when a webhook is created
+pub fn ping_payload(repo: &RegisteredRepo) -> Value {
+    let (owner, name) = repo.repo_identifier.split_once('/').unwrap_or(("", &repo.repo_identifier));
+    json!({
+        "zen": "Testing the webhook from Pulsiora.",
+        "hook_id": 0,
+        "repository": {
+            "full_name": repo.repo_identifier,
+            "name": name,
+            "owner": { "login": owner },
+            "clone_url": repo.repo_url,
+            "default_branch": "main",
+        },
+        "sender": { "login": "pulsiora" },
+    })
+}
+
+/// The outcome of sending a signed ping to `url`: the status it was answered
+/// with, or why it couldn't be sent
+pub fn delivery_check(url: &str, outcome: Result<u16, String>) -> HealthCheck {
+    match outcome {
+        Ok(status) if (200..300).contains(&status) => {
+            HealthCheck::ok("delivery", format!("{} took a signed ping ({})", url, status))
+        }
+        Ok(401) => HealthCheck::failing("delivery", format!("{} rejected the ping's signature (401)", url)),
+        // The test comes from the server, not from an allowed address
+        Ok(403) => HealthCheck::ok(
+            "delivery",
+            format!("{} is reachable; the webhook allow-list turned the test away as it isn't sent from an allowed address (403)", url),
+        ),
+        Ok(status) => HealthCheck::failing("delivery", format!("{} answered the ping with {}", url, status)),
+        Err(e) => HealthCheck::failing("delivery", format!("Couldn't reach {}: {}", url, e)),
+    }
+}
+
+/// How GitHub sees the webhook among `hooks` that delivers to `url`
+pub fn github_hook_check(hooks: &[GitHubHook], url: &str) -> HealthCheck {
+    let Some(hook) = hooks.iter().find(|hook| hook.config.url.as_deref() == Some(url)) else {
+        return HealthCheck::failing("github", format!("No webhook of the repository delivers to {}", url));
+    };
+    let mut problems = Vec::new();
+    if !hook.active {
+        problems.push("The webhook is inactive".to_string());
+    }
+    if hook.config.content_type.as_deref() != Some("json") {
+        problems.push("The webhook sends form-encoded payloads; set its content type to application/json".to_string());
+    }
+    problems.extend(missing_event_warnings(&hook.events));
+    let last = &hook.last_response;
+    match last.code {
+        Some(code) if !(200..300).contains(&code) => problems.push(format!(
+            "GitHub's last delivery was answered with {}{}",
+            code,
+            last.message.as_deref().map(|message| format!(": {}", message)).unwrap_or_default()
+        )),
+        None if last.status.as_deref().is_some_and(|status| status != "unused") => problems.push(format!(
+            "GitHub's last delivery failed: {}",
+            last.message.as_deref().or(last.status.as_deref()).unwrap_or("no answer")
+        )),
+        _ => {}
+    }
+    if problems.is_empty() {
+        let last = match last.code {
+            Some(code) => format!("its last delivery was answered with {}", code),
+            None => "it hasn't delivered anything yet".to_string(),
+        };
+        HealthCheck::ok("github", format!("Webhook {} is active and {}", hook.id, last))
+    } else {
+        HealthCheck::failing("github", format!("Webhook {}: {}", hook.id, problems.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::github::{GitHubHookConfig, GitHubHookResponse};
+    use crate::health::HealthStatus;
+
+    #[test]
+    fn test_secret_check() {
+        assert_eq!(secret_check(Some("s3cret"), Some("s3cret")).status, HealthStatus::Ok);
+        assert_eq!(secret_check(Some("s3cret"), Some("other")).status, HealthStatus::Failing);
+        assert_eq!(secret_check(None, Some("s3cret")).status, HealthStatus::Failing);
+        assert_eq!(secret_check(Some("s3cret"), None).status, HealthStatus::Ok);
+        assert_eq!(secret_check(None, None).status, HealthStatus::Ok);
+    }
+
+    #[test]
+    fn test_github_hook_check() {
+        let url = "https://ci.example.com/api/v1/webhook/github";
+        let mut hook = GitHubHook {
+            id: 7,
+            active: true,
+            events: vec!["push".to_string(), "pull_request".to_string()],
+            config: GitHubHookConfig {
+                url: Some(url.to_string()),
+                content_type: Some("json".to_string()),
+            },
+            last_response: GitHubHookResponse {
+                code: Some(200),
+                status: Some("active".to_string()),
+                message: Some("OK".to_string()),
+            },
+        };
+        let check = github_hook_check(std::slice::from_ref(&hook), url);
+        assert_eq!(check.status, HealthStatus::Ok, "{}", check.detail);
+        assert_eq!(
+            github_hook_check(std::slice::from_ref(&hook), "https://other/").status,
+            HealthStatus::Failing
+        );
+
+        hook.config.content_type = Some("form".to_string());
+        hook.events = vec!["push".to_string()];
+        hook.last_response.code = Some(401);
+        hook.last_response.message = Some("Unauthorized".to_string());
+        let check = github_hook_check(&[hook.clone()], url);
+        assert_eq!(check.status, HealthStatus::Failing);
+        assert!(check.detail.contains("application/json"));
+        assert!(check.detail.contains("pull_request events"));
+        assert!(check.detail.contains("answered with 401: Unauthorized"));
+
+        assert!(missing_event_warnings(&["*".to_string()]).is_empty());
+        assert_eq!(missing_event_warnings(&[]).len(), 2);
+    }
+
+    #[test]
+    fn test_delivery_check() {
+        let url = "https://ci.example.com/api/v1/webhook/github";
+        assert_eq!(delivery_check(url, Ok(200)).status, HealthStatus::Ok);
+        assert_eq!(delivery_check(url, Ok(403)).status, HealthStatus::Ok);
+        assert_eq!(delivery_check(url, Ok(404)).status, HealthStatus::Failing);
+        assert_eq!(delivery_check(url, Ok(401)).status, HealthStatus::Failing);
+        assert_eq!(delivery_check(url, Err("connection refused".to_string())).status, HealthStatus::Failing);
+    }
+}